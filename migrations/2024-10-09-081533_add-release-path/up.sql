@@ -0,0 +1,16 @@
+--
+-- Copyright (c) 2020-2022 science+computing ag and other contributors
+--
+-- This program and the accompanying materials are made
+-- available under the terms of the Eclipse Public License 2.0
+-- which is available at https://www.eclipse.org/legal/epl-2.0/
+--
+-- SPDX-License-Identifier: EPL-2.0
+--
+-- Your SQL goes here
+-- The path of the released artifact relative to the release store
+-- (NULL for releases that used the path of the artifact in the staging store)
+ALTER TABLE
+    releases
+ADD COLUMN
+    path VARCHAR NULL;
@@ -0,0 +1,21 @@
+--
+-- Copyright (c) 2020-2022 science+computing ag and other contributors
+--
+-- This program and the accompanying materials are made
+-- available under the terms of the Eclipse Public License 2.0
+-- which is available at https://www.eclipse.org/legal/epl-2.0/
+--
+-- SPDX-License-Identifier: EPL-2.0
+--
+-- Your SQL goes here
+CREATE TABLE release_groups (
+    id SERIAL PRIMARY KEY NOT NULL,
+    uuid UUID NOT NULL UNIQUE,
+    release_date TIMESTAMP WITH TIME ZONE NOT NULL,
+    release_store_id INTEGER REFERENCES release_stores(id) NOT NULL
+);
+
+ALTER TABLE
+    releases
+ADD COLUMN
+    release_group_id INTEGER REFERENCES release_groups(id) NULL;
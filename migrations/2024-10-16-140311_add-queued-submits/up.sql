@@ -0,0 +1,20 @@
+--
+-- Copyright (c) 2020-2022 science+computing ag and other contributors
+--
+-- This program and the accompanying materials are made
+-- available under the terms of the Eclipse Public License 2.0
+-- which is available at https://www.eclipse.org/legal/epl-2.0/
+--
+-- SPDX-License-Identifier: EPL-2.0
+--
+-- Your SQL goes here
+CREATE TABLE queued_submits (
+    id SERIAL PRIMARY KEY NOT NULL,
+    uuid UUID NOT NULL UNIQUE,
+    repo_hash_id INTEGER REFERENCES githashes(id) NOT NULL,
+    arguments TEXT[] NOT NULL,
+    state VARCHAR NOT NULL,
+    enqueued_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    started_at TIMESTAMP WITH TIME ZONE NULL,
+    finished_at TIMESTAMP WITH TIME ZONE NULL
+);
@@ -0,0 +1,15 @@
+--
+-- Copyright (c) 2020-2022 science+computing ag and other contributors
+--
+-- This program and the accompanying materials are made
+-- available under the terms of the Eclipse Public License 2.0
+-- which is available at https://www.eclipse.org/legal/epl-2.0/
+--
+-- SPDX-License-Identifier: EPL-2.0
+--
+-- Your SQL goes here
+-- Submits that were recorded before the state existed are considered finished
+ALTER TABLE
+    submits
+ADD COLUMN
+    state VARCHAR NOT NULL DEFAULT 'finished';
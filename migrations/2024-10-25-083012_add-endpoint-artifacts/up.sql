@@ -0,0 +1,19 @@
+--
+-- Copyright (c) 2020-2022 science+computing ag and other contributors
+--
+-- This program and the accompanying materials are made
+-- available under the terms of the Eclipse Public License 2.0
+-- which is available at https://www.eclipse.org/legal/epl-2.0/
+--
+-- SPDX-License-Identifier: EPL-2.0
+--
+-- Your SQL goes here
+CREATE TABLE endpoint_artifacts (
+    id SERIAL PRIMARY KEY NOT NULL,
+    endpoint_id INTEGER REFERENCES endpoints(id) NOT NULL,
+    volume VARCHAR NOT NULL,
+    sha256 VARCHAR NOT NULL,
+    file_name VARCHAR NOT NULL,
+    cached_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    UNIQUE (endpoint_id, volume, sha256)
+);
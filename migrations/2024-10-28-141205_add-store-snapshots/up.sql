@@ -0,0 +1,24 @@
+--
+-- Copyright (c) 2020-2022 science+computing ag and other contributors
+--
+-- This program and the accompanying materials are made
+-- available under the terms of the Eclipse Public License 2.0
+-- which is available at https://www.eclipse.org/legal/epl-2.0/
+--
+-- SPDX-License-Identifier: EPL-2.0
+--
+-- Your SQL goes here
+CREATE TABLE store_snapshots (
+    id SERIAL PRIMARY KEY NOT NULL,
+    name VARCHAR NOT NULL UNIQUE,
+    release_store_id INTEGER REFERENCES release_stores(id) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE TABLE store_snapshot_artifacts (
+    id SERIAL PRIMARY KEY NOT NULL,
+    store_snapshot_id INTEGER REFERENCES store_snapshots(id) ON DELETE CASCADE NOT NULL,
+    path VARCHAR NOT NULL,
+    sha256 VARCHAR NOT NULL,
+    UNIQUE (store_snapshot_id, path)
+);
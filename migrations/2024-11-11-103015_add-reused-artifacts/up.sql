@@ -0,0 +1,18 @@
+--
+-- Copyright (c) 2020-2022 science+computing ag and other contributors
+--
+-- This program and the accompanying materials are made
+-- available under the terms of the Eclipse Public License 2.0
+-- which is available at https://www.eclipse.org/legal/epl-2.0/
+--
+-- SPDX-License-Identifier: EPL-2.0
+--
+-- Your SQL goes here
+CREATE TABLE reused_artifacts (
+    id SERIAL PRIMARY KEY NOT NULL,
+    submit_id INTEGER REFERENCES submits(id) NOT NULL,
+    job_uuid UUID NOT NULL,
+    artifact_id INTEGER REFERENCES artifacts(id) NOT NULL,
+    source_submit_id INTEGER REFERENCES submits(id) NOT NULL,
+    policy VARCHAR NOT NULL
+);
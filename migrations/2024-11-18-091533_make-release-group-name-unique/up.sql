@@ -0,0 +1,36 @@
+--
+-- Copyright (c) 2020-2022 science+computing ag and other contributors
+--
+-- This program and the accompanying materials are made
+-- available under the terms of the Eclipse Public License 2.0
+-- which is available at https://www.eclipse.org/legal/epl-2.0/
+--
+-- SPDX-License-Identifier: EPL-2.0
+--
+-- Your SQL goes here
+
+-- Release groups are identified by their name, so duplicate names of existing groups get the
+-- UUID of the group appended (except for the oldest group with that name)
+UPDATE
+    release_groups
+SET
+    name = name || '-' || uuid
+WHERE
+    name IS NOT NULL
+    AND id NOT IN (
+        SELECT
+            MIN(id)
+        FROM
+            release_groups
+        WHERE
+            name IS NOT NULL
+        GROUP BY
+            name
+    );
+
+DROP INDEX release_groups_name_idx;
+
+ALTER TABLE
+    release_groups
+ADD CONSTRAINT
+    release_groups_name_key UNIQUE (name);
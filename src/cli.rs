@@ -15,6 +15,7 @@ use clap::Arg;
 use clap::ArgAction;
 use clap::ArgGroup;
 use clap::Command;
+use clap::ValueHint;
 
 use tracing::{debug, error};
 
@@ -49,6 +50,21 @@ pub fn cli() -> Command {
                 .value_name("PKG")
                 .help("List only releases for package PKG"),
         )
+        .arg(
+            Arg::new("group")
+                .required(false)
+                .long("group")
+                .short('g')
+                .value_name("GROUP")
+                .help("List only releases of the release group GROUP (name or UUID)"),
+        )
+        .arg(
+            Arg::new("arch")
+                .required(false)
+                .long("arch")
+                .value_name("ARCH")
+                .help("List only releases of artifacts that were built for the architecture ARCH"),
+        )
         .arg(
             Arg::new("limit")
                 .required(false)
@@ -57,7 +73,25 @@ pub fn cli() -> Command {
                 .value_name("LIMIT")
                 .help("List newest LIMIT releases (0=unlimited)")
                 .value_parser(clap::value_parser!(usize)),
-        );
+        )
+        .arg(
+            Arg::new("verify_files")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .long("verify-files")
+                .help("Verify the size and checksum of the released files")
+                .long_help(indoc::indoc!(r#"
+                    Verify the size and the SHA-256 checksum of the released files against the values that
+                    were recorded when the artifacts were built, and report corrupted or truncated files.
+                    Artifacts of older versions of butido have no checksum recorded, only their size is
+                    verified.
+
+                    Fails if a file is corrupted or truncated. Files that are not available locally are
+                    reported, but are not an error.
+                "#)),
+        )
+        .arg(arg_offset("releases"))
+        .arg(arg_page("releases"));
 
     Command::new("butido")
         .author(crate_authors!())
@@ -67,7 +101,13 @@ pub fn cli() -> Command {
             The following environment variables can be passed to butido:
 
                 RUST_LOG - to enable logging, for exact usage see the Rust cookbook
+
+            Executables named "butido-<name>" on the PATH can be run as "butido <name>" (plugins). They get
+            the context of butido in the environment variables BUTIDO_REPO_ROOT, BUTIDO_CONFIG_FILES,
+            BUTIDO_PROFILE (if set), BUTIDO_DATABASE_URL and BUTIDO_EXECUTABLE.
         "#))
+        .allow_external_subcommands(true)
+        .external_subcommand_value_parser(clap::value_parser!(std::ffi::OsString))
 
         .arg(Arg::new("version")
             .action(ArgAction::SetTrue)
@@ -77,6 +117,30 @@ pub fn cli() -> Command {
             .help("Detailed version output with build information")
         )
 
+        .arg(Arg::new("columns")
+            .required(false)
+            .global(true)
+            .long("columns")
+            .value_name("COLUMNS")
+            .value_delimiter(',')
+            .help("Only show these columns (comma separated) in the tables of listing subcommands")
+            .long_help(indoc::indoc!(r#"
+                Only show these columns, in this order, in the tables of listing subcommands
+                (e.g., "--columns submit,package,status"). The column names are the table headers, case and
+                punctuation are ignored ("submit-uuid" selects the column "Submit UUID").
+
+                Tables are truncated to the width of the terminal, the CSV output contains the full values.
+            "#))
+        )
+
+        .arg(Arg::new("full-ids")
+            .action(ArgAction::SetTrue)
+            .required(false)
+            .global(true)
+            .long("full-ids")
+            .help("Show the full UUIDs in tables instead of the first 8 characters")
+        )
+
         .arg(Arg::new("tracing-chrome")
             .action(ArgAction::SetTrue)
             .required(false)
@@ -84,6 +148,57 @@ pub fn cli() -> Command {
             .help("Generate a Chrome compatible trace file (trace-*.json)")
         )
 
+        .arg(Arg::new("log-json")
+            .action(ArgAction::SetTrue)
+            .required(false)
+            .long("log-json")
+            .help("Print the diagnostic output of butido as JSON lines to stderr")
+            .long_help(indoc::indoc!(r#"
+                Print the diagnostic output of butido (the messages that are filtered with RUST_LOG) as JSON
+                lines to stderr, so that it can be indexed by a log aggregation system.
+
+                Every line contains the level, the target and the fields of the message. Messages that belong
+                to a submit or a job carry the spans "submit_context" (field "submit_uuid") and "job" (fields
+                "submit_uuid", "job_uuid", "package" and "version").
+            "#))
+        )
+
+        .arg(Arg::new("error-format")
+            .required(false)
+            .long("error-format")
+            .value_name("FORMAT")
+            .value_parser(["text", "json"])
+            .default_value("text")
+            .help("How the error is printed to stderr if butido fails")
+            .long_help(indoc::indoc!(r#"
+                How the error is printed to stderr if butido fails. With "json", one JSON object with the
+                fields "category", "exit_code" and "messages" (the error and its causes) is printed.
+
+                The exit code depends on the category of the error:
+                    1  other errors
+                    2  invalid command line arguments
+                    3  "config": the configuration cannot be loaded or is invalid
+                    4  "database": the database cannot be reached or a query failed
+                    5  "resolution": the package or its dependencies cannot be found
+                    6  "build-failure": jobs failed and no job succeeded
+                    7  "partial-failure": jobs failed, but other jobs succeeded
+                    8  "infrastructure": only jobs failed that did not finish or whose failure class is
+                       retried on other endpoints, or the endpoints cannot be used
+            "#))
+        )
+
+        .arg(Arg::new("profile")
+            .required(false)
+            .long("profile")
+            .value_name("PROFILE")
+            .help("Use the settings of this configuration profile")
+            .long_help(indoc::indoc!(r#"
+                Select a profile from the configuration. The settings in the table `profiles.<PROFILE>` of the
+                configuration override the other settings (but not the ones from environment variables), e.g., to
+                use a different set of endpoints or other stores.
+            "#))
+        )
+
         .arg(Arg::new("hide_bars")
             .action(ArgAction::SetTrue)
             .required(false)
@@ -91,6 +206,20 @@ pub fn cli() -> Command {
             .help("Hide all progress bars")
         )
 
+        .arg(Arg::new("offline")
+            .action(ArgAction::SetTrue)
+            .required(false)
+            .long("offline")
+            .help("Forbid network access (source downloads, image pulls, remote caches)")
+            .long_help(indoc::indoc!(r#"
+                Forbid all network access of butido itself, for air-gapped build environments. Everything has to
+                be available locally: sources that are not in the source cache are not downloaded (sources with a
+                file:// URL are still copied), the remote cache is not used, Kubernetes nodes do not pull images
+                and commands that need the network (e.g. "outdated") fail with a message about what is missing.
+                The endpoints and the database are still used.
+            "#))
+        )
+
         .arg(Arg::new("database_host")
             .required(false)
             .long("db-url")
@@ -208,16 +337,90 @@ pub fn cli() -> Command {
                     .short('J')
                     .value_name("JOB UUID")
                     .help("Print only artifacts for a certain job")
-                    .value_parser(uuid::Uuid::parse_str)
+                    .value_parser(parse_uuid_prefix)
                 )
                 .arg(Arg::new("limit")
                     .required(false)
                     .long("limit")
                     .short('L')
                     .value_name("LIMIT")
-                    .help("List newest LIMIT artifacts (0=unlimited)")
+                    .help("List newest (or biggest, see --sort) LIMIT artifacts (0=unlimited)")
                     .value_parser(clap::value_parser!(usize))
                 )
+                .arg(arg_offset("artifacts"))
+                .arg(arg_page("artifacts"))
+                .arg(Arg::new("sort")
+                    .required(false)
+                    .long("sort")
+                    .value_name("FIELD")
+                    .value_parser(["id", "size"])
+                    .default_value("id")
+                    .help("Sort the artifacts by FIELD")
+                    .long_help(indoc::indoc!(r#"
+                        Sort the artifacts by FIELD.
+                        "id" sorts by creation (newest at the bottom), "size" sorts by the size of the artifact
+                        (biggest at the bottom). Artifacts without a recorded size are considered the smallest.
+                    "#))
+                )
+                .arg(Arg::new("missing_on_disk")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("missing-on-disk")
+                    .conflicts_with_all(["job_uuid", "limit", "offset", "page"])
+                    .help("Cross-check the artifacts in the DB with the files on disk")
+                    .long_help(indoc::indoc!(r#"
+                        Cross-check the artifacts and releases in the DB with the files in the staging directory
+                        and the release stores.
+                        Reports artifacts whose file is neither in the staging directory nor in a release store,
+                        releases whose file is missing in the release store, and files in the staging directory
+                        or the release stores that are not in the DB.
+                    "#))
+                )
+                .arg(Arg::new("fix")
+                    .action(ArgAction::Append)
+                    .required(false)
+                    .long("fix")
+                    .value_name("ACTION")
+                    .value_parser(["delete-orphan-rows", "register-orphan-files"])
+                    .requires("missing_on_disk")
+                    .help("Fix the problems that were found by --missing-on-disk")
+                    .long_help(indoc::indoc!(r#"
+                        Fix the problems that were found by --missing-on-disk (can be passed multiple times).
+                        "delete-orphan-rows" deletes the artifacts and releases whose files are missing from the DB.
+                        "register-orphan-files" adds the files in the staging directory that are not in the DB as
+                        artifacts of the job that reported them (or of the only job of the submit). Files in the
+                        release stores cannot be registered, as the artifact they were released from is unknown.
+                    "#))
+                )
+            )
+
+            .subcommand(Command::new("artifact-provenance")
+                .about("Show the provenance of an artifact")
+                .long_about(indoc::indoc!(r#"
+                    Show where an artifact comes from: the job that built it, the submit and the commit of the
+                    repository, the image and the artifacts of the dependencies that were put into the container
+                    of the job, and the releases of the artifact.
+
+                    If an artifact with this path was built more than once, the newest one is shown.
+                "#))
+                .arg(Arg::new("artifact")
+                    .required(true)
+                    .index(1)
+                    .value_name("ARTIFACT")
+                    .help("The path of the artifact (in the staging store or in a release store)")
+                )
+                .arg(Arg::new("attestation")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("attestation")
+                    .help("Print the provenance attestation of the latest release of the artifact")
+                    .long_help(indoc::indoc!(r#"
+                        Print the provenance attestation of the latest release of the artifact, as it was
+                        written next to the released artifact: a DSSE envelope with an in-toto statement with
+                        a SLSA provenance predicate. Attestations are only created if the "provenance" section is
+                        set in the configuration.
+                    "#))
+                )
             )
 
             .subcommand(Command::new("envvars")
@@ -230,6 +433,152 @@ pub fn cli() -> Command {
                 )
             )
 
+            .subcommand(Command::new("export-stats")
+                .about("Export per-job build statistics for offline analysis")
+                .long_about(indoc::indoc!(r#"
+                    Export one record per job (package, version, image, endpoint, start and end time, duration,
+                    status and the total size of the artifacts of the job), so that the efficiency of the build
+                    farm can be analyzed with other tools.
+
+                    The status is "success" or "failure" as parsed from the job log, or "unknown" if the log does
+                    not contain the result. Retried jobs (which failed because of the infrastructure) are
+                    exported with the status "retried".
+                "#))
+                .arg(arg_newer_than_date("Only export jobs of submits newer than DATE")
+                    .long("since")
+                )
+                .arg(Arg::new("format")
+                    .required(false)
+                    .long("format")
+                    .value_name("FORMAT")
+                    .value_parser(["csv", "json"])
+                    .default_value("csv")
+                    .help("Export the records as CSV (with a header) or as JSON lines (one object per job)")
+                )
+                .arg(Arg::new("output")
+                    .required(false)
+                    .long("output")
+                    .short('o')
+                    .value_name("PATH")
+                    .value_parser(clap::value_parser!(PathBuf))
+                    .help("Write the records to this file instead of stdout")
+                )
+            )
+
+            .subcommand(Command::new("queued-submits")
+                .about("List the builds that were enqueued with \"build --enqueue\"")
+                .arg(Arg::new("csv")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("csv")
+                    .help("Format output as CSV")
+                )
+                .arg(Arg::new("limit")
+                    .required(false)
+                    .long("limit")
+                    .short('L')
+                    .value_name("LIMIT")
+                    .help("List newest LIMIT queued submits (0=unlimited)")
+                    .value_parser(clap::value_parser!(usize))
+                )
+            )
+
+            .subcommand(Command::new("flaky")
+                .about("List packages whose builds alternate between success and failure")
+                .long_about(indoc::indoc!(r#"
+                    Find packages whose jobs alternated between success and failure although they were built
+                    with the same image from the same commit of the repository. The packages are ranked by
+                    how often the result flipped, relative to the number of jobs.
+                "#))
+                .arg(Arg::new("csv")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("csv")
+                    .help("Format output as CSV")
+                )
+                .arg(arg_newer_than_date("Only consider jobs of submits newer than DATE")
+                    .long("since")
+                    .default_value("30d")
+                )
+                .arg(Arg::new("limit")
+                    .required(false)
+                    .long("limit")
+                    .short('L')
+                    .value_name("LIMIT")
+                    .help("List the LIMIT most flaky packages (0=unlimited)")
+                    .value_parser(clap::value_parser!(usize))
+                )
+            )
+
+            .subcommand(Command::new("history")
+                .about("Show the build history of a package")
+                .long_about(indoc::indoc!(r#"
+                    Show all jobs that built a package, in chronological order: the version that was built,
+                    the image, the result, the size of the artifacts and when the artifacts were released
+                    first.
+
+                    With --graph, the results and the sizes of the artifacts over time are printed as
+                    graphs below the table.
+                "#))
+                .arg(Arg::new("csv")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("csv")
+                    .help("Format output as CSV")
+                )
+                .arg(Arg::new("graph")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("graph")
+                    .conflicts_with("csv")
+                    .help("Print the results and the sizes of the artifacts over time as graphs")
+                )
+                .arg(Arg::new("package_name")
+                    .required(true)
+                    .index(1)
+                    .value_name("PKG")
+                    .help("The name of the package")
+                )
+                .arg(Arg::new("package_version_constraint")
+                    .required(false)
+                    .index(2)
+                    .value_name("VERSION_CONSTRAINT")
+                    .help("A version constraint to match the package version against (optional), e.g., '=1.0.0'")
+                )
+            )
+
+            .subcommand(Command::new("licenses")
+                .about("Report the licenses of the packages of a submit")
+                .long_about(indoc::indoc!(r#"
+                    Report the licenses of the packages of a submit (the requested package and the packages of
+                    all jobs of the submit), as recorded in the database when the submit was built.
+
+                    Packages without license or with a license that is listed in "license_deny_list" in the
+                    configuration are flagged. Packages whose artifacts were reused from earlier submits did not
+                    run as job of the submit and are not included.
+                "#))
+                .arg(Arg::new("submit")
+                    .required(true)
+                    .index(1)
+                    .value_name("SUBMIT")
+                    .help("The Submit to report the licenses of")
+                    .value_parser(parse_uuid_prefix)
+                )
+                .arg(Arg::new("csv")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("csv")
+                    .conflicts_with("json")
+                    .help("Format output as CSV")
+                )
+                .arg(Arg::new("json")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("json")
+                    .help("Format output as JSON")
+                )
+            )
+
             .subcommand(Command::new("images")
                 .about("List images from the DB")
                 .arg(Arg::new("csv")
@@ -243,11 +592,48 @@ pub fn cli() -> Command {
             .subcommand(Command::new("submit")
                 .about("Show details about one specific submit")
                 .arg(Arg::new("submit")
-                    .required(true)
+                    .required(false)
                     .index(1)
                     .value_name("SUBMIT")
-                    .help("The Submit to show details about")
-                    .value_parser(uuid::Uuid::parse_str)
+                    .help("The Submit to show details about (selected interactively if omitted)")
+                    .value_parser(parse_uuid_prefix)
+                )
+                .arg(Arg::new("schedule")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("schedule")
+                    .help("Show the jobs of the submit grouped into waves of jobs that could run in parallel")
+                    .long_help(indoc::indoc!(r#"
+                        Show the jobs of the submit grouped into numbered waves of jobs that could run in parallel.
+
+                        The dependencies of the jobs are not stored in the database, so the dependency tree is
+                        recomputed from the package definitions in the current repository checkout. A warning is
+                        printed if the repository HEAD differs from the commit of the submit.
+                    "#))
+                )
+                .arg(Arg::new("junit_out")
+                    .required(false)
+                    .long("junit-out")
+                    .value_name("PATH")
+                    .value_parser(clap::value_parser!(PathBuf))
+                    .help("Write a JUnit XML report for the jobs of the submit to this file")
+                    .long_help(indoc::indoc!(r#"
+                        Write a JUnit XML report for the submit to this file. Each job is rendered as a test case
+                        (with its duration and, for failed jobs, the last lines of the log), so that CI systems can
+                        display the results of the submit.
+                    "#))
+                )
+                .arg(Arg::new("html_out")
+                    .required(false)
+                    .long("html")
+                    .value_name("PATH")
+                    .value_parser(clap::value_parser!(PathBuf))
+                    .help("Write a standalone HTML report of the submit to this file")
+                    .long_help(indoc::indoc!(r#"
+                        Write a standalone HTML report of the submit to this file, e.g. to attach it to a release
+                        ticket. The report contains a summary of the submit, a table of the jobs with their status
+                        and links to their artifacts, and the last lines of the logs of the jobs that failed.
+                    "#))
                 )
             )
 
@@ -281,6 +667,8 @@ pub fn cli() -> Command {
                     .help("List newest LIMIT submits (0=unlimited)")
                     .value_parser(clap::value_parser!(usize))
                 )
+                .arg(arg_offset("submits"))
+                .arg(arg_page("submits"))
                 .arg(Arg::new("for-commit")
                     .required(false)
                     .long("commit")
@@ -294,6 +682,58 @@ pub fn cli() -> Command {
                     .value_name("IMAGE")
                     .help("Limit listed submits to submits on IMAGE")
                 )
+                .arg(arg_older_than_date("List only submits older than DATE"))
+                .arg(Arg::new("stale")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("stale")
+                    .help("List only submits that are stuck in a non-final state")
+                    .long_help(indoc::indoc!(r#"
+                        List only submits that are stuck in a non-final state ("created" or "running"),
+                        e.g. because butido crashed during the build.
+                        Note that the submits that are currently being built are in one of these states as well,
+                        use --older-than to skip them.
+                    "#))
+                )
+                .arg(Arg::new("stats_per_day")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("stats-per-day")
+                    .conflicts_with_all(["stats_per_week", "cleanup"])
+                    .help("Show statistics per day instead of the submits")
+                    .long_help(indoc::indoc!(r#"
+                        Instead of listing the submits, aggregate them by the day they were submitted and show the
+                        number of (succeeded and failed) submits, the success rate and the average duration of the
+                        submits for each day.
+
+                        A submit succeeded if it finished and none of its jobs failed. The duration of a submit is the
+                        time from the submit until its last job finished.
+
+                        All filters apply. Without --limit, all (matching) submits are aggregated.
+                    "#))
+                )
+                .arg(Arg::new("stats_per_week")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("stats-per-week")
+                    .conflicts_with_all(["stats_per_day", "cleanup"])
+                    .help("Show statistics per (ISO) week instead of the submits")
+                    .long_help(indoc::indoc!(r#"
+                        Like --stats-per-day, but aggregate the submits by the ISO week (e.g. "2024-W45") they were
+                        submitted in.
+                    "#))
+                )
+                .arg(Arg::new("cleanup")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("cleanup")
+                    .requires("stale")
+                    .help("Clean up the listed stale submits")
+                    .long_help(indoc::indoc!(r#"
+                        Clean up the listed stale submits.
+                        Submits without any jobs are deleted from the DB, submits with jobs are marked as "aborted".
+                    "#))
+                )
             )
 
             .subcommand(Command::new("jobs")
@@ -311,7 +751,7 @@ pub fn cli() -> Command {
                     .short('S')
                     .value_name("UUID")
                     .help("Only list jobs of a certain submit")
-                    .value_parser(uuid::Uuid::parse_str)
+                    .value_parser(parse_uuid_prefix)
                 )
 
                 .arg(Arg::new("image")
@@ -338,6 +778,8 @@ pub fn cli() -> Command {
                     .help("List newest LIMIT jobs (0=unlimited)")
                     .value_parser(clap::value_parser!(usize))
                 )
+                .arg(arg_offset("jobs"))
+                .arg(arg_page("jobs"))
 
                 .arg(arg_older_than_date("List only jobs older than DATE"))
                 .arg(arg_newer_than_date("List only jobs newer than DATE"))
@@ -357,6 +799,47 @@ pub fn cli() -> Command {
                     .value_name("PKG")
                     .help("Only show jobs for PKG")
                 )
+                .arg(Arg::new("license")
+                    .required(false)
+                    .long("license")
+                    .value_name("PATTERN")
+                    .help("Only show jobs for packages with a license that matches PATTERN (glob, e.g. 'GPL*')")
+                )
+                .arg(Arg::new("namespace")
+                    .required(false)
+                    .long("namespace")
+                    .value_name("NAMESPACE")
+                    .help("Only show jobs for packages in the namespace NAMESPACE (e.g. 'team' for 'team/foo')")
+                )
+
+                .arg(Arg::new("image_digest")
+                    .required(false)
+                    .long("image-digest")
+                    .value_name("DIGEST")
+                    .help("Only show jobs that ran on the image with DIGEST (or a prefix of it)")
+                )
+
+                .arg(Arg::new("arch")
+                    .required(false)
+                    .long("arch")
+                    .value_name("ARCH")
+                    .help("Only show jobs that built for the architecture ARCH")
+                )
+
+                .arg(Arg::new("group_by")
+                    .required(false)
+                    .long("group-by")
+                    .value_name("FIELD")
+                    .value_parser(["package", "image", "endpoint"])
+                    .help("Show statistics per package, image or endpoint instead of the jobs")
+                    .long_help(indoc::indoc!(r#"
+                        Instead of listing the jobs, aggregate them by package (name), image or endpoint and show
+                        the number of (succeeded and failed) jobs, the time of the last successful job and the
+                        average duration of the jobs for each group.
+
+                        All filters apply. Without --limit, all (matching) jobs are aggregated.
+                    "#))
+                )
 
             )
 
@@ -370,11 +853,11 @@ pub fn cli() -> Command {
                 )
 
                 .arg(Arg::new("job_uuid")
-                    .required(true)
+                    .required(false)
                     .index(1)
                     .value_name("UUID")
-                    .help("The job to show")
-                    .value_parser(uuid::Uuid::parse_str)
+                    .help("The job to show (selected interactively if omitted)")
+                    .value_parser(parse_uuid_prefix)
                 )
 
                 .arg(Arg::new("show_log")
@@ -401,6 +884,52 @@ pub fn cli() -> Command {
                     .help("Show the environment of the job")
                 )
 
+                .arg(Arg::new("show_probes")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("probes")
+                    .short('P')
+                    .help("Show the output of the environment probes of the job (e.g. compiler versions)")
+                )
+
+                .arg(Arg::new("show_artifacts")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("artifacts")
+                    .short('A')
+                    .help("Show the artifacts of the job (path, size, checksum and releases)")
+                )
+
+                .arg(Arg::new("fetch_artifacts")
+                    .required(false)
+                    .long("fetch-artifacts")
+                    .value_name("DIR")
+                    .value_parser(clap::value_parser!(PathBuf))
+                    .conflicts_with("export_workdir")
+                    .help("Copy the artifacts of the job to DIR instead of showing the job")
+                    .long_help(indoc::indoc!(r#"
+                        Copy the artifacts of the job to DIR (keeping their paths), instead of showing the job.
+                        The artifacts are copied from the staging store of the submit or, if they are not there
+                        anymore, from a release store they were released to. The copies are verified against
+                        the size and checksum recorded for the artifacts. Existing files are not overwritten.
+                    "#))
+                )
+
+                .arg(Arg::new("export_workdir")
+                    .required(false)
+                    .long("export-workdir")
+                    .value_name("FILE")
+                    .value_parser(clap::value_parser!(PathBuf))
+                    .help("Export the file system of the container of the job to FILE instead of showing the job")
+                    .long_help(indoc::indoc!(r#"
+                        Export the file system of the container of the job (e.g. the build directory of a failed
+                        job) as a TAR archive to FILE, instead of showing the job.
+                        This is a shortcut for "endpoint <ENDPOINT> container <CONTAINER> export --output FILE",
+                        the container must still exist on the endpoint the job ran on.
+                        The archive is compressed with zstd if the file name ends with ".zst".
+                    "#))
+                )
+
                 .arg(script_arg_line_numbers())
                 .arg(script_arg_no_line_numbers())
                 .arg(script_arg_highlight())
@@ -409,11 +938,52 @@ pub fn cli() -> Command {
             .subcommand(Command::new("log-of")
                 .about("Print log of a job, short version of 'db job --log'")
                 .arg(Arg::new("job_uuid")
-                    .required(true)
+                    .required(false)
                     .index(1)
                     .value_name("UUID")
-                    .help("The job to print the log of")
-                    .value_parser(uuid::Uuid::parse_str)
+                    .help("The job to print the log of (selected interactively if omitted)")
+                    .value_parser(parse_uuid_prefix)
+                )
+                .arg(Arg::new("filter")
+                    .required(false)
+                    .action(ArgAction::Append)
+                    .long("filter")
+                    .value_name("FILTER")
+                    .value_parser(["errors", "warnings"])
+                    .help("Only print the log lines with errors or warnings")
+                    .long_help(indoc::indoc!(r#"
+                        Only print the log lines that contain errors or warnings. Can be passed multiple times.
+                        The lines are selected with the regular expressions from the "log_filters" setting in the
+                        configuration. Status lines of the script (phases, state) are always printed.
+                    "#))
+                )
+                .arg(Arg::new("phase")
+                    .required(false)
+                    .long("phase")
+                    .value_name("PHASE")
+                    .help("Only print the log of this phase (as announced by the script)")
+                )
+                .arg(Arg::new("tail")
+                    .required(false)
+                    .long("tail")
+                    .short('n')
+                    .value_name("N")
+                    .value_parser(clap::value_parser!(usize))
+                    .help("Only print the last N log lines (after filtering), markers do not count")
+                )
+                .arg(Arg::new("color")
+                    .required(false)
+                    .long("color")
+                    .value_name("WHEN")
+                    .value_parser(["always", "never", "auto"])
+                    .default_value("auto")
+                    .help("When to print the log in color")
+                    .long_help(indoc::indoc!(r#"
+                        When to print the log in color. Logs are stored with the colors (ANSI escape sequences) of
+                        the build output. With "never", the escape sequences are removed and the status lines of
+                        the script are not highlighted. With "auto" (the default), the log is printed in color if
+                        stdout is a terminal.
+                    "#))
                 )
             )
             .subcommand(releases_list_command.clone())
@@ -452,6 +1022,35 @@ pub fn cli() -> Command {
                     Do not perform script linting before starting the build.
                 "#))
             )
+            .arg(Arg::new("locked")
+                .required(false)
+                .long("locked")
+                .value_name("LOCKFILE")
+                .num_args(0..=1)
+                .default_missing_value(crate::util::lockfile::DEFAULT_LOCKFILE_PATH)
+                .conflicts_with("enqueue")
+                .help("Refuse to build if the dependency tree deviates from the lockfile")
+                .long_help(indoc::indoc!(r#"
+                    Refuse to build if the dependency tree deviates from the lockfile (default: "butido.lock"),
+                    which was generated with "butido lock generate". Every deviation is listed: changed package
+                    versions, added or removed packages, changed source URLs or hashes, changed dependency edges
+                    and a changed image or image digest.
+
+                    The image digest is only compared if it is known, i.e., if the build runs on Docker endpoints.
+                "#))
+            )
+            .arg(Arg::new("ignore_disk_space")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .long("ignore-disk-space")
+                .help("Build even if there is not enough free disk space")
+                .long_help(indoc::indoc!(r#"
+                    Build even if there is not enough free disk space.
+                    Before the build starts, the free space in the staging directory, the source cache and on the
+                    endpoints is checked against the "disk_space_limits" from the configuration. With this flag,
+                    a warning is shown instead of refusing the build.
+                "#))
+            )
 
             .arg(Arg::new("staging_dir")
                 .required(false)
@@ -460,6 +1059,20 @@ pub fn cli() -> Command {
                 .value_parser(dir_exists_validator)
                 .help("Do not throw dice on staging directory name, but hardcode for this run.")
             )
+            .arg(arg_use_store())
+            .arg(Arg::new("snapshot")
+                .required(false)
+                .long("snapshot")
+                .value_name("SNAPSHOT")
+                .conflicts_with("use_store")
+                .help("Only reuse artifacts from this snapshot of a release store (see \"store snapshot\")")
+                .long_help(indoc::indoc!(r#"
+                    Resolve the dependencies against the artifacts that were recorded in the snapshot (see "store
+                    snapshot create") instead of the current contents of the release stores, e.g., for reproducible
+                    builds of a maintenance branch. Artifacts from the staging store are still reused.
+                    The build fails if an artifact of the snapshot was removed from the release store or changed.
+                "#))
+            )
 
             .arg(Arg::new("shebang")
                 .required(false)
@@ -481,6 +1094,54 @@ pub fn cli() -> Command {
                 "#))
             )
 
+            .arg(Arg::new("env_file")
+                .required(false)
+                .action(ArgAction::Append)
+                .long("env-file")
+                .value_name("FILE")
+                .value_hint(ValueHint::FilePath)
+                .help("Pass the environment variables from a dotenv file to all build jobs")
+                .long_help(indoc::indoc!(r#"
+                    Pass the variables from this dotenv file (lines of the form "KEY=VALUE") to each build job.
+                    Can be passed multiple times, variables from later files take precedence. Variables that are
+                    passed with --env take precedence over variables from these files.
+
+                    With --enqueue, the path is made absolute and the file is read when the daemon runs the
+                    build, so it must be readable by the daemon.
+                "#))
+            )
+
+            .arg(Arg::new("skip_phase")
+                .required(false)
+                .action(ArgAction::Append)
+                .long("skip-phase")
+                .value_name("PHASE")
+                .help("Do not execute this phase of the package")
+                .long_help(indoc::indoc!(r#"
+                    Do not execute this phase of the requested package. Can be passed multiple times.
+                    Only the requested package is modified, its dependencies are built with all phases.
+                    The skipped phases are recorded in the submit and visible in the script of the job.
+                "#))
+            )
+
+            .arg(Arg::new("override_phase")
+                .required(false)
+                .action(ArgAction::Append)
+                .long("override-phase")
+                .value_name("PHASE=FILE")
+                .conflicts_with("enqueue")
+                .help("Execute the script from FILE instead of this phase of the package")
+                .long_help(indoc::indoc!(r#"
+                    Execute the script from FILE instead of the phase PHASE of the requested package, e.g.
+                    "--override-phase install=./local-install.sh". Can be passed multiple times.
+                    The script is interpolated like the phases in the pkg.toml files. Only the requested
+                    package is modified, its dependencies are built unchanged. The overridden phases are
+                    recorded in the submit and visible in the script of the job.
+
+                    Cannot be used with --enqueue, because the file is not available to the daemon.
+                "#))
+            )
+
             .arg(Arg::new("image")
                 .required(true)
                 .value_name("IMAGE NAME")
@@ -489,6 +1150,66 @@ pub fn cli() -> Command {
                 .help("Name of the Docker image to use")
             )
 
+            .arg(Arg::new("endpoint")
+                .required(false)
+                .action(ArgAction::Append)
+                .long("endpoint")
+                .value_name("NAME|TAG")
+                .conflicts_with_all(["local_exec", "local_chroot"])
+                .help("Only run the jobs on this endpoint or the endpoints with this tag (can be passed multiple times)")
+                .long_help(indoc::indoc!(r#"
+                    Only run the jobs of the submit on the endpoint NAME (or on the endpoints with the tag TAG)
+                    instead of all configured endpoints, e.g. to test a new build host or to avoid a host that
+                    is under maintenance. Can be passed multiple times to use several endpoints.
+                "#))
+            )
+
+            .arg(Arg::new("arch")
+                .required(false)
+                .action(ArgAction::Append)
+                .long("arch")
+                .value_name("ARCH")
+                .conflicts_with_all(["local_exec", "local_chroot"])
+                .help("Build for this architecture (can be passed multiple times)")
+                .long_help(indoc::indoc!(r#"
+                    Build the packages for the architecture ARCH, on the endpoints with that architecture.
+                    Can be passed multiple times to build for several architectures in one submit, there is
+                    one job per package and architecture. The artifacts of the jobs for an architecture are
+                    stored in the subdirectory ARCH of the staging directory.
+                    Default: the architectures of the image (that the package supports), if any are configured.
+                "#))
+            )
+
+            .arg(Arg::new("local_exec")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .long("local-exec")
+                .conflicts_with_all(["local_chroot", "enqueue"])
+                .help("Run the jobs directly on the host instead of the Docker endpoints")
+                .long_help(indoc::indoc!(r#"
+                    Run the scripts of the jobs directly on the host instead of the Docker endpoints, one job at a
+                    time, for quick iterations on a package. Each job gets a work directory in the temporary
+                    directory and "/inputs", "/outputs", "/patches" and "/build" in the script (and in the
+                    BUTIDO_SOURCE_* variables) are replaced by the directories in it. The work directory of a failed job is kept for debugging.
+
+                    The jobs are recorded with the endpoint "local" and their artifacts are written to the
+                    "local" directory in the staging directory. They are never reused by the build cache and
+                    cannot be released. The image is only recorded, the script does not run in it.
+                "#))
+            )
+            .arg(Arg::new("local_chroot")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .long("local-chroot")
+                .conflicts_with("enqueue")
+                .help("Like --local-exec, but run the jobs in a bubblewrap sandbox")
+                .long_help(indoc::indoc!(r#"
+                    Like --local-exec, but the scripts run in a bubblewrap ("bwrap") sandbox, in which the host
+                    file system is read-only and the work directory of the job is mounted at "/inputs",
+                    "/outputs", "/patches" and "/build", so the script does not need to be rewritten.
+                "#))
+            )
+
             .arg(Arg::new("write-log-file")
                 .action(ArgAction::SetTrue)
                 .required(false)
@@ -499,11 +1220,192 @@ pub fn cli() -> Command {
                     With this flag set, butido does not only write the build logs to database, but also to the configured
                     log directory.
 
-                    The log of a build is written to `<log_dir>/<build id>.log`.
+                    The log of a build is written to `<log_dir>/<build id>.log`.
+                "#))
+            )
+
+            .arg(Arg::new("reuse_artifacts")
+                .required(false)
+                .long("reuse-artifacts")
+                .value_name("POLICY")
+                .value_parser(["never", "same-githash", "any"])
+                .default_value("any")
+                .help("Which artifacts of previous submits are reused instead of running the jobs")
+                .long_help(indoc::indoc!(r#"
+                    If no dependency of a package had to be built, butido searches the staging and release stores
+                    for artifacts of a previous job of the package with the same script and environment and reuses
+                    them instead of running the job. This setting controls which artifacts may be reused:
+
+                        never:         Never reuse artifacts, run all jobs
+                        same-githash:  Only reuse artifacts that were built from the same commit of the repository
+                        any:           Reuse artifacts that were built from any commit
+
+                    The reused artifacts, the submits that built them and the policy are recorded with the submit
+                    (see "db submit"). The build cache is not affected by this setting (see --no-cache).
+                "#))
+            )
+
+            .arg(Arg::new("no_cache")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .long("no-cache")
+                .help("Do not reuse artifacts from the build cache")
+                .long_help(indoc::indoc!(r#"
+                    Before a job is run, butido computes a cache key from the package definition, the digest of the
+                    image and the hashes of all input artifacts. If a previous job with the same cache key produced
+                    artifacts that are still available in the staging or release stores, these artifacts are reused
+                    instead of running the job.
+
+                    With this flag set, the cache is not used and all jobs are run. The cache keys are not computed
+                    either, so the artifacts of these jobs are not reused by later builds.
+                "#))
+            )
+
+            .arg(Arg::new("enqueue")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .long("enqueue")
+                .conflicts_with_all(["staging_dir", "print_schedule"])
+                .help("Only record the build in the database, to be run by the daemon")
+                .long_help(indoc::indoc!(r#"
+                    Do not run the build, but record it (the arguments of this command and the commit of the
+                    repository) in the database and exit. The build is then run by `butido daemon`.
+
+                    The commit must be available in the repository of the daemon. Relative paths of files
+                    (e.g. --env-file or --summary-out) are made absolute.
+                "#))
+            )
+
+            .arg(Arg::new("submit_uuid")
+                .required(false)
+                .long("submit-uuid")
+                .value_name("UUID")
+                .value_parser(uuid::Uuid::parse_str)
+                .conflicts_with("staging_dir")
+                .hide(true)
+                .help("Use this UUID for the submit (used by the daemon to run enqueued builds)")
+            )
+
+            .arg(Arg::new("priority")
+                .required(false)
+                .long("priority")
+                .value_name("PRIORITY")
+                .default_value("0")
+                .allow_negative_numbers(true)
+                .value_parser(clap::value_parser!(i32))
+                .help("The priority of the jobs of this build in the job queue")
+                .long_help(indoc::indoc!(r#"
+                    All jobs (of all builds that use the same database) are dispatched to the endpoints via a shared
+                    job queue. Jobs with a higher priority are dispatched first. Jobs with the same priority are
+                    dispatched fairly across the builds, so a large build does not block smaller builds.
+
+                    The priority of a job is this priority plus the "priority" setting of its package (if any).
+                "#))
+            )
+
+            .arg(Arg::new("wait_for_locks")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .long("wait-for-locks")
+                .help("Wait for conflicting submits to finish instead of failing")
+                .long_help(indoc::indoc!(r#"
+                    A submit locks its staging directory and, while a job builds a package, the package (in the
+                    database), so that conflicting submits do not build the same package at the same time.
+                    Packages whose artifacts are reused are not locked. By default, a submit (or the job) fails if
+                    another submit holds one of these locks. With this flag, it waits until the lock is released.
+                "#))
+            )
+
+            .arg(Arg::new("print_schedule")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .long("print-schedule")
+                .help("Only print the build schedule and exit")
+                .long_help(indoc::indoc!(r#"
+                    With this flag set, butido does not build anything, but only prints the computed build order.
+
+                    The jobs are grouped into numbered waves: All jobs of one wave only depend on jobs of the previous
+                    waves and can therefore run in parallel (given enough endpoint capacity).
+                "#))
+            )
+
+            .arg(Arg::new("log_format")
+                .required(false)
+                .long("log-format")
+                .value_name("FORMAT")
+                .value_parser(["plain", "json"])
+                .help("Print status lines in this format instead of progress bars")
+                .long_help(indoc::indoc!(r#"
+                    Replace the progress bars with status lines that are printed to stderr, one line per event (job
+                    started, phase transitions, progress, job finished, ...). This is useful in environments where
+                    progress bars cannot be displayed, e.g., in CI logs.
+
+                    With "plain", human readable lines are printed, with "json", each line is a JSON object.
+                "#))
+            )
+
+            .arg(Arg::new("summary_out")
+                .required(false)
+                .long("summary-out")
+                .value_name("PATH")
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(ValueHint::FilePath)
+                .help("Write a summary of the build as JSON to this file")
+                .long_help(indoc::indoc!(r#"
+                    After the build finished, write a machine-readable summary of the build (submit, jobs with their
+                    status and errors, created artifacts) as JSON to this file.
+
+                    The summary is written even if the build failed or could not be run at all, the errors of the
+                    latter are listed in "errors".
+                "#))
+            )
+
+            .arg(Arg::new("junit_out")
+                .required(false)
+                .long("junit-out")
+                .value_name("PATH")
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(ValueHint::FilePath)
+                .help("Write a JUnit XML report for the jobs of the build to this file")
+                .long_help(indoc::indoc!(r#"
+                    After the build finished, write a JUnit XML report to this file. Each job is rendered as a test
+                    case (with its duration and, for failed jobs, the last lines of the log), so that CI systems can
+                    display the results of the build.
+
+                    The number of log lines is configured with `build_error_lines`.
+                    The report is written even if the build failed.
                 "#))
             )
         )
 
+        .subcommand(Command::new("daemon")
+            .about("Run the builds that were enqueued with \"build --enqueue\"")
+            .long_about(indoc::indoc!(r#"
+                Run the builds that were enqueued with "build --enqueue", one after another, in the order in which
+                they were enqueued. For each build, the recorded commit is checked out in the repository and
+                "butido build" is run with the recorded arguments. The state of the builds can be shown with
+                "butido db queued-submits".
+
+                The daemon checks out other commits, so it should run in a dedicated clone of the repository.
+                Multiple daemons (in different clones) can run the enqueued builds in parallel. A build whose
+                daemon was killed is run again by another daemon.
+            "#))
+            .arg(Arg::new("poll_interval")
+                .required(false)
+                .long("poll-interval")
+                .value_name("SECONDS")
+                .default_value("10")
+                .value_parser(clap::value_parser!(u64))
+                .help("Check for enqueued builds in this interval")
+            )
+            .arg(Arg::new("once")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .long("once")
+                .help("Run only the next enqueued build (if any) and exit")
+            )
+        )
+
         .subcommand(Command::new("what-depends")
             .about("List all packages that depend on a specific package")
             .arg(Arg::new("package_name")
@@ -560,6 +1462,202 @@ pub fn cli() -> Command {
                 .help("Specify which dependency types are to be printed. By default, all are checked")
             )
         )
+        .subcommand(Command::new("new-package")
+            .about("Create the pkg.toml for a new package (or a new version of a package)")
+            .long_about(indoc::indoc!(r#"
+                Create a pkg.toml skeleton for a new package with the name, the version and a source. The
+                source is downloaded to compute its hash. The phases are taken from a template.
+
+                A new version of an existing package is put into a directory named after the version below
+                the directory of the package, so that it inherits the settings that all versions have in
+                common. A new package gets its own directory in the root of the repository. Use --path to
+                put the pkg.toml somewhere else.
+            "#))
+            .arg(Arg::new("package_name")
+                .required(true)
+                .index(1)
+                .value_name("PACKAGE_NAME")
+                .help("The name of the package")
+            )
+            .arg(Arg::new("package_version")
+                .required(true)
+                .index(2)
+                .value_name("PACKAGE_VERSION")
+                .help("The version of the package")
+            )
+            .arg(Arg::new("url")
+                .required(true)
+                .long("url")
+                .value_name("URL")
+                .value_parser(url::Url::parse)
+                .help("The URL of the source of the package")
+            )
+            .arg(Arg::new("source_name")
+                .required(false)
+                .long("source-name")
+                .value_name("NAME")
+                .default_value("src")
+                .value_parser(source_name_validator)
+                .help("The name of the source in the pkg.toml")
+            )
+            .arg(Arg::new("template")
+                .required(false)
+                .long("template")
+                .short('t')
+                .value_name("TEMPLATE")
+                .value_parser(clap::builder::PossibleValuesParser::new(crate::commands::NEW_PACKAGE_TEMPLATES))
+                .default_value("empty")
+                .help("The template for the phases of the package (\"empty\" inherits all phases)")
+            )
+            .arg(Arg::new("path")
+                .required(false)
+                .long("path")
+                .value_name("DIR")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("The directory for the pkg.toml, relative to the repository root")
+            )
+            .arg(Arg::new("timeout")
+                .required(false)
+                .long("timeout")
+                .value_name("TIMEOUT")
+                .help("Set timeout for the download of the source in seconds")
+                .value_parser(clap::value_parser!(u64))
+            )
+        )
+        .subcommand(Command::new("bump-version")
+            .about("Change the version of a package and refresh the hashes of its sources")
+            .long_about(indoc::indoc!(r#"
+                Change the version of a package in its pkg.toml. The old version in the URLs of the sources
+                is replaced by the new version, the sources are downloaded and their hashes are updated.
+                Sources that are inherited from pkg.toml files further up in the tree are overridden in the
+                pkg.toml of the package, the other pkg.toml files are not changed.
+
+                Afterwards, the packages that depend (directly or transitively) on the package and need to
+                be rebuilt are printed.
+            "#))
+            .arg(Arg::new("package_name")
+                .required(true)
+                .index(1)
+                .value_name("PACKAGE_NAME")
+                .help("The name of the package")
+            )
+            .arg(Arg::new("new_version")
+                .required(true)
+                .index(2)
+                .value_name("NEW_VERSION")
+                .help("The new version of the package")
+            )
+            .arg(Arg::new("package_version")
+                .required(false)
+                .long("from")
+                .value_name("VERSION")
+                .help("The version to bump, if there are multiple versions of the package")
+            )
+            .arg(Arg::new("build_image")
+                .required(false)
+                .long("build")
+                .value_name("IMAGE")
+                .help("Run a test build of the bumped package on IMAGE")
+            )
+            .arg(Arg::new("timeout")
+                .required(false)
+                .long("timeout")
+                .value_name("TIMEOUT")
+                .help("Set timeout for the download of the sources in seconds")
+                .value_parser(clap::value_parser!(u64))
+            )
+        )
+        .subcommand(Command::new("watch")
+            .about("Rebuild when the package definitions change")
+            .long_about(indoc::indoc!(r#"
+                Watch the repository (or some directories of it) for changes to the package definitions
+                (pkg.toml files, patches, scripts, ...) and run a butido command whenever files changed, e.g.:
+
+                    butido watch --path packages/foo -- build foo --image debian:bookworm
+
+                Use "build --enqueue" to submit the build to the daemon instead of running it locally.
+                Changes are collected until no file changed for the debounce time, so that saving several
+                files triggers only one run. The changed files are printed before each run. Hidden files and
+                directories (e.g. ".git") are ignored.
+            "#))
+            .arg(Arg::new("path")
+                .required(false)
+                .long("path")
+                .short('p')
+                .action(ArgAction::Append)
+                .value_name("PATH")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Only watch this directory of the repository (can be passed multiple times)")
+            )
+            .arg(Arg::new("interval")
+                .required(false)
+                .long("interval")
+                .value_name("DURATION")
+                .default_value("1s")
+                .value_parser(humantime::parse_duration)
+                .help("How often the files are checked for changes")
+            )
+            .arg(Arg::new("debounce")
+                .required(false)
+                .long("debounce")
+                .value_name("DURATION")
+                .default_value("2s")
+                .value_parser(humantime::parse_duration)
+                .help("Wait until no file changed for DURATION before running the command")
+            )
+            .arg(Arg::new("command")
+                .required(true)
+                .last(true)
+                .num_args(1..)
+                .value_name("BUTIDO ARGS")
+                .help("The arguments of the butido command to run on changes (after \"--\")")
+            )
+        )
+
+        .subcommand(Command::new("outdated")
+            .about("List packages for which a newer version is available upstream")
+            .long_about(indoc::indoc!(r#"
+                Check the upstream of the packages for newer versions than the newest version of the package
+                in the repository. The upstream of a package is configured in its pkg.toml, either as GitHub
+                repository (the tags of its releases are the versions):
+
+                    upstream = { github = "owner/repository" }
+
+                or as web page (e.g. a directory listing) and a regex that finds the versions on the page
+                (the first capture group is the version):
+
+                    upstream = { url = "https://example.com/releases/", regex = 'foo-([\d.]+)\.tar\.gz' }
+
+                A regex can also be set for GitHub repositories, to extract the version from the tags. The
+                environment variable GITHUB_TOKEN is used for the GitHub API if it is set.
+            "#))
+            .arg(Arg::new("package_name_regex")
+                .required(false)
+                .index(1)
+                .value_name("REGEX")
+                .help("Only check packages whose name matches REGEX")
+            )
+            .arg(Arg::new("all")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .long("all")
+                .short('a')
+                .help("Also list the packages that are up to date")
+            )
+            .arg(Arg::new("csv")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .long("csv")
+                .help("Format output as CSV")
+            )
+            .arg(Arg::new("timeout")
+                .required(false)
+                .long("timeout")
+                .value_name("TIMEOUT")
+                .help("Set timeout for the requests in seconds")
+                .value_parser(clap::value_parser!(u64))
+            )
+        )
         .subcommand(Command::new("versions-of")
             .alias("versions")
             .about("List the versions of a package")
@@ -615,6 +1713,7 @@ pub fn cli() -> Command {
                 .value_parser(dir_exists_validator)
                 .help("Also consider this staging directory when searching for artifacts")
             )
+            .arg(arg_use_store())
             .arg(Arg::new("env_filter")
                 .required(false)
                 .action(ArgAction::Append)
@@ -656,6 +1755,36 @@ pub fn cli() -> Command {
                 .help("Do not use the fancy format, but simply <name> <version>")
             )
 
+            .arg(Arg::new("explain")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .long("explain")
+                .conflicts_with("terse")
+                .help("Print every effective field of the package with the pkg.toml file (and level) it comes from")
+                .long_help(indoc::indoc!(r#"
+                    Print every effective field of the package with the pkg.toml file it comes from.
+
+                    The level is the position of the pkg.toml file in the chain of files that are merged
+                    into the package (0 is the pkg.toml at the root of the repository). Fields that are
+                    defined in the pkg.toml of the package itself are marked as "defined", all others as
+                    "inherited". If a field is also defined on other levels, these are listed as
+                    overridden.
+                "#))
+            )
+
+            .arg(Arg::new("maintainer")
+                .required(false)
+                .long("maintainer")
+                .value_name("MAINTAINER")
+                .help("Only show packages whose maintainer contains MAINTAINER (case-insensitive)")
+            )
+            .arg(Arg::new("namespace")
+                .required(false)
+                .long("namespace")
+                .value_name("NAMESPACE")
+                .help("Only show packages in the namespace NAMESPACE (e.g. 'team' for 'team/foo')")
+            )
+
             .arg(Arg::new("show_all")
                 .action(ArgAction::SetTrue)
                 .required(false)
@@ -843,6 +1972,28 @@ pub fn cli() -> Command {
                     .help("Set timeout for download in seconds")
                     .value_parser(clap::value_parser!(u64))
                 )
+                .arg(Arg::new("rate_limit")
+                    .required(false)
+                    .long("rate-limit")
+                    .value_name("BYTES")
+                    .help("Limit the throughput of all downloads together to BYTES per second (e.g. \"10 MiB\")")
+                    .value_parser(clap::value_parser!(bytesize::ByteSize))
+                )
+                .arg(Arg::new("retries")
+                    .required(false)
+                    .long("retries")
+                    .value_name("N")
+                    .default_value("3")
+                    .help("Resume an interrupted download up to N times")
+                    .long_help(indoc::indoc!(r#"
+                        Resume an interrupted download up to N times.
+                        Sources are downloaded into a ".part" file next to the cached source, together with the
+                        information that is required to resume the download. Interrupted downloads are resumed with
+                        an HTTP range request or FTP REST (if the server supports it), by a retry or by running the download
+                        again later. The file is moved into place once its hash was verified.
+                    "#))
+                    .value_parser(clap::value_parser!(u32))
+                )
             )
             .subcommand(Command::new("of")
                 .about("Get the paths of the sources of a package")
@@ -859,6 +2010,22 @@ pub fn cli() -> Command {
                     .help("Get the source file paths for the package in matching versions (or all versions, if omitted)")
                 )
             )
+            .subcommand(Command::new("audit")
+                .about("Check the files in the source cache against the journal of the source cache")
+                .long_about(indoc::indoc!(r#"
+                    Check the files in the source cache against the journal of the source cache.
+                    Every source that is put into the source cache is recorded in the journal (".butido-journal.jsonl"
+                    in the source cache) with the URL it was downloaded from, the time, its size and its verified hash.
+                    This command checks that every recorded file still exists and was not modified since, without
+                    loading the repository.
+                "#))
+                .arg(Arg::new("unrecorded")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("unrecorded")
+                    .help("Also list the source files that are not recorded in the journal (e.g. downloaded manually)")
+                )
+            )
         )
 
         .subcommand(Command::new("release")
@@ -896,6 +2063,124 @@ pub fn cli() -> Command {
                 )
             )
 
+            .subcommand(Command::new("submit")
+                .about("Release all artifacts of a submit as a named release group")
+                .long_about(indoc::indoc!(r#"
+                    Release all artifacts of a submit at once, as one release group with a name.
+                    Artifacts are only recorded for successful jobs, so all successful artifacts of the
+                    submit are released.
+
+                    The release group can be used to filter "db releases" (with --group) and to roll back
+                    the release as a unit (with "release rollback").
+                "#))
+                .arg(Arg::new("submit_uuid")
+                    .required(true)
+                    .index(1)
+                    .value_name("SUBMIT")
+                    .help("The submit uuid from which to release the artifacts")
+                    .value_parser(parse_uuid_prefix)
+                )
+                .arg(Arg::new("release_store_name")
+                    .required(true)
+                    .long("store")
+                    .value_name("RELEASE_STORE_NAME")
+                    .help("Release store name to release to")
+                )
+                .arg(Arg::new("name")
+                    .required(false)
+                    .long("name")
+                    .value_name("NAME")
+                    .help("The unique name of the release group (defaults to \"submit-<SUBMIT>-<RELEASE_STORE_NAME>\")")
+                )
+                .arg(Arg::new("package_do_update")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("update")
+                    .help("Do update a package if it already exists in the release store")
+                )
+                .arg(Arg::new("yes")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("yes")
+                    .short('y')
+                    .help("Don't ask for confirmation before overwriting existing files (with --update)")
+                )
+                .arg(Arg::new("dry_run")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("dry-run")
+                    .help("Only print the release plan, don't release anything")
+                )
+                .arg(Arg::new("quiet")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("quiet")
+                    .short('q')
+                    .help("Don't print the release plan and the paths to released files after releases are complete")
+                )
+            )
+
+            .subcommand(Command::new("rollback")
+                .about("Remove all artifacts of a release group from the release store")
+                .long_about(indoc::indoc!(r#"
+                    Removes the files and the database entries of all releases that were made together
+                    (with one "release new" or "release submit" call) as a unit.
+                    The UUID of the release group is printed when releasing, groups released with "release submit"
+                    can also be rolled back by their name.
+
+                    A release group that overwrote files of other releases (with --update), or whose files were
+                    overwritten by later releases, cannot be rolled back, because the files of the other releases
+                    would be removed as well.
+                "#))
+                .arg(Arg::new("release_group")
+                    .required(true)
+                    .index(1)
+                    .value_name("RELEASE_GROUP")
+                    .help("The name or UUID of the release group")
+                )
+                .arg(Arg::new("yes")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("yes")
+                    .short('y')
+                    .help("Don't ask for confirmation")
+                )
+            )
+
+            .subcommand(Command::new("sync")
+                .about("Push the changes of a release store to its mirrors")
+                .long_about(indoc::indoc!(r#"
+                    Push the changes of a release store to its remote mirrors with rsync: new and changed
+                    artifacts are transferred and artifacts that were removed from the release store are
+                    deleted on the mirrors.
+
+                    The files that were synced to a mirror are recorded in the database, so that only the
+                    changes since the last sync are transferred. Deleting removed artifacts also deletes
+                    other files on the mirror that do not exist in the release store.
+
+                    The mirrors are configured with "release_mirrors" in the configuration.
+                "#))
+                .arg(Arg::new("release_store_name")
+                    .required(true)
+                    .long("store")
+                    .value_name("RELEASE_STORE_NAME")
+                    .help("Release store name to sync")
+                )
+                .arg(Arg::new("target")
+                    .action(ArgAction::Append)
+                    .required(false)
+                    .long("target")
+                    .value_name("TARGET")
+                    .help("Sync to this rsync destination instead of the configured mirrors (can be passed multiple times)")
+                )
+                .arg(Arg::new("dry_run")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("dry-run")
+                    .help("Only print the files that would be transferred and removed")
+                )
+            )
+
             .subcommand(Command::new("new")
                 .about("Release artifacts")
                 .arg(Arg::new("submit_uuid")
@@ -903,7 +2188,7 @@ pub fn cli() -> Command {
                     .index(1)
                     .value_name("SUBMIT")
                     .help("The submit UUID from which to release a package")
-                    .value_parser(uuid::Uuid::parse_str)
+                    .value_parser(parse_uuid_prefix)
                 )
                 .arg(Arg::new("release_store_name")
                     .required(true)
@@ -921,34 +2206,180 @@ pub fn cli() -> Command {
                     .value_name("PKG")
                     .help("The name of the package (or, if omitted, release all packages of the submit)")
                 )
-                .arg(Arg::new("package_version")
+                .arg(Arg::new("package_version")
+                    .required(false)
+                    .index(3)
+                    .value_name("VERSION")
+                    .help("The exact version of the package (string match)")
+                )
+                .arg(Arg::new("kind")
+                    .action(ArgAction::Append)
+                    .required(false)
+                    .long("kind")
+                    .value_name("KIND")
+                    .help("Only release artifacts of this kind (can be passed multiple times)")
+                    .long_help(indoc::indoc!(r#"
+                        Only release artifacts of this kind. Can be passed multiple times to release artifacts
+                        of several kinds. If omitted, artifacts of all kinds are released.
+
+                        The kind of an artifact is determined by the "artifact_kinds" setting of its package,
+                        which maps glob patterns for the file name of the artifact to kinds.
+                        Artifacts that do not match any pattern are of the kind "default".
+                    "#))
+                )
+                .arg(Arg::new("package_do_update")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("update")
+                    .help("Do update a package if it already exists in the release store")
+                )
+                .arg(Arg::new("yes")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("yes")
+                    .short('y')
+                    .alias("non-interactive")
+                    .help("Don't ask for confirmation before overwriting existing files (with --update)")
+                )
+                .arg(Arg::new("dry_run")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("dry-run")
+                    .help("Only print the release plan, don't release anything")
+                    .long_help(indoc::indoc!(r#"
+                        Only print the release plan, don't release anything.
+
+                        The release plan lists the artifacts that would be released, the jobs that produced
+                        them, the destination paths in the release store and whether existing files would be
+                        overwritten.
+                    "#))
+                )
+                .arg(Arg::new("quiet")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("quiet")
+                    .short('q')
+                    .help("Don't print the release plan and the paths to released files after releases are complete")
+                )
+            )
+
+        )
+
+        .subcommand(Command::new("store")
+            .about("Inspect the staging and release stores")
+            .subcommand(Command::new("stats")
+                .about("Show how much space the released artifacts of each package use")
+                .long_about(indoc::indoc!(r#"
+                    Show the number and accumulated size of the released artifacts per package and
+                    release store, with the share of the release store the package uses.
+                    Packages are sorted by size, the biggest package of each store is at the bottom.
+                "#))
+                .arg(Arg::new("csv")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("csv")
+                    .help("Format output as CSV")
+                )
+                .arg(Arg::new("release_store_name")
+                    .required(false)
+                    .long("store")
+                    .value_name("RELEASE_STORE_NAME")
+                    .help("Only show stats for this release store")
+                )
+            )
+            .subcommand(Command::new("snapshot")
+                .about("Manage frozen snapshots of release stores")
+                .subcommand(Command::new("create")
+                    .about("Record the artifacts that are currently in a release store as a snapshot")
+                    .long_about(indoc::indoc!(r#"
+                        Record the path and the SHA256 hash of every artifact that is currently in the release store
+                        in the database, as a snapshot with the passed name.
+                        Builds can then resolve their dependencies against the snapshot ("build --snapshot") instead of
+                        the current contents of the release stores.
+                    "#))
+                    .arg(Arg::new("snapshot_name")
+                        .required(true)
+                        .index(1)
+                        .value_name("NAME")
+                        .help("The name of the snapshot")
+                    )
+                    .arg(Arg::new("release_store_name")
+                        .required(true)
+                        .long("store")
+                        .value_name("RELEASE_STORE_NAME")
+                        .help("The release store to take the snapshot of")
+                    )
+                )
+                .subcommand(Command::new("list")
+                    .about("List the snapshots")
+                    .arg(Arg::new("csv")
+                        .action(ArgAction::SetTrue)
+                        .required(false)
+                        .long("csv")
+                        .help("Format output as CSV")
+                    )
+                )
+            )
+            .subcommand(Command::new("find")
+                .about("Show which store the artifacts of packages would be reused from")
+                .long_about(indoc::indoc!(r#"
+                    Find the artifacts of the matching packages like a build does when it reuses artifacts and
+                    show every store that contains them, with the SHA256 hash, size and modification time of the
+                    file.
+                    The stores are searched in order: the staging store (if passed) first, then the release stores
+                    in the order of the "artifact_stores" setting (or "--use-store"). The artifact from the first
+                    store is used, the artifact in the other stores is shadowed by it.
+                "#))
+                .arg(Arg::new("package_name_regex")
+                    .required(true)
+                    .index(1)
+                    .value_name("REGEX")
+                    .help("The regex to match the package name against")
+                )
+                .arg(Arg::new("package_version_constraint")
+                    .required(false)
+                    .index(2)
+                    .value_name("VERSION_CONSTRAINT")
+                    .help("A version constraint to match the package version against (optional), e.g., '=1.0.0'")
+                )
+                .arg(Arg::new("no_script_filter")
+                    .action(ArgAction::SetTrue)
+                    .long("no-script-filter")
+                    .short('S')
+                    .required(false)
+                    .help("Don't check for script equality. Can cause inexact results.")
+                )
+                .arg(Arg::new("staging_dir")
                     .required(false)
-                    .index(3)
-                    .value_name("VERSION")
-                    .help("The exact version of the package (string match)")
+                    .long("staging-dir")
+                    .value_name("PATH")
+                    .value_parser(dir_exists_validator)
+                    .help("Also search this staging directory, before the release stores")
                 )
-                .arg(Arg::new("package_do_update")
-                    .action(ArgAction::SetTrue)
+                .arg(arg_use_store())
+                .arg(Arg::new("env_filter")
                     .required(false)
-                    .long("update")
-                    .help("Do update a package if it already exists in the release store")
+                    .action(ArgAction::Append)
+                    .long("env")
+                    .short('E')
+                    .value_name("KV")
+                    .value_parser(env_pass_validator)
+                    .help("Filter for this \"key=value\" environment variable")
                 )
-                .arg(Arg::new("noninteractive")
-                    .action(ArgAction::SetTrue)
+                .arg(Arg::new("image")
                     .required(false)
-                    .long("non-interactive")
-                    .help("Don't be interactive (only with --update at the moment)")
-                    .requires("package_do_update")
+                    .long("image")
+                    .short('I')
+                    .value_name("IMAGE")
+                    .help("Only list artifacts that were built on IMAGE")
                 )
-                .arg(Arg::new("quiet")
+                .arg(Arg::new("csv")
                     .action(ArgAction::SetTrue)
                     .required(false)
-                    .long("quiet")
-                    .short('q')
-                    .help("Don't print the paths to released files after releases are complete")
+                    .long("csv")
+                    .help("Format output as CSV")
                 )
             )
-
         )
 
         .subcommand(Command::new("lint")
@@ -965,6 +2396,17 @@ pub fn cli() -> Command {
                 .value_name("VERSION_CONSTRAINT")
                 .help("A version constraint to match the package version against (optional), e.g., '=1.0.0'")
             )
+            .arg(Arg::new("scripts")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .long("scripts")
+                .help("Check the package scripts with shellcheck instead of the configured linter")
+                .long_help(indoc::indoc!(r#"
+                    Render the script of each package (all phases) and check it with shellcheck (see the
+                    "shellcheck_command" setting). The findings are reported with the phase and the pkg.toml
+                    file (and line) that defines the phase.
+                "#))
+            )
         )
 
         .subcommand(Command::new("tree-of")
@@ -1034,10 +2476,346 @@ pub fn cli() -> Command {
             )
         )
 
+        .subcommand(Command::new("runtime-closure")
+            .about("Print the packages that are required at runtime of a package as JSON")
+            .long_about(indoc::indoc!(r#"
+                Print the runtime closure of a package as JSON: the runtime dependencies of the package and,
+                recursively, their runtime dependencies. Build dependencies and the packages that are only
+                required by build dependencies are not included.
+
+                With --submit, the closure of the requested package of the submit is computed with the image
+                and the environment of the submit, and the artifacts that the submit built (or reused) for
+                every package are included. The packages are loaded from the repository, so the commit of the
+                submit should be checked out.
+            "#))
+            .arg(Arg::new("package_name")
+                .required_unless_present("submit")
+                .index(1)
+                .value_name("NAME")
+                .help("Package name to compute the runtime closure of")
+            )
+            .arg(Arg::new("package_version_constraint")
+                .required(false)
+                .index(2)
+                .value_name("VERSION_CONSTRAINT")
+                .conflicts_with("submit")
+                .help("A version constraint to search for (optional), e.g., '=1.0.0'")
+            )
+            .arg(Arg::new("submit")
+                .required(false)
+                .long("submit")
+                .value_name("SUBMIT")
+                .value_parser(parse_uuid_prefix)
+                .help("Compute the runtime closure of the package of this submit, with its artifacts")
+            )
+            .arg(Arg::new("image")
+                .required(false)
+                .value_name("IMAGE NAME")
+                .short('I')
+                .long("image")
+                .conflicts_with("submit")
+                .help("Name of the Docker image to use")
+                .long_help(indoc::indoc!(r#"
+                    Name of the Docker image to use.
+
+                    Required because tree might look different on different images because of
+                    conditions on dependencies.
+                "#))
+            )
+            .arg(Arg::new("env")
+                .required(false)
+                .action(ArgAction::Append)
+                .short('E')
+                .long("env")
+                .value_parser(env_pass_validator)
+                .conflicts_with("submit")
+                .help("Additional env to be passed when building packages")
+                .long_help(indoc::indoc!(r#"
+                    Additional env to be passed when building packages.
+
+                    Required because tree might look different on different images because of
+                    conditions on dependencies.
+                "#))
+            )
+        )
+
+        .subcommand(Command::new("lock")
+            .about("Handle lockfiles that pin the dependency tree of a package")
+            .subcommand(Command::new("generate")
+                .about("Generate the lockfile of a package")
+                .long_about(indoc::indoc!(r#"
+                    Generate a lockfile (JSON) that pins the dependency tree of a package: the exact version and
+                    the sources (URL and hash) of every package, the dependency edges between the packages and
+                    the digest of the image on the Docker endpoints.
+
+                    "butido build --locked" refuses to build if the dependency tree deviates from the lockfile.
+                "#))
+                .arg(Arg::new("package_name")
+                    .required(true)
+                    .index(1)
+                    .value_name("NAME")
+                    .help("The name of the package to lock")
+                )
+                .arg(Arg::new("package_version_constraint")
+                    .required(false)
+                    .index(2)
+                    .value_name("VERSION_CONSTRAINT")
+                    .help("A version constraint to search for (optional), e.g., '=1.0.0'")
+                )
+                .arg(Arg::new("image")
+                    .required(true)
+                    .value_name("IMAGE NAME")
+                    .short('I')
+                    .long("image")
+                    .help("Name of the Docker image to lock")
+                    .long_help(indoc::indoc!(r#"
+                        Name of the Docker image to lock.
+
+                        Required because tree might look different on different images because of
+                        conditions on dependencies.
+                    "#))
+                )
+                .arg(Arg::new("env")
+                    .required(false)
+                    .action(ArgAction::Append)
+                    .short('E')
+                    .long("env")
+                    .value_parser(env_pass_validator)
+                    .help("Additional env to be passed when building packages")
+                    .long_help(indoc::indoc!(r#"
+                        Additional env to be passed when building packages.
+
+                        Required because tree might look different on different images because of
+                        conditions on dependencies.
+                    "#))
+                )
+                .arg(Arg::new("output")
+                    .required(false)
+                    .short('o')
+                    .long("output")
+                    .value_name("LOCKFILE")
+                    .default_value(crate::util::lockfile::DEFAULT_LOCKFILE_PATH)
+                    .help("The file to write the lockfile to")
+                )
+            )
+        )
+
+        .subcommand(Command::new("why")
+            .about("Explain why a package is in the dependency tree of another package")
+            .long_about(indoc::indoc!(r#"
+                Print every chain of dependencies from the root package to the package. Every hop shows
+                whether it is a build or a runtime dependency and the dependency (e.g., "foo =1.0") that
+                selected the version of the package. A chain is a runtime dependency of the root package if
+                every hop of it is a runtime dependency.
+            "#))
+            .arg(Arg::new("package_name")
+                .required(true)
+                .index(1)
+                .value_name("NAME")
+                .help("The name of the package to explain")
+            )
+            .arg(Arg::new("in_tree_of")
+                .required(true)
+                .long("in-tree-of")
+                .value_name("ROOT")
+                .help("The name of the root package of the dependency tree")
+            )
+            .arg(Arg::new("root_version_constraint")
+                .required(false)
+                .long("root-version")
+                .value_name("VERSION_CONSTRAINT")
+                .help("A version constraint for the root package (optional), e.g., '=1.0.0'")
+            )
+            .arg(Arg::new("image")
+                .required(false)
+                .value_name("IMAGE NAME")
+                .short('I')
+                .long("image")
+                .help("Name of the Docker image to use")
+                .long_help(indoc::indoc!(r#"
+                    Name of the Docker image to use.
+
+                    Required because tree might look different on different images because of
+                    conditions on dependencies.
+                "#))
+            )
+            .arg(Arg::new("env")
+                .required(false)
+                .action(ArgAction::Append)
+                .short('E')
+                .long("env")
+                .value_parser(env_pass_validator)
+                .help("Additional env to be passed when building packages")
+                .long_help(indoc::indoc!(r#"
+                    Additional env to be passed when building packages.
+
+                    Required because tree might look different on different images because of
+                    conditions on dependencies.
+                "#))
+            )
+            .arg(Arg::new("limit")
+                .required(false)
+                .long("limit")
+                .value_name("N")
+                .default_value("20")
+                .value_parser(clap::value_parser!(usize))
+                .help("Print at most N chains per root package (0 prints all)")
+            )
+        )
+
         .subcommand(Command::new("metrics")
             .about("Print metrics about butido")
         )
 
+        .subcommand(Command::new("config")
+            .about("Inspect and modify the configuration")
+            .subcommand(Command::new("show")
+                .about("Show where the configuration is loaded from, or the merged configuration")
+                .long_about(indoc::indoc!(r#"
+                    The configuration is merged from these sources, where later sources override earlier ones:
+
+                    1. The system configuration: /etc/butido/config.toml (optional)
+                    2. The repository configuration: config.toml in the top-level of the repository
+                    3. The user configuration: config.toml in the XDG configuration directory, usually
+                       ~/.config/butido/config.toml (optional)
+                    4. The profile that is selected with --profile (optional)
+                    5. Environment variables with the prefix BUTIDO_ (e.g., BUTIDO_DATABASE_PASSWORD)
+
+                    Without flags, the sources are listed in this order.
+                "#))
+                .arg(Arg::new("effective")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("effective")
+                    .help("Print the merged configuration with the source of each value")
+                )
+                .arg(Arg::new("show_secrets")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("show-secrets")
+                    .requires("effective")
+                    .help("Do not mask passwords and tokens")
+                )
+            )
+            .subcommand(Command::new("get")
+                .about("Print a value of the merged configuration")
+                .long_about(indoc::indoc!(r#"
+                    Print a value of the merged configuration (see "config show"). If the key refers to a table or an
+                    array, all values in it are printed with their keys.
+                "#))
+                .arg(Arg::new("key")
+                    .required(true)
+                    .index(1)
+                    .value_name("KEY")
+                    .help("The dotted key of the value (e.g., docker.endpoints.builder1.maxjobs)")
+                )
+            )
+            .subcommand(Command::new("set")
+                .about("Set a value in a configuration file")
+                .long_about(indoc::indoc!(r#"
+                    Set a value in a configuration file (by default the configuration file of the repository).
+                    Formatting and comments in the file are kept. Missing tables are created.
+
+                    The value is parsed as TOML value (e.g., 4, true, "text" or [1, 2]). Values that are not valid
+                    TOML are used as strings, so paths and URLs do not have to be quoted.
+
+                    Example:
+
+                        butido config set docker.endpoints.builder1.maxjobs 4
+                "#))
+                .arg(Arg::new("key")
+                    .required(true)
+                    .index(1)
+                    .value_name("KEY")
+                    .help("The dotted key of the value (e.g., docker.endpoints.builder1.maxjobs)")
+                )
+                .arg(Arg::new("value")
+                    .required(true)
+                    .index(2)
+                    .value_name("VALUE")
+                    .help("The new value")
+                )
+                .arg(Arg::new("file")
+                    .required(false)
+                    .long("file")
+                    .value_name("PATH")
+                    .value_parser(clap::value_parser!(PathBuf))
+                    .help("The configuration file to modify (default: config.toml in the repository)")
+                )
+            )
+        )
+
+        .subcommand(Command::new("validate-config")
+            .about("Check the configuration and report all problems")
+            .long_about(indoc::indoc!(r#"
+                Check the configuration files (see "config show") and report all problems that are found: syntax
+                errors, unknown keys and wrong types (with file, line and column), missing required settings,
+                directories that do not exist, invalid image names and other invalid settings.
+            "#))
+            .arg(Arg::new("connect")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .long("connect")
+                .help("Also check whether the configured endpoints can be connected to")
+                .long_help(indoc::indoc!(r#"
+                    Also connect to each configured endpoint and check its Docker version and the availability of
+                    the configured images.
+                "#))
+            )
+        )
+
+        .subcommand(Command::new("doctor")
+            .about("Check the environment that butido needs and print hints to fix the problems")
+            .long_about(indoc::indoc!(r#"
+                Check everything butido needs to work: the configuration, the connection to the database and
+                whether all migrations were run, the connection to the (Docker) endpoints with their (API)
+                versions and images, whether the staging directory, the source cache, the log directory and
+                the release stores are writable and whether the external programs that are needed with the
+                configuration (hooks, metadata generators, kubectl, rsync, ...) can be found.
+
+                All checks are run (unless the configuration is invalid) and a hint how to fix the problem is
+                printed for every failed check. The exit code is non-zero if any check failed, checks that
+                only affect rarely used commands (e.g. "release sync") produce warnings.
+            "#))
+        )
+
+        .subcommand(Command::new("cache")
+            .about("Compiler cache maintenance commands")
+            .long_about(indoc::indoc!(r#"
+                Maintain the compiler caches (ccache or sccache) of the endpoints (see the "compiler_cache"
+                setting of the endpoints). The commands are run in a helper container that is created from
+                IMAGE, so the image must contain the compiler cache tool.
+            "#))
+            .arg(Arg::new("endpoint_name")
+                .required(false)
+                .long("endpoint")
+                .short('e')
+                .value_name("ENDPOINT_NAME")
+                .help("Only use the cache of this endpoint")
+            )
+            .arg(Arg::new("image")
+                .required(true)
+                .long("image")
+                .short('I')
+                .value_name("IMAGE NAME")
+                .help("The image to run the compiler cache tool in")
+            )
+
+            .subcommand(Command::new("stats")
+                .about("Show the statistics (e.g., hit rate) of the compiler caches")
+            )
+            .subcommand(Command::new("clear")
+                .about("Remove everything from the compiler caches")
+                .arg(Arg::new("yes")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .long("yes")
+                    .short('y')
+                    .help("Don't ask for confirmation")
+                )
+            )
+        )
+
         .subcommand(Command::new("endpoint")
             .about("Endpoint maintenance commands")
             .arg(Arg::new("endpoint_name")
@@ -1075,6 +2853,41 @@ pub fn cli() -> Command {
                     .long("csv")
                     .help("Format output as CSV")
                 )
+                .arg(Arg::new("watch")
+                    .required(false)
+                    .long("watch")
+                    .short('w')
+                    .value_name("SECONDS")
+                    .num_args(0..=1)
+                    .default_missing_value("5")
+                    .value_parser(clap::value_parser!(u64).range(1..))
+                    .help("Re-render the stats periodically (every 5 seconds if no interval is given)")
+                    .long_help(indoc::indoc!(r#"
+                        Clear the screen and re-render the stats table periodically, until butido is interrupted.
+                        The optional value is the interval in seconds (defaults to 5).
+
+                        The "Running" column shows the number of running butido containers on the endpoint.
+                        The "Load" column shows the load average of the endpoint host, which is read from one
+                        of the running butido containers. It is "-" if there is no running butido container.
+                    "#))
+                )
+                .arg(Arg::new("threshold")
+                    .required(false)
+                    .long("threshold")
+                    .value_name("PERCENT")
+                    .conflicts_with("watch")
+                    .value_parser(clap::value_parser!(f64))
+                    .help("Exit with an error if the disk usage of any endpoint is above PERCENT")
+                    .long_help(indoc::indoc!(r#"
+                        Exit with an error if the disk usage of any endpoint is above PERCENT (or cannot be
+                        determined), e.g. to monitor the endpoints with a cron job.
+                        The stats are printed anyway.
+
+                        The disk usage ("Disk usage" column) is the size of the docker data root (images,
+                        containers, volumes and build cache, as reported by "docker system df") relative to
+                        the size of the data root plus the available space on its file system.
+                    "#))
+                )
             )
             .subcommand(Command::new("containers")
                 .about("Work with the containers of the endpoint(s)")
@@ -1082,6 +2895,19 @@ pub fn cli() -> Command {
                     .about("Remove exited containers")
                     .arg(arg_older_than_date("Prune only containers older than DATE"))
                     .arg(arg_newer_than_date("Prune only containers newer than DATE"))
+                    .arg(Arg::new("policy")
+                        .action(ArgAction::SetTrue)
+                        .required(false)
+                        .long("policy")
+                        .help("Remove the containers of finished jobs according to the configured retention")
+                        .long_help(indoc::indoc!(r#"
+                            Remove the containers of finished jobs according to "containers.retention" in the
+                            configuration, instead of all exited containers: The containers of successful jobs
+                            are removed if "remove_successful" is set, the containers of failed jobs are removed
+                            when they are older than "keep_failed_hours".
+                            Containers of jobs that are not recorded in the database (yet) are never removed.
+                        "#))
+                    )
                 )
                 .subcommand(Command::new("stop")
                     .about("Stop running containers")
@@ -1201,7 +3027,29 @@ pub fn cli() -> Command {
 
                 .subcommand(Command::new("inspect")
                     .about("Display details about the container")
-                    .long_about("Display details about the container. Do not assume the output format to be stable.")
+                    .long_about(indoc::indoc!(r#"
+                        Display details about the container. Do not assume the output format to be stable.
+
+                        Besides the details from Docker, the job, package and submit the container belongs to are
+                        shown (from the labels of the container and the DB).
+                    "#))
+                )
+                .subcommand(Command::new("export")
+                    .about("Export the file system of the container as a TAR archive")
+                    .long_about(indoc::indoc!(r#"
+                        Export the file system of the container (e.g. the build directory of a failed job) as a
+                        TAR archive, so that it can be analyzed after the container was pruned.
+                        The archive is compressed with zstd if the file name ends with ".zst".
+                        Volumes and mounts of the container are not exported.
+                    "#))
+                    .arg(Arg::new("output")
+                        .required(true)
+                        .long("output")
+                        .short('o')
+                        .value_name("FILE")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .help("Write the archive to FILE (e.g. failed.tar.zst)")
+                    )
                 )
             )
             .subcommand(Command::new("images")
@@ -1295,6 +3143,19 @@ fn env_pass_validator(s: &str) -> Result<String, String> {
     }
 }
 
+fn source_name_validator(s: &str) -> Result<String, String> {
+    if !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(s.to_owned())
+    } else {
+        Err(format!(
+            "Invalid source name '{s}', only letters, digits, '-' and '_' are allowed"
+        ))
+    }
+}
+
 fn dir_exists_validator(s: &str) -> Result<String, String> {
     if PathBuf::from(&s).is_dir() {
         Ok(s.to_owned())
@@ -1303,6 +3164,48 @@ fn dir_exists_validator(s: &str) -> Result<String, String> {
     }
 }
 
+fn arg_offset(what: &str) -> Arg {
+    Arg::new("offset")
+        .required(false)
+        .long("offset")
+        .value_name("OFFSET")
+        .help(format!("Skip the OFFSET newest {what}"))
+        .value_parser(clap::value_parser!(usize))
+}
+
+fn arg_page(what: &str) -> Arg {
+    Arg::new("page")
+        .required(false)
+        .long("page")
+        .value_name("PAGE")
+        .conflicts_with("offset")
+        .help(format!(
+            "List the PAGE-th page of LIMIT {what} (starting at 1)"
+        ))
+        .long_help(format!(
+            "List the PAGE-th page of {what}, the first page contains the LIMIT newest {what}. \
+             This is the same as --offset (PAGE - 1) * LIMIT."
+        ))
+        .value_parser(clap::value_parser!(u64).range(1..))
+}
+
+fn arg_use_store() -> Arg {
+    Arg::new("use_store")
+        .required(false)
+        .action(ArgAction::Append)
+        .long("use-store")
+        .value_name("STORE")
+        .help("Only reuse artifacts from this store (can be passed multiple times)")
+        .long_help(indoc::indoc!(r#"
+            Only reuse artifacts from the passed stores, searched in the order in which they are passed
+            (instead of the stores of the "artifact_stores" setting). STORE is the name of a release store
+            or "staging" for the staging store, which must be passed first (artifacts from the staging
+            directory are not used if the staging store is not passed).
+            E.g., "--use-store staging --use-store prod-2023" only reuses artifacts from the staging store
+            and the "prod-2023" release store, to build against a frozen store for a maintenance branch.
+        "#))
+}
+
 fn arg_older_than_date(about: &str) -> Arg {
     Arg::new("older_than")
         .required(false)
@@ -1363,6 +3266,20 @@ fn arg_newer_than_date(about: &str) -> Arg {
         .value_parser(parse_date_from_string)
 }
 
+/// A full UUID or a prefix of at least 4 characters of it (resolved against the database later)
+fn parse_uuid_prefix(s: &str) -> std::result::Result<String, String> {
+    if uuid::Uuid::parse_str(s).is_ok() {
+        return Ok(s.to_owned());
+    }
+
+    if s.len() < 4 || !s.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+        return Err(format!(
+            "Not a UUID or a UUID prefix of at least 4 characters: {s}"
+        ));
+    }
+    Ok(s.to_owned())
+}
+
 fn parse_date_from_string(s: &str) -> std::result::Result<String, String> {
     humantime::parse_duration(s)
         .map_err(|e| e.to_string())
@@ -1384,6 +3301,15 @@ fn parse_date_from_string(s: &str) -> std::result::Result<String, String> {
 #[cfg(test)]
 mod tests {
     use super::env_pass_validator;
+    use super::parse_uuid_prefix;
+
+    #[test]
+    fn test_parse_uuid_prefix() {
+        assert!(parse_uuid_prefix("1a2b3c4d-5e6f-4a7b-8c9d-0e1f2a3b4c5d").is_ok());
+        assert!(parse_uuid_prefix("1a2b3c4d-5e").is_ok());
+        assert!(parse_uuid_prefix("1a2").is_err());
+        assert!(parse_uuid_prefix("1a2b%").is_err());
+    }
 
     #[test]
     fn test_env_pass_validator_1() {
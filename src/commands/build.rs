@@ -10,6 +10,7 @@
 
 //! Implementation of the 'build' subcommand
 
+use std::collections::HashMap;
 use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
@@ -25,6 +26,7 @@ use colored::Colorize;
 use diesel::r2d2::ConnectionManager;
 use diesel::r2d2::Pool;
 use diesel::ExpressionMethods;
+use diesel::OptionalExtension;
 use diesel::PgConnection;
 use diesel::QueryDsl;
 use diesel::RunQueryDsl;
@@ -36,23 +38,37 @@ use tracing::{debug, info, trace, warn};
 use uuid::Uuid;
 
 use crate::config::*;
-use crate::db::models::{EnvVar, GitHash, Image, Job, Package, Submit};
+use crate::db::models::{
+    EnvVar, GitHash, Image, Job, Package, QueuedJob, QueuedSubmit, Submit, SubmitState,
+};
+use crate::db::ReuseArtifacts;
+use crate::db::SubmitLocks;
+use crate::endpoint::LocalExecutor;
+use crate::endpoint::LocalIsolation;
 use crate::filestore::path::StoreRoot;
-use crate::filestore::ReleaseStore;
 use crate::filestore::StagingStore;
 use crate::job::JobResource;
+use crate::job::SubmitEstimate;
 use crate::log::LogItem;
 use crate::orchestrator::OrchestratorSetup;
 use crate::package::condition::ConditionData;
 use crate::package::Dag;
 use crate::package::PackageName;
 use crate::package::PackageVersion;
+use crate::package::PhaseModifications;
 use crate::package::Shebang;
 use crate::repository::Repository;
 use crate::schema;
 use crate::source::SourceCache;
+use crate::util::disk_space::free_space;
+use crate::util::disk_space::DiskSpaceVerdict;
+use crate::util::docker::ImageName;
 use crate::util::docker::ImageNameLookup;
+use crate::util::error::ErrorCategory;
+use crate::util::junit::JunitReport;
+use crate::util::lockfile::Lockfile;
 use crate::util::progress::ProgressBars;
+use crate::util::progress::StatusLineFormat;
 use crate::util::EnvironmentVariableName;
 
 /// Implementation of the "build" subcommand
@@ -68,6 +84,13 @@ pub async fn build(
 ) -> Result<()> {
     let command_span = tracing::debug_span!("command-build");
 
+    let progressbars = match matches.get_one::<String>("log_format").map(String::as_str) {
+        Some("plain") => progressbars.with_status_lines(StatusLineFormat::Plain),
+        Some("json") => progressbars.with_status_lines(StatusLineFormat::Json),
+        Some(other) => return Err(anyhow!("Unknown log format: {}", other)),
+        None => progressbars,
+    };
+
     let loading_span = tracing::debug_span!(parent: &command_span, "loading");
     // There's no async code for a long time in this function, so this is safe.
     let loading_span_guard = loading_span.enter();
@@ -95,14 +118,31 @@ pub async fn build(
     trace!("Repository HEAD = {}", hash_str);
     let phases = config.available_phases();
 
-    let mut endpoint_configurations = config
-        .docker()
-        .endpoints()
+    let local = if matches.get_flag("local_exec") {
+        Some(LocalExecutor::new(LocalIsolation::None)?)
+    } else if matches.get_flag("local_chroot") {
+        Some(LocalExecutor::new(LocalIsolation::Bubblewrap)?)
+    } else {
+        None
+    };
+
+    let endpoints = if local.is_some() {
+        Vec::new()
+    } else {
+        let selectors = matches
+            .get_many::<String>("endpoint")
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect::<Vec<_>>();
+        selected_endpoints(config.docker().endpoints(), &selectors)?
+    };
+    let mut endpoint_configurations = endpoints
         .iter()
         .map(|(ep_name, ep_cfg)| {
             crate::endpoint::EndpointConfiguration::builder()
-                .endpoint_name(ep_name.clone())
-                .endpoint(ep_cfg.clone())
+                .endpoint_name((*ep_name).clone())
+                .endpoint((*ep_cfg).clone())
                 .required_images(
                     config
                         .docker()
@@ -137,11 +177,26 @@ pub async fn build(
         .map(PackageVersion::from);
     info!("We want {} ({:?})", pname, pvers);
 
-    let additional_env = matches
-        .get_many::<String>("env")
-        .unwrap_or_default()
-        .map(|s| crate::util::env::parse_to_env(s.as_ref()))
-        .collect::<Result<Vec<(EnvironmentVariableName, String)>>>()?;
+    let additional_env = {
+        let from_files = matches
+            .get_many::<String>("env_file")
+            .unwrap_or_default()
+            .map(|file| crate::util::env::read_env_file(Path::new(file)))
+            .collect::<Result<Vec<_>>>()?;
+        let from_args = matches
+            .get_many::<String>("env")
+            .unwrap_or_default()
+            .map(|s| crate::util::env::parse_to_env(s.as_ref()))
+            .collect::<Result<Vec<(EnvironmentVariableName, String)>>>()?;
+
+        // Later variables take precedence over earlier ones with the same name
+        let mut additional_env = Vec::<(EnvironmentVariableName, String)>::new();
+        for (name, value) in from_files.into_iter().flatten().chain(from_args) {
+            additional_env.retain(|(n, _)| *n != name);
+            additional_env.push((name, value));
+        }
+        additional_env
+    };
 
     let packages = if let Some(pvers) = pvers {
         debug!(
@@ -161,36 +216,101 @@ pub async fn build(
         return Err(anyhow!(
             "Found multiple packages ({}). Cannot decide which one to build",
             packages.len()
-        ));
+        ))
+        .context(ErrorCategory::Resolution);
     }
     let package = *packages
         .first()
-        .ok_or_else(|| anyhow!("Found no package."))?;
+        .ok_or_else(|| anyhow!("Found no package."))
+        .context(ErrorCategory::Resolution)?;
 
-    let release_stores = config
-        .release_stores()
-        .iter()
-        .map(|storename| {
-            let bar_release_loading = progressbars.bar()?;
-
-            let p = config.releases_directory().join(storename);
-            let p_str = p.to_string_lossy();
-            debug!("Loading release directory: {}", p_str);
-            let r = ReleaseStore::load(StoreRoot::new(p.clone())?, &bar_release_loading);
-            if r.is_ok() {
-                bar_release_loading
-                    .finish_with_message(format!("Loaded releases in {p_str} successfully"));
-            } else {
-                bar_release_loading
-                    .finish_with_message(format!("Failed to load releases in {p_str}"));
-            }
-            r.map(Arc::new)
-        })
-        .collect::<Result<Vec<_>>>()?;
+    let phase_modifications = PhaseModifications::new(
+        matches.get_many::<String>("skip_phase").unwrap_or_default(),
+        matches
+            .get_many::<String>("override_phase")
+            .unwrap_or_default(),
+        phases,
+    )?;
+
+    if matches.get_flag("enqueue") {
+        return enqueue(matches, &database_pool, &hash_str, package);
+    }
+
+    let dag = {
+        let bar_tree_building = progressbars.bar()?;
+        let condition_data = ConditionData {
+            image_name: Some(&image_name),
+            env: &additional_env,
+        };
+
+        let mut package = package.clone();
+        package.modify_phases(&phase_modifications);
+
+        let dag = Dag::for_root_package(package, &repo, Some(&bar_tree_building), &condition_data)
+            .context(ErrorCategory::Resolution)?;
+        bar_tree_building.finish_with_message("Finished loading Dag");
+        dag
+    };
+
+    if let Some(lockfile_path) = matches.get_one::<String>("locked") {
+        check_lockfile(
+            config,
+            Path::new(lockfile_path),
+            &dag,
+            &image_name,
+            &endpoints,
+        )
+        .await?;
+    }
+
+    if matches.get_flag("print_schedule") {
+        let image_name_short = image_name_lookup.shorten(image_name.as_ref());
+        let header = crate::commands::util::mk_header(vec!["Wave", "Package", "Version", "Image"]);
+        let data = dag
+            .waves()
+            .into_iter()
+            .enumerate()
+            .flat_map(|(i, wave)| {
+                let image_name_short = &image_name_short;
+                wave.into_iter().map(move |p| {
+                    vec![
+                        (i + 1).to_string(),
+                        p.name().to_string(),
+                        p.version().to_string(),
+                        image_name_short.clone(),
+                    ]
+                })
+            })
+            .collect::<Vec<_>>();
+        return crate::commands::util::display_data(header, data, false);
+    }
+
+    crate::util::hooks::run_pre_submit_hooks(config.pre_submit_hooks(), &dag.all_packages())
+        .await
+        .context("Checking the packages of the submit")?;
+
+    let store_selection = crate::commands::util::store_selection(config, matches)?;
+    let release_stores = if let Some(snapshot_name) = matches.get_one::<String>("snapshot") {
+        info!(
+            parent: &loading_span,
+            "Resolving dependencies against snapshot {}", snapshot_name
+        );
+        vec![
+            crate::commands::util::load_release_store_snapshot(
+                config,
+                &progressbars,
+                &mut database_pool.get()?,
+                snapshot_name,
+            )
+            .await?,
+        ]
+    } else {
+        crate::commands::util::load_release_stores(config, &progressbars, &store_selection)?
+    };
 
     drop(loading_span_guard);
 
-    let (staging_store, staging_dir, submit_id) = {
+    let (staging_store, staging_dir, submit_id, submit_locks) = {
         let bar_staging_loading = progressbars.bar()?;
 
         let (submit_id, p) = if let Some(staging_dir) =
@@ -215,14 +335,34 @@ pub async fn build(
 
             (uuid, staging_dir)
         } else {
-            let submit_id = uuid::Uuid::new_v4();
-            let staging_dir = config
-                .staging_directory()
-                .join(submit_id.hyphenated().to_string());
+            let submit_id = matches
+                .get_one::<Uuid>("submit_uuid")
+                .copied()
+                .unwrap_or_else(uuid::Uuid::new_v4);
+            // The artifacts of local builds are kept apart, so that they cannot be released
+            let staging_root = if local.is_some() {
+                config
+                    .staging_directory()
+                    .join(crate::consts::LOCAL_STAGING_DIR_NAME)
+            } else {
+                config.staging_directory().clone()
+            };
+            let staging_dir = staging_root.join(submit_id.hyphenated().to_string());
 
             (submit_id, staging_dir)
         };
 
+        // Conflicting submits must not use the same staging directory at the same time, the
+        // packages are locked by the jobs that build them (see `SubmitLocks`)
+        let locks = SubmitLocks::acquire(
+            &database_pool,
+            &submit_id,
+            vec![format!("staging directory {}", p.display())],
+            matches.get_flag("wait_for_locks"),
+        )
+        .await
+        .map(Arc::new)?;
+
         if !p.is_dir() {
             tokio::fs::create_dir_all(&p)
                 .instrument(
@@ -240,24 +380,7 @@ pub async fn build(
         }
         r.map(RwLock::new)
             .map(Arc::new)
-            .map(|store| (store, p, submit_id))?
-    };
-
-    let dag = {
-        let bar_tree_building = progressbars.bar()?;
-        let condition_data = ConditionData {
-            image_name: Some(&image_name),
-            env: &additional_env,
-        };
-
-        let dag = Dag::for_root_package(
-            package.clone(),
-            &repo,
-            Some(&bar_tree_building),
-            &condition_data,
-        )?;
-        bar_tree_building.finish_with_message("Finished loading Dag");
-        dag
+            .map(|store| (store, p, submit_id, locks))?
     };
 
     let source_cache = SourceCache::new(config.source_cache_root().clone());
@@ -289,6 +412,26 @@ pub async fn build(
         warn!(parent: &loading_span, "No linter set in configuration, no script linting will be performed!");
     } // linting
 
+    let image = config
+        .docker()
+        .images()
+        .iter()
+        .find(|image| image.name == image_name);
+    let image_endpoint_tags = image
+        .map(|image| image.endpoint_tags.as_slice())
+        .unwrap_or_default();
+    // Jobs that run on the host are built for the architecture of the host
+    let archs = if local.is_some() {
+        vec![None]
+    } else {
+        build_archs(
+            matches,
+            image
+                .map(|image| image.archs.as_slice())
+                .unwrap_or_default(),
+            package,
+        )?
+    };
     dag.all_packages()
         .into_iter()
         .map(|pkg| {
@@ -314,10 +457,80 @@ pub async fn build(
                 }
             }
 
+            for arch in archs.iter().flatten() {
+                if pkg
+                    .archs()
+                    .as_ref()
+                    .is_some_and(|supported| !supported.contains(arch))
+                {
+                    return Err(anyhow!(
+                        "Package {} {} cannot be built for {} (supported architectures: {})",
+                        pkg.name(),
+                        pkg.version(),
+                        arch,
+                        pkg.archs().iter().flatten().join(", ")
+                    ));
+                }
+            }
+
+            // Jobs that run on the host are not scheduled on endpoints
+            let required_tags = pkg
+                .endpoint_tags()
+                .iter()
+                .flatten()
+                .chain(image_endpoint_tags)
+                .cloned()
+                .collect::<Vec<_>>();
+            if local.is_some() {
+                return Ok(());
+            }
+            for arch in archs.iter() {
+                let suitable_endpoint = endpoints.iter().any(|(_, ep)| {
+                    has_endpoint_tags(ep.tags(), &required_tags)
+                        && (arch.is_none() || ep.arch() == arch)
+                });
+                if !suitable_endpoint {
+                    return Err(anyhow!(
+                        "Package {} {} requires an endpoint with the tags [{}]{}, but none of the endpoints has them: {}",
+                        pkg.name(),
+                        pkg.version(),
+                        required_tags.iter().unique().join(", "),
+                        arch.as_ref()
+                            .map(|arch| format!(" and the architecture {arch}"))
+                            .unwrap_or_default(),
+                        endpoints
+                            .iter()
+                            .map(|(name, ep)| {
+                                format!(
+                                    "{} [{}] {}",
+                                    name,
+                                    ep.tags().join(", "),
+                                    ep.arch().as_deref().unwrap_or("(no architecture)")
+                                )
+                            })
+                            .join(", ")
+                    ));
+                }
+            }
+
             Ok(())
         })
         .collect::<Result<Vec<()>>>()?;
 
+    check_disk_space(
+        config,
+        &database_pool,
+        &dag.all_packages(),
+        &staging_dir,
+        &endpoints
+            .iter()
+            .map(|(ep_name, _)| (*ep_name).clone())
+            .collect::<Vec<_>>(),
+        matches.get_flag("ignore_disk_space"),
+    )
+    .instrument(tracing::trace_span!(parent: &loading_span, "check disk space"))
+    .await?;
+
     drop(loading_span);
     let submit_span = tracing::debug_span!(parent: &command_span, "submit");
 
@@ -355,6 +568,7 @@ pub async fn build(
         &db_image,
         &db_package,
         &db_githash,
+        &phase_modifications.describe(),
     )?;
     trace!(
         parent: &submit_span,
@@ -381,26 +595,80 @@ pub async fn build(
             v = mkgreen(&db_package.version)
         )?;
         writeln!(outlock, "On repo hash:    {}", mkgreen(&db_githash.hash))?;
+        if archs.iter().any(Option::is_some) {
+            writeln!(
+                outlock,
+                "For archs:       {}",
+                mkgreen(&archs.iter().flatten().join(", "))
+            )?;
+        }
+        if local.is_some() {
+            writeln!(
+                outlock,
+                "{}",
+                "Running on the host, the artifacts cannot be released".yellow()
+            )?;
+        }
+        if !phase_modifications.is_empty() {
+            writeln!(
+                outlock,
+                "Modified phases: {}",
+                phase_modifications.describe().join(", ").yellow()
+            )?;
+        }
     }
 
     trace!(parent: &submit_span, "Setting up job sets");
     let resources: Vec<JobResource> = additional_env.into_iter().map(JobResource::from).collect();
-    let jobdag =
-        crate::job::Dag::from_package_dag(dag, shebang, image_name, phases.clone(), resources);
+    let jobdag = crate::job::Dag::from_package_dag(
+        dag,
+        shebang,
+        image_name,
+        phases.clone(),
+        resources,
+        &archs,
+    );
     trace!(parent: &submit_span, "Setting up job sets finished successfully");
+    let jobs = jobdag
+        .iter()
+        .map(|jobdef| (*jobdef.job.uuid(), jobdef.job.package().clone()))
+        .collect::<Vec<_>>();
+
+    let parallelism = if local.is_some() {
+        1 // see LocalExecutor
+    } else {
+        endpoints.iter().map(|(_, ep)| ep.maxjobs()).sum()
+    };
+    let estimate = SubmitEstimate::load(&mut database_pool.get().unwrap(), &jobdag, parallelism)?;
+    if let Some(estimate) = estimate.as_ref() {
+        writeln!(
+            std::io::stdout(),
+            "Estimated duration: {} ({} jobs on {} slots)",
+            crate::job::format_estimate(estimate.remaining()),
+            jobs.len(),
+            parallelism
+        )?;
+    }
     drop(submit_span);
 
-    let build_span = tracing::debug_span!(parent: &command_span, "build");
+    // Carries the submit UUID to all diagnostics of the build, hence enabled on every level
+    let submit_context = tracing::error_span!(
+        parent: &command_span,
+        "submit_context",
+        submit_uuid = %submit.uuid
+    );
+    let build_span = tracing::debug_span!(parent: &submit_context, "build");
 
     trace!(parent: &build_span, "Setting up Orchestrator");
     let orch = OrchestratorSetup::builder()
-        .progress_generator(progressbars)
+        .progress_generator(progressbars.clone())
         .endpoint_config(endpoint_configurations)
         .staging_store(staging_store)
         .release_stores(release_stores)
         .database(database_pool.clone())
         .source_cache(source_cache)
-        .submit(submit)
+        .submit(submit.clone())
+        .submit_locks(submit_locks.clone())
         .log_dir(if matches.get_flag("write-log-file") {
             Some(config.log_dir().clone())
         } else {
@@ -408,15 +676,105 @@ pub async fn build(
         })
         .jobdag(jobdag)
         .config(config)
+        .no_cache(matches.get_flag("no_cache"))
+        .reuse_staging_artifacts(store_selection.staging())
+        .reuse_artifacts(
+            matches
+                .get_one::<String>("reuse_artifacts")
+                .unwrap() // safe by clap default
+                .parse::<ReuseArtifacts>()?,
+        )
+        .priority(*matches.get_one::<i32>("priority").unwrap()) // safe by clap
         .repository(git_repo)
+        .estimate(estimate)
+        .local(local)
         .build()
         .setup()
         .instrument(build_span.clone())
-        .await?;
+        .instrument(submit_context.clone())
+        .await;
+    if orch.is_err() {
+        submit.set_state(&mut database_pool.get().unwrap(), SubmitState::Aborted)?;
+    }
+    // Setting up the orchestrator fails if the endpoints cannot be used
+    let orch = orch.context(ErrorCategory::Infrastructure)?;
 
     info!(parent: &build_span, "Running orchestrator...");
+    submit.set_state(&mut database_pool.get().unwrap(), SubmitState::Running)?;
+    let build_started = std::time::Instant::now();
     let mut artifacts = vec![];
-    let errors = orch.run(&mut artifacts).instrument(build_span).await?;
+    let errors = orch
+        .run(&mut artifacts)
+        .instrument(build_span)
+        .instrument(submit_context)
+        .await;
+    QueuedJob::remove_all_of_submit(&mut *database_pool.get().unwrap(), &submit)?;
+    let final_state = if errors.is_ok() {
+        SubmitState::Finished
+    } else {
+        SubmitState::Aborted
+    };
+    submit.set_state(&mut database_pool.get().unwrap(), final_state)?;
+
+    // The summary is also written if the build could not be run, the error of the build takes
+    // precedence over an error writing the summary though
+    let summary_written = matches
+        .get_one::<PathBuf>("summary_out")
+        .map(|path| {
+            let summary = BuildSummary::new(
+                &mut database_pool.get().unwrap(),
+                &submit,
+                build_started,
+                &jobs,
+                errors.as_ref(),
+                &artifacts,
+                &staging_dir,
+            )?;
+            summary.write_to_file(path)
+        })
+        .unwrap_or(Ok(()));
+    let errors = errors?;
+    summary_written?;
+    progressbars.summary(
+        build_started,
+        format!(
+            "Build of {} jobs finished ({} failed), {} artifacts staged",
+            jobs.len(),
+            errors.len(),
+            artifacts.len()
+        ),
+    )?;
+
+    if let Some(path) = matches.get_one::<PathBuf>("junit_out") {
+        let mut report = JunitReport::for_submit(
+            &mut database_pool.get().unwrap(),
+            &submit,
+            *config.build_error_lines(),
+        )?;
+        for (uuid, package) in jobs.iter() {
+            if let Some(error) = errors.get(uuid).filter(|_| !report.contains_job(uuid)) {
+                report.add_unrecorded_failure(
+                    uuid,
+                    package.name().as_ref(),
+                    package.version().as_ref(),
+                    error,
+                );
+            }
+        }
+        report.write_to_file(path)?;
+    }
+
+    let error_category = if errors.is_empty() {
+        None
+    } else {
+        Some(build_error_category(
+            &mut database_pool.get().unwrap(),
+            config,
+            &errors,
+            !artifacts.is_empty(),
+        )?)
+    };
+
     let out = std::io::stdout();
     let mut outlock = out.lock();
 
@@ -427,17 +785,25 @@ pub async fn build(
         writeln!(outlock, "{}", staging_dir.join(artifact_path).display()).map_err(Error::from)
     })?;
 
-    let mut had_error = false;
     for (job_uuid, error) in errors {
-        had_error = true;
         for cause in error.chain() {
             writeln!(outlock, "{}: {}", "[ERROR]".red(), cause)?;
         }
 
-        let data = schema::jobs::table
+        let Some(data) = schema::jobs::table
             .filter(schema::jobs::dsl::uuid.eq(job_uuid))
+            .filter(schema::jobs::retried.eq(false))
             .inner_join(schema::packages::table)
-            .first::<(Job, Package)>(&mut *database_pool.get().unwrap())?;
+            .first::<(Job, Package)>(&mut *database_pool.get().unwrap())
+            .optional()?
+        else {
+            writeln!(
+                outlock,
+                "{}\n\n",
+                format!("Job {job_uuid} did not finish, there is no log").red()
+            )?;
+            continue;
+        };
 
         let number_log_lines = *config.build_error_lines();
         writeln!(
@@ -502,9 +868,455 @@ pub async fn build(
         }
     }
 
-    if had_error {
-        Err(anyhow!("One or multiple errors during build"))
+    match error_category {
+        Some(category) => Err(anyhow!("One or multiple errors during build")).context(category),
+        None => Ok(()),
+    }
+}
+
+/// The category of the error of a build with failed jobs (see `--error-format`)
+///
+/// A job failed because of the infrastructure if its container did not finish (the job was not
+/// recorded) or if its failure class is one of the classes that are retried on other endpoints
+/// (`docker.retry_failure_classes`).
+fn build_error_category(
+    conn: &mut PgConnection,
+    config: &Configuration,
+    errors: &HashMap<Uuid, Error>,
+    produced_artifacts: bool,
+) -> Result<ErrorCategory> {
+    let failure_classes = schema::jobs::table
+        .filter(schema::jobs::uuid.eq_any(errors.keys().copied().collect::<Vec<_>>()))
+        .filter(schema::jobs::retried.eq(false))
+        .select((schema::jobs::uuid, schema::jobs::failure_class))
+        .load::<(Uuid, Option<String>)>(conn)?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+
+    let infrastructure_failure = |job_uuid: &Uuid| match failure_classes.get(job_uuid) {
+        None => true,
+        Some(class) => class.as_ref().is_some_and(|class| {
+            class == crate::log::DOCKER_FAILURE_CLASS
+                || config.docker().retry_failure_classes().contains(class)
+        }),
+    };
+
+    Ok(if errors.keys().all(infrastructure_failure) {
+        ErrorCategory::Infrastructure
+    } else if produced_artifacts {
+        ErrorCategory::PartialFailure
     } else {
+        ErrorCategory::BuildFailure
+    })
+}
+
+/// Machine-readable summary of a build, written with `--summary-out`
+#[derive(serde::Serialize)]
+struct BuildSummary<'a> {
+    submit: Uuid,
+    success: bool,
+    duration_secs: u64,
+    /// The causes of the error if the build could not be run at all
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<String>,
+    jobs: Vec<BuildSummaryJob<'a>>,
+    artifacts: Vec<PathBuf>,
+}
+
+impl<'a> BuildSummary<'a> {
+    /// Create the summary from the result of running the orchestrator
+    fn new(
+        conn: &mut PgConnection,
+        submit: &Submit,
+        build_started: std::time::Instant,
+        jobs: &'a [(Uuid, crate::package::Package)],
+        result: std::result::Result<&HashMap<Uuid, Error>, &Error>,
+        artifacts: &[crate::filestore::ArtifactPath],
+        staging_dir: &Path,
+    ) -> Result<Self> {
+        let built_jobs = schema::jobs::table
+            .filter(schema::jobs::submit_id.eq(submit.id))
+            .select(schema::jobs::uuid)
+            .load::<Uuid>(conn)?;
+        let job_errors = result.ok();
+
+        Ok(BuildSummary {
+            submit: submit.uuid,
+            success: job_errors.is_some_and(HashMap::is_empty),
+            duration_secs: build_started.elapsed().as_secs(),
+            errors: result
+                .err()
+                .map(|error| error.chain().map(|cause| cause.to_string()).collect())
+                .unwrap_or_default(),
+            jobs: jobs
+                .iter()
+                .map(|(uuid, package)| {
+                    let error = job_errors.and_then(|errors| errors.get(uuid));
+                    let (status, error_messages) = if let Some(error) = error {
+                        let causes = error.chain().map(|cause| cause.to_string()).collect();
+                        ("failed", causes)
+                    } else if built_jobs.contains(uuid) {
+                        ("built", Vec::new())
+                    } else {
+                        // The job reused artifacts or did not run because a dependency failed
+                        ("not built", Vec::new())
+                    };
+
+                    BuildSummaryJob {
+                        uuid: *uuid,
+                        package: package.name(),
+                        version: package.version(),
+                        maintainer: package.maintainer().as_ref(),
+                        license: package.license().as_ref(),
+                        description: package.description().as_ref(),
+                        homepage: package.homepage().as_ref(),
+                        status,
+                        errors: error_messages,
+                    }
+                })
+                .collect(),
+            artifacts: artifacts
+                .iter()
+                .map(|artifact_path| staging_dir.join(artifact_path))
+                .collect(),
+        })
+    }
+
+    fn write_to_file(&self, path: &Path) -> Result<()> {
+        let file = std::fs::File::create(path)
+            .with_context(|| anyhow!("Creating summary file {}", path.display()))?;
+        serde_json::to_writer_pretty(file, self)
+            .with_context(|| anyhow!("Writing summary to {}", path.display()))
+    }
+}
+
+#[derive(serde::Serialize)]
+struct BuildSummaryJob<'a> {
+    uuid: Uuid,
+    package: &'a PackageName,
+    version: &'a PackageVersion,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    maintainer: Option<&'a String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    license: Option<&'a String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    homepage: Option<&'a String>,
+    status: &'static str,
+    errors: Vec<String>,
+}
+
+/// Record the build in the database, so that the daemon (`butido daemon`) runs it
+fn enqueue(
+    matches: &ArgMatches,
+    database_pool: &Pool<ConnectionManager<PgConnection>>,
+    repo_hash: &str,
+    package: &crate::package::Package,
+) -> Result<()> {
+    let build_command = crate::cli::cli()
+        .find_subcommand("build")
+        .cloned()
+        .ok_or_else(|| anyhow!("Butido bug: The build subcommand is missing"))?;
+    let arguments =
+        crate::commands::util::command_line_arguments(&build_command, matches, &["enqueue"])?;
+
+    let mut conn = database_pool.get()?;
+    let githash = GitHash::create_or_fetch(&mut conn, repo_hash)?;
+    let queued = QueuedSubmit::create(&mut conn, &Uuid::new_v4(), &githash, &arguments)?;
+
+    writeln!(
+        std::io::stdout(),
+        "Enqueued submit {} for {} {} on repo hash {}",
+        queued.uuid.to_string().green(),
+        package.name(),
+        package.version(),
+        githash.hash
+    )?;
+    Ok(())
+}
+
+/// The architectures to build for: the architectures passed with "--arch" or the architectures of
+/// the image that the package supports
+///
+/// The only element is `None` if the submit does not build for specific architectures, because
+/// the image has no architectures configured.
+fn build_archs(
+    matches: &ArgMatches,
+    image_archs: &[String],
+    package: &crate::package::Package,
+) -> Result<Vec<Option<String>>> {
+    let archs = if let Some(archs) = matches.get_many::<String>("arch") {
+        let archs = archs.unique().cloned().collect::<Vec<_>>();
+        if let Some(arch) = archs
+            .iter()
+            .find(|arch| !image_archs.is_empty() && !image_archs.contains(arch))
+        {
+            return Err(anyhow!(
+                "The image is not available for {} (architectures of the image: {})",
+                arch,
+                image_archs.join(", ")
+            ));
+        }
+        archs
+    } else {
+        let archs = image_archs
+            .iter()
+            .filter(|arch| {
+                package
+                    .archs()
+                    .as_ref()
+                    .map_or(true, |supported| supported.contains(arch))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if archs.is_empty() && !image_archs.is_empty() {
+            return Err(anyhow!(
+                "Package {} {} supports none of the architectures of the image: {}",
+                package.name(),
+                package.version(),
+                image_archs.join(", ")
+            ));
+        }
+        archs
+    };
+
+    if archs.is_empty() {
+        Ok(vec![None])
+    } else {
+        Ok(archs.into_iter().map(Some).collect())
+    }
+}
+
+/// The endpoints to build on: the endpoints selected with "--endpoint" (by name or tag) or all
+/// configured endpoints if there are no `selectors`
+fn selected_endpoints<'a>(
+    endpoints: &'a HashMap<EndpointName, Endpoint>,
+    selectors: &[String],
+) -> Result<Vec<(&'a EndpointName, &'a Endpoint)>> {
+    if selectors.is_empty() {
+        return Ok(endpoints.iter().collect());
+    }
+
+    let mut selected = Vec::new();
+    for selector in selectors {
+        let matching = endpoints
+            .iter()
+            .filter(|(name, ep)| name.as_ref() == selector.as_str() || ep.tags().contains(selector))
+            .collect::<Vec<_>>();
+        if matching.is_empty() {
+            return Err(anyhow!(
+                "No endpoint with the name or tag {} (configured endpoints: {})",
+                selector,
+                endpoints.keys().sorted().join(", ")
+            ));
+        }
+        selected.extend(matching);
+    }
+    Ok(selected.into_iter().unique_by(|(name, _)| *name).collect())
+}
+
+/// Refuse to build if the dependency tree deviates from the lockfile (see `build --locked`)
+///
+/// The digest of the image is determined on the endpoints of the build, it is not known for local
+/// builds and builds on Kubernetes endpoints.
+async fn check_lockfile(
+    config: &Configuration,
+    lockfile_path: &Path,
+    dag: &Dag,
+    image_name: &ImageName,
+    endpoints: &[(&EndpointName, &Endpoint)],
+) -> Result<()> {
+    let locked = Lockfile::read_from_file(lockfile_path)?;
+    let endpoint_names = endpoints
+        .iter()
+        .map(|(ep_name, _)| (*ep_name).clone())
+        .collect::<Vec<_>>();
+    let digest = crate::commands::lock::image_digest(config, &endpoint_names, image_name).await?;
+    if digest.is_none() && locked.image_digest().is_some() {
+        warn!(
+            "The digest of image {} cannot be determined, it is not checked against the lockfile",
+            image_name
+        );
+    }
+
+    let differences = locked.differences(&Lockfile::from_dag(dag, image_name, digest));
+    if differences.is_empty() {
+        info!(
+            "Dependency tree matches lockfile {}",
+            lockfile_path.display()
+        );
+        return Ok(());
+    }
+    Err(anyhow!(
+        "The dependency tree deviates from lockfile {}:\n{}",
+        lockfile_path.display(),
+        differences.iter().map(|d| format!("  {d}")).join("\n")
+    ))
+    .context(ErrorCategory::Resolution)
+}
+
+/// Check the free disk space before the submit starts (see `DiskSpaceLimits`)
+///
+/// The staging directory and the endpoints need space for the artifacts of the submit, whose
+/// size is estimated from the last builds of the packages.
+async fn check_disk_space(
+    config: &Configuration,
+    database_pool: &Pool<ConnectionManager<PgConnection>>,
+    packages: &[&crate::package::Package],
+    staging_dir: &Path,
+    endpoint_names: &[EndpointName],
+    ignore: bool,
+) -> Result<()> {
+    let estimated = {
+        let mut conn = database_pool.get()?;
+        packages
+            .iter()
+            .map(|p| {
+                crate::db::models::Artifact::last_build_size(
+                    &mut conn,
+                    p.name().as_ref(),
+                    p.version().as_ref(),
+                )
+                .map(Option::unwrap_or_default)
+            })
+            .sum::<Result<u64>>()?
+    };
+    debug!(
+        "Estimated size of the artifacts of the submit: {}",
+        bytesize::ByteSize::b(estimated)
+    );
+
+    let mut locations = vec![
+        (
+            format!("the staging directory {}", staging_dir.display()),
+            free_space(staging_dir).await?,
+            estimated,
+        ),
+        (
+            format!("the source cache {}", config.source_cache_root().display()),
+            free_space(config.source_cache_root()).await?,
+            0,
+        ),
+    ];
+
+    for endpoint in crate::commands::endpoint::connect_to_endpoints(config, endpoint_names).await? {
+        match endpoint.free_disk_space().await {
+            Ok(Some(free)) => {
+                locations.push((format!("endpoint {}", endpoint.name()), free, estimated))
+            }
+            Ok(None) => debug!(
+                "Cannot determine the free disk space of endpoint {}",
+                endpoint.name()
+            ),
+            Err(e) => warn!(
+                "Checking the free disk space of endpoint {} failed: {:#}",
+                endpoint.name(),
+                e
+            ),
+        }
+    }
+
+    let mut refused = Vec::new();
+    for (location, free, required) in locations {
+        let message = format!(
+            "{} free in {}, the submit needs about {}",
+            bytesize::ByteSize::b(free),
+            location,
+            bytesize::ByteSize::b(required)
+        );
+        match DiskSpaceVerdict::check(free, required, config.disk_space_limits()) {
+            DiskSpaceVerdict::Ok => debug!("{}", message),
+            DiskSpaceVerdict::Warn => warn!("Low disk space: {}", message),
+            DiskSpaceVerdict::Refuse => refused.push(message),
+        }
+    }
+
+    if refused.is_empty() {
         Ok(())
+    } else if ignore {
+        for message in refused {
+            warn!("Not enough disk space (ignored): {}", message);
+        }
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Not enough disk space (use --ignore-disk-space to build anyway):\n{}",
+            refused.join("\n")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoints() -> HashMap<EndpointName, Endpoint> {
+        toml::from_str(indoc::indoc!(
+            r#"
+            [a]
+            uri = "unix:///var/run/a.sock"
+            endpoint_type = "socket"
+            maxjobs = 1
+            tags = ["gpu"]
+
+            [b]
+            uri = "unix:///var/run/b.sock"
+            endpoint_type = "socket"
+            maxjobs = 1
+            tags = ["gpu", "fast-disk"]
+
+            [c]
+            uri = "unix:///var/run/c.sock"
+            endpoint_type = "socket"
+            maxjobs = 1
+            "#
+        ))
+        .unwrap()
+    }
+
+    fn selected_names(
+        endpoints: &HashMap<EndpointName, Endpoint>,
+        selectors: &[&str],
+    ) -> Vec<String> {
+        let selectors = selectors.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        selected_endpoints(endpoints, &selectors)
+            .unwrap()
+            .into_iter()
+            .map(|(name, _)| name.to_string())
+            .sorted()
+            .collect()
+    }
+
+    #[test]
+    fn test_selected_endpoints_without_selectors() {
+        let endpoints = endpoints();
+        assert_eq!(selected_names(&endpoints, &[]), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_selected_endpoints_by_name() {
+        let endpoints = endpoints();
+        assert_eq!(selected_names(&endpoints, &["c"]), vec!["c"]);
+        assert_eq!(selected_names(&endpoints, &["a", "c"]), vec!["a", "c"]);
+    }
+
+    #[test]
+    fn test_selected_endpoints_by_tag() {
+        let endpoints = endpoints();
+        assert_eq!(selected_names(&endpoints, &["gpu"]), vec!["a", "b"]);
+        assert_eq!(selected_names(&endpoints, &["fast-disk"]), vec!["b"]);
+        // An endpoint that is selected by name and by tag is used once
+        assert_eq!(
+            selected_names(&endpoints, &["fast-disk", "b", "c"]),
+            vec!["b", "c"]
+        );
+    }
+
+    #[test]
+    fn test_selected_endpoints_unknown() {
+        let endpoints = endpoints();
+        let selectors = vec![String::from("a"), String::from("unknown")];
+        assert!(selected_endpoints(&endpoints, &selectors).is_err());
     }
 }
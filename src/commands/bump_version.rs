@@ -0,0 +1,215 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Implementation of the 'bump-version' subcommand
+
+use std::collections::BTreeSet;
+use std::io::Write;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use clap::ArgMatches;
+use filters::failable::filter::FailableFilter;
+use tracing::{info, warn};
+use url::Url;
+
+use crate::package::Package;
+use crate::package::PackageName;
+use crate::package::PackageVersion;
+use crate::repository::Repository;
+
+/// Implementation of the "bump-version" subcommand
+pub async fn bump_version(matches: &ArgMatches, repo: Repository) -> Result<()> {
+    // safe by clap:
+    let name = matches.get_one::<String>("package_name").unwrap();
+    let new_version = matches.get_one::<String>("new_version").unwrap();
+    let name = PackageName::from(name.clone());
+    let new_version = PackageVersion::from(new_version.clone());
+    let timeout = matches.get_one::<u64>("timeout").copied();
+
+    let package = match matches.get_one::<String>("package_version") {
+        Some(version) => repo
+            .find(&name, &PackageVersion::from(version.clone()))
+            .into_iter()
+            .next(),
+        None => {
+            let packages = repo.find_by_name(&name);
+            if packages.len() > 1 {
+                return Err(anyhow!(
+                    "There are {} versions of {}, please pass the version to bump",
+                    packages.len(),
+                    name
+                ));
+            }
+            packages.into_iter().next()
+        }
+    }
+    .ok_or_else(|| anyhow!("Package {} not found", name))?;
+
+    if !repo.find(&name, &new_version).is_empty() {
+        return Err(anyhow!(
+            "Package {} {} exists already in the repository",
+            name,
+            new_version
+        ));
+    }
+
+    // Only the pkg.toml of the package itself is changed, pkg.toml files further up in the tree
+    // are shared with other packages:
+    let version_origin = crate::repository::explain(package)?
+        .into_iter()
+        .find(|field| field.key() == "version")
+        .map(|field| field.origin().clone())
+        .ok_or_else(|| anyhow!("Bug: No version for {}", package.display_name_version()))?;
+    if version_origin != *package.origin() {
+        return Err(anyhow!(
+            "The version of {} is defined in {}, which is not the pkg.toml of the package ({})",
+            package.display_name_version(),
+            version_origin.display(),
+            package.origin().display()
+        ));
+    }
+
+    let path = package.origin();
+    let mut document = std::fs::read_to_string(path)
+        .with_context(|| anyhow!("Reading {}", path.display()))?
+        .parse::<toml_edit::DocumentMut>()
+        .with_context(|| anyhow!("Parsing {}", path.display()))?;
+    document["version"] = toml_edit::value(new_version.as_str());
+
+    for (source_name, source) in package.sources() {
+        let url = bump_url(source.url(), package.version(), &new_version)?;
+        if url == *source.url() {
+            warn!(
+                "The URL of the source {} does not contain the version, it is not changed: {}",
+                source_name, url
+            );
+        }
+
+        crate::util::offline::ensure_online(format_args!(
+            "Downloading {} to compute the hash of the source {}",
+            url, source_name
+        ))?;
+        info!("Downloading {} to compute its hash", url);
+        let hash = crate::commands::source::hash_of_url(&url, source.hash().hashtype(), timeout)
+            .await
+            .with_context(|| anyhow!("Computing the hash of {}", url))?;
+
+        // The (possibly inherited) source is overridden in the pkg.toml of the package
+        let source_item = &mut document["sources"][source_name.as_str()];
+        source_item["url"] = toml_edit::value(url.as_str());
+        source_item["hash"]["type"] = toml_edit::value(source.hash().hashtype().to_string());
+        source_item["hash"]["hash"] = toml_edit::value(hash.to_string());
+    }
+
+    std::fs::write(path, document.to_string())
+        .with_context(|| anyhow!("Writing {}", path.display()))?;
+
+    let mut out = std::io::stdout();
+    writeln!(
+        out,
+        "Bumped {} to {} in {}",
+        package.display_name_version(),
+        new_version,
+        path.display()
+    )?;
+
+    let dependents = reverse_dependencies(&repo, &name)?;
+    if dependents.is_empty() {
+        writeln!(out, "No packages depend on {name}")?;
+    } else {
+        writeln!(out, "Packages that need to be rebuilt:")?;
+        for package in dependents {
+            writeln!(out, "    {}", package.display_name_version())?;
+        }
+    }
+
+    if let Some(image) = matches.get_one::<String>("build_image") {
+        test_build(&name, &new_version, image)?;
+    }
+    Ok(())
+}
+
+/// Replace the version in the URL of a source
+fn bump_url(url: &Url, old: &PackageVersion, new: &PackageVersion) -> Result<Url> {
+    Url::parse(&url.as_str().replace(old.as_str(), new.as_str()))
+        .with_context(|| anyhow!("Replacing the version in {}", url))
+}
+
+/// All packages that depend (directly or transitively) on a package with the name `name`
+fn reverse_dependencies<'a>(repo: &'a Repository, name: &PackageName) -> Result<Vec<&'a Package>> {
+    let mut dependents = BTreeSet::new();
+    let mut queue = vec![name.clone()];
+    let mut seen = BTreeSet::from([name.clone()]);
+
+    while let Some(name) = queue.pop() {
+        let filter =
+            crate::util::filters::build_package_filter_by_dependency_name(&name, true, true);
+        for package in repo.packages() {
+            if filter.filter(package)? {
+                dependents.insert((package.name(), package.version()));
+                if seen.insert(package.name().clone()) {
+                    queue.push(package.name().clone());
+                }
+            }
+        }
+    }
+
+    Ok(dependents
+        .into_iter()
+        .flat_map(|(name, version)| repo.find(name, version))
+        .collect())
+}
+
+/// Build the bumped package with butido itself
+fn test_build(name: &PackageName, version: &PackageVersion, image: &str) -> Result<()> {
+    let butido = std::env::current_exe().context("Finding the butido executable")?;
+    info!("Running a test build of {} {}", name, version);
+
+    let mut command = std::process::Command::new(&butido);
+    if crate::util::offline::is_offline() {
+        command.arg("--offline");
+    }
+    let status = command
+        .arg("build")
+        .arg("--image")
+        .arg(image)
+        .arg(name.as_str())
+        .arg(version.as_str())
+        .status()
+        .with_context(|| anyhow!("Running {}", butido.display()))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!("The test build of {} {} failed", name, version))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bump_url() {
+        let url = Url::parse("https://example.com/foo/1.2/foo-1.2.tar.gz").unwrap();
+        let bumped = bump_url(
+            &url,
+            &PackageVersion::from(String::from("1.2")),
+            &PackageVersion::from(String::from("1.3")),
+        )
+        .unwrap();
+        assert_eq!(
+            bumped.as_str(),
+            "https://example.com/foo/1.3/foo-1.3.tar.gz"
+        );
+    }
+}
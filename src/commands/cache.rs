@@ -0,0 +1,100 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Implementation of the 'cache' subcommand
+
+use std::io::Write;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use clap::ArgMatches;
+use colored::Colorize;
+use tokio_stream::StreamExt;
+use tracing::info;
+
+use crate::config::CompilerCacheTool;
+use crate::config::Configuration;
+use crate::config::EndpointName;
+use crate::util::docker::ImageNameLookup;
+
+/// Implementation of the "cache" subcommand
+pub async fn cache(matches: &ArgMatches, config: &Configuration) -> Result<()> {
+    match matches.subcommand() {
+        Some(("stats", matches)) => run(matches, config, CompilerCacheTool::stats_command).await,
+        Some(("clear", matches)) => {
+            if !matches.get_flag("yes")
+                && !dialoguer::Confirm::new()
+                    .with_prompt("Really clear the compiler caches?")
+                    .interact()?
+            {
+                return Ok(());
+            }
+            run(matches, config, CompilerCacheTool::clear_command).await
+        }
+        Some((other, _)) => Err(anyhow!("Unknown subcommand: {}", other)),
+        None => Err(anyhow!("No subcommand")),
+    }
+}
+
+/// Run a command of the compiler cache tool on all (selected) endpoints that have a compiler cache
+async fn run(
+    matches: &ArgMatches,
+    config: &Configuration,
+    command: fn(&CompilerCacheTool) -> &'static str,
+) -> Result<()> {
+    let image = ImageNameLookup::create(config.docker().images())?
+        .expand(matches.get_one::<String>("image").unwrap())?; // safe by clap
+
+    let endpoint_names = config
+        .docker()
+        .endpoints()
+        .iter()
+        .filter(|(_, ep)| ep.compiler_cache().is_some())
+        .map(|(name, _)| name.clone())
+        .filter(|name| {
+            matches
+                .get_one::<String>("endpoint_name")
+                .map(|selected| selected == name.as_ref())
+                .unwrap_or(true)
+        })
+        .collect::<Vec<EndpointName>>();
+
+    if endpoint_names.is_empty() {
+        info!("No endpoint with a compiler cache");
+        return Ok(());
+    }
+
+    let endpoints = super::endpoint::connect_to_endpoints(config, &endpoint_names).await?;
+    let mut outputs = endpoints
+        .iter()
+        .map(|ep| {
+            let image = &image;
+            async move {
+                ep.run_in_compiler_cache(image, |tool| command(&tool))
+                    .await
+                    .map(|output| (ep.name().clone(), output))
+            }
+        })
+        .collect::<futures::stream::FuturesUnordered<_>>()
+        .collect::<Result<Vec<_>>>()
+        .await?;
+    outputs.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let out = std::io::stdout();
+    let mut outlock = out.lock();
+    for (name, output) in outputs {
+        if let Some(output) = output {
+            writeln!(outlock, "{}", name.as_ref().bold())?;
+            writeln!(outlock, "{}", output.trim_end())?;
+            writeln!(outlock)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,238 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Implementation of the 'config' subcommand
+
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use clap::ArgMatches;
+use colored::Colorize;
+
+/// Implementation of the "config" subcommand
+pub fn config(
+    matches: &ArgMatches,
+    repo_path: &Path,
+    config_files: &[PathBuf],
+    profile: Option<&str>,
+) -> Result<()> {
+    match matches.subcommand() {
+        Some(("show", matches)) => show(matches, config_files, profile),
+        Some(("get", matches)) => get(matches, config_files, profile),
+        Some(("set", matches)) => set(matches, repo_path),
+        Some((other, _)) => Err(anyhow!("Unknown subcommand: {}", other)),
+        None => Err(anyhow!("No subcommand")),
+    }
+}
+
+fn show(matches: &ArgMatches, config_files: &[PathBuf], profile: Option<&str>) -> Result<()> {
+    let out = std::io::stdout();
+    let mut outlock = out.lock();
+
+    if !matches.get_flag("effective") {
+        writeln!(
+            outlock,
+            "Configuration sources (later sources override earlier ones):"
+        )?;
+        for (idx, file) in config_files.iter().enumerate() {
+            let missing = if file.exists() { "" } else { " (missing)" };
+            writeln!(outlock, "{:>2}. {}{missing}", idx + 1, file.display())?;
+        }
+        if let Some(profile) = profile {
+            writeln!(outlock, "    Profile: {profile}")?;
+        }
+        writeln!(
+            outlock,
+            "{:>2}. Environment variables with the prefix {}_",
+            config_files.len() + 1,
+            crate::config::CONFIG_ENV_PREFIX
+        )?;
+        return Ok(());
+    }
+
+    let config = crate::config::load_config(config_files, profile)?;
+    let values = crate::config::effective_values(&config)?;
+    let show_secrets = matches.get_flag("show_secrets");
+    let key_width = values.iter().map(|v| v.key.len()).max().unwrap_or(0);
+
+    for value in values {
+        let is_secret = value.key.ends_with("password") || value.key.ends_with("token");
+        let shown = if is_secret && !show_secrets {
+            String::from("\"********\"")
+        } else {
+            value.value
+        };
+
+        writeln!(
+            outlock,
+            "{:<key_width$} = {shown}  {}",
+            value.key,
+            format!("# {}", value.origin).bright_black()
+        )?;
+    }
+
+    Ok(())
+}
+
+fn get(matches: &ArgMatches, config_files: &[PathBuf], profile: Option<&str>) -> Result<()> {
+    let key = matches.get_one::<String>("key").unwrap(); // safe by clap
+    let config = crate::config::load_config(config_files, profile)?;
+    let value = config
+        .get::<::config::Value>(key)
+        .with_context(|| anyhow!("Failed to get the configuration value '{}'", key))?;
+
+    let out = std::io::stdout();
+    let mut outlock = out.lock();
+
+    if matches!(
+        value.kind,
+        ::config::ValueKind::Table(_) | ::config::ValueKind::Array(_)
+    ) {
+        let table_prefix = format!("{key}.");
+        let array_prefix = format!("{key}[");
+        for value in crate::config::effective_values(&config)?
+            .into_iter()
+            .filter(|v| v.key.starts_with(&table_prefix) || v.key.starts_with(&array_prefix))
+        {
+            writeln!(outlock, "{} = {}", value.key, value.value)?;
+        }
+    } else {
+        writeln!(outlock, "{}", value.into_string()?)?;
+    }
+
+    Ok(())
+}
+
+fn set(matches: &ArgMatches, repo_path: &Path) -> Result<()> {
+    let key = matches.get_one::<String>("key").unwrap(); // safe by clap
+    let value = matches.get_one::<String>("value").unwrap(); // safe by clap
+    let file = matches
+        .get_one::<PathBuf>("file")
+        .cloned()
+        .unwrap_or_else(|| repo_path.join("config.toml"));
+
+    let content = if file.exists() {
+        std::fs::read_to_string(&file).with_context(|| anyhow!("Reading {}", file.display()))?
+    } else {
+        String::new()
+    };
+    let mut document = content
+        .parse::<toml_edit::DocumentMut>()
+        .with_context(|| anyhow!("Parsing {}", file.display()))?;
+
+    set_value(&mut document, key, parse_value(value))?;
+
+    let content = document.to_string();
+    if let Some(e) = super::validate_config::schema_error(&content) {
+        return Err(anyhow!("{}", e.message()))
+            .with_context(|| anyhow!("Setting '{}' would make {} invalid", key, file.display()));
+    }
+
+    std::fs::write(&file, content).with_context(|| anyhow!("Writing {}", file.display()))
+}
+
+/// Parse a value from the command line as TOML value
+///
+/// Values that are not valid TOML (e.g., paths or URLs without quotes) are used as strings.
+fn parse_value(value: &str) -> toml_edit::Value {
+    value
+        .parse::<toml_edit::Value>()
+        .unwrap_or_else(|_| toml_edit::Value::from(value))
+}
+
+/// Set the value of a dotted key in the document, creating tables as required
+///
+/// If the key already has a value, the formatting and comments around the value are kept.
+fn set_value(
+    document: &mut toml_edit::DocumentMut,
+    key: &str,
+    value: toml_edit::Value,
+) -> Result<()> {
+    let segments = key.split('.').collect::<Vec<_>>();
+    if segments.iter().any(|segment| segment.is_empty()) {
+        return Err(anyhow!("Invalid key: '{}'", key));
+    }
+    let (last, parents) = segments.split_last().unwrap(); // safe because split() yields one item
+
+    let mut table: &mut dyn toml_edit::TableLike = document.as_table_mut();
+    for segment in parents {
+        let mut new_table = toml_edit::Table::new();
+        new_table.set_implicit(true);
+        table = table
+            .entry(segment)
+            .or_insert(toml_edit::Item::Table(new_table))
+            .as_table_like_mut()
+            .ok_or_else(|| anyhow!("Cannot set '{}': '{}' is not a table", key, segment))?;
+    }
+
+    let mut value = value;
+    match table.get_mut(last) {
+        Some(toml_edit::Item::Value(existing)) => {
+            *value.decor_mut() = existing.decor().clone();
+            *existing = value;
+        }
+        Some(toml_edit::Item::Table(_)) | Some(toml_edit::Item::ArrayOfTables(_)) => {
+            return Err(anyhow!("Cannot set '{}': it is a table", key));
+        }
+        Some(toml_edit::Item::None) | None => {
+            value.decor_mut().clear();
+            table.insert(last, toml_edit::Item::Value(value));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_value_keeps_formatting() -> Result<()> {
+        let mut document = indoc::indoc!(
+            r#"
+                # The endpoints
+                [docker.endpoints.builder1]
+                uri     = "http://builder1:8095"
+                maxjobs = 1 # per endpoint
+            "#
+        )
+        .parse::<toml_edit::DocumentMut>()?;
+
+        set_value(
+            &mut document,
+            "docker.endpoints.builder1.maxjobs",
+            parse_value("4"),
+        )?;
+        set_value(
+            &mut document,
+            "docker.endpoints.builder2.uri",
+            parse_value("http://builder2:8095"),
+        )?;
+        assert!(set_value(&mut document, "docker.endpoints", parse_value("1")).is_err());
+        assert!(set_value(&mut document, "docker..endpoints", parse_value("1")).is_err());
+
+        let content = document.to_string();
+        assert!(content.starts_with("# The endpoints\n"));
+        assert!(content.contains("uri     = \"http://builder1:8095\"\n"));
+        assert!(content.contains("maxjobs = 4 # per endpoint\n"));
+
+        let parsed = toml::from_str::<toml::Table>(&content)?;
+        assert_eq!(
+            parsed["docker"]["endpoints"]["builder2"]["uri"].as_str(),
+            Some("http://builder2:8095")
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Implementation of the 'daemon' subcommand
+
+use std::path::Path;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use clap::ArgMatches;
+use diesel::r2d2::ConnectionManager;
+use diesel::r2d2::Pool;
+use diesel::PgConnection;
+use tracing::{error, info, warn};
+
+use crate::db::models::GitHash;
+use crate::db::models::QueuedSubmit;
+use crate::db::models::QueuedSubmitState;
+use crate::db::DbConnectionConfig;
+
+/// Implementation of the "daemon" subcommand
+pub async fn daemon(
+    db_connection_config: DbConnectionConfig<'_>,
+    global_matches: &ArgMatches,
+    matches: &ArgMatches,
+    repo_path: &Path,
+) -> Result<()> {
+    let poll_interval =
+        std::time::Duration::from_secs(*matches.get_one::<u64>("poll_interval").unwrap()); // safe by clap
+    let once = matches.get_flag("once");
+    let pool = db_connection_config.establish_pool()?;
+
+    // The global arguments (e.g., the profile) are passed on to the builds
+    let global_arguments =
+        crate::commands::util::command_line_arguments(&crate::cli::cli(), global_matches, &[])?;
+
+    info!("Waiting for enqueued builds");
+    loop {
+        let next = QueuedSubmit::claim_next(&mut pool.get()?)?;
+        match next {
+            Some(queued) => {
+                // Other daemons run the submit again if this daemon stops refreshing the heartbeat
+                let heartbeat = tokio::spawn(refresh_heartbeat(pool.clone(), queued.clone()));
+                let result = run_queued_submit(&pool, &queued, repo_path, &global_arguments).await;
+                heartbeat.abort();
+
+                let state = match result {
+                    Ok(true) => QueuedSubmitState::Succeeded,
+                    Ok(false) => QueuedSubmitState::Failed,
+                    Err(e) => {
+                        error!("Running queued submit {} failed: {:?}", queued.uuid, e);
+                        QueuedSubmitState::Failed
+                    }
+                };
+                info!("Queued submit {} finished: {}", queued.uuid, state);
+                queued.finish(&mut pool.get()?, state)?;
+
+                if once {
+                    return Ok(());
+                }
+            }
+            None if once => {
+                info!("No enqueued builds");
+                return Ok(());
+            }
+            None => tokio::time::sleep(poll_interval).await,
+        }
+    }
+}
+
+/// Refresh the heartbeat of the queued submit periodically, while it runs
+async fn refresh_heartbeat(pool: Pool<ConnectionManager<PgConnection>>, queued: QueuedSubmit) {
+    let mut interval = tokio::time::interval(QueuedSubmit::HEARTBEAT_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+        let refreshed = pool
+            .get()
+            .map_err(anyhow::Error::from)
+            .and_then(|mut conn| queued.refresh_heartbeat(&mut conn));
+        if let Err(e) = refreshed {
+            warn!(
+                "Cannot refresh the heartbeat of queued submit {}: {:?}",
+                queued.uuid, e
+            );
+        }
+    }
+}
+
+/// Check out the commit of the queued submit and run the build
+///
+/// Returns whether the build succeeded.
+async fn run_queued_submit(
+    pool: &Pool<ConnectionManager<PgConnection>>,
+    queued: &QueuedSubmit,
+    repo_path: &Path,
+    global_arguments: &[String],
+) -> Result<bool> {
+    let githash = GitHash::with_id(&mut pool.get()?, queued.repo_hash_id)?;
+    info!(
+        "Running queued submit {} on repo hash {}",
+        queued.uuid, githash.hash
+    );
+
+    {
+        let repo = git2::Repository::open(repo_path)
+            .with_context(|| anyhow!("Opening repository at {}", repo_path.display()))?;
+        let oid = git2::Oid::from_str(&githash.hash)
+            .with_context(|| anyhow!("Parsing commit hash {}", githash.hash))?;
+        let commit = repo.find_commit(oid).with_context(|| {
+            anyhow!(
+                "Commit {} not found in {}, it has to be fetched first",
+                githash.hash,
+                repo_path.display()
+            )
+        })?;
+
+        // A safe checkout, which fails instead of overwriting local modifications
+        repo.checkout_tree(commit.as_object(), None)
+            .with_context(|| anyhow!("Checking out commit {}", githash.hash))?;
+        repo.set_head_detached(oid)
+            .with_context(|| anyhow!("Setting HEAD to commit {}", githash.hash))?;
+    }
+
+    let status = tokio::process::Command::new(std::env::current_exe()?)
+        .args(global_arguments)
+        .arg("build")
+        .args(&queued.arguments)
+        .arg(format!("--submit-uuid={}", queued.uuid))
+        .current_dir(repo_path)
+        .status()
+        .await
+        .context("Running butido build")?;
+
+    Ok(status.success())
+}
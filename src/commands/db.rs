@@ -10,7 +10,10 @@
 
 //! Implementation of the 'db' subcommand
 
+use std::collections::HashSet;
+use std::io::IsTerminal;
 use std::io::Write;
+use std::path::Path;
 use std::path::PathBuf;
 use std::process::Command;
 use std::str::FromStr;
@@ -22,49 +25,98 @@ use anyhow::Result;
 use clap::ArgMatches;
 use colored::Colorize;
 use diesel::BelongingToDsl;
+use diesel::BoolExpressionMethods;
+use diesel::Connection;
 use diesel::ExpressionMethods;
 use diesel::JoinOnDsl;
+use diesel::NullableExpressionMethods;
+use diesel::OptionalExtension;
+use diesel::PgConnection;
+use diesel::PgSortExpressionMethods;
 use diesel::QueryDsl;
 use diesel::RunQueryDsl;
+use diesel::TextExpressionMethods;
 use diesel_migrations::embed_migrations;
 use diesel_migrations::EmbeddedMigrations;
 use diesel_migrations::HarnessWithOutput;
 use diesel_migrations::MigrationHarness;
+use futures::StreamExt;
 use itertools::Itertools;
-use tracing::{debug, info, trace};
+use tracing::{debug, info, trace, warn};
 
 use crate::commands::util::get_date_filter;
 use crate::config::Configuration;
 use crate::db::models;
 use crate::db::DbConnectionConfig;
+use crate::filestore::ArtifactPath;
 use crate::log::JobResult;
+use crate::package::condition::ConditionData;
+use crate::package::Dag;
+use crate::package::HashType;
+use crate::package::PackageName;
+use crate::package::PackageVersion;
+use crate::package::PackageVersionConstraint;
 use crate::package::Script;
+use crate::repository::Repository;
 use crate::schema;
+use crate::util::docker::ImageName;
 use crate::util::docker::ImageNameLookup;
+use crate::util::html_report::HtmlReport;
+use crate::util::junit::JunitReport;
+use crate::util::progress::ProgressBars;
+use crate::util::EnvironmentVariableName;
 
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
 
 /// Implementation of the "db" subcommand
-pub fn db(
+pub async fn db<F>(
     db_connection_config: DbConnectionConfig<'_>,
     config: &Configuration,
     matches: &ArgMatches,
-) -> Result<()> {
+    repo_path: &Path,
+    load_repo: F,
+    progressbars: &ProgressBars,
+) -> Result<()>
+where
+    F: FnOnce() -> Result<Repository>,
+{
     let default_limit = config.database_default_query_limit();
 
     match matches.subcommand() {
         Some(("cli", matches)) => cli(db_connection_config, matches),
         Some(("setup", _matches)) => setup(db_connection_config),
+        Some(("artifacts", matches)) if matches.get_flag("missing_on_disk") => {
+            artifacts_audit(db_connection_config, config, matches, load_repo)
+        }
         Some(("artifacts", matches)) => artifacts(db_connection_config, matches, default_limit),
+        Some(("artifact-provenance", matches)) => {
+            artifact_provenance(db_connection_config, config, matches)
+        }
         Some(("envvars", matches)) => envvars(db_connection_config, matches),
+        Some(("export-stats", matches)) => export_stats(db_connection_config, matches),
+        Some(("flaky", matches)) => flaky(db_connection_config, matches, default_limit),
+        Some(("history", matches)) => history(db_connection_config, matches),
         Some(("images", matches)) => images(db_connection_config, matches),
-        Some(("submit", matches)) => submit(db_connection_config, config, matches),
+        Some(("licenses", matches)) => licenses(db_connection_config, config, matches),
+        Some(("queued-submits", matches)) => {
+            queued_submits(db_connection_config, matches, default_limit)
+        }
+        Some(("submit", matches)) => {
+            submit(db_connection_config, config, matches, repo_path, load_repo)
+        }
         Some(("submits", matches)) => submits(db_connection_config, config, matches, default_limit),
         Some(("jobs", matches)) => jobs(db_connection_config, config, matches, default_limit),
-        Some(("job", matches)) => job(db_connection_config, config, matches),
-        Some(("log-of", matches)) => log_of(db_connection_config, matches),
+        Some(("job", matches)) => job(db_connection_config, config, matches).await,
+        Some(("log-of", matches)) => log_of(db_connection_config, config, matches),
         Some(("releases", matches)) => {
-            releases(db_connection_config, config, matches, default_limit)
+            releases(
+                db_connection_config,
+                config,
+                matches,
+                default_limit,
+                progressbars,
+            )
+            .await
         }
         Some((other, _)) => Err(anyhow!("Unknown subcommand: {}", other)),
         None => Err(anyhow!("No subcommand")),
@@ -173,6 +225,36 @@ fn get_limit(matches: &ArgMatches, default_limit: &usize) -> Result<i64> {
     }
 }
 
+/// Helper function to get the OFFSET for DB queries from the --offset or --page CLI parameters
+fn get_offset(matches: &ArgMatches, limit: i64) -> Result<i64> {
+    match matches.get_one::<u64>("page") {
+        Some(_) if limit == i64::MAX => Err(anyhow!("--page requires a LIMIT greater than 0")),
+        Some(page) => i64::try_from(page - 1)? // safe by clap: page >= 1
+            .checked_mul(limit)
+            .ok_or_else(|| anyhow!("Page {} is out of range", page)),
+        None => Ok(i64::try_from(
+            *matches.get_one::<usize>("offset").unwrap_or(&0),
+        )?),
+    }
+}
+
+/// Print which rows of a paginated listing are shown to stderr (so that CSV output stays intact)
+fn print_page_footer(offset: i64, shown: usize, total: i64) -> Result<()> {
+    let mut err = std::io::stderr();
+    if shown == 0 {
+        writeln!(err, "showing 0 of {total}")?;
+    } else {
+        writeln!(
+            err,
+            "showing {}\u{2013}{} of {}",
+            offset + 1,
+            offset + shown as i64,
+            total
+        )?;
+    }
+    Ok(())
+}
+
 /// Implementation of the "db artifacts" subcommand
 fn artifacts(
     conn_cfg: DbConnectionConfig<'_>,
@@ -182,39 +264,543 @@ fn artifacts(
     use crate::schema::artifacts::dsl;
 
     let csv = matches.get_flag("csv");
-    let job_uuid = matches.get_one::<uuid::Uuid>("job_uuid");
+    let sort_by_size = matches.get_one::<String>("sort").map(String::as_str) == Some("size");
     let limit = get_limit(matches, default_limit)?;
+    let offset = get_offset(matches, limit)?;
 
-    let hdrs = crate::commands::util::mk_header(vec!["Path", "Released", "Job"]);
+    let hdrs = crate::commands::util::mk_header(vec!["Path", "Kind", "Size", "Released", "Job"]);
     let mut conn = conn_cfg.establish_connection()?;
-    let mut query = dsl::artifacts
-        .order_by(schema::artifacts::id.desc()) // required for the --limit implementation
-        .inner_join(schema::jobs::table)
-        .left_join(schema::releases::table)
-        .into_boxed()
-        .limit(limit);
-    if let Some(job_uuid) = job_uuid {
-        query = query.filter(schema::jobs::dsl::uuid.eq(job_uuid))
+    let job_uuid = matches
+        .get_one::<String>("job_uuid")
+        .map(|id| crate::commands::ids::resolve_job_uuid(&mut conn, id))
+        .transpose()?;
+    let filtered_query = || {
+        let query = dsl::artifacts
+            .inner_join(schema::jobs::table)
+            .left_join(schema::releases::table)
+            .into_boxed();
+        if let Some(job_uuid) = job_uuid.as_ref() {
+            query.filter(schema::jobs::dsl::uuid.eq(job_uuid))
+        } else {
+            query
+        }
+    };
+
+    let total = filtered_query().count().get_result::<i64>(&mut conn)?;
+    let mut query = filtered_query().limit(limit).offset(offset);
+
+    // The order is required for the --limit implementation
+    query = if sort_by_size {
+        query.order_by((
+            schema::artifacts::size.desc().nulls_last(),
+            schema::artifacts::id.desc(),
+        ))
+    } else {
+        query.order_by(schema::artifacts::id.desc())
     };
 
     let data = query
         .load::<(models::Artifact, models::Job, Option<models::Release>)>(&mut conn)?
         .into_iter()
-        .rev() // We want the newest artifacts at the bottom (reverse the order for --limit)
+        .rev() // We want the newest (or biggest) artifacts at the bottom (reverse the order for --limit)
         .map(|(artifact, job, rel)| {
             let rel = rel
                 .map(|r| r.release_date.to_string())
                 .unwrap_or_else(|| String::from("no"));
-            vec![artifact.path, rel, job.uuid.to_string()]
+            let size = artifact
+                .size
+                .map(|s| bytesize::ByteSize::b(s as u64).to_string())
+                .unwrap_or_else(|| String::from("unknown"));
+            vec![
+                artifact.path,
+                artifact.kind,
+                size,
+                rel,
+                job.uuid.to_string(),
+            ]
         })
         .collect::<Vec<_>>();
 
-    if data.is_empty() {
+    if data.is_empty() && total == 0 {
         info!("No artifacts in database");
     } else {
+        let shown = data.len();
         crate::commands::util::display_data(hdrs, data, csv)?;
+        print_page_footer(offset, shown, total)?;
+    }
+
+    Ok(())
+}
+
+/// A problem that was found by "db artifacts --missing-on-disk"
+enum AuditFinding {
+    /// An artifact whose file is neither in the staging directory nor in a release store
+    MissingArtifact { artifact_id: i32, path: PathBuf },
+    /// A release whose file is missing in the release store
+    MissingRelease { release_id: i32, path: PathBuf },
+    /// A file in the staging directory of a submit that is not in the DB
+    OrphanStagingFile {
+        submit_uuid: uuid::Uuid,
+        path: PathBuf,
+    },
+    /// A file in a release store that is not in the DB
+    OrphanReleaseFile { path: PathBuf },
+}
+
+/// Implementation of the "db artifacts --missing-on-disk" subcommand
+fn artifacts_audit<F>(
+    conn_cfg: DbConnectionConfig<'_>,
+    config: &Configuration,
+    matches: &ArgMatches,
+    load_repo: F,
+) -> Result<()>
+where
+    F: FnOnce() -> Result<Repository>,
+{
+    let csv = matches.get_flag("csv");
+    let fixes = matches
+        .get_many::<String>("fix")
+        .map(|fixes| fixes.map(String::as_str).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let mut conn = conn_cfg.establish_connection()?;
+
+    // The artifacts of local builds are not in the staging directories of the submits
+    let artifacts = schema::artifacts::table
+        .inner_join(
+            schema::jobs::table
+                .inner_join(schema::submits::table)
+                .inner_join(schema::endpoints::table),
+        )
+        .filter(schema::endpoints::name.ne(crate::consts::LOCAL_ENDPOINT_NAME))
+        .select((schema::artifacts::all_columns, schema::submits::uuid))
+        .load::<(models::Artifact, uuid::Uuid)>(&mut conn)?;
+    let releases = schema::releases::table
+        .inner_join(schema::release_stores::table)
+        .inner_join(schema::artifacts::table)
+        .select((
+            schema::releases::all_columns,
+            schema::release_stores::store_name,
+            schema::artifacts::all_columns,
+        ))
+        .load::<(models::Release, String, models::Artifact)>(&mut conn)?;
+
+    let mut findings = Vec::new();
+
+    // An artifact whose staging file was removed is fine as long as one of its releases exists:
+    let mut released_artifacts = HashSet::new();
+    let mut release_files = HashSet::new();
+    for (release, store_name, artifact) in releases.iter() {
+        let path = config
+            .releases_directory()
+            .join(store_name)
+            .join(release.path_in_store(artifact));
+        if path.is_file() {
+            released_artifacts.insert(artifact.id);
+        } else {
+            findings.push(AuditFinding::MissingRelease {
+                release_id: release.id,
+                path: path.clone(),
+            });
+        }
+
+        // The provenance attestation of a release is not an orphan either
+        release_files.insert(crate::util::provenance::attestation_path(&path));
+        release_files.insert(path);
+    }
+
+    let mut staging_files = HashSet::new();
+    for (artifact, submit_uuid) in artifacts.iter() {
+        let path = config
+            .staging_directory()
+            .join(submit_uuid.to_string())
+            .join(&artifact.path);
+        if !path.is_file() && !released_artifacts.contains(&artifact.id) {
+            findings.push(AuditFinding::MissingArtifact {
+                artifact_id: artifact.id,
+                path: path.clone(),
+            });
+        }
+        staging_files.insert(path);
+    }
+
+    for (submit_uuid, path) in files_in_staging_directory(config.staging_directory())? {
+        if !staging_files.contains(&path) {
+            findings.push(AuditFinding::OrphanStagingFile { submit_uuid, path });
+        }
+    }
+
+    for store_name in config.release_stores() {
+        let store_dir = config.releases_directory().join(store_name);
+        if !store_dir.is_dir() {
+            debug!("Release store {} does not exist yet", store_dir.display());
+            continue;
+        }
+        for path in files_in_release_store(&store_dir)? {
+            if !release_files.contains(&path) {
+                findings.push(AuditFinding::OrphanReleaseFile { path });
+            }
+        }
+    }
+
+    if findings.is_empty() {
+        info!("All artifacts and releases in the database are on disk and vice versa");
+        return Ok(());
+    }
+
+    let hdrs = crate::commands::util::mk_header(vec!["Problem", "Path", "Row"]);
+    let data = findings
+        .iter()
+        .map(|finding| {
+            let (problem, path, row) = match finding {
+                AuditFinding::MissingArtifact { artifact_id, path } => {
+                    ("missing file", path, format!("artifact {artifact_id}"))
+                }
+                AuditFinding::MissingRelease { release_id, path } => {
+                    ("missing file", path, format!("release {release_id}"))
+                }
+                AuditFinding::OrphanStagingFile { path, .. } => {
+                    ("not in DB", path, String::from("-"))
+                }
+                AuditFinding::OrphanReleaseFile { path } => ("not in DB", path, String::from("-")),
+            };
+            vec![problem.to_string(), path.display().to_string(), row]
+        })
+        .collect::<Vec<_>>();
+    crate::commands::util::display_data(hdrs, data, csv)?;
+
+    if fixes.contains(&"delete-orphan-rows") {
+        delete_orphan_rows(&mut conn, &findings)?;
+    }
+    if fixes.contains(&"register-orphan-files") {
+        register_orphan_files(&mut conn, config, &findings, load_repo()?)?;
+    }
+
+    Ok(())
+}
+
+/// All files in the staging directories of the submits, with the UUID of the submit
+fn files_in_staging_directory(staging_dir: &Path) -> Result<Vec<(uuid::Uuid, PathBuf)>> {
+    let mut files = Vec::new();
+    let entries = std::fs::read_dir(staging_dir)
+        .with_context(|| anyhow!("Reading {}", staging_dir.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let submit_uuid = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| uuid::Uuid::parse_str(name).ok());
+        let Some(submit_uuid) = submit_uuid.filter(|_| entry.path().is_dir()) else {
+            debug!(
+                "Not a staging directory of a submit: {}",
+                entry.path().display()
+            );
+            continue;
+        };
+
+        for file in walkdir::WalkDir::new(entry.path()).follow_links(false) {
+            let file = file?;
+            if file.file_type().is_file() {
+                files.push((submit_uuid, file.into_path()));
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// All files in a release store, without the temporary directories of releases in progress
+fn files_in_release_store(store_dir: &Path) -> Result<Vec<PathBuf>> {
+    walkdir::WalkDir::new(store_dir)
+        .follow_links(false)
+        .into_iter()
+        .filter_entry(|e| {
+            let is_release_tmp_dir = e.depth() == 1
+                && e.file_type().is_dir()
+                && e.file_name()
+                    .to_str()
+                    .is_some_and(|n| n.starts_with(crate::consts::RELEASE_TMP_DIR_PREFIX));
+            !is_release_tmp_dir
+        })
+        .filter_ok(|e| e.file_type().is_file())
+        .map_ok(walkdir::DirEntry::into_path)
+        .map(|r| r.map_err(Error::from))
+        .collect()
+}
+
+/// Delete the artifacts and releases whose files are missing from the DB
+fn delete_orphan_rows(conn: &mut PgConnection, findings: &[AuditFinding]) -> Result<()> {
+    let release_ids = findings
+        .iter()
+        .filter_map(|finding| match finding {
+            AuditFinding::MissingRelease { release_id, .. } => Some(*release_id),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let artifact_ids = findings
+        .iter()
+        .filter_map(|finding| match finding {
+            AuditFinding::MissingArtifact { artifact_id, .. } => Some(*artifact_id),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    conn.transaction::<_, Error, _>(|conn| {
+        // The releases of the deleted artifacts are missing as well, otherwise the artifacts
+        // wouldn't be missing:
+        let deleted_releases = diesel::delete(
+            schema::releases::table.filter(
+                schema::releases::id
+                    .eq_any(&release_ids)
+                    .or(schema::releases::artifact_id.eq_any(&artifact_ids)),
+            ),
+        )
+        .execute(conn)?;
+        let deleted_artifacts = diesel::delete(
+            schema::artifacts::table.filter(schema::artifacts::id.eq_any(&artifact_ids)),
+        )
+        .execute(conn)?;
+
+        info!(
+            "Deleted {} artifacts and {} releases from the database",
+            deleted_artifacts, deleted_releases
+        );
+        Ok(())
+    })
+}
+
+/// Add the files in the staging directories that are not in the DB as artifacts
+fn register_orphan_files(
+    conn: &mut PgConnection,
+    config: &Configuration,
+    findings: &[AuditFinding],
+    repo: Repository,
+) -> Result<()> {
+    let mut registered = 0;
+    for finding in findings {
+        let (submit_uuid, path) = match finding {
+            AuditFinding::OrphanStagingFile { submit_uuid, path } => (submit_uuid, path),
+            AuditFinding::OrphanReleaseFile { path } => {
+                warn!(
+                    "Cannot register {}, files in release stores can only be added by a release",
+                    path.display()
+                );
+                continue;
+            }
+            _ => continue,
+        };
+
+        let submit_dir = config.staging_directory().join(submit_uuid.to_string());
+        let art_path = ArtifactPath::new(path.strip_prefix(&submit_dir)?.to_path_buf())?;
+        let jobs = schema::jobs::table
+            .inner_join(schema::submits::table)
+            .inner_join(schema::packages::table)
+            .filter(schema::submits::uuid.eq(submit_uuid))
+            .select((schema::jobs::all_columns, schema::packages::all_columns))
+            .load::<(models::Job, models::Package)>(conn)?;
+        let Some((job, package)) = producing_job(&jobs, &art_path) else {
+            warn!(
+                "Cannot determine the job of submit {} that produced {}, not registering it",
+                submit_uuid,
+                path.display()
+            );
+            continue;
+        };
+
+        let size = std::fs::metadata(path)
+            .with_context(|| anyhow!("Getting size of {}", path.display()))?
+            .len();
+        let sha256 = {
+            use sha2::Digest;
+            let mut file =
+                std::fs::File::open(path).with_context(|| anyhow!("Opening {}", path.display()))?;
+            let mut hasher = sha2::Sha256::new();
+            std::io::copy(&mut file, &mut hasher)
+                .with_context(|| anyhow!("Hashing {}", path.display()))?;
+            format!("{:x}", hasher.finalize())
+        };
+        let kind = repo
+            .find(
+                &PackageName::from(package.name.clone()),
+                &PackageVersion::from(package.version.clone()),
+            )
+            .into_iter()
+            .next()
+            .map(|p| p.artifact_kind(art_path.as_ref()).to_string())
+            .unwrap_or_else(|| crate::package::DEFAULT_ARTIFACT_KIND.to_string());
+
+        models::Artifact::create(conn, &art_path, job, Some(size), &kind, Some(&sha256))?;
+        debug!(
+            "Registered {} as artifact of job {}",
+            path.display(),
+            job.uuid
+        );
+        registered += 1;
     }
 
+    info!(
+        "Registered {} files as artifacts in the database",
+        registered
+    );
+    Ok(())
+}
+
+/// The job of a submit that produced an artifact
+///
+/// That is the job that reported an artifact with the same file name, or the only job of the
+/// submit.
+fn producing_job<'a>(
+    jobs: &'a [(models::Job, models::Package)],
+    art_path: &ArtifactPath,
+) -> Option<&'a (models::Job, models::Package)> {
+    let file_name = art_path.file_name()?;
+    let mut reporting = jobs.iter().filter(|(job, _)| {
+        job.reported_artifacts
+            .iter()
+            .any(|reported| Path::new(reported).file_name() == Some(file_name))
+    });
+
+    match (reporting.next(), reporting.next()) {
+        (Some(job), None) => Some(job),
+        (None, _) if jobs.len() == 1 => jobs.first(),
+        _ => None,
+    }
+}
+
+/// Implementation of the "db artifact-provenance" subcommand
+fn artifact_provenance(
+    conn_cfg: DbConnectionConfig<'_>,
+    config: &Configuration,
+    matches: &ArgMatches,
+) -> Result<()> {
+    let mut conn = conn_cfg.establish_connection()?;
+    let path = matches.get_one::<String>("artifact").unwrap(); // safe by clap
+    let artifact = schema::artifacts::table
+        .left_join(schema::releases::table)
+        .filter(
+            schema::artifacts::path
+                .eq(path)
+                .or(schema::releases::path.eq(path)),
+        )
+        .select(schema::artifacts::all_columns)
+        .order_by(schema::artifacts::id.desc())
+        .first::<models::Artifact>(&mut conn)
+        .optional()?
+        .ok_or_else(|| anyhow!("No artifact found: {}", path))?;
+
+    let mut out = std::io::stdout();
+    if matches.get_flag("attestation") {
+        let attestation = schema::release_attestations::table
+            .inner_join(schema::releases::table)
+            .filter(schema::releases::artifact_id.eq(artifact.id))
+            .order_by(schema::releases::release_date.desc())
+            .select(schema::release_attestations::all_columns)
+            .first::<models::ReleaseAttestation>(&mut conn)
+            .optional()?
+            .ok_or_else(|| anyhow!("No provenance attestation recorded for {}", artifact.path))?;
+        writeln!(out, "{}", attestation.envelope)?;
+        return Ok(());
+    }
+
+    let (job, submit, githash, package, image) = schema::jobs::table
+        .inner_join(schema::submits::table.inner_join(schema::githashes::table))
+        .inner_join(schema::packages::table)
+        .inner_join(schema::images::table)
+        .filter(schema::jobs::id.eq(artifact.job_id))
+        .select((
+            schema::jobs::all_columns,
+            schema::submits::all_columns,
+            schema::githashes::all_columns,
+            schema::packages::all_columns,
+            schema::images::all_columns,
+        ))
+        .first::<(
+            models::Job,
+            models::Submit,
+            models::GitHash,
+            models::Package,
+            models::Image,
+        )>(&mut conn)?;
+    let inputs = crate::util::provenance::input_artifacts(&mut conn, config, &job, submit.id)?;
+    let releases = schema::releases::table
+        .inner_join(schema::release_stores::table)
+        .filter(schema::releases::artifact_id.eq(artifact.id))
+        .order_by(schema::releases::release_date)
+        .load::<(models::Release, models::ReleaseStore)>(&mut conn)?
+        .into_iter()
+        .map(|(release, store)| {
+            let attestation = match models::ReleaseAttestation::of_release(&mut conn, &release)? {
+                Some(attestation) if attestation.signed => "signed attestation".green(),
+                Some(_) => "unsigned attestation".yellow(),
+                None => "no attestation".normal(),
+            };
+            Ok(format!(
+                "\t{} {}/{} ({})",
+                release.release_date,
+                store.store_name,
+                release.path_in_store(&artifact),
+                attestation
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let s = indoc::formatdoc!(
+        r#"
+            Artifact:   {artifact_path}
+            SHA-256:    {sha256}
+            Package:    {package_name} {package_version}
+            Job:        {job_uuid}
+            Submit:     {submit_uuid}
+            Commit:     {commit}
+            Image:      {image_name}
+            Digest:     {image_digest}
+            Started:    {started_at}
+            Finished:   {finished_at}
+            Butido:     {butido_version}
+        "#,
+        artifact_path = artifact.path.cyan(),
+        sha256 = artifact.sha256.as_deref().unwrap_or("unknown").cyan(),
+        package_name = package.name.cyan(),
+        package_version = package.version.cyan(),
+        job_uuid = job.uuid.to_string().cyan(),
+        submit_uuid = submit.uuid.to_string().cyan(),
+        commit = githash.hash.cyan(),
+        image_name = image.name.cyan(),
+        image_digest = job.image_digest.as_deref().unwrap_or("unknown").cyan(),
+        started_at = job
+            .started_at
+            .map(|t| t.to_string())
+            .unwrap_or_else(|| String::from("unknown"))
+            .cyan(),
+        finished_at = job
+            .finished_at
+            .map(|t| t.to_string())
+            .unwrap_or_else(|| String::from("unknown"))
+            .cyan(),
+        butido_version = job.butido_version.as_deref().unwrap_or("unknown").cyan(),
+    );
+    writeln!(out, "{s}")?;
+
+    if inputs.is_empty() {
+        writeln!(out, "Inputs:     none recorded")?;
+    } else {
+        writeln!(out, "Inputs:")?;
+        for input in inputs {
+            writeln!(
+                out,
+                "\t{} ({})",
+                input.path,
+                input.sha256.as_deref().unwrap_or("hash unknown")
+            )?;
+        }
+    }
+
+    if releases.is_empty() {
+        writeln!(out, "Releases:   not released")?;
+    } else {
+        writeln!(out, "Releases:")?;
+        for release in releases {
+            writeln!(out, "{release}")?;
+        }
+    }
     Ok(())
 }
 
@@ -240,6 +826,506 @@ fn envvars(conn_cfg: DbConnectionConfig<'_>, matches: &ArgMatches) -> Result<()>
     Ok(())
 }
 
+/// A package that was found by "db flaky"
+struct FlakyPackage {
+    name: String,
+    version: String,
+    jobs: usize,
+    failed: usize,
+    /// How often the result changed between two jobs with the same image and repository commit
+    flips: usize,
+    /// The number of pairs of consecutive jobs with the same image and repository commit
+    comparisons: usize,
+    failed_jobs: Vec<uuid::Uuid>,
+}
+
+impl FlakyPackage {
+    fn flakiness(&self) -> f64 {
+        self.flips as f64 / self.comparisons.max(1) as f64
+    }
+}
+
+/// One record of "db export-stats"
+#[derive(serde::Serialize)]
+struct JobStatistics {
+    job_uuid: uuid::Uuid,
+    submit_uuid: uuid::Uuid,
+    package: String,
+    version: String,
+    image: String,
+    endpoint: String,
+    started_at: Option<String>,
+    finished_at: Option<String>,
+    duration_secs: Option<i64>,
+    status: &'static str,
+    artifact_size: u64,
+}
+
+/// Implementation of the "db export-stats" subcommand
+fn export_stats(conn_cfg: DbConnectionConfig<'_>, matches: &ArgMatches) -> Result<()> {
+    let since = get_date_filter("newer_than", matches)?;
+    let format = matches.get_one::<String>("format").unwrap(); // safe by clap default
+    let mut conn = conn_cfg.establish_connection()?;
+
+    let mut query = schema::jobs::table
+        .inner_join(schema::submits::table)
+        .inner_join(schema::packages::table)
+        .inner_join(schema::images::table)
+        .inner_join(schema::endpoints::table)
+        .order_by(schema::jobs::id.asc())
+        .into_boxed();
+    if let Some(since) = since.as_ref() {
+        query = query.filter(schema::submits::submit_time.gt(since));
+    }
+    let jobs = query
+        .select((
+            schema::jobs::all_columns,
+            schema::submits::uuid,
+            schema::packages::all_columns,
+            schema::images::name,
+            schema::endpoints::name,
+        ))
+        .load::<(models::Job, uuid::Uuid, models::Package, String, String)>(&mut conn)?;
+
+    let job_ids = jobs.iter().map(|(job, ..)| job.id).collect::<Vec<_>>();
+    let mut artifact_sizes = std::collections::HashMap::<i32, u64>::new();
+    for (job_id, size) in schema::artifacts::table
+        .filter(schema::artifacts::job_id.eq_any(&job_ids))
+        .select((schema::artifacts::job_id, schema::artifacts::size))
+        .load::<(i32, Option<i64>)>(&mut conn)?
+    {
+        *artifact_sizes.entry(job_id).or_default() +=
+            size.and_then(|s| u64::try_from(s).ok()).unwrap_or(0);
+    }
+
+    let records = jobs
+        .into_iter()
+        .map(|(job, submit_uuid, package, image, endpoint)| {
+            let status = if job.retried {
+                "retried"
+            } else {
+                match is_job_successfull(&job)? {
+                    Some(true) => "success",
+                    Some(false) => "failure",
+                    None => "unknown",
+                }
+            };
+
+            Ok(JobStatistics {
+                job_uuid: job.uuid,
+                submit_uuid,
+                package: package.name,
+                version: package.version,
+                image,
+                endpoint,
+                started_at: job
+                    .started_at
+                    .map(|t| t.format("%Y-%m-%dT%H:%M:%S").to_string()),
+                finished_at: job
+                    .finished_at
+                    .map(|t| t.format("%Y-%m-%dT%H:%M:%S").to_string()),
+                duration_secs: job.duration().map(|d| d.num_seconds()),
+                status,
+                artifact_size: artifact_sizes.get(&job.id).copied().unwrap_or(0),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let out: Box<dyn Write> = match matches.get_one::<PathBuf>("output") {
+        Some(path) => Box::new(std::io::BufWriter::new(
+            std::fs::File::create(path).with_context(|| anyhow!("Creating {}", path.display()))?,
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    write_job_statistics(out, &records, format)?;
+    info!("Exported {} jobs", records.len());
+    Ok(())
+}
+
+/// Write the records of "db export-stats" in the requested format ("csv" or "json")
+fn write_job_statistics<W: Write>(
+    mut out: W,
+    records: &[JobStatistics],
+    format: &str,
+) -> Result<()> {
+    match format {
+        "csv" => {
+            let mut writer = csv::Writer::from_writer(out);
+            for record in records {
+                writer.serialize(record)?;
+            }
+            writer.flush()?;
+        }
+        "json" => {
+            for record in records {
+                serde_json::to_writer(&mut out, record)?;
+                writeln!(out)?;
+            }
+            out.flush()?;
+        }
+        other => return Err(anyhow!("Unknown export format: {}", other)),
+    }
+    Ok(())
+}
+
+/// Implementation of the "db flaky" subcommand
+fn flaky(
+    conn_cfg: DbConnectionConfig<'_>,
+    matches: &ArgMatches,
+    default_limit: &usize,
+) -> Result<()> {
+    let csv = matches.get_flag("csv");
+    let limit = usize::try_from(get_limit(matches, default_limit)?).unwrap_or(usize::MAX);
+    let since =
+        get_date_filter("newer_than", matches)?.ok_or_else(|| anyhow!("No time to start from"))?; // safe by clap default
+    let mut conn = conn_cfg.establish_connection()?;
+
+    let jobs = schema::jobs::table
+        .inner_join(schema::submits::table)
+        .inner_join(schema::packages::table)
+        .filter(schema::submits::submit_time.gt(&since))
+        .select((
+            schema::jobs::all_columns,
+            schema::submits::all_columns,
+            schema::packages::all_columns,
+        ))
+        .load::<(models::Job, models::Submit, models::Package)>(&mut conn)?;
+
+    // The results of the jobs per package, image and repository commit, in chronological order
+    let mut runs = std::collections::BTreeMap::<_, Vec<_>>::new();
+    for (job, submit, package) in jobs {
+        // Retried attempts failed because of the infrastructure, not because of the package
+        if job.retried {
+            continue;
+        }
+        let Some(success) = is_job_successfull(&job)? else {
+            continue;
+        };
+        runs.entry((
+            package.name,
+            package.version,
+            job.image_id,
+            submit.repo_hash_id,
+        ))
+        .or_default()
+        .push((
+            job.started_at.unwrap_or(submit.submit_time),
+            job.id,
+            job.uuid,
+            success,
+        ));
+    }
+
+    let mut packages = Vec::<FlakyPackage>::new();
+    for ((name, version, _, _), mut results) in runs {
+        results.sort_by_key(|(time, id, _, _)| (*time, *id));
+
+        let package = match packages.last_mut() {
+            Some(last) if last.name == name && last.version == version => last,
+            _ => {
+                packages.push(FlakyPackage {
+                    name,
+                    version,
+                    jobs: 0,
+                    failed: 0,
+                    flips: 0,
+                    comparisons: 0,
+                    failed_jobs: Vec::new(),
+                });
+                packages.last_mut().unwrap() // just pushed
+            }
+        };
+
+        package.jobs += results.len();
+        package.comparisons += results.len() - 1;
+        package.flips += results
+            .iter()
+            .tuple_windows()
+            .filter(|(a, b)| a.3 != b.3)
+            .count();
+        for (_, _, job_uuid, _) in results.iter().filter(|r| !r.3) {
+            package.failed += 1;
+            package.failed_jobs.push(*job_uuid);
+        }
+    }
+
+    packages.retain(|p| p.flips > 0);
+    packages.sort_by(|a, b| {
+        b.flakiness()
+            .total_cmp(&a.flakiness())
+            .then_with(|| b.flips.cmp(&a.flips))
+    });
+
+    if packages.is_empty() {
+        info!("No flaky packages found");
+        return Ok(());
+    }
+
+    let hdrs = crate::commands::util::mk_header(vec![
+        "Package",
+        "Version",
+        "Flakiness",
+        "Flips",
+        "Jobs",
+        "Failed",
+        "Failed jobs (examples)",
+    ]);
+    let data = packages
+        .into_iter()
+        .take(limit)
+        .map(|p| {
+            vec![
+                p.name.clone(),
+                p.version.clone(),
+                format!("{:.0}%", p.flakiness() * 100.0),
+                p.flips.to_string(),
+                p.jobs.to_string(),
+                p.failed.to_string(),
+                p.failed_jobs.iter().rev().take(3).join(" "),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    crate::commands::util::display_data(hdrs, data, csv)
+}
+
+/// Implementation of the "db history" subcommand
+fn history(conn_cfg: DbConnectionConfig<'_>, matches: &ArgMatches) -> Result<()> {
+    let csv = matches.get_flag("csv");
+    let graph = matches.get_flag("graph");
+    let package_name = matches.get_one::<String>("package_name").unwrap(); // safe by clap
+    let package_version_constraint = matches
+        .get_one::<String>("package_version_constraint")
+        .map(|s| s.to_owned())
+        .map(PackageVersionConstraint::try_from)
+        .transpose()
+        .context("Parsing package version constraint")?;
+    let mut conn = conn_cfg.establish_connection()?;
+
+    let jobs = schema::jobs::table
+        .inner_join(schema::submits::table)
+        .inner_join(schema::packages::table)
+        .inner_join(schema::images::table)
+        .filter(schema::packages::name.eq(package_name))
+        .select((
+            schema::jobs::all_columns,
+            schema::submits::all_columns,
+            schema::packages::all_columns,
+            schema::images::all_columns,
+        ))
+        .load::<(models::Job, models::Submit, models::Package, models::Image)>(&mut conn)?
+        .into_iter()
+        .filter(|(_, _, package, _)| {
+            package_version_constraint
+                .as_ref()
+                .map(|c| c.matches(&PackageVersion::from(package.version.clone())))
+                .unwrap_or(true)
+        })
+        .sorted_by_key(|(job, submit, _, _)| (job.started_at.unwrap_or(submit.submit_time), job.id))
+        .collect::<Vec<_>>();
+
+    if jobs.is_empty() {
+        info!("No jobs found for package {}", package_name);
+        return Ok(());
+    }
+
+    // The total size of the artifacts and the date of the first release, per job
+    let job_ids = jobs.iter().map(|(job, _, _, _)| job.id).collect::<Vec<_>>();
+    let mut artifacts =
+        std::collections::HashMap::<i32, (Option<u64>, Option<chrono::NaiveDateTime>)>::new();
+    let artifact_rows = schema::artifacts::table
+        .left_join(schema::releases::table)
+        .filter(schema::artifacts::job_id.eq_any(&job_ids))
+        .select((
+            schema::artifacts::id,
+            schema::artifacts::job_id,
+            schema::artifacts::size,
+            schema::releases::release_date.nullable(),
+        ))
+        .load::<(i32, i32, Option<i64>, Option<chrono::NaiveDateTime>)>(&mut conn)?;
+    let mut counted = HashSet::new();
+    for (artifact_id, job_id, size, release_date) in artifact_rows {
+        let (total_size, first_release) = artifacts.entry(job_id).or_default();
+        // An artifact that was released multiple times is listed once per release
+        if counted.insert(artifact_id) {
+            if let Some(size) = size {
+                *total_size = Some(total_size.unwrap_or(0) + size as u64);
+            }
+        }
+        *first_release = match (*first_release, release_date) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+    }
+
+    let mut sizes = Vec::with_capacity(jobs.len());
+    let mut results = Vec::with_capacity(jobs.len());
+    let mut data = Vec::with_capacity(jobs.len());
+    for (job, submit, package, image) in jobs {
+        let success = is_job_successfull(&job)?;
+        let (size, released) = artifacts.get(&job.id).copied().unwrap_or_default();
+        sizes.push(size);
+        results.push(success);
+
+        data.push(vec![
+            job.started_at.unwrap_or(submit.submit_time).to_string(),
+            package.version,
+            image.name,
+            match success {
+                Some(true) => String::from("success"),
+                Some(false) => String::from("failure"),
+                None => String::from("unknown"),
+            },
+            size.map(|s| bytesize::ByteSize::b(s).to_string())
+                .unwrap_or_else(|| String::from("unknown")),
+            released
+                .map(|d| d.to_string())
+                .unwrap_or_else(|| String::from("no")),
+            submit.uuid.to_string(),
+            job.uuid.to_string(),
+        ]);
+    }
+
+    let hdrs = crate::commands::util::mk_header(vec![
+        "Time",
+        "Version",
+        "Image",
+        "Result",
+        "Artifacts size",
+        "Released",
+        "Submit",
+        "Job",
+    ]);
+    crate::commands::util::display_data(hdrs, data, csv)?;
+
+    if graph {
+        let timeline = results
+            .iter()
+            .map(|success| match success {
+                Some(true) => "+".green().to_string(),
+                Some(false) => "-".red().to_string(),
+                None => String::from("?"),
+            })
+            .join("");
+        let mut out = std::io::stdout().lock();
+        writeln!(out, "Results:        {timeline}")?;
+        writeln!(
+            out,
+            "Artifacts size: {}",
+            crate::commands::util::sparkline(&sizes)
+        )?;
+    }
+    Ok(())
+}
+
+/// A package of the license report of a submit (`db licenses`)
+#[derive(serde::Serialize)]
+struct LicenseReportEntry {
+    name: String,
+    version: String,
+    license: Option<String>,
+    maintainer: Option<String>,
+    status: &'static str,
+    denied: Vec<String>,
+}
+
+/// Implementation of the "db licenses" subcommand
+fn licenses(
+    conn_cfg: DbConnectionConfig<'_>,
+    config: &Configuration,
+    matches: &ArgMatches,
+) -> Result<()> {
+    let csv = matches.get_flag("csv");
+    let mut conn = conn_cfg.establish_connection()?;
+    let submit_uuid = &crate::commands::ids::submit_uuid(&mut conn, matches, "submit")?;
+
+    let submit = models::Submit::with_id(&mut conn, submit_uuid)?;
+    let requested_package =
+        models::Package::fetch_by_id(&mut conn, submit.requested_package_id)?
+            .ok_or_else(|| anyhow!("Package for submit {} not found", submit.uuid))?;
+    let packages = schema::jobs::table
+        .inner_join(schema::packages::table)
+        .filter(schema::jobs::submit_id.eq(submit.id))
+        .select(schema::packages::all_columns)
+        .load::<models::Package>(&mut conn)?
+        .into_iter()
+        .chain(std::iter::once(requested_package))
+        .unique_by(|package| package.id)
+        .sorted_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+
+    let report = packages
+        .map(|package| {
+            let denied = package
+                .license
+                .as_deref()
+                .map(|license| crate::package::denied_licenses(license, config.license_deny_list()))
+                .unwrap_or_default()
+                .into_iter()
+                .map(String::from)
+                .collect::<Vec<_>>();
+            let status = match package.license.as_deref() {
+                None | Some("") => "missing",
+                Some(_) if !denied.is_empty() => "denied",
+                Some(_) => "ok",
+            };
+
+            LicenseReportEntry {
+                name: package.name,
+                version: package.version,
+                license: package.license,
+                maintainer: package.maintainer,
+                status,
+                denied,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if matches.get_flag("json") {
+        let out = std::io::stdout();
+        let mut outlock = out.lock();
+        serde_json::to_writer_pretty(&mut outlock, &report)?;
+        writeln!(outlock)?;
+        return Ok(());
+    }
+
+    let flagged = report.iter().filter(|entry| entry.status != "ok").count();
+    let hdrs = crate::commands::util::mk_header(vec![
+        "Package",
+        "Version",
+        "License",
+        "Maintainer",
+        "Status",
+    ]);
+    let data = report
+        .into_iter()
+        .map(|entry| {
+            let status = if entry.status == "denied" {
+                format!("denied ({})", entry.denied.join(", "))
+            } else {
+                entry.status.to_string()
+            };
+            let status = match (csv, entry.status) {
+                (true, _) | (false, "ok") => status,
+                (false, _) => status.red().to_string(),
+            };
+
+            vec![
+                entry.name,
+                entry.version,
+                entry.license.unwrap_or_default(),
+                entry.maintainer.unwrap_or_default(),
+                status,
+            ]
+        })
+        .collect::<Vec<_>>();
+    crate::commands::util::display_data(hdrs, data, csv)?;
+
+    if flagged > 0 && !csv {
+        warn!("{} packages with missing or denied licenses", flagged);
+    }
+    Ok(())
+}
+
 /// Implementation of the "db images" subcommand
 fn images(conn_cfg: DbConnectionConfig<'_>, matches: &ArgMatches) -> Result<()> {
     use crate::schema::images::dsl;
@@ -262,14 +1348,69 @@ fn images(conn_cfg: DbConnectionConfig<'_>, matches: &ArgMatches) -> Result<()>
     Ok(())
 }
 
+/// Implementation of the "db queued-submits" subcommand
+fn queued_submits(
+    conn_cfg: DbConnectionConfig<'_>,
+    matches: &ArgMatches,
+    default_limit: &usize,
+) -> Result<()> {
+    let csv = matches.get_flag("csv");
+    let limit = get_limit(matches, default_limit)?;
+    let hdrs = crate::commands::util::mk_header(vec![
+        "UUID",
+        "State",
+        "Enqueued",
+        "Started",
+        "Finished",
+        "Commit",
+        "Arguments",
+    ]);
+    let mut conn = conn_cfg.establish_connection()?;
+
+    let fmt_time =
+        |time: Option<chrono::NaiveDateTime>| time.map(|time| time.to_string()).unwrap_or_default();
+    let data = schema::queued_submits::table
+        .inner_join(schema::githashes::table)
+        .order_by(schema::queued_submits::id.desc()) // required for the --limit implementation
+        .limit(limit)
+        .load::<(models::QueuedSubmit, models::GitHash)>(&mut conn)?
+        .into_iter()
+        .rev()
+        .map(|(queued, githash)| {
+            vec![
+                queued.uuid.to_string(),
+                queued.state,
+                queued.enqueued_at.to_string(),
+                fmt_time(queued.started_at),
+                fmt_time(queued.finished_at),
+                githash.hash,
+                queued.arguments.join(" "),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    if data.is_empty() {
+        info!("No queued submits in database");
+    } else {
+        crate::commands::util::display_data(hdrs, data, csv)?;
+    }
+
+    Ok(())
+}
+
 /// Implementation of the "db submit" subcommand
-fn submit(
+fn submit<F>(
     conn_cfg: DbConnectionConfig<'_>,
     config: &Configuration,
     matches: &ArgMatches,
-) -> Result<()> {
+    repo_path: &Path,
+    load_repo: F,
+) -> Result<()>
+where
+    F: FnOnce() -> Result<Repository>,
+{
     let mut conn = conn_cfg.establish_connection()?;
-    let submit_id = matches.get_one::<uuid::Uuid>("submit").unwrap(); // safe by clap
+    let submit_id = &crate::commands::ids::submit_uuid(&mut conn, matches, "submit")?;
 
     let submit = models::Submit::with_id(&mut conn, submit_id)
         .with_context(|| anyhow!("Loading submit '{}' from DB", submit_id))?;
@@ -284,17 +1425,22 @@ fn submit(
         .load::<models::Job>(&mut conn)
         .with_context(|| anyhow!("Loading jobs for submit = {}", submit_id))?;
 
-    let n_jobs = jobs.len();
+    let job_results = jobs_successfull(&jobs.iter().collect::<Vec<_>>())?;
+    let n_jobs = jobs.iter().filter(|j| !j.retried).count();
     let (jobs_unknown, jobs_success, jobs_err) = {
         let mut unkn = 0;
         let mut succ = 0;
         let mut err = 0;
 
-        for j in jobs.iter() {
-            match crate::log::ParsedLog::from_str(&j.log_text)?.is_successfull() {
-                JobResult::Unknown => unkn += 1,
-                JobResult::Success => succ += 1,
-                JobResult::Errored => err += 1,
+        for (_, result) in jobs
+            .iter()
+            .zip(job_results.iter())
+            .filter(|(j, _)| !j.retried)
+        {
+            match result {
+                None => unkn += 1,
+                Some(true) => succ += 1,
+                Some(false) => err += 1,
             }
         }
 
@@ -310,6 +1456,7 @@ fn submit(
             Submit   {submit_id}
             Date:    {submit_dt}
             Commit:  {submit_commit}
+            State:   {submit_state}
             Jobs:    {n_jobs}
             Success: {n_jobs_success}
             Unknown: {n_jobs_unknown}
@@ -319,18 +1466,81 @@ fn submit(
         submit_id = submit.uuid.to_string().cyan(),
         submit_dt = submit.submit_time.to_string().cyan(),
         submit_commit = githash.hash.cyan(),
+        submit_state = submit.state.cyan(),
         n_jobs = n_jobs.to_string().cyan(),
         n_jobs_success = jobs_success.to_string().green(),
         n_jobs_unknown = jobs_unknown.to_string().red(),
         n_jobs_err = jobs_err.to_string().red(),
     )?;
 
+    let failure_classes = jobs
+        .iter()
+        .filter_map(|job| job.failure_class.as_deref())
+        .counts();
+    if !failure_classes.is_empty() {
+        let failure_classes = failure_classes
+            .into_iter()
+            .sorted()
+            .map(|(class, n)| format!("{class}: {n}"))
+            .join(", ");
+        writeln!(outlock, "Failures: {}\n", failure_classes.yellow())?;
+    }
+
+    if !submit.phase_modifications.is_empty() {
+        writeln!(
+            outlock,
+            "Modified phases: {}\n",
+            submit.phase_modifications.join(", ").yellow()
+        )?;
+    }
+
+    let reused_artifacts = models::ReusedArtifact::of_submit(&mut conn, &submit)?;
+    if !reused_artifacts.is_empty() {
+        writeln!(outlock, "Reused artifacts:")?;
+        for (reused, artifact, source_submit_uuid) in reused_artifacts {
+            writeln!(
+                outlock,
+                "\t{} (job {}, built by submit {}, policy {})",
+                artifact.path.cyan(),
+                reused.job_uuid,
+                source_submit_uuid,
+                reused.policy
+            )?;
+        }
+        writeln!(outlock)?;
+    }
+
+    if let Some(path) = matches.get_one::<PathBuf>("junit_out") {
+        JunitReport::for_submit(&mut conn, &submit, *config.build_error_lines())?
+            .write_to_file(path)?;
+        writeln!(outlock, "JUnit report written to {}", path.display())?;
+    }
+
+    if let Some(path) = matches.get_one::<PathBuf>("html_out") {
+        HtmlReport::for_submit(&mut conn, config, &submit, *config.build_error_lines())?
+            .write_to_file(path)?;
+        writeln!(outlock, "HTML report written to {}", path.display())?;
+    }
+
     let image_name_lookup = ImageNameLookup::create(config.docker().images())?;
 
+    if matches.get_flag("schedule") {
+        return submit_schedule(
+            &mut conn,
+            &submit,
+            &githash,
+            &jobs,
+            &image_name_lookup,
+            repo_path,
+            load_repo,
+        );
+    }
+
     let header = crate::commands::util::mk_header(
         [
             "Job",
             "Success",
+            "Failure",
             "Package",
             "Version",
             "Container",
@@ -339,31 +1549,167 @@ fn submit(
         ]
         .to_vec(),
     );
-    let data = jobs
+    let data = jobs.iter().zip(job_results).map(|(job, result)| {
+        let image = models::Image::fetch_for_job(&mut conn, job)?
+            .ok_or_else(|| anyhow!("Image for job {} not found", job.uuid))?;
+        let package = models::Package::fetch_for_job(&mut conn, job)?
+            .ok_or_else(|| anyhow!("Package for job {} not found", job.uuid))?;
+        let endpoint = models::Endpoint::fetch_for_job(&mut conn, job)?
+            .ok_or_else(|| anyhow!("Endpoint for job {} not found", job.uuid))?;
+
+        Ok(vec![
+            job.uuid.to_string().cyan(),
+            match result {
+                Some(true) => "Success".green(),
+                Some(false) => "Error".red(),
+                None => "Unknown".yellow(),
+            },
+            job.failure_class.as_deref().unwrap_or("-").yellow(),
+            package.name.cyan(),
+            package.version.cyan(),
+            job.container_hash.normal(),
+            endpoint.name.normal(),
+            image_name_lookup.shorten(&image.name).normal(),
+        ])
+    });
+    crate::commands::util::display_data_stream(header, data, false)
+}
+
+/// Build the package DAG of a submit from the repository
+///
+/// The DAG is built for the requested package of the submit with the image and the environment
+/// of the submit. The packages are loaded from the current checkout of the repository, which
+/// should be the commit of the submit. Returns the DAG and the requested image.
+pub(super) fn submit_dag<F>(
+    conn: &mut PgConnection,
+    submit: &models::Submit,
+    githash: &models::GitHash,
+    jobs: &[models::Job],
+    repo_path: &Path,
+    load_repo: F,
+) -> Result<(Dag, models::Image)>
+where
+    F: FnOnce() -> Result<Repository>,
+{
+    let git_repo = git2::Repository::open(repo_path)
+        .with_context(|| anyhow!("Opening repository at {}", repo_path.display()))?;
+    let head = crate::util::git::get_repo_head_commit_hash(&git_repo)?;
+    if head != githash.hash {
+        warn!(
+            "Repository HEAD ({}) differs from the commit of the submit ({}), the packages might not reflect the submit",
+            head, githash.hash
+        );
+    }
+
+    let requested_package = models::Package::fetch_by_id(conn, submit.requested_package_id)?
+        .ok_or_else(|| anyhow!("Package for submit {} not found", submit.uuid))?;
+    let requested_image = models::Image::fetch_by_id(conn, submit.requested_image_id)?
+        .ok_or_else(|| anyhow!("Image for submit {} not found", submit.uuid))?;
+    let image_name = ImageName::from(requested_image.name.clone());
+
+    // The additional environment of a submit is passed to all of its jobs, so the environment
+    // variables that all jobs have in common are the ones that were used for the submit
+    let env = jobs
         .iter()
         .map(|job| {
-            let image = models::Image::fetch_for_job(&mut conn, job)?
-                .ok_or_else(|| anyhow!("Image for job {} not found", job.uuid))?;
-            let package = models::Package::fetch_for_job(&mut conn, job)?
-                .ok_or_else(|| anyhow!("Package for job {} not found", job.uuid))?;
-            let endpoint = models::Endpoint::fetch_for_job(&mut conn, job)?
-                .ok_or_else(|| anyhow!("Endpoint for job {} not found", job.uuid))?;
-
-            Ok(vec![
-                job.uuid.to_string().cyan(),
-                match is_job_successfull(job)? {
-                    Some(true) => "Success".green(),
-                    Some(false) => "Error".red(),
-                    None => "Unknown".yellow(),
-                },
-                package.name.cyan(),
-                package.version.cyan(),
-                job.container_hash.normal(),
-                endpoint.name.normal(),
-                image_name_lookup.shorten(&image.name).normal(),
-            ])
+            models::JobEnv::belonging_to(job)
+                .inner_join(schema::envvars::table)
+                .load::<(models::JobEnv, models::EnvVar)>(conn)
+                .map(|envs| {
+                    envs.into_iter()
+                        .map(|(_, env)| (env.name, env.value))
+                        .collect::<HashSet<_>>()
+                })
+                .map_err(Error::from)
         })
-        .collect::<Result<Vec<Vec<colored::ColoredString>>>>()?;
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .reduce(|a, b| a.intersection(&b).cloned().collect())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, value)| (EnvironmentVariableName::from(name.as_str()), value))
+        .collect::<Vec<_>>();
+
+    let repo = load_repo()?;
+    let package = repo
+        .find(
+            &PackageName::from(requested_package.name.clone()),
+            &PackageVersion::from(requested_package.version.clone()),
+        )
+        .into_iter()
+        .next()
+        .ok_or_else(|| {
+            anyhow!(
+                "Package {} {} not found in the repository",
+                requested_package.name,
+                requested_package.version
+            )
+        })?;
+
+    let condition_data = ConditionData {
+        image_name: Some(&image_name),
+        env: &env,
+    };
+    let dag = Dag::for_root_package(package.clone(), &repo, None, &condition_data)?;
+    Ok((dag, requested_image))
+}
+
+/// Print the jobs of a submit grouped into waves of jobs that could run in parallel
+///
+/// The dependencies between the jobs are not stored in the database, so the package DAG is
+/// recomputed from the repository.
+fn submit_schedule<F>(
+    conn: &mut PgConnection,
+    submit: &models::Submit,
+    githash: &models::GitHash,
+    jobs: &[models::Job],
+    image_name_lookup: &ImageNameLookup,
+    repo_path: &Path,
+    load_repo: F,
+) -> Result<()>
+where
+    F: FnOnce() -> Result<Repository>,
+{
+    let (dag, requested_image) = submit_dag(conn, submit, githash, jobs, repo_path, load_repo)?;
+
+    let jobs = jobs
+        .iter()
+        .map(|job| {
+            models::Package::fetch_for_job(conn, job)?
+                .ok_or_else(|| anyhow!("Package for job {} not found", job.uuid))
+                .map(|package| (package, job))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let image_name_short = image_name_lookup.shorten(&requested_image.name);
+    let header =
+        crate::commands::util::mk_header(vec!["Wave", "Job", "Package", "Version", "Image"]);
+    let data = dag
+        .waves()
+        .into_iter()
+        .enumerate()
+        .flat_map(|(i, wave)| {
+            let jobs = &jobs;
+            let image_name_short = &image_name_short;
+            wave.into_iter().map(move |p| {
+                let job_uuid = jobs
+                    .iter()
+                    .find(|(package, _)| {
+                        package.name == p.name().as_str() && package.version == p.version().as_str()
+                    })
+                    .map(|(_, job)| job.uuid.to_string())
+                    .unwrap_or_else(|| String::from("-"));
+
+                vec![
+                    (i + 1).to_string(),
+                    job_uuid,
+                    p.name().to_string(),
+                    p.version().to_string(),
+                    image_name_short.clone(),
+                ]
+            })
+        })
+        .collect::<Vec<_>>();
     crate::commands::util::display_data(header, data, false)
 }
 
@@ -375,38 +1721,76 @@ fn submits(
     default_limit: &usize,
 ) -> Result<()> {
     let csv = matches.get_flag("csv");
-    let limit = get_limit(matches, default_limit)?;
+    let stats_per = if matches.get_flag("stats_per_day") {
+        Some(SubmitPeriod::Day)
+    } else if matches.get_flag("stats_per_week") {
+        Some(SubmitPeriod::Week)
+    } else {
+        None
+    };
+
+    // The aggregated view should cover all submits unless a limit is passed explicitly
+    let limit = if stats_per.is_some() {
+        get_limit(matches, &0)?
+    } else {
+        get_limit(matches, default_limit)?
+    };
+    let offset = get_offset(matches, limit)?;
     let hdrs = crate::commands::util::mk_header(vec![
         "Time",
         "UUID",
         "For Package",
         "For Package Version",
+        "State",
     ]);
     let mut conn = conn_cfg.establish_connection()?;
+    let older_than_filter = get_date_filter("older_than", matches)?;
+    let image = matches
+        .get_one::<String>("image")
+        .map(|image| -> Result<_> {
+            let image_name_lookup = ImageNameLookup::create(config.docker().images())?;
+            Ok(image_name_lookup.expand(image)?.as_ref().to_string())
+        })
+        .transpose()?;
+    let non_final_states = models::SubmitState::NON_FINAL.map(|state| state.to_string());
 
-    let query = schema::submits::table
-        .order_by(schema::submits::id.desc()) // required for the --limit implementation
-        .inner_join(
-            schema::githashes::table.on(schema::submits::repo_hash_id.eq(schema::githashes::id)),
-        )
-        .inner_join(schema::images::table)
-        .into_boxed();
+    // The query with all filters, but without the order, limit and offset (it is used to count the
+    // submits as well)
+    let filtered_query = || {
+        let query = schema::submits::table
+            .inner_join(
+                schema::githashes::table
+                    .on(schema::submits::repo_hash_id.eq(schema::githashes::id)),
+            )
+            .inner_join(schema::images::table)
+            .into_boxed();
 
-    let query = if let Some(commithash) = matches.get_one::<String>("for-commit") {
-        query.filter(schema::githashes::hash.eq(commithash))
-    } else {
-        query
-    };
+        let query = if let Some(commithash) = matches.get_one::<String>("for-commit") {
+            query.filter(schema::githashes::hash.eq(commithash))
+        } else {
+            query
+        };
 
-    let query = if let Some(image) = matches.get_one::<String>("image") {
-        let image_name_lookup = ImageNameLookup::create(config.docker().images())?;
-        let image = image_name_lookup.expand(image)?;
-        query.filter(schema::images::name.eq(image.as_ref().to_string()))
-    } else {
-        query
+        let query = if let Some(image) = image.as_ref() {
+            query.filter(schema::images::name.eq(image))
+        } else {
+            query
+        };
+
+        let query = if matches.get_flag("stale") {
+            query.filter(schema::submits::state.eq_any(&non_final_states))
+        } else {
+            query
+        };
+
+        if let Some(datetime) = older_than_filter.as_ref() {
+            query.filter(schema::submits::submit_time.lt(datetime))
+        } else {
+            query
+        }
     };
 
-    let submits = if let Some(pkgname) = matches.get_one::<String>("with_pkg") {
+    let (total, submits) = if let Some(pkgname) = matches.get_one::<String>("with_pkg") {
         // In the case of a with_pkg command, we must execute two queries on the database, as the
         // diesel framework does not yet support aliases for queries (see
         // https://github.com/diesel-rs/diesel/pull/2254).
@@ -414,19 +1798,30 @@ fn submits(
         // out all submits that did not include the "with pkg" and once to join the requested
         // package for the output.
 
-        // Get all submits which included the package, but were not necessarily made _for_ the package
-        let query = query
-            .inner_join(schema::jobs::table)
-            .inner_join(
-                schema::packages::table.on(schema::jobs::package_id.eq(schema::packages::id)),
-            )
-            .filter(schema::packages::name.eq(&pkgname))
-            .limit(limit);
+        // All submits which included the package, but were not necessarily made _for_ the package
+        let with_pkg_query = || {
+            filtered_query()
+                .inner_join(schema::jobs::table)
+                .inner_join(
+                    schema::packages::table.on(schema::jobs::package_id.eq(schema::packages::id)),
+                )
+                .filter(schema::packages::name.eq(pkgname))
+        };
+
+        let total = with_pkg_query()
+            .select(diesel::dsl::count_distinct(schema::submits::id))
+            .get_result::<i64>(&mut conn)?;
 
         // Only load the IDs of the submits, so we can later use them to filter the submits
-        let submit_ids = query.select(schema::submits::id).load::<i32>(&mut conn)?;
+        let submit_ids = with_pkg_query()
+            .select(schema::submits::id)
+            .distinct()
+            .order_by(schema::submits::id.desc()) // required for the --limit implementation
+            .limit(limit)
+            .offset(offset)
+            .load::<i32>(&mut conn)?;
 
-        schema::submits::table
+        let submits = schema::submits::table
             .order_by(schema::submits::id.desc()) // required for the --limit implementation
             .inner_join({
                 schema::packages::table
@@ -434,54 +1829,258 @@ fn submits(
             })
             .filter(schema::submits::id.eq_any(submit_ids))
             .select((schema::submits::all_columns, schema::packages::all_columns))
-            .load::<(models::Submit, models::Package)>(&mut conn)?
-    } else if let Some(pkgname) = matches.get_one::<String>("for_pkg") {
-        // Get all submits _for_ the package
-        query
-            .inner_join({
-                schema::packages::table
-                    .on(schema::submits::requested_package_id.eq(schema::packages::id))
-            })
-            .filter(schema::packages::dsl::name.eq(&pkgname))
-            .select((schema::submits::all_columns, schema::packages::all_columns))
-            .limit(limit)
-            .load::<(models::Submit, models::Package)>(&mut conn)?
+            .load::<(models::Submit, models::Package)>(&mut conn)?;
+        (total, submits)
     } else {
-        query
-            .inner_join({
+        // All submits, or all submits _for_ the package
+        let for_pkg_query = || {
+            let query = filtered_query().inner_join({
                 schema::packages::table
                     .on(schema::submits::requested_package_id.eq(schema::packages::id))
-            })
+            });
+            if let Some(pkgname) = matches.get_one::<String>("for_pkg") {
+                query.filter(schema::packages::dsl::name.eq(pkgname))
+            } else {
+                query
+            }
+        };
+
+        let total = for_pkg_query().count().get_result::<i64>(&mut conn)?;
+        let submits = for_pkg_query()
+            .order_by(schema::submits::id.desc()) // required for the --limit implementation
             .select((schema::submits::all_columns, schema::packages::all_columns))
             .limit(limit)
-            .load::<(models::Submit, models::Package)>(&mut conn)?
+            .offset(offset)
+            .load::<(models::Submit, models::Package)>(&mut conn)?;
+        (total, submits)
     };
 
+    if let Some(period) = stats_per {
+        let submits = submits
+            .into_iter()
+            .map(|(submit, _)| submit)
+            .collect::<Vec<_>>();
+        return submits_stats(&mut conn, period, &submits, csv);
+    }
+
     // Helper to map (Submit, Package) -> Vec<String>
-    let submit_to_vec = |(submit, package): (models::Submit, models::Package)| {
+    let submit_to_vec = |(submit, package): &(models::Submit, models::Package)| {
         vec![
             submit.submit_time.to_string(),
             submit.uuid.to_string(),
-            package.name,
-            package.version,
+            package.name.clone(),
+            package.version.clone(),
+            submit.state.clone(),
         ]
     };
 
-    let data = submits
-        .into_iter()
-        .rev()
-        .map(submit_to_vec)
-        .collect::<Vec<_>>();
+    let data = submits.iter().rev().map(submit_to_vec).collect::<Vec<_>>();
 
-    if data.is_empty() {
+    if data.is_empty() && total == 0 {
         info!("No submits in database");
     } else {
+        let shown = data.len();
         crate::commands::util::display_data(hdrs, data, csv)?;
+        print_page_footer(offset, shown, total)?;
+    }
+
+    if matches.get_flag("cleanup") {
+        let stale = submits
+            .into_iter()
+            .map(|(submit, _)| submit)
+            .collect::<Vec<_>>();
+        cleanup_stale_submits(&mut conn, &stale)?;
     }
 
     Ok(())
 }
 
+/// The periods of time "db submits --stats-per-day/--stats-per-week" aggregates the submits by
+#[derive(Clone, Copy, Debug)]
+enum SubmitPeriod {
+    Day,
+    Week,
+}
+
+impl SubmitPeriod {
+    /// The first day of the period that contains `date`
+    fn start_of(self, date: chrono::NaiveDate) -> chrono::NaiveDate {
+        use chrono::Datelike;
+
+        match self {
+            SubmitPeriod::Day => date,
+            SubmitPeriod::Week => {
+                date - chrono::Duration::days(i64::from(date.weekday().num_days_from_monday()))
+            }
+        }
+    }
+
+    fn format(self, start: chrono::NaiveDate) -> String {
+        match self {
+            SubmitPeriod::Day => start.format("%Y-%m-%d").to_string(),
+            SubmitPeriod::Week => start.format("%G-W%V").to_string(),
+        }
+    }
+}
+
+/// The statistics of the submits of a period of time (see `db submits --stats-per-day`)
+#[derive(Debug, Default)]
+struct SubmitPeriodStats {
+    succeeded: usize,
+    failed: usize,
+    unknown: usize,
+    durations: Vec<chrono::Duration>,
+}
+
+impl SubmitPeriodStats {
+    fn add(&mut self, success: Option<bool>, duration: Option<chrono::Duration>) {
+        match success {
+            Some(true) => self.succeeded += 1,
+            Some(false) => self.failed += 1,
+            None => self.unknown += 1,
+        }
+        self.durations.extend(duration);
+    }
+
+    /// The percentage of the succeeded submits of all submits that succeeded or failed
+    fn success_rate(&self) -> Option<f64> {
+        let decided = self.succeeded + self.failed;
+        (decided > 0).then(|| self.succeeded as f64 * 100.0 / decided as f64)
+    }
+}
+
+/// Show the submits of "db submits" aggregated by the day or week they were submitted
+fn submits_stats(
+    conn: &mut PgConnection,
+    period: SubmitPeriod,
+    submits: &[models::Submit],
+    csv: bool,
+) -> Result<()> {
+    if submits.is_empty() {
+        info!("No submits in database");
+        return Ok(());
+    }
+
+    // PostgreSQL limits the number of bind parameters of a statement
+    let mut jobs = Vec::new();
+    for chunk in submits.chunks(5_000) {
+        let submit_ids = chunk.iter().map(|submit| submit.id).collect::<Vec<_>>();
+        jobs.extend(
+            schema::jobs::table
+                .filter(schema::jobs::submit_id.eq_any(submit_ids))
+                .filter(schema::jobs::retried.eq(false))
+                .load::<models::Job>(conn)?,
+        );
+    }
+    let job_results = jobs_successfull(&jobs.iter().collect::<Vec<_>>())?;
+    let jobs_of_submit = jobs
+        .iter()
+        .zip(job_results)
+        .into_group_map_by(|(job, _)| job.submit_id);
+
+    let mut periods = std::collections::BTreeMap::<chrono::NaiveDate, SubmitPeriodStats>::new();
+    for submit in submits {
+        let jobs = jobs_of_submit
+            .get(&submit.id)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let success = if submit.state == models::SubmitState::Aborted.to_string()
+            || jobs.iter().any(|(_, result)| *result == Some(false))
+        {
+            Some(false)
+        } else if submit.state == models::SubmitState::Finished.to_string()
+            && jobs.iter().all(|(_, result)| *result == Some(true))
+        {
+            Some(true)
+        } else {
+            None
+        };
+        let duration = jobs
+            .iter()
+            .filter_map(|(job, _)| job.finished_at)
+            .max()
+            .map(|finished| finished - submit.submit_time);
+
+        periods
+            .entry(period.start_of(submit.submit_time.date()))
+            .or_default()
+            .add(success, duration);
+    }
+
+    let hdrs = crate::commands::util::mk_header(vec![
+        match period {
+            SubmitPeriod::Day => "Day",
+            SubmitPeriod::Week => "Week",
+        },
+        "Submits",
+        "Succeeded",
+        "Failed",
+        "Unknown",
+        "Success rate",
+        "Avg. duration",
+    ]);
+
+    let data = periods
+        .into_iter()
+        .map(|(start, stats)| {
+            vec![
+                period.format(start),
+                (stats.succeeded + stats.failed + stats.unknown).to_string(),
+                stats.succeeded.to_string(),
+                stats.failed.to_string(),
+                stats.unknown.to_string(),
+                stats
+                    .success_rate()
+                    .map(|rate| format!("{rate:.1}%"))
+                    .unwrap_or_else(|| String::from("-")),
+                format_average_duration(&stats.durations),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    crate::commands::util::display_data(hdrs, data, csv)
+}
+
+/// Clean up stale submits (see "db submits --stale --cleanup")
+///
+/// Submits without jobs are deleted, as nothing was built for them. Submits with jobs are marked
+/// as aborted, so that the jobs that were built stay in the database.
+fn cleanup_stale_submits(conn: &mut PgConnection, submits: &[models::Submit]) -> Result<()> {
+    let (mut deleted, mut aborted) = (0, 0);
+    for submit in submits {
+        conn.transaction::<_, Error, _>(|conn| {
+            let has_jobs = diesel::select(diesel::dsl::exists(
+                schema::jobs::table.filter(schema::jobs::submit_id.eq(submit.id)),
+            ))
+            .get_result::<bool>(conn)?;
+
+            if has_jobs {
+                submit.set_state(conn, models::SubmitState::Aborted)?;
+                aborted += 1;
+            } else {
+                diesel::delete(
+                    schema::job_queue::table.filter(schema::job_queue::submit_id.eq(submit.id)),
+                )
+                .execute(conn)?;
+                diesel::delete(
+                    schema::submit_envs::table.filter(schema::submit_envs::submit_id.eq(submit.id)),
+                )
+                .execute(conn)?;
+                diesel::delete(submit).execute(conn)?;
+                deleted += 1;
+            }
+            Ok(())
+        })
+        .with_context(|| anyhow!("Cleaning up submit {}", submit.uuid))?;
+    }
+
+    info!(
+        "Deleted {} stale submits without jobs, marked {} stale submits as aborted",
+        deleted, aborted
+    );
+    Ok(())
+}
+
 /// Implementation of the "db jobs" subcommand
 fn jobs(
     conn_cfg: DbConnectionConfig<'_>,
@@ -491,38 +2090,24 @@ fn jobs(
 ) -> Result<()> {
     let csv = matches.get_flag("csv");
     let hdrs = crate::commands::util::mk_header(vec![
-        "Submit", "Job", "Time", "Host", "Ok?", "Package", "Version", "Distro", "Type",
+        "Submit", "Job", "Time", "Host", "Ok?", "Failure", "Package", "Version", "Distro", "Type",
     ]);
     let mut conn = conn_cfg.establish_connection()?;
     let older_than_filter = get_date_filter("older_than", matches)?;
     let newer_than_filter = get_date_filter("newer_than", matches)?;
 
-    let mut sel = schema::jobs::table
-        .inner_join(schema::submits::table)
-        .inner_join(schema::endpoints::table)
-        .inner_join(schema::packages::table)
-        .inner_join(schema::images::table)
-        .left_outer_join(schema::artifacts::table)
-        .into_boxed();
-
-    if let Some(submit_uuid) = matches.get_one::<uuid::Uuid>("submit_uuid") {
-        sel = sel.filter(schema::submits::uuid.eq(submit_uuid))
-    }
-
     let image_name_lookup = ImageNameLookup::create(config.docker().images())?;
-    if let Some(image_name) = matches
+    let image_name = matches
         .get_one::<String>("image")
         .map(|s| image_name_lookup.expand(s))
         .transpose()?
-    {
-        sel = sel.filter(schema::images::name.eq(image_name.as_ref().to_string()))
-    }
+        .map(|image_name| image_name.as_ref().to_string());
 
     // Filter for environment variables from the CLI
     //
     // If we get a filter for environment on CLI, we fetch all job ids that are associated with the
-    // passed environment variables and make `sel` filter for those.
-    if let Some((name, val)) = matches
+    // passed environment variables and make the query filter for those.
+    let env_filter_jids = if let Some((name, val)) = matches
         .get_one::<String>("env_filter")
         .map(|s| crate::util::env::parse_to_env(s.as_ref()))
         .transpose()?
@@ -539,36 +2124,96 @@ fn jobs(
             .select(schema::job_envs::job_id)
             .load::<i32>(&mut conn)?;
 
-        debug!(
-            "Filtering for these IDs (because of env filter): {:?}",
-            jids
-        );
-        sel = sel.filter(schema::jobs::dsl::id.eq_any(jids));
-    }
+        debug!(
+            "Filtering for these IDs (because of env filter): {:?}",
+            jids
+        );
+        Some(jids)
+    } else {
+        None
+    };
+
+    let submit_uuid = matches
+        .get_one::<String>("submit_uuid")
+        .map(|id| crate::commands::ids::resolve_submit_uuid(&mut conn, id))
+        .transpose()?;
+
+    // The query with all filters, but without the order, limit and offset (it is used to count the
+    // jobs as well)
+    let filtered_sel = || {
+        let mut sel = schema::jobs::table
+            .inner_join(schema::submits::table)
+            .inner_join(schema::endpoints::table)
+            .inner_join(schema::packages::table)
+            .inner_join(schema::images::table)
+            .left_outer_join(schema::artifacts::table)
+            .into_boxed();
+
+        if let Some(submit_uuid) = submit_uuid.as_ref() {
+            sel = sel.filter(schema::submits::uuid.eq(submit_uuid))
+        }
+
+        if let Some(image_name) = image_name.as_ref() {
+            sel = sel.filter(schema::images::name.eq(image_name))
+        }
+
+        if let Some(jids) = env_filter_jids.as_ref() {
+            sel = sel.filter(schema::jobs::dsl::id.eq_any(jids));
+        }
 
-    if let Some(datetime) = older_than_filter.as_ref() {
-        sel = sel.filter(schema::submits::dsl::submit_time.lt(datetime))
-    }
+        if let Some(datetime) = older_than_filter.as_ref() {
+            sel = sel.filter(schema::submits::dsl::submit_time.lt(datetime))
+        }
 
-    if let Some(datetime) = newer_than_filter.as_ref() {
-        sel = sel.filter(schema::submits::dsl::submit_time.gt(datetime))
-    }
+        if let Some(datetime) = newer_than_filter.as_ref() {
+            sel = sel.filter(schema::submits::dsl::submit_time.gt(datetime))
+        }
 
-    if let Some(ep_name) = matches.get_one::<String>("endpoint") {
-        sel = sel.filter(schema::endpoints::name.eq(ep_name))
-    }
+        if let Some(ep_name) = matches.get_one::<String>("endpoint") {
+            sel = sel.filter(schema::endpoints::name.eq(ep_name))
+        }
 
-    if let Some(pkg_name) = matches.get_one::<String>("package") {
-        sel = sel.filter(schema::packages::name.eq(pkg_name))
-    }
+        if let Some(pkg_name) = matches.get_one::<String>("package") {
+            sel = sel.filter(schema::packages::name.eq(pkg_name))
+        }
 
-    let limit = get_limit(matches, default_limit)?;
+        if let Some(license) = matches.get_one::<String>("license") {
+            sel = sel.filter(schema::packages::license.like(glob_to_like(license)))
+        }
 
-    let image_name_lookup = ImageNameLookup::create(config.docker().images())?;
+        if let Some(namespace) = matches.get_one::<String>("namespace") {
+            let prefix = format!("{namespace}{}", PackageName::NAMESPACE_SEPARATOR);
+            sel = sel.filter(schema::packages::name.like(format!("{}%", glob_to_like(&prefix))))
+        }
+
+        if let Some(digest) = matches.get_one::<String>("image_digest") {
+            // The digest may be passed without the "sha256:" prefix and abbreviated
+            let digest = digest.strip_prefix("sha256:").unwrap_or(digest);
+            sel = sel.filter(schema::jobs::image_digest.like(format!("sha256:{digest}%")))
+        }
+
+        if let Some(arch) = matches.get_one::<String>("arch") {
+            sel = sel.filter(schema::jobs::arch.eq(arch))
+        }
+
+        sel
+    };
 
-    let data = sel
+    let group_by = matches.get_one::<String>("group_by");
+
+    // The aggregated view should cover all jobs unless a limit is passed explicitly
+    let limit = if group_by.is_some() {
+        get_limit(matches, &0)?
+    } else {
+        get_limit(matches, default_limit)?
+    };
+    let offset = get_offset(matches, limit)?;
+
+    let total = filtered_sel().count().get_result::<i64>(&mut conn)?;
+    let rows = filtered_sel()
         .order_by(schema::jobs::id.desc()) // required for the --limit implementation
         .limit(limit)
+        .offset(offset)
         .load::<(
             models::Job,
             models::Submit,
@@ -576,50 +2221,199 @@ fn jobs(
             models::Package,
             models::Image,
             Option<models::Artifact>,
-        )>(&mut conn)?
-        .into_iter()
-        .rev() // required for the --limit implementation
-        .map(|(job, submit, ep, package, image, artifact)| {
-            let success = is_job_successfull(&job)?
+        )>(&mut conn)?;
+
+    if let Some(group_by) = group_by {
+        return jobs_grouped(group_by, rows, &image_name_lookup, csv);
+    }
+
+    if rows.is_empty() && total == 0 {
+        info!("No submits in database");
+        return Ok(());
+    }
+
+    let shown = rows.len();
+    // Retried jobs are not parsed, their result is not shown
+    let data = with_job_results(
+        rows.into_iter().rev(), // required for the --limit implementation
+        |row| Some(&row.0).filter(|job| !job.retried),
+    )
+    .map(|row| {
+        let ((job, submit, ep, package, image, artifact), result) = row?;
+        let success = if job.retried {
+            String::from("retried")
+        } else {
+            result
                 .map(|b| if b { "yes" } else { "no" })
                 .map(String::from)
-                .unwrap_or_else(|| String::from("?"));
-            let artifact_type = if let Some(artifact) = artifact {
-                artifact
-                    .path
-                    .split(".")
-                    .last()
-                    .map(str::to_uppercase)
-                    .unwrap_or(String::from("?"))
-            } else {
-                String::from("-")
-            };
+                .unwrap_or_else(|| String::from("?"))
+        };
+        let artifact_type = if let Some(artifact) = artifact {
+            artifact
+                .path
+                .split(".")
+                .last()
+                .map(str::to_uppercase)
+                .unwrap_or(String::from("?"))
+        } else {
+            String::from("-")
+        };
 
-            Ok(vec![
-                submit.uuid.to_string(),
-                job.uuid.to_string(),
-                submit.submit_time.format("%Y-%m-%d %H:%M:%S").to_string(),
-                ep.name,
-                success,
-                package.name,
-                package.version,
-                image_name_lookup.shorten(&image.name),
-                artifact_type,
-            ])
+        Ok(vec![
+            submit.uuid.to_string(),
+            job.uuid.to_string(),
+            submit.submit_time.format("%Y-%m-%d %H:%M:%S").to_string(),
+            ep.name,
+            success,
+            job.failure_class.unwrap_or_else(|| String::from("-")),
+            package.name,
+            package.version,
+            image_name_lookup.shorten(&image.name),
+            artifact_type,
+        ])
+    });
+
+    crate::commands::util::display_data_stream(hdrs, data, csv)?;
+    print_page_footer(offset, shown, total)?;
+    Ok(())
+}
+
+/// Translate a glob pattern (`*` and `?`) to a pattern for SQL `LIKE`
+fn glob_to_like(glob: &str) -> String {
+    glob.chars()
+        .map(|c| match c {
+            '*' => String::from("%"),
+            '?' => String::from("_"),
+            '%' | '_' | '\\' => format!("\\{c}"),
+            c => c.to_string(),
         })
-        .collect::<Result<Vec<_>>>()?;
+        .collect()
+}
 
-    if data.is_empty() {
-        info!("No submits in database");
-    } else {
-        crate::commands::util::display_data(hdrs, data, csv)?;
+/// The statistics of a group of jobs (see `db jobs --group-by`)
+#[derive(Debug, Default, Eq, PartialEq)]
+struct JobGroupStats {
+    succeeded: usize,
+    failed: usize,
+    unknown: usize,
+    last_success: Option<chrono::NaiveDateTime>,
+    durations: Vec<chrono::Duration>,
+}
+
+impl JobGroupStats {
+    fn add(
+        &mut self,
+        success: Option<bool>,
+        time: chrono::NaiveDateTime,
+        duration: Option<chrono::Duration>,
+    ) {
+        match success {
+            Some(true) => {
+                self.succeeded += 1;
+                self.last_success = std::cmp::max(self.last_success, Some(time));
+            }
+            Some(false) => self.failed += 1,
+            None => self.unknown += 1,
+        }
+        self.durations.extend(duration);
     }
+}
 
-    Ok(())
+/// The average of the durations, in whole seconds, or "-" if there are none
+fn format_average_duration(durations: &[chrono::Duration]) -> String {
+    i32::try_from(durations.len())
+        .ok()
+        .filter(|count| *count > 0)
+        .map(|count| {
+            durations
+                .iter()
+                .fold(chrono::Duration::zero(), |sum, d| sum + *d)
+                / count
+        })
+        .and_then(|d| d.to_std().ok())
+        .map(|d| {
+            humantime::format_duration(std::time::Duration::from_secs(d.as_secs())).to_string()
+        })
+        .unwrap_or_else(|| String::from("-"))
+}
+
+/// Show the jobs of "db jobs" aggregated by package, image or endpoint
+fn jobs_grouped(
+    group_by: &str,
+    rows: Vec<(
+        models::Job,
+        models::Submit,
+        models::Endpoint,
+        models::Package,
+        models::Image,
+        Option<models::Artifact>,
+    )>,
+    image_name_lookup: &ImageNameLookup,
+    csv: bool,
+) -> Result<()> {
+    let mut groups = std::collections::BTreeMap::<String, JobGroupStats>::new();
+
+    // A job with multiple artifacts appears multiple times, but must only be counted once
+    let rows = rows
+        .into_iter()
+        .unique_by(|row| row.0.id)
+        .collect::<Vec<_>>();
+    let job_results = jobs_successfull(&rows.iter().map(|row| &row.0).collect::<Vec<_>>())?;
+    for ((job, submit, ep, package, image, _), result) in rows.into_iter().zip(job_results) {
+        let key = match group_by {
+            "package" => package.name,
+            "image" => image_name_lookup.shorten(&image.name),
+            "endpoint" => ep.name,
+            other => return Err(anyhow!("Cannot group jobs by {}", other)),
+        };
+
+        groups.entry(key).or_default().add(
+            result,
+            job.finished_at.unwrap_or(submit.submit_time),
+            job.duration(),
+        );
+    }
+
+    if groups.is_empty() {
+        info!("No jobs in database");
+        return Ok(());
+    }
+
+    let mut group_header = group_by.to_string();
+    group_header[..1].make_ascii_uppercase();
+    let hdrs = crate::commands::util::mk_header(vec![
+        &group_header,
+        "Jobs",
+        "Succeeded",
+        "Failed",
+        "Unknown",
+        "Last success",
+        "Avg. duration",
+    ]);
+
+    let data = groups
+        .into_iter()
+        .map(|(key, stats)| {
+            vec![
+                key,
+                (stats.succeeded + stats.failed + stats.unknown).to_string(),
+                stats.succeeded.to_string(),
+                stats.failed.to_string(),
+                stats.unknown.to_string(),
+                stats
+                    .last_success
+                    .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_else(|| String::from("-")),
+                format_average_duration(&stats.durations),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    crate::commands::util::display_data(hdrs, data, csv)
 }
 
 /// Implementation of the "db job" subcommand
-fn job(
+async fn job(
     conn_cfg: DbConnectionConfig<'_>,
     config: &Configuration,
     matches: &ArgMatches,
@@ -631,7 +2425,7 @@ fn job(
     let show_script = matches.get_flag("show_script");
     let csv = matches.get_flag("csv");
     let mut conn = conn_cfg.establish_connection()?;
-    let job_uuid = matches.get_one::<uuid::Uuid>("job_uuid").unwrap();
+    let job_uuid = &crate::commands::ids::job_uuid(&mut conn, matches, "job_uuid")?;
 
     let data = schema::jobs::table
         .filter(schema::jobs::dsl::uuid.eq(job_uuid))
@@ -647,6 +2441,20 @@ fn job(
             models::Image,
         )>(&mut conn)?;
 
+    if let Some(output) = matches.get_one::<PathBuf>("export_workdir") {
+        return export_workdir(config, &data.0, &data.2, output).await;
+    }
+
+    if let Some(dir) = matches.get_one::<PathBuf>("fetch_artifacts") {
+        let artifacts = models::Artifact::of_job_with_releases(&mut conn, &data.0)?;
+        return fetch_artifacts(config, &data.1, &artifacts, dir).await;
+    }
+    let artifacts = if matches.get_flag("show_artifacts") {
+        Some(models::Artifact::of_job_with_releases(&mut conn, &data.0)?)
+    } else {
+        None
+    };
+
     trace!("Parsing log");
     let parsed_log = crate::log::ParsedLog::from_str(&data.0.log_text)?;
     trace!("Parsed log = {:?}", parsed_log);
@@ -661,7 +2469,14 @@ fn job(
             "Package Version",
             "Ran on",
             "Image Name",
+            "Image Digest",
+            "Arch",
             "Container",
+            "Docker Version",
+            "Butido Version",
+            "Sources Hash",
+            "Warnings",
+            "Reported Artifacts",
         ]);
 
         let data = vec![vec![
@@ -675,9 +2490,21 @@ fn job(
             data.3.version.to_string(),
             data.2.name.to_string(),
             data.4.name.to_string(),
+            data.0.image_digest.unwrap_or_default(),
+            data.0.arch.unwrap_or_default(),
             data.0.container_hash,
+            data.0.endpoint_docker_version.unwrap_or_default(),
+            data.0.butido_version.unwrap_or_default(),
+            data.0.sources_hash.unwrap_or_default(),
+            data.0.warnings.map(|n| n.to_string()).unwrap_or_default(),
+            data.0.reported_artifacts.join(" "),
         ]];
-        crate::commands::util::display_data(hdrs, data, csv)
+        crate::commands::util::display_data(hdrs, data, csv)?;
+
+        if let Some(artifacts) = artifacts {
+            display_job_artifacts(artifacts, csv)?;
+        }
+        Ok(())
     } else {
         let env_vars = if matches.get_flag("show_env") {
             Some({
@@ -694,6 +2521,26 @@ fn job(
             None
         };
 
+        let probes = if matches.get_flag("show_probes") {
+            Some({
+                models::JobProbe::belonging_to(&data.0)
+                    .order_by(schema::job_probes::id)
+                    .load::<models::JobProbe>(&mut conn)?
+                    .into_iter()
+                    .map(|probe| {
+                        let output = probe
+                            .output
+                            .lines()
+                            .map(|line| format!("\t\t{line}"))
+                            .join("\n");
+                        format!("\t{}:\n{}", probe.name, output)
+                    })
+                    .join("\n")
+            })
+        } else {
+            None
+        };
+
         let mut out = std::io::stdout();
         let s = indoc::formatdoc!(
             r#"
@@ -701,13 +2548,21 @@ fn job(
                 Submit:     {submit_uuid}
                 Succeeded:  {succeeded}
                 Package:    {package_name} {package_version}
+                License:    {license}
+                Maintainer: {maintainer}
 
-                Ran on:     {endpoint_name}
+                Ran on:     {endpoint_name} (Docker {docker_version})
                 Image:      {image_name}
+                Digest:     {image_digest}
+                Arch:       {arch}
                 Container:  {container_hash}
+                Sources:    {sources_hash}
+                Butido:     {butido_version}
 
                 Script:     {script_len} lines
                 Log:        {log_len} lines
+                Warnings:   {warnings}
+                Artifacts:  {reported_artifacts}
 
             "#,
             job_uuid = match success {
@@ -723,11 +2578,33 @@ fn job(
             },
             package_name = data.3.name.cyan(),
             package_version = data.3.version.cyan(),
+            license = data.3.license.as_deref().unwrap_or("unknown").cyan(),
+            maintainer = data.3.maintainer.as_deref().unwrap_or("unknown").cyan(),
             endpoint_name = data.2.name.cyan(),
             image_name = data.4.name.cyan(),
+            image_digest = data.0.image_digest.as_deref().unwrap_or("unknown").cyan(),
+            arch = data.0.arch.as_deref().unwrap_or("any").cyan(),
             container_hash = data.0.container_hash.cyan(),
+            sources_hash = data.0.sources_hash.as_deref().unwrap_or("unknown").cyan(),
+            docker_version = data
+                .0
+                .endpoint_docker_version
+                .as_deref()
+                .unwrap_or("unknown")
+                .cyan(),
+            butido_version = data.0.butido_version.as_deref().unwrap_or("unknown").cyan(),
             script_len = format!("{:<4}", data.0.script_text.lines().count()).cyan(),
             log_len = format!("{:<4}", data.0.log_text.lines().count()).cyan(),
+            warnings = match data.0.warnings {
+                Some(0) => String::from("0").green(),
+                Some(n) => n.to_string().yellow(),
+                None => String::from("not reported").normal(),
+            },
+            reported_artifacts = if data.0.reported_artifacts.is_empty() {
+                String::from("none reported").normal()
+            } else {
+                data.0.reported_artifacts.join(", ").cyan()
+            },
         );
         writeln!(out, "{s}")?;
 
@@ -744,6 +2621,24 @@ fn job(
             writeln!(out, "{s}")?;
         }
 
+        if let Some(probes) = probes {
+            let probes = if probes.is_empty() {
+                String::from("No environment probes recorded")
+            } else {
+                probes
+            };
+            let s = indoc::formatdoc!(
+                r#"
+                ---
+
+                {probes}
+
+            "#,
+                probes = probes
+            );
+            writeln!(out, "{s}")?;
+        }
+
         if show_script {
             let theme = configured_theme.as_ref().ok_or_else(|| {
                 anyhow!("Highlighting for script enabled, but no theme configured")
@@ -788,114 +2683,543 @@ fn job(
             writeln!(out, "{s}")?;
         }
 
+        if let Some(artifacts) = artifacts {
+            writeln!(out, "---\n")?;
+            display_job_artifacts(artifacts, csv)?;
+        }
+
         Ok(())
     }
 }
 
+/// Print the artifacts of a job as table
+fn display_job_artifacts(
+    artifacts: Vec<(
+        models::Artifact,
+        Vec<(models::Release, models::ReleaseStore)>,
+    )>,
+    csv: bool,
+) -> Result<()> {
+    if artifacts.is_empty() {
+        writeln!(std::io::stdout(), "No artifacts recorded for this job")?;
+        return Ok(());
+    }
+
+    let hdrs =
+        crate::commands::util::mk_header(vec!["Path", "Kind", "Size", "SHA-256", "Released"]);
+    let data = artifacts
+        .into_iter()
+        .map(|(artifact, releases)| {
+            let released = if releases.is_empty() {
+                String::from("no")
+            } else {
+                releases
+                    .iter()
+                    .map(|(release, store)| {
+                        format!(
+                            "{}:{} ({})",
+                            store.store_name,
+                            release.path_in_store(&artifact),
+                            release.release_date
+                        )
+                    })
+                    .join(", ")
+            };
+            vec![
+                artifact.path.clone(),
+                artifact.kind.clone(),
+                artifact
+                    .size
+                    .map(|s| bytesize::ByteSize::b(s as u64).to_string())
+                    .unwrap_or_else(|| String::from("unknown")),
+                artifact.sha256.unwrap_or_else(|| String::from("unknown")),
+                released,
+            ]
+        })
+        .collect::<Vec<_>>();
+    crate::commands::util::display_data(hdrs, data, csv)
+}
+
+/// Copy the artifacts of a job to `dir`, from the staging store of the submit or, if they are
+/// not there anymore, from a release store
+///
+/// The artifacts keep their paths (relative to `dir`). Existing files are not overwritten.
+async fn fetch_artifacts(
+    config: &Configuration,
+    submit: &models::Submit,
+    artifacts: &[(
+        models::Artifact,
+        Vec<(models::Release, models::ReleaseStore)>,
+    )],
+    dir: &Path,
+) -> Result<()> {
+    if artifacts.is_empty() {
+        return Err(anyhow!("No artifacts recorded for this job"));
+    }
+
+    let staging_dir = config.staging_directory().join(submit.uuid.to_string());
+    for (artifact, releases) in artifacts {
+        let source = std::iter::once(staging_dir.join(&artifact.path))
+            .chain(releases.iter().rev().map(|(release, store)| {
+                config
+                    .releases_directory()
+                    .join(&store.store_name)
+                    .join(release.path_in_store(artifact))
+            }))
+            .find(|path| path.is_file())
+            .ok_or_else(|| {
+                anyhow!(
+                    "Artifact {} is neither in the staging store nor in a release store",
+                    artifact.path
+                )
+            })?;
+
+        let dest = dir.join(&artifact.path);
+        if dest.exists() {
+            return Err(anyhow!("Does already exist: {}", dest.display()));
+        }
+        if let Some(parent) = dest.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .with_context(|| anyhow!("Creating {}", parent.display()))?;
+        }
+        tokio::fs::copy(&source, &dest)
+            .await
+            .with_context(|| anyhow!("Copying {} to {}", source.display(), dest.display()))?;
+
+        let state = verify_release_file(&dest, artifact.size, artifact.sha256.as_deref()).await?;
+        if state.is_damaged() {
+            return Err(anyhow!(
+                "The copy of {} in {} is damaged: {}",
+                artifact.path,
+                source.display(),
+                state
+            ));
+        }
+        writeln!(std::io::stdout(), "{}", dest.display())?;
+    }
+    Ok(())
+}
+
 /// Implementation of the subcommand "db log-of"
-fn log_of(conn_cfg: DbConnectionConfig<'_>, matches: &ArgMatches) -> Result<()> {
+fn log_of(
+    conn_cfg: DbConnectionConfig<'_>,
+    config: &Configuration,
+    matches: &ArgMatches,
+) -> Result<()> {
     let mut conn = conn_cfg.establish_connection()?;
-    let job_uuid = matches.get_one::<uuid::Uuid>("job_uuid").unwrap();
+    let job_uuid = &crate::commands::ids::job_uuid(&mut conn, matches, "job_uuid")?;
+    let patterns = matches
+        .get_many::<String>("filter")
+        .map(|filters| config.log_filters().regex_set(filters.map(String::as_str)))
+        .transpose()?;
+    let phase = matches.get_one::<String>("phase").map(String::as_str);
+    let tail = matches.get_one::<usize>("tail").copied();
+    let color = match matches.get_one::<String>("color").map(String::as_str) {
+        Some("always") => true,
+        Some("never") => false,
+        _ => std::io::stdout().is_terminal(),
+    };
+    if color {
+        colored::control::set_override(true);
+    }
     let out = std::io::stdout();
     let mut lock = out.lock();
 
-    schema::jobs::table
+    let items = schema::jobs::table
         .filter(schema::jobs::dsl::uuid.eq(job_uuid))
         .select(schema::jobs::dsl::log_text)
         .first::<String>(&mut conn)
         .map_err(Error::from)
         .and_then(|s| crate::log::ParsedLog::from_str(&s))?
+        .select(phase, patterns.as_ref());
+
+    let items = match tail {
+        Some(n) => crate::log::tail(items, n),
+        None => items,
+    };
+    items
         .into_iter()
         .map(|line| {
-            line.display()
-                .and_then(|d| writeln!(lock, "{d}").map_err(Error::from))
+            if color {
+                line.display()
+                    .and_then(|d| writeln!(lock, "{d}").map_err(Error::from))
+            } else {
+                line.raw().and_then(|raw| {
+                    writeln!(lock, "{}", crate::log::strip_ansi_escapes(&raw)).map_err(Error::from)
+                })
+            }
         })
         .collect::<Result<Vec<()>>>()
         .map(|_| ())
 }
 
 /// Implementation of the "db releases" subcommand
-pub fn releases(
+pub async fn releases(
     conn_cfg: DbConnectionConfig<'_>,
     config: &Configuration,
     matches: &ArgMatches,
     default_limit: &usize,
+    progressbars: &ProgressBars,
 ) -> Result<()> {
     let csv = matches.get_flag("csv");
+    let verify_files = matches.get_flag("verify_files");
     let mut conn = conn_cfg.establish_connection()?;
     let limit = get_limit(matches, default_limit)?;
-    let header = crate::commands::util::mk_header(["Package", "Version", "Date", "Path"].to_vec());
-    let mut query = schema::jobs::table
-        .inner_join(schema::packages::table)
-        .inner_join(schema::artifacts::table)
-        .inner_join(
-            schema::releases::table.on(schema::releases::artifact_id.eq(schema::artifacts::id)),
-        )
-        .inner_join(
-            schema::release_stores::table
-                .on(schema::release_stores::id.eq(schema::releases::release_store_id)),
-        )
-        .order_by(schema::releases::id.desc()) // required for the --limit implementation
-        .limit(limit)
-        .into_boxed();
-
-    if let Some(date) = crate::commands::util::get_date_filter("older_than", matches)? {
-        query = query.filter(schema::releases::release_date.lt(date));
+    let offset = get_offset(matches, limit)?;
+    let mut header = vec!["Package", "Version", "Date", "Group", "Path"];
+    if verify_files {
+        header.push("File");
     }
+    let header = crate::commands::util::mk_header(header);
+    let older_than_filter = crate::commands::util::get_date_filter("older_than", matches)?;
+    let newer_than_filter = crate::commands::util::get_date_filter("newer_than", matches)?;
+
+    // The query with all filters, but without the order, limit and offset (it is used to count the
+    // releases as well)
+    let filtered_query = || {
+        let mut query = schema::jobs::table
+            .inner_join(schema::packages::table)
+            .inner_join(schema::artifacts::table)
+            .inner_join(
+                schema::releases::table.on(schema::releases::artifact_id.eq(schema::artifacts::id)),
+            )
+            .inner_join(
+                schema::release_stores::table
+                    .on(schema::release_stores::id.eq(schema::releases::release_store_id)),
+            )
+            .left_join(
+                schema::release_groups::table.on(schema::release_groups::id
+                    .nullable()
+                    .eq(schema::releases::release_group_id)),
+            )
+            .into_boxed();
 
-    if let Some(date) = crate::commands::util::get_date_filter("newer_than", matches)? {
-        query = query.filter(schema::releases::release_date.gt(date));
-    }
+        if let Some(date) = older_than_filter.as_ref() {
+            query = query.filter(schema::releases::release_date.lt(date));
+        }
 
-    if let Some(store) = matches.get_one::<String>("store") {
-        query = query.filter(schema::release_stores::dsl::store_name.eq(store));
-    }
+        if let Some(date) = newer_than_filter.as_ref() {
+            query = query.filter(schema::releases::release_date.gt(date));
+        }
 
-    if let Some(pkg) = matches.get_one::<String>("package") {
-        query = query.filter(schema::packages::dsl::name.eq(pkg));
-    }
+        if let Some(store) = matches.get_one::<String>("store") {
+            query = query.filter(schema::release_stores::dsl::store_name.eq(store));
+        }
 
-    let data = query
+        if let Some(pkg) = matches.get_one::<String>("package") {
+            query = query.filter(schema::packages::dsl::name.eq(pkg));
+        }
+
+        if let Some(group) = matches.get_one::<String>("group") {
+            query = match uuid::Uuid::parse_str(group) {
+                Ok(group_uuid) => query.filter(schema::release_groups::uuid.eq(group_uuid)),
+                Err(_) => query.filter(schema::release_groups::name.eq(group)),
+            };
+        }
+
+        if let Some(arch) = matches.get_one::<String>("arch") {
+            query = query.filter(schema::jobs::arch.eq(arch));
+        }
+
+        query
+    };
+
+    let total = filtered_query().count().get_result::<i64>(&mut conn)?;
+    let query = filtered_query()
+        .order_by(schema::releases::id.desc()) // required for the --limit implementation
+        .limit(limit)
+        .offset(offset);
+
+    let releases = query
         .select({
             let art = schema::artifacts::all_columns;
             let pac = schema::packages::all_columns;
             let rel = schema::releases::all_columns;
             let rst = schema::release_stores::all_columns;
-            (art, pac, rel, rst)
+            let grp = schema::release_groups::all_columns.nullable();
+            (art, pac, rel, rst, grp)
         })
         .load::<(
             models::Artifact,
             models::Package,
             models::Release,
             models::ReleaseStore,
-        )>(&mut conn)?
+            Option<models::ReleaseGroup>,
+        )>(&mut conn)?;
+
+    let file_states = if verify_files {
+        let bar = progressbars.bytes_bar()?;
+        bar.set_length(
+            releases
+                .iter()
+                .filter_map(|(art, ..)| art.size)
+                .map(|size| u64::try_from(size).unwrap_or_default())
+                .sum(),
+        );
+        bar.set_message("Verifying release files");
+        let states = futures::stream::iter(releases.iter().map(|(art, _, rel, rstore, _)| {
+            let p = config
+                .releases_directory()
+                .join(&rstore.store_name)
+                .join(rel.path_in_store(art));
+            let bar = bar.clone();
+            async move {
+                let state = verify_release_file(&p, art.size, art.sha256.as_deref()).await;
+                bar.inc(
+                    art.size
+                        .and_then(|s| u64::try_from(s).ok())
+                        .unwrap_or_default(),
+                );
+                state
+            }
+        }))
+        .buffered(VERIFY_FILES_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>>>()?;
+        bar.finish_with_message("Verified release files");
+        Some(states)
+    } else {
+        None
+    };
+
+    let mut damaged = 0;
+    let data = releases
         .into_iter()
-        .map(|(art, pack, rel, rstore)| {
+        .enumerate()
+        .map(|(i, (art, pack, rel, rstore, group))| {
             let p = config
                 .releases_directory()
                 .join(&rstore.store_name)
-                .join(&art.path);
+                .join(rel.path_in_store(&art));
 
-            vec![
+            let mut row = vec![
                 pack.name,
                 pack.version,
                 rel.release_date.to_string(),
+                group
+                    .map(|g| g.name.unwrap_or_else(|| g.uuid.to_string()))
+                    .unwrap_or_default(),
                 if p.is_file() {
                     p.display().to_string()
                 } else {
-                    let relative_path = PathBuf::from(rstore.store_name).join(art.path);
+                    let relative_path =
+                        PathBuf::from(rstore.store_name).join(rel.path_in_store(&art));
                     format!("{} is not available locally", relative_path.display())
                 },
-            ]
+            ];
+            if let Some(state) = file_states.as_ref().map(|states| &states[i]) {
+                if state.is_damaged() {
+                    damaged += 1;
+                    row.push(state.to_string().red().to_string());
+                } else {
+                    row.push(state.to_string());
+                }
+            }
+            row
         })
         .collect::<Vec<Vec<_>>>();
 
-    crate::commands::util::display_data(header, data, csv)
+    let shown = data.len();
+    crate::commands::util::display_data(header, data, csv)?;
+    if total > 0 {
+        print_page_footer(offset, shown, total)?;
+    }
+    if damaged > 0 {
+        return Err(anyhow!(
+            "{} release artifacts are corrupted or truncated",
+            damaged
+        ));
+    }
+    Ok(())
+}
+
+/// The number of release files that are verified at the same time
+const VERIFY_FILES_CONCURRENCY: usize = 8;
+
+/// The state of a release file, compared to the size and checksum recorded in the database
+#[derive(Debug, PartialEq, Eq)]
+enum ReleaseFileState {
+    /// Size and checksum match
+    Ok,
+    /// The size matches, but there is no checksum recorded for the artifact
+    NoChecksum,
+    /// The file does not exist (the release store is not available on this host)
+    Missing,
+    Truncated {
+        expected: u64,
+        actual: u64,
+    },
+    SizeMismatch {
+        expected: u64,
+        actual: u64,
+    },
+    ChecksumMismatch,
+}
+
+impl ReleaseFileState {
+    fn is_damaged(&self) -> bool {
+        matches!(
+            self,
+            ReleaseFileState::Truncated { .. }
+                | ReleaseFileState::SizeMismatch { .. }
+                | ReleaseFileState::ChecksumMismatch
+        )
+    }
+}
+
+impl std::fmt::Display for ReleaseFileState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReleaseFileState::Ok => write!(f, "ok"),
+            ReleaseFileState::NoChecksum => write!(f, "ok (size only, no checksum recorded)"),
+            ReleaseFileState::Missing => write!(f, "missing"),
+            ReleaseFileState::Truncated { expected, actual } => {
+                write!(f, "truncated ({actual} of {expected} bytes)")
+            }
+            ReleaseFileState::SizeMismatch { expected, actual } => {
+                write!(f, "size mismatch ({actual} instead of {expected} bytes)")
+            }
+            ReleaseFileState::ChecksumMismatch => write!(f, "checksum mismatch"),
+        }
+    }
+}
+
+/// Compare a file of an artifact (e.g. a release) with the size and SHA-256 checksum that were
+/// recorded for the artifact
+async fn verify_release_file(
+    path: &Path,
+    size: Option<i64>,
+    sha256: Option<&str>,
+) -> Result<ReleaseFileState> {
+    let metadata = match tokio::fs::metadata(path).await {
+        Ok(metadata) if metadata.is_file() => metadata,
+        Ok(_) => return Ok(ReleaseFileState::Missing),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(ReleaseFileState::Missing),
+        Err(e) => return Err(e).with_context(|| anyhow!("Reading metadata of {}", path.display())),
+    };
+
+    if let Some(expected) = size.and_then(|s| u64::try_from(s).ok()) {
+        let actual = metadata.len();
+        if actual < expected {
+            return Ok(ReleaseFileState::Truncated { expected, actual });
+        } else if actual != expected {
+            return Ok(ReleaseFileState::SizeMismatch { expected, actual });
+        }
+    }
+
+    let Some(expected) = sha256 else {
+        return Ok(ReleaseFileState::NoChecksum);
+    };
+    let file = tokio::fs::File::open(path)
+        .await
+        .with_context(|| anyhow!("Opening {}", path.display()))?;
+    let actual = HashType::Sha256
+        .hash_from_reader(tokio::io::BufReader::new(file))
+        .await
+        .with_context(|| anyhow!("Hashing {}", path.display()))?;
+    if actual.to_string() == expected {
+        Ok(ReleaseFileState::Ok)
+    } else {
+        Ok(ReleaseFileState::ChecksumMismatch)
+    }
+}
+
+/// Export the file system of the container of the job (see "endpoint container export")
+async fn export_workdir(
+    config: &Configuration,
+    job: &models::Job,
+    endpoint: &models::Endpoint,
+    output: &Path,
+) -> Result<()> {
+    let endpoint_name = crate::config::EndpointName::from(endpoint.name.clone());
+    let endpoints =
+        crate::commands::endpoint::connect_to_endpoints(config, &[endpoint_name]).await?;
+    let endpoint = endpoints
+        .first()
+        .ok_or_else(|| anyhow!("Cannot connect to endpoint '{}'", endpoint.name))?;
+    let container = endpoint
+        .get_container_by_id(&job.container_hash)
+        .await?
+        .ok_or_else(|| {
+            anyhow!(
+                "The container {} of job {} does not exist on '{}' anymore",
+                job.container_hash,
+                job.uuid,
+                endpoint.name()
+            )
+        })?;
+    crate::commands::endpoint_container::export(&container, output).await
 }
 
 /// Check if a job is successful
 ///
 /// Returns Ok(None) if cannot be decided
 fn is_job_successfull(job: &models::Job) -> Result<Option<bool>> {
-    crate::log::ParsedLog::from_str(&job.log_text).map(|pl| pl.is_successfull().to_bool())
+    // Only the result is needed, the items are not kept
+    let mut parser = crate::log::LogParser::default();
+    for line in job.log_text.split_inclusive('\n') {
+        parser.feed(line.as_bytes())?;
+    }
+    parser.finish()?;
+    Ok(parser.result().to_bool())
+}
+
+/// Check if the jobs are successful, parsing their logs in parallel
+///
+/// The results are in the order of the jobs. Parsing the logs is the expensive part of listing
+/// many jobs, so the logs are parsed on all cores instead of one after another.
+fn jobs_successfull(jobs: &[&models::Job]) -> Result<Vec<Option<bool>>> {
+    use rayon::iter::IntoParallelRefIterator;
+    use rayon::iter::ParallelIterator;
+
+    jobs.par_iter().map(|job| is_job_successfull(job)).collect()
+}
+
+/// The number of job logs that `with_job_results()` parses in parallel before yielding the results
+const JOB_RESULTS_CHUNK_SIZE: usize = 64;
+
+/// Pair the items with the result of their job (see `is_job_successfull()`), in order
+///
+/// The logs are parsed in parallel, a chunk at a time, and the results of a chunk are yielded
+/// as soon as it is parsed, so that the rows of a listing can be printed while the remaining logs
+/// are parsed. Items for which `job_of` returns no job get no result.
+fn with_job_results<T, I, F>(items: I, job_of: F) -> impl Iterator<Item = Result<(T, Option<bool>)>>
+where
+    I: IntoIterator<Item = T>,
+    T: Sync,
+    F: Fn(&T) -> Option<&models::Job> + Send + Sync,
+{
+    use rayon::iter::IntoParallelRefIterator;
+    use rayon::iter::ParallelIterator;
+
+    let mut items = items.into_iter();
+    std::iter::from_fn(move || {
+        let chunk = items
+            .by_ref()
+            .take(JOB_RESULTS_CHUNK_SIZE)
+            .collect::<Vec<_>>();
+        if chunk.is_empty() {
+            return None;
+        }
+
+        let results = chunk
+            .par_iter()
+            .map(|item| {
+                job_of(item)
+                    .map(is_job_successfull)
+                    .transpose()
+                    .map(Option::flatten)
+            })
+            .collect::<Vec<_>>();
+        Some(
+            chunk
+                .into_iter()
+                .zip(results)
+                .map(|(item, result)| result.map(|result| (item, result))),
+        )
+    })
+    .flatten()
 }
@@ -0,0 +1,402 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Implementation of the 'doctor' subcommand
+//!
+//! The checks do not stop at the first problem (except for an invalid configuration, which the
+//! other checks depend on), so that all problems of the environment are reported at once.
+
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use clap::ArgMatches;
+use colored::Colorize;
+use diesel_migrations::MigrationHarness;
+
+use crate::config::check_compatibility;
+use crate::config::Configuration;
+use crate::config::EndpointType;
+use crate::config::NotValidatedConfiguration;
+use crate::config::ReleaseMetadataGenerator;
+use crate::db::DbConnectionConfig;
+use crate::util::error::ErrorCategory;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Status {
+    Ok,
+    /// Only some commands or settings are affected
+    Warning,
+    Failed,
+}
+
+/// The result of one check of the environment
+#[derive(Debug)]
+struct Check {
+    name: String,
+    status: Status,
+    /// What is wrong, for warnings and failed checks
+    problem: Option<String>,
+    /// How the problem can be fixed
+    hint: Option<String>,
+}
+
+impl Check {
+    fn ok(name: impl Into<String>) -> Self {
+        Check {
+            name: name.into(),
+            status: Status::Ok,
+            problem: None,
+            hint: None,
+        }
+    }
+
+    fn problem(
+        name: impl Into<String>,
+        status: Status,
+        problem: impl Into<String>,
+        hint: impl Into<String>,
+    ) -> Self {
+        Check {
+            name: name.into(),
+            status,
+            problem: Some(problem.into()),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn write_to(&self, out: &mut impl Write) -> Result<()> {
+        let status = match self.status {
+            Status::Ok => " OK ".green(),
+            Status::Warning => "WARN".yellow(),
+            Status::Failed => "FAIL".red(),
+        };
+        match self.problem.as_ref() {
+            Some(problem) => writeln!(out, "[{status}] {}: {problem}", self.name)?,
+            None => writeln!(out, "[{status}] {}", self.name)?,
+        }
+        if let Some(hint) = self.hint.as_ref() {
+            writeln!(out, "       {} {hint}", "hint:".bold())?;
+        }
+        Ok(())
+    }
+}
+
+/// Implementation of the "doctor" subcommand
+pub async fn doctor(
+    cli: &ArgMatches,
+    repo_path: &Path,
+    config_files: &[PathBuf],
+    profile: Option<&str>,
+) -> Result<()> {
+    let out = std::io::stdout();
+    let mut outlock = out.lock();
+
+    let config = match load_config(config_files, profile) {
+        Ok(config) => config,
+        Err(e) => {
+            Check::problem(
+                "configuration",
+                Status::Failed,
+                error_chain(&e),
+                "Run `butido validate-config` to find the location of the problems",
+            )
+            .write_to(&mut outlock)?;
+            return Err(anyhow!(
+                "The configuration is invalid, the other checks were skipped"
+            ))
+            .context(ErrorCategory::Config);
+        }
+    };
+
+    let mut checks = vec![Check::ok("configuration")];
+    checks.extend(check_database(&config, cli));
+    checks.extend(check_endpoints(&config).await);
+    checks.extend(check_directories(&config));
+    checks.extend(check_tools(&config, repo_path));
+
+    for check in checks.iter() {
+        check.write_to(&mut outlock)?;
+    }
+
+    let failed = checks
+        .iter()
+        .filter(|check| check.status == Status::Failed)
+        .count();
+    if failed > 0 {
+        return Err(anyhow!("{} of {} checks failed", failed, checks.len()));
+    }
+    Ok(())
+}
+
+fn load_config(config_files: &[PathBuf], profile: Option<&str>) -> Result<Configuration> {
+    let config = crate::config::load_config(config_files, profile)?;
+    check_compatibility(&config)?;
+    config
+        .try_deserialize::<NotValidatedConfiguration>()
+        .context("Failed to load (type check) the butido configuration")?
+        .validate()
+}
+
+fn error_chain(e: &anyhow::Error) -> String {
+    e.chain()
+        .map(|cause| cause.to_string())
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
+/// Check that the database can be connected to and that all migrations were run
+fn check_database(config: &Configuration, cli: &ArgMatches) -> Vec<Check> {
+    let connection = DbConnectionConfig::parse(config, cli)
+        .and_then(|db_connection_config| db_connection_config.establish_connection());
+    let mut conn = match connection {
+        Ok(conn) => conn,
+        Err(e) => {
+            return vec![Check::problem(
+                "database connection",
+                Status::Failed,
+                error_chain(&e),
+                "Check that PostgreSQL is running and the database_* settings (or the --database-* options)",
+            )]
+        }
+    };
+
+    let migrations = match conn.pending_migrations(super::db::MIGRATIONS) {
+        Ok(pending) if pending.is_empty() => Check::ok("database migrations"),
+        Ok(pending) => Check::problem(
+            "database migrations",
+            Status::Failed,
+            format!("{} migration(s) not applied", pending.len()),
+            "Run `butido db setup`",
+        ),
+        Err(e) => Check::problem(
+            "database migrations",
+            Status::Failed,
+            e.to_string(),
+            "Check that the database user can read the migrations table",
+        ),
+    };
+    vec![Check::ok("database connection"), migrations]
+}
+
+/// Check that every (Docker) endpoint can be connected to, has a compatible (API) version and has
+/// all images
+async fn check_endpoints(config: &Configuration) -> Vec<Check> {
+    let mut checks = Vec::new();
+    for (name, endpoint) in config.docker().endpoints() {
+        if *endpoint.endpoint_type() == EndpointType::Kubernetes {
+            // Kubernetes endpoints only need kubectl (see the tools check)
+            continue;
+        }
+
+        let check_name = format!("endpoint {name}");
+
+        let connected =
+            super::endpoint::connect_to_endpoints(config, std::slice::from_ref(name)).await;
+        checks.push(match connected {
+            Ok(_) => Check::ok(check_name),
+            Err(e) => Check::problem(
+                check_name,
+                Status::Failed,
+                error_chain(&e),
+                format!(
+                    "Check that the Docker daemon at {} is reachable, that its version is one of \
+                     docker.docker_versions and docker.docker_api_versions and that all images are \
+                     pulled (`butido endpoint {name} images present`)",
+                    endpoint.uri()
+                ),
+            ),
+        });
+    }
+    checks
+}
+
+/// Check that the directories that butido writes to exist and are writable
+fn check_directories(config: &Configuration) -> Vec<Check> {
+    let stores = config.release_stores().iter().map(|store| {
+        (
+            format!("release store {store}"),
+            config.releases_directory().join(store),
+            Status::Failed,
+        )
+    });
+    [
+        (
+            String::from("staging directory"),
+            config.staging_directory().clone(),
+            Status::Failed,
+        ),
+        (
+            String::from("source cache"),
+            config.source_cache_root().clone(),
+            Status::Failed,
+        ),
+        // Only needed if the logs are written to files
+        (
+            String::from("log directory"),
+            config.log_dir().clone(),
+            Status::Warning,
+        ),
+    ]
+    .into_iter()
+    .chain(stores)
+    .map(|(name, path, status)| match check_writable(&path) {
+        Ok(()) => Check::ok(name),
+        Err(e) => Check::problem(
+            name,
+            status,
+            error_chain(&e),
+            format!(
+                "Create {} and make it writable for the user running butido",
+                path.display()
+            ),
+        ),
+    })
+    .collect()
+}
+
+/// Check that a file can be created in the directory
+fn check_writable(dir: &Path) -> Result<()> {
+    if !dir.is_dir() {
+        return Err(anyhow!("Not a directory: {}", dir.display()));
+    }
+
+    let probe = dir.join(format!(".butido-doctor-{}", std::process::id()));
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+        .with_context(|| anyhow!("Cannot create a file in {}", dir.display()))?;
+    std::fs::remove_file(&probe).with_context(|| anyhow!("Removing {}", probe.display()))
+}
+
+/// The external programs that butido runs with the configuration, with the reason why they are
+/// needed and whether they are only needed by commands that are rarely used
+fn required_tools(config: &Configuration) -> Vec<(String, String, Status)> {
+    let mut tools = Vec::new();
+    let mut require = |program: &str, reason: String, status: Status| {
+        tools.push((program.to_string(), reason, status));
+    };
+
+    if config
+        .docker()
+        .endpoints()
+        .values()
+        .any(|endpoint| *endpoint.endpoint_type() == EndpointType::Kubernetes)
+    {
+        require(
+            "kubectl",
+            String::from("Kubernetes endpoints"),
+            Status::Failed,
+        );
+    }
+    for hook in config.post_build_hooks() {
+        let program = match hook.image() {
+            Some(_) => "docker",
+            None => hook.command()[0].as_str(), // safe because the configuration validates it
+        };
+        require(
+            program,
+            format!("post-build hook '{}'", hook.name()),
+            Status::Failed,
+        );
+    }
+    for hook in config.pre_submit_hooks() {
+        if let Some(command) = hook.command().as_ref().and_then(|c| c.first()) {
+            require(
+                command,
+                format!("pre-submit hook '{}'", hook.name()),
+                Status::Failed,
+            );
+        }
+    }
+    for (store, generators) in config.release_metadata() {
+        for generator in generators {
+            let program = match generator {
+                ReleaseMetadataGenerator::Rpm => "createrepo_c",
+                ReleaseMetadataGenerator::Deb => "dpkg-scanpackages",
+                ReleaseMetadataGenerator::Json { .. } => continue,
+                ReleaseMetadataGenerator::Command { command, .. } => command[0].as_str(),
+            };
+            require(
+                program,
+                format!("release metadata of {store}"),
+                Status::Failed,
+            );
+        }
+    }
+    if !config.release_mirrors().is_empty() {
+        require(
+            "rsync",
+            String::from("`butido release sync`"),
+            Status::Warning,
+        );
+    }
+    if let Some(notifications) = config.notifications().as_ref() {
+        require(
+            &notifications.command()[0],
+            String::from("notifications"),
+            Status::Warning,
+        );
+    }
+    require(
+        &config.shellcheck_command().to_string_lossy(),
+        String::from("`butido lint`"),
+        Status::Warning,
+    );
+    tools
+}
+
+/// Check that the external programs that are needed with the configuration can be found
+fn check_tools(config: &Configuration, repo_path: &Path) -> Vec<Check> {
+    let mut checks = required_tools(config)
+        .into_iter()
+        .map(|(program, reason, status)| {
+            let name = format!("tool {program} (for {reason})");
+            match which::which(&program) {
+                Ok(_) => Check::ok(name),
+                Err(e) => Check::problem(
+                    name,
+                    status,
+                    e.to_string(),
+                    format!("Install {program} or add its directory to PATH"),
+                ),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if config.script_linter().is_some() {
+        checks.push(match crate::ui::find_linter_command(repo_path, config) {
+            Ok(_) => Check::ok("script linter"),
+            Err(e) => Check::problem(
+                "script linter",
+                Status::Failed,
+                error_chain(&e),
+                "Fix the script_linter setting (relative paths are relative to the repository)",
+            ),
+        });
+    }
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_writable() {
+        let dir = std::env::temp_dir();
+        assert!(check_writable(&dir).is_ok());
+        assert!(check_writable(&dir.join("butido-doctor-does-not-exist")).is_err());
+    }
+}
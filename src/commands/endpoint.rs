@@ -13,6 +13,7 @@
 use std::collections::HashMap;
 use std::io::Write;
 use std::ops::Deref;
+use std::str::FromStr;
 use std::sync::Arc;
 
 use anyhow::anyhow;
@@ -20,17 +21,23 @@ use anyhow::Context;
 use anyhow::Error;
 use anyhow::Result;
 use clap::ArgMatches;
+use diesel::PgConnection;
 use itertools::Itertools;
 use tokio_stream::StreamExt;
-use tracing::{debug, info, trace};
+use tracing::{debug, info, trace, warn};
 
 use crate::config::Configuration;
+use crate::config::ContainerRetention;
 use crate::config::EndpointName;
+use crate::config::EndpointType;
+use crate::db::DbConnectionConfig;
+use crate::endpoint::ContainerStat;
 use crate::endpoint::Endpoint;
 use crate::util::docker::ImageNameLookup;
 use crate::util::progress::ProgressBars;
 
 pub async fn endpoint(
+    db_connection_config: DbConnectionConfig<'_>,
     matches: &ArgMatches,
     config: &Configuration,
     progress_generator: ProgressBars,
@@ -56,9 +63,17 @@ pub async fn endpoint(
             stats(endpoint_names, matches, config, progress_generator).await
         }
         Some(("container", matches)) => {
-            crate::commands::endpoint_container::container(endpoint_names, matches, config).await
+            crate::commands::endpoint_container::container(
+                db_connection_config,
+                endpoint_names,
+                matches,
+                config,
+            )
+            .await
+        }
+        Some(("containers", matches)) => {
+            containers(db_connection_config, endpoint_names, matches, config).await
         }
-        Some(("containers", matches)) => containers(endpoint_names, matches, config).await,
         Some(("images", matches)) => images(endpoint_names, matches, config).await,
         Some((other, _)) => Err(anyhow!("Unknown subcommand: {}", other)),
         None => Err(anyhow!("No subcommand")),
@@ -121,72 +136,205 @@ async fn stats(
     progress_generator: ProgressBars,
 ) -> Result<()> {
     let csv = matches.get_flag("csv");
+    let watch_interval = matches.get_one::<u64>("watch").copied();
+    let threshold = matches.get_one::<f64>("threshold").copied();
     let endpoints = connect_to_endpoints(config, &endpoint_names).await?;
-    let bar = progress_generator.bar()?;
-    bar.set_length(endpoint_names.len() as u64);
-    bar.set_message("Fetching stats");
 
-    let hdr = crate::commands::util::mk_header(
-        [
-            "Name",
-            "Containers",
-            "Images",
-            "Id",
-            "Kernel",
-            "Memory",
-            "Memory limit",
-            "Cores",
-            "OS",
-            "System Time",
-        ]
-        .to_vec(),
-    );
+    let mk_hdr = || {
+        crate::commands::util::mk_header(
+            [
+                "Name",
+                "Containers",
+                "Running",
+                "Images",
+                "Id",
+                "Kernel",
+                "Memory",
+                "Memory limit",
+                "Cores",
+                "Load",
+                "OS",
+                "System Time",
+                "Data root",
+                "Data root size",
+                "Available",
+                "Images size",
+                "Volumes size",
+                "Disk usage",
+            ]
+            .to_vec(),
+        )
+    };
 
-    let data = endpoints
-        .into_iter()
-        .map(|endpoint| {
-            let bar = bar.clone();
-            async move {
-                let r = endpoint.stats().await;
+    let Some(interval) = watch_interval else {
+        let bar = progress_generator.bar()?;
+        bar.set_length(endpoint_names.len() as u64);
+        bar.set_message("Fetching stats");
+
+        let stats = fetch_stats_rows(&endpoints, Some(&bar))
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>>>()
+            .inspect_err(|_| {
+                bar.finish_with_message("Fetching stats errored");
+            })?;
+
+        bar.finish_with_message("Fetching stats successful");
+        let threshold_check = threshold.map(|threshold| check_disk_usage(&stats, threshold));
+        let data = stats.into_iter().map(|(_, _, row)| row).collect();
+        crate::commands::util::display_data(mk_hdr(), data, csv)?;
+        return threshold_check.unwrap_or(Ok(()));
+    };
+
+    let interval = std::time::Duration::from_secs(interval);
+    loop {
+        // An endpoint that cannot be reached is left out of this iteration instead of ending the
+        // watch, it shows up again as soon as it is reachable again
+        let (data, errors): (Vec<_>, Vec<_>) = fetch_stats_rows(&endpoints, None)
+            .await
+            .into_iter()
+            .partition_map(|stats| match stats {
+                Ok((_, _, row)) => itertools::Either::Left(row),
+                Err(e) => itertools::Either::Right(e),
+            });
+
+        // Clear the screen and move the cursor to the top left corner before re-rendering
+        print!("\x1b[2J\x1b[H");
+        println!(
+            "Every {}: {}",
+            humantime::format_duration(interval),
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
+        );
+        crate::commands::util::display_data(mk_hdr(), data, csv)?;
+        for error in errors {
+            warn!("{:?}", error);
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Fail if the disk usage of any endpoint is above the threshold (in percent) or unknown
+fn check_disk_usage(stats: &[(String, Option<f64>, Vec<String>)], threshold: f64) -> Result<()> {
+    let unknown = stats
+        .iter()
+        .filter(|(_, usage, _)| usage.is_none())
+        .map(|(name, _, _)| name.as_str())
+        .collect::<Vec<_>>();
+    let above = stats
+        .iter()
+        .filter_map(|(name, usage, _)| {
+            usage
+                .filter(|usage| *usage > threshold)
+                .map(|usage| format!("{} ({:.1}%)", name, usage))
+        })
+        .collect::<Vec<_>>();
+
+    if !above.is_empty() {
+        return Err(anyhow!(
+            "Disk usage above {}% on: {}",
+            threshold,
+            above.join(", ")
+        ));
+    }
+    if !unknown.is_empty() {
+        return Err(anyhow!(
+            "Disk usage cannot be determined on: {}",
+            unknown.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// Fetch the stats of the endpoints and format them as rows for the "endpoint stats" table
+///
+/// Returns the name of the endpoint and its disk usage (in percent, if known) with each row. The
+/// rows are sorted by name, the endpoints whose stats could not be fetched come last.
+async fn fetch_stats_rows(
+    endpoints: &[Arc<Endpoint>],
+    bar: Option<&indicatif::ProgressBar>,
+) -> Vec<Result<(String, Option<f64>, Vec<String>)>> {
+    let mut rows = endpoints
+        .iter()
+        .map(|endpoint| async move {
+            let row = fetch_stats_row(endpoint)
+                .await
+                .with_context(|| anyhow!("Fetching stats of endpoint {}", endpoint.name()));
+            if let Some(bar) = bar {
                 bar.inc(1);
-                r
             }
+            row
         })
         .collect::<futures::stream::FuturesUnordered<_>>()
-        .collect::<Result<Vec<_>>>()
-        .await
-        .inspect_err(|_| {
-            bar.finish_with_message("Fetching stats errored");
-        })?
-        .into_iter()
-        .map(|stat| {
-            vec![
-                stat.name,
-                stat.containers.to_string(),
-                stat.images.to_string(),
-                stat.id.to_string(),
-                stat.kernel_version,
-                bytesize::ByteSize::b(stat.mem_total).to_string(),
-                stat.memory_limit.to_string(),
-                stat.n_cpu.to_string(),
-                stat.operating_system.to_string(),
-                stat.system_time.unwrap_or_else(|| String::from("unknown")),
-            ]
-        })
-        .collect();
+        .collect::<Vec<_>>()
+        .await;
+
+    rows.sort_by(|a, b| match (a, b) {
+        (Ok(a), Ok(b)) => a.2[0].cmp(&b.2[0]),
+        (Ok(_), Err(_)) => std::cmp::Ordering::Less,
+        (Err(_), Ok(_)) => std::cmp::Ordering::Greater,
+        (Err(_), Err(_)) => std::cmp::Ordering::Equal,
+    });
+    rows
+}
 
-    bar.finish_with_message("Fetching stats successful");
-    crate::commands::util::display_data(hdr, data, csv)
+/// Fetch the stats of one endpoint for the "endpoint stats" table
+///
+/// The system information and the containers of the endpoint are fetched only once.
+async fn fetch_stats_row(endpoint: &Endpoint) -> Result<(String, Option<f64>, Vec<String>)> {
+    let bytes = |bytes: u64| bytesize::ByteSize::b(bytes).to_string();
+    let info = endpoint.info().await?;
+    let containers = endpoint.container_stats().await?;
+    let running = containers
+        .iter()
+        .filter(|container| container.is_running_butido_container())
+        .count();
+    let load = endpoint
+        .load_average(&containers)
+        .await?
+        .unwrap_or_else(|| String::from("-"));
+    let disk_usage = endpoint.disk_usage(&info, &containers).await?;
+    let used_percent = disk_usage.used_percent();
+    let stat = crate::endpoint::EndpointStats::from(info);
+
+    let row = vec![
+        stat.name,
+        stat.containers.to_string(),
+        running.to_string(),
+        stat.images.to_string(),
+        stat.id.to_string(),
+        stat.kernel_version,
+        bytes(stat.mem_total),
+        stat.memory_limit.to_string(),
+        stat.n_cpu.to_string(),
+        load,
+        stat.operating_system.to_string(),
+        stat.system_time.unwrap_or_else(|| String::from("unknown")),
+        disk_usage.data_root,
+        bytes(disk_usage.data_root_size),
+        disk_usage
+            .available
+            .map(bytes)
+            .unwrap_or_else(|| String::from("unknown")),
+        bytes(disk_usage.images_size),
+        bytes(disk_usage.volumes_size),
+        used_percent
+            .map(|usage| format!("{:.1}%", usage))
+            .unwrap_or_else(|| String::from("unknown")),
+    ];
+    Ok((endpoint.name().to_string(), used_percent, row))
 }
 
 async fn containers(
+    db_connection_config: DbConnectionConfig<'_>,
     endpoint_names: Vec<EndpointName>,
     matches: &ArgMatches,
     config: &Configuration,
 ) -> Result<()> {
     match matches.subcommand() {
         Some(("list", matches)) => containers_list(endpoint_names, matches, config).await,
-        Some(("prune", matches)) => containers_prune(endpoint_names, matches, config).await,
+        Some(("prune", matches)) => {
+            containers_prune(db_connection_config, endpoint_names, matches, config).await
+        }
         Some(("top", matches)) => containers_top(endpoint_names, matches, config).await,
         Some(("stop", matches)) => containers_stop(endpoint_names, matches, config).await,
         Some((other, _)) => Err(anyhow!("Unknown subcommand: {}", other)),
@@ -275,12 +423,22 @@ async fn containers_list(
 }
 
 async fn containers_prune(
+    db_connection_config: DbConnectionConfig<'_>,
     endpoint_names: Vec<EndpointName>,
     matches: &ArgMatches,
     config: &Configuration,
 ) -> Result<()> {
     let older_than_filter = crate::commands::util::get_date_filter("older_than", matches)?;
     let newer_than_filter = crate::commands::util::get_date_filter("newer_than", matches)?;
+    let policy = if matches.get_flag("policy") {
+        Some((
+            config.containers().retention(),
+            db_connection_config.establish_pool()?,
+        ))
+    } else {
+        None
+    };
+    let policy = policy.as_ref();
 
     let stats = connect_to_endpoints(config, &endpoint_names)
         .await?
@@ -290,7 +448,16 @@ async fn containers_prune(
                 .container_stats()
                 .await?
                 .into_iter()
-                .filter(|stat| stat.state == "exited")
+                .map(|stat| match policy {
+                    Some((retention, pool)) => {
+                        expired_by_policy(&stat, retention, &mut pool.get()?)
+                            .map(|expired| expired.then_some(stat))
+                    }
+                    None => Ok(Some(stat).filter(|stat| stat.state == "exited")),
+                })
+                .filter_map(Result::transpose)
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
                 .filter(|stat| {
                     older_than_filter
                         .as_ref()
@@ -323,6 +490,11 @@ async fn containers_prune(
         .into_iter()
         .flat_map(Vec::into_iter)
         .map(|(ep, stat)| async move {
+            if policy.is_some() {
+                // The containers of failed jobs may still be running
+                return ep.remove_container(&stat.id).await;
+            }
+
             ep.get_container_by_id(&stat.id)
                 .await?
                 .ok_or_else(|| anyhow!("Failed to find existing container {}", stat.id))?
@@ -335,6 +507,46 @@ async fn containers_prune(
         .await
 }
 
+/// Whether the container of a job is removed by the retention policy of the containers
+///
+/// Only containers of jobs that are recorded in the database (i.e., finished jobs) are removed.
+/// The container of a job that was retried or whose failure was classified is the container of a
+/// failed job, even if its log reports success.
+fn expired_by_policy(
+    stat: &ContainerStat,
+    retention: &ContainerRetention,
+    conn: &mut PgConnection,
+) -> Result<bool> {
+    if !stat.is_butido_container() {
+        return Ok(false);
+    }
+    let Some(job) = crate::db::models::Job::with_container_hash(conn, &stat.id)? else {
+        return Ok(false);
+    };
+
+    let successful = !job.retried
+        && job.failure_class.is_none()
+        && crate::log::ParsedLog::from_str(&job.log_text)?
+            .is_successfull()
+            .to_bool()
+            .unwrap_or(false);
+    Ok(is_expired(
+        retention,
+        successful,
+        chrono::Utc::now() - stat.created,
+    ))
+}
+
+/// Whether a container of the passed age is removed by the retention policy
+fn is_expired(retention: &ContainerRetention, successful: bool, age: chrono::Duration) -> bool {
+    if successful {
+        return retention.remove_successful();
+    }
+    retention
+        .keep_failed_hours()
+        .is_some_and(|hours| age > chrono::Duration::hours(hours as i64))
+}
+
 async fn containers_top(
     endpoint_names: Vec<EndpointName>,
     matches: &ArgMatches,
@@ -603,6 +815,8 @@ async fn images_present(
 
 /// Helper function to connect to all endpoints from the configuration, that appear (by name) in
 /// the `endpoint_names` list
+///
+/// Kubernetes endpoints are skipped, because they are not Docker endpoints.
 pub(super) async fn connect_to_endpoints(
     config: &Configuration,
     endpoint_names: &[EndpointName],
@@ -612,6 +826,13 @@ pub(super) async fn connect_to_endpoints(
         .endpoints()
         .iter()
         .filter(|(ep_name, _)| endpoint_names.contains(ep_name))
+        .filter(|(ep_name, ep_cfg)| {
+            let is_kubernetes = *ep_cfg.endpoint_type() == EndpointType::Kubernetes;
+            if is_kubernetes {
+                debug!("Skipping Kubernetes endpoint {}", ep_name);
+            }
+            !is_kubernetes
+        })
         .map(|(ep_name, ep_cfg)| {
             crate::endpoint::EndpointConfiguration::builder()
                 .endpoint_name(ep_name.clone())
@@ -642,3 +863,25 @@ pub(super) async fn connect_to_endpoints(
 
     crate::endpoint::util::setup_endpoints(endpoint_configurations).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_expired() {
+        let hours = chrono::Duration::hours;
+
+        let keep_all = toml::from_str::<ContainerRetention>("").unwrap();
+        assert!(!is_expired(&keep_all, true, hours(1000)));
+        assert!(!is_expired(&keep_all, false, hours(1000)));
+
+        let retention = toml::from_str::<ContainerRetention>(
+            "remove_successful = true\nkeep_failed_hours = 48",
+        )
+        .unwrap();
+        assert!(is_expired(&retention, true, hours(0)));
+        assert!(!is_expired(&retention, false, hours(47)));
+        assert!(is_expired(&retention, false, hours(49)));
+    }
+}
@@ -11,18 +11,33 @@
 //! Implementation of the 'endpoint container' subcommand
 
 use std::borrow::Cow;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
 
 use anyhow::anyhow;
+use anyhow::Context;
 use anyhow::Error;
 use anyhow::Result;
 use clap::ArgMatches;
+use diesel::ExpressionMethods;
+use diesel::OptionalExtension;
+use diesel::QueryDsl;
+use diesel::RunQueryDsl;
+use shiplift::rep::ContainerDetails;
 use shiplift::Container;
 use tokio_stream::StreamExt;
+use tracing::warn;
+use uuid::Uuid;
 
 use crate::config::Configuration;
 use crate::config::EndpointName;
+use crate::db::models;
+use crate::db::DbConnectionConfig;
+use crate::schema;
 
 pub async fn container(
+    db_connection_config: DbConnectionConfig<'_>,
     endpoint_names: Vec<EndpointName>,
     matches: &ArgMatches,
     config: &Configuration,
@@ -118,7 +133,11 @@ pub async fn container(
                 Ok(())
             }
         }
-        Some(("inspect", _)) => inspect(container).await,
+        Some(("inspect", _)) => inspect(container, db_connection_config).await,
+        Some(("export", matches)) => {
+            let output = matches.get_one::<PathBuf>("output").unwrap(); // safe by clap
+            export(&container, output).await
+        }
         Some((other, _)) => Err(anyhow!("Unknown subcommand: {}", other)),
         None => Err(anyhow!("No subcommand")),
     }
@@ -159,7 +178,6 @@ async fn stop(matches: &ArgMatches, container: Container<'_>) -> Result<()> {
 
 async fn exec(matches: &ArgMatches, container: Container<'_>) -> Result<()> {
     use futures::TryStreamExt;
-    use std::io::Write;
 
     let execopts = shiplift::builder::ExecContainerOptions::builder()
         .cmd({
@@ -190,6 +208,90 @@ async fn exec(matches: &ArgMatches, container: Container<'_>) -> Result<()> {
         .await
 }
 
+/// Export the file system of the container as a TAR archive to `output`
+///
+/// The archive is compressed with zstd if the name of the output file ends with ".zst".
+/// Volumes and mounts of the container are not part of the archive.
+pub(super) async fn export(container: &Container<'_>, output: &Path) -> Result<()> {
+    if output.exists() {
+        return Err(anyhow!("{} exists already", output.display()));
+    }
+    let file =
+        std::fs::File::create(output).with_context(|| anyhow!("Creating {}", output.display()))?;
+    let compress = output.extension().is_some_and(|ext| ext == "zst");
+
+    // Writing (and compressing) the archive blocks, so it is done in a blocking task that
+    // receives the chunks of the export
+    let (sender, receiver) = tokio::sync::mpsc::channel::<Vec<u8>>(4);
+    let writer = tokio::task::spawn_blocking(move || -> Result<u64> {
+        if compress {
+            let mut encoder = zstd::Encoder::new(file, 0)?;
+            let written = write_chunks(receiver, &mut encoder)?;
+            encoder.finish()?;
+            Ok(written)
+        } else {
+            let mut writer = std::io::BufWriter::new(file);
+            let written = write_chunks(receiver, &mut writer)?;
+            writer.flush()?;
+            Ok(written)
+        }
+    });
+
+    let exported = send_export(container, sender).await;
+    // A failed write closes the channel, which ends the export early, so the error of the
+    // writer is returned first
+    let written = writer
+        .await
+        .context("Writing the archive panicked")
+        .and_then(|written| written)
+        .and_then(|written| exported.map(|_| written));
+
+    match written {
+        Ok(n) => writeln!(
+            std::io::stdout().lock(),
+            "Exported container {} ({}) to {}",
+            container.id(),
+            bytesize::ByteSize::b(n),
+            output.display()
+        )
+        .map_err(Error::from),
+        Err(e) => {
+            // Do not leave a truncated archive behind
+            let _ = std::fs::remove_file(output);
+            Err(e).with_context(|| anyhow!("Exporting container {}", container.id()))
+        }
+    }
+}
+
+/// Send the chunks of the exported file system of the container to `sender`
+///
+/// Stops early (without an error) if the receiver is gone.
+async fn send_export(
+    container: &Container<'_>,
+    sender: tokio::sync::mpsc::Sender<Vec<u8>>,
+) -> Result<()> {
+    let mut stream = container.export();
+    while let Some(chunk) = stream.next().await {
+        if sender.send(chunk?).await.is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Write the received chunks to `writer`, returns the (uncompressed) size
+fn write_chunks(
+    mut receiver: tokio::sync::mpsc::Receiver<Vec<u8>>,
+    writer: &mut impl Write,
+) -> Result<u64> {
+    let mut written = 0;
+    while let Some(chunk) = receiver.blocking_recv() {
+        writer.write_all(&chunk)?;
+        written += chunk.len() as u64;
+    }
+    Ok(written)
+}
+
 // Print inspect details about the container
 //
 //
@@ -199,11 +301,11 @@ async fn exec(matches: &ArgMatches, container: Container<'_>) -> Result<()> {
 //
 // This is the most ugly function of the whole codebase. As ugly as it is: It is simply printing
 // things, nothing here is too complex code-wise (except some nested formatting stuff...)
-async fn inspect(container: Container<'_>) -> Result<()> {
+async fn inspect(container: Container<'_>, conn_cfg: DbConnectionConfig<'_>) -> Result<()> {
     use itertools::Itertools;
-    use std::io::Write;
 
     let d = container.inspect().await?;
+    let butido_context = butido_context(&d, conn_cfg);
 
     fn option_vec<'a>(ov: Option<&Vec<String>>) -> Cow<'a, str> {
         ov.map(|v| format!("Some({})", v.iter().join(", ")))
@@ -546,6 +648,140 @@ async fn inspect(container: Container<'_>) -> Result<()> {
                 format!("\n{s}")
             }
         )
-    )
-    .map_err(Error::from)
+    )?;
+
+    writeln!(std::io::stdout(), "{butido_context}").map_err(Error::from)
+}
+
+/// The UUID of the job that a container was created for
+///
+/// The containers are labeled with the UUID of their job. Containers that were created before the
+/// labels were introduced have it at the end of their name ("butido-<package>-<version>-<uuid>").
+fn job_uuid_of_container(details: &ContainerDetails) -> Option<Uuid> {
+    details
+        .config
+        .labels
+        .as_ref()
+        .and_then(|labels| labels.get(crate::consts::CONTAINER_LABEL_JOB_UUID))
+        .and_then(|uuid| Uuid::parse_str(uuid).ok())
+        .or_else(|| {
+            let name = details
+                .name
+                .trim_start_matches('/')
+                .strip_prefix("butido-")?;
+            let uuid = name.get(name.len().checked_sub(36)?..)?;
+            Uuid::parse_str(uuid).ok()
+        })
+}
+
+/// Describe the job, package and submit a container belongs to
+///
+/// The job is recorded in the database when its container finished, the submit of a job that is
+/// still running is found via the job queue.
+fn butido_context(details: &ContainerDetails, conn_cfg: DbConnectionConfig<'_>) -> String {
+    let job_uuid = job_uuid_of_container(details);
+    let label = |name: &str| {
+        details
+            .config
+            .labels
+            .as_ref()
+            .and_then(|labels| labels.get(name))
+            .map(String::as_str)
+    };
+
+    let mut lines = vec![String::from("butido:")];
+    let recorded = load_job_of_container(details, job_uuid.as_ref(), conn_cfg);
+
+    match recorded {
+        Ok((Some((job, submit, package, endpoint)), _)) => {
+            let state = match job.finished_at {
+                Some(finished_at) => format!("finished at {finished_at}"),
+                None => String::from("finished"),
+            };
+            lines.push(format!("    job: {} ({})", job.uuid, state));
+            lines.push(format!("    package: {} {}", package.name, package.version));
+            lines.push(format!(
+                "    submit: {} (submitted at {}, {})",
+                submit.uuid, submit.submit_time, submit.state
+            ));
+            lines.push(format!("    endpoint: {endpoint}"));
+            lines.push(format!("    details: butido db job {}", job.uuid));
+        }
+        Ok((None, queued_submit)) => {
+            match job_uuid {
+                Some(uuid) => lines.push(format!("    job: {uuid} (not recorded yet)")),
+                None => lines.push(String::from("    job: unknown, not a butido container?")),
+            }
+            if let Some((name, version)) = label(crate::consts::CONTAINER_LABEL_PACKAGE_NAME)
+                .zip(label(crate::consts::CONTAINER_LABEL_PACKAGE_VERSION))
+            {
+                lines.push(format!("    package: {name} {version}"));
+            }
+            if let Some(submit) = queued_submit {
+                lines.push(format!(
+                    "    submit: {} (submitted at {}, {})",
+                    submit.uuid, submit.submit_time, submit.state
+                ));
+                lines.push(format!("    details: butido db submit {}", submit.uuid));
+            }
+        }
+        Err(e) => {
+            warn!(
+                "Cannot load the job of the container from the database: {:#}",
+                e
+            );
+            if let Some(uuid) = job_uuid {
+                lines.push(format!("    job: {uuid}"));
+            }
+            if let Some((name, version)) = label(crate::consts::CONTAINER_LABEL_PACKAGE_NAME)
+                .zip(label(crate::consts::CONTAINER_LABEL_PACKAGE_VERSION))
+            {
+                lines.push(format!("    package: {name} {version}"));
+            }
+        }
+    }
+
+    lines.join("\n")
+}
+
+/// A job that was recorded in the database, with its submit, package and endpoint name
+type RecordedJob = (models::Job, models::Submit, models::Package, String);
+
+/// Load the job of a container from the database
+///
+/// If the job is not recorded yet (because its container is still running), the submit of the job
+/// is loaded from the job queue instead.
+fn load_job_of_container(
+    details: &ContainerDetails,
+    job_uuid: Option<&Uuid>,
+    conn_cfg: DbConnectionConfig<'_>,
+) -> Result<(Option<RecordedJob>, Option<models::Submit>)> {
+    let mut conn = conn_cfg.establish_connection()?;
+    let query = schema::jobs::table
+        .inner_join(schema::submits::table)
+        .inner_join(schema::packages::table)
+        .inner_join(schema::endpoints::table)
+        .select((
+            schema::jobs::all_columns,
+            schema::submits::all_columns,
+            schema::packages::all_columns,
+            schema::endpoints::name,
+        ))
+        .into_boxed();
+    let query = match job_uuid {
+        Some(uuid) => query.filter(schema::jobs::uuid.eq(uuid)),
+        None => query.filter(schema::jobs::container_hash.eq(&details.id)),
+    };
+    let job = query.first::<RecordedJob>(&mut conn).optional()?;
+
+    let queued_submit = match (job.as_ref(), job_uuid) {
+        (None, Some(uuid)) => schema::job_queue::table
+            .inner_join(schema::submits::table)
+            .filter(schema::job_queue::job_uuid.eq(uuid))
+            .select(schema::submits::all_columns)
+            .first::<models::Submit>(&mut conn)
+            .optional()?,
+        _ => None,
+    };
+    Ok((job, queued_submit))
 }
@@ -12,7 +12,6 @@
 
 use std::io::Write;
 use std::path::PathBuf;
-use std::sync::Arc;
 
 use anyhow::Context;
 use anyhow::Error;
@@ -25,9 +24,6 @@ use itertools::Itertools;
 use tracing::{debug, trace};
 
 use crate::config::Configuration;
-use crate::filestore::path::StoreRoot;
-use crate::filestore::ReleaseStore;
-use crate::filestore::StagingStore;
 use crate::package::PackageVersionConstraint;
 use crate::repository::Repository;
 use crate::util::docker::ImageNameLookup;
@@ -73,44 +69,16 @@ pub async fn find_artifact(
         package_name_regex, package_version_constraint
     );
 
-    let release_stores = config
-        .release_stores()
-        .iter()
-        .map(|storename| {
-            let bar_release_loading = progressbars.bar()?;
+    let store_selection = crate::commands::util::store_selection(config, matches)?;
+    let release_stores =
+        crate::commands::util::load_release_stores(config, &progressbars, &store_selection)?;
 
-            let p = config.releases_directory().join(storename);
-            let p_str = p.to_string_lossy();
-            debug!("Loading release directory: {}", p_str);
-            let r = ReleaseStore::load(StoreRoot::new(p.clone())?, &bar_release_loading);
-            if r.is_ok() {
-                bar_release_loading
-                    .finish_with_message(format!("Loaded releases in {p_str} successfully"));
-            } else {
-                bar_release_loading
-                    .finish_with_message(format!("Failed to load releases in {p_str}"));
-            }
-
-            r.map(Arc::new)
-        })
-        .collect::<Result<Vec<_>>>()?;
-
-    let staging_store = if let Some(p) = matches.get_one::<String>("staging_dir").map(PathBuf::from)
-    {
-        let bar_staging_loading = progressbars.bar()?;
-
-        if !p.is_dir() {
-            tokio::fs::create_dir_all(&p).await?;
-        }
-
-        debug!("Loading staging directory: {}", p.display());
-        let r = StagingStore::load(StoreRoot::new(p.clone())?, &bar_staging_loading);
-        if r.is_ok() {
-            bar_staging_loading.finish_with_message("Loaded staging successfully");
-        } else {
-            bar_staging_loading.finish_with_message("Failed to load staging");
-        }
-        Some(r?)
+    let staging_dir = matches
+        .get_one::<String>("staging_dir")
+        .map(PathBuf::from)
+        .filter(|_| store_selection.staging());
+    let staging_store = if let Some(p) = staging_dir {
+        Some(crate::commands::util::load_staging_store(p, &progressbars).await?)
     } else {
         None
     };
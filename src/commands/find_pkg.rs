@@ -10,14 +10,17 @@
 
 //! Implementation of the 'find-pkg' subcommand
 
+use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Result;
 use clap::ArgMatches;
 use futures::stream::StreamExt;
 use futures::stream::TryStreamExt;
+use itertools::Itertools;
 use tracing::trace;
 
 use crate::config::Configuration;
+use crate::package::Package;
 use crate::package::PackageVersionConstraint;
 use crate::repository::Repository;
 use crate::ui::*;
@@ -41,6 +44,11 @@ pub async fn find_pkg(
         .transpose()
         .context("Parsing package version constraint")?;
 
+    let maintainer = matches
+        .get_one::<String>("maintainer")
+        .map(|m| m.to_lowercase());
+    let namespace = matches.get_one::<String>("namespace").map(String::as_str);
+
     let iter = repo
         .packages()
         .filter(|p| package_name_regex.captures(p.name()).is_some())
@@ -50,6 +58,18 @@ pub async fn find_pkg(
                 .map(|v| v.matches(p.version()))
                 .unwrap_or(true)
         })
+        .filter(|p| {
+            maintainer
+                .as_ref()
+                .map(|m| {
+                    p.maintainer()
+                        .as_ref()
+                        .map(|pm| pm.to_lowercase().contains(m))
+                        .unwrap_or(false)
+                })
+                .unwrap_or(true)
+        })
+        .filter(|p| namespace.is_none() || p.name().namespace() == namespace)
         .inspect(|pkg| trace!("Found package: {:?}", pkg));
 
     let out = std::io::stdout();
@@ -59,6 +79,11 @@ pub async fn find_pkg(
             writeln!(outlock, "{} {}", p.name(), p.version())?;
         }
         Ok(())
+    } else if matches.get_flag("explain") {
+        for p in iter {
+            explain_package(&mut outlock, p)?;
+        }
+        Ok(())
     } else {
         let flags = crate::ui::PackagePrintFlags {
             print_all: matches.get_flag("show_all"),
@@ -100,3 +125,53 @@ pub async fn find_pkg(
         .await
     }
 }
+
+/// Print all effective fields of a package and where they come from
+fn explain_package(out: &mut impl std::io::Write, package: &Package) -> Result<()> {
+    let fields = crate::repository::explain(package)
+        .with_context(|| anyhow!("Explaining {}", package.display_name_version()))?;
+
+    write!(
+        out,
+        "{} ({}",
+        package.display_name_version(),
+        package.origin().display()
+    )?;
+    if let Some(overlay) = package.overlay() {
+        write!(out, ", overlay: {}", overlay.display())?;
+    }
+    writeln!(out, ")")?;
+
+    for (level, layer) in package.layers().iter().enumerate() {
+        writeln!(out, "    level {level}: {}", layer.display())?;
+    }
+    writeln!(out)?;
+
+    let key_width = fields.iter().map(|f| f.key().len()).max().unwrap_or(0);
+    for field in fields {
+        let value = if field.value().contains('\n') {
+            format!("<{} lines>", field.value().lines().count())
+        } else {
+            field.value().clone()
+        };
+        let how = if field.is_inherited(package) {
+            "inherited"
+        } else {
+            "defined"
+        };
+        write!(
+            out,
+            "{:key_width$} = {value}  [{how}, level {}: {}",
+            field.key(),
+            field.level(),
+            field.origin().display()
+        )?;
+        if !field.overrides().is_empty() {
+            let levels = field.overrides().iter().map(ToString::to_string).join(", ");
+            write!(out, ", overrides level {levels}")?;
+        }
+        writeln!(out, "]")?;
+    }
+    writeln!(out)?;
+    Ok(())
+}
@@ -0,0 +1,245 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Helpers to get the UUIDs of jobs and submits from the command line arguments
+//!
+//! The UUIDs can be abbreviated to an unambiguous prefix (like short git hashes). If the argument is
+//! omitted on a terminal, the job or submit is selected interactively with a fuzzy search over the
+//! most recent ones.
+
+use std::io::IsTerminal;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use clap::ArgMatches;
+use diesel::ExpressionMethods;
+use diesel::PgConnection;
+use diesel::QueryDsl;
+use diesel::RunQueryDsl;
+use diesel::TextExpressionMethods;
+use uuid::Uuid;
+
+use crate::schema;
+
+/// The number of recent jobs or submits that are offered for the interactive selection
+const PICK_LIMIT: i64 = 500;
+
+/// The number of matching UUIDs that are listed if a prefix is ambiguous
+const MAX_CANDIDATES: i64 = 10;
+
+/// The UUID of the job that was passed as argument `name` (or selected interactively)
+pub fn job_uuid(conn: &mut PgConnection, matches: &ArgMatches, name: &str) -> Result<Uuid> {
+    match matches.get_one::<String>(name) {
+        Some(id) => resolve_job_uuid(conn, id),
+        None => pick_job(conn),
+    }
+}
+
+/// The UUID of the submit that was passed as argument `name` (or selected interactively)
+pub fn submit_uuid(conn: &mut PgConnection, matches: &ArgMatches, name: &str) -> Result<Uuid> {
+    match matches.get_one::<String>(name) {
+        Some(id) => resolve_submit_uuid(conn, id),
+        None => pick_submit(conn),
+    }
+}
+
+/// Resolve a job UUID, which may be abbreviated to an unambiguous prefix
+pub fn resolve_job_uuid(conn: &mut PgConnection, id: &str) -> Result<Uuid> {
+    if let Ok(uuid) = Uuid::parse_str(id) {
+        return Ok(uuid);
+    }
+
+    let candidates = schema::jobs::table
+        .filter(
+            diesel::dsl::sql::<diesel::sql_types::Text>("jobs.uuid::text").like(prefix_pattern(id)),
+        )
+        .select(schema::jobs::uuid)
+        .order_by(schema::jobs::id.desc())
+        .limit(MAX_CANDIDATES + 1)
+        .load::<Uuid>(conn)?;
+    unambiguous("job", id, candidates)
+}
+
+/// Resolve a submit UUID, which may be abbreviated to an unambiguous prefix
+pub fn resolve_submit_uuid(conn: &mut PgConnection, id: &str) -> Result<Uuid> {
+    if let Ok(uuid) = Uuid::parse_str(id) {
+        return Ok(uuid);
+    }
+
+    let candidates = schema::submits::table
+        .filter(
+            diesel::dsl::sql::<diesel::sql_types::Text>("submits.uuid::text")
+                .like(prefix_pattern(id)),
+        )
+        .select(schema::submits::uuid)
+        .order_by(schema::submits::id.desc())
+        .limit(MAX_CANDIDATES + 1)
+        .load::<Uuid>(conn)?;
+    unambiguous("submit", id, candidates)
+}
+
+/// The SQL LIKE pattern for a UUID prefix (the CLI only accepts hex digits and hyphens)
+fn prefix_pattern(prefix: &str) -> String {
+    format!("{}%", prefix.to_lowercase())
+}
+
+/// The only UUID that matches a prefix, or an error that lists the matching UUIDs
+fn unambiguous(what: &str, prefix: &str, candidates: Vec<Uuid>) -> Result<Uuid> {
+    match candidates.as_slice() {
+        [] => Err(anyhow!("No {} UUID starts with '{}'", what, prefix)),
+        [uuid] => Ok(*uuid),
+        _ => {
+            let mut listing = candidates
+                .iter()
+                .take(MAX_CANDIDATES as usize)
+                .map(|uuid| format!("\n    {uuid}"))
+                .collect::<String>();
+            if candidates.len() > MAX_CANDIDATES as usize {
+                listing.push_str("\n    ...");
+            }
+            Err(anyhow!(
+                "The {} UUID prefix '{}' is ambiguous, it matches:{}",
+                what,
+                prefix,
+                listing
+            ))
+        }
+    }
+}
+
+/// Let the user select one of the most recent jobs
+fn pick_job(conn: &mut PgConnection) -> Result<Uuid> {
+    ensure_interactive("job")?;
+
+    let jobs = schema::jobs::table
+        .inner_join(schema::submits::table)
+        .inner_join(schema::packages::table)
+        .select((
+            schema::jobs::uuid,
+            schema::submits::submit_time,
+            schema::packages::name,
+            schema::packages::version,
+            schema::jobs::retried,
+            schema::jobs::failure_class,
+            diesel::dsl::exists(
+                schema::artifacts::table.filter(schema::artifacts::job_id.eq(schema::jobs::id)),
+            ),
+        ))
+        .order_by(schema::jobs::id.desc())
+        .limit(PICK_LIMIT)
+        .load::<(
+            Uuid,
+            chrono::NaiveDateTime,
+            String,
+            String,
+            bool,
+            Option<String>,
+            bool,
+        )>(conn)?;
+
+    let items = jobs
+        .iter()
+        .map(
+            |(uuid, time, name, version, retried, failure_class, has_artifacts)| {
+                let status = match (retried, has_artifacts, failure_class) {
+                    (true, _, _) => "retried",
+                    (false, true, _) => "success",
+                    (false, false, Some(failure_class)) => failure_class.as_str(),
+                    (false, false, None) => "failed",
+                };
+                format!(
+                    "{uuid}  {}  {name} {version}  {status}",
+                    time.format("%Y-%m-%d %H:%M:%S")
+                )
+            },
+        )
+        .collect::<Vec<_>>();
+
+    pick("job", &items).map(|i| jobs[i].0)
+}
+
+/// Let the user select one of the most recent submits
+fn pick_submit(conn: &mut PgConnection) -> Result<Uuid> {
+    ensure_interactive("submit")?;
+
+    let submits = schema::submits::table
+        .inner_join(
+            schema::packages::table
+                .on(schema::submits::requested_package_id.eq(schema::packages::id)),
+        )
+        .select((
+            schema::submits::uuid,
+            schema::submits::submit_time,
+            schema::packages::name,
+            schema::packages::version,
+            schema::submits::state,
+        ))
+        .order_by(schema::submits::id.desc())
+        .limit(PICK_LIMIT)
+        .load::<(Uuid, chrono::NaiveDateTime, String, String, String)>(conn)?;
+
+    let items = submits
+        .iter()
+        .map(|(uuid, time, name, version, state)| {
+            format!(
+                "{uuid}  {}  {name} {version}  {state}",
+                time.format("%Y-%m-%d %H:%M:%S")
+            )
+        })
+        .collect::<Vec<_>>();
+
+    pick("submit", &items).map(|i| submits[i].0)
+}
+
+/// The interactive selection needs a terminal, otherwise the UUID must be passed
+fn ensure_interactive(what: &str) -> Result<()> {
+    if std::io::stdin().is_terminal() && std::io::stderr().is_terminal() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "No {} UUID passed (it can only be selected interactively on a terminal)",
+            what
+        ))
+    }
+}
+
+/// Fuzzy search in `items`, returns the index of the selected item
+fn pick(what: &str, items: &[String]) -> Result<usize> {
+    if items.is_empty() {
+        return Err(anyhow!("No {} in the database", what));
+    }
+
+    dialoguer::FuzzySelect::new()
+        .with_prompt(format!("Select a {what} (type to search, ESC to abort)"))
+        .items(items)
+        .default(0)
+        .interact_opt()?
+        .ok_or_else(|| anyhow!("No {} selected", what))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unambiguous() {
+        let a = Uuid::parse_str("1a2b3c4d-0000-4000-8000-000000000001").unwrap();
+        let b = Uuid::parse_str("1a2b3c4d-0000-4000-8000-000000000002").unwrap();
+
+        assert_eq!(unambiguous("job", "1a2b", vec![a]).unwrap(), a);
+        assert!(unambiguous("job", "ffff", vec![]).is_err());
+
+        let err = unambiguous("job", "1a2b", vec![a, b])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("ambiguous"));
+        assert!(err.contains(&b.to_string()));
+    }
+}
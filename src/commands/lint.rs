@@ -10,15 +10,21 @@
 
 //! Implementation of the 'lint' subcommand
 
+use std::io::Write;
 use std::path::Path;
 
 use anyhow::anyhow;
+use anyhow::Context;
 use anyhow::Result;
 use clap::ArgMatches;
+use tokio_stream::StreamExt;
 
 use crate::config::*;
+use crate::package::Package;
 use crate::package::PackageName;
 use crate::package::PackageVersionConstraint;
+use crate::package::ScriptBuilder;
+use crate::package::Shebang;
 use crate::repository::Repository;
 use crate::util::progress::ProgressBars;
 
@@ -30,8 +36,6 @@ pub async fn lint(
     config: &Configuration,
     repo: Repository,
 ) -> Result<()> {
-    let linter = crate::ui::find_linter_command(repo_path, config)?
-        .ok_or_else(|| anyhow!("No linter command found"))?;
     let pname = matches
         .get_one::<String>("package_name")
         .map(|s| s.to_owned())
@@ -55,5 +59,212 @@ pub async fn lint(
                 .unwrap_or(true)
         });
 
+    if matches.get_flag("scripts") {
+        return shellcheck_packages(iter, config, bar).await;
+    }
+
+    let linter = crate::ui::find_linter_command(repo_path, config)?
+        .ok_or_else(|| anyhow!("No linter command found"))?;
     crate::commands::util::lint_packages(iter, &linter, config, bar).await
 }
+
+/// A finding of shellcheck in the script of a package
+struct Finding {
+    /// The line in the rendered script
+    line: usize,
+    column: usize,
+    level: String,
+    message: String,
+}
+
+/// Run shellcheck on the rendered scripts of the packages
+///
+/// The findings are mapped back to the phases (and the pkg.toml files that define the phases).
+async fn shellcheck_packages<'a, I>(
+    iter: I,
+    config: &Configuration,
+    bar: indicatif::ProgressBar,
+) -> Result<()>
+where
+    I: Iterator<Item = &'a Package> + 'a,
+{
+    let shebang = Shebang::from(config.shebang().clone());
+    let packages = iter.collect::<Vec<_>>();
+    bar.set_length(packages.len() as u64);
+
+    let results = packages
+        .into_iter()
+        .map(|pkg| {
+            let shebang = shebang.clone();
+            let bar = bar.clone();
+            async move {
+                let script = ScriptBuilder::new(&shebang).build(
+                    pkg,
+                    config.available_phases(),
+                    *config.strict_script_interpolation(),
+                )?;
+
+                let mut cmd = tokio::process::Command::new(config.shellcheck_command());
+                cmd.arg("--format=gcc").arg("-");
+                let (_, stdout, stderr) = script.lint(cmd).await.with_context(|| {
+                    anyhow!(
+                        "Running {} (see 'shellcheck_command')",
+                        config.shellcheck_command().display()
+                    )
+                })?;
+                bar.inc(1);
+
+                let findings = stdout.lines().filter_map(parse_finding).collect::<Vec<_>>();
+                if findings.is_empty() && !stderr.trim().is_empty() {
+                    return Err(anyhow!("shellcheck failed: {}", stderr.trim()))
+                        .with_context(|| anyhow!("Linting {} {}", pkg.name(), pkg.version()));
+                }
+                Ok((pkg, script, findings))
+            }
+        })
+        .collect::<futures::stream::FuturesUnordered<_>>()
+        .collect::<Result<Vec<_>>>()
+        .await?;
+
+    let out = std::io::stdout();
+    let mut outlock = out.lock();
+    let mut n_findings = 0;
+    for (pkg, script, findings) in results.iter() {
+        for finding in findings {
+            n_findings += 1;
+            let location = match locate_in_phase(script.as_ref(), finding.line) {
+                Some((phase, phase_line)) => {
+                    let file = pkg
+                        .phase_origins()
+                        .iter()
+                        .find(|(name, _)| name.as_str() == phase)
+                        .map(|(_, origin)| origin);
+                    let file_location = file
+                        .and_then(|file| {
+                            let content = std::fs::read_to_string(file).ok()?;
+                            let line = phase_definition_line(&content, phase)?;
+                            Some(format!("{}:{}", file.display(), line + phase_line))
+                        })
+                        .or_else(|| file.map(|file| file.display().to_string()))
+                        .unwrap_or_else(|| String::from("unknown file"));
+                    format!("{file_location}: phase {phase}, line {phase_line}")
+                }
+                None => format!("script line {}", finding.line),
+            };
+
+            writeln!(
+                outlock,
+                "{} {}: {}:{}: {}: {}",
+                pkg.name(),
+                pkg.version(),
+                location,
+                finding.column,
+                finding.level,
+                finding.message
+            )?;
+        }
+    }
+
+    if n_findings == 0 {
+        bar.finish_with_message(format!("Linted {} package scripts", results.len()));
+        Ok(())
+    } else {
+        bar.finish_with_message("Linting errored");
+        Err(anyhow!("shellcheck reported {} problem(s)", n_findings))
+    }
+}
+
+/// Parse a line of the output of `shellcheck --format=gcc`
+fn parse_finding(line: &str) -> Option<Finding> {
+    // Format: "-:LINE:COLUMN: LEVEL: MESSAGE [SCxxxx]"
+    let mut parts = line.strip_prefix("-:")?.splitn(4, ':');
+    let line = parts.next()?.parse().ok()?;
+    let column = parts.next()?.parse().ok()?;
+    let level = parts.next()?.trim().to_string();
+    let message = parts.next()?.trim().to_string();
+    Some(Finding {
+        line,
+        column,
+        level,
+        message,
+    })
+}
+
+/// Find the phase of a line in the rendered script and the line number within the phase
+fn locate_in_phase(script: &str, line: usize) -> Option<(&str, usize)> {
+    // The phases are enclosed in "### phase NAME" and "### / NAME phase" (see `ScriptBuilder`),
+    // followed by an empty line
+    script
+        .lines()
+        .take(line)
+        .enumerate()
+        .filter_map(|(idx, l)| {
+            l.strip_prefix("### phase ")
+                .map(|name| (name.trim(), idx + 1))
+        })
+        .last()
+        .filter(|(_, start)| line > start + 1)
+        .map(|(name, start)| (name, line - start - 1))
+}
+
+/// Find the line in a pkg.toml where the script of a phase starts
+fn phase_definition_line(content: &str, phase: &str) -> Option<usize> {
+    let mut table = String::new();
+    content.lines().enumerate().find_map(|(idx, line)| {
+        let line = line.trim_start();
+        if line.starts_with('[') {
+            table = line
+                .trim_matches(|c| c == '[' || c == ']')
+                .trim()
+                .to_string();
+            return None;
+        }
+
+        let key = line.split('=').next()?.trim();
+        let key = if table == "phases" {
+            key.to_string()
+        } else if table.is_empty() {
+            key.strip_prefix("phases.")?.to_string()
+        } else {
+            return None;
+        };
+
+        (key == phase || key == format!("{phase}.script")).then_some(idx + 1)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate_in_phase() {
+        let script = "#!/bin/bash\n### phase build\n\nmake\nmake install\n### / build phase\n";
+        assert_eq!(locate_in_phase(script, 1), None);
+        assert_eq!(locate_in_phase(script, 4), Some(("build", 1)));
+        assert_eq!(locate_in_phase(script, 5), Some(("build", 2)));
+    }
+
+    #[test]
+    fn test_phase_definition_line() {
+        let content =
+            "name = \"a\"\n\n[phases]\nunpack.script = '''\n  tar xf x\n'''\nbuild.script = '''\n";
+        assert_eq!(phase_definition_line(content, "unpack"), Some(4));
+        assert_eq!(phase_definition_line(content, "build"), Some(7));
+        assert_eq!(phase_definition_line(content, "pack"), None);
+    }
+
+    #[test]
+    fn test_parse_finding() {
+        let finding =
+            parse_finding("-:5:8: warning: Quote this to prevent word splitting. [SC2086]")
+                .unwrap();
+        assert_eq!(finding.line, 5);
+        assert_eq!(finding.column, 8);
+        assert_eq!(finding.level, "warning");
+        assert_eq!(
+            finding.message,
+            "Quote this to prevent word splitting. [SC2086]"
+        );
+    }
+}
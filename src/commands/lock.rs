@@ -0,0 +1,159 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Implementation of the 'lock' subcommand
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use clap::ArgMatches;
+use itertools::Itertools;
+use tracing::warn;
+
+use crate::config::Configuration;
+use crate::config::EndpointName;
+use crate::package::condition::ConditionData;
+use crate::package::Dag;
+use crate::package::PackageName;
+use crate::package::PackageVersionConstraint;
+use crate::repository::Repository;
+use crate::util::docker::ImageName;
+use crate::util::docker::ImageNameLookup;
+use crate::util::error::ErrorCategory;
+use crate::util::lockfile::Lockfile;
+use crate::util::EnvironmentVariableName;
+
+/// Implementation of the "lock" subcommand
+pub async fn lock<F>(matches: &ArgMatches, config: &Configuration, load_repo: F) -> Result<()>
+where
+    F: FnOnce() -> Result<Repository>,
+{
+    match matches.subcommand() {
+        Some(("generate", matches)) => generate(matches, config, load_repo()?).await,
+        Some((other, _)) => Err(anyhow!("Unknown subcommand: {}", other)),
+        None => Err(anyhow!("No subcommand")),
+    }
+}
+
+async fn generate(matches: &ArgMatches, config: &Configuration, repo: Repository) -> Result<()> {
+    let pname = matches
+        .get_one::<String>("package_name")
+        .map(|s| PackageName::from(s.to_owned()))
+        .unwrap(); // safe by clap
+    let pvers = matches
+        .get_one::<String>("package_version_constraint")
+        .map(|s| s.to_owned())
+        .map(PackageVersionConstraint::try_from)
+        .transpose()?;
+    let output = matches
+        .get_one::<String>("output")
+        .map(PathBuf::from)
+        .unwrap(); // safe by clap
+
+    let image_name_lookup = ImageNameLookup::create(config.docker().images())?;
+    let image_name = matches
+        .get_one::<String>("image")
+        .map(|s| image_name_lookup.expand(s))
+        .unwrap()?; // safe by clap
+
+    let additional_env = matches
+        .get_many::<String>("env")
+        .unwrap_or_default()
+        .map(AsRef::as_ref)
+        .map(crate::util::env::parse_to_env)
+        .collect::<Result<Vec<(EnvironmentVariableName, String)>>>()?;
+
+    let packages = repo
+        .packages()
+        .filter(|p| *p.name() == pname)
+        .filter(|p| {
+            pvers
+                .as_ref()
+                .map(|v| v.matches(p.version()))
+                .unwrap_or(true)
+        })
+        .collect::<Vec<_>>();
+    if packages.len() > 1 {
+        return Err(anyhow!(
+            "Found multiple packages ({}). Cannot decide which one to lock",
+            packages.iter().map(|p| p.version()).join(", ")
+        ))
+        .context(ErrorCategory::Resolution);
+    }
+    let package = packages
+        .first()
+        .ok_or_else(|| anyhow!("Found no package."))
+        .context(ErrorCategory::Resolution)?;
+
+    let condition_data = ConditionData {
+        image_name: Some(&image_name),
+        env: &additional_env,
+    };
+    let dag = Dag::for_root_package((*package).clone(), &repo, None, &condition_data)
+        .context(ErrorCategory::Resolution)?;
+
+    let endpoint_names = config
+        .docker()
+        .endpoints()
+        .keys()
+        .cloned()
+        .collect::<Vec<_>>();
+    let digest = image_digest(config, &endpoint_names, &image_name).await?;
+    if digest.is_none() {
+        warn!(
+            "The digest of image {} cannot be determined without Docker endpoints, it is not locked",
+            image_name
+        );
+    }
+
+    let lockfile = Lockfile::from_dag(&dag, &image_name, digest);
+    lockfile.write_to_file(&output)?;
+    writeln!(
+        std::io::stdout(),
+        "Locked {} packages of {} {} in {}",
+        dag.all_packages().len(),
+        package.name(),
+        package.version(),
+        output.display()
+    )
+    .map_err(anyhow::Error::from)
+}
+
+/// The digest (ID) of the image on the Docker endpoints `endpoint_names`
+///
+/// `None` is returned if there are no Docker endpoints (Kubernetes endpoints are skipped, the
+/// nodes of the cluster pull the images themselves). It is an error if the endpoints disagree on
+/// the digest, the image could not be locked then.
+pub(super) async fn image_digest(
+    config: &Configuration,
+    endpoint_names: &[EndpointName],
+    image: &ImageName,
+) -> Result<Option<String>> {
+    let mut digests = Vec::new();
+    for endpoint in crate::commands::endpoint::connect_to_endpoints(config, endpoint_names).await? {
+        digests.push((endpoint.name().clone(), endpoint.image_id(image).await?));
+    }
+
+    if digests.iter().map(|(_, digest)| digest).all_equal() {
+        Ok(digests.into_iter().next().map(|(_, digest)| digest))
+    } else {
+        Err(anyhow!(
+            "The endpoints disagree on the digest of image {}: {}",
+            image,
+            digests
+                .iter()
+                .map(|(name, digest)| format!("{name}: {digest}"))
+                .join(", ")
+        ))
+    }
+}
@@ -11,9 +11,24 @@
 mod build;
 pub use build::build;
 
+mod bump_version;
+pub use bump_version::bump_version;
+
+mod cache;
+pub use cache::cache;
+
+mod config;
+pub use config::config;
+
+mod daemon;
+pub use daemon::daemon;
+
 mod db;
 pub use db::db;
 
+mod doctor;
+pub use doctor::doctor;
+
 mod endpoint;
 pub use endpoint::endpoint;
 pub(super) mod endpoint_container;
@@ -21,6 +36,8 @@ pub(super) mod endpoint_container;
 mod env_of;
 pub use env_of::env_of;
 
+mod ids;
+
 mod find_artifact;
 pub use find_artifact::find_artifact;
 
@@ -33,22 +50,55 @@ pub use dependencies_of::dependencies_of;
 mod lint;
 pub use lint::lint;
 
+mod outdated;
+pub use outdated::outdated;
+
+mod new_package;
+pub use new_package::new_package;
+pub use new_package::TEMPLATES as NEW_PACKAGE_TEMPLATES;
+
+mod watch;
+pub use watch::watch;
+
 mod what_depends;
 pub use what_depends::what_depends;
 
+mod why;
+pub use why::why;
+
+mod plugin;
+pub use plugin::find_plugin;
+pub use plugin::plugin;
+
 mod release;
 pub use release::release;
+mod release_metadata;
+mod release_sync;
+
+mod runtime_closure;
+pub use runtime_closure::runtime_closure;
 
 mod source;
 pub use source::source;
 
+mod store;
+pub use store::store;
+
+mod validate_config;
+pub use validate_config::validate_config;
+
 mod versions_of;
 pub use versions_of::versions_of;
 
 mod tree_of;
 pub use tree_of::tree_of;
 
+mod lock;
+pub use lock::lock;
+
 mod metrics;
 pub use metrics::metrics;
 
 mod util;
+pub use util::set_table_options;
+pub use util::TableOptions;
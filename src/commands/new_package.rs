@@ -0,0 +1,245 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Implementation of the 'new-package' subcommand
+
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use clap::ArgMatches;
+use tracing::{info, warn};
+use url::Url;
+
+use crate::config::Configuration;
+use crate::package::HashType;
+use crate::package::PackageName;
+use crate::package::PackageVersion;
+use crate::repository::Repository;
+
+/// The names of the templates for the phases of a new package
+pub const TEMPLATES: &[&str] = &["empty", "autotools", "cmake", "python"];
+
+/// The phases of a template, as (phase name, script) pairs
+///
+/// `{source}` is replaced with the path of the source file in the container.
+fn template_phases(template: &str) -> Result<&'static [(&'static str, &'static str)]> {
+    const UNPACK: (&str, &str) = ("unpack", "tar xf {source} --strip-components=1\n");
+
+    match template {
+        "empty" => Ok(&[]),
+        "autotools" => Ok(&[
+            UNPACK,
+            ("configure", "./configure --prefix=/usr\n"),
+            (
+                "build",
+                "make -j \"$(nproc)\"\nmake DESTDIR=/tmp/install install\n",
+            ),
+        ]),
+        "cmake" => Ok(&[
+            UNPACK,
+            (
+                "configure",
+                "cmake -S . -B build -DCMAKE_INSTALL_PREFIX=/usr\n",
+            ),
+            (
+                "build",
+                "cmake --build build --parallel \"$(nproc)\"\nDESTDIR=/tmp/install cmake --install build\n",
+            ),
+        ]),
+        "python" => Ok(&[
+            UNPACK,
+            (
+                "build",
+                "python3 -m pip install --no-deps --no-build-isolation --prefix=/usr --root=/tmp/install .\n",
+            ),
+        ]),
+        other => Err(anyhow!("Unknown template: {}", other)),
+    }
+}
+
+/// Implementation of the "new-package" subcommand
+pub async fn new_package(
+    matches: &ArgMatches,
+    config: &Configuration,
+    repo_path: &Path,
+    repo: Repository,
+) -> Result<()> {
+    // safe by clap:
+    let name = matches.get_one::<String>("package_name").unwrap();
+    let version = matches.get_one::<String>("package_version").unwrap();
+    let name = PackageName::from(name.clone());
+    let version = PackageVersion::from(version.clone());
+    let url = matches.get_one::<Url>("url").unwrap(); // safe by clap
+    let source_name = matches.get_one::<String>("source_name").unwrap(); // safe by clap
+    let template = matches.get_one::<String>("template").unwrap(); // safe by clap
+    let timeout = matches.get_one::<u64>("timeout").copied();
+
+    if !repo.find(&name, &version).is_empty() {
+        return Err(anyhow!(
+            "Package {} {} exists already in the repository",
+            name,
+            version
+        ));
+    }
+
+    let path = match matches.get_one::<PathBuf>("path") {
+        Some(dir) => repo_path.join(dir).join("pkg.toml"),
+        None => default_path(repo_path, &repo, &name, &version),
+    };
+    if path.exists() {
+        return Err(anyhow!("{} exists already", path.display()));
+    }
+
+    let phases = template_phases(template)?
+        .iter()
+        .filter(|(phase, _)| {
+            let available = config
+                .available_phases()
+                .iter()
+                .any(|p| p.as_str() == *phase);
+            if !available {
+                warn!(
+                    "Phase {} of the template {} is not an available phase, skipping it",
+                    phase, template
+                );
+            }
+            available
+        })
+        .map(|(phase, script)| {
+            let script = script.replace("{source}", &format!("/inputs/{source_name}.source"));
+            (*phase, script)
+        })
+        .collect::<Vec<_>>();
+
+    info!("Downloading {} to compute its hash", url);
+    let hash = crate::commands::source::hash_of_url(url, &HashType::Sha256, timeout)
+        .await
+        .with_context(|| anyhow!("Computing the hash of {}", url))?;
+
+    let pkg_toml = render_pkg_toml(
+        &name,
+        &version,
+        source_name,
+        url,
+        &hash.to_string(),
+        &phases,
+    );
+
+    let dir = path
+        .parent()
+        .ok_or_else(|| anyhow!("Bug: {} has no parent directory", path.display()))?;
+    std::fs::create_dir_all(dir).with_context(|| anyhow!("Creating {}", dir.display()))?;
+    std::fs::write(&path, pkg_toml).with_context(|| anyhow!("Writing {}", path.display()))?;
+
+    writeln!(std::io::stdout(), "{}", path.display())?;
+    Ok(())
+}
+
+/// The path of the pkg.toml of a new package
+///
+/// A new version of an existing package is put into a directory for the version below the
+/// directory of the package (so that it inherits the settings that are common to all versions),
+/// a new package gets its own directory in the root of the repository.
+fn default_path(
+    repo_path: &Path,
+    repo: &Repository,
+    name: &PackageName,
+    version: &PackageVersion,
+) -> PathBuf {
+    let package_dir = repo
+        .find_by_name(name)
+        .into_iter()
+        .filter(|p| p.overlay().is_none())
+        .find_map(|p| {
+            let dir = p.origin().parent()?;
+            if dir.file_name()? == p.version().as_str() {
+                dir.parent().map(Path::to_path_buf)
+            } else {
+                Some(dir.to_path_buf())
+            }
+        })
+        .unwrap_or_else(|| repo_path.join(name.as_str()));
+
+    if repo.find_by_name(name).is_empty() {
+        package_dir.join("pkg.toml")
+    } else {
+        package_dir.join(version.as_str()).join("pkg.toml")
+    }
+}
+
+fn render_pkg_toml(
+    name: &PackageName,
+    version: &PackageVersion,
+    source_name: &str,
+    url: &Url,
+    hash: &str,
+    phases: &[(&str, String)],
+) -> String {
+    let quote = |s: &str| toml::Value::String(s.to_string()).to_string();
+
+    let mut pkg_toml = format!(
+        "name = {}\nversion = {}\n\n[dependencies]\nbuild = []\nruntime = []\n\n[sources.{}]\nurl = {}\nhash.type = \"sha256\"\nhash.hash = {}\n",
+        quote(name.as_str()),
+        quote(version.as_str()),
+        source_name,
+        quote(url.as_str()),
+        quote(hash),
+    );
+
+    if !phases.is_empty() {
+        pkg_toml.push_str("\n[phases]\n");
+        for (phase, script) in phases {
+            pkg_toml.push_str(&format!("\n{phase}.script = '''\n"));
+            for line in script.lines() {
+                pkg_toml.push_str(&format!("    {line}\n"));
+            }
+            pkg_toml.push_str("'''\n");
+        }
+    }
+
+    pkg_toml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_pkg_toml_is_valid_toml() {
+        let phases = template_phases("autotools")
+            .unwrap()
+            .iter()
+            .map(|(phase, script)| (*phase, script.replace("{source}", "/inputs/src.source")))
+            .collect::<Vec<_>>();
+        let pkg_toml = render_pkg_toml(
+            &PackageName::from(String::from("foo")),
+            &PackageVersion::from(String::from("1.0")),
+            "src",
+            &Url::parse("https://example.com/foo-1.0.tar.gz").unwrap(),
+            "0123abcd",
+            &phases,
+        );
+
+        let value = pkg_toml.parse::<toml::Table>().unwrap();
+        assert_eq!(value["name"].as_str(), Some("foo"));
+        assert_eq!(
+            value["sources"]["src"]["hash"]["hash"].as_str(),
+            Some("0123abcd")
+        );
+        assert!(value["phases"]["build"]["script"]
+            .as_str()
+            .unwrap()
+            .contains("make DESTDIR=/tmp/install install"));
+    }
+}
@@ -0,0 +1,192 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Implementation of the 'outdated' subcommand
+
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use clap::ArgMatches;
+use colored::Colorize;
+use futures::stream::StreamExt;
+use tracing::{info, warn};
+
+use crate::package::compare_versions;
+use crate::package::Package;
+use crate::package::Upstream;
+use crate::repository::Repository;
+
+/// Implementation of the "outdated" subcommand
+pub async fn outdated(matches: &ArgMatches, repo: Repository) -> Result<()> {
+    let csv = matches.get_flag("csv");
+    let show_all = matches.get_flag("all");
+    let timeout = matches.get_one::<u64>("timeout").copied();
+    let package_name_regex = matches
+        .get_one::<String>("package_name_regex")
+        .map(|regex| crate::commands::util::mk_package_name_regex(regex.as_str()))
+        .transpose()?;
+
+    // Only the newest version of each package is compared with the upstream version
+    let mut newest = BTreeMap::<&str, &Package>::new();
+    for package in repo
+        .packages()
+        .filter(|p| p.upstream().is_some())
+        .filter(|p| {
+            package_name_regex
+                .as_ref()
+                .map(|regex| regex.is_match(p.name()))
+                .unwrap_or(true)
+        })
+    {
+        newest
+            .entry(package.name().as_str())
+            .and_modify(|current| {
+                if compare_versions(package.version(), current.version()).is_gt() {
+                    *current = package;
+                }
+            })
+            .or_insert(package);
+    }
+
+    if newest.is_empty() {
+        info!("No packages with an upstream configuration");
+        return Ok(());
+    }
+
+    crate::util::offline::ensure_online(format_args!(
+        "Checking the upstream versions of {} packages",
+        newest.len()
+    ))?;
+    let client = crate::commands::source::http_client(timeout)?;
+    let results = futures::stream::iter(newest.into_values())
+        .map(|package| {
+            let client = &client;
+            async move {
+                let upstream = package.upstream().as_ref().unwrap(); // filtered above
+                let result = newest_upstream_version(client, upstream).await;
+                (package, result)
+            }
+        })
+        .buffer_unordered(8)
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut data = Vec::new();
+    for (package, result) in results {
+        let upstream_version = match result {
+            Ok(Some(version)) => version,
+            Ok(None) => {
+                warn!(
+                    "No upstream version found for {}",
+                    package.display_name_version()
+                );
+                continue;
+            }
+            Err(e) => {
+                warn!(
+                    "Checking the upstream of {} failed: {:#}",
+                    package.display_name_version(),
+                    e
+                );
+                continue;
+            }
+        };
+
+        let is_outdated = compare_versions(&upstream_version, package.version()).is_gt();
+        if !is_outdated && !show_all {
+            continue;
+        }
+
+        let status = match (is_outdated, csv) {
+            (true, true) => String::from("outdated"),
+            (true, false) => "outdated".yellow().to_string(),
+            (false, _) => String::from("up to date"),
+        };
+        data.push(vec![
+            package.name().to_string(),
+            package.version().to_string(),
+            upstream_version,
+            status,
+        ]);
+    }
+    data.sort();
+
+    if data.is_empty() {
+        info!("All packages are up to date");
+        return Ok(());
+    }
+
+    let hdrs = crate::commands::util::mk_header(vec!["Package", "Version", "Upstream", "Status"]);
+    crate::commands::util::display_data(hdrs, data, csv)
+}
+
+/// The newest version that is available upstream
+async fn newest_upstream_version(
+    client: &reqwest::Client,
+    upstream: &Upstream,
+) -> Result<Option<String>> {
+    let regex = upstream.version_regex()?;
+
+    let versions = match (upstream.github(), upstream.url()) {
+        (Some(repository), _) => {
+            #[derive(serde::Deserialize)]
+            struct Release {
+                tag_name: String,
+                draft: bool,
+                prerelease: bool,
+            }
+
+            let url = format!("https://api.github.com/repos/{repository}/releases");
+            let mut request = client
+                .get(&url)
+                .header("Accept", "application/vnd.github+json");
+            if let Ok(token) = std::env::var("GITHUB_TOKEN") {
+                request = request.bearer_auth(token);
+            }
+            let text = request
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .with_context(|| anyhow!("Requesting {}", url))?
+                .text()
+                .await
+                .with_context(|| anyhow!("Reading {}", url))?;
+            serde_json::from_str::<Vec<Release>>(&text)
+                .with_context(|| anyhow!("Parsing the releases from {}", url))?
+                .into_iter()
+                .filter(|release| !release.draft && !release.prerelease)
+                .filter_map(|release| {
+                    Upstream::find_versions(&regex, &release.tag_name)
+                        .next()
+                        .map(String::from)
+                })
+                .collect::<Vec<_>>()
+        }
+        (None, Some(url)) => {
+            let text = client
+                .get(url.as_ref())
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .with_context(|| anyhow!("Requesting {}", url))?
+                .text()
+                .await
+                .with_context(|| anyhow!("Reading {}", url))?;
+            Upstream::find_versions(&regex, &text)
+                .map(String::from)
+                .collect::<Vec<_>>()
+        }
+        (None, None) => return Err(anyhow!("Upstream without github or url")),
+    };
+
+    Ok(versions.into_iter().max_by(|a, b| compare_versions(a, b)))
+}
@@ -0,0 +1,80 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Implementation of external subcommands ("plugins")
+//!
+//! An executable `butido-<name>` on the PATH is run for `butido <name>`, with the remaining
+//! arguments. The context of butido is passed to it in environment variables, so that plugins
+//! can use the same configuration, database and repository.
+
+use std::ffi::OsString;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use clap::ArgMatches;
+use tracing::debug;
+
+use crate::db::DbConnectionConfig;
+
+/// The executable of the plugin for the subcommand, if there is one on the PATH
+pub fn find_plugin(subcommand: &str) -> Option<PathBuf> {
+    which::which(format!("butido-{subcommand}")).ok()
+}
+
+/// Run a plugin
+///
+/// butido exits with the exit code of the plugin if the plugin fails.
+pub async fn plugin(
+    executable: &Path,
+    matches: &ArgMatches,
+    repo_path: &Path,
+    config_files: &[PathBuf],
+    profile: Option<&str>,
+    db_connection_config: DbConnectionConfig<'_>,
+) -> Result<()> {
+    let args = matches
+        .get_many::<OsString>("")
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    debug!("Running plugin {} with {:?}", executable.display(), args);
+
+    let mut command = tokio::process::Command::new(executable);
+    command
+        .args(args)
+        .env("BUTIDO_REPO_ROOT", repo_path)
+        .env(
+            "BUTIDO_CONFIG_FILES",
+            std::env::join_paths(config_files).context("Joining the config file paths")?,
+        )
+        .env(
+            "BUTIDO_DATABASE_URL",
+            db_connection_config.get_database_uri(),
+        )
+        .env(
+            "BUTIDO_EXECUTABLE",
+            std::env::current_exe().context("Getting the path of the butido executable")?,
+        );
+    if let Some(profile) = profile {
+        command.env("BUTIDO_PROFILE", profile);
+    }
+
+    let status = command
+        .status()
+        .await
+        .with_context(|| anyhow!("Running {}", executable.display()))?;
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
+    Ok(())
+}
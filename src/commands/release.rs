@@ -11,6 +11,7 @@
 //! Implementation of the 'release' subcommand
 
 use std::io::Write;
+use std::path::Path;
 use std::path::PathBuf;
 
 use anyhow::anyhow;
@@ -19,39 +20,142 @@ use anyhow::Error;
 use anyhow::Result;
 use clap::ArgMatches;
 use diesel::prelude::*;
-use resiter::AndThen;
 use tokio_stream::StreamExt;
-use tracing::{debug, error, info, trace};
+use tracing::{debug, error, info, trace, warn};
 
 use crate::config::Configuration;
+use crate::config::ProvenanceConfig;
 use crate::db::models as dbmodels;
 use crate::db::DbConnectionConfig;
+use crate::package::HashType;
+use crate::util::progress::ProgressBars;
+use crate::util::provenance::attestation_path;
+use crate::util::provenance::Provenance;
 
 /// Implementation of the "release" subcommand
 pub async fn release(
     db_connection_config: DbConnectionConfig<'_>,
     config: &Configuration,
     matches: &ArgMatches,
+    progressbars: ProgressBars,
 ) -> Result<()> {
     match matches.subcommand() {
         Some(("list", matches)) => {
             let default_limit = config.database_default_query_limit();
-            crate::commands::db::releases(db_connection_config, config, matches, default_limit)
+            crate::commands::db::releases(
+                db_connection_config,
+                config,
+                matches,
+                default_limit,
+                &progressbars,
+            )
+            .await
         }
         Some(("new", matches)) => new_release(db_connection_config, config, matches).await,
+        Some(("submit", matches)) => submit_release(db_connection_config, config, matches).await,
         Some(("rm", matches)) => rm_release(db_connection_config, config, matches).await,
+        Some(("sync", matches)) => {
+            crate::commands::release_sync::sync_release(
+                db_connection_config,
+                config,
+                matches,
+                progressbars,
+            )
+            .await
+        }
+        Some(("rollback", matches)) => {
+            rollback_release(db_connection_config, config, matches).await
+        }
         Some((other, _matches)) => Err(anyhow!("Unknown subcommand: {}", other)),
         None => Err(anyhow!("Missing subcommand")),
     }
 }
 
+/// Options for releasing the artifacts of a submit
+struct ReleaseOptions<'a> {
+    submit_uuid: &'a uuid::Uuid,
+    release_store_name: &'a str,
+    package_name: Option<&'a String>,
+    package_version: Option<&'a String>,
+    kinds: Option<Vec<&'a String>>,
+    group_name: Option<&'a str>,
+    do_update: bool,
+    yes: bool,
+    dry_run: bool,
+    quiet: bool,
+}
+
+/// Implementation of the "release new" subcommand
 async fn new_release(
     db_connection_config: DbConnectionConfig<'_>,
     config: &Configuration,
     matches: &ArgMatches,
 ) -> Result<()> {
-    let print_released_file_pathes = !matches.get_flag("quiet");
+    let submit_uuid = crate::commands::ids::submit_uuid(
+        &mut db_connection_config.establish_connection()?,
+        matches,
+        "submit_uuid",
+    )?;
+    let options = ReleaseOptions {
+        submit_uuid: &submit_uuid,
+        release_store_name: matches.get_one::<String>("release_store_name").unwrap(), // safe by clap
+        package_name: matches.get_one::<String>("package_name"),
+        package_version: matches.get_one::<String>("package_version"),
+        kinds: matches
+            .get_many::<String>("kind")
+            .map(|kinds| kinds.collect()),
+        group_name: None,
+        do_update: matches.get_flag("package_do_update"),
+        yes: matches.get_flag("yes"),
+        dry_run: matches.get_flag("dry_run"),
+        quiet: matches.get_flag("quiet"),
+    };
+
+    release_artifacts(db_connection_config, config, options).await
+}
+
+/// Implementation of the "release submit" subcommand
+async fn submit_release(
+    db_connection_config: DbConnectionConfig<'_>,
+    config: &Configuration,
+    matches: &ArgMatches,
+) -> Result<()> {
+    let submit_uuid = crate::commands::ids::submit_uuid(
+        &mut db_connection_config.establish_connection()?,
+        matches,
+        "submit_uuid",
+    )?;
     let release_store_name = matches.get_one::<String>("release_store_name").unwrap(); // safe by clap
+    let default_name = format!("submit-{submit_uuid}-{release_store_name}");
+    let options = ReleaseOptions {
+        submit_uuid: &submit_uuid,
+        release_store_name,
+        package_name: None,
+        package_version: None,
+        kinds: None,
+        group_name: Some(
+            matches
+                .get_one::<String>("name")
+                .map(String::as_str)
+                .unwrap_or(&default_name),
+        ),
+        do_update: matches.get_flag("package_do_update"),
+        yes: matches.get_flag("yes"),
+        dry_run: matches.get_flag("dry_run"),
+        quiet: matches.get_flag("quiet"),
+    };
+
+    release_artifacts(db_connection_config, config, options).await
+}
+
+async fn release_artifacts(
+    db_connection_config: DbConnectionConfig<'_>,
+    config: &Configuration,
+    options: ReleaseOptions<'_>,
+) -> Result<()> {
+    let print_released_file_pathes = !options.quiet;
+    let dry_run = options.dry_run;
+    let release_store_name = options.release_store_name;
     if !(config.releases_directory().exists() && config.releases_directory().is_dir()) {
         return Err(anyhow!(
             "Release directory does not exist or does not point to directory: {}",
@@ -59,14 +163,24 @@ async fn new_release(
         ));
     }
 
-    let pname = matches.get_one::<String>("package_name");
+    if !config
+        .release_stores()
+        .iter()
+        .any(|name| name == release_store_name)
+    {
+        return Err(anyhow!(
+            "Unknown release store name: {}",
+            release_store_name
+        ));
+    }
 
-    let pvers = matches.get_one::<String>("package_version");
+    let pname = options.package_name;
+    let pvers = options.package_version;
 
     debug!("Release called for: {:?} {:?}", pname, pvers);
 
     let pool = db_connection_config.establish_pool()?;
-    let submit_uuid = matches.get_one::<uuid::Uuid>("submit_uuid").unwrap(); // safe by clap
+    let submit_uuid = options.submit_uuid;
     debug!("Release called for submit: {:?}", submit_uuid);
 
     let submit = crate::schema::submits::dsl::submits
@@ -74,12 +188,39 @@ async fn new_release(
         .first::<dbmodels::Submit>(&mut pool.get().unwrap())?;
     debug!("Found Submit: {:?}", submit_uuid);
 
+    let local_jobs = crate::schema::jobs::table
+        .inner_join(crate::schema::endpoints::table)
+        .filter(crate::schema::jobs::submit_id.eq(submit.id))
+        .filter(crate::schema::endpoints::name.eq(crate::consts::LOCAL_ENDPOINT_NAME))
+        .count()
+        .get_result::<i64>(&mut pool.get().unwrap())?;
+    if local_jobs > 0 {
+        return Err(anyhow!(
+            "Submit {} ran on the host (build --local-exec), its artifacts cannot be released",
+            submit_uuid
+        ));
+    }
+
+    // Release groups are identified by their name (e.g. for "release rollback")
+    if let Some(group_name) = options.group_name {
+        if dbmodels::ReleaseGroup::with_name(&mut pool.get().unwrap(), group_name)?.is_some() {
+            return Err(anyhow!(
+                "There is a release group with the name {} already, choose another name with --name",
+                group_name
+            ));
+        }
+    }
+
     let arts = {
         let sel = crate::schema::artifacts::dsl::artifacts
             .inner_join(crate::schema::jobs::table.inner_join(crate::schema::packages::table))
             .filter(crate::schema::jobs::submit_id.eq(submit.id))
             .left_outer_join(crate::schema::releases::table) // not released
-            .select(crate::schema::artifacts::all_columns);
+            .select((
+                crate::schema::artifacts::all_columns,
+                crate::schema::packages::all_columns,
+                crate::schema::jobs::uuid,
+            ));
 
         match (pname, pvers) {
             (Some(name), Some(vers)) => {
@@ -90,7 +231,9 @@ async fn new_release(
                     "Query: {:?}",
                     diesel::debug_query::<diesel::pg::Pg, _>(&query)
                 );
-                query.load::<dbmodels::Artifact>(&mut pool.get().unwrap())?
+                query.load::<(dbmodels::Artifact, dbmodels::Package, uuid::Uuid)>(
+                    &mut pool.get().unwrap(),
+                )?
             }
             (Some(name), None) => {
                 let query = sel.filter(crate::schema::packages::name.eq(name));
@@ -98,7 +241,9 @@ async fn new_release(
                     "Query: {:?}",
                     diesel::debug_query::<diesel::pg::Pg, _>(&query)
                 );
-                query.load::<dbmodels::Artifact>(&mut pool.get().unwrap())?
+                query.load::<(dbmodels::Artifact, dbmodels::Package, uuid::Uuid)>(
+                    &mut pool.get().unwrap(),
+                )?
             }
             (None, Some(vers)) => {
                 let query = sel.filter(crate::schema::packages::version.like(vers));
@@ -106,141 +251,573 @@ async fn new_release(
                     "Query: {:?}",
                     diesel::debug_query::<diesel::pg::Pg, _>(&query)
                 );
-                query.load::<dbmodels::Artifact>(&mut pool.get().unwrap())?
+                query.load::<(dbmodels::Artifact, dbmodels::Package, uuid::Uuid)>(
+                    &mut pool.get().unwrap(),
+                )?
             }
             (None, None) => {
                 debug!(
                     "Query: {:?}",
                     diesel::debug_query::<diesel::pg::Pg, _>(&sel)
                 );
-                sel.load::<dbmodels::Artifact>(&mut pool.get().unwrap())?
+                sel.load::<(dbmodels::Artifact, dbmodels::Package, uuid::Uuid)>(
+                    &mut pool.get().unwrap(),
+                )?
             }
         }
     };
+    let arts = if let Some(kinds) = options.kinds.as_ref() {
+        arts.into_iter()
+            .filter(|(art, _, _)| kinds.contains(&&art.kind))
+            .collect()
+    } else {
+        arts
+    };
     debug!("Artifacts = {:?}", arts);
 
     if arts.is_empty() {
         return Err(anyhow!("No matching artifacts found to release"));
     }
 
-    arts.iter()
-        .filter_map(|art| {
-            art.path_buf()
-                .parent()
-                .map(|p| config.releases_directory().join(release_store_name).join(p))
-        })
-        .map(|p| async {
-            debug!("mkdir {:?}", p);
-            tokio::fs::create_dir_all(p).await.map_err(Error::from)
-        })
-        .collect::<futures::stream::FuturesUnordered<_>>()
-        .collect::<Result<()>>()
-        .await?;
+    let layout = config
+        .release_store_layouts()
+        .get(release_store_name)
+        .map(String::as_str)
+        .map(ReleaseLayout::new)
+        .transpose()?;
 
     let staging_base: &PathBuf = &config.staging_directory().join(submit.uuid.to_string());
-
-    let release_store =
-        crate::db::models::ReleaseStore::create(&mut pool.get().unwrap(), release_store_name)?;
-    let do_update = matches.get_flag("package_do_update");
-    let interactive = !matches.get_flag("noninteractive");
-
-    let now = chrono::offset::Local::now().naive_local();
-    let any_err = arts
+    let do_update = options.do_update;
+    let plan = arts
         .into_iter()
-        .map(|art| async {
-            #[allow(clippy::redundant_locals)]
-            let art = art; // ensure it is moved
-            let art_path = staging_base.join(&art.path);
+        .map(|(art, package, job_uuid)| {
+            let release_path = match layout.as_ref() {
+                Some(layout) => layout.render(&art, &package, &submit.uuid)?,
+                None => art.path_buf(),
+            };
+            let source_path = staging_base.join(&art.path);
             let dest_path = config
                 .releases_directory()
                 .join(release_store_name)
-                .join(&art.path);
-            debug!(
-                "Trying to release {} to {}",
-                art_path.display(),
-                dest_path.display()
-            );
-
-            if !art_path.is_file() {
-                trace!(
-                    "Artifact does not exist as file, cannot release it: {:?}",
-                    art
-                );
-                Err(anyhow!("Not a file: {}", art_path.display()))
+                .join(&release_path);
+            let action = if !source_path.is_file() {
+                PlannedAction::MissingSource
+            } else if dest_path.exists() && !do_update {
+                PlannedAction::Exists
+            } else if dest_path.exists() {
+                PlannedAction::Overwrite
             } else {
-                if dest_path.exists() && !do_update {
-                    return Err(anyhow!("Does already exist: {}", dest_path.display()));
-                } else if dest_path.exists() && do_update {
-                    writeln!(
-                        std::io::stderr(),
-                        "Going to update: {}",
-                        dest_path.display()
-                    )?;
-                    if interactive
-                        && !dialoguer::Confirm::new()
-                            .with_prompt("Continue?")
-                            .interact()?
-                    {
-                        return Err(anyhow!(
-                            "Does already exist: {} and update was denied",
-                            dest_path.display()
-                        ));
-                    }
-                }
+                PlannedAction::Copy
+            };
+
+            Ok(PlannedRelease {
+                art,
+                package,
+                job_uuid,
+                release_path,
+                source_path,
+                dest_path,
+                action,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
 
-                if dest_path.exists() {
-                    debug!(
-                        "Removing {} before writing new file to this path",
-                        dest_path.display()
-                    );
-                    tokio::fs::remove_file(&dest_path).await.with_context(|| {
-                        anyhow!(
-                            "Removing {} before writing new file to this path",
-                            dest_path.display()
-                        )
-                    })?;
+    if dry_run || !options.quiet {
+        print_release_plan(&plan)?;
+    }
+
+    if dry_run {
+        return Ok(());
+    }
+
+    if let Some(planned) = plan
+        .iter()
+        .find(|p| p.action == PlannedAction::MissingSource)
+    {
+        trace!(
+            "Artifact does not exist as file, cannot release it: {:?}",
+            planned.art
+        );
+        return Err(anyhow!("Not a file: {}", planned.source_path.display()));
+    }
+
+    if let Some(planned) = plan.iter().find(|p| p.action == PlannedAction::Exists) {
+        return Err(anyhow!(
+            "Does already exist: {} (use --update to overwrite)",
+            planned.dest_path.display()
+        ));
+    }
+
+    let n_overwrites = plan
+        .iter()
+        .filter(|p| p.action == PlannedAction::Overwrite)
+        .count();
+    if n_overwrites > 0
+        && !options.yes
+        && !dialoguer::Confirm::new()
+            .with_prompt(format!("Overwrite {n_overwrites} existing file(s)?"))
+            .interact()?
+    {
+        return Err(anyhow!("Overwriting existing files was denied"));
+    }
+
+    let release_store =
+        crate::db::models::ReleaseStore::create(&mut pool.get().unwrap(), release_store_name)?;
+
+    let group_uuid = uuid::Uuid::new_v4();
+    let tmp_dir = config
+        .releases_directory()
+        .join(release_store_name)
+        .join(format!(
+            "{}{}",
+            crate::consts::RELEASE_TMP_DIR_PREFIX,
+            group_uuid
+        ));
+
+    // Copy the artifacts to a temporary directory inside the release store first. Moving them
+    // into place afterwards is cheap and can be undone if anything goes wrong.
+    let copied = plan
+        .iter()
+        .map(|planned| {
+            let tmp_path = tmp_dir.join(&planned.release_path);
+            async move {
+                if let Some(parent) = tmp_path.parent() {
+                    debug!("mkdir {:?}", parent);
+                    tokio::fs::create_dir_all(parent).await?;
                 }
 
-                // else !dest_path.exists()
-                tokio::fs::copy(&art_path, &dest_path)
-                    .await
-                    .with_context(|| {
-                        anyhow!("Copying {} to {}", art_path.display(), dest_path.display())
-                    })
-                    .and_then(|_| {
-                        debug!("Updating {:?} to set released = true", art);
-                        let rel = crate::db::models::Release::create(
-                            &mut pool.get().unwrap(),
-                            &art,
-                            &now,
-                            &release_store,
-                        )?;
-                        debug!("Release object = {:?}", rel);
-                        Ok(dest_path)
-                    })
+                debug!(
+                    "Copying {} to {}",
+                    planned.source_path.display(),
+                    tmp_path.display()
+                );
+                crate::filestore::copy_artifact(
+                    &planned.source_path,
+                    &tmp_path,
+                    config.release_copy_mode(),
+                )
+                .await
             }
         })
         .collect::<futures::stream::FuturesUnordered<_>>()
-        .collect::<Vec<Result<_>>>()
+        .collect::<Result<()>>()
+        .await;
+
+    if let Err(e) = copied {
+        remove_tmp_dir(&tmp_dir).await;
+        return Err(e).context("Releasing artifacts failed, nothing was released");
+    }
+
+    let now = chrono::offset::Local::now().naive_local();
+    let mut moved = Vec::new();
+    let mut releases = Vec::new();
+    let res = pool.get().unwrap().transaction::<_, Error, _>(|conn| {
+        let group = dbmodels::ReleaseGroup::create(
+            conn,
+            &group_uuid,
+            &now,
+            &release_store,
+            options.group_name,
+        )?;
+
+        for planned in plan.iter() {
+            debug!("Updating {:?} to set released = true", planned.art);
+            let release_path = planned.release_path.to_str().ok_or_else(|| {
+                anyhow!(
+                    "Path is not valid UTF-8: {}",
+                    planned.release_path.display()
+                )
+            })?;
+            let rel = dbmodels::Release::create(
+                conn,
+                &planned.art,
+                &now,
+                &release_store,
+                release_path,
+                Some(&group),
+            )?;
+            debug!("Release object = {:?}", rel);
+            releases.push(rel);
+        }
+
+        // Moving the files is the last step, so that the database transaction is rolled back
+        // if it fails
+        for planned in plan.iter() {
+            move_into_place(planned, &tmp_dir)?;
+            moved.push(planned);
+        }
+        Ok(())
+    });
+
+    if let Err(e) = res {
+        undo_moves(&moved, &tmp_dir);
+        remove_tmp_dir(&tmp_dir).await;
+        return Err(e).context("Releasing artifacts failed, nothing was released");
+    }
+
+    remove_tmp_dir(&tmp_dir).await;
+    if let Some(provenance_config) = config.provenance() {
+        write_attestations(
+            &mut pool.get().unwrap(),
+            config,
+            provenance_config,
+            plan.iter().zip(releases.iter()),
+            &now,
+        )
         .await
-        .into_iter()
-        .and_then_ok(|dest_path| {
-            if print_released_file_pathes {
-                writeln!(std::io::stdout(), "{}", dest_path.display()).map_err(Error::from)
-            } else {
-                Ok(())
+        .context("Artifacts were released, but writing the provenance attestations failed")?;
+    }
+
+    crate::commands::release_metadata::generate_release_metadata(
+        &mut pool.get().unwrap(),
+        config,
+        release_store_name,
+    )
+    .await
+    .context("Artifacts were released, but generating the repository metadata failed")?;
+
+    if print_released_file_pathes {
+        let mut out = std::io::stdout();
+        for planned in plan.iter() {
+            writeln!(out, "{}", planned.dest_path.display())?;
+        }
+    }
+
+    writeln!(std::io::stderr(), "Release group: {group_uuid}")?;
+    Ok(())
+}
+
+/// Write the provenance attestation of each released artifact next to it and record it in the
+/// database
+async fn write_attestations<'a>(
+    conn: &mut PgConnection,
+    config: &Configuration,
+    provenance_config: &ProvenanceConfig,
+    released: impl Iterator<Item = (&'a PlannedRelease, &'a dbmodels::Release)>,
+    now: &chrono::NaiveDateTime,
+) -> Result<()> {
+    for (planned, release) in released {
+        let sha256 = match planned.art.sha256.as_ref() {
+            Some(sha256) => sha256.clone(),
+            None => {
+                let file = tokio::fs::File::open(&planned.dest_path)
+                    .await
+                    .with_context(|| anyhow!("Opening {}", planned.dest_path.display()))?;
+                HashType::Sha256
+                    .hash_from_reader(tokio::io::BufReader::new(file))
+                    .await
+                    .with_context(|| anyhow!("Hashing {}", planned.dest_path.display()))?
+                    .to_string()
             }
+        };
+
+        let provenance = Provenance::for_artifact(
+            conn,
+            config,
+            provenance_config,
+            &planned.art,
+            release.path_in_store(&planned.art),
+            sha256,
+        )?;
+        let (envelope, signed) = provenance.envelope(provenance_config).await?;
+
+        let attestation_path = attestation_path(&planned.dest_path);
+        debug!("Writing provenance to {}", attestation_path.display());
+        tokio::fs::write(&attestation_path, &envelope)
+            .await
+            .with_context(|| anyhow!("Writing {}", attestation_path.display()))?;
+        dbmodels::ReleaseAttestation::create(conn, release, &envelope, signed, now)?;
+    }
+    Ok(())
+}
+
+/// Implementation of the "release rollback" subcommand
+///
+/// Removes all files and database entries of a release group.
+/// A release group that replaced files of other releases (with `--update`) cannot be rolled back,
+/// because the files of the other releases would be removed as well.
+async fn rollback_release(
+    db_connection_config: DbConnectionConfig<'_>,
+    config: &Configuration,
+    matches: &ArgMatches,
+) -> Result<()> {
+    let group_name = matches.get_one::<String>("release_group").unwrap(); // safe by clap
+    let mut conn = db_connection_config.establish_connection()?;
+
+    let group = dbmodels::ReleaseGroup::with_name_or_uuid(&mut conn, group_name)?;
+    let release_store = crate::schema::release_stores::table
+        .find(group.release_store_id)
+        .first::<dbmodels::ReleaseStore>(&mut conn)?;
+    let releases = crate::schema::releases::table
+        .inner_join(crate::schema::artifacts::table)
+        .filter(crate::schema::releases::release_group_id.eq(group.id))
+        .select((
+            crate::schema::releases::all_columns,
+            crate::schema::artifacts::all_columns,
+        ))
+        .load::<(dbmodels::Release, dbmodels::Artifact)>(&mut conn)?;
+    let other_releases = crate::schema::releases::table
+        .inner_join(crate::schema::artifacts::table)
+        .filter(crate::schema::releases::release_store_id.eq(group.release_store_id))
+        .filter(
+            crate::schema::releases::release_group_id
+                .is_null()
+                .or(crate::schema::releases::release_group_id.ne(group.id)),
+        )
+        .select((
+            crate::schema::releases::all_columns,
+            crate::schema::artifacts::all_columns,
+        ))
+        .load::<(dbmodels::Release, dbmodels::Artifact)>(&mut conn)?;
+
+    let shared = shared_release_paths(&releases, &other_releases);
+    if !shared.is_empty() {
+        return Err(anyhow!(
+            "Release group {} shares files with other releases (it replaced them with --update \
+             or was replaced by them), cannot roll it back: {}",
+            group.uuid,
+            shared.join(", ")
+        ));
+    }
+
+    let paths = releases
+        .iter()
+        .flat_map(|(release, artifact)| {
+            let path = config
+                .releases_directory()
+                .join(&release_store.store_name)
+                .join(release.path_in_store(artifact));
+
+            // The provenance attestations of the artifacts, if any, are removed as well
+            let attestation_path = attestation_path(&path);
+            std::iter::once(path).chain(attestation_path.is_file().then_some(attestation_path))
         })
-        .filter_map(Result::err)
-        .inspect(|err| error!("Error: {}", err.to_string()))
-        .last()
-        .is_some(); // consume iterator completely, if not empty, there was an error
+        .collect::<Vec<_>>();
 
-    if any_err {
-        Err(anyhow!("Releasing one or more artifacts failed"))
-    } else {
+    for path in paths.iter() {
+        writeln!(std::io::stderr(), "Going to delete: {}", path.display())?;
+    }
+    writeln!(
+        std::io::stderr(),
+        "Going to remove from database: Release group {} from {} with {} release(s)",
+        group.uuid,
+        group.release_date,
+        releases.len()
+    )?;
+    if !matches.get_flag("yes")
+        && !dialoguer::Confirm::new()
+            .with_prompt("Continue?")
+            .interact()?
+    {
+        return Ok(());
+    }
+
+    conn.transaction::<_, Error, _>(|conn| {
+        diesel::delete(
+            crate::schema::releases::table
+                .filter(crate::schema::releases::release_group_id.eq(group.id)),
+        )
+        .execute(conn)?;
+        diesel::delete(&group).execute(conn)?;
+
+        // Removing the files is the last step, so that the database transaction is rolled back
+        // if it fails
+        for path in paths.iter() {
+            match std::fs::remove_file(path) {
+                Ok(()) => debug!("Removed {}", path.display()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    warn!("Does not exist anymore: {}", path.display())
+                }
+                Err(e) => {
+                    return Err(Error::from(e))
+                        .with_context(|| anyhow!("Removing {}", path.display()))
+                }
+            }
+        }
         Ok(())
+    })?;
+
+    info!("Release group {} rolled back", group.uuid);
+    crate::commands::release_metadata::generate_release_metadata(
+        &mut conn,
+        config,
+        &release_store.store_name,
+    )
+    .await
+    .context("Release group was rolled back, but generating the repository metadata failed")
+}
+
+/// The paths in the release store of the `releases` that are paths of `other_releases` as well
+fn shared_release_paths<'a>(
+    releases: &'a [(dbmodels::Release, dbmodels::Artifact)],
+    other_releases: &[(dbmodels::Release, dbmodels::Artifact)],
+) -> Vec<&'a str> {
+    let other_paths = other_releases
+        .iter()
+        .map(|(release, artifact)| release.path_in_store(artifact))
+        .collect::<std::collections::HashSet<_>>();
+    releases
+        .iter()
+        .map(|(release, artifact)| release.path_in_store(artifact))
+        .filter(|path| other_paths.contains(path))
+        .collect()
+}
+
+/// The path in the temporary directory where an overwritten file is kept until the release is
+/// complete
+fn backup_path(planned: &PlannedRelease, tmp_dir: &Path) -> PathBuf {
+    tmp_dir.join(".overwritten").join(&planned.release_path)
+}
+
+/// Move an artifact from the temporary directory to its destination in the release store
+///
+/// An existing file at the destination is moved to the temporary directory, so that it can be
+/// restored if the release fails.
+fn move_into_place(planned: &PlannedRelease, tmp_dir: &Path) -> Result<()> {
+    if planned.action == PlannedAction::Overwrite {
+        let backup = backup_path(planned, tmp_dir);
+        if let Some(parent) = backup.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        debug!(
+            "Moving {} to {}",
+            planned.dest_path.display(),
+            backup.display()
+        );
+        std::fs::rename(&planned.dest_path, &backup)
+            .with_context(|| anyhow!("Moving {} out of the way", planned.dest_path.display()))?;
+    }
+
+    if let Some(parent) = planned.dest_path.parent() {
+        std::fs::create_dir_all(parent)?;
     }
+
+    let tmp_path = tmp_dir.join(&planned.release_path);
+    debug!(
+        "Moving {} to {}",
+        tmp_path.display(),
+        planned.dest_path.display()
+    );
+    let res = std::fs::rename(&tmp_path, &planned.dest_path).with_context(|| {
+        anyhow!(
+            "Moving {} to {}",
+            tmp_path.display(),
+            planned.dest_path.display()
+        )
+    });
+
+    if res.is_err() && planned.action == PlannedAction::Overwrite {
+        let backup = backup_path(planned, tmp_dir);
+        if let Err(e) = std::fs::rename(&backup, &planned.dest_path) {
+            error!(
+                "Restoring {} from {}: {}",
+                planned.dest_path.display(),
+                backup.display(),
+                e
+            );
+        }
+    }
+
+    res
+}
+
+/// Undo `move_into_place()` for the passed artifacts (on a best-effort basis)
+fn undo_moves(moved: &[&PlannedRelease], tmp_dir: &Path) {
+    for planned in moved.iter().rev() {
+        if let Err(e) = std::fs::remove_file(&planned.dest_path) {
+            error!("Removing {}: {}", planned.dest_path.display(), e);
+        }
+
+        if planned.action == PlannedAction::Overwrite {
+            let backup = backup_path(planned, tmp_dir);
+            if let Err(e) = std::fs::rename(&backup, &planned.dest_path) {
+                error!(
+                    "Restoring {} from {}: {}",
+                    planned.dest_path.display(),
+                    backup.display(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+async fn remove_tmp_dir(tmp_dir: &Path) {
+    if tmp_dir.exists() {
+        if let Err(e) = tokio::fs::remove_dir_all(tmp_dir).await {
+            warn!(
+                "Removing temporary directory {} failed: {}",
+                tmp_dir.display(),
+                e
+            );
+        }
+    }
+}
+
+/// What releasing an artifact will do
+#[derive(Debug, Eq, PartialEq)]
+enum PlannedAction {
+    /// Copy the artifact to the release store
+    Copy,
+
+    /// Overwrite an existing file in the release store
+    Overwrite,
+
+    /// The destination exists already, but updating was not requested
+    Exists,
+
+    /// The artifact does not exist in the staging store
+    MissingSource,
+}
+
+impl std::fmt::Display for PlannedAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlannedAction::Copy => write!(f, "copy"),
+            PlannedAction::Overwrite => write!(f, "overwrite"),
+            PlannedAction::Exists => write!(f, "exists (no --update)"),
+            PlannedAction::MissingSource => write!(f, "missing in staging"),
+        }
+    }
+}
+
+/// An artifact that is about to be released
+struct PlannedRelease {
+    art: dbmodels::Artifact,
+    package: dbmodels::Package,
+    job_uuid: uuid::Uuid,
+    release_path: PathBuf,
+    source_path: PathBuf,
+    dest_path: PathBuf,
+    action: PlannedAction,
+}
+
+fn print_release_plan(plan: &[PlannedRelease]) -> Result<()> {
+    let hdrs = crate::commands::util::mk_header(vec![
+        "Package",
+        "Version",
+        "Kind",
+        "Job",
+        "Artifact",
+        "Destination",
+        "Action",
+    ]);
+    let data = plan
+        .iter()
+        .map(|planned| {
+            vec![
+                planned.package.name.clone(),
+                planned.package.version.clone(),
+                planned.art.kind.clone(),
+                planned.job_uuid.to_string(),
+                planned.art.path.clone(),
+                planned.dest_path.display().to_string(),
+                planned.action.to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    crate::commands::util::display_data(hdrs, data, false)
 }
 
 pub async fn rm_release(
@@ -295,7 +872,7 @@ pub async fn rm_release(
     let artifact_path = config
         .releases_directory()
         .join(release_store_name)
-        .join(&artifact.path);
+        .join(release.path_in_store(&artifact));
     if !artifact_path.is_file() {
         return Err(anyhow!("Not a file: {}", artifact_path.display()));
     }
@@ -321,8 +898,161 @@ pub async fn rm_release(
     tokio::fs::remove_file(&artifact_path).await?;
     info!("File removed");
 
+    let attestation_path = attestation_path(&artifact_path);
+    if attestation_path.is_file() {
+        tokio::fs::remove_file(&attestation_path).await?;
+        info!("Provenance attestation removed");
+    }
+
     diesel::delete(&release).execute(&mut conn)?;
     info!("Release deleted from database");
 
-    Ok(())
+    crate::commands::release_metadata::generate_release_metadata(
+        &mut conn,
+        config,
+        release_store_name,
+    )
+    .await
+    .context("Release was removed, but generating the repository metadata failed")
+}
+
+/// Helper for rendering the path of an artifact in a release store from the configured layout
+struct ReleaseLayout {
+    handlebars: handlebars::Handlebars<'static>,
+}
+
+impl ReleaseLayout {
+    fn new(layout: &str) -> Result<Self> {
+        let mut handlebars = handlebars::Handlebars::new();
+        handlebars.register_escape_fn(handlebars::no_escape);
+        handlebars.set_strict_mode(true);
+        handlebars
+            .register_template_string("layout", layout)
+            .with_context(|| anyhow!("Registering release store layout: {}", layout))?;
+        Ok(ReleaseLayout { handlebars })
+    }
+
+    /// Render the path of the artifact, relative to the release store
+    fn render(
+        &self,
+        art: &dbmodels::Artifact,
+        package: &dbmodels::Package,
+        submit_uuid: &uuid::Uuid,
+    ) -> Result<PathBuf> {
+        let art_path = art.path_buf();
+        let filename = art_path
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .ok_or_else(|| anyhow!("Artifact has no file name: {}", art.path))?;
+
+        let mut data = std::collections::BTreeMap::new();
+        data.insert("name", package.name.clone());
+        data.insert("version", package.version.clone());
+        data.insert("filename", filename);
+        data.insert("path", art.path.clone());
+        data.insert("submit", submit_uuid.to_string());
+
+        let rendered = self
+            .handlebars
+            .render("layout", &data)
+            .with_context(|| anyhow!("Rendering release path for artifact {}", art.path))?;
+        let rendered = PathBuf::from(rendered);
+
+        let is_valid = !rendered.as_os_str().is_empty()
+            && rendered
+                .components()
+                .all(|c| matches!(c, std::path::Component::Normal(_)));
+        if !is_valid {
+            return Err(anyhow!(
+                "Release path for artifact {} is not a relative path without '..': {}",
+                art.path,
+                rendered.display()
+            ));
+        }
+
+        Ok(rendered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn artifact(path: &str) -> dbmodels::Artifact {
+        dbmodels::Artifact {
+            id: 1,
+            path: String::from(path),
+            job_id: 1,
+            size: None,
+            kind: String::from("default"),
+            sha256: None,
+        }
+    }
+
+    fn package() -> dbmodels::Package {
+        dbmodels::Package {
+            id: 1,
+            name: String::from("foo"),
+            version: String::from("1.0"),
+            maintainer: None,
+            license: None,
+            description: None,
+            homepage: None,
+        }
+    }
+
+    #[test]
+    fn test_release_layout_render() {
+        let layout = ReleaseLayout::new("{{name}}/{{version}}/{{filename}}").unwrap();
+        let path = layout
+            .render(
+                &artifact("x86_64/foo-1.0.tar.gz"),
+                &package(),
+                &uuid::Uuid::nil(),
+            )
+            .unwrap();
+        assert_eq!(path, PathBuf::from("foo/1.0/foo-1.0.tar.gz"));
+    }
+
+    #[test]
+    fn test_release_layout_rejects_escaping_paths() {
+        let layout = ReleaseLayout::new("../{{filename}}").unwrap();
+        let res = layout.render(&artifact("foo-1.0.tar.gz"), &package(), &uuid::Uuid::nil());
+        assert!(res.is_err());
+
+        let layout = ReleaseLayout::new("/{{filename}}").unwrap();
+        let res = layout.render(&artifact("foo-1.0.tar.gz"), &package(), &uuid::Uuid::nil());
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_shared_release_paths() {
+        let release = |id, path: Option<&str>| dbmodels::Release {
+            id,
+            artifact_id: 1,
+            release_date: chrono::NaiveDateTime::default(),
+            release_store_id: 1,
+            path: path.map(String::from),
+            release_group_id: Some(id),
+        };
+        let group = [
+            (
+                release(2, Some("foo/foo-1.0.tar.gz")),
+                artifact("foo-1.0.tar.gz"),
+            ),
+            (release(2, None), artifact("bar-1.0.tar.gz")),
+        ];
+
+        let updated = [(release(1, None), artifact("foo/foo-1.0.tar.gz"))];
+        assert_eq!(
+            shared_release_paths(&group, &updated),
+            ["foo/foo-1.0.tar.gz"]
+        );
+
+        let unrelated = [(
+            release(1, Some("foo/foo-0.9.tar.gz")),
+            artifact("foo-0.9.tar.gz"),
+        )];
+        assert!(shared_release_paths(&group, &unrelated).is_empty());
+    }
 }
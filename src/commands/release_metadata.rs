@@ -0,0 +1,249 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Generation of the repository metadata of release stores
+//!
+//! The generators that are configured for a release store (see `ReleaseMetadataGenerator`) run
+//! after artifacts were released to the store or removed from it.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use diesel::prelude::*;
+use serde::Serialize;
+use tracing::{debug, info};
+
+use crate::config::Configuration;
+use crate::config::ReleaseMetadataGenerator;
+use crate::db::models as dbmodels;
+use crate::schema;
+
+/// The name of the file that `dpkg-scanpackages` output is written to
+const DEB_INDEX_FILE_NAME: &str = "Packages";
+
+/// The JSON index of a release store
+#[derive(Debug, Serialize)]
+struct JsonIndex<'a> {
+    store: &'a str,
+    artifacts: Vec<JsonIndexEntry>,
+}
+
+/// An artifact in the JSON index of a release store
+#[derive(Debug, PartialEq, Serialize)]
+struct JsonIndexEntry {
+    name: String,
+    version: String,
+    path: String,
+    size: Option<i64>,
+    sha256: Option<String>,
+    released: String,
+}
+
+/// Run the metadata generators that are configured for the release store
+pub(super) async fn generate_release_metadata(
+    conn: &mut PgConnection,
+    config: &Configuration,
+    store_name: &str,
+) -> Result<()> {
+    let Some(generators) = config.release_metadata().get(store_name) else {
+        return Ok(());
+    };
+
+    let store_dir = config.releases_directory().join(store_name);
+    for generator in generators {
+        debug!("Generating {} metadata of {}", generator, store_name);
+        match generator {
+            ReleaseMetadataGenerator::Rpm => {
+                let command = ["createrepo_c", "--update", "."].map(String::from);
+                run_in_store(&store_dir, store_name, &command).await?;
+            }
+            ReleaseMetadataGenerator::Deb => {
+                let command = ["dpkg-scanpackages", "--multiversion", "."].map(String::from);
+                let packages = run_in_store(&store_dir, store_name, &command).await?;
+                write_file(&store_dir.join(DEB_INDEX_FILE_NAME), &packages).await?;
+            }
+            ReleaseMetadataGenerator::Json { file } => {
+                let releases = schema::releases::table
+                    .inner_join(
+                        schema::artifacts::table
+                            .inner_join(schema::jobs::table.inner_join(schema::packages::table)),
+                    )
+                    .inner_join(schema::release_stores::table)
+                    .filter(schema::release_stores::store_name.eq(store_name))
+                    .order_by((schema::releases::release_date, schema::releases::id))
+                    .select((
+                        schema::releases::all_columns,
+                        schema::artifacts::all_columns,
+                        schema::packages::all_columns,
+                    ))
+                    .load::<(dbmodels::Release, dbmodels::Artifact, dbmodels::Package)>(conn)?;
+
+                let artifacts = json_index_entries(releases)
+                    .into_iter()
+                    .filter(|entry| store_dir.join(&entry.path).is_file())
+                    .collect();
+                let index = JsonIndex {
+                    store: store_name,
+                    artifacts,
+                };
+                write_file(&store_dir.join(file), &serde_json::to_vec_pretty(&index)?).await?;
+            }
+            ReleaseMetadataGenerator::Command { command, .. } => {
+                run_in_store(&store_dir, store_name, command).await?;
+            }
+        }
+        info!("Generated {} metadata of {}", generator, store_name);
+    }
+    Ok(())
+}
+
+/// The entries of the JSON index, one per path in the release store
+///
+/// The releases must be ordered by release date, if a path was released multiple times (with
+/// "--update"), the last release is the one that is in the store.
+fn json_index_entries(
+    releases: Vec<(dbmodels::Release, dbmodels::Artifact, dbmodels::Package)>,
+) -> Vec<JsonIndexEntry> {
+    let mut entries = BTreeMap::new();
+    for (release, artifact, package) in releases {
+        let path = release.path_in_store(&artifact).to_string();
+        let entry = JsonIndexEntry {
+            name: package.name,
+            version: package.version,
+            path: path.clone(),
+            size: artifact.size,
+            sha256: artifact.sha256,
+            released: release.release_date.and_utc().to_rfc3339(),
+        };
+        entries.insert(path, entry);
+    }
+    entries.into_values().collect()
+}
+
+/// Run a command in the directory of a release store and return its output
+async fn run_in_store(store_dir: &Path, store_name: &str, command: &[String]) -> Result<Vec<u8>> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| anyhow!("Empty metadata generator command"))?;
+    let output = tokio::process::Command::new(program)
+        .args(args)
+        .current_dir(store_dir)
+        .env("BUTIDO_RELEASE_STORE", store_name)
+        .output()
+        .await
+        .with_context(|| anyhow!("Running {} in {}", program, store_dir.display()))?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{} failed in {} ({}): {}",
+            program,
+            store_dir.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Replace the file at `path` with the content, clients never see a partially written file
+async fn write_file(path: &Path, content: &[u8]) -> Result<()> {
+    let mut tmp_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Not a file path: {}", path.display()))?
+        .to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(&tmp_path, content)
+        .await
+        .with_context(|| anyhow!("Writing {}", tmp_path.display()))?;
+    tokio::fs::rename(&tmp_path, path)
+        .await
+        .with_context(|| anyhow!("Moving {} into place", tmp_path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(id: i32, artifact_id: i32, path: Option<&str>, days: i64) -> dbmodels::Release {
+        dbmodels::Release {
+            id,
+            artifact_id,
+            release_date: chrono::DateTime::from_timestamp(1_700_000_000 + days * 86400, 0)
+                .unwrap()
+                .naive_utc(),
+            release_store_id: 1,
+            path: path.map(String::from),
+            release_group_id: None,
+        }
+    }
+
+    fn artifact(id: i32, path: &str) -> dbmodels::Artifact {
+        dbmodels::Artifact {
+            id,
+            path: String::from(path),
+            job_id: id,
+            size: Some(100),
+            kind: String::from("default"),
+            sha256: Some(String::from("abcd")),
+        }
+    }
+
+    fn package(version: &str) -> dbmodels::Package {
+        dbmodels::Package {
+            id: 1,
+            name: String::from("foo"),
+            version: String::from(version),
+            maintainer: None,
+            license: None,
+            description: None,
+            homepage: None,
+        }
+    }
+
+    #[test]
+    fn test_json_index_entries() {
+        let releases = vec![
+            (
+                release(1, 1, None, 0),
+                artifact(1, "foo-1.0.rpm"),
+                package("1.0"),
+            ),
+            (
+                release(2, 2, Some("foo/foo.rpm"), 1),
+                artifact(2, "foo-1.1.rpm"),
+                package("1.1"),
+            ),
+            // Released with --update, replaces the previous file
+            (
+                release(3, 3, Some("foo/foo.rpm"), 2),
+                artifact(3, "foo-1.2.rpm"),
+                package("1.2"),
+            ),
+        ];
+
+        let entries = json_index_entries(releases);
+        assert_eq!(
+            entries
+                .iter()
+                .map(|e| (e.path.as_str(), e.version.as_str()))
+                .collect::<Vec<_>>(),
+            [("foo-1.0.rpm", "1.0"), ("foo/foo.rpm", "1.2")]
+        );
+        assert_eq!(entries[0].released, "2023-11-14T22:13:20+00:00");
+    }
+}
@@ -0,0 +1,360 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Implementation of the 'release sync' subcommand
+//!
+//! The files of a release store are pushed to its mirrors with rsync. The files that were synced
+//! to a mirror are recorded in the database (with their size and modification time), so that only
+//! the files that were added or changed since the last sync are transferred, and only the files
+//! that were removed since then are deleted on the mirror.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::Path;
+use std::process::Stdio;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use clap::ArgMatches;
+use itertools::Itertools;
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info};
+
+use crate::config::Configuration;
+use crate::db::models::ReleaseMirrorFile;
+use crate::db::models::ReleaseStore;
+use crate::db::DbConnectionConfig;
+use crate::util::progress::ProgressBars;
+
+/// A file in the release store
+#[derive(Debug)]
+struct StoreFile {
+    /// The path relative to the root of the release store
+    path: String,
+    size: i64,
+    modified_at: NaiveDateTime,
+}
+
+/// The changes of a release store since the last sync to a mirror
+#[derive(Debug)]
+struct MirrorDelta<'a> {
+    /// The files that are new or changed
+    transfer: Vec<&'a StoreFile>,
+
+    /// The paths of the files that were removed from the release store
+    remove: Vec<String>,
+}
+
+impl MirrorDelta<'_> {
+    fn is_empty(&self) -> bool {
+        self.transfer.is_empty() && self.remove.is_empty()
+    }
+}
+
+/// Compare the files in the release store with the files that were synced to a mirror
+fn mirror_delta<'a>(files: &'a [StoreFile], synced: &[ReleaseMirrorFile]) -> MirrorDelta<'a> {
+    let synced_state = synced
+        .iter()
+        .map(|file| (file.path.as_str(), (file.size, file.modified_at)))
+        .collect::<HashMap<_, _>>();
+    let store_paths = files
+        .iter()
+        .map(|file| file.path.as_str())
+        .collect::<HashSet<_>>();
+
+    MirrorDelta {
+        transfer: files
+            .iter()
+            .filter(|file| {
+                synced_state.get(file.path.as_str()) != Some(&(file.size, file.modified_at))
+            })
+            .collect(),
+        remove: synced
+            .iter()
+            .filter(|file| !store_paths.contains(file.path.as_str()))
+            .map(|file| file.path.clone())
+            .sorted()
+            .collect(),
+    }
+}
+
+/// Implementation of the "release sync" subcommand
+pub(super) async fn sync_release(
+    db_connection_config: DbConnectionConfig<'_>,
+    config: &Configuration,
+    matches: &ArgMatches,
+    progressbars: ProgressBars,
+) -> Result<()> {
+    let store_name = matches.get_one::<String>("release_store_name").unwrap(); // safe by clap
+    let dry_run = matches.get_flag("dry_run");
+    if !config.release_stores().contains(store_name) {
+        return Err(anyhow!("Unknown release store name: {}", store_name));
+    }
+
+    let targets = match matches.get_many::<String>("target") {
+        Some(targets) => targets.cloned().collect::<Vec<_>>(),
+        None => config
+            .release_mirrors()
+            .get(store_name)
+            .cloned()
+            .unwrap_or_default(),
+    };
+    if targets.is_empty() {
+        return Err(anyhow!(
+            "No mirrors configured for release store {}, pass --target",
+            store_name
+        ));
+    }
+
+    if let Some(target) = targets.iter().find(|target| is_remote_target(target)) {
+        crate::util::offline::ensure_online(format_args!(
+            "Syncing release store {} to the mirror {}",
+            store_name, target
+        ))?;
+    }
+
+    let store = crate::commands::util::load_release_store(config, &progressbars, store_name)?;
+    let root = store.root_path();
+    let files = store
+        .artifacts()
+        .sorted()
+        .map(|path| {
+            let full_path = root
+                .join(path)?
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Artifact vanished from the release store: {}",
+                        path.display()
+                    )
+                })?
+                .joined();
+            let metadata = std::fs::metadata(&full_path)
+                .with_context(|| anyhow!("Reading metadata of {}", full_path.display()))?;
+            let modified = metadata
+                .modified()?
+                .duration_since(std::time::UNIX_EPOCH)?
+                .as_secs();
+            Ok(StoreFile {
+                path: path
+                    .to_str()
+                    .ok_or_else(|| anyhow!("Path is not valid UTF-8: {}", path.display()))?
+                    .to_string(),
+                size: i64::try_from(metadata.len())?,
+                // Seconds are precise enough and survive the round trip through the database
+                modified_at: chrono::DateTime::from_timestamp(i64::try_from(modified)?, 0)
+                    .ok_or_else(|| anyhow!("Invalid modification time of {}", full_path.display()))?
+                    .naive_utc(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut conn = db_connection_config.establish_connection()?;
+    let release_store = ReleaseStore::create(&mut conn, store_name)?;
+    for target in targets.iter() {
+        let synced = ReleaseMirrorFile::of_target(&mut conn, &release_store, target)?;
+        let delta = mirror_delta(&files, &synced);
+        if delta.is_empty() {
+            info!("Mirror {} is up to date", target);
+            continue;
+        }
+
+        info!(
+            "Syncing {} to {}: {} files to transfer, {} files to remove",
+            store_name,
+            target,
+            delta.transfer.len(),
+            delta.remove.len()
+        );
+        if dry_run {
+            let mut out = std::io::stdout().lock();
+            for file in delta.transfer.iter() {
+                writeln!(out, "{target}: transfer {}", file.path)?;
+            }
+            for path in delta.remove.iter() {
+                writeln!(out, "{target}: remove {path}")?;
+            }
+            continue;
+        }
+
+        if !delta.transfer.is_empty() {
+            let file_list = delta
+                .transfer
+                .iter()
+                .map(|file| file.path.as_str())
+                .join("\n");
+            rsync(
+                &["--archive", "--files-from=-"],
+                root.as_ref(),
+                target,
+                Some(file_list),
+            )
+            .await?;
+        }
+
+        if !delta.remove.is_empty() {
+            // Neither create nor update files, only delete the files that do not exist in the
+            // release store anymore
+            let exclude_tmp_dirs = format!("--exclude=/{}*", crate::consts::RELEASE_TMP_DIR_PREFIX);
+            rsync(
+                &[
+                    "--archive",
+                    "--delete",
+                    "--existing",
+                    "--ignore-existing",
+                    &exclude_tmp_dirs,
+                ],
+                root.as_ref(),
+                target,
+                None,
+            )
+            .await?;
+        }
+
+        let transferred = delta
+            .transfer
+            .iter()
+            .map(|file| (file.path.clone(), file.size, file.modified_at))
+            .collect::<Vec<_>>();
+        ReleaseMirrorFile::record_sync(
+            &mut conn,
+            &release_store,
+            target,
+            &transferred,
+            &delta.remove,
+        )?;
+        info!("Synced {} to {}", store_name, target);
+    }
+    Ok(())
+}
+
+/// Whether an rsync target is on another host ("host:path", "host::module" or "rsync://host/...")
+fn is_remote_target(target: &str) -> bool {
+    target.starts_with("rsync://")
+        || target
+            .split_once(':')
+            .is_some_and(|(host, _)| !host.is_empty() && !host.contains('/'))
+}
+
+/// Run rsync from the directory `source` to `target`, passing `stdin` to it
+async fn rsync(args: &[&str], source: &Path, target: &str, stdin: Option<String>) -> Result<()> {
+    // The trailing slashes make rsync sync the contents of the directories
+    let mut source = OsString::from(source);
+    source.push("/");
+    let target = format!("{}/", target.trim_end_matches('/'));
+
+    let mut command = tokio::process::Command::new("rsync");
+    command
+        .args(args)
+        .arg(&source)
+        .arg(&target)
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    debug!("Running {:?}", command);
+
+    let mut child = command.spawn().context("Starting rsync")?;
+    if let Some(stdin) = stdin {
+        let mut child_stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("Cannot write to the stdin of rsync"))?;
+        child_stdin.write_all(stdin.as_bytes()).await?;
+        child_stdin.shutdown().await?;
+    }
+
+    let output = child.wait_with_output().await.context("Running rsync")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "rsync to {} failed ({}): {}",
+            target,
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(secs: i64) -> NaiveDateTime {
+        chrono::DateTime::from_timestamp(1_700_000_000 + secs, 0)
+            .unwrap()
+            .naive_utc()
+    }
+
+    fn store_file(path: &str, size: i64, modified: i64) -> StoreFile {
+        StoreFile {
+            path: String::from(path),
+            size,
+            modified_at: time(modified),
+        }
+    }
+
+    fn synced_file(path: &str, size: i64, modified: i64) -> ReleaseMirrorFile {
+        ReleaseMirrorFile {
+            id: 1,
+            release_store_id: 1,
+            target: String::from("mirror:/srv"),
+            path: String::from(path),
+            size,
+            modified_at: time(modified),
+            synced_at: time(100),
+        }
+    }
+
+    #[test]
+    fn test_mirror_delta() {
+        let files = vec![
+            store_file("a-1.0.tar.gz", 10, 0),
+            store_file("b-1.0.tar.gz", 20, 0),
+            store_file("c-1.0.tar.gz", 30, 5),
+            store_file("d-1.0.tar.gz", 40, 0),
+        ];
+        let synced = vec![
+            synced_file("a-1.0.tar.gz", 10, 0),
+            synced_file("b-1.0.tar.gz", 15, 0),
+            synced_file("c-1.0.tar.gz", 30, 0),
+            synced_file("old-1.0.tar.gz", 10, 0),
+        ];
+
+        let delta = mirror_delta(&files, &synced);
+        assert_eq!(
+            delta
+                .transfer
+                .iter()
+                .map(|f| f.path.as_str())
+                .collect::<Vec<_>>(),
+            ["b-1.0.tar.gz", "c-1.0.tar.gz", "d-1.0.tar.gz"]
+        );
+        assert_eq!(delta.remove, ["old-1.0.tar.gz"]);
+
+        let delta = mirror_delta(&files[..1], &synced[..1]);
+        assert!(delta.is_empty());
+    }
+
+    #[test]
+    fn test_is_remote_target() {
+        assert!(is_remote_target("mirror.example.com:/srv/releases"));
+        assert!(is_remote_target("mirror::releases"));
+        assert!(is_remote_target("rsync://mirror.example.com/releases"));
+        assert!(!is_remote_target("/mnt/mirror"));
+        assert!(!is_remote_target("./mirror:1"));
+    }
+}
@@ -0,0 +1,272 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Implementation of the 'runtime-closure' subcommand
+//!
+//! The runtime closure of a package are the packages that have to be installed together with the
+//! package: its runtime dependencies and their runtime dependencies, without the build
+//! dependencies and the packages that are only required by those.
+
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use clap::ArgMatches;
+use diesel::ExpressionMethods;
+use diesel::PgConnection;
+use diesel::QueryDsl;
+use diesel::RunQueryDsl;
+use itertools::Itertools;
+
+use crate::config::Configuration;
+use crate::db::models;
+use crate::db::DbConnectionConfig;
+use crate::package::condition::ConditionData;
+use crate::package::Dag;
+use crate::package::Package;
+use crate::package::PackageName;
+use crate::package::PackageVersionConstraint;
+use crate::repository::Repository;
+use crate::schema;
+use crate::util::docker::ImageNameLookup;
+use crate::util::EnvironmentVariableName;
+
+/// The runtime closure of a package
+#[derive(serde::Serialize)]
+struct RuntimeClosure {
+    name: String,
+    version: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    submit: Option<uuid::Uuid>,
+
+    /// The artifacts of the package itself (only with --submit)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    artifacts: Option<Vec<String>>,
+
+    runtime_dependencies: Vec<RuntimeDependency>,
+}
+
+/// A package of a runtime closure
+#[derive(serde::Serialize)]
+struct RuntimeDependency {
+    name: String,
+    version: String,
+
+    /// The artifacts that the submit built (or reused) for the package (only with --submit)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    artifacts: Option<Vec<String>>,
+}
+
+/// Implementation of the "runtime-closure" subcommand
+pub async fn runtime_closure<F>(
+    db_connection_config: DbConnectionConfig<'_>,
+    config: &Configuration,
+    matches: &ArgMatches,
+    repo_path: &Path,
+    load_repo: F,
+) -> Result<()>
+where
+    F: FnOnce() -> Result<Repository>,
+{
+    let closures = if matches.contains_id("submit") {
+        let mut conn = db_connection_config.establish_connection()?;
+        vec![submit_runtime_closure(
+            &mut conn, matches, repo_path, load_repo,
+        )?]
+    } else {
+        repository_runtime_closures(config, matches, load_repo()?)?
+    };
+
+    let out = std::io::stdout();
+    let mut outlock = out.lock();
+    serde_json::to_writer_pretty(&mut outlock, &closures)?;
+    writeln!(outlock)?;
+    Ok(())
+}
+
+/// The runtime closures of the packages in the repository that match the arguments
+fn repository_runtime_closures(
+    config: &Configuration,
+    matches: &ArgMatches,
+    repo: Repository,
+) -> Result<Vec<RuntimeClosure>> {
+    let pname = matches
+        .get_one::<String>("package_name")
+        .map(|s| PackageName::from(s.to_owned()))
+        .unwrap(); // safe by clap
+    let pvers = matches
+        .get_one::<String>("package_version_constraint")
+        .map(|s| s.to_owned())
+        .map(PackageVersionConstraint::try_from)
+        .transpose()?;
+
+    let image_name_lookup = ImageNameLookup::create(config.docker().images())?;
+    let image_name = matches
+        .get_one::<String>("image")
+        .map(|s| image_name_lookup.expand(s))
+        .transpose()?;
+
+    let additional_env = matches
+        .get_many::<String>("env")
+        .unwrap_or_default()
+        .map(AsRef::as_ref)
+        .map(crate::util::env::parse_to_env)
+        .collect::<Result<Vec<(EnvironmentVariableName, String)>>>()?;
+
+    let condition_data = ConditionData {
+        image_name: image_name.as_ref(),
+        env: &additional_env,
+    };
+
+    let closures = repo
+        .packages()
+        .filter(|p| *p.name() == pname)
+        .filter(|p| {
+            pvers
+                .as_ref()
+                .map(|v| v.matches(p.version()))
+                .unwrap_or(true)
+        })
+        .map(|package| {
+            let dag = Dag::for_root_package(package.clone(), &repo, None, &condition_data)?;
+            Ok(RuntimeClosure {
+                name: package.name().to_string(),
+                version: package.version().to_string(),
+                image: image_name.as_ref().map(|image| image.to_string()),
+                submit: None,
+                artifacts: None,
+                runtime_dependencies: dag
+                    .runtime_closure()
+                    .into_iter()
+                    .map(|p| RuntimeDependency {
+                        name: p.name().to_string(),
+                        version: p.version().to_string(),
+                        artifacts: None,
+                    })
+                    .collect(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if closures.is_empty() {
+        return Err(anyhow!("No package found: {}", pname));
+    }
+    Ok(closures)
+}
+
+/// The runtime closure of the requested package of a submit, with the artifacts of the submit
+fn submit_runtime_closure<F>(
+    conn: &mut PgConnection,
+    matches: &ArgMatches,
+    repo_path: &Path,
+    load_repo: F,
+) -> Result<RuntimeClosure>
+where
+    F: FnOnce() -> Result<Repository>,
+{
+    let submit_uuid = crate::commands::ids::submit_uuid(conn, matches, "submit")?;
+    let submit = models::Submit::with_id(conn, &submit_uuid)
+        .with_context(|| anyhow!("Loading submit '{}' from DB", submit_uuid))?;
+    let githash = models::GitHash::with_id(conn, submit.repo_hash_id)
+        .with_context(|| anyhow!("Loading GitHash '{}' from DB", submit.repo_hash_id))?;
+    let jobs = schema::jobs::table
+        .filter(schema::jobs::submit_id.eq(submit.id))
+        .filter(schema::jobs::retried.eq(false))
+        .load::<models::Job>(conn)
+        .with_context(|| anyhow!("Loading jobs for submit = {}", submit_uuid))?;
+
+    let (dag, image) = super::db::submit_dag(conn, &submit, &githash, &jobs, repo_path, load_repo)?;
+    let root = &dag.dag()[*dag.root_idx()];
+    if let Some(pname) = matches.get_one::<String>("package_name") {
+        if root.name().as_str() != pname {
+            return Err(anyhow!(
+                "Submit {} built {}, not {}",
+                submit.uuid,
+                root.display_name_version(),
+                pname
+            ));
+        }
+    }
+
+    let artifacts = submit_artifacts(conn, &submit)?;
+    let artifacts_of = |package: &Package| {
+        Some(
+            artifacts
+                .iter()
+                .filter(|(name, version, _)| {
+                    name == package.name().as_str() && version == package.version().as_str()
+                })
+                .map(|(_, _, path)| path.clone())
+                .sorted()
+                .collect::<Vec<_>>(),
+        )
+    };
+
+    Ok(RuntimeClosure {
+        name: root.name().to_string(),
+        version: root.version().to_string(),
+        image: Some(image.name),
+        submit: Some(submit.uuid),
+        artifacts: artifacts_of(root),
+        runtime_dependencies: dag
+            .runtime_closure()
+            .into_iter()
+            .map(|p| RuntimeDependency {
+                name: p.name().to_string(),
+                version: p.version().to_string(),
+                artifacts: artifacts_of(p),
+            })
+            .collect(),
+    })
+}
+
+/// The artifacts of a submit as (package name, package version, artifact path)
+///
+/// This includes the artifacts that were built by the jobs of the submit as well as the
+/// artifacts of previous submits that were reused by the submit.
+fn submit_artifacts(
+    conn: &mut PgConnection,
+    submit: &models::Submit,
+) -> Result<Vec<(String, String, String)>> {
+    let built = schema::artifacts::table
+        .inner_join(schema::jobs::table.inner_join(schema::packages::table))
+        .filter(schema::jobs::submit_id.eq(submit.id))
+        .filter(schema::jobs::retried.eq(false))
+        .select((
+            schema::packages::name,
+            schema::packages::version,
+            schema::artifacts::path,
+        ))
+        .load::<(String, String, String)>(conn)
+        .with_context(|| anyhow!("Loading the artifacts of submit {}", submit.uuid))?;
+
+    let reused_artifact_ids = models::ReusedArtifact::of_submit(conn, submit)?
+        .into_iter()
+        .map(|(_, artifact, _)| artifact.id)
+        .collect::<Vec<_>>();
+    let reused = schema::artifacts::table
+        .inner_join(schema::jobs::table.inner_join(schema::packages::table))
+        .filter(schema::artifacts::id.eq_any(reused_artifact_ids))
+        .select((
+            schema::packages::name,
+            schema::packages::version,
+            schema::artifacts::path,
+        ))
+        .load::<(String, String, String)>(conn)
+        .with_context(|| anyhow!("Loading the reused artifacts of submit {}", submit.uuid))?;
+
+    Ok(built.into_iter().chain(reused).collect())
+}
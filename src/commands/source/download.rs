@@ -14,21 +14,26 @@ use std::sync::Arc;
 
 use anyhow::anyhow;
 use anyhow::Context;
-use anyhow::Error;
 use anyhow::Result;
 use clap::ArgMatches;
+use tokio::io::AsyncReadExt;
 use tokio::io::AsyncWriteExt;
 use tokio::sync::Mutex;
 use tokio_stream::StreamExt;
 use tracing::{info, trace, warn};
+use url::Url;
 
 use crate::config::*;
+use crate::package::HashType;
+use crate::package::HashValue;
 use crate::package::PackageName;
 use crate::package::PackageVersionConstraint;
 use crate::repository::Repository;
 use crate::source::*;
 use crate::util::progress::ProgressBars;
 
+use super::ftp;
+
 const NUMBER_OF_MAX_CONCURRENT_DOWNLOADS: usize = 100;
 const APP_USER_AGENT: &str = concat! {env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")};
 
@@ -39,23 +44,22 @@ const APP_USER_AGENT: &str = concat! {env!("CARGO_PKG_NAME"), "/", env!("CARGO_P
 /// the user.
 ///
 /// The problem this helper solves is that we only have one status bar for all downloads, and all
-/// download tasks must be able to increase the number of bytes received, for example, (that is
-/// displayed in the status message) but in a sync way.
+/// download tasks must be able to increase the number of bytes received and expected (which the
+/// bar uses to display the throughput and the ETA), but in a sync way.
 #[derive(Clone)]
 struct ProgressWrapper {
     download_count: u64,
     finished_downloads: u64,
-    current_bytes: usize,
     sum_bytes: u64,
     bar: Arc<Mutex<indicatif::ProgressBar>>,
 }
 
 impl ProgressWrapper {
     fn new(bar: indicatif::ProgressBar) -> Self {
+        bar.set_length(0);
         Self {
             download_count: 0,
             finished_downloads: 0,
-            current_bytes: 0,
             sum_bytes: 0,
             bar: Arc::new(Mutex::new(bar)),
         }
@@ -64,34 +68,27 @@ impl ProgressWrapper {
     async fn inc_download_count(&mut self) {
         self.download_count += 1;
         self.set_message().await;
-        let bar = self.bar.lock().await;
-        bar.inc_length(1);
     }
 
     async fn inc_download_bytes(&mut self, bytes: u64) {
         self.sum_bytes += bytes;
+        self.bar.lock().await.inc_length(bytes);
         self.set_message().await;
     }
 
     async fn finish_one_download(&mut self) {
         self.finished_downloads += 1;
-        self.bar.lock().await.inc(1);
         self.set_message().await;
     }
 
     async fn add_bytes(&mut self, len: usize) {
-        self.current_bytes += len;
-        self.set_message().await;
+        self.bar.lock().await.inc(len as u64);
     }
 
     async fn set_message(&self) {
         let bar = self.bar.lock().await;
-        let current_mbytes = (self.current_bytes as f64) / 1_000_000_f64;
-        let sum_mbytes = (self.sum_bytes as f64) / 1_000_000_f64;
         bar.set_message(format!(
-            "Downloading ({:.2}/{:.2} MB, {dlfinished}/{dlsum} downloads finished)",
-            current_mbytes,
-            sum_mbytes,
+            "Downloading ({dlfinished}/{dlsum} downloads finished)",
             dlfinished = self.finished_downloads,
             dlsum = self.download_count
         ));
@@ -112,15 +109,19 @@ impl ProgressWrapper {
             self.download_count
         ));
     }
-}
 
-async fn perform_download(
-    source: &SourceEntry,
-    progress: Arc<Mutex<ProgressWrapper>>,
-    timeout: Option<u64>,
-) -> Result<()> {
-    trace!("Downloading: {:?}", source);
+    /// Get a summary of the finished downloads
+    async fn summary(&self) -> String {
+        let received = self.bar.lock().await.position();
+        format!(
+            "Downloaded {} sources ({})",
+            self.finished_downloads,
+            bytesize::ByteSize::b(received)
+        )
+    }
+}
 
+pub fn http_client(timeout: Option<u64>) -> Result<reqwest::Client> {
     let client_builder = reqwest::Client::builder()
         .user_agent(APP_USER_AGENT)
         .redirect(reqwest::redirect::Policy::limited(10));
@@ -131,19 +132,23 @@ async fn perform_download(
         client_builder
     };
 
-    let client = client_builder
+    client_builder
         .build()
-        .context("Building HTTP client failed")?;
-
-    let request = client
-        .get(source.url().as_ref())
-        .build()
-        .with_context(|| anyhow!("Building request for {} failed", source.url().as_ref()))?;
+        .context("Building HTTP client failed")
+}
 
-    let response = match client.execute(request).await {
-        Ok(resp) => resp,
-        Err(e) => return Err(e).with_context(|| anyhow!("Downloading '{}'", &source.url())),
-    };
+/// Download the file at `url` (without storing it) and compute its hash
+pub async fn hash_of_url(
+    url: &Url,
+    hashtype: &HashType,
+    timeout: Option<u64>,
+) -> Result<HashValue> {
+    trace!("Downloading {} for hashing", url);
+    let response = http_client(timeout)?
+        .get(url.as_ref())
+        .send()
+        .await
+        .with_context(|| anyhow!("Downloading '{}'", url))?;
 
     if response.status() != reqwest::StatusCode::OK {
         return Err(anyhow!(
@@ -151,14 +156,189 @@ async fn perform_download(
             response.status(),
             reqwest::StatusCode::OK
         ))
-        .with_context(|| anyhow!("Downloading \"{}\" failed", &source.url()));
+        .with_context(|| anyhow!("Downloading \"{}\" failed", url));
     }
 
-    progress
-        .lock()
+    let bytes = response
+        .bytes()
         .await
-        .inc_download_bytes(response.content_length().unwrap_or(0))
-        .await;
+        .with_context(|| anyhow!("Downloading \"{}\" failed", url))?;
+    hashtype.hash_from_reader(bytes.as_ref()).await
+}
+
+/// A limit for the throughput of all downloads together
+#[derive(Debug)]
+struct RateLimit {
+    bytes_per_sec: u64,
+    started: std::time::Instant,
+    received: u64,
+}
+
+impl RateLimit {
+    fn new(bytes_per_sec: u64) -> Self {
+        RateLimit {
+            bytes_per_sec,
+            started: std::time::Instant::now(),
+            received: 0,
+        }
+    }
+
+    /// Record `len` received bytes and return how long to wait to stay below the limit
+    fn delay(&mut self, len: usize) -> Option<std::time::Duration> {
+        self.received += len as u64;
+        let expected = std::time::Duration::from_secs_f64(
+            self.received as f64 / self.bytes_per_sec.max(1) as f64,
+        );
+        expected
+            .checked_sub(self.started.elapsed())
+            .filter(|delay| !delay.is_zero())
+    }
+}
+
+/// Download a source into its partial file, resume it if the connection is interrupted and move
+/// it into place once its hash is verified
+async fn perform_download(
+    source: &SourceEntry,
+    progress: Arc<Mutex<ProgressWrapper>>,
+    timeout: Option<u64>,
+    rate_limit: Option<&Mutex<RateLimit>>,
+    retries: u32,
+    local_source_mode: &LocalSourceMode,
+) -> Result<()> {
+    trace!("Downloading: {:?}", source);
+
+    let client = match source.url().scheme() {
+        "http" | "https" => Some(http_client(timeout)?),
+        "ftp" => None,
+        "file" => {
+            let size = source.fetch_local(local_source_mode).await?;
+            let mut progress = progress.lock().await;
+            progress.inc_download_bytes(size).await;
+            progress.add_bytes(size as usize).await;
+            drop(progress);
+            return source.finish_partial().await;
+        }
+        scheme => {
+            return Err(anyhow!(
+                "Unsupported URL scheme '{}' (supported: http, https, ftp, file)",
+                scheme
+            ))
+            .with_context(|| anyhow!("Downloading '{}'", source.url()))
+        }
+    };
+
+    let mut length_known = false;
+    let mut attempt = 0;
+    loop {
+        let res = match client.as_ref() {
+            Some(client) => {
+                download_attempt(client, source, &progress, rate_limit, &mut length_known).await?
+            }
+            None => {
+                ftp_download_attempt(source, &progress, rate_limit, &mut length_known, timeout)
+                    .await?
+            }
+        };
+        match res {
+            Ok(()) => break,
+            Err(e) if attempt < retries => {
+                attempt += 1;
+                warn!(
+                    "Resuming download of {} (attempt {}/{}): {:?}",
+                    source.url(),
+                    attempt,
+                    retries,
+                    e
+                );
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    source.finish_partial().await
+}
+
+/// Download (the rest of) a source into its partial file
+///
+/// The outer error is returned if the download cannot succeed, the inner error if the download
+/// was interrupted and can be resumed.
+async fn download_attempt(
+    client: &reqwest::Client,
+    source: &SourceEntry,
+    progress: &Mutex<ProgressWrapper>,
+    rate_limit: Option<&Mutex<RateLimit>>,
+    length_known: &mut bool,
+) -> Result<Result<()>> {
+    use reqwest::header::CONTENT_RANGE;
+    use reqwest::header::ETAG;
+    use reqwest::header::IF_RANGE;
+    use reqwest::header::LAST_MODIFIED;
+    use reqwest::header::RANGE;
+    use reqwest::StatusCode;
+
+    let partial = source
+        .partial_download()
+        .await?
+        .filter(|(received, _)| *received > 0);
+    let mut request = client.get(source.url().as_ref());
+    if let Some((received, meta)) = partial.as_ref() {
+        info!(
+            "Resuming download of {} after {}",
+            source.url(),
+            bytesize::ByteSize::b(*received)
+        );
+        request = request.header(RANGE, format!("bytes={received}-"));
+        if let Some(validator) = meta.validator.as_ref() {
+            request = request.header(IF_RANGE, validator);
+        }
+    }
+
+    let response = match request.send().await {
+        Ok(resp) => resp,
+        Err(e) => return Ok(Err(e).with_context(|| anyhow!("Downloading '{}'", &source.url()))),
+    };
+
+    let resume = match (response.status(), partial.as_ref()) {
+        (StatusCode::OK, _) => false,
+        (StatusCode::PARTIAL_CONTENT, Some((received, _))) => {
+            // The server has to send the range that was requested
+            let expected = format!("bytes {received}-");
+            let content_range = response
+                .headers()
+                .get(CONTENT_RANGE)
+                .and_then(|h| h.to_str().ok())
+                .unwrap_or("");
+            if !content_range.starts_with(&expected) {
+                source.remove_partial().await?;
+                return Ok(Err(anyhow!(
+                    "Server sent range \"{}\" instead of \"{}...\", restarting the download",
+                    content_range,
+                    expected
+                )));
+            }
+            true
+        }
+
+        // The partial file has the size of the source already, its hash is verified afterwards
+        (StatusCode::RANGE_NOT_SATISFIABLE, Some(_)) => return Ok(Ok(())),
+        (status, _) => {
+            return Err(anyhow!(
+                "Received HTTP status code \"{}\" but \"{}\" is expected for a successful download",
+                status,
+                StatusCode::OK
+            ))
+            .with_context(|| anyhow!("Downloading \"{}\" failed", &source.url()));
+        }
+    };
+
+    if !*length_known {
+        *length_known = true;
+        progress
+            .lock()
+            .await
+            .inc_download_bytes(response.content_length().unwrap_or(0))
+            .await;
+    }
 
     // Check the content type to warn the user when downloading HTML files or when the server
     // didn't specify a content type.
@@ -181,24 +361,174 @@ async fn perform_download(
         source.url()
     );
 
-    let file = source.create().await.with_context(|| {
+    // Weak ETags cannot be used for range requests
+    let validator = response
+        .headers()
+        .get(ETAG)
+        .and_then(|h| h.to_str().ok())
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| {
+            response
+                .headers()
+                .get(LAST_MODIFIED)
+                .and_then(|h| h.to_str().ok())
+        })
+        .map(String::from);
+    let meta = PartialDownload {
+        url: source.url().clone(),
+        validator,
+    };
+    let file = source.open_partial(&meta, resume).await.with_context(|| {
         anyhow!(
             "Creating source file destination: {}",
-            source.path().display()
+            source.partial_path().display()
         )
     })?;
     let mut file = tokio::io::BufWriter::new(file);
 
     let mut stream = response.bytes_stream();
     while let Some(bytes) = stream.next().await {
-        let bytes = bytes?;
-        tokio::try_join!(file.write_all(bytes.as_ref()), async {
-            progress.lock().await.add_bytes(bytes.len()).await;
-            Ok(())
+        let bytes = match bytes {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                file.flush().await?;
+                return Ok(Err(e).with_context(|| {
+                    anyhow!("Downloading \"{}\" was interrupted", &source.url())
+                }));
+            }
+        };
+        write_chunk(&mut file, bytes.as_ref(), progress, rate_limit).await?;
+    }
+
+    file.flush().await?;
+    Ok(Ok(()))
+}
+
+/// Download (the rest of) a source from an FTP server into its partial file
+///
+/// Like `download_attempt()`, the outer error is returned if the download cannot succeed (the
+/// server replied with a permanent error), the inner error if it can be resumed.
+async fn ftp_download_attempt(
+    source: &SourceEntry,
+    progress: &Mutex<ProgressWrapper>,
+    rate_limit: Option<&Mutex<RateLimit>>,
+    length_known: &mut bool,
+    timeout: Option<u64>,
+) -> Result<Result<()>> {
+    match ftp_download(source, progress, rate_limit, length_known, timeout).await {
+        Ok(()) => Ok(Ok(())),
+        Err(e) if e.downcast_ref::<ftp::PermanentReply>().is_some() => {
+            Err(e).with_context(|| anyhow!("Downloading \"{}\" failed", &source.url()))
+        }
+        Err(e) => Ok(Err(e)),
+    }
+}
+
+async fn ftp_download(
+    source: &SourceEntry,
+    progress: &Mutex<ProgressWrapper>,
+    rate_limit: Option<&Mutex<RateLimit>>,
+    length_known: &mut bool,
+    timeout: Option<u64>,
+) -> Result<()> {
+    let path = ftp::path_of(source.url())?;
+    let partial = source.partial_download().await?;
+    let mut conn = ftp::FtpConnection::connect(source.url(), timeout).await?;
+    let size = conn.size(&path).await?;
+    let validator = conn.modification_time(&path).await?;
+
+    // The download is only resumed if the file on the server was not modified in the meantime
+    let offset = match partial {
+        Some((received, meta)) if meta.validator == validator => received,
+        _ => 0,
+    };
+    if offset > 0 {
+        info!(
+            "Resuming download of {} after {}",
+            source.url(),
+            bytesize::ByteSize::b(offset)
+        );
+    }
+    if !*length_known {
+        *length_known = true;
+        progress
+            .lock()
+            .await
+            .inc_download_bytes(size.map(|size| size.saturating_sub(offset)).unwrap_or(0))
+            .await;
+    }
+    if size.is_some_and(|size| offset >= size) {
+        // The partial file has the size of the source already, its hash is verified afterwards
+        return Ok(());
+    }
+
+    let meta = PartialDownload {
+        url: source.url().clone(),
+        validator,
+    };
+    let file = source
+        .open_partial(&meta, offset > 0)
+        .await
+        .with_context(|| {
+            anyhow!(
+                "Creating source file destination: {}",
+                source.partial_path().display()
+            )
         })?;
+    let mut file = tokio::io::BufWriter::new(file);
+
+    let mut data = conn.retrieve(&path, offset).await?;
+    let mut buf = vec![0; 64 * 1024];
+    let mut received = offset;
+    loop {
+        let n = match data.read(&mut buf).await {
+            Ok(n) => n,
+            Err(e) => {
+                file.flush().await?;
+                return Err(e)
+                    .with_context(|| anyhow!("Downloading \"{}\" was interrupted", &source.url()));
+            }
+        };
+        if n == 0 {
+            break;
+        }
+        write_chunk(&mut file, &buf[..n], progress, rate_limit).await?;
+        received += n as u64;
     }
+    file.flush().await?;
+    drop(data);
+    conn.finish_transfer().await?;
+
+    match size {
+        Some(size) if received < size => Err(anyhow!(
+            "Downloading \"{}\" ended after {} of {} bytes",
+            source.url(),
+            received,
+            size
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Write a received chunk of a source to its partial file, with the progress and the rate limit
+async fn write_chunk(
+    file: &mut tokio::io::BufWriter<tokio::fs::File>,
+    bytes: &[u8],
+    progress: &Mutex<ProgressWrapper>,
+    rate_limit: Option<&Mutex<RateLimit>>,
+) -> Result<()> {
+    tokio::try_join!(file.write_all(bytes), async {
+        progress.lock().await.add_bytes(bytes.len()).await;
+        Ok(())
+    })?;
 
-    file.flush().await.map_err(Error::from).map(|_| ())
+    if let Some(rate_limit) = rate_limit {
+        let delay = rate_limit.lock().await.delay(bytes.len());
+        if let Some(delay) = delay {
+            tokio::time::sleep(delay).await;
+        }
+    }
+    Ok(())
 }
 
 // Implementation of the 'source download' subcommand
@@ -210,6 +540,10 @@ pub async fn download(
 ) -> Result<()> {
     let force = matches.get_flag("force");
     let timeout = matches.get_one::<u64>("timeout").copied();
+    let retries = *matches.get_one::<u32>("retries").unwrap(); // safe by clap
+    let rate_limit = matches
+        .get_one::<bytesize::ByteSize>("rate_limit")
+        .map(|limit| Mutex::new(RateLimit::new(limit.as_u64())));
     let cache = PathBuf::from(config.source_cache_root());
     let sc = SourceCache::new(cache);
     let pname = matches
@@ -227,7 +561,8 @@ pub async fn download(
         .map(|s| crate::commands::util::mk_package_name_regex(s.as_ref()))
         .transpose()?;
 
-    let progressbar = Arc::new(Mutex::new(ProgressWrapper::new(progressbars.bar()?)));
+    let started = std::time::Instant::now();
+    let progressbar = Arc::new(Mutex::new(ProgressWrapper::new(progressbars.bytes_bar()?)));
 
     let download_sema = Arc::new(tokio::sync::Semaphore::new(
         NUMBER_OF_MAX_CONCURRENT_DOWNLOADS,
@@ -239,6 +574,7 @@ pub async fn download(
             sc.sources_for(p).into_iter().map(|source| {
                 let download_sema = download_sema.clone();
                 let progressbar = progressbar.clone();
+                let rate_limit = rate_limit.as_ref();
                 async move {
                     let source_path_exists = source.path().exists();
                     if !source_path_exists && source.download_manually() {
@@ -252,16 +588,36 @@ pub async fn download(
                     if source_path_exists && !force {
                         Err(anyhow!("Source exists: {}", source.path().display()))
                     } else {
+                        // Sources with a file:// URL are copied, which works offline as well
+                        if source.url().scheme() != "file" {
+                            crate::util::offline::ensure_online(format_args!(
+                                "Source {} is not in the source cache, downloading it from {}",
+                                source.path().display(),
+                                source.url()
+                            ))?;
+                        }
+
                         if source_path_exists
                         /* && force is implied by 'if' above*/
                         {
                             source.remove_file().await?;
                         }
+                        if force {
+                            source.remove_partial().await?;
+                        }
 
                         progressbar.lock().await.inc_download_count().await;
                         {
                             let permit = download_sema.acquire_owned().await?;
-                            perform_download(&source, progressbar.clone(), timeout).await?;
+                            perform_download(
+                                &source,
+                                progressbar.clone(),
+                                timeout,
+                                rate_limit,
+                                retries,
+                                config.local_source_mode(),
+                            )
+                            .await?;
                             drop(permit);
                         }
                         progressbar.lock().await.finish_one_download().await;
@@ -280,10 +636,28 @@ pub async fn download(
         progressbar.lock().await.error().await;
         return r;
     } else {
-        progressbar.lock().await.success().await;
+        let progressbar = progressbar.lock().await;
+        progressbar.success().await;
+        progressbars.summary(started, progressbar.summary().await)?;
     }
 
     super::verify(matches, config, repo, progressbars).await?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limit_delay() {
+        let mut limit = RateLimit::new(1000);
+        let delay = limit.delay(500).unwrap();
+        assert!(delay > std::time::Duration::from_millis(400));
+        assert!(delay <= std::time::Duration::from_millis(500));
+
+        let delay = limit.delay(500).unwrap();
+        assert!(delay > std::time::Duration::from_millis(900));
+    }
+}
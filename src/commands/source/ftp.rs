@@ -0,0 +1,289 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! A minimal FTP client for downloading sources
+//!
+//! Only what is required to download a file is implemented: login (anonymous if the URL has no
+//! user), binary transfers in passive mode and resuming transfers with REST.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::io::BufReader;
+use tokio::net::TcpStream;
+use tracing::trace;
+use url::Url;
+
+/// A reply of the server that means that the command will not succeed if it is repeated (5xx)
+#[derive(Debug)]
+pub struct PermanentReply {
+    code: u16,
+    text: String,
+}
+
+impl std::fmt::Display for PermanentReply {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "FTP server replied {} {}", self.code, self.text)
+    }
+}
+
+impl std::error::Error for PermanentReply {}
+
+/// The control connection to an FTP server
+pub struct FtpConnection {
+    control: BufReader<TcpStream>,
+    peer: SocketAddr,
+    timeout: Duration,
+}
+
+impl FtpConnection {
+    /// Connect to the server of the URL and log in
+    pub async fn connect(url: &Url, timeout: Option<u64>) -> Result<Self> {
+        let host = url
+            .host_str()
+            .ok_or_else(|| anyhow!("No host in URL: {}", url))?;
+        let port = url.port_or_known_default().unwrap_or(21);
+        let timeout = Duration::from_secs(timeout.unwrap_or(60));
+
+        trace!("Connecting to FTP server {}:{}", host, port);
+        let stream = tokio::time::timeout(timeout, TcpStream::connect((host, port)))
+            .await
+            .map_err(|_| anyhow!("Timeout"))
+            .and_then(|res| res.map_err(anyhow::Error::from))
+            .with_context(|| anyhow!("Connecting to {}:{}", host, port))?;
+        let peer = stream.peer_addr()?;
+        let mut conn = FtpConnection {
+            control: BufReader::new(stream),
+            peer,
+            timeout,
+        };
+
+        conn.expect(None, &[220]).await?;
+        let user = match url.username() {
+            "" => String::from("anonymous"),
+            user => decode(user)?,
+        };
+        let password = match url.password() {
+            Some(password) => decode(password)?,
+            None => String::from("anonymous@"),
+        };
+        let (code, _) = conn
+            .expect(Some(&format!("USER {user}")), &[230, 331])
+            .await?;
+        if code == 331 {
+            conn.expect(Some(&format!("PASS {password}")), &[230, 202])
+                .await?;
+        }
+        conn.expect(Some("TYPE I"), &[200]).await?;
+        Ok(conn)
+    }
+
+    /// The size of a file, if the server supports the SIZE command
+    pub async fn size(&mut self, path: &str) -> Result<Option<u64>> {
+        let (code, text) = self.command(&format!("SIZE {path}")).await?;
+        Ok((code == 213)
+            .then(|| text.trim().parse::<u64>().ok())
+            .flatten())
+    }
+
+    /// The modification time of a file, if the server supports the MDTM command
+    pub async fn modification_time(&mut self, path: &str) -> Result<Option<String>> {
+        let (code, text) = self.command(&format!("MDTM {path}")).await?;
+        Ok((code == 213).then(|| text.trim().to_owned()))
+    }
+
+    /// Start the transfer of a file from `offset` on, the file is sent over the returned data
+    /// connection
+    ///
+    /// `finish_transfer()` has to be called after the data connection was read completely.
+    pub async fn retrieve(&mut self, path: &str, offset: u64) -> Result<TcpStream> {
+        let data_addr = match self.command("EPSV").await? {
+            (229, text) => SocketAddr::new(self.peer.ip(), parse_epsv(&text)?),
+
+            // The address of the control connection is used instead of the announced one,
+            // servers behind NAT often announce their internal address
+            _ => {
+                let (_, text) = self.expect(Some("PASV"), &[227]).await?;
+                SocketAddr::new(self.peer.ip(), parse_pasv(&text)?)
+            }
+        };
+
+        trace!("Opening FTP data connection to {}", data_addr);
+        let data = tokio::time::timeout(self.timeout, TcpStream::connect(data_addr))
+            .await
+            .map_err(|_| anyhow!("Timeout"))
+            .and_then(|res| res.map_err(anyhow::Error::from))
+            .with_context(|| anyhow!("Opening data connection to {}", data_addr))?;
+
+        if offset > 0 {
+            self.expect(Some(&format!("REST {offset}")), &[350]).await?;
+        }
+        self.expect(Some(&format!("RETR {path}")), &[125, 150])
+            .await?;
+        Ok(data)
+    }
+
+    /// Wait for the server to confirm that the transfer is complete
+    pub async fn finish_transfer(&mut self) -> Result<()> {
+        self.expect(None, &[226, 250]).await.map(|_| ())
+    }
+
+    /// Send a command (if any) and fail if the reply does not have one of the expected codes
+    async fn expect(&mut self, command: Option<&str>, codes: &[u16]) -> Result<(u16, String)> {
+        let (code, text) = match command {
+            Some(command) => self.command(command).await?,
+            None => self.reply().await?,
+        };
+        if codes.contains(&code) {
+            Ok((code, text))
+        } else if (500..600).contains(&code) {
+            Err(PermanentReply { code, text }.into())
+        } else {
+            Err(anyhow!("FTP server replied {} {}", code, text))
+        }
+    }
+
+    async fn command(&mut self, command: &str) -> Result<(u16, String)> {
+        if command.starts_with("PASS ") {
+            trace!("FTP command: PASS ***");
+        } else {
+            trace!("FTP command: {}", command);
+        }
+        let stream = self.control.get_mut();
+        stream.write_all(command.as_bytes()).await?;
+        stream.write_all(b"\r\n").await?;
+        self.reply().await
+    }
+
+    /// Read a (possibly multi-line) reply
+    async fn reply(&mut self) -> Result<(u16, String)> {
+        let mut text = String::new();
+        let mut code = None;
+        loop {
+            let mut line = String::new();
+            let n = tokio::time::timeout(self.timeout, self.control.read_line(&mut line))
+                .await
+                .map_err(|_| anyhow!("Timeout while waiting for the FTP server"))??;
+            if n == 0 {
+                return Err(anyhow!("FTP server closed the connection"));
+            }
+            trace!("FTP reply: {}", line.trim_end());
+
+            let (line_code, last, line_text) = parse_reply_line(&line);
+            if code.is_none() {
+                code = line_code;
+            }
+            text.push_str(line_text);
+            if last && line_code.is_some() && line_code == code {
+                break;
+            }
+            text.push('\n');
+        }
+        let code = code.ok_or_else(|| anyhow!("Invalid FTP reply: {}", text))?;
+        Ok((code, text))
+    }
+}
+
+/// Parse a line of a reply into its code (if it starts with one), whether it is the last line of
+/// the reply and its text
+fn parse_reply_line(line: &str) -> (Option<u16>, bool, &str) {
+    let line = line.trim_end_matches(['\r', '\n']);
+    let code = line
+        .get(..3)
+        .filter(|code| code.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|code| code.parse::<u16>().ok());
+    match (code, line.as_bytes().get(3)) {
+        (Some(code), Some(b'-')) => (Some(code), false, &line[4..]),
+        (Some(code), Some(b' ')) => (Some(code), true, &line[4..]),
+        (Some(code), None) => (Some(code), true, ""),
+        _ => (None, false, line),
+    }
+}
+
+/// The port of a reply to EPSV: "Entering Extended Passive Mode (|||6446|)"
+fn parse_epsv(text: &str) -> Result<u16> {
+    text.split_once("(|||")
+        .and_then(|(_, rest)| rest.split_once("|)"))
+        .and_then(|(port, _)| port.parse::<u16>().ok())
+        .ok_or_else(|| anyhow!("Invalid reply to EPSV: {}", text))
+}
+
+/// The port of a reply to PASV: "Entering Passive Mode (192,168,1,2,19,137)"
+fn parse_pasv(text: &str) -> Result<u16> {
+    let numbers = text
+        .split_once('(')
+        .and_then(|(_, rest)| rest.split_once(')'))
+        .map(|(numbers, _)| {
+            numbers
+                .split(',')
+                .map(|n| n.trim().parse::<u8>())
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .and_then(|numbers| numbers.ok())
+        .filter(|numbers| numbers.len() == 6)
+        .ok_or_else(|| anyhow!("Invalid reply to PASV: {}", text))?;
+    Ok((u16::from(numbers[4]) << 8) | u16::from(numbers[5]))
+}
+
+/// The decoded path of a file on an FTP server
+pub fn path_of(url: &Url) -> Result<String> {
+    decode(url.path())
+}
+
+fn decode(s: &str) -> Result<String> {
+    percent_encoding::percent_decode_str(s)
+        .decode_utf8()
+        .map(String::from)
+        .with_context(|| anyhow!("Decoding '{}'", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reply_line() {
+        assert_eq!(
+            parse_reply_line("220 Welcome\r\n"),
+            (Some(220), true, "Welcome")
+        );
+        assert_eq!(
+            parse_reply_line("211-Features:\r\n"),
+            (Some(211), false, "Features:")
+        );
+        assert_eq!(parse_reply_line(" SIZE\r\n"), (None, false, " SIZE"));
+        assert_eq!(parse_reply_line("226\r\n"), (Some(226), true, ""));
+    }
+
+    #[test]
+    fn test_parse_passive_replies() {
+        assert_eq!(
+            parse_epsv("Entering Extended Passive Mode (|||6446|)").unwrap(),
+            6446
+        );
+        assert_eq!(
+            parse_pasv("Entering Passive Mode (192,168,1,2,19,137).").unwrap(),
+            19 * 256 + 137
+        );
+        assert!(parse_pasv("Entering Passive Mode (192,168,1,2,19)").is_err());
+        assert!(parse_epsv("Entering Extended Passive Mode").is_err());
+    }
+
+    #[test]
+    fn test_path_of() {
+        let url = Url::parse("ftp://ftp.example.com/pub/foo%20bar-1.0.tar.gz").unwrap();
+        assert_eq!(path_of(&url).unwrap(), "/pub/foo bar-1.0.tar.gz");
+    }
+}
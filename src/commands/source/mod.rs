@@ -11,6 +11,7 @@
 //! Implementation of the 'source' subcommand
 
 use std::io::Write;
+use std::path::Path;
 use std::path::PathBuf;
 
 use anyhow::anyhow;
@@ -31,22 +32,30 @@ use crate::source::*;
 use crate::util::progress::ProgressBars;
 
 mod download;
+mod ftp;
+pub(in crate::commands) use download::hash_of_url;
+pub(in crate::commands) use download::http_client;
 
 /// Implementation of the "source" subcommand
-pub async fn source(
+pub async fn source<F>(
     matches: &ArgMatches,
     config: &Configuration,
-    repo: Repository,
+    load_repo: F,
     progressbars: ProgressBars,
-) -> Result<()> {
+) -> Result<()>
+where
+    F: FnOnce() -> Result<Repository>,
+{
     match matches.subcommand() {
-        Some(("verify", matches)) => verify(matches, config, repo, progressbars).await,
-        Some(("list-missing", matches)) => list_missing(matches, config, repo).await,
-        Some(("url", matches)) => url(matches, repo).await,
+        Some(("verify", matches)) => verify(matches, config, load_repo()?, progressbars).await,
+        Some(("list-missing", matches)) => list_missing(matches, config, load_repo()?).await,
+        Some(("url", matches)) => url(matches, load_repo()?).await,
         Some(("download", matches)) => {
-            crate::commands::source::download::download(matches, config, repo, progressbars).await
+            crate::commands::source::download::download(matches, config, load_repo()?, progressbars)
+                .await
         }
-        Some(("of", matches)) => of(matches, config, repo).await,
+        Some(("of", matches)) => of(matches, config, load_repo()?).await,
+        Some(("audit", matches)) => audit(matches, config, progressbars).await,
         Some((other, _)) => Err(anyhow!("Unknown subcommand: {}", other)),
         None => Err(anyhow!("No subcommand")),
     }
@@ -87,18 +96,27 @@ pub(in crate::commands) async fn verify_impl<'a, I>(
 where
     I: Iterator<Item = &'a Package> + 'a,
 {
+    // Sources are hashed completely, so the bar tracks the bytes that are verified to be able to
+    // show the throughput and the ETA
+    let started = std::time::Instant::now();
     let sources = packages
         .flat_map(|p| sc.sources_for(p).into_iter())
+        .map(|src| {
+            let size = src.path().metadata().map(|m| m.len()).unwrap_or(0);
+            (src, size)
+        })
         .collect::<Vec<_>>();
+    let sum_bytes = sources.iter().map(|(_, size)| size).sum::<u64>();
+    let number_of_sources = sources.len();
 
-    let bar = progressbars.bar()?;
+    let bar = progressbars.bytes_bar()?;
     bar.set_message("Verifying sources");
-    bar.set_length(sources.len() as u64);
+    bar.set_length(sum_bytes);
 
     let results = sources
         .into_iter()
-        .map(|src| (bar.clone(), src))
-        .map(|(bar, source)| async move {
+        .map(|(src, size)| (bar.clone(), src, size))
+        .map(|(bar, source, size)| async move {
             trace!("Verifying: {}", source.path().display());
             if source.path().exists() {
                 trace!("Exists: {}", source.path().display());
@@ -107,11 +125,11 @@ where
                 })?;
 
                 trace!("Success verifying: {}", source.path().display());
-                bar.inc(1);
+                bar.inc(size);
                 Ok(())
             } else {
                 trace!("Failed verifying: {}", source.path().display());
-                bar.inc(1);
+                bar.inc(size);
                 Err(anyhow!("Source missing: {}", source.path().display()))
             }
         })
@@ -121,11 +139,20 @@ where
 
     info!("Verification processes finished");
 
-    if results.iter().any(Result::is_err) {
+    let number_of_failures = results.iter().filter(|r| r.is_err()).count();
+    if number_of_failures > 0 {
         bar.finish_with_message("Source verification failed");
     } else {
         bar.finish_with_message("Source verification successful");
     }
+    progressbars.summary(
+        started,
+        format!(
+            "Verified {} of {number_of_sources} sources ({})",
+            number_of_sources - number_of_failures,
+            bytesize::ByteSize::b(sum_bytes)
+        ),
+    )?;
 
     let out = std::io::stdout();
     let mut any_error = false;
@@ -248,3 +275,127 @@ async fn of(matches: &ArgMatches, config: &Configuration, repo: Repository) -> R
         })
         .map(|_| ())
 }
+
+/// Audit the files in the source cache against the journal of the source cache
+///
+/// Only the journal is used, the repository is not required: every recorded file must still
+/// exist and have the size and hash it had when it was put into the source cache.
+async fn audit(
+    matches: &ArgMatches,
+    config: &Configuration,
+    progressbars: ProgressBars,
+) -> Result<()> {
+    let root = config.source_cache_root();
+    let records = SourceJournal::new(root).records().await?;
+
+    let started = std::time::Instant::now();
+    let sum_bytes = records.values().map(|record| record.size).sum::<u64>();
+    let bar = progressbars.bytes_bar()?;
+    bar.set_message("Auditing sources");
+    bar.set_length(sum_bytes);
+
+    let mut problems = records
+        .values()
+        .map(|record| {
+            let bar = bar.clone();
+            async move {
+                let result = audit_file(&root.join(&record.path), record).await;
+                bar.inc(record.size);
+                result.err().map(|e| (record, e))
+            }
+        })
+        .collect::<futures::stream::FuturesUnordered<_>>()
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    problems.sort_by(|a, b| a.0.path.cmp(&b.0.path));
+
+    if problems.is_empty() {
+        bar.finish_with_message("Source audit successful");
+    } else {
+        bar.finish_with_message("Source audit failed");
+    }
+    progressbars.summary(
+        started,
+        format!(
+            "Audited {} recorded sources ({}), {} failed",
+            records.len(),
+            bytesize::ByteSize::b(sum_bytes),
+            problems.len()
+        ),
+    )?;
+
+    let out = std::io::stdout();
+    let mut outlock = out.lock();
+    for (record, e) in problems.iter() {
+        writeln!(
+            outlock,
+            "{}: {}",
+            record.path.display(),
+            format!("{e:#}").red()
+        )?;
+        writeln!(
+            outlock,
+            "\tdownloaded {} from {}, {} with {} hash {}",
+            record.downloaded,
+            record.url,
+            bytesize::ByteSize::b(record.size),
+            record.hash.hashtype(),
+            record.hash.value()
+        )?;
+    }
+
+    if matches.get_flag("unrecorded") {
+        for entry in walkdir::WalkDir::new(root).min_depth(2).max_depth(2) {
+            let entry = entry.with_context(|| anyhow!("Listing {}", root.display()))?;
+            let path = entry.path().strip_prefix(root)?;
+            let is_source = path.extension().is_some_and(|ext| ext == "source");
+            if is_source && !records.contains_key(path) {
+                writeln!(
+                    outlock,
+                    "{}: {}",
+                    path.display(),
+                    "not recorded in the journal".yellow()
+                )?;
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{} of {} recorded sources were modified or removed since they were downloaded",
+            problems.len(),
+            records.len()
+        ))
+    }
+}
+
+/// Check that a file still has the size and the hash of its record in the journal
+async fn audit_file(path: &Path, record: &JournalRecord) -> Result<()> {
+    let metadata = match tokio::fs::metadata(path).await {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(anyhow!("Missing")),
+        Err(e) => return Err(e).with_context(|| anyhow!("Reading {}", path.display())),
+    };
+    if metadata.len() != record.size {
+        return Err(anyhow!(
+            "Modified: the size changed from {} to {} bytes",
+            record.size,
+            metadata.len()
+        ));
+    }
+
+    let reader = tokio::fs::File::open(path)
+        .await
+        .map(tokio::io::BufReader::new)
+        .with_context(|| anyhow!("Opening {}", path.display()))?;
+    record
+        .hash
+        .matches_hash_of(reader)
+        .await
+        .context("Modified")
+}
@@ -0,0 +1,409 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Implementation of the 'store' subcommand
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use clap::ArgMatches;
+use diesel::BelongingToDsl;
+use diesel::ExpressionMethods;
+use diesel::QueryDsl;
+use diesel::RunQueryDsl;
+use itertools::Itertools;
+use tracing::{debug, info, trace};
+
+use crate::config::Configuration;
+use crate::db::models::StoreSnapshot;
+use crate::db::models::StoreSnapshotArtifact;
+use crate::db::DbConnectionConfig;
+use crate::filestore::MergedStores;
+use crate::package::PackageVersionConstraint;
+use crate::repository::Repository;
+use crate::schema;
+use crate::util::docker::ImageNameLookup;
+use crate::util::progress::ProgressBars;
+
+/// Implementation of the "store" subcommand
+pub async fn store<F>(
+    db_connection_config: DbConnectionConfig<'_>,
+    config: &Configuration,
+    matches: &ArgMatches,
+    progressbars: ProgressBars,
+    load_repo: F,
+) -> Result<()>
+where
+    F: FnOnce() -> Result<Repository>,
+{
+    match matches.subcommand() {
+        Some(("stats", matches)) => stats(db_connection_config, config, matches),
+        Some(("snapshot", matches)) => {
+            snapshot(db_connection_config, config, matches, progressbars).await
+        }
+        Some(("find", matches)) => {
+            find(
+                db_connection_config,
+                config,
+                matches,
+                progressbars,
+                load_repo()?,
+            )
+            .await
+        }
+        Some((other, _matches)) => Err(anyhow!("Unknown subcommand: {}", other)),
+        None => Err(anyhow!("Missing subcommand")),
+    }
+}
+
+/// Implementation of the "store stats" subcommand
+///
+/// Shows how much space the released artifacts of each package use in the release stores.
+fn stats(
+    db_connection_config: DbConnectionConfig<'_>,
+    config: &Configuration,
+    matches: &ArgMatches,
+) -> Result<()> {
+    let csv = matches.get_flag("csv");
+    let store_name = matches.get_one::<String>("release_store_name");
+
+    if let Some(name) = store_name {
+        if !config.release_stores().contains(name) {
+            return Err(anyhow!("Unknown release store name: {}", name));
+        }
+    }
+
+    let mut conn = db_connection_config.establish_connection()?;
+    let mut query = schema::releases::table
+        .inner_join(schema::release_stores::table)
+        .inner_join(
+            schema::artifacts::table
+                .inner_join(schema::jobs::table.inner_join(schema::packages::table)),
+        )
+        .select((
+            schema::release_stores::store_name,
+            schema::packages::name,
+            schema::artifacts::path,
+            schema::releases::path,
+            schema::artifacts::size,
+        ))
+        .into_boxed();
+
+    if let Some(name) = store_name {
+        query = query.filter(schema::release_stores::store_name.eq(name));
+    }
+
+    // (store name, package name) -> (number of artifacts, accumulated size)
+    let mut usage: BTreeMap<(String, String), (usize, u64)> = BTreeMap::new();
+    for (store, package, path, release_path, size) in
+        query.load::<(String, String, String, Option<String>, Option<i64>)>(&mut conn)?
+    {
+        // Artifacts from before the size was recorded in the database are looked up on disk
+        let size = match size {
+            Some(size) => size as u64,
+            None => {
+                let full_path = config
+                    .releases_directory()
+                    .join(&store)
+                    .join(release_path.as_ref().unwrap_or(&path));
+                match std::fs::metadata(&full_path) {
+                    Ok(meta) => meta.len(),
+                    Err(e) => {
+                        debug!("Cannot get size of {}: {}", full_path.display(), e);
+                        0
+                    }
+                }
+            }
+        };
+
+        let entry = usage.entry((store, package)).or_default();
+        entry.0 += 1;
+        entry.1 += size;
+    }
+
+    if usage.is_empty() {
+        info!("No released artifacts in database");
+        return Ok(());
+    }
+
+    let store_totals = usage
+        .iter()
+        .map(|((store, _), (_, size))| (store.clone(), *size))
+        .into_grouping_map()
+        .sum();
+
+    let hdrs =
+        crate::commands::util::mk_header(vec!["Store", "Package", "Artifacts", "Size", "Share"]);
+    let data = usage
+        .into_iter()
+        .sorted_by(|(ka, (_, sa)), (kb, (_, sb))| ka.0.cmp(&kb.0).then(sa.cmp(sb)))
+        .map(|((store, package), (count, size))| {
+            let total = store_totals.get(&store).copied().unwrap_or(0);
+            let share = if total == 0 {
+                0.0
+            } else {
+                100.0 / total as f64 * size as f64
+            };
+
+            vec![
+                store,
+                package,
+                count.to_string(),
+                bytesize::ByteSize::b(size).to_string(),
+                format!("{share:.1}%"),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    crate::commands::util::display_data(hdrs, data, csv)
+}
+
+/// Implementation of the "store find" subcommand
+///
+/// Shows the artifacts that would be reused for the matching packages and every store that
+/// contains them, in the order in which the stores are searched when a build reuses artifacts.
+/// The artifact from the first store is used, the others are shadowed by it.
+async fn find(
+    db_connection_config: DbConnectionConfig<'_>,
+    config: &Configuration,
+    matches: &ArgMatches,
+    progressbars: ProgressBars,
+    repo: Repository,
+) -> Result<()> {
+    let csv = matches.get_flag("csv");
+    let package_name_regex = crate::commands::util::mk_package_name_regex({
+        matches.get_one::<String>("package_name_regex").unwrap() // safe by clap
+    })?;
+
+    let package_version_constraint = matches
+        .get_one::<String>("package_version_constraint")
+        .map(|s| s.to_owned())
+        .map(PackageVersionConstraint::try_from)
+        .transpose()
+        .context("Parsing package version constraint")?;
+
+    let env_filter = matches
+        .get_many::<String>("env_filter")
+        .map(|vals| {
+            vals.map(AsRef::as_ref)
+                .map(crate::util::env::parse_to_env)
+                .collect::<Result<Vec<_>>>()
+        })
+        .transpose()?
+        .unwrap_or_default();
+
+    let image_name_lookup = ImageNameLookup::create(config.docker().images())?;
+    let image_name = matches
+        .get_one::<String>("image")
+        .map(|s| image_name_lookup.expand(s))
+        .transpose()?;
+
+    let store_selection = crate::commands::util::store_selection(config, matches)?;
+    let release_stores =
+        crate::commands::util::load_release_stores(config, &progressbars, &store_selection)?;
+    let staging_dir = matches
+        .get_one::<String>("staging_dir")
+        .map(PathBuf::from)
+        .filter(|_| store_selection.staging());
+    let staging_store = if let Some(p) = staging_dir {
+        Some(crate::commands::util::load_staging_store(p, &progressbars).await?)
+    } else {
+        None
+    };
+    let merged_stores = MergedStores::new(staging_store.as_ref(), &release_stores);
+    let database_pool = db_connection_config.establish_pool()?;
+
+    let mut data = Vec::new();
+    let packages = repo
+        .packages()
+        .filter(|p| package_name_regex.captures(p.name()).is_some())
+        .filter(|p| {
+            package_version_constraint
+                .as_ref()
+                .map(|v| v.matches(p.version()))
+                .unwrap_or(true)
+        });
+
+    for pkg in packages {
+        trace!("Finding artifacts for {} {}", pkg.name(), pkg.version());
+        let artifacts = crate::db::FindArtifacts::builder()
+            .config(config)
+            .release_stores(&release_stores)
+            .staging_store(staging_store.as_ref())
+            .database_pool(database_pool.clone())
+            .env_filter(&env_filter)
+            .script_filter(!matches.get_flag("no_script_filter"))
+            .image_name(image_name.as_ref())
+            .package(pkg)
+            .build()
+            .run()?;
+
+        // An artifact can be found multiple times, keep the release date if there is one
+        let artifacts = artifacts
+            .into_iter()
+            .map(|(path, release_date)| (path.artifact_path().clone(), release_date))
+            .into_grouping_map()
+            .max();
+
+        for (artifact_path, release_date) in artifacts.into_iter().sorted() {
+            for (i, (origin, full_path)) in merged_stores
+                .provenance(&artifact_path)
+                .into_iter()
+                .enumerate()
+            {
+                let path = full_path.joined();
+                let metadata = tokio::fs::metadata(&path)
+                    .await
+                    .with_context(|| anyhow!("Getting metadata of {}", path.display()))?;
+                let sha256 = full_path.sha256().await?;
+                let modified = metadata
+                    .modified()
+                    .map(chrono::DateTime::<chrono::Local>::from)
+                    .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_else(|_| String::from("unknown"));
+
+                data.push(vec![
+                    pkg.name().to_string(),
+                    pkg.version().to_string(),
+                    origin.to_string(),
+                    if i == 0 { "used" } else { "shadowed" }.to_string(),
+                    release_date
+                        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+                        .unwrap_or_else(|| String::from("-")),
+                    sha256.to_string(),
+                    bytesize::ByteSize::b(metadata.len()).to_string(),
+                    modified,
+                    path.display().to_string(),
+                ]);
+            }
+        }
+    }
+
+    if data.is_empty() {
+        info!("No artifacts found in the stores");
+        return Ok(());
+    }
+
+    let hdrs = crate::commands::util::mk_header(vec![
+        "Package", "Version", "Store", "Status", "Released", "SHA256", "Size", "Modified", "Path",
+    ]);
+    crate::commands::util::display_data(hdrs, data, csv)
+}
+
+/// Implementation of the "store snapshot" subcommand
+async fn snapshot(
+    db_connection_config: DbConnectionConfig<'_>,
+    config: &Configuration,
+    matches: &ArgMatches,
+    progressbars: ProgressBars,
+) -> Result<()> {
+    match matches.subcommand() {
+        Some(("create", matches)) => {
+            snapshot_create(db_connection_config, config, matches, progressbars).await
+        }
+        Some(("list", matches)) => snapshot_list(db_connection_config, matches),
+        Some((other, _matches)) => Err(anyhow!("Unknown subcommand: {}", other)),
+        None => Err(anyhow!("Missing subcommand")),
+    }
+}
+
+/// Implementation of the "store snapshot create" subcommand
+///
+/// Records the paths and hashes of all artifacts in the release store.
+async fn snapshot_create(
+    db_connection_config: DbConnectionConfig<'_>,
+    config: &Configuration,
+    matches: &ArgMatches,
+    progressbars: ProgressBars,
+) -> Result<()> {
+    let snapshot_name = matches.get_one::<String>("snapshot_name").unwrap(); // safe by clap
+    let store_name = matches.get_one::<String>("release_store_name").unwrap(); // safe by clap
+    if !config.release_stores().contains(store_name) {
+        return Err(anyhow!("Unknown release store name: {}", store_name));
+    }
+
+    let mut conn = db_connection_config.establish_connection()?;
+    if StoreSnapshot::with_name(&mut conn, snapshot_name)?.is_some() {
+        return Err(anyhow!("Snapshot '{}' exists already", snapshot_name));
+    }
+
+    let store = crate::commands::util::load_release_store(config, &progressbars, store_name)?;
+
+    let bar = progressbars.bar()?;
+    bar.set_length(store.artifacts().count() as u64);
+    bar.set_message(format!("Hashing the artifacts in {store_name}"));
+    let mut artifacts = Vec::new();
+    for path in store.artifacts().sorted() {
+        let full_path = store.root_path().join(path)?.ok_or_else(|| {
+            anyhow!(
+                "Artifact vanished from the release store: {}",
+                path.display()
+            )
+        })?;
+        let sha256 = full_path.sha256().await?;
+        let path = path
+            .to_str()
+            .ok_or_else(|| anyhow!("Path is not valid UTF-8: {}", path.display()))?;
+        artifacts.push((path.to_string(), sha256.to_string()));
+        bar.inc(1);
+    }
+    bar.finish_with_message(format!("Hashed the artifacts in {store_name}"));
+
+    let release_store = crate::db::models::ReleaseStore::create(&mut conn, store_name)?;
+    let snapshot = StoreSnapshot::create(&mut conn, snapshot_name, &release_store, &artifacts)?;
+    info!(
+        "Created snapshot {} of release store {} with {} artifacts",
+        snapshot.name,
+        store_name,
+        artifacts.len()
+    );
+    Ok(())
+}
+
+/// Implementation of the "store snapshot list" subcommand
+fn snapshot_list(db_connection_config: DbConnectionConfig<'_>, matches: &ArgMatches) -> Result<()> {
+    let csv = matches.get_flag("csv");
+    let mut conn = db_connection_config.establish_connection()?;
+
+    let snapshots = schema::store_snapshots::table
+        .inner_join(schema::release_stores::table)
+        .order_by(schema::store_snapshots::created_at)
+        .select((
+            schema::store_snapshots::all_columns,
+            schema::release_stores::store_name,
+        ))
+        .load::<(StoreSnapshot, String)>(&mut conn)?;
+
+    if snapshots.is_empty() {
+        info!("No snapshots in database");
+        return Ok(());
+    }
+
+    let hdrs = crate::commands::util::mk_header(vec!["Name", "Store", "Created", "Artifacts"]);
+    let data = snapshots
+        .into_iter()
+        .map(|(snapshot, store_name)| {
+            let count = StoreSnapshotArtifact::belonging_to(&snapshot)
+                .count()
+                .get_result::<i64>(&mut conn)?;
+            Ok(vec![
+                snapshot.name,
+                store_name,
+                snapshot.created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                count.to_string(),
+            ])
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    crate::commands::util::display_data(hdrs, data, csv)
+}
@@ -10,26 +10,39 @@
 
 //! Utility module for subcommand implementation helpers
 
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::io::IsTerminal;
 use std::io::Write;
 use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
 
 use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Error;
 use anyhow::Result;
 use clap::ArgMatches;
+use diesel::PgConnection;
+use diesel::QueryDsl;
+use diesel::RunQueryDsl;
 use itertools::Itertools;
 use regex::Regex;
 use tokio_stream::StreamExt;
-use tracing::{error, info, trace};
+use tracing::{debug, error, info, trace};
 
 use crate::config::*;
+use crate::db::models::StoreSnapshot;
+use crate::filestore::path::ArtifactPath;
+use crate::filestore::path::StoreRoot;
+use crate::filestore::ReleaseStore;
+use crate::filestore::StagingStore;
+use crate::filestore::StoreSelection;
 use crate::package::Package;
 use crate::package::PhaseName;
 use crate::package::ScriptBuilder;
 use crate::package::Shebang;
+use crate::util::progress::ProgressBars;
 
 /// Helper for getting a boolean value by name form the argument object
 pub fn getbool(m: &ArgMatches, name: &str, cmp: &str) -> bool {
@@ -173,10 +186,36 @@ pub fn mk_header(vec: Vec<&str>) -> Vec<ascii_table::Column> {
         .collect()
 }
 
+/// How `display_data()` prints tables, set from the global command line arguments
+#[derive(Debug, Default)]
+pub struct TableOptions {
+    /// Only print these columns (`--columns`), all columns if empty
+    pub columns: Vec<String>,
+
+    /// Do not shorten UUIDs in the ascii tables (`--full-ids`)
+    pub full_ids: bool,
+}
+
+static TABLE_OPTIONS: std::sync::OnceLock<TableOptions> = std::sync::OnceLock::new();
+
+/// Set the options for all tables that are printed with `display_data()`
+pub fn set_table_options(options: TableOptions) {
+    // Only set once, from the command line arguments
+    let _ = TABLE_OPTIONS.set(options);
+}
+
+/// The number of characters of a UUID that are shown in tables (see `--full-ids`)
+const SHORT_ID_LENGTH: usize = 8;
+
 /// Display the passed data as nice ascii table,
 /// or, if stdout is a pipe, print it nicely parseable
 ///
 /// If `csv` is `true`, convert the data to CSV and print that instead.
+///
+/// Only the columns that were selected with `--columns` are printed. In the ascii table, UUIDs are
+/// shortened (unless `--full-ids` is passed) and the widest columns are truncated (with an
+/// ellipsis) so that the table fits into the terminal, the CSV and the pipe output always contain
+/// the full values.
 pub fn display_data<D: Display>(
     headers: Vec<ascii_table::Column>,
     data: Vec<Vec<D>>,
@@ -186,13 +225,14 @@ pub fn display_data<D: Display>(
         return Ok(());
     }
 
+    let options = TABLE_OPTIONS.get_or_init(TableOptions::default);
+    let (headers, data) = filter_columns(headers, data, &options.columns)?;
+
     if csv {
         use csv::WriterBuilder;
         let mut wtr = WriterBuilder::new().from_writer(vec![]);
         for record in data.into_iter() {
-            let r: Vec<String> = record.into_iter().map(|e| e.to_string()).collect();
-
-            wtr.write_record(&r)?;
+            wtr.write_record(&record)?;
         }
 
         let out = std::io::stdout();
@@ -203,12 +243,29 @@ pub fn display_data<D: Display>(
             .and_then(|t| String::from_utf8(t).map_err(Error::from))
             .and_then(|text| writeln!(lock, "{text}").map_err(Error::from))
     } else if std::io::stdout().is_terminal() {
+        let terminal_width = terminal_size::terminal_size()
+            .map(|tpl| tpl.0 .0 as usize) // an ugly interface indeed!
+            .unwrap_or(80);
         let mut ascii_table = ascii_table::AsciiTable::default();
-        ascii_table.set_max_width(
-            terminal_size::terminal_size()
-                .map(|tpl| tpl.0 .0 as usize) // an ugly interface indeed!
-                .unwrap_or(80),
-        );
+        ascii_table.set_max_width(terminal_width);
+
+        let data = if options.full_ids {
+            data
+        } else {
+            data.into_iter()
+                .map(|row| row.into_iter().map(shorten_uuid).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        };
+        let widths = fit_column_widths(&headers, &data, terminal_width);
+        let data = data
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .zip(widths.iter())
+                    .map(|(value, width)| truncate_visible(&value, *width))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
 
         headers.into_iter().enumerate().for_each(|(i, c)| {
             *ascii_table.column(i) = c;
@@ -220,12 +277,232 @@ pub fn display_data<D: Display>(
         let out = std::io::stdout();
         let mut lock = out.lock();
         for list in data {
-            writeln!(lock, "{}", list.iter().map(|d| d.to_string()).join(" "))?;
+            writeln!(lock, "{}", list.join(" "))?;
         }
         Ok(())
     }
 }
 
+/// Like `display_data()`, but the rows are printed while they are produced
+///
+/// The CSV and the pipe output are written row by row, so that the first rows are printed before
+/// the last ones are produced (e.g., while the logs of the remaining jobs are parsed). The ascii
+/// table needs all rows to size its columns, so the rows are collected first.
+pub fn display_data_stream<D, I>(
+    headers: Vec<ascii_table::Column>,
+    rows: I,
+    csv: bool,
+) -> Result<()>
+where
+    D: Display,
+    I: IntoIterator<Item = Result<Vec<D>>>,
+{
+    if !csv && std::io::stdout().is_terminal() {
+        let data = rows.into_iter().collect::<Result<Vec<_>>>()?;
+        return display_data(headers, data, csv);
+    }
+
+    let options = TABLE_OPTIONS.get_or_init(TableOptions::default);
+    let (_, indices) = select_columns(headers, &options.columns)?;
+    let rows = rows
+        .into_iter()
+        .map(|row| row.map(|row| filter_row(row, indices.as_deref())));
+
+    let out = std::io::stdout();
+    let mut lock = out.lock();
+    if csv {
+        let mut wtr = csv::WriterBuilder::new().from_writer(&mut lock);
+        let mut written = false;
+        for record in rows {
+            wtr.write_record(&record?)?;
+            wtr.flush()?;
+            written = true;
+        }
+        drop(wtr);
+        if written {
+            writeln!(lock)?;
+        }
+    } else {
+        for row in rows {
+            writeln!(lock, "{}", row?.join(" "))?;
+        }
+    }
+    Ok(())
+}
+
+/// Shorten a value that is a UUID to its first characters (which are accepted as short IDs)
+fn shorten_uuid(value: String) -> String {
+    if value.len() == 36 && uuid::Uuid::parse_str(&value).is_ok() {
+        value[..SHORT_ID_LENGTH].to_string()
+    } else {
+        value
+    }
+}
+
+/// Normalize a column name, so that e.g. "submit-uuid" selects the column "Submit UUID"
+fn normalize_column_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Keep only the `selected` columns (in the selected order), all columns if nothing is selected
+fn filter_columns<D: Display>(
+    headers: Vec<ascii_table::Column>,
+    data: Vec<Vec<D>>,
+    selected: &[String],
+) -> Result<(Vec<ascii_table::Column>, Vec<Vec<String>>)> {
+    let (headers, indices) = select_columns(headers, selected)?;
+    let data = data
+        .into_iter()
+        .map(|row| filter_row(row, indices.as_deref()))
+        .collect();
+    Ok((headers, data))
+}
+
+/// The `selected` headers and their indices, all headers (and no indices) if nothing is selected
+fn select_columns(
+    headers: Vec<ascii_table::Column>,
+    selected: &[String],
+) -> Result<(Vec<ascii_table::Column>, Option<Vec<usize>>)> {
+    if selected.is_empty() {
+        return Ok((headers, None));
+    }
+
+    let names = headers
+        .iter()
+        .map(|c| normalize_column_name(c.header()))
+        .collect::<Vec<_>>();
+    let indices = selected
+        .iter()
+        .map(|column| {
+            let name = normalize_column_name(column);
+            names.iter().position(|n| *n == name).ok_or_else(|| {
+                anyhow!(
+                    "Unknown column '{}', available columns: {}",
+                    column,
+                    headers.iter().map(|c| c.header().to_lowercase()).join(", ")
+                )
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let headers = indices.iter().map(|i| headers[*i].clone()).collect();
+    Ok((headers, Some(indices)))
+}
+
+/// Keep only the columns of the row at `indices` (see `select_columns()`)
+fn filter_row<D: Display>(row: Vec<D>, indices: Option<&[usize]>) -> Vec<String> {
+    let row = row.into_iter().map(|d| d.to_string());
+    match indices {
+        None => row.collect(),
+        Some(indices) => {
+            let row = row.collect::<Vec<_>>();
+            indices
+                .iter()
+                .map(|i| row.get(*i).cloned().unwrap_or_default())
+                .collect()
+        }
+    }
+}
+
+/// The maximum (visible) widths of the columns, so that the table fits into `terminal_width`
+///
+/// The widest column is shrunk until the table fits, but no column is shrunk below the width of
+/// its header (or 8 characters).
+fn fit_column_widths(
+    headers: &[ascii_table::Column],
+    data: &[Vec<String>],
+    terminal_width: usize,
+) -> Vec<usize> {
+    const MIN_COLUMN_WIDTH: usize = 8;
+
+    let mut widths = headers
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            data.iter()
+                .filter_map(|row| row.get(i))
+                .map(|v| visible_width(v))
+                .chain(std::iter::once(c.header().chars().count()))
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+    let minimum = headers
+        .iter()
+        .zip(widths.iter())
+        .map(|(c, w)| (*w).min(c.header().chars().count().max(MIN_COLUMN_WIDTH)))
+        .collect::<Vec<_>>();
+
+    // Each column is surrounded by a border and a space on each side
+    let available = terminal_width.saturating_sub(3 * widths.len() + 1);
+    while widths.iter().sum::<usize>() > available {
+        let Some((i, _)) = widths
+            .iter()
+            .enumerate()
+            .filter(|(i, w)| **w > minimum[*i])
+            .max_by_key(|(_, w)| **w)
+        else {
+            break; // nothing left to shrink
+        };
+        widths[i] -= 1;
+    }
+    widths
+}
+
+/// The number of visible characters of a string (without ANSI color codes)
+fn visible_width(s: &str) -> usize {
+    let mut width = 0;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip until the end of the escape sequence (e.g. "\x1b[33m")
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            width += 1;
+        }
+    }
+    width
+}
+
+/// Truncate a string to `width` visible characters, ending it with an ellipsis
+///
+/// ANSI color codes are kept (and reset after the ellipsis).
+fn truncate_visible(s: &str, width: usize) -> String {
+    if visible_width(s) <= width {
+        return s.to_string();
+    }
+
+    let mut truncated = String::new();
+    let mut visible = 0;
+    let mut colored = false;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            colored = true;
+            truncated.push(c);
+            for c in chars.by_ref() {
+                truncated.push(c);
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else if visible + 1 < width {
+            truncated.push(c);
+            visible += 1;
+        } else {
+            break;
+        }
+    }
+    truncated.push('\u{2026}');
+    if colored {
+        truncated.push_str("\x1b[0m");
+    }
+    truncated
+}
+
 pub fn get_date_filter(
     name: &str,
     matches: &ArgMatches,
@@ -261,3 +538,343 @@ pub fn get_date_filter(
         })
         .transpose()
 }
+
+/// The stores that are searched for artifacts to reuse
+///
+/// These are the stores passed with `--use-store`, the stores of the `artifact_stores` setting
+/// or all stores.
+pub fn store_selection(config: &Configuration, matches: &ArgMatches) -> Result<StoreSelection> {
+    if let Some(names) = matches.get_many::<String>("use_store") {
+        let names = names.cloned().collect::<Vec<_>>();
+        StoreSelection::new(&names, config.release_stores())
+    } else if let Some(names) = config.artifact_stores() {
+        StoreSelection::new(names, config.release_stores())
+    } else {
+        Ok(StoreSelection::all(config.release_stores()))
+    }
+}
+
+/// Load the selected release stores, in the order in which they are searched
+pub fn load_release_stores(
+    config: &Configuration,
+    progressbars: &ProgressBars,
+    selection: &StoreSelection,
+) -> Result<Vec<Arc<ReleaseStore>>> {
+    selection
+        .release_stores()
+        .iter()
+        .map(|storename| load_release_store(config, progressbars, storename).map(Arc::new))
+        .collect()
+}
+
+/// Load the release store with the passed name
+pub fn load_release_store(
+    config: &Configuration,
+    progressbars: &ProgressBars,
+    storename: &str,
+) -> Result<ReleaseStore> {
+    let bar_release_loading = progressbars.bar()?;
+
+    let p = config.releases_directory().join(storename);
+    let p_str = p.to_string_lossy();
+    debug!("Loading release directory: {}", p_str);
+    let r = ReleaseStore::load(StoreRoot::new(p.clone())?, &bar_release_loading);
+    if r.is_ok() {
+        bar_release_loading.finish_with_message(format!("Loaded releases in {p_str} successfully"));
+    } else {
+        bar_release_loading.finish_with_message(format!("Failed to load releases in {p_str}"));
+    }
+    r
+}
+
+/// Load the release store of a snapshot, which only contains the artifacts of the snapshot
+///
+/// Fails if an artifact of the snapshot was removed from the release store or was changed.
+pub async fn load_release_store_snapshot(
+    config: &Configuration,
+    progressbars: &ProgressBars,
+    database_connection: &mut PgConnection,
+    snapshot_name: &str,
+) -> Result<Arc<ReleaseStore>> {
+    let snapshot = StoreSnapshot::with_name(database_connection, snapshot_name)?
+        .ok_or_else(|| anyhow!("Unknown snapshot: {}", snapshot_name))?;
+    let store_name = crate::schema::release_stores::table
+        .find(snapshot.release_store_id)
+        .select(crate::schema::release_stores::store_name)
+        .first::<String>(database_connection)?;
+    if !config.release_stores().contains(&store_name) {
+        return Err(anyhow!(
+            "The release store '{}' of snapshot '{}' is not configured",
+            store_name,
+            snapshot_name
+        ));
+    }
+
+    let artifacts = snapshot
+        .artifacts(database_connection)?
+        .into_iter()
+        .map(|artifact| {
+            Ok((
+                ArtifactPath::new(PathBuf::from(artifact.path))?,
+                artifact.sha256,
+            ))
+        })
+        .collect::<Result<HashMap<_, _>>>()?;
+
+    let mut store = load_release_store(config, progressbars, &store_name)?;
+
+    let bar = progressbars.bar()?;
+    bar.set_length(artifacts.len() as u64);
+    bar.set_message(format!(
+        "Verifying the artifacts of snapshot {snapshot_name}"
+    ));
+    for (path, sha256) in artifacts.iter() {
+        let full_path = store.root_path().join(path)?.ok_or_else(|| {
+            anyhow!(
+                "Artifact {} of snapshot '{}' was removed from the release store '{}'",
+                path.display(),
+                snapshot_name,
+                store_name
+            )
+        })?;
+        if full_path.sha256().await?.to_string() != *sha256 {
+            return Err(anyhow!(
+                "Artifact {} of snapshot '{}' was changed in the release store '{}'",
+                path.display(),
+                snapshot_name,
+                store_name
+            ));
+        }
+        bar.inc(1);
+    }
+    bar.finish_with_message(format!(
+        "Verified {} artifacts of snapshot {snapshot_name}",
+        artifacts.len()
+    ));
+
+    store.retain(|path| artifacts.contains_key(path));
+    Ok(Arc::new(store))
+}
+
+/// Load the staging store in the passed directory, which is created if it does not exist
+pub async fn load_staging_store(p: PathBuf, progressbars: &ProgressBars) -> Result<StagingStore> {
+    let bar_staging_loading = progressbars.bar()?;
+
+    if !p.is_dir() {
+        tokio::fs::create_dir_all(&p).await?;
+    }
+
+    debug!("Loading staging directory: {}", p.display());
+    let r = StagingStore::load(StoreRoot::new(p)?, &bar_staging_loading);
+    if r.is_ok() {
+        bar_staging_loading.finish_with_message("Loaded staging successfully");
+    } else {
+        bar_staging_loading.finish_with_message("Failed to load staging");
+    }
+    r
+}
+
+/// Reconstruct the arguments that were passed to a command on the command line (without the
+/// arguments of its subcommands)
+///
+/// Default values are not part of the returned arguments, and neither are the arguments in
+/// `skip`. Positional arguments come first, options are returned as `--name=value`. The values of
+/// arguments that are paths (see `clap::ValueHint`) are made absolute, so that the arguments can
+/// be used in another working directory.
+pub fn command_line_arguments(
+    command: &clap::Command,
+    matches: &ArgMatches,
+    skip: &[&str],
+) -> Result<Vec<String>> {
+    use clap::parser::ValueSource;
+
+    let raw_values = |arg: &clap::Arg| -> Result<Vec<String>> {
+        let is_path = matches!(
+            arg.get_value_hint(),
+            clap::ValueHint::AnyPath | clap::ValueHint::FilePath | clap::ValueHint::DirPath
+        );
+        matches
+            .get_raw(arg.get_id().as_str())
+            .into_iter()
+            .flatten()
+            .map(|raw| {
+                let raw = if is_path {
+                    std::path::absolute(raw)
+                        .with_context(|| anyhow!("Making {:?} absolute", raw))?
+                        .into_os_string()
+                } else {
+                    raw.to_os_string()
+                };
+                raw.into_string()
+                    .map_err(|raw| anyhow!("Argument is not valid UTF-8: {:?}", raw))
+            })
+            .collect()
+    };
+
+    let (positionals, options): (Vec<_>, Vec<_>) = command
+        .get_arguments()
+        .filter(|arg| !skip.contains(&arg.get_id().as_str()))
+        .filter(|arg| matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine))
+        .partition(|arg| arg.is_positional());
+
+    let mut arguments = Vec::new();
+    for arg in positionals.into_iter().sorted_by_key(|arg| arg.get_index()) {
+        arguments.extend(raw_values(arg)?);
+    }
+
+    for arg in options {
+        let id = arg.get_id().as_str();
+        let long = arg
+            .get_long()
+            .ok_or_else(|| anyhow!("Argument without long name: {}", id))?;
+
+        match arg.get_action() {
+            clap::ArgAction::SetTrue => arguments.push(format!("--{long}")),
+            clap::ArgAction::Count => {
+                (0..matches.get_count(id)).for_each(|_| arguments.push(format!("--{long}")))
+            }
+            _ => arguments.extend(
+                raw_values(arg)?
+                    .into_iter()
+                    .map(|value| format!("--{long}={value}")),
+            ),
+        }
+    }
+
+    Ok(arguments)
+}
+
+/// Render the values as sparkline, one block character per value
+///
+/// The values are scaled between the smallest and the biggest value, `None` values are rendered
+/// as space.
+pub fn sparkline(values: &[Option<u64>]) -> String {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    let min = values.iter().flatten().min().copied().unwrap_or(0);
+    let max = values.iter().flatten().max().copied().unwrap_or(0);
+    values
+        .iter()
+        .map(|value| match value {
+            None => ' ',
+            Some(_) if max == min => BLOCKS[BLOCKS.len() / 2],
+            Some(v) => {
+                let index = (v - min) as f64 * (BLOCKS.len() - 1) as f64 / (max - min) as f64;
+                BLOCKS[index.round() as usize]
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Arg;
+    use clap::ArgAction;
+    use clap::Command;
+
+    use super::*;
+
+    #[test]
+    fn test_command_line_arguments() -> Result<()> {
+        let command = Command::new("build")
+            .arg(Arg::new("name").index(1))
+            .arg(Arg::new("env").long("env").action(ArgAction::Append))
+            .arg(
+                Arg::new("no_lint")
+                    .long("no-lint")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("shebang")
+                    .long("shebang")
+                    .default_value("#!/bin/sh"),
+            )
+            .arg(
+                Arg::new("enqueue")
+                    .long("enqueue")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("summary_out")
+                    .long("summary-out")
+                    .value_hint(clap::ValueHint::FilePath),
+            );
+
+        let matches = command.clone().try_get_matches_from([
+            "build",
+            "--env",
+            "A=1",
+            "pkg",
+            "--no-lint",
+            "--env=B=2",
+            "--enqueue",
+            "--summary-out=summary.json",
+        ])?;
+
+        let summary = std::env::current_dir()?.join("summary.json");
+        assert_eq!(
+            command_line_arguments(&command, &matches, &["enqueue"])?,
+            [
+                String::from("pkg"),
+                String::from("--env=A=1"),
+                String::from("--env=B=2"),
+                String::from("--no-lint"),
+                format!("--summary-out={}", summary.display()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_columns() -> Result<()> {
+        let headers = mk_header(vec!["Submit UUID", "Package", "Status"]);
+        let data = vec![vec!["1234", "foo", "ok"], vec!["5678", "bar", "failed"]];
+        let selected = [String::from("status"), String::from("submit-uuid")];
+
+        let (headers, data) = filter_columns(headers, data, &selected)?;
+        assert_eq!(
+            headers.iter().map(|c| c.header()).collect::<Vec<_>>(),
+            ["Status", "Submit UUID"]
+        );
+        assert_eq!(data, [["ok", "1234"], ["failed", "5678"]]);
+
+        let headers = mk_header(vec!["Package"]);
+        assert!(filter_columns(headers, vec![vec!["foo"]], &[String::from("version")]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_shorten_uuid() {
+        let uuid = String::from("1a2b3c4d-5e6f-4a7b-8c9d-0e1f2a3b4c5d");
+        assert_eq!(shorten_uuid(uuid), "1a2b3c4d");
+        assert_eq!(shorten_uuid(String::from("foo")), "foo");
+    }
+
+    #[test]
+    fn test_truncate_visible() {
+        assert_eq!(truncate_visible("short", 8), "short");
+        assert_eq!(truncate_visible("a-long-value", 8), "a-long-\u{2026}");
+
+        let colored = "\x1b[33moutdated\x1b[0m";
+        assert_eq!(visible_width(colored), 8);
+        assert_eq!(truncate_visible(colored, 4), "\x1b[33mout\u{2026}\x1b[0m");
+    }
+
+    #[test]
+    fn test_fit_column_widths() {
+        let headers = mk_header(vec!["Name", "Description"]);
+        let data = vec![vec!["x".repeat(10), "y".repeat(100)]];
+
+        // 2 columns: 7 characters for the borders
+        assert_eq!(fit_column_widths(&headers, &data, 200), [10, 100]);
+        assert_eq!(fit_column_widths(&headers, &data, 47), [10, 30]);
+        assert_eq!(fit_column_widths(&headers, &data, 10), [8, 11]);
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(sparkline(&[]), "");
+        assert_eq!(sparkline(&[Some(1), Some(8), None, Some(4)]), "▁█ ▄");
+        assert_eq!(sparkline(&[Some(5), Some(5)]), "▅▅");
+    }
+}
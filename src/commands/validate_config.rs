@@ -0,0 +1,273 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Implementation of the 'validate-config' subcommand
+
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use clap::ArgMatches;
+use colored::Colorize;
+
+use crate::config::check_compatibility;
+use crate::config::NotValidatedConfiguration;
+use crate::util::error::ErrorCategory;
+
+/// A problem that was found in the configuration
+struct Problem {
+    /// Where the problem is, if known (file, line and column)
+    location: Option<String>,
+    message: String,
+}
+
+/// Implementation of the "validate-config" subcommand
+pub async fn validate_config(
+    matches: &ArgMatches,
+    config_files: &[PathBuf],
+    profile: Option<&str>,
+) -> Result<()> {
+    let mut problems = config_files
+        .iter()
+        .filter_map(|file| check_file(file))
+        .collect::<Vec<_>>();
+
+    // Checking the merged configuration is only helpful if the individual files can be parsed,
+    // because the errors would only be repeated without location otherwise
+    if problems.is_empty() {
+        problems.extend(check_merged(matches, config_files, profile).await);
+    }
+
+    let out = std::io::stdout();
+    let mut outlock = out.lock();
+    for file in config_files.iter().filter(|file| file.exists()) {
+        writeln!(outlock, "Checked {}", file.display())?;
+    }
+
+    if problems.is_empty() {
+        writeln!(outlock, "{}", "The configuration is valid".green())?;
+        return Ok(());
+    }
+
+    for problem in problems.iter() {
+        match problem.location.as_ref() {
+            Some(location) => writeln!(
+                outlock,
+                "{}: {}: {}",
+                "error".red(),
+                location.bold(),
+                problem.message
+            )?,
+            None => writeln!(outlock, "{}: {}", "error".red(), problem.message)?,
+        }
+    }
+
+    Err(anyhow!(
+        "Found {} problem(s) in the configuration",
+        problems.len()
+    ))
+    .context(ErrorCategory::Config)
+}
+
+/// Check the syntax of one configuration file and check it against the configuration schema
+fn check_file(file: &Path) -> Option<Problem> {
+    let content = match std::fs::read_to_string(file) {
+        Ok(content) => content,
+        Err(e) => {
+            return Some(Problem {
+                location: Some(file.display().to_string()),
+                message: format!("Cannot read file: {e}"),
+            })
+        }
+    };
+
+    let location = |e: &toml::de::Error| {
+        let (line, column) = e
+            .span()
+            .map(|span| line_and_column(&content, span.start))
+            .unwrap_or((1, 1));
+        Some(format!("{}:{line}:{column}", file.display()))
+    };
+
+    if let Err(e) = toml::from_str::<toml::Table>(&content) {
+        return Some(Problem {
+            location: location(&e),
+            message: e.message().to_string(),
+        });
+    }
+
+    schema_error(&content).map(|e| Problem {
+        location: location(&e),
+        message: e.message().to_string(),
+    })
+}
+
+/// Check the content of one configuration file against the configuration schema
+///
+/// The configuration files are merged, so required fields might be set in another file.
+/// Missing fields are reported when checking the merged configuration instead.
+pub(super) fn schema_error(content: &str) -> Option<toml::de::Error> {
+    match toml::from_str::<NotValidatedConfiguration>(content) {
+        Err(e) if !e.message().starts_with("missing field") => Some(e),
+        _ => None,
+    }
+}
+
+/// Check the merged configuration (files and environment variables)
+async fn check_merged(
+    matches: &ArgMatches,
+    config_files: &[PathBuf],
+    profile: Option<&str>,
+) -> Vec<Problem> {
+    let without_location = |e: anyhow::Error| Problem {
+        location: None,
+        message: e
+            .chain()
+            .map(|cause| cause.to_string())
+            .collect::<Vec<_>>()
+            .join(": "),
+    };
+
+    let config = match crate::config::load_config(config_files, profile)
+        .and_then(|config| check_compatibility(&config).map(|_| config))
+    {
+        Ok(config) => config,
+        Err(e) => return vec![without_location(e)],
+    };
+
+    let config = match config.try_deserialize::<NotValidatedConfiguration>() {
+        Ok(config) => config,
+        Err(e) => return vec![without_location(e.into())],
+    };
+
+    let mut problems = Vec::new();
+    let mut problem = |key: &str, message: String| {
+        problems.push(Problem {
+            location: locate_key(config_files, key),
+            message,
+        })
+    };
+
+    for (key, path) in [
+        ("log_dir", config.log_dir()),
+        ("releases_root", config.releases_directory()),
+        ("staging", config.staging_directory()),
+        ("source_cache", config.source_cache_root()),
+    ] {
+        if !path.is_dir() {
+            problem(key, format!("Not a directory: {key} = {}", path.display()));
+        }
+    }
+
+    if config.releases_directory().is_dir() {
+        for store in config.release_stores() {
+            let path = config.releases_directory().join(store);
+            if !path.is_dir() {
+                problem(
+                    "release_stores",
+                    format!("Release store directory missing: {}", path.display()),
+                );
+            }
+        }
+    }
+
+    for image in config.docker().images() {
+        for name in [&image.name, &image.short_name] {
+            if !name.is_valid_reference() {
+                problem("images", format!("Not a valid image name: \"{name}\""));
+            }
+        }
+    }
+
+    if let Err(e) = crate::util::docker::ImageNameLookup::create(config.docker().images()) {
+        problem("images", e.to_string());
+    }
+
+    // The remaining (semantic) checks, without the directory checks that were already done above
+    let config = match config.validate_config(true) {
+        Ok(config) => config,
+        Err(e) => {
+            problems.push(without_location(e));
+            return problems;
+        }
+    };
+
+    if matches.get_flag("connect") {
+        for name in config.docker().endpoints().keys() {
+            let connected =
+                super::endpoint::connect_to_endpoints(&config, std::slice::from_ref(name)).await;
+            if let Err(e) = connected {
+                problems.push(Problem {
+                    location: locate_key(config_files, name.as_ref()),
+                    message: format!(
+                        "Endpoint {name} is not usable: {}",
+                        e.chain()
+                            .map(|cause| cause.to_string())
+                            .collect::<Vec<_>>()
+                            .join(": ")
+                    ),
+                });
+            }
+        }
+    }
+
+    problems
+}
+
+/// Find the location where a key is set, searching the files with the highest precedence first
+///
+/// This only looks at the last segment of the key and is therefore a best-effort search.
+fn locate_key(config_files: &[PathBuf], key: &str) -> Option<String> {
+    config_files.iter().rev().find_map(|file| {
+        let content = std::fs::read_to_string(file).ok()?;
+        content.lines().enumerate().find_map(|(idx, line)| {
+            let trimmed = line.trim_start();
+            let rest = trimmed
+                .strip_prefix(key)
+                .or_else(|| trimmed.strip_prefix(&format!("\"{key}\"")))
+                .or_else(|| trimmed.strip_prefix(&format!("[docker.endpoints.{key}]")))?;
+            let rest = rest.trim_start();
+            if rest.is_empty() || rest.starts_with('=') {
+                let column = line.len() - trimmed.len() + 1;
+                Some(format!("{}:{}:{column}", file.display(), idx + 1))
+            } else {
+                None
+            }
+        })
+    })
+}
+
+/// Convert a byte offset into a (1-based) line and column
+fn line_and_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rfind('\n')
+        .map(|nl| before.len() - nl)
+        .unwrap_or(before.len() + 1);
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::line_and_column;
+
+    #[test]
+    fn test_line_and_column() {
+        let content = "a = 1\nbb = 2\n";
+        assert_eq!(line_and_column(content, 0), (1, 1));
+        assert_eq!(line_and_column(content, 4), (1, 5));
+        assert_eq!(line_and_column(content, 6), (2, 1));
+        assert_eq!(line_and_column(content, 9), (2, 4));
+    }
+}
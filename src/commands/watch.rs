@@ -0,0 +1,215 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Implementation of the 'watch' subcommand
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use clap::ArgMatches;
+use colored::Colorize;
+use tracing::{debug, warn};
+
+/// The modification time and size of a file, to detect changes
+type FileState = (SystemTime, u64);
+
+/// Implementation of the "watch" subcommand
+pub async fn watch(matches: &ArgMatches, repo_path: &Path) -> Result<()> {
+    let interval = *matches.get_one::<Duration>("interval").unwrap(); // safe by clap default
+    let debounce = *matches.get_one::<Duration>("debounce").unwrap(); // safe by clap default
+    let command = matches
+        .get_many::<String>("command")
+        .unwrap() // safe by clap
+        .cloned()
+        .collect::<Vec<_>>();
+    let paths = match matches.get_many::<PathBuf>("path") {
+        Some(paths) => paths.map(|p| repo_path.join(p)).collect::<Vec<_>>(),
+        None => vec![repo_path.to_path_buf()],
+    };
+    for path in paths.iter() {
+        if !path.exists() {
+            return Err(anyhow!("Path does not exist: {}", path.display()));
+        }
+    }
+
+    let butido = std::env::current_exe().context("Finding the butido executable")?;
+    let mut out = std::io::stdout();
+    writeln!(
+        out,
+        "Watching {} for changes, running \"butido {}\" on changes (Ctrl-C to stop)",
+        paths
+            .iter()
+            .map(|p| p.strip_prefix(repo_path).unwrap_or(p).display().to_string())
+            .map(|p| if p.is_empty() { String::from(".") } else { p })
+            .collect::<Vec<_>>()
+            .join(", "),
+        command.join(" ")
+    )?;
+
+    let mut known = snapshot(&paths)?;
+    loop {
+        tokio::time::sleep(interval).await;
+        let current = snapshot(&paths)?;
+        if current == known {
+            continue;
+        }
+
+        // Wait until the files did not change for the debounce time, so that e.g. saving several
+        // files or a "git checkout" triggers only one rebuild
+        let mut settled = current;
+        loop {
+            tokio::time::sleep(debounce).await;
+            let next = snapshot(&paths)?;
+            if next == settled {
+                break;
+            }
+            settled = next;
+        }
+
+        let changes = Changes::between(&known, &settled);
+        known = settled;
+        if changes.is_empty() {
+            continue; // changed and changed back
+        }
+
+        writeln!(out, "{}", changes.summary(repo_path).yellow())?;
+        debug!("Running {} {:?}", butido.display(), command);
+        let status = tokio::process::Command::new(&butido)
+            .args(&command)
+            .current_dir(repo_path)
+            .status()
+            .await
+            .with_context(|| anyhow!("Running {}", butido.display()))?;
+
+        if status.success() {
+            writeln!(out, "{}", "Rebuild finished successfully".green())?;
+        } else {
+            warn!("The rebuild failed: {}", status);
+            writeln!(out, "{}", format!("Rebuild failed ({status})").red())?;
+        }
+        writeln!(out, "Waiting for changes...")?;
+    }
+}
+
+/// The state of all files below `paths` (hidden files and directories, e.g. ".git", are skipped)
+fn snapshot(paths: &[PathBuf]) -> Result<BTreeMap<PathBuf, FileState>> {
+    let mut files = BTreeMap::new();
+    for path in paths {
+        let walker = walkdir::WalkDir::new(path)
+            .follow_links(true)
+            .into_iter()
+            .filter_entry(|entry| {
+                entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.')
+            });
+        for entry in walker {
+            let entry = match entry {
+                Ok(entry) => entry,
+                // Files can be removed while walking (e.g. temporary files of editors)
+                Err(e) if e.io_error().map(|e| e.kind()) == Some(std::io::ErrorKind::NotFound) => {
+                    continue
+                }
+                Err(e) => return Err(e).with_context(|| anyhow!("Walking {}", path.display())),
+            };
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue; // removed in the meantime
+            };
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            files.insert(entry.into_path(), (modified, metadata.len()));
+        }
+    }
+    Ok(files)
+}
+
+/// The files that changed between two snapshots
+#[derive(Debug, Default, PartialEq)]
+struct Changes {
+    added: Vec<PathBuf>,
+    modified: Vec<PathBuf>,
+    removed: Vec<PathBuf>,
+}
+
+impl Changes {
+    fn between(old: &BTreeMap<PathBuf, FileState>, new: &BTreeMap<PathBuf, FileState>) -> Self {
+        let mut changes = Changes::default();
+        for (path, state) in new {
+            match old.get(path) {
+                None => changes.added.push(path.clone()),
+                Some(old_state) if old_state != state => changes.modified.push(path.clone()),
+                Some(_) => {}
+            }
+        }
+        changes.removed = old
+            .keys()
+            .filter(|path| !new.contains_key(*path))
+            .cloned()
+            .collect();
+        changes
+    }
+
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.modified.is_empty() && self.removed.is_empty()
+    }
+
+    /// A summary like "Changed: foo/pkg.toml (modified), foo/fix.patch (added)"
+    fn summary(&self, repo_path: &Path) -> String {
+        let list = |paths: &[PathBuf], what: &str| {
+            paths
+                .iter()
+                .map(|p| {
+                    format!(
+                        "{} ({what})",
+                        p.strip_prefix(repo_path).unwrap_or(p).display()
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut changed = list(&self.modified, "modified");
+        changed.extend(list(&self.added, "added"));
+        changed.extend(list(&self.removed, "removed"));
+        format!("Changed: {}", changed.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changes_between_snapshots() {
+        let t = |secs| (SystemTime::UNIX_EPOCH + Duration::from_secs(secs), 10);
+        let old = BTreeMap::from([
+            (PathBuf::from("/repo/foo/pkg.toml"), t(1)),
+            (PathBuf::from("/repo/foo/old.patch"), t(1)),
+            (PathBuf::from("/repo/pkg.toml"), t(1)),
+        ]);
+        let new = BTreeMap::from([
+            (PathBuf::from("/repo/foo/pkg.toml"), t(2)),
+            (PathBuf::from("/repo/foo/new.patch"), t(2)),
+            (PathBuf::from("/repo/pkg.toml"), t(1)),
+        ]);
+
+        let changes = Changes::between(&old, &new);
+        assert_eq!(
+            changes.summary(Path::new("/repo")),
+            "Changed: foo/pkg.toml (modified), foo/new.patch (added), foo/old.patch (removed)"
+        );
+        assert!(Changes::between(&new, &new).is_empty());
+    }
+}
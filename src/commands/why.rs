@@ -0,0 +1,202 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Implementation of the 'why' subcommand
+
+use std::io::Write;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use clap::ArgMatches;
+use colored::Colorize;
+use itertools::Itertools;
+use petgraph::graph::NodeIndex;
+
+use crate::config::Configuration;
+use crate::package::condition::ConditionCheckable;
+use crate::package::condition::ConditionData;
+use crate::package::Dag;
+use crate::package::DependencyType;
+use crate::package::Package;
+use crate::package::PackageName;
+use crate::package::PackageVersionConstraint;
+use crate::package::ParseDependency;
+use crate::repository::Repository;
+use crate::util::docker::ImageNameLookup;
+use crate::util::EnvironmentVariableName;
+
+/// Implementation of the "why" subcommand
+pub async fn why(matches: &ArgMatches, repo: Repository, config: &Configuration) -> Result<()> {
+    let pname = matches
+        .get_one::<String>("package_name")
+        .map(|s| PackageName::from(s.to_owned()))
+        .unwrap(); // safe by clap
+    let root_name = matches
+        .get_one::<String>("in_tree_of")
+        .map(|s| PackageName::from(s.to_owned()))
+        .unwrap(); // safe by clap
+    let root_version = matches
+        .get_one::<String>("root_version_constraint")
+        .map(|s| s.to_owned())
+        .map(PackageVersionConstraint::try_from)
+        .transpose()?;
+    let limit = matches
+        .get_one::<usize>("limit")
+        .copied()
+        .filter(|limit| *limit > 0);
+
+    let image_name_lookup = ImageNameLookup::create(config.docker().images())?;
+    let image_name = matches
+        .get_one::<String>("image")
+        .map(|s| image_name_lookup.expand(s))
+        .transpose()?;
+
+    let additional_env = matches
+        .get_many::<String>("env")
+        .unwrap_or_default()
+        .map(AsRef::as_ref)
+        .map(crate::util::env::parse_to_env)
+        .collect::<Result<Vec<(EnvironmentVariableName, String)>>>()?;
+
+    let condition_data = ConditionData {
+        image_name: image_name.as_ref(),
+        env: &additional_env,
+    };
+
+    let roots = repo
+        .packages()
+        .filter(|p| *p.name() == root_name)
+        .filter(|p| {
+            root_version
+                .as_ref()
+                .map(|v| v.matches(p.version()))
+                .unwrap_or(true)
+        })
+        .collect::<Vec<_>>();
+    if roots.is_empty() {
+        return Err(anyhow!("No package found: {}", root_name));
+    }
+
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    for root in roots {
+        let dag = Dag::for_root_package(root.clone(), &repo, None, &condition_data)?;
+        let mut chains = dag.chains_to(&pname);
+        let mut printed = 0;
+
+        for chain in chains.by_ref().take(limit.unwrap_or(usize::MAX)) {
+            printed += 1;
+            write_chain(&mut out, &dag, &chain, &condition_data)?;
+        }
+
+        if printed == 0 {
+            writeln!(
+                out,
+                "{} is not in the tree of {}",
+                pname,
+                root.display_name_version()
+            )?;
+        } else if chains.next().is_some() {
+            writeln!(
+                out,
+                "{}",
+                format!("Only the first {printed} chains are shown, use --limit 0 to show all")
+                    .yellow()
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Write one chain of dependencies, every hop with the dependency that selected the package
+fn write_chain<W: Write>(
+    out: &mut W,
+    dag: &Dag,
+    chain: &[NodeIndex],
+    condition_data: &ConditionData<'_>,
+) -> Result<()> {
+    let graph = dag.dag();
+    let hops = chain
+        .iter()
+        .tuple_windows()
+        .map(|(parent, child)| {
+            let kinds = graph
+                .edges_connecting(*parent, *child)
+                .map(|edge| edge.weight().clone())
+                .sorted_by_key(|kind| *kind == DependencyType::Build)
+                .collect::<Vec<_>>();
+            (&graph[*parent], &graph[*child], kinds)
+        })
+        .collect::<Vec<_>>();
+
+    // The package is only needed at runtime of the root package if every hop is a runtime
+    // dependency
+    let runtime = hops
+        .iter()
+        .all(|(_, _, kinds)| kinds.contains(&DependencyType::Runtime));
+    writeln!(
+        out,
+        "{} ({})",
+        graph[chain[0]].display_name_version().bold(),
+        if runtime {
+            "runtime dependency"
+        } else {
+            "build dependency"
+        }
+    )?;
+
+    for (depth, (parent, child, kinds)) in hops.into_iter().enumerate() {
+        let indent = "  ".repeat(depth + 1);
+        for kind in kinds {
+            let (kind_name, spec) = match kind {
+                DependencyType::Build => (
+                    "build",
+                    dependency_spec(parent.dependencies().build(), child, condition_data)?,
+                ),
+                DependencyType::Runtime => (
+                    "runtime",
+                    dependency_spec(parent.dependencies().runtime(), child, condition_data)?,
+                ),
+            };
+            writeln!(
+                out,
+                "{indent}{} {} {} (\"{}\" in {})",
+                "->".dimmed(),
+                child.display_name_version().cyan(),
+                kind_name,
+                spec.unwrap_or("?"),
+                parent.display_name_version()
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Find the dependency that selected `child`, i.e., the one with a matching name and version
+/// whose condition matches
+fn dependency_spec<'a, D>(
+    dependencies: &'a [D],
+    child: &Package,
+    condition_data: &ConditionData<'_>,
+) -> Result<Option<&'a str>>
+where
+    D: AsRef<str> + ConditionCheckable + ParseDependency,
+{
+    for dependency in dependencies {
+        if !dependency.check_condition(condition_data)? {
+            continue;
+        }
+        let (name, version) = dependency.parse_as_name_and_version()?;
+        if name == *child.name() && version == *child.version() {
+            return Ok(Some(dependency.as_ref()));
+        }
+    }
+    Ok(None)
+}
@@ -8,10 +8,13 @@
 // SPDX-License-Identifier: EPL-2.0
 //
 
+use std::path::PathBuf;
+
 use getset::CopyGetters;
 use getset::Getters;
 use serde::Deserialize;
 
+use crate::util::docker::Mount;
 use crate::util::EnvironmentVariableName;
 
 /// The configuration for the containers
@@ -35,4 +38,36 @@ pub struct ContainerConfig {
     /// Pass the current Git hash to the container
     #[getset(get = "pub")]
     git_commit_hash: Option<EnvironmentVariableName>,
+
+    /// Host paths (on the endpoints) below which paths may be mounted into the containers
+    #[getset(get = "pub")]
+    #[serde(default)]
+    allowed_mounts: Vec<PathBuf>,
+
+    /// Host paths that are mounted into all build containers
+    #[getset(get = "pub")]
+    #[serde(default)]
+    mounts: Vec<Mount>,
+
+    /// What happens to the containers of the jobs after the jobs finished
+    #[getset(get_copy = "pub")]
+    #[serde(default)]
+    retention: ContainerRetention,
+}
+
+/// What happens to the containers of the jobs after the jobs finished
+///
+/// By default, all containers are kept.
+#[derive(Clone, Copy, Debug, Default, CopyGetters, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ContainerRetention {
+    /// Remove the container of a job as soon as the job succeeded
+    #[getset(get_copy = "pub")]
+    #[serde(default)]
+    remove_successful: bool,
+
+    /// Remove the containers of failed jobs when they are older than this many hours (when
+    /// pruning the containers with `--policy`), keep them forever if not set
+    #[getset(get_copy = "pub")]
+    keep_failed_hours: Option<u64>,
 }
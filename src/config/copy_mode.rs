@@ -0,0 +1,41 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+use serde::Deserialize;
+
+/// How artifacts are copied from the staging store to a release store
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+pub enum ArtifactCopyMode {
+    /// Always copy the file contents
+    #[default]
+    #[serde(rename = "copy")]
+    Copy,
+
+    /// Create a hard link if the stores are on the same device
+    #[serde(rename = "hardlink")]
+    Hardlink,
+
+    /// Create a reflink (copy-on-write clone) if the stores are on the same device
+    #[serde(rename = "reflink")]
+    Reflink,
+}
+
+/// How sources with a file:// URL are put into the source cache
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+pub enum LocalSourceMode {
+    /// Copy the file into the source cache
+    #[default]
+    #[serde(rename = "copy")]
+    Copy,
+
+    /// Create a symlink to the file in the source cache
+    #[serde(rename = "symlink")]
+    Symlink,
+}
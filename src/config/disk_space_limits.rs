@@ -0,0 +1,48 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+use getset::CopyGetters;
+use serde::Deserialize;
+
+/// The free disk space that must be left after a submit (`butido build`)
+///
+/// Before a submit starts, the free space in the staging directory, in the source cache and on
+/// the endpoints is compared with these limits, after subtracting the estimated size of the
+/// artifacts of the submit (based on the sizes of the artifacts of previous builds).
+#[derive(Clone, Copy, Debug, CopyGetters, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DiskSpaceLimits {
+    /// The submit is refused if less space (in bytes) would be left
+    #[serde(default = "default_min_free")]
+    #[getset(get_copy = "pub")]
+    min_free: u64,
+
+    /// A warning is shown if less space (in bytes) would be left
+    #[serde(default = "default_warn_free")]
+    #[getset(get_copy = "pub")]
+    warn_free: u64,
+}
+
+impl Default for DiskSpaceLimits {
+    fn default() -> Self {
+        DiskSpaceLimits {
+            min_free: default_min_free(),
+            warn_free: default_warn_free(),
+        }
+    }
+}
+
+fn default_min_free() -> u64 {
+    1024 * 1024 * 1024
+}
+
+fn default_warn_free() -> u64 {
+    10 * 1024 * 1024 * 1024
+}
@@ -13,6 +13,9 @@ use std::collections::HashMap;
 use getset::{CopyGetters, Getters};
 use serde::Deserialize;
 
+use crate::config::util::default_endpoint_health_check_interval;
+use crate::config::util::default_max_job_retries;
+use crate::config::util::default_max_parallel_uploads;
 use crate::config::Endpoint;
 use crate::config::EndpointName;
 use crate::util::docker::ContainerImage;
@@ -51,4 +54,37 @@ pub struct DockerConfig {
     /// A map of endpoints (name -> settings) that are used as container hosts to run builds on
     #[getset(get = "pub")]
     endpoints: HashMap<EndpointName, Endpoint>,
+
+    /// Interval in seconds in which the health of the endpoints is checked during a build
+    ///
+    /// No jobs are scheduled on endpoints that are unreachable. A value of 0 disables the health
+    /// checks.
+    #[serde(default = "default_endpoint_health_check_interval")]
+    #[getset(get_copy = "pub")]
+    endpoint_health_check_interval: u64,
+
+    /// Whether jobs that failed because their endpoint became unreachable are rescheduled on a
+    /// healthy endpoint
+    #[serde(default)]
+    #[getset(get_copy = "pub")]
+    reschedule_failed_jobs: bool,
+
+    /// The failure classes (see `failure_classifiers`) of failed jobs that are retried on another
+    /// endpoint, because they are failures of the infrastructure and not of the build
+    ///
+    /// The class "docker" stands for jobs that failed because of an error of the Docker daemon.
+    #[serde(default)]
+    #[getset(get = "pub")]
+    retry_failure_classes: Vec<String>,
+
+    /// How often a job is retried at most because of the `retry_failure_classes` or rescheduled
+    /// because its endpoint became unreachable
+    #[serde(default = "default_max_job_retries")]
+    #[getset(get_copy = "pub")]
+    max_job_retries: usize,
+
+    /// How many artifacts are uploaded into the container of a job in parallel at most
+    #[serde(default = "default_max_parallel_uploads")]
+    #[getset(get_copy = "pub")]
+    max_parallel_uploads: usize,
 }
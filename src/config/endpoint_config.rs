@@ -8,9 +8,13 @@
 // SPDX-License-Identifier: EPL-2.0
 //
 
+use std::path::PathBuf;
+
 use getset::{CopyGetters, Getters};
 use serde::Deserialize;
 
+use crate::util::docker::Mount;
+
 #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Ord, PartialOrd, Hash)]
 #[serde(transparent)]
 pub struct EndpointName(String);
@@ -44,10 +48,12 @@ impl EndpointName {
 #[serde(deny_unknown_fields)]
 pub struct Endpoint {
     /// The URI where the endpoint is reachable
+    ///
+    /// For Kubernetes endpoints, this is the kubectl context (the current context if empty).
     #[getset(get = "pub")]
     uri: String,
 
-    /// The type of the endpoint (either "socket" or "http")
+    /// The type of the endpoint (either "socket", "http" or "kubernetes")
     #[getset(get = "pub")]
     endpoint_type: EndpointType,
 
@@ -65,6 +71,109 @@ pub struct Endpoint {
     /// Timeout in seconds for connecting to this endpoint
     #[getset(get = "pub")]
     timeout: Option<u64>,
+
+    /// The compiler cache that is mounted into the build containers on this endpoint
+    #[getset(get = "pub")]
+    compiler_cache: Option<CompilerCache>,
+
+    /// The volume (a named Docker volume or an absolute path on the endpoint host) in which the
+    /// artifacts of the dependencies are cached on this endpoint
+    ///
+    /// Artifacts that are already cached are not uploaded again, the inputs of the jobs are
+    /// symlinks into the cache instead.
+    #[getset(get = "pub")]
+    artifact_cache: Option<String>,
+
+    /// The namespace of the build pods on a Kubernetes endpoint (the namespace of the context if
+    /// not set)
+    #[getset(get = "pub")]
+    namespace: Option<String>,
+
+    /// Tags of the endpoint, e.g. "gpu" or "fast-disk"
+    ///
+    /// Jobs of packages and images that require tags are only scheduled on endpoints that have
+    /// all of the required tags.
+    #[getset(get = "pub")]
+    #[serde(default)]
+    tags: Vec<String>,
+
+    /// The architecture of the endpoint, e.g. "x86_64" or "aarch64"
+    ///
+    /// Jobs that build for an architecture are only scheduled on endpoints with that
+    /// architecture.
+    #[getset(get = "pub")]
+    arch: Option<String>,
+}
+
+/// Whether an endpoint with the tags `tags` may run a job that requires the tags `required`
+pub fn has_endpoint_tags(tags: &[String], required: &[String]) -> bool {
+    required.iter().all(|tag| tags.contains(tag))
+}
+
+/// A persistent cache of a compiler cache tool (e.g., ccache) on an endpoint
+#[derive(Clone, Debug, Getters, CopyGetters, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompilerCache {
+    /// The compiler cache tool that is used in the containers
+    #[getset(get_copy = "pub")]
+    tool: CompilerCacheTool,
+
+    /// The name of a Docker volume or an absolute path on the endpoint host
+    ///
+    /// A path on the endpoint host must be below one of the `containers.allowed_mounts` and can
+    /// only be set in the system configuration.
+    #[getset(get = "pub")]
+    volume: String,
+}
+
+impl CompilerCache {
+    /// The mount of the compiler cache into the containers, if the volume is a path on the
+    /// endpoint host (and not a named Docker volume)
+    ///
+    /// Docker volume names cannot contain slashes, so every volume with a slash is a path.
+    pub fn host_mount(&self) -> Option<Mount> {
+        self.volume.contains('/').then(|| Mount {
+            source: PathBuf::from(&self.volume),
+            target: PathBuf::from(crate::consts::COMPILER_CACHE_DIR_PATH),
+            writable: true,
+        })
+    }
+}
+
+/// The supported compiler cache tools
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, parse_display::Display)]
+#[serde(rename_all = "lowercase")]
+#[display(style = "lowercase")]
+pub enum CompilerCacheTool {
+    Ccache,
+    Sccache,
+}
+
+impl CompilerCacheTool {
+    /// The environment variable that tells the tool where its cache is
+    pub fn dir_env_var(&self) -> &'static str {
+        match self {
+            CompilerCacheTool::Ccache => "CCACHE_DIR",
+            CompilerCacheTool::Sccache => "SCCACHE_DIR",
+        }
+    }
+
+    /// The shell command that prints the statistics of the cache
+    pub fn stats_command(&self) -> &'static str {
+        match self {
+            CompilerCacheTool::Ccache => "ccache --show-stats",
+            CompilerCacheTool::Sccache => "sccache --show-stats",
+        }
+    }
+
+    /// The shell command that removes everything from the cache
+    pub fn clear_command(&self) -> &'static str {
+        match self {
+            CompilerCacheTool::Ccache => "ccache --clear --zero-stats",
+            // sccache has no command to clear a local cache
+            CompilerCacheTool::Sccache => "rm -rf \"$SCCACHE_DIR\"/*",
+        }
+    }
 }
 
 /// The type of an endpoint
@@ -74,4 +183,6 @@ pub enum EndpointType {
     Socket,
     #[serde(rename = "http")]
     Http,
+    #[serde(rename = "kubernetes")]
+    Kubernetes,
 }
@@ -0,0 +1,28 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+use getset::Getters;
+use serde::Deserialize;
+
+/// A command that is run in the container of every job before the script, e.g. `gcc --version`
+///
+/// The output of the command is recorded with the job, so that the build environment of a job is
+/// known even after the image tag was moved.
+#[derive(Clone, Debug, Getters, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnvironmentProbe {
+    /// The name of the probe, e.g. "gcc"
+    #[getset(get = "pub")]
+    name: String,
+
+    /// The shell command that is run in the container
+    #[getset(get = "pub")]
+    command: String,
+}
@@ -0,0 +1,74 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+use getset::Getters;
+use serde::Deserialize;
+
+/// A class of job failures that is recognized by patterns in the log of a failed job
+#[derive(Clone, Debug, Getters, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FailureClassifier {
+    /// The name of the class, e.g. "oom"
+    #[getset(get = "pub")]
+    name: String,
+
+    /// Regular expressions, a failed job belongs to the class if a log line matches one of them
+    #[getset(get = "pub")]
+    patterns: Vec<String>,
+}
+
+/// The default failure classes, in the order in which they are checked
+pub fn default_failure_classifiers() -> Vec<FailureClassifier> {
+    let classifier = |name: &str, patterns: &[&str]| FailureClassifier {
+        name: name.to_string(),
+        patterns: patterns.iter().map(|p| p.to_string()).collect(),
+    };
+
+    vec![
+        classifier(
+            "oom",
+            &[
+                r"(?i)out of memory",
+                r"Cannot allocate memory",
+                r"virtual memory exhausted",
+                r"std::bad_alloc",
+            ],
+        ),
+        classifier(
+            "disk-full",
+            &[r"No space left on device", r"Disk quota exceeded"],
+        ),
+        classifier(
+            "network",
+            &[
+                r"Could not resolve host",
+                r"Temporary failure in name resolution",
+                r"Connection (refused|reset by peer|timed out)",
+                r"Network is unreachable",
+            ],
+        ),
+        classifier(
+            "compiler-ice",
+            &[
+                r"(?i)internal compiler error",
+                r"Please submit a full bug report",
+            ],
+        ),
+        classifier(
+            "test-failure",
+            &[
+                r"(?i)\b[1-9][0-9]* (tests?|examples?) failed\b",
+                r"(?i)test suite failed",
+                r"^FAIL:? ",
+                r"\*\*\* \[[^\]]*(check|test)[^\]]*\] Error",
+            ],
+        ),
+    ]
+}
@@ -0,0 +1,540 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Locating and loading the configuration files
+//!
+//! The configuration is merged from these sources, where later sources override earlier ones:
+//!
+//! 1. The system configuration (`/etc/butido/config.toml`), if it exists
+//! 2. The repository configuration (`config.toml` in the top-level of the repository)
+//! 3. The user configuration (`config.toml` in the XDG configuration directory, usually
+//!    `~/.config/butido/`), if it exists
+//! 4. The profile that is selected with `--profile`, if any (see below)
+//! 5. Environment variables with the `BUTIDO_` prefix (e.g., `BUTIDO_DATABASE_PASSWORD`)
+//!
+//! A profile is a table `profiles.<name>` in the configuration that contains settings that
+//! override the other settings when the profile is selected (e.g., a different set of endpoints
+//! for a test farm).
+//! Tables are merged with the other settings, except for the named collections (e.g.,
+//! `docker.endpoints`, see `PROFILE_REPLACED_TABLES`), which the profile replaces as a whole.
+//!
+//! The host paths that may be mounted into the containers (`containers.allowed_mounts`) and
+//! compiler caches on endpoint host paths can only be set in the system configuration.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use tracing::debug;
+
+/// The path of the system wide configuration file
+const SYSTEM_CONFIG_FILE: &str = "/etc/butido/config.toml";
+
+/// The prefix of environment variables that override configuration values
+pub const CONFIG_ENV_PREFIX: &str = "BUTIDO";
+
+/// The key of the host paths that may be mounted into the containers
+const ALLOWED_MOUNTS_KEY: &str = "containers.allowed_mounts";
+
+/// The tables that a profile replaces as a whole instead of merging them with the other settings
+///
+/// Otherwise, e.g., the endpoints of a profile would be used in addition to the default endpoints.
+const PROFILE_REPLACED_TABLES: &[&str] = &[
+    "docker.endpoints",
+    "package_namespaces",
+    "release_store_layouts",
+    "release_metadata",
+    "release_mirrors",
+];
+
+/// The origin that config-rs reports for values from environment variables
+const ENVIRONMENT_ORIGIN: &str = "the environment";
+
+/// Find the configuration files, in the order in which they are merged (later files override
+/// earlier ones)
+///
+/// The configuration file in the repository is always returned (even if it does not exist, as it
+/// is required), the other ones only if they exist.
+pub fn config_files(repo_path: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::with_capacity(3);
+
+    let system_config = PathBuf::from(SYSTEM_CONFIG_FILE);
+    if system_config.is_file() {
+        debug!(
+            "System configuration file found: {}",
+            system_config.display()
+        );
+        files.push(system_config);
+    }
+
+    files.push(repo_path.join("config.toml"));
+
+    let xdg = xdg::BaseDirectories::with_prefix("butido")?;
+    if let Some(xdg_config) = xdg.find_config_file("config.toml") {
+        debug!(
+            "Configuration file found with XDG: {}",
+            xdg_config.display()
+        );
+        files.push(xdg_config);
+    } else {
+        debug!(
+            "No configuration file found with XDG: {}",
+            xdg.get_config_home().display()
+        );
+    }
+
+    Ok(files)
+}
+
+/// Load the configuration from the passed files, the selected profile and the `BUTIDO_*`
+/// environment variables
+pub fn load_config(files: &[PathBuf], profile: Option<&str>) -> Result<::config::Config> {
+    let config = build_config(files, profile)?;
+    let system_config = files
+        .iter()
+        .find(|file| file.as_path() == Path::new(SYSTEM_CONFIG_FILE));
+    check_system_only_settings(&config, system_config.map(PathBuf::as_path))?;
+    Ok(config)
+}
+
+fn build_config(files: &[PathBuf], profile: Option<&str>) -> Result<::config::Config> {
+    let from_files = files
+        .iter()
+        .fold(::config::Config::builder(), |builder, file| {
+            builder.add_source(::config::File::from(file.as_path()))
+        })
+        .build()
+        .context("Failed to load and build the butido configuration")?;
+
+    let profile_values = profile
+        .map(|name| {
+            from_files
+                .get_table(&format!("profiles.{name}"))
+                .with_context(|| anyhow!("Failed to load the configuration profile '{}'", name))
+        })
+        .transpose()?;
+
+    let builder = match profile_values {
+        Some(values) => {
+            let mut base = ::config::Source::collect(&from_files)
+                .context("Failed to load and build the butido configuration")?;
+            for table in PROFILE_REPLACED_TABLES {
+                if lookup(&values, table).is_some() {
+                    debug!("Profile replaces '{}'", table);
+                    remove(&mut base, table);
+                }
+            }
+            ::config::Config::builder()
+                .add_source(MapSource(base))
+                .add_source(MapSource(values))
+        }
+        None => ::config::Config::builder().add_source(from_files),
+    };
+
+    builder
+        .add_source(::config::Environment::with_prefix(CONFIG_ENV_PREFIX))
+        .build()
+        .context("Failed to load and build the butido configuration")
+}
+
+/// Check that the settings that give access to the endpoint hosts are the ones of the system
+/// configuration
+///
+/// Otherwise, a repository, the user, a profile or the environment could mount any path of the
+/// endpoint hosts into the containers.
+fn check_system_only_settings(
+    config: &::config::Config,
+    system_config: Option<&Path>,
+) -> Result<()> {
+    let system = system_config
+        .map(|file| {
+            ::config::Config::builder()
+                .add_source(::config::File::from(file))
+                .build()
+                .with_context(|| anyhow!("Failed to load {}", file.display()))
+        })
+        .transpose()?;
+    check_allowed_mounts(config, system.as_ref())?;
+    check_compiler_cache_paths(config, system.as_ref())
+}
+
+fn check_allowed_mounts(
+    config: &::config::Config,
+    system: Option<&::config::Config>,
+) -> Result<()> {
+    let allowed_in_system =
+        system.and_then(|system| system.get::<Vec<PathBuf>>(ALLOWED_MOUNTS_KEY).ok());
+    let allowed = config.get::<Vec<PathBuf>>(ALLOWED_MOUNTS_KEY).ok();
+
+    if allowed != allowed_in_system {
+        return Err(anyhow!(
+            "'{}' may only be set in the system configuration ({})",
+            ALLOWED_MOUNTS_KEY,
+            SYSTEM_CONFIG_FILE
+        ));
+    }
+    Ok(())
+}
+
+/// Look up a dotted key (e.g., `docker.endpoints`) in a table of settings
+fn lookup<'a>(
+    table: &'a ::config::Map<String, ::config::Value>,
+    key: &str,
+) -> Option<&'a ::config::Value> {
+    match key.split_once('.') {
+        None => table.get(key),
+        Some((head, rest)) => match table.get(head).map(|value| &value.kind) {
+            Some(::config::ValueKind::Table(sub_table)) => lookup(sub_table, rest),
+            _ => None,
+        },
+    }
+}
+
+/// Remove a dotted key (e.g., `docker.endpoints`) from a table of settings
+fn remove(table: &mut ::config::Map<String, ::config::Value>, key: &str) {
+    match key.split_once('.') {
+        None => table.retain(|name, _| name != key),
+        Some((head, rest)) => {
+            if let Some(::config::ValueKind::Table(sub_table)) =
+                table.get_mut(head).map(|value| &mut value.kind)
+            {
+                remove(sub_table, rest);
+            }
+        }
+    }
+}
+
+/// Check that the compiler caches on endpoint host paths (see `CompilerCache::host_mount()`) are
+/// set in the system configuration
+fn check_compiler_cache_paths(
+    config: &::config::Config,
+    system: Option<&::config::Config>,
+) -> Result<()> {
+    let Ok(endpoints) = config.get_table("docker.endpoints") else {
+        return Ok(());
+    };
+
+    for name in endpoints.keys() {
+        let key = format!("docker.endpoints.{name}.compiler_cache.volume");
+        let Ok(volume) = config.get_string(&key) else {
+            continue;
+        };
+        let in_system = system.and_then(|system| system.get_string(&key).ok());
+        if volume.contains('/') && in_system.as_ref() != Some(&volume) {
+            return Err(anyhow!(
+                "The compiler cache path of endpoint {} ({}) may only be set in the system \
+                 configuration ({})",
+                name,
+                volume,
+                SYSTEM_CONFIG_FILE
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Already loaded settings (e.g., of a profile), as a source for the configuration
+#[derive(Clone, Debug)]
+struct MapSource(::config::Map<String, ::config::Value>);
+
+impl ::config::Source for MapSource {
+    fn clone_into_box(&self) -> Box<dyn ::config::Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<::config::Map<String, ::config::Value>, ::config::ConfigError> {
+        Ok(self.0.clone())
+    }
+}
+
+/// A configuration value after merging all sources, together with the source it came from
+#[derive(Debug, Eq, PartialEq)]
+pub struct EffectiveValue {
+    /// The (dotted) key, with indices for array elements (e.g., `docker.images[0].name`)
+    pub key: String,
+    pub value: String,
+    pub origin: String,
+}
+
+/// Get all values of the merged configuration, sorted by key, with the source they came from
+pub fn effective_values(config: &::config::Config) -> Result<Vec<EffectiveValue>> {
+    let mut values = Vec::new();
+    for (key, value) in ::config::Source::collect(config)? {
+        flatten_value(key, value, &mut values);
+    }
+    values.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(values)
+}
+
+fn flatten_value(key: String, value: ::config::Value, out: &mut Vec<EffectiveValue>) {
+    let origin = match value.origin() {
+        Some(ENVIRONMENT_ORIGIN) => {
+            format!("environment ({CONFIG_ENV_PREFIX}_{})", key.to_uppercase())
+        }
+        Some(origin) => origin.to_string(),
+        None => String::from("unknown"),
+    };
+
+    match value.kind {
+        ::config::ValueKind::Table(table) => {
+            for (sub_key, sub_value) in table {
+                flatten_value(format!("{key}.{sub_key}"), sub_value, out);
+            }
+        }
+        ::config::ValueKind::Array(array) => {
+            for (idx, element) in array.into_iter().enumerate() {
+                flatten_value(format!("{key}[{idx}]"), element, out);
+            }
+        }
+        ::config::ValueKind::String(s) => out.push(EffectiveValue {
+            key,
+            value: format!("{s:?}"),
+            origin,
+        }),
+        kind => out.push(EffectiveValue {
+            key,
+            value: kind.to_string(),
+            origin,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effective_values_have_provenance() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("butido-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        let file = dir.join("config.toml");
+        std::fs::write(
+            &file,
+            indoc::indoc!(
+                r#"
+                    compatibility = 1
+                    [docker]
+                    images = [ { name = "local:debian12", short_name = "deb12" } ]
+                "#
+            ),
+        )?;
+
+        let env = [(String::from("BUTIDO_LOG_DIR"), String::from("/tmp/logs"))]
+            .into_iter()
+            .collect();
+        let config = ::config::Config::builder()
+            .add_source(::config::File::from(file.as_path()))
+            .add_source(::config::Environment::with_prefix(CONFIG_ENV_PREFIX).source(Some(env)))
+            .set_override("shebang", "#!/bin/bash")?
+            .build()?;
+        std::fs::remove_dir_all(&dir)?;
+
+        let values = effective_values(&config)?;
+        let keys = values.iter().map(|v| v.key.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            [
+                "compatibility",
+                "docker.images[0].name",
+                "docker.images[0].short_name",
+                "log_dir",
+                "shebang"
+            ]
+        );
+
+        // config-rs reports files relative to the current directory
+        let file_origin = Path::new(&dir.file_name().unwrap()).join("config.toml");
+        for value in values
+            .iter()
+            .filter(|v| !["log_dir", "shebang"].contains(&v.key.as_str()))
+        {
+            assert!(
+                Path::new(&value.origin).ends_with(&file_origin),
+                "Unexpected origin of {}: {}",
+                value.key,
+                value.origin
+            );
+        }
+
+        let log_dir = values.iter().find(|v| v.key == "log_dir").unwrap();
+        assert_eq!(log_dir.value, "\"/tmp/logs\"");
+        assert_eq!(log_dir.origin, "environment (BUTIDO_LOG_DIR)");
+
+        let shebang = values.iter().find(|v| v.key == "shebang").unwrap();
+        assert_eq!(shebang.origin, "unknown");
+        Ok(())
+    }
+
+    #[test]
+    fn test_profile_overrides_settings() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("butido-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        let file = dir.join("config.toml");
+        std::fs::write(
+            &file,
+            indoc::indoc!(
+                r#"
+                    staging = "/tmp/staging"
+                    releases_root = "/tmp/releases"
+                    [profiles.test]
+                    staging = "/tmp/test-staging"
+                "#
+            ),
+        )?;
+
+        let default = load_config(std::slice::from_ref(&file), None)?;
+        let test = load_config(std::slice::from_ref(&file), Some("test"));
+        let unknown = load_config(std::slice::from_ref(&file), Some("unknown"));
+        std::fs::remove_dir_all(&dir)?;
+
+        assert_eq!(default.get_string("staging")?, "/tmp/staging");
+        let test = test?;
+        assert_eq!(test.get_string("staging")?, "/tmp/test-staging");
+        assert_eq!(test.get_string("releases_root")?, "/tmp/releases");
+        assert!(unknown.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_profile_replaces_endpoints() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("butido-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        let file = dir.join("config.toml");
+        std::fs::write(
+            &file,
+            indoc::indoc!(
+                r#"
+                    [docker]
+                    images = [ { name = "local:debian12" } ]
+                    [docker.endpoints.production]
+                    uri = "http://prodhost:8095"
+                    endpoint_type = "http"
+                    [profiles.test.docker.endpoints.testing]
+                    uri = "http://testhost:8095"
+                    endpoint_type = "http"
+                "#
+            ),
+        )?;
+
+        let default = load_config(std::slice::from_ref(&file), None);
+        let test = load_config(std::slice::from_ref(&file), Some("test"));
+        std::fs::remove_dir_all(&dir)?;
+
+        let endpoint_names = |config: &::config::Config| -> Result<Vec<String>> {
+            let mut names = config
+                .get_table("docker.endpoints")?
+                .into_keys()
+                .collect::<Vec<_>>();
+            names.sort();
+            Ok(names)
+        };
+        let (default, test) = (default?, test?);
+        assert_eq!(endpoint_names(&default)?, ["production"]);
+        assert_eq!(endpoint_names(&test)?, ["testing"]);
+        assert_eq!(test.get_array("docker.images")?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_allowed_mounts_only_from_system_config() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("butido-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        let system = dir.join("system.toml");
+        let repo = dir.join("config.toml");
+        let user = dir.join("user.toml");
+        std::fs::write(
+            &system,
+            indoc::indoc!(
+                r#"
+                    [containers]
+                    allowed_mounts = [ "/opt/toolchains" ]
+                "#
+            ),
+        )?;
+        std::fs::write(
+            &repo,
+            indoc::indoc!(
+                r#"
+                    staging = "/tmp/staging"
+                    [profiles.test.containers]
+                    allowed_mounts = [ "/" ]
+                "#
+            ),
+        )?;
+        std::fs::write(
+            &user,
+            indoc::indoc!(
+                r#"
+                    [containers]
+                    allowed_mounts = [ "/" ]
+                "#
+            ),
+        )?;
+
+        let from_system = build_config(&[system.clone(), repo.clone()], None)?;
+        let from_user = build_config(&[system.clone(), repo.clone(), user], None)?;
+        let from_profile = build_config(&[system.clone(), repo], Some("test"))?;
+
+        let checks = [
+            check_system_only_settings(&from_system, Some(system.as_path())).is_ok(),
+            check_system_only_settings(&from_system, None).is_err(),
+            check_system_only_settings(&from_user, Some(system.as_path())).is_err(),
+            check_system_only_settings(&from_profile, Some(system.as_path())).is_err(),
+        ];
+        std::fs::remove_dir_all(&dir)?;
+        assert_eq!(checks, [true; 4]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compiler_cache_path_only_from_system_config() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("butido-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        let system = dir.join("system.toml");
+        let repo = dir.join("config.toml");
+        std::fs::write(
+            &system,
+            indoc::indoc!(
+                r#"
+                    [docker.endpoints.builder.compiler_cache]
+                    tool = "ccache"
+                    volume = "/srv/ccache"
+                "#
+            ),
+        )?;
+        std::fs::write(
+            &repo,
+            indoc::indoc!(
+                r#"
+                    [docker.endpoints.other.compiler_cache]
+                    tool = "ccache"
+                    volume = "ccache-volume"
+                    [profiles.test.docker.endpoints.builder.compiler_cache]
+                    tool = "ccache"
+                    volume = "/"
+                "#
+            ),
+        )?;
+
+        let from_system = build_config(&[system.clone(), repo.clone()], None)?;
+        let from_profile = build_config(&[system.clone(), repo], Some("test"))?;
+
+        let checks = [
+            check_compiler_cache_paths(&from_system, Some(&build_config(&[system], None)?)).is_ok(),
+            check_compiler_cache_paths(&from_system, None).is_err(),
+            check_compiler_cache_paths(&from_profile, Some(&from_system)).is_err(),
+        ];
+        std::fs::remove_dir_all(&dir)?;
+        assert_eq!(checks, [true; 3]);
+        Ok(())
+    }
+}
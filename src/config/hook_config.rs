@@ -0,0 +1,65 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+use getset::Getters;
+use serde::Deserialize;
+
+/// A command that checks the artifacts of every successful job, e.g. `namcap`
+///
+/// The paths of the artifacts are appended to the command. If the command exits with a non-zero
+/// exit code, the job fails.
+#[derive(Clone, Debug, Getters, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PostBuildHook {
+    /// The name of the hook, shown when it fails
+    #[getset(get = "pub")]
+    name: String,
+
+    /// The command and its arguments
+    #[getset(get = "pub")]
+    command: Vec<String>,
+
+    /// Run the command in a container of this image (with the local Docker daemon) instead of on
+    /// the host
+    ///
+    /// The staging directory is mounted read-only at the same path in the container.
+    #[getset(get = "pub")]
+    #[serde(default)]
+    image: Option<String>,
+}
+
+/// A check of the packages of a submit, after the dependency tree was resolved and before any job
+/// starts
+///
+/// The submit is rejected if any of the configured checks fails.
+#[derive(Clone, Debug, Getters, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PreSubmitHook {
+    /// The name of the hook, shown when it fails
+    #[getset(get = "pub")]
+    name: String,
+
+    /// A command that gets the resolved packages as JSON array on stdin
+    ///
+    /// If the command exits with a non-zero exit code, the submit is rejected.
+    #[getset(get = "pub")]
+    #[serde(default)]
+    command: Option<Vec<String>>,
+
+    /// Glob patterns of the names of packages that must not be part of a submit
+    #[getset(get = "pub")]
+    #[serde(default)]
+    denied_packages: Vec<String>,
+
+    /// The metadata fields that every package of a submit must set (e.g. "license")
+    #[getset(get = "pub")]
+    #[serde(default)]
+    required_metadata: Vec<String>,
+}
@@ -0,0 +1,72 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use getset::Getters;
+use regex::RegexSet;
+use serde::Deserialize;
+
+/// The regular expressions that select the lines of a job log for `db log-of --filter`
+#[derive(Clone, Debug, Getters, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LogFilterConfig {
+    /// Lines that contain errors
+    #[serde(default = "default_error_patterns")]
+    #[getset(get = "pub")]
+    errors: Vec<String>,
+
+    /// Lines that contain warnings
+    #[serde(default = "default_warning_patterns")]
+    #[getset(get = "pub")]
+    warnings: Vec<String>,
+}
+
+impl Default for LogFilterConfig {
+    fn default() -> Self {
+        LogFilterConfig {
+            errors: default_error_patterns(),
+            warnings: default_warning_patterns(),
+        }
+    }
+}
+
+impl LogFilterConfig {
+    /// Compile the patterns of the passed filters ("errors", "warnings") into one set
+    pub fn regex_set<'a>(&self, filters: impl IntoIterator<Item = &'a str>) -> Result<RegexSet> {
+        let mut patterns = Vec::new();
+        for filter in filters {
+            match filter {
+                "errors" => patterns.extend(self.errors.iter()),
+                "warnings" => patterns.extend(self.warnings.iter()),
+                other => return Err(anyhow!("Unknown log filter: {}", other)),
+            }
+        }
+
+        RegexSet::new(patterns).context("Compiling the log filter patterns (log_filters)")
+    }
+}
+
+fn default_error_patterns() -> Vec<String> {
+    [
+        r"(?i)\b(error|fatal|failed)\b",
+        r"Segmentation fault",
+        r"No such file or directory",
+        r"command not found",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+fn default_warning_patterns() -> Vec<String> {
+    vec![String::from(r"(?i)\bwarning\b")]
+}
@@ -0,0 +1,69 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+use getset::CopyGetters;
+use serde::Deserialize;
+
+/// Limits for the logs of the jobs that are parsed and stored in the database
+///
+/// The plain text log files (`--write-log-file`) are not limited.
+#[derive(Clone, Copy, Debug, CopyGetters, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LogLimits {
+    /// The maximum length of a log line in bytes, longer lines are truncated
+    #[serde(default = "default_max_line_length")]
+    #[getset(get_copy = "pub")]
+    max_line_length: usize,
+
+    /// The maximum size of the log of a job in bytes
+    #[serde(default = "default_max_size")]
+    #[getset(get_copy = "pub")]
+    max_size: usize,
+
+    /// Which part of the log is kept if a log exceeds `max_size`
+    #[serde(default)]
+    #[getset(get_copy = "pub")]
+    truncation: LogTruncation,
+}
+
+impl Default for LogLimits {
+    fn default() -> Self {
+        LogLimits {
+            max_line_length: default_max_line_length(),
+            max_size: default_max_size(),
+            truncation: LogTruncation::default(),
+        }
+    }
+}
+
+/// Which part of a log is kept if it has to be truncated
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+pub enum LogTruncation {
+    /// Keep the beginning of the log
+    #[serde(rename = "head")]
+    Head,
+
+    /// Keep the end of the log
+    #[serde(rename = "tail")]
+    Tail,
+
+    /// Keep the beginning and the end of the log (half of the size each)
+    #[default]
+    #[serde(rename = "head-and-tail")]
+    HeadAndTail,
+}
+
+fn default_max_line_length() -> usize {
+    64 * 1024
+}
+
+fn default_max_size() -> usize {
+    64 * 1024 * 1024
+}
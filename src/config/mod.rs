@@ -26,13 +26,49 @@ pub use configuration::*;
 mod container_config;
 pub use container_config::*;
 
+mod copy_mode;
+pub use copy_mode::*;
+
+mod disk_space_limits;
+pub use disk_space_limits::*;
+
 mod docker_config;
 pub use docker_config::*;
 
 mod endpoint_config;
 pub use endpoint_config::*;
 
+mod environment_probe;
+pub use environment_probe::*;
+
+mod failure_classifier;
+pub use failure_classifier::*;
+
+mod files;
+pub use files::*;
+
+mod hook_config;
+pub use hook_config::*;
+
+mod log_filter_config;
+pub use log_filter_config::*;
+
+mod log_limits;
+pub use log_limits::*;
+
 mod not_validated;
 pub use not_validated::*;
 
+mod notification_config;
+pub use notification_config::*;
+
+mod provenance_config;
+pub use provenance_config::*;
+
+mod release_metadata;
+pub use release_metadata::*;
+
+mod remote_cache_config;
+pub use remote_cache_config::*;
+
 mod util;
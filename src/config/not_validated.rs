@@ -13,12 +13,29 @@ use anyhow::Context;
 use anyhow::Result;
 use getset::Getters;
 use serde::Deserialize;
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::path::PathBuf;
 
+use crate::config::default_failure_classifiers;
 use crate::config::util::*;
+use crate::config::ArtifactCopyMode;
 use crate::config::Configuration;
 use crate::config::ContainerConfig;
+use crate::config::DiskSpaceLimits;
 use crate::config::DockerConfig;
+use crate::config::EnvironmentProbe;
+use crate::config::FailureClassifier;
+use crate::config::LocalSourceMode;
+use crate::config::LogFilterConfig;
+use crate::config::LogLimits;
+use crate::config::NotificationConfig;
+use crate::config::PostBuildHook;
+use crate::config::PreSubmitHook;
+use crate::config::ProvenanceConfig;
+use crate::config::ReleaseMetadataGenerator;
+use crate::config::RemoteCacheConfig;
+use crate::filestore::StoreSelection;
 use crate::package::PhaseName;
 
 // The configuration version must be increased each time breaking configuration changes are made
@@ -52,6 +69,28 @@ pub struct NotValidatedConfiguration {
     #[getset(get = "pub")]
     progress_format: String,
 
+    /// The format of the progress status output for operations that transfer or process bytes,
+    /// e.g. downloads or unpacking archives
+    #[serde(default = "default_progress_format_bytes")]
+    #[getset(get = "pub")]
+    progress_format_bytes: String,
+
+    /// Additional package-definition roots that are layered over the repository
+    ///
+    /// Relative paths are relative to the repository root, later overlays take precedence.
+    #[serde(default)]
+    #[getset(get = "pub")]
+    repository_overlays: Vec<PathBuf>,
+
+    /// The namespaces of the packages in subtrees of the repository
+    ///
+    /// Maps a namespace to a directory, relative to the repository root (and to the root of each
+    /// overlay). The packages below the directory are put into the namespace, packages outside of
+    /// it must not use the namespace.
+    #[serde(default)]
+    #[getset(get = "pub")]
+    package_namespaces: HashMap<String, PathBuf>,
+
     /// The format used to print a package
     ///
     /// This is handlebars syntax
@@ -59,6 +98,46 @@ pub struct NotValidatedConfiguration {
     #[getset(get = "pub")]
     package_print_format: String,
 
+    /// The limits for the logs of the jobs that are stored in the database
+    #[serde(default)]
+    #[getset(get = "pub")]
+    log_limits: LogLimits,
+
+    /// The free disk space that must be left after a submit
+    #[serde(default)]
+    #[getset(get = "pub")]
+    disk_space_limits: DiskSpaceLimits,
+
+    /// The patterns for filtering the logs of jobs (`db log-of --filter`)
+    #[serde(default)]
+    #[getset(get = "pub")]
+    log_filters: LogFilterConfig,
+
+    /// License identifiers (glob patterns) that must not be used by packages (`db licenses`)
+    #[serde(default)]
+    #[getset(get = "pub")]
+    license_deny_list: Vec<String>,
+
+    /// The classes of job failures, in the order in which they are checked
+    #[serde(default = "default_failure_classifiers")]
+    #[getset(get = "pub")]
+    failure_classifiers: Vec<FailureClassifier>,
+
+    /// Commands that check the artifacts of every successful job
+    #[serde(default)]
+    #[getset(get = "pub")]
+    post_build_hooks: Vec<PostBuildHook>,
+
+    /// Checks of the packages of a submit before any job starts
+    #[serde(default)]
+    #[getset(get = "pub")]
+    pre_submit_hooks: Vec<PreSubmitHook>,
+
+    /// Commands that record the build environment in the container of every job
+    #[serde(default)]
+    #[getset(get = "pub")]
+    environment_probes: Vec<EnvironmentProbe>,
+
     /// How many lines should be printed from the log if a build fails
     #[serde(default = "default_build_error_lines")]
     #[getset(get = "pub")]
@@ -72,6 +151,11 @@ pub struct NotValidatedConfiguration {
     #[getset(get = "pub")]
     script_linter: Option<PathBuf>,
 
+    /// The shellcheck executable that is used by `lint --scripts`
+    #[serde(default = "default_shellcheck_command")]
+    #[getset(get = "pub")]
+    shellcheck_command: PathBuf,
+
     /// The shebang that is added at the very beginning of the package scripts
     #[serde(default = "default_script_shebang")]
     #[getset(get = "pub")]
@@ -86,6 +170,38 @@ pub struct NotValidatedConfiguration {
     #[getset(get = "pub")]
     release_stores: Vec<String>,
 
+    /// The layout of the artifacts in the release stores, per release store name
+    ///
+    /// This is handlebars syntax. Release stores without a layout mirror the path of the artifact
+    /// in the staging store.
+    #[serde(default)]
+    #[getset(get = "pub")]
+    release_store_layouts: HashMap<String, String>,
+
+    /// The stores that are searched for artifacts to reuse, in this order ("staging" for the
+    /// staging store)
+    ///
+    /// If not set, the staging store and all release stores (in the order of `release_stores`)
+    /// are searched.
+    #[serde(default)]
+    #[getset(get = "pub")]
+    artifact_stores: Option<Vec<String>>,
+
+    /// The generators of repository metadata of the release stores, per release store name
+    #[serde(default)]
+    #[getset(get = "pub")]
+    release_metadata: HashMap<String, Vec<ReleaseMetadataGenerator>>,
+
+    /// The remote mirrors of the release stores (rsync destinations), per release store name
+    #[serde(default)]
+    #[getset(get = "pub")]
+    release_mirrors: HashMap<String, Vec<String>>,
+
+    /// How artifacts are copied from the staging store to the release stores
+    #[serde(default)]
+    #[getset(get = "pub")]
+    release_copy_mode: ArtifactCopyMode,
+
     /// The directory where intermediate ("staging") artifacts are stored.
     /// This is used as a root directory, a UUID-named directory will be added below this, using
     /// the UUID of the submit
@@ -98,6 +214,16 @@ pub struct NotValidatedConfiguration {
     #[getset(get = "pub")]
     source_cache_root: PathBuf,
 
+    /// How sources with a file:// URL are put into the source cache
+    #[serde(default)]
+    #[getset(get = "pub")]
+    local_source_mode: LocalSourceMode,
+
+    /// Where the checkpoints of the jobs are stored, checkpoints are disabled if not set
+    #[serde(default)]
+    #[getset(get = "pub")]
+    checkpoint_dir: Option<PathBuf>,
+
     /// The hostname/FQDN/IP used to connect to the database
     #[getset(get = "pub")]
     database_host: String,
@@ -140,6 +266,26 @@ pub struct NotValidatedConfiguration {
     /// The names of the phases which should be compiled into the packaging script
     #[getset(get = "pub")]
     available_phases: Vec<PhaseName>,
+
+    /// The configuration for the remote build cache, if any
+    #[getset(get = "pub")]
+    remote_cache: Option<RemoteCacheConfig>,
+
+    /// The configuration for desktop notifications when long local operations finish, if any
+    #[getset(get = "pub")]
+    notifications: Option<NotificationConfig>,
+
+    /// The configuration for provenance attestations of released artifacts, if any
+    #[getset(get = "pub")]
+    provenance: Option<ProvenanceConfig>,
+
+    /// Named profiles with settings that override the other settings if the profile is selected
+    /// (with `--profile`)
+    ///
+    /// The selected profile is already merged into the other settings when the configuration is
+    /// loaded, so the profiles are only kept here for validation.
+    #[serde(default)]
+    profiles: HashMap<String, ::config::Value>,
 }
 
 fn load_changelog() -> Result<std::collections::HashMap<String, String>> {
@@ -215,7 +361,9 @@ impl NotValidatedConfiguration {
     pub fn validate(self) -> Result<Configuration> {
         self.validate_config(false)
     }
-    fn validate_config(self, skip_filesystem_checks: bool) -> Result<Configuration> {
+
+    /// Like `validate()`, but optionally without checking that the configured directories exist
+    pub fn validate_config(self, skip_filesystem_checks: bool) -> Result<Configuration> {
         // A trivial helper to check if a directory is missing:
         let check_directory_exists = |path: &PathBuf, config_key_name: &str| -> Result<()> {
             if skip_filesystem_checks || path.is_dir() {
@@ -248,6 +396,137 @@ impl NotValidatedConfiguration {
             ));
         }
 
+        if let Some(names) = self.artifact_stores.as_ref() {
+            StoreSelection::new(names, &self.release_stores)
+                .context("Invalid setting 'artifact_stores'")?;
+        }
+
+        for (store_name, layout) in self.release_store_layouts.iter() {
+            if !self.release_stores.contains(store_name) {
+                return Err(anyhow!(
+                    "Layout configured for unknown release store: {}",
+                    store_name
+                ));
+            }
+
+            handlebars::Template::compile(layout)
+                .with_context(|| anyhow!("Invalid layout for release store {}", store_name))?;
+        }
+
+        for (store_name, generators) in self.release_metadata.iter() {
+            if !self.release_stores.contains(store_name) {
+                return Err(anyhow!(
+                    "Metadata generators configured for unknown release store: {}",
+                    store_name
+                ));
+            }
+
+            for generator in generators.iter() {
+                match generator {
+                    ReleaseMetadataGenerator::Json { file }
+                        if file.is_empty()
+                            || std::path::Path::new(file).is_absolute()
+                            || file.split('/').any(|c| c == "..") =>
+                    {
+                        return Err(anyhow!(
+                            "The JSON index of release store {} must be a relative path inside the store: {}",
+                            store_name,
+                            file
+                        ));
+                    }
+                    ReleaseMetadataGenerator::Command { name, command } if command.is_empty() => {
+                        return Err(anyhow!("Metadata generator '{}' has no command", name));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        for (namespace, subtree) in self.package_namespaces.iter() {
+            if !crate::package::PackageName::is_valid_namespace(namespace) {
+                return Err(anyhow!("Invalid package namespace: {}", namespace));
+            }
+            if subtree.is_absolute()
+                || subtree
+                    .components()
+                    .any(|c| c == std::path::Component::ParentDir)
+            {
+                return Err(anyhow!(
+                    "The subtree of package namespace {} must be a relative path inside the repository: {}",
+                    namespace,
+                    subtree.display()
+                ));
+            }
+        }
+
+        for store_name in self.release_mirrors.keys() {
+            if !self.release_stores.contains(store_name) {
+                return Err(anyhow!(
+                    "Mirrors configured for unknown release store: {}",
+                    store_name
+                ));
+            }
+        }
+
+        for hook in self.post_build_hooks.iter() {
+            if hook.command().is_empty() {
+                return Err(anyhow!("Post-build hook '{}' has no command", hook.name()));
+            }
+        }
+
+        let mut probe_names = HashSet::new();
+        for probe in self.environment_probes.iter() {
+            if probe.name().is_empty() || probe.command().trim().is_empty() {
+                return Err(anyhow!(
+                    "Environment probes must have a name and a command: {:?}",
+                    probe
+                ));
+            }
+            if !probe_names.insert(probe.name()) {
+                return Err(anyhow!("Duplicate environment probe: {}", probe.name()));
+            }
+        }
+
+        for hook in self.pre_submit_hooks.iter() {
+            if hook.command().as_ref().is_some_and(Vec::is_empty) {
+                return Err(anyhow!(
+                    "Pre-submit hook '{}' has an empty command",
+                    hook.name()
+                ));
+            }
+            if hook.command().is_none()
+                && hook.denied_packages().is_empty()
+                && hook.required_metadata().is_empty()
+            {
+                return Err(anyhow!("Pre-submit hook '{}' checks nothing", hook.name()));
+            }
+            if let Some(field) = hook
+                .required_metadata()
+                .iter()
+                .find(|field| !crate::util::hooks::METADATA_FIELDS.contains(&field.as_str()))
+            {
+                return Err(anyhow!(
+                    "Pre-submit hook '{}' requires unknown metadata field '{}', known fields: {}",
+                    hook.name(),
+                    field,
+                    crate::util::hooks::METADATA_FIELDS.join(", ")
+                ));
+            }
+        }
+
+        self.log_filters.regex_set(["errors", "warnings"])?;
+        crate::log::FailureClassifiers::new(&self.failure_classifiers)?;
+        for class in self.docker.retry_failure_classes() {
+            let known = class == crate::log::DOCKER_FAILURE_CLASS
+                || self.failure_classifiers.iter().any(|c| c.name() == class);
+            if !known {
+                return Err(anyhow!(
+                    "Unknown failure class in docker.retry_failure_classes: {}",
+                    class
+                ));
+            }
+        }
+
         // Error if there are no phases configured
         if self.available_phases.is_empty() {
             return Err(anyhow!("No phases configured"));
@@ -273,6 +552,79 @@ impl NotValidatedConfiguration {
             }
         }
 
+        for mount in self.containers.mounts() {
+            mount
+                .check_allowed(self.containers.allowed_mounts())
+                .context("Checking 'containers.mounts'")?;
+        }
+
+        for (name, endpoint) in self.docker.endpoints() {
+            if let Some(mount) = endpoint
+                .compiler_cache()
+                .as_ref()
+                .and_then(crate::config::CompilerCache::host_mount)
+            {
+                mount
+                    .check_allowed(self.containers.allowed_mounts())
+                    .with_context(|| anyhow!("Checking the compiler cache of endpoint {}", name))?;
+            }
+        }
+
+        for (profile_name, profile) in self.profiles.iter() {
+            match profile.clone().into_table() {
+                Ok(table) if table.contains_key("profiles") => {
+                    return Err(anyhow!(
+                        "Profile '{}' must not define profiles",
+                        profile_name
+                    ));
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    return Err(e)
+                        .with_context(|| anyhow!("Profile '{}' is not a table", profile_name));
+                }
+            }
+        }
+
+        if self
+            .notifications
+            .as_ref()
+            .is_some_and(|notifications| notifications.command().is_empty())
+        {
+            return Err(anyhow!("'notifications.command' must not be empty"));
+        }
+
+        if let Some(provenance) = self.provenance.as_ref() {
+            if url::Url::parse(provenance.builder_id()).is_err() {
+                return Err(anyhow!(
+                    "'provenance.builder_id' must be a URI: {}",
+                    provenance.builder_id()
+                ));
+            }
+
+            if provenance
+                .sign_command()
+                .as_ref()
+                .is_some_and(|command| command.is_empty())
+            {
+                return Err(anyhow!("'provenance.sign_command' must not be empty"));
+            }
+        }
+
+        if let Some(remote_cache) = self.remote_cache.as_ref() {
+            if remote_cache.token().is_some() && remote_cache.username().is_some() {
+                return Err(anyhow!(
+                    "Only one of 'remote_cache.token' and 'remote_cache.username' can be set"
+                ));
+            }
+
+            if remote_cache.password().is_some() && remote_cache.username().is_none() {
+                return Err(anyhow!(
+                    "'remote_cache.password' requires 'remote_cache.username' to be set"
+                ));
+            }
+        }
+
         Ok(Configuration { inner: self })
     }
 }
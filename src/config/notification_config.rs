@@ -0,0 +1,42 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+use getset::CopyGetters;
+use getset::Getters;
+use serde::Deserialize;
+
+/// The configuration for desktop notifications when long local operations finish
+///
+/// Notifications are sent for `butido build` and `butido source download` if they ran for at least
+/// `min_duration` seconds, no matter whether they succeeded or failed.
+#[derive(Debug, CopyGetters, Getters, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationConfig {
+    /// The minimum duration (in seconds) of an operation to be notified about
+    #[serde(default = "default_min_duration")]
+    #[getset(get_copy = "pub")]
+    min_duration: u64,
+
+    /// The command that shows the notification, the summary and the body are appended to it
+    #[serde(default = "default_notification_command")]
+    #[getset(get = "pub")]
+    command: Vec<String>,
+}
+
+fn default_min_duration() -> u64 {
+    60
+}
+
+fn default_notification_command() -> Vec<String> {
+    vec![
+        String::from("notify-send"),
+        String::from("--app-name=butido"),
+    ]
+}
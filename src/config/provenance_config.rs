@@ -0,0 +1,37 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+use getset::Getters;
+use serde::Deserialize;
+
+/// The configuration for the provenance attestations of released artifacts
+///
+/// If configured, `butido release` writes an in-toto statement with a SLSA provenance predicate
+/// for each released artifact next to the artifact and records it in the database.
+#[derive(Debug, Getters, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProvenanceConfig {
+    /// The URI that identifies the build platform in the attestations
+    #[getset(get = "pub")]
+    builder_id: String,
+
+    /// The command that signs the attestations, if they should be signed
+    ///
+    /// The command gets the DSSE pre-authentication encoding of the statement on stdin and has to
+    /// print the raw signature to stdout.
+    #[serde(default)]
+    #[getset(get = "pub")]
+    sign_command: Option<Vec<String>>,
+
+    /// The key ID that is recorded with the signatures
+    #[serde(default)]
+    #[getset(get = "pub")]
+    key_id: Option<String>,
+}
@@ -0,0 +1,59 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+use serde::Deserialize;
+
+/// A generator of repository metadata, which runs over a release store after artifacts were
+/// added to it or removed from it
+///
+/// The generators make a release store usable as package repository for the package managers of
+/// the distributions.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(tag = "type", deny_unknown_fields)]
+pub enum ReleaseMetadataGenerator {
+    /// RPM repository metadata (the "repodata" directory), generated with `createrepo_c`
+    #[serde(rename = "rpm")]
+    Rpm,
+
+    /// A Debian "Packages" index, generated with `dpkg-scanpackages`
+    #[serde(rename = "deb")]
+    Deb,
+
+    /// A JSON index of the released artifacts (package name and version, path, size, SHA-256
+    /// hash and release date), generated by butido from the database
+    #[serde(rename = "json")]
+    Json {
+        /// The path of the index, relative to the release store
+        #[serde(default = "default_json_index_file")]
+        file: String,
+    },
+
+    /// A custom command, which runs in the directory of the release store
+    ///
+    /// The name of the release store is passed in the environment variable
+    /// `BUTIDO_RELEASE_STORE`.
+    #[serde(rename = "command")]
+    Command { name: String, command: Vec<String> },
+}
+
+fn default_json_index_file() -> String {
+    String::from("index.json")
+}
+
+impl std::fmt::Display for ReleaseMetadataGenerator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReleaseMetadataGenerator::Rpm => write!(f, "rpm"),
+            ReleaseMetadataGenerator::Deb => write!(f, "deb"),
+            ReleaseMetadataGenerator::Json { .. } => write!(f, "json"),
+            ReleaseMetadataGenerator::Command { name, .. } => write!(f, "{name}"),
+        }
+    }
+}
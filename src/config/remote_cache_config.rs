@@ -0,0 +1,47 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+use getset::Getters;
+use serde::Deserialize;
+use url::Url;
+
+/// The configuration for a remote (HTTP) build cache
+#[derive(Debug, Getters, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RemoteCacheConfig {
+    /// The base URL of the cache, the artifacts of a job are stored at `<url>/<cache key>.tar`
+    #[getset(get = "pub")]
+    url: Url,
+
+    /// The token to use for bearer authentication
+    #[getset(get = "pub")]
+    token: Option<String>,
+
+    /// The user name to use for basic authentication
+    #[getset(get = "pub")]
+    username: Option<String>,
+
+    /// The password to use for basic authentication
+    #[getset(get = "pub")]
+    password: Option<String>,
+
+    /// Timeout for requests to the cache in seconds
+    #[getset(get = "pub")]
+    timeout: Option<u64>,
+
+    /// Whether artifacts of successful builds should be uploaded to the cache
+    #[serde(default = "default_remote_cache_upload")]
+    #[getset(get = "pub")]
+    upload: bool,
+}
+
+fn default_remote_cache_upload() -> bool {
+    true
+}
@@ -19,11 +19,19 @@ pub fn default_progress_format() -> String {
     String::from("{elapsed_precise} {percent:>3}% {bar:5.cyan/blue} | {msg}")
 }
 
+/// The default progress bar format for operations that transfer or process bytes
+#[rustversion::attr(since(1.83), allow(clippy::literal_string_with_formatting_args))]
+pub fn default_progress_format_bytes() -> String {
+    String::from(
+        "{elapsed_precise} {percent:>3}% {bar:5.cyan/blue} {bytes}/{total_bytes} ({bytes_per_sec}, ETA {eta}) | {msg}",
+    )
+}
+
 /// The default format that is used to print one package
 pub fn default_package_print_format() -> String {
     String::from(indoc::indoc!(
         r#"
-            {{i}} - {{p.name}} : {{p.version}}
+            {{i}} - {{p.name}} : {{p.version}}{{#if overlay}} (overlay: {{overlay}}){{/if}}
             {{~ #if print_any}}
 
             ==================================
@@ -109,11 +117,31 @@ pub fn default_script_shebang() -> String {
     String::from("#!/bin/bash")
 }
 
+/// The default shellcheck executable (searched in $PATH)
+pub fn default_shellcheck_command() -> std::path::PathBuf {
+    std::path::PathBuf::from("shellcheck")
+}
+
 /// The default value for the number of log lines that should be printed if a build fails
 pub fn default_build_error_lines() -> usize {
     10
 }
 
+/// The default value for the interval (in seconds) in which the health of the endpoints is checked
+pub fn default_endpoint_health_check_interval() -> u64 {
+    30
+}
+
+/// The default value for the number of times a job is retried because of an infrastructure failure
+pub fn default_max_job_retries() -> usize {
+    2
+}
+
+/// The default value for the number of artifacts that are uploaded into a container in parallel
+pub fn default_max_parallel_uploads() -> usize {
+    8
+}
+
 /// The default value for the database connection timeout (in seconds)
 pub fn default_database_connection_timeout() -> u16 {
     30
@@ -19,5 +19,47 @@ pub const OUTPUTS_DIR_NAME: &str = "outputs";
 
 pub const PATCH_DIR_PATH: &str = "/patches";
 
+/// The path to the directory inside the container where the sources that are marked with
+/// `extract = true` are extracted to (each in a directory with the name of the source)
+pub const BUILD_DIR_PATH: &str = "/build";
+
 /// The path where the script that is executed inside the container is copied to.
 pub const SCRIPT_PATH: &str = "/script";
+
+/// The endpoint name under which jobs that ran on the host (`build --local-exec`) are recorded
+pub const LOCAL_ENDPOINT_NAME: &str = "local";
+
+/// The endpoint name under which jobs whose artifacts were fetched from the remote build cache are
+/// recorded
+pub const REMOTE_CACHE_ENDPOINT_NAME: &str = "remote-cache";
+
+/// The directory in the staging directory that holds the staging directories of local builds,
+/// whose artifacts cannot be released
+pub const LOCAL_STAGING_DIR_NAME: &str = "local";
+
+/// The path inside the container where the compiler cache of the endpoint (if any) is mounted
+pub const COMPILER_CACHE_DIR_PATH: &str = "/var/cache/butido-compiler-cache";
+
+/// The path inside the container where the artifact cache of the endpoint (if any) is mounted
+pub const ARTIFACT_CACHE_DIR_PATH: &str = "/var/cache/butido-artifacts";
+
+/// The environment variable that tells the script which compiler cache tool is available
+pub const COMPILER_CACHE_ENV_VAR: &str = "BUTIDO_COMPILER_CACHE";
+
+/// The environment variable that tells the script where the checkpoint of the package (if any)
+/// was restored
+pub const CHECKPOINT_ENV_VAR: &str = "BUTIDO_CHECKPOINT";
+
+/// The prefix of the environment variables that tell the script where the sources are, followed by
+/// the name of the source (e.g. `BUTIDO_SOURCE_SRC`)
+pub const SOURCE_ENV_VAR_PREFIX: &str = "BUTIDO_SOURCE_";
+
+/// The prefix of the temporary directories inside a release store in which artifacts are
+/// prepared before they are moved into place by a release.
+/// These directories are ignored when loading the release store.
+pub const RELEASE_TMP_DIR_PREFIX: &str = ".butido-release-";
+
+/// The labels of the containers butido creates, to find the job a container belongs to
+pub const CONTAINER_LABEL_JOB_UUID: &str = "butido.job.uuid";
+pub const CONTAINER_LABEL_PACKAGE_NAME: &str = "butido.package.name";
+pub const CONTAINER_LABEL_PACKAGE_VERSION: &str = "butido.package.version";
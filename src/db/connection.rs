@@ -19,7 +19,7 @@ use tracing::debug;
 
 use crate::config::Configuration;
 
-#[derive(Getters)]
+#[derive(Clone, Copy, Getters)]
 pub struct DbConnectionConfig<'a> {
     #[getset(get = "pub")]
     database_host: &'a str,
@@ -80,7 +80,7 @@ impl<'a> DbConnectionConfig<'a> {
         })
     }
 
-    fn get_database_uri(self) -> String {
+    pub fn get_database_uri(self) -> String {
         format!(
             "postgres://{user}:{password}@{host}:{port}/{name}?connect_timeout={timeout}",
             host = self.database_host,
@@ -10,8 +10,10 @@
 
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
 
+use anyhow::anyhow;
 use anyhow::Result;
 use chrono::NaiveDateTime;
 use diesel::r2d2::ConnectionManager;
@@ -76,13 +78,79 @@ pub struct FindArtifacts<'a> {
     #[builder(default)]
     image_name: Option<&'a ImageName>,
 
+    /// Filter for the architecture the artifacts were built for
+    #[builder(default)]
+    arch: Option<&'a String>,
+
+    /// Only find artifacts of submits of this commit of the repository (the ID of the githash)
+    #[builder(default)]
+    repo_hash_id: Option<i32>,
+
     /// Search for this package
     package: &'a Package,
 }
 
+/// An artifact that was found, with the IDs of the artifact and the submit that built it
+#[derive(Debug)]
+pub struct FoundArtifact<'a> {
+    pub path: FullArtifactPath<'a>,
+
+    /// The date of the release, if the artifact was released
+    pub release_date: Option<NaiveDateTime>,
+    pub artifact_id: i32,
+    pub submit_id: i32,
+}
+
+/// Which artifacts of previous submits may be reused instead of running a job (see
+/// `build --reuse-artifacts`)
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReuseArtifacts {
+    /// Never reuse artifacts, all jobs are run
+    Never,
+
+    /// Only reuse artifacts that were built from the same commit of the repository
+    SameGithash,
+
+    /// Reuse the artifacts of any commit
+    Any,
+}
+
+impl ReuseArtifacts {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReuseArtifacts::Never => "never",
+            ReuseArtifacts::SameGithash => "same-githash",
+            ReuseArtifacts::Any => "any",
+        }
+    }
+}
+
+impl FromStr for ReuseArtifacts {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "never" => Ok(ReuseArtifacts::Never),
+            "same-githash" => Ok(ReuseArtifacts::SameGithash),
+            "any" => Ok(ReuseArtifacts::Any),
+            other => Err(anyhow!("Unknown artifact reuse policy: {}", other)),
+        }
+    }
+}
+
 impl<'a> FindArtifacts<'a> {
     /// Run the FindArtifact as configured
     pub fn run(self) -> Result<Vec<(FullArtifactPath<'a>, Option<NaiveDateTime>)>> {
+        self.find().map(|found| {
+            found
+                .into_iter()
+                .map(|artifact| (artifact.path, artifact.release_date))
+                .collect()
+        })
+    }
+
+    /// Run the FindArtifact as configured, with the artifacts and submits in the database
+    pub fn find(self) -> Result<Vec<FoundArtifact<'a>>> {
         let shebang = Shebang::from(self.config.shebang().clone());
         let script = if self.script_filter {
             let script = ScriptBuilder::new(&shebang).build(
@@ -146,6 +214,14 @@ impl<'a> FindArtifacts<'a> {
             query = query.filter(schema::images::name.eq(image_name.as_ref()));
         }
 
+        if let Some(arch) = self.arch {
+            query = query.filter(schema::jobs::arch.eq(arch));
+        }
+
+        if let Some(repo_hash_id) = self.repo_hash_id {
+            query = query.filter(schema::submits::repo_hash_id.eq(repo_hash_id));
+        }
+
         trace!("Query = {}", diesel::debug_query(&query));
 
         query
@@ -184,22 +260,32 @@ impl<'a> FindArtifacts<'a> {
                 let envs_equal =
                     environments_equal(&job_env, package_environment.as_ref(), self.env_filter);
                 trace!("environments where equal = {}", envs_equal);
-                Ok((tpl.0, envs_equal))
+                Ok(((tpl.0, job.submit_id), envs_equal))
             })
             .filter(|r| match r {
                 // the actual filtering from above
                 Err(_) => true,
                 Ok((_, bl)) => *bl,
             })
-            .and_then_ok(|(art, _)| {
+            .and_then_ok(|((art, submit_id), _)| {
                 if let Some(release) = art.get_release(&mut self.database_pool.get().unwrap())? {
-                    Ok((art, Some(release.release_date)))
+                    // The artifact might be stored at a different path in the release store
+                    let release_path =
+                        ArtifactPath::new(PathBuf::from(release.path_in_store(&art)))?;
+                    Ok((art, submit_id, Some((release.release_date, release_path))))
                 } else {
-                    Ok((art, None))
+                    Ok((art, submit_id, None))
                 }
             })
-            .and_then_ok(|(p, ndt)| ArtifactPath::new(PathBuf::from(p.path)).map(|a| (a, ndt)))
-            .and_then_ok(|(artpath, ndt)| {
+            .and_then_ok(|(art, submit_id, rel)| {
+                let artpath = ArtifactPath::new(PathBuf::from(&art.path))?;
+                let (ndt, release_path) = rel.unzip();
+                let found = |path| FoundArtifact {
+                    path,
+                    release_date: ndt,
+                    artifact_id: art.id,
+                    submit_id,
+                };
                 if let Some(staging) = self.staging_store.as_ref() {
                     trace!(
                         "Searching in staging: {:?} for {:?}",
@@ -208,20 +294,18 @@ impl<'a> FindArtifacts<'a> {
                     );
                     if let Some(art) = staging.get(&artpath) {
                         trace!("Found in staging: {:?}", art);
-                        return staging.root_path().join(art).map(|p| p.map(|p| (p, ndt)));
+                        return staging.root_path().join(art).map(|p| p.map(found));
                     }
                 }
 
                 // If we cannot find the artifact in the release store either, we return None.
                 // This is the case if there indeed was a release, but it was removed from the
                 // filesystem.
+                let release_path = release_path.as_ref().unwrap_or(&artpath);
                 for release_store in self.release_stores {
-                    if let Some(art) = release_store.get(&artpath) {
+                    if let Some(art) = release_store.get(release_path) {
                         trace!("Found in release: {:?}", art);
-                        return release_store
-                            .root_path()
-                            .join(art)
-                            .map(|p| p.map(|p| (p, ndt)));
+                        return release_store.root_path().join(art).map(|p| p.map(found));
                     }
                 }
 
@@ -232,7 +316,7 @@ impl<'a> FindArtifacts<'a> {
                 Ok(None)
             })
             .filter_map_ok(|opt| opt)
-            .collect::<Result<Vec<(FullArtifactPath<'a>, Option<NaiveDateTime>)>>>()
+            .collect::<Result<Vec<FoundArtifact<'a>>>>()
     }
 }
 
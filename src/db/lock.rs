@@ -0,0 +1,257 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Locks that prevent conflicting submits from running at the same time
+//!
+//! Submits that build the same packages (or use the same staging directory) at the same time
+//! race on the staging store and the endpoints. Therefore, a submit locks its staging directory
+//! and, while a job builds a package, the package with PostgreSQL advisory locks. Packages whose
+//! artifacts are reused are not locked, so that submits that only share dependencies do not
+//! conflict. The locks belong to a database session, so they are released by the database if
+//! butido is killed.
+
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::PoisonError;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use diesel::r2d2::ConnectionManager;
+use diesel::r2d2::Pool;
+use diesel::r2d2::PooledConnection;
+use diesel::sql_types::BigInt;
+use diesel::sql_types::Bool;
+use diesel::sql_types::Integer;
+use diesel::sql_types::Nullable;
+use diesel::sql_types::Text;
+use diesel::PgConnection;
+use diesel::QueryableByName;
+use diesel::RunQueryDsl;
+use sha2::Digest;
+use tracing::{debug, warn};
+
+/// The interval in which a submit that waits for a lock checks whether the lock is free
+const LOCK_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// The locks of one submit, which are released when this object is dropped
+pub struct SubmitLocks {
+    conn: Mutex<PooledConnection<ConnectionManager<PgConnection>>>,
+
+    /// Whether to wait for locks that are held by other submits instead of failing
+    wait: bool,
+}
+
+/// The lock of a single resource of a submit (see `SubmitLocks::lock()`), which is released
+/// when this object is dropped
+pub struct ResourceLock<'a> {
+    locks: &'a SubmitLocks,
+    resource: String,
+    key: i64,
+}
+
+#[derive(QueryableByName)]
+struct LockResult {
+    #[diesel(sql_type = Bool)]
+    locked: bool,
+}
+
+#[derive(QueryableByName)]
+struct LockHolder {
+    #[diesel(sql_type = Text)]
+    application_name: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    client_addr: Option<String>,
+    #[diesel(sql_type = Integer)]
+    pid: i32,
+}
+
+impl std::fmt::Display for LockHolder {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "'{}' (from {}, database PID {})",
+            self.application_name,
+            self.client_addr.as_deref().unwrap_or("local socket"),
+            self.pid
+        )
+    }
+}
+
+impl SubmitLocks {
+    /// Lock the passed resources (e.g., "staging directory /foo") for the submit
+    ///
+    /// If a resource is locked by another submit, this either fails with an error that names the
+    /// holder of the lock or, if `wait` is set, waits until the lock is released. The same holds
+    /// for the resources that are locked later with `lock()`.
+    pub async fn acquire(
+        pool: &Pool<ConnectionManager<PgConnection>>,
+        submit_id: &uuid::Uuid,
+        mut resources: Vec<String>,
+        wait: bool,
+    ) -> Result<Self> {
+        let mut conn = pool.get()?;
+
+        // The application name is shown to the other submits if they conflict with this one
+        let application_name = format!(
+            "butido {} submit {}",
+            std::env::var("USER").unwrap_or_else(|_| String::from("unknown")),
+            submit_id.hyphenated()
+        );
+        diesel::sql_query("SELECT set_config('application_name', $1, false)")
+            .bind::<Text, _>(&application_name)
+            .execute(&mut conn)
+            .context("Setting the application name of the database session")?;
+
+        let locks = SubmitLocks {
+            conn: Mutex::new(conn),
+            wait,
+        };
+
+        // Always locking in the same order prevents deadlocks between waiting submits
+        resources.sort();
+        resources.dedup();
+
+        for resource in resources {
+            locks.lock_resource(&resource, lock_key(&resource)).await?;
+        }
+
+        Ok(locks)
+    }
+
+    /// Lock one more resource (e.g., "package foo 1.0") for the submit until the returned lock
+    /// is dropped
+    ///
+    /// Returns whether the lock had to be waited for, i.e., whether another submit held it.
+    /// A resource can be locked several times by the same submit.
+    pub async fn lock(&self, resource: String) -> Result<(ResourceLock<'_>, bool)> {
+        let key = lock_key(&resource);
+        let waited = self.lock_resource(&resource, key).await?;
+        Ok((
+            ResourceLock {
+                locks: self,
+                resource,
+                key,
+            },
+            waited,
+        ))
+    }
+
+    /// Lock the key of the resource, returns whether the lock had to be waited for
+    async fn lock_resource(&self, resource: &str, key: i64) -> Result<bool> {
+        let mut waiting = false;
+
+        while !self.try_lock(key)? {
+            let holder = self
+                .holder(key)?
+                .map(|holder| holder.to_string())
+                .unwrap_or_else(|| String::from("an unknown submit"));
+
+            if !self.wait {
+                return Err(anyhow!("{} is locked by {}", resource, holder)).context(
+                    "Another submit conflicts with this one, use --wait-for-locks to wait for it",
+                );
+            }
+
+            if !waiting {
+                warn!("Waiting for {} (locked by {})", resource, holder);
+                waiting = true;
+            }
+            tokio::time::sleep(LOCK_POLL_INTERVAL).await;
+        }
+        debug!("Locked {}", resource);
+        Ok(waiting)
+    }
+
+    fn connection(&self) -> MutexGuard<'_, PooledConnection<ConnectionManager<PgConnection>>> {
+        // The connection is only used for single statements, so it is fine to use it after a
+        // panic of another thread
+        self.conn.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn try_lock(&self, key: i64) -> Result<bool> {
+        diesel::sql_query("SELECT pg_try_advisory_lock($1) AS locked")
+            .bind::<BigInt, _>(key)
+            .get_result::<LockResult>(&mut *self.connection())
+            .map(|result| result.locked)
+            .context("Acquiring advisory lock")
+    }
+
+    fn unlock(&self, key: i64) -> Result<()> {
+        diesel::sql_query("SELECT pg_advisory_unlock($1) AS locked")
+            .bind::<BigInt, _>(key)
+            .get_result::<LockResult>(&mut *self.connection())
+            .context("Releasing advisory lock")?;
+        Ok(())
+    }
+
+    fn holder(&self, key: i64) -> Result<Option<LockHolder>> {
+        // A bigint key is shown with its high half as "classid" and its low half as "objid"
+        let (high, low) = ((key as u64 >> 32) as i64, (key as u64 & 0xffff_ffff) as i64);
+
+        diesel::sql_query(indoc::indoc!(
+            r#"
+                SELECT a.application_name, host(a.client_addr) AS client_addr, a.pid
+                FROM pg_locks l JOIN pg_stat_activity a ON a.pid = l.pid
+                WHERE l.locktype = 'advisory' AND l.granted AND l.objsubid = 1
+                    AND l.classid::bigint = $1 AND l.objid::bigint = $2
+                    AND l.pid <> pg_backend_pid()
+                LIMIT 1
+            "#
+        ))
+        .bind::<BigInt, _>(high)
+        .bind::<BigInt, _>(low)
+        .load::<LockHolder>(&mut *self.connection())
+        .map(|holders| holders.into_iter().next())
+        .context("Finding the holder of an advisory lock")
+    }
+}
+
+impl Drop for SubmitLocks {
+    fn drop(&mut self) {
+        // The connection goes back into the pool, so the locks have to be released explicitly
+        let released = diesel::sql_query(
+            "SELECT pg_advisory_unlock_all(), set_config('application_name', '', false)",
+        )
+        .execute(&mut *self.connection());
+
+        if let Err(e) = released {
+            warn!("Failed to release the locks of the submit: {}", e);
+        }
+    }
+}
+
+impl Drop for ResourceLock<'_> {
+    fn drop(&mut self) {
+        match self.locks.unlock(self.key) {
+            Ok(()) => debug!("Unlocked {}", self.resource),
+            Err(e) => warn!("Failed to release the lock of {}: {}", self.resource, e),
+        }
+    }
+}
+
+/// Map a resource name to the key of its advisory lock
+fn lock_key(resource: &str) -> i64 {
+    let digest = sha2::Sha256::digest(resource.as_bytes());
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    i64::from_be_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_key_is_stable() {
+        assert_eq!(lock_key("package a 1"), lock_key("package a 1"));
+        assert_ne!(lock_key("package a 1"), lock_key("package a 2"));
+    }
+}
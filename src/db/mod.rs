@@ -11,7 +11,12 @@
 mod connection;
 pub use connection::*;
 
+mod lock;
+pub use lock::ResourceLock;
+pub use lock::SubmitLocks;
+
 mod find_artifacts;
 pub use find_artifacts::FindArtifacts;
+pub use find_artifacts::ReuseArtifacts;
 
 pub mod models;
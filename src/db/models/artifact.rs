@@ -20,6 +20,7 @@ use diesel::prelude::*;
 
 use crate::db::models::Job;
 use crate::db::models::Release;
+use crate::db::models::ReleaseStore;
 use crate::schema::artifacts;
 use crate::schema::artifacts::*;
 
@@ -29,6 +30,11 @@ pub struct Artifact {
     pub id: i32,
     pub path: String,
     pub job_id: i32,
+    pub size: Option<i64>,
+    pub kind: String,
+
+    /// The SHA-256 hash of the file (not recorded for artifacts of older versions of butido)
+    pub sha256: Option<String>,
 }
 
 #[derive(Insertable)]
@@ -36,6 +42,9 @@ pub struct Artifact {
 struct NewArtifact<'a> {
     pub path: &'a str,
     pub job_id: i32,
+    pub size: Option<i64>,
+    pub kind: &'a str,
+    pub sha256: Option<&'a str>,
 }
 
 impl Artifact {
@@ -48,9 +57,17 @@ impl Artifact {
         database_connection: &mut PgConnection,
         release_date: &NaiveDateTime,
         release_store_name: &str,
+        release_path: &str,
     ) -> Result<crate::db::models::Release> {
         let rs = crate::db::models::ReleaseStore::create(database_connection, release_store_name)?;
-        crate::db::models::Release::create(database_connection, &self, release_date, &rs)
+        crate::db::models::Release::create(
+            database_connection,
+            &self,
+            release_date,
+            &rs,
+            release_path,
+            None,
+        )
     }
 
     pub fn get_release(&self, database_connection: &mut PgConnection) -> Result<Option<Release>> {
@@ -65,10 +82,127 @@ impl Artifact {
             .map_err(Error::from)
     }
 
+    /// The artifacts of a job, with the releases (and the release stores) of each artifact
+    ///
+    /// The artifacts are sorted by path, the releases by date.
+    pub fn of_job_with_releases(
+        database_connection: &mut PgConnection,
+        job: &Job,
+    ) -> Result<Vec<(Artifact, Vec<(Release, ReleaseStore)>)>> {
+        use crate::schema;
+
+        let rows = schema::artifacts::table
+            .filter(schema::artifacts::job_id.eq(job.id))
+            .left_join(schema::releases::table.inner_join(schema::release_stores::table))
+            .order_by((schema::artifacts::path, schema::releases::release_date))
+            .select((
+                schema::artifacts::all_columns,
+                (
+                    schema::releases::all_columns,
+                    schema::release_stores::all_columns,
+                )
+                    .nullable(),
+            ))
+            .load::<(Artifact, Option<(Release, ReleaseStore)>)>(database_connection)?;
+
+        let mut artifacts = Vec::<(Artifact, Vec<_>)>::new();
+        for (artifact, release) in rows {
+            match artifacts.last_mut() {
+                Some((last, releases)) if last.id == artifact.id => releases.extend(release),
+                _ => artifacts.push((artifact, release.into_iter().collect())),
+            }
+        }
+        Ok(artifacts)
+    }
+
+    /// The SHA-256 hash that was recorded for an artifact when it was built
+    ///
+    /// `path` is the path of the artifact in the store it was found in: the staging store of the
+    /// submit (if `release_store_name` is `None`) or the release store with the passed name.
+    pub fn recorded_sha256(
+        database_connection: &mut PgConnection,
+        art_path: &str,
+        submit_id: i32,
+        release_store_name: Option<&str>,
+    ) -> Result<Option<String>> {
+        use crate::schema;
+
+        let recorded = match release_store_name {
+            None => schema::artifacts::table
+                .inner_join(schema::jobs::table)
+                .filter(schema::jobs::submit_id.eq(submit_id))
+                .filter(schema::artifacts::path.eq(art_path))
+                .select(schema::artifacts::sha256)
+                .order_by(schema::artifacts::id.desc())
+                .first::<Option<String>>(database_connection)
+                .optional()?,
+
+            // The path in the release store is the path of the artifact, unless the release store
+            // has a layout. The last release is the one that is in the store.
+            Some(store_name) => schema::releases::table
+                .inner_join(schema::artifacts::table)
+                .inner_join(schema::release_stores::table)
+                .filter(schema::release_stores::store_name.eq(store_name))
+                .filter(
+                    schema::releases::path
+                        .eq(art_path)
+                        .or(schema::releases::path
+                            .is_null()
+                            .and(schema::artifacts::path.eq(art_path))),
+                )
+                .select(schema::artifacts::sha256)
+                .order_by(schema::releases::release_date.desc())
+                .first::<Option<String>>(database_connection)
+                .optional()?,
+        };
+        Ok(recorded.flatten())
+    }
+
+    /// The total size (in bytes) of the artifacts of the last build of a package
+    ///
+    /// Returns `None` if the package was not built yet (or the sizes of its artifacts are
+    /// unknown).
+    pub fn last_build_size(
+        database_connection: &mut PgConnection,
+        package_name: &str,
+        package_version: &str,
+    ) -> Result<Option<u64>> {
+        use crate::schema;
+
+        let last_job = schema::artifacts::table
+            .inner_join(schema::jobs::table.inner_join(schema::packages::table))
+            .filter(schema::packages::name.eq(package_name))
+            .filter(schema::packages::version.eq(package_version))
+            .filter(schema::artifacts::size.is_not_null())
+            .select(schema::jobs::id)
+            .order_by(schema::jobs::id.desc())
+            .first::<i32>(database_connection)
+            .optional()?;
+
+        let Some(last_job) = last_job else {
+            return Ok(None);
+        };
+
+        let sizes = dsl::artifacts
+            .filter(job_id.eq(last_job))
+            .select(size)
+            .load::<Option<i64>>(database_connection)?;
+        Ok(Some(
+            sizes
+                .into_iter()
+                .flatten()
+                .map(|s| u64::try_from(s).unwrap_or_default())
+                .sum(),
+        ))
+    }
+
     pub fn create(
         database_connection: &mut PgConnection,
         art_path: &ArtifactPath,
         job: &Job,
+        art_size: Option<u64>,
+        art_kind: &str,
+        art_sha256: Option<&str>,
     ) -> Result<Artifact> {
         let path_str = art_path
             .to_str()
@@ -77,6 +211,12 @@ impl Artifact {
         let new_art = NewArtifact {
             path: path_str,
             job_id: job.id,
+            size: art_size
+                .map(i64::try_from)
+                .transpose()
+                .context("Artifact size does not fit into the database")?,
+            kind: art_kind,
+            sha256: art_sha256,
         };
 
         database_connection.transaction::<_, Error, _>(|conn| {
@@ -0,0 +1,106 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! The artifacts that are cached on the endpoints (see `artifact_cache` in the endpoint
+//! configuration)
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::db::models::Endpoint;
+use crate::schema::endpoint_artifacts;
+use crate::schema::endpoint_artifacts::*;
+
+#[derive(Debug, Identifiable, Queryable, Associations)]
+#[diesel(belongs_to(Endpoint))]
+#[diesel(table_name = endpoint_artifacts)]
+pub struct EndpointArtifact {
+    pub id: i32,
+    pub endpoint_id: i32,
+    pub volume: String,
+    pub sha256: String,
+    pub file_name: String,
+    pub cached_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = endpoint_artifacts)]
+struct NewEndpointArtifact<'a> {
+    pub endpoint_id: i32,
+    pub volume: &'a str,
+    pub sha256: &'a str,
+    pub file_name: &'a str,
+    pub cached_at: &'a NaiveDateTime,
+}
+
+impl EndpointArtifact {
+    /// Record that an artifact was uploaded to the cache volume of an endpoint
+    ///
+    /// If the artifact was recorded in the meantime (by another job), the existing entry is kept.
+    pub fn create(
+        database_connection: &mut PgConnection,
+        ep_id: i32,
+        cache_volume: &str,
+        hash: &str,
+        cached_file_name: &str,
+    ) -> Result<()> {
+        let now = chrono::offset::Local::now().naive_local();
+        let new_entry = NewEndpointArtifact {
+            endpoint_id: ep_id,
+            volume: cache_volume,
+            sha256: hash,
+            file_name: cached_file_name,
+            cached_at: &now,
+        };
+
+        diesel::insert_into(endpoint_artifacts::table)
+            .values(&new_entry)
+            .on_conflict_do_nothing()
+            .execute(database_connection)?;
+        Ok(())
+    }
+
+    /// The cache entry of the artifact with the passed hash, if it is cached on the endpoint
+    pub fn find(
+        database_connection: &mut PgConnection,
+        ep_id: i32,
+        cache_volume: &str,
+        hash: &str,
+    ) -> Result<Option<EndpointArtifact>> {
+        dsl::endpoint_artifacts
+            .filter(endpoint_id.eq(ep_id))
+            .filter(volume.eq(cache_volume))
+            .filter(sha256.eq(hash))
+            .first::<EndpointArtifact>(database_connection)
+            .optional()
+            .map_err(anyhow::Error::from)
+    }
+
+    /// Remove the cache entry of an artifact, e.g. because it is missing in the cache volume
+    ///
+    /// Only the entry for `cached_file_name` is removed, an entry that was recorded by another
+    /// job in the meantime is kept.
+    pub fn remove(
+        database_connection: &mut PgConnection,
+        ep_id: i32,
+        cache_volume: &str,
+        hash: &str,
+        cached_file_name: &str,
+    ) -> Result<()> {
+        diesel::delete(dsl::endpoint_artifacts)
+            .filter(endpoint_id.eq(ep_id))
+            .filter(volume.eq(cache_volume))
+            .filter(sha256.eq(hash))
+            .filter(file_name.eq(cached_file_name))
+            .execute(database_connection)?;
+        Ok(())
+    }
+}
@@ -8,13 +8,19 @@
 // SPDX-License-Identifier: EPL-2.0
 //
 
+use std::str::FromStr;
+
+use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Error;
 use anyhow::Result;
+use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use tracing::trace;
 
 use crate::db::models::{Endpoint, Image, Package, Submit};
+use crate::job::CacheKey;
+use crate::log::ParsedLog;
 use crate::package::Script;
 use crate::schema::jobs;
 use crate::schema::jobs::*;
@@ -36,6 +42,21 @@ pub struct Job {
     pub script_text: String,
     pub log_text: String,
     pub uuid: ::uuid::Uuid,
+    pub cache_key: Option<String>,
+    pub started_at: Option<NaiveDateTime>,
+    pub finished_at: Option<NaiveDateTime>,
+    pub butido_version: Option<String>,
+    pub image_digest: Option<String>,
+    pub endpoint_docker_version: Option<String>,
+    pub sources_hash: Option<String>,
+    pub reported_artifacts: Vec<String>,
+    pub warnings: Option<i32>,
+    pub failure_class: Option<String>,
+    pub retried: bool,
+    pub arch: Option<String>,
+
+    /// The artifacts of the dependencies that were put into the container of the job
+    pub input_artifacts: Vec<String>,
 }
 
 #[derive(Debug, Insertable)]
@@ -49,6 +70,17 @@ struct NewJob<'a> {
     pub script_text: String,
     pub log_text: String,
     pub uuid: &'a ::uuid::Uuid,
+    pub cache_key: Option<&'a str>,
+    pub started_at: &'a NaiveDateTime,
+    pub finished_at: &'a NaiveDateTime,
+    pub butido_version: &'a str,
+    pub image_digest: Option<&'a str>,
+    pub endpoint_docker_version: Option<&'a str>,
+    pub sources_hash: &'a str,
+    pub reported_artifacts: Vec<String>,
+    pub warnings: Option<i32>,
+    pub arch: Option<&'a str>,
+    pub input_artifacts: Vec<String>,
 }
 
 impl Job {
@@ -63,7 +95,20 @@ impl Job {
         container: &ContainerHash,
         script: &Script,
         log: &str,
+        cache_key: Option<&CacheKey>,
+        job_started_at: &NaiveDateTime,
+        job_finished_at: &NaiveDateTime,
+        job_image_digest: Option<&str>,
+        docker_version: Option<&str>,
+        job_sources_hash: &str,
+        job_arch: Option<&str>,
+        job_input_artifacts: Vec<String>,
     ) -> Result<Job> {
+        // The structured results the script reported via markers are stored next to the log
+        let markers = ParsedLog::from_str(log)
+            .with_context(|| format!("Parsing log of job {job_uuid}"))?
+            .markers();
+
         let new_job = NewJob {
             uuid: job_uuid,
             submit_id: submit.id,
@@ -73,6 +118,19 @@ impl Job {
             container_hash: container.as_ref(),
             script_text: script.as_ref().replace('\0', ""),
             log_text: log.replace('\0', ""),
+            cache_key: cache_key.map(CacheKey::as_ref),
+            started_at: job_started_at,
+            finished_at: job_finished_at,
+            butido_version: env!("CARGO_PKG_VERSION"),
+            image_digest: job_image_digest,
+            endpoint_docker_version: docker_version,
+            sources_hash: job_sources_hash,
+            reported_artifacts: markers.artifacts,
+            warnings: markers
+                .warnings
+                .map(|n| i32::try_from(n).unwrap_or(i32::MAX)),
+            arch: job_arch,
+            input_artifacts: job_input_artifacts,
         };
 
         trace!("Creating Job in database: {:?}", new_job);
@@ -95,6 +153,62 @@ impl Job {
         })
     }
 
+    pub fn with_uuid(
+        database_connection: &mut PgConnection,
+        job_uuid: &::uuid::Uuid,
+    ) -> Result<Option<Job>> {
+        dsl::jobs
+            .filter(uuid.eq(job_uuid))
+            .first::<Job>(database_connection)
+            .optional()
+            .with_context(|| anyhow!("Loading job {}", job_uuid))
+    }
+
+    /// The job that ran in the container with the passed ID, if there is one
+    pub fn with_container_hash(
+        database_connection: &mut PgConnection,
+        hash: &str,
+    ) -> Result<Option<Job>> {
+        dsl::jobs
+            .filter(container_hash.eq(hash))
+            .first::<Job>(database_connection)
+            .optional()
+            .with_context(|| anyhow!("Loading the job of container {}", hash))
+    }
+
+    /// Record the class of the failure of the job (see `crate::log::FailureClassifiers`)
+    pub fn set_failure_class(
+        &self,
+        database_connection: &mut PgConnection,
+        class: &str,
+    ) -> Result<()> {
+        diesel::update(self)
+            .set(failure_class.eq(class))
+            .execute(database_connection)
+            .map(|_| ())
+            .with_context(|| anyhow!("Setting failure class of job {}", self.uuid))
+    }
+
+    /// Mark the job as a failed attempt that is retried
+    ///
+    /// The attempt gets a new UUID, so that the UUID of the job refers to the final attempt.
+    pub fn mark_retried(&self, database_connection: &mut PgConnection) -> Result<()> {
+        diesel::update(self)
+            .set((uuid.eq(::uuid::Uuid::new_v4()), retried.eq(true)))
+            .execute(database_connection)
+            .map(|_| ())
+            .with_context(|| anyhow!("Marking job {} as retried", self.uuid))
+    }
+
+    /// The time the job took to run, if it was recorded
+    ///
+    /// Jobs that were created before the start and finish times were recorded have no duration.
+    pub fn duration(&self) -> Option<chrono::Duration> {
+        self.started_at
+            .zip(self.finished_at)
+            .map(|(started, finished)| finished - started)
+    }
+
     pub fn env(
         &self,
         database_connection: &mut PgConnection,
@@ -0,0 +1,54 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+use anyhow::Result;
+use diesel::prelude::*;
+
+use crate::db::models::Job;
+use crate::schema::job_probes;
+
+/// The output of an environment probe (e.g. `gcc --version`) in the container of a job
+#[derive(Debug, Identifiable, Queryable, Associations)]
+#[diesel(belongs_to(Job))]
+#[diesel(table_name = job_probes)]
+pub struct JobProbe {
+    pub id: i32,
+    pub job_id: i32,
+    pub name: String,
+    pub output: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = job_probes)]
+struct NewJobProbe<'a> {
+    pub job_id: i32,
+    pub name: &'a str,
+    pub output: &'a str,
+}
+
+impl JobProbe {
+    pub fn create(
+        database_connection: &mut PgConnection,
+        job: &Job,
+        name: &str,
+        output: &str,
+    ) -> Result<()> {
+        let new_probe = NewJobProbe {
+            job_id: job.id,
+            name,
+            output,
+        };
+
+        diesel::insert_into(job_probes::table)
+            .values(&new_probe)
+            .execute(database_connection)?;
+        Ok(())
+    }
+}
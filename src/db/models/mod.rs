@@ -14,6 +14,9 @@ pub use artifact::*;
 mod endpoint;
 pub use endpoint::*;
 
+mod endpoint_artifact;
+pub use endpoint_artifact::*;
+
 mod envvar;
 pub use envvar::*;
 
@@ -26,17 +29,41 @@ pub use job::*;
 mod job_env;
 pub use job_env::*;
 
+mod job_probe;
+pub use job_probe::*;
+
 mod githash;
 pub use githash::*;
 
 mod package;
 pub use package::*;
 
+mod queued_job;
+pub use queued_job::*;
+
+mod queued_submit;
+pub use queued_submit::*;
+
 mod releases;
 pub use releases::*;
 
+mod release_attestation;
+pub use release_attestation::*;
+
+mod release_group;
+pub use release_group::*;
+
+mod release_mirror_file;
+pub use release_mirror_file::*;
+
 mod release_store;
 pub use release_store::*;
 
+mod reused_artifact;
+pub use reused_artifact::*;
+
+mod store_snapshot;
+pub use store_snapshot::*;
+
 mod submit;
 pub use submit::*;
@@ -22,13 +22,22 @@ pub struct Package {
     pub id: i32,
     pub name: String,
     pub version: String,
+    pub maintainer: Option<String>,
+    pub license: Option<String>,
+    pub description: Option<String>,
+    pub homepage: Option<String>,
 }
 
-#[derive(Insertable)]
+#[derive(Insertable, AsChangeset)]
 #[diesel(table_name = packages)]
+#[diesel(treat_none_as_null = true)]
 struct NewPackage<'a> {
     pub name: &'a str,
     pub version: &'a str,
+    pub maintainer: Option<&'a str>,
+    pub license: Option<&'a str>,
+    pub description: Option<&'a str>,
+    pub homepage: Option<&'a str>,
 }
 
 impl Package {
@@ -39,24 +48,20 @@ impl Package {
         let new_package = NewPackage {
             name: p.name().deref(),
             version: p.version().deref(),
+            maintainer: p.maintainer().as_deref(),
+            license: p.license().as_deref(),
+            description: p.description().as_deref(),
+            homepage: p.homepage().as_deref(),
         };
 
-        database_connection.transaction::<_, Error, _>(|conn| {
-            diesel::insert_into(packages::table)
-                .values(&new_package)
-                .on_conflict_do_nothing()
-                .execute(conn)?;
-
-            dsl::packages
-                .filter({
-                    let p_name = p.name().deref();
-                    let p_vers = p.version().deref();
-
-                    name.eq(p_name).and(version.eq(p_vers))
-                })
-                .first::<Package>(conn)
-                .map_err(Error::from)
-        })
+        // The metadata is updated if the package exists already, it reflects the latest submit
+        diesel::insert_into(packages::table)
+            .values(&new_package)
+            .on_conflict((name, version))
+            .do_update()
+            .set(&new_package)
+            .get_result::<Package>(database_connection)
+            .map_err(Error::from)
     }
 
     pub fn fetch_for_job(
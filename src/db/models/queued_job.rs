@@ -0,0 +1,375 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! The job queue that is shared by all butido processes that use the same database
+//!
+//! Before a job is dispatched to an endpoint, it is added to the queue and waits until it is the
+//! next job in the queue. Only jobs that are dispatchable, i.e. for which their butido process
+//! has a free endpoint that can run them, take part in the ordering, so a job that cannot be
+//! placed does not block the jobs of other submits. The next job is the dispatchable one with the
+//! highest priority. If multiple jobs have the same priority, the submit that was served least
+//! recently is preferred (so that one large submit does not starve other submits), then the job
+//! that was enqueued first.
+//!
+//! The butido processes refresh the heartbeats of their jobs (the waiting and the dispatched
+//! ones) regularly. Jobs with a stale heartbeat belong to a process that was killed and are
+//! removed from the queue.
+
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sql_types::Integer;
+use diesel::sql_types::Timestamptz;
+
+use crate::db::models::Submit;
+use crate::schema::job_queue;
+use crate::schema::job_queue::*;
+
+/// Jobs whose heartbeat is older than this are considered dead (e.g., because the butido process
+/// was killed)
+const STALE_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, Eq, PartialEq, Identifiable, Queryable, Associations)]
+#[diesel(belongs_to(Submit))]
+#[diesel(table_name = job_queue)]
+pub struct QueuedJob {
+    pub id: i32,
+    pub submit_id: i32,
+    pub job_uuid: ::uuid::Uuid,
+    pub priority: i32,
+    pub enqueued_at: NaiveDateTime,
+    pub heartbeat: NaiveDateTime,
+    pub dispatched_at: Option<NaiveDateTime>,
+    pub dispatchable: bool,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = job_queue)]
+struct NewQueuedJob<'a> {
+    pub submit_id: i32,
+    pub job_uuid: &'a ::uuid::Uuid,
+    pub priority: i32,
+    pub enqueued_at: &'a NaiveDateTime,
+    pub heartbeat: &'a NaiveDateTime,
+}
+
+impl QueuedJob {
+    /// The heartbeat of a job is refreshed if it is older than this
+    pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+    /// Add a job to the queue
+    pub fn enqueue(
+        database_connection: &mut PgConnection,
+        submit: &Submit,
+        job: &::uuid::Uuid,
+        job_priority: i32,
+    ) -> Result<QueuedJob> {
+        let now = chrono::offset::Local::now().naive_local();
+        let new_job = NewQueuedJob {
+            submit_id: submit.id,
+            job_uuid: job,
+            priority: job_priority,
+            enqueued_at: &now,
+            heartbeat: &now,
+        };
+
+        database_connection.transaction::<_, anyhow::Error, _>(|conn| {
+            remove_stale(conn, &now)?;
+
+            // A job that is rescheduled (see `EndpointScheduler::may_reschedule()`) is already in the
+            // queue and waits again
+            diesel::insert_into(job_queue::table)
+                .values(&new_job)
+                .on_conflict(job_uuid)
+                .do_update()
+                .set((
+                    heartbeat.eq(&now),
+                    dispatched_at.eq(None::<NaiveDateTime>),
+                    dispatchable.eq(false),
+                ))
+                .get_result::<QueuedJob>(conn)
+                .with_context(|| anyhow!("Adding job {} to the job queue", job))
+        })
+    }
+
+    /// Check whether this job is the next job in the queue
+    ///
+    /// `can_dispatch` tells whether the calling process currently has a free endpoint that can
+    /// run the job. A job that cannot be dispatched is never the next job and is skipped by the
+    /// other waiting jobs.
+    ///
+    /// This also refreshes the heartbeat of the job, so it has to be called regularly while the
+    /// job waits. A job that was removed from the queue in the meantime (because its heartbeat
+    /// was stale) is enqueued again at its old position. Jobs of other processes with a stale
+    /// heartbeat are removed from the queue.
+    pub fn is_next(
+        &mut self,
+        database_connection: &mut PgConnection,
+        can_dispatch: bool,
+    ) -> Result<bool> {
+        let now = chrono::offset::Local::now().naive_local();
+        if is_older_than(&self.heartbeat, &now, Self::HEARTBEAT_INTERVAL)
+            || self.dispatchable != can_dispatch
+        {
+            let updated = diesel::update(&*self)
+                .set((heartbeat.eq(&now), dispatchable.eq(can_dispatch)))
+                .execute(database_connection)
+                .with_context(|| anyhow!("Refreshing heartbeat of queued job {}", self.job_uuid))?;
+            if updated == 0 {
+                self.requeue(database_connection, &now, can_dispatch)?;
+            }
+            self.heartbeat = now;
+            self.dispatchable = can_dispatch;
+        }
+
+        if !can_dispatch {
+            return Ok(false);
+        }
+
+        let stale = remove_stale(database_connection, &now)?;
+        let next = diesel::sql_query(indoc::indoc!(
+            r#"
+                SELECT q.id
+                FROM job_queue q
+                WHERE q.dispatched_at IS NULL AND q.dispatchable AND q.heartbeat >= $1
+                ORDER BY
+                    q.priority DESC,
+                    (
+                        SELECT max(d.dispatched_at) FROM job_queue d
+                        WHERE d.submit_id = q.submit_id
+                    ) ASC NULLS FIRST,
+                    q.enqueued_at,
+                    q.id
+                LIMIT 1
+            "#
+        ))
+        .bind::<Timestamptz, _>(stale)
+        .get_result::<QueueHead>(database_connection)
+        .optional()
+        .context("Finding the next job in the job queue")?;
+
+        Ok(next.map(|head| head.id) == Some(self.id))
+    }
+
+    /// Add the job to the queue again after it was removed, with its old priority and position
+    fn requeue(
+        &mut self,
+        database_connection: &mut PgConnection,
+        now: &NaiveDateTime,
+        can_dispatch: bool,
+    ) -> Result<()> {
+        let new_job = NewQueuedJob {
+            submit_id: self.submit_id,
+            job_uuid: &self.job_uuid,
+            priority: self.priority,
+            enqueued_at: &self.enqueued_at,
+            heartbeat: now,
+        };
+        *self = diesel::insert_into(job_queue::table)
+            .values(&new_job)
+            .get_result::<QueuedJob>(database_connection)
+            .with_context(|| anyhow!("Adding job {} to the job queue again", self.job_uuid))?;
+        if can_dispatch {
+            diesel::update(&*self)
+                .set(dispatchable.eq(true))
+                .execute(database_connection)
+                .with_context(|| anyhow!("Updating queued job {}", self.job_uuid))?;
+        }
+        Ok(())
+    }
+
+    /// Refresh the heartbeats of all jobs of a submit in the queue
+    ///
+    /// This has to be called regularly (see `HEARTBEAT_INTERVAL`) while the submit runs, so that
+    /// its dispatched jobs are not removed from the queue.
+    pub fn refresh_heartbeats(
+        database_connection: &mut PgConnection,
+        submit: &Submit,
+    ) -> Result<()> {
+        let now = chrono::offset::Local::now().naive_local();
+        diesel::update(job_queue::table)
+            .filter(submit_id.eq(submit.id))
+            .set(heartbeat.eq(&now))
+            .execute(database_connection)
+            .map(|_| ())
+            .with_context(|| {
+                anyhow!(
+                    "Refreshing heartbeats of the jobs of submit {}",
+                    submit.uuid
+                )
+            })
+    }
+
+    /// Mark the job as dispatched to an endpoint
+    pub fn mark_dispatched(&self, database_connection: &mut PgConnection) -> Result<()> {
+        let now = chrono::offset::Local::now().naive_local();
+        diesel::update(self)
+            .set(dispatched_at.eq(&now))
+            .execute(database_connection)
+            .map(|_| ())
+            .with_context(|| anyhow!("Marking queued job {} as dispatched", self.job_uuid))
+    }
+
+    /// Remove all jobs of a submit from the queue (after the submit finished)
+    pub fn remove_all_of_submit(
+        database_connection: &mut PgConnection,
+        submit: &Submit,
+    ) -> Result<()> {
+        diesel::delete(job_queue::table)
+            .filter(submit_id.eq(submit.id))
+            .execute(database_connection)
+            .map(|_| ())
+            .with_context(|| anyhow!("Removing jobs of submit {} from the job queue", submit.uuid))
+    }
+}
+
+/// Remove the jobs with a stale heartbeat, waiting or dispatched, from the queue
+///
+/// Jobs of butido processes that were killed would otherwise stay in the queue forever (and the
+/// dispatched ones would count for the fairness between the submits). Returns the time before
+/// which a heartbeat is stale.
+fn remove_stale(
+    database_connection: &mut PgConnection,
+    now: &NaiveDateTime,
+) -> Result<NaiveDateTime> {
+    let stale = *now - chrono::Duration::from_std(STALE_TIMEOUT)?;
+    diesel::delete(job_queue::table)
+        .filter(heartbeat.lt(stale))
+        .execute(database_connection)
+        .context("Removing stale jobs from the job queue")?;
+    Ok(stale)
+}
+
+fn is_older_than(time: &NaiveDateTime, now: &NaiveDateTime, duration: Duration) -> bool {
+    // A negative difference (clock skew between hosts) counts as "not older"
+    (*now - *time)
+        .to_std()
+        .map(|elapsed| elapsed > duration)
+        .unwrap_or(false)
+}
+
+/// The ID of the next job in the queue
+#[derive(QueryableByName)]
+struct QueueHead {
+    #[diesel(sql_type = Integer)]
+    id: i32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel_migrations::embed_migrations;
+    use diesel_migrations::EmbeddedMigrations;
+    use diesel_migrations::MigrationHarness;
+
+    use crate::db::models::GitHash;
+    use crate::db::models::Image;
+    use crate::db::models::Package;
+    use crate::util::docker::ImageName;
+
+    const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+    /// Connect to the database in `BUTIDO_TEST_DATABASE_URL`
+    ///
+    /// Everything the test writes is rolled back at the end.
+    fn test_database() -> PgConnection {
+        let url = std::env::var("BUTIDO_TEST_DATABASE_URL")
+            .expect("BUTIDO_TEST_DATABASE_URL must point to a test database");
+        let mut conn = PgConnection::establish(&url).unwrap();
+        conn.run_pending_migrations(MIGRATIONS).unwrap();
+        conn.begin_test_transaction().unwrap();
+        conn
+    }
+
+    fn submit(conn: &mut PgConnection) -> Submit {
+        let image =
+            Image::create_or_fetch(conn, &ImageName::from(String::from("test-image"))).unwrap();
+        let package = Package::create_or_fetch(
+            conn,
+            &crate::package::tests::package("a", "1", "https://rust-lang.org", "123"),
+        )
+        .unwrap();
+        let githash = GitHash::create_or_fetch(conn, "0123456789abcdef").unwrap();
+        let now = chrono::offset::Local::now().naive_local();
+        Submit::create(
+            conn,
+            &now,
+            &::uuid::Uuid::new_v4(),
+            &image,
+            &package,
+            &githash,
+            &[],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    #[ignore = "needs a database in BUTIDO_TEST_DATABASE_URL"]
+    fn test_blocked_head_does_not_starve_dispatchable_job() {
+        let mut conn = test_database();
+        let blocked_submit = submit(&mut conn);
+        let other_submit = submit(&mut conn);
+
+        let mut head =
+            QueuedJob::enqueue(&mut conn, &blocked_submit, &::uuid::Uuid::new_v4(), 10).unwrap();
+        let mut other =
+            QueuedJob::enqueue(&mut conn, &other_submit, &::uuid::Uuid::new_v4(), 0).unwrap();
+
+        // The head has no free endpoint, the other job is dispatched although its priority is lower
+        assert!(!head.is_next(&mut conn, false).unwrap());
+        assert!(other.is_next(&mut conn, true).unwrap());
+        other.mark_dispatched(&mut conn).unwrap();
+
+        // As soon as the head can be dispatched, it is the next job again
+        assert!(head.is_next(&mut conn, true).unwrap());
+    }
+
+    #[test]
+    #[ignore = "needs a database in BUTIDO_TEST_DATABASE_URL"]
+    fn test_stale_jobs_are_removed() {
+        let mut conn = test_database();
+        let dead_submit = submit(&mut conn);
+        let live_submit = submit(&mut conn);
+
+        let dead = QueuedJob::enqueue(&mut conn, &dead_submit, &::uuid::Uuid::new_v4(), 0).unwrap();
+        dead.mark_dispatched(&mut conn).unwrap();
+        let mut live =
+            QueuedJob::enqueue(&mut conn, &live_submit, &::uuid::Uuid::new_v4(), 0).unwrap();
+
+        // The process of the dead job was killed and does not refresh its heartbeats anymore
+        let long_ago = chrono::offset::Local::now().naive_local() - chrono::Duration::minutes(5);
+        diesel::update(&dead)
+            .set(heartbeat.eq(&long_ago))
+            .execute(&mut conn)
+            .unwrap();
+        assert!(live.is_next(&mut conn, true).unwrap());
+        assert_eq!(
+            job_queue::table
+                .filter(id.eq(dead.id))
+                .count()
+                .get_result::<i64>(&mut conn)
+                .unwrap(),
+            0
+        );
+
+        // A live job that was removed because of a stale heartbeat is enqueued again
+        diesel::update(&live)
+            .set(heartbeat.eq(&long_ago))
+            .execute(&mut conn)
+            .unwrap();
+        QueuedJob::enqueue(&mut conn, &dead_submit, &::uuid::Uuid::new_v4(), 0).unwrap();
+        live.heartbeat = long_ago;
+        assert!(live.is_next(&mut conn, true).unwrap());
+    }
+}
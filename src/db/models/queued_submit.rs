@@ -0,0 +1,154 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Error;
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::db::models::GitHash;
+use crate::schema::queued_submits;
+use crate::schema::queued_submits::*;
+
+/// Running submits whose heartbeat is older than this are considered dead (e.g., because the
+/// daemon that ran them was killed)
+const STALE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The state of a submit that was enqueued with `butido build --enqueue`
+#[derive(Clone, Copy, Debug, Eq, PartialEq, parse_display::Display)]
+#[display(style = "lowercase")]
+pub enum QueuedSubmitState {
+    /// The submit waits for a daemon (`butido daemon`) to run it
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// A submit that was enqueued to be run by the daemon (`butido daemon`)
+///
+/// The arguments are the arguments of the `build` subcommand, which are passed to `butido build`
+/// when the daemon runs the submit.
+#[derive(Clone, Debug, Eq, PartialEq, Identifiable, Queryable)]
+#[diesel(table_name = queued_submits)]
+pub struct QueuedSubmit {
+    pub id: i32,
+    pub uuid: ::uuid::Uuid,
+    pub repo_hash_id: i32,
+    pub arguments: Vec<String>,
+    pub state: String,
+    pub enqueued_at: NaiveDateTime,
+    pub started_at: Option<NaiveDateTime>,
+    pub finished_at: Option<NaiveDateTime>,
+    pub heartbeat: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = queued_submits)]
+struct NewQueuedSubmit<'a> {
+    pub uuid: &'a ::uuid::Uuid,
+    pub repo_hash_id: i32,
+    pub arguments: &'a [String],
+    pub state: String,
+    pub enqueued_at: &'a NaiveDateTime,
+}
+
+impl QueuedSubmit {
+    /// The daemon refreshes the heartbeat of the submit it runs in this interval
+    pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+    pub fn create(
+        database_connection: &mut PgConnection,
+        submit_id: &::uuid::Uuid,
+        repo_hash: &GitHash,
+        build_arguments: &[String],
+    ) -> Result<QueuedSubmit> {
+        let now = chrono::offset::Local::now().naive_local();
+        let new_submit = NewQueuedSubmit {
+            uuid: submit_id,
+            repo_hash_id: repo_hash.id,
+            arguments: build_arguments,
+            state: QueuedSubmitState::Queued.to_string(),
+            enqueued_at: &now,
+        };
+
+        diesel::insert_into(queued_submits::table)
+            .values(&new_submit)
+            .get_result::<QueuedSubmit>(database_connection)
+            .context("Inserting new queued submit into queued_submits table")
+    }
+
+    /// Take the submit that was enqueued first and mark it as running
+    ///
+    /// The row is locked while doing so, so that multiple daemons never run the same submit.
+    /// Running submits whose heartbeat is stale (see `refresh_heartbeat()`) were claimed by a
+    /// daemon that was killed, they are claimed (and run) again.
+    pub fn claim_next(database_connection: &mut PgConnection) -> Result<Option<QueuedSubmit>> {
+        let now = chrono::offset::Local::now().naive_local();
+        let stale = now - chrono::Duration::from_std(STALE_TIMEOUT)?;
+
+        database_connection.transaction::<_, Error, _>(|conn| {
+            let next = dsl::queued_submits
+                .filter(
+                    state.eq(QueuedSubmitState::Queued.to_string()).or(state
+                        .eq(QueuedSubmitState::Running.to_string())
+                        .and(heartbeat.is_null().or(heartbeat.lt(stale)))),
+                )
+                .order_by(id.asc())
+                .for_update()
+                .skip_locked()
+                .first::<QueuedSubmit>(conn)
+                .optional()
+                .context("Loading the next queued submit")?;
+
+            next.map(|next| {
+                diesel::update(&next)
+                    .set((
+                        state.eq(QueuedSubmitState::Running.to_string()),
+                        started_at.eq(&now),
+                        heartbeat.eq(&now),
+                    ))
+                    .get_result::<QueuedSubmit>(conn)
+                    .with_context(|| anyhow!("Marking queued submit {} as running", next.uuid))
+            })
+            .transpose()
+        })
+    }
+
+    /// Record that the daemon still runs the submit
+    ///
+    /// This has to be called regularly (see `HEARTBEAT_INTERVAL`) while the submit runs.
+    pub fn refresh_heartbeat(&self, database_connection: &mut PgConnection) -> Result<()> {
+        let now = chrono::offset::Local::now().naive_local();
+        diesel::update(self)
+            .set(heartbeat.eq(&now))
+            .execute(database_connection)
+            .map(|_| ())
+            .with_context(|| anyhow!("Refreshing the heartbeat of queued submit {}", self.uuid))
+    }
+
+    /// Record that the daemon finished running the submit
+    pub fn finish(
+        &self,
+        database_connection: &mut PgConnection,
+        new_state: QueuedSubmitState,
+    ) -> Result<()> {
+        let now = chrono::offset::Local::now().naive_local();
+        diesel::update(self)
+            .set((state.eq(new_state.to_string()), finished_at.eq(&now)))
+            .execute(database_connection)
+            .map(|_| ())
+            .with_context(|| anyhow!("Updating the state of queued submit {}", self.uuid))
+    }
+}
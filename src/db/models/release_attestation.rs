@@ -0,0 +1,79 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! The provenance attestations of released artifacts
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::db::models::Release;
+use crate::schema::release_attestations;
+
+/// The provenance attestation (a DSSE envelope with an in-toto statement) of a release
+#[derive(Debug, Identifiable, Queryable, Associations)]
+#[diesel(belongs_to(Release))]
+#[diesel(table_name = release_attestations)]
+pub struct ReleaseAttestation {
+    pub id: i32,
+    pub release_id: i32,
+
+    /// The DSSE envelope as JSON, as it was written next to the released artifact
+    pub envelope: String,
+
+    /// Whether the envelope contains a signature
+    pub signed: bool,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = release_attestations)]
+struct NewReleaseAttestation<'a> {
+    pub release_id: i32,
+    pub envelope: &'a str,
+    pub signed: bool,
+    pub created_at: &'a NaiveDateTime,
+}
+
+impl ReleaseAttestation {
+    pub fn create(
+        database_connection: &mut PgConnection,
+        release: &Release,
+        envelope: &str,
+        signed: bool,
+        created_at: &NaiveDateTime,
+    ) -> Result<()> {
+        let new_attestation = NewReleaseAttestation {
+            release_id: release.id,
+            envelope,
+            signed,
+            created_at,
+        };
+
+        diesel::insert_into(release_attestations::table)
+            .values(&new_attestation)
+            .execute(database_connection)
+            .with_context(|| anyhow!("Recording the attestation of release {}", release.id))?;
+        Ok(())
+    }
+
+    /// The attestation of a release, if one was recorded
+    pub fn of_release(
+        database_connection: &mut PgConnection,
+        release: &Release,
+    ) -> Result<Option<ReleaseAttestation>> {
+        ReleaseAttestation::belonging_to(release)
+            .first::<ReleaseAttestation>(database_connection)
+            .optional()
+            .with_context(|| anyhow!("Loading the attestation of release {}", release.id))
+    }
+}
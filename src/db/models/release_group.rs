@@ -0,0 +1,102 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Error;
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::db::models::ReleaseStore;
+use crate::schema::release_groups;
+use crate::schema::release_groups::*;
+
+/// A group of releases that were made with one release operation
+#[derive(Debug, Identifiable, Queryable, Associations)]
+#[diesel(belongs_to(ReleaseStore))]
+#[diesel(table_name = release_groups)]
+pub struct ReleaseGroup {
+    pub id: i32,
+    pub uuid: ::uuid::Uuid,
+    pub release_date: NaiveDateTime,
+    pub release_store_id: i32,
+    pub name: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = release_groups)]
+struct NewReleaseGroup<'a> {
+    pub uuid: &'a ::uuid::Uuid,
+    pub release_date: &'a NaiveDateTime,
+    pub release_store_id: i32,
+    pub name: Option<&'a str>,
+}
+
+impl ReleaseGroup {
+    pub fn create(
+        database_connection: &mut PgConnection,
+        group_uuid: &::uuid::Uuid,
+        date: &NaiveDateTime,
+        store: &ReleaseStore,
+        group_name: Option<&str>,
+    ) -> Result<ReleaseGroup> {
+        let new_group = NewReleaseGroup {
+            uuid: group_uuid,
+            release_date: date,
+            release_store_id: store.id,
+            name: group_name,
+        };
+
+        database_connection.transaction::<_, Error, _>(|conn| {
+            diesel::insert_into(release_groups::table)
+                .values(&new_group)
+                .execute(conn)
+                .context("Inserting new release group into release_groups table")?;
+
+            Self::with_uuid(conn, group_uuid)
+        })
+    }
+
+    pub fn with_uuid(
+        database_connection: &mut PgConnection,
+        group_uuid: &::uuid::Uuid,
+    ) -> Result<ReleaseGroup> {
+        dsl::release_groups
+            .filter(release_groups::uuid.eq(group_uuid))
+            .first::<ReleaseGroup>(database_connection)
+            .with_context(|| anyhow!("Loading release group {}", group_uuid))
+    }
+
+    pub fn with_name(
+        database_connection: &mut PgConnection,
+        group_name: &str,
+    ) -> Result<Option<ReleaseGroup>> {
+        dsl::release_groups
+            .filter(release_groups::name.eq(group_name))
+            .first::<ReleaseGroup>(database_connection)
+            .optional()
+            .with_context(|| anyhow!("Loading release group {}", group_name))
+    }
+
+    /// Load the release group with the name or UUID `group`
+    pub fn with_name_or_uuid(
+        database_connection: &mut PgConnection,
+        group: &str,
+    ) -> Result<ReleaseGroup> {
+        if let Some(found) = Self::with_name(database_connection, group)? {
+            return Ok(found);
+        }
+
+        let group_uuid = ::uuid::Uuid::parse_str(group)
+            .map_err(|_| anyhow!("No release group with the name or UUID {}", group))?;
+        Self::with_uuid(database_connection, &group_uuid)
+    }
+}
@@ -0,0 +1,121 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! The files of a release store that were synced to a remote mirror
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Error;
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+
+use crate::db::models::ReleaseStore;
+use crate::schema::release_mirror_files;
+
+/// The maximum number of files that are inserted with one statement
+///
+/// PostgreSQL limits the number of bind parameters of a statement.
+const INSERT_CHUNK_SIZE: usize = 5_000;
+
+/// A file as it was synced to a mirror, with the size and modification time it had at that time
+#[derive(Debug, Identifiable, Queryable, Associations)]
+#[diesel(belongs_to(ReleaseStore))]
+#[diesel(table_name = release_mirror_files)]
+pub struct ReleaseMirrorFile {
+    pub id: i32,
+    pub release_store_id: i32,
+    pub target: String,
+    pub path: String,
+    pub size: i64,
+    pub modified_at: NaiveDateTime,
+    pub synced_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = release_mirror_files)]
+struct NewReleaseMirrorFile<'a> {
+    pub release_store_id: i32,
+    pub target: &'a str,
+    pub path: &'a str,
+    pub size: i64,
+    pub modified_at: &'a NaiveDateTime,
+    pub synced_at: &'a NaiveDateTime,
+}
+
+impl ReleaseMirrorFile {
+    /// The files that were synced from the release store to the mirror `target`
+    pub fn of_target(
+        database_connection: &mut PgConnection,
+        store: &ReleaseStore,
+        target: &str,
+    ) -> Result<Vec<ReleaseMirrorFile>> {
+        ReleaseMirrorFile::belonging_to(store)
+            .filter(release_mirror_files::target.eq(target))
+            .load::<ReleaseMirrorFile>(database_connection)
+            .with_context(|| anyhow!("Loading the files synced to {}", target))
+    }
+
+    /// Record a sync to the mirror `target`: the files (path, size and modification time) that
+    /// were transferred and the paths of the files that were removed
+    pub fn record_sync(
+        database_connection: &mut PgConnection,
+        store: &ReleaseStore,
+        target: &str,
+        transferred: &[(String, i64, NaiveDateTime)],
+        removed: &[String],
+    ) -> Result<()> {
+        let now = chrono::offset::Local::now().naive_local();
+
+        database_connection.transaction::<_, Error, _>(|conn| {
+            diesel::delete(
+                ReleaseMirrorFile::belonging_to(store)
+                    .filter(release_mirror_files::target.eq(target))
+                    .filter(release_mirror_files::path.eq_any(removed)),
+            )
+            .execute(conn)
+            .context("Removing files from release_mirror_files table")?;
+
+            for chunk in transferred.chunks(INSERT_CHUNK_SIZE) {
+                let new_files = chunk
+                    .iter()
+                    .map(|(path, size, modified_at)| NewReleaseMirrorFile {
+                        release_store_id: store.id,
+                        target,
+                        path,
+                        size: *size,
+                        modified_at,
+                        synced_at: &now,
+                    })
+                    .collect::<Vec<_>>();
+
+                diesel::insert_into(release_mirror_files::table)
+                    .values(&new_files)
+                    .on_conflict((
+                        release_mirror_files::release_store_id,
+                        release_mirror_files::target,
+                        release_mirror_files::path,
+                    ))
+                    .do_update()
+                    .set((
+                        release_mirror_files::size.eq(excluded(release_mirror_files::size)),
+                        release_mirror_files::modified_at
+                            .eq(excluded(release_mirror_files::modified_at)),
+                        release_mirror_files::synced_at
+                            .eq(excluded(release_mirror_files::synced_at)),
+                    ))
+                    .execute(conn)
+                    .context("Recording files in release_mirror_files table")?;
+            }
+            Ok(())
+        })
+    }
+}
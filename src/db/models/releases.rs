@@ -14,6 +14,7 @@ use chrono::NaiveDateTime;
 use diesel::prelude::*;
 
 use crate::db::models::Artifact;
+use crate::db::models::ReleaseGroup;
 use crate::db::models::ReleaseStore;
 use crate::schema::releases;
 use crate::schema::releases::*;
@@ -21,11 +22,14 @@ use crate::schema::releases::*;
 #[derive(Debug, Identifiable, Queryable, Associations)]
 #[diesel(belongs_to(Artifact))]
 #[diesel(belongs_to(ReleaseStore))]
+#[diesel(belongs_to(ReleaseGroup))]
 pub struct Release {
     pub id: i32,
     pub artifact_id: i32,
     pub release_date: NaiveDateTime,
     pub release_store_id: i32,
+    pub path: Option<String>,
+    pub release_group_id: Option<i32>,
 }
 
 #[derive(Insertable)]
@@ -34,6 +38,8 @@ struct NewRelease<'a> {
     pub artifact_id: i32,
     pub release_date: &'a NaiveDateTime,
     pub release_store_id: i32,
+    pub path: Option<&'a str>,
+    pub release_group_id: Option<i32>,
 }
 
 impl Release {
@@ -42,11 +48,15 @@ impl Release {
         art: &Artifact,
         date: &'a NaiveDateTime,
         store: &'a ReleaseStore,
+        release_path: &'a str,
+        group: Option<&'a ReleaseGroup>,
     ) -> Result<Release> {
         let new_rel = NewRelease {
             artifact_id: art.id,
             release_date: date,
             release_store_id: store.id,
+            path: Some(release_path),
+            release_group_id: group.map(|g| g.id),
         };
 
         database_connection.transaction::<_, Error, _>(|conn| {
@@ -60,4 +70,12 @@ impl Release {
                 .map_err(Error::from)
         })
     }
+
+    /// The path of the released artifact, relative to the release store
+    ///
+    /// Releases that were made before the path was recorded in the database use the path of the
+    /// artifact.
+    pub fn path_in_store<'a>(&'a self, art: &'a Artifact) -> &'a str {
+        self.path.as_deref().unwrap_or(&art.path)
+    }
 }
@@ -0,0 +1,94 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! The artifacts of previous submits that were reused instead of running a job
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use diesel::prelude::*;
+
+use crate::db::models::{Artifact, Submit};
+use crate::db::ReuseArtifacts;
+use crate::schema::reused_artifacts;
+use crate::schema::submits;
+
+/// An artifact that was reused for a job of a submit (see `build --reuse-artifacts`)
+#[derive(Debug, Identifiable, Queryable, Associations)]
+#[diesel(belongs_to(Submit))]
+#[diesel(belongs_to(Artifact))]
+#[diesel(table_name = reused_artifacts)]
+pub struct ReusedArtifact {
+    pub id: i32,
+    pub submit_id: i32,
+
+    /// The UUID of the job that was not run because the artifact was reused
+    pub job_uuid: ::uuid::Uuid,
+    pub artifact_id: i32,
+
+    /// The submit that built the artifact
+    pub source_submit_id: i32,
+
+    /// The reuse policy of the submit
+    pub policy: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = reused_artifacts)]
+struct NewReusedArtifact<'a> {
+    pub submit_id: i32,
+    pub job_uuid: &'a ::uuid::Uuid,
+    pub artifact_id: i32,
+    pub source_submit_id: i32,
+    pub policy: &'a str,
+}
+
+impl ReusedArtifact {
+    pub fn create(
+        database_connection: &mut PgConnection,
+        submit: &Submit,
+        job_uuid: &::uuid::Uuid,
+        artifact_id: i32,
+        source_submit_id: i32,
+        policy: ReuseArtifacts,
+    ) -> Result<()> {
+        let new_reused_artifact = NewReusedArtifact {
+            submit_id: submit.id,
+            job_uuid,
+            artifact_id,
+            source_submit_id,
+            policy: policy.as_str(),
+        };
+
+        diesel::insert_into(reused_artifacts::table)
+            .values(&new_reused_artifact)
+            .execute(database_connection)
+            .with_context(|| anyhow!("Recording reused artifact for job {}", job_uuid))?;
+        Ok(())
+    }
+
+    /// The artifacts reused by the submit, with the UUID of the submit that built them
+    pub fn of_submit(
+        database_connection: &mut PgConnection,
+        submit: &Submit,
+    ) -> Result<Vec<(ReusedArtifact, Artifact, ::uuid::Uuid)>> {
+        ReusedArtifact::belonging_to(submit)
+            .inner_join(crate::schema::artifacts::table)
+            .inner_join(submits::table.on(submits::id.eq(reused_artifacts::source_submit_id)))
+            .order_by(reused_artifacts::id)
+            .select((
+                reused_artifacts::all_columns,
+                crate::schema::artifacts::all_columns,
+                submits::uuid,
+            ))
+            .load(database_connection)
+            .with_context(|| anyhow!("Loading the reused artifacts of submit {}", submit.uuid))
+    }
+}
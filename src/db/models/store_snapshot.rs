@@ -0,0 +1,131 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Frozen snapshots of the contents of release stores, to resolve the dependencies of a submit
+//! against them
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Error;
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::db::models::ReleaseStore;
+use crate::schema::store_snapshot_artifacts;
+use crate::schema::store_snapshots;
+
+/// The maximum number of artifacts that are inserted with one statement
+///
+/// PostgreSQL limits the number of bind parameters of a statement.
+const INSERT_CHUNK_SIZE: usize = 10_000;
+
+#[derive(Debug, Identifiable, Queryable, Associations)]
+#[diesel(belongs_to(ReleaseStore))]
+#[diesel(table_name = store_snapshots)]
+pub struct StoreSnapshot {
+    pub id: i32,
+    pub name: String,
+    pub release_store_id: i32,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = store_snapshots)]
+struct NewStoreSnapshot<'a> {
+    pub name: &'a str,
+    pub release_store_id: i32,
+    pub created_at: &'a NaiveDateTime,
+}
+
+/// An artifact in a snapshot, with the path relative to the release store
+#[derive(Debug, Identifiable, Queryable, Associations)]
+#[diesel(belongs_to(StoreSnapshot))]
+#[diesel(table_name = store_snapshot_artifacts)]
+pub struct StoreSnapshotArtifact {
+    pub id: i32,
+    pub store_snapshot_id: i32,
+    pub path: String,
+    pub sha256: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = store_snapshot_artifacts)]
+struct NewStoreSnapshotArtifact<'a> {
+    pub store_snapshot_id: i32,
+    pub path: &'a str,
+    pub sha256: &'a str,
+}
+
+impl StoreSnapshot {
+    /// Record the artifacts (path and SHA-256 hash) of a release store as a snapshot
+    pub fn create(
+        database_connection: &mut PgConnection,
+        snapshot_name: &str,
+        store: &ReleaseStore,
+        artifacts: &[(String, String)],
+    ) -> Result<StoreSnapshot> {
+        let now = chrono::offset::Local::now().naive_local();
+        let new_snapshot = NewStoreSnapshot {
+            name: snapshot_name,
+            release_store_id: store.id,
+            created_at: &now,
+        };
+
+        database_connection.transaction::<_, Error, _>(|conn| {
+            if Self::with_name(conn, snapshot_name)?.is_some() {
+                return Err(anyhow!("Snapshot '{}' exists already", snapshot_name));
+            }
+
+            let snapshot = diesel::insert_into(store_snapshots::table)
+                .values(&new_snapshot)
+                .get_result::<StoreSnapshot>(conn)
+                .context("Inserting new snapshot into store_snapshots table")?;
+
+            for chunk in artifacts.chunks(INSERT_CHUNK_SIZE) {
+                let new_artifacts = chunk
+                    .iter()
+                    .map(|(path, sha256)| NewStoreSnapshotArtifact {
+                        store_snapshot_id: snapshot.id,
+                        path,
+                        sha256,
+                    })
+                    .collect::<Vec<_>>();
+
+                diesel::insert_into(store_snapshot_artifacts::table)
+                    .values(&new_artifacts)
+                    .execute(conn)
+                    .context("Inserting artifacts into store_snapshot_artifacts table")?;
+            }
+
+            Ok(snapshot)
+        })
+    }
+
+    pub fn with_name(
+        database_connection: &mut PgConnection,
+        snapshot_name: &str,
+    ) -> Result<Option<StoreSnapshot>> {
+        store_snapshots::table
+            .filter(store_snapshots::name.eq(snapshot_name))
+            .first::<StoreSnapshot>(database_connection)
+            .optional()
+            .with_context(|| anyhow!("Loading snapshot '{}'", snapshot_name))
+    }
+
+    pub fn artifacts(
+        &self,
+        database_connection: &mut PgConnection,
+    ) -> Result<Vec<StoreSnapshotArtifact>> {
+        StoreSnapshotArtifact::belonging_to(self)
+            .load::<StoreSnapshotArtifact>(database_connection)
+            .with_context(|| anyhow!("Loading the artifacts of snapshot '{}'", self.name))
+    }
+}
@@ -8,6 +8,7 @@
 // SPDX-License-Identifier: EPL-2.0
 //
 
+use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Error;
 use anyhow::Result;
@@ -20,6 +21,26 @@ use crate::db::models::Package;
 use crate::schema::submits;
 use crate::schema::submits::*;
 
+/// The state of a submit
+///
+/// A submit that stays in a non-final state (created or running) is stale, e.g. because butido
+/// crashed during the build (see `butido db submits --stale`).
+#[derive(Clone, Copy, Debug, Eq, PartialEq, parse_display::Display)]
+#[display(style = "lowercase")]
+pub enum SubmitState {
+    /// The submit is recorded, but the build did not start yet
+    Created,
+    Running,
+    Finished,
+    /// The build could not be set up or was interrupted by an error
+    Aborted,
+}
+
+impl SubmitState {
+    /// The states of submits whose build is (or should be) still in progress
+    pub const NON_FINAL: [SubmitState; 2] = [SubmitState::Created, SubmitState::Running];
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Identifiable, Queryable, Associations)]
 #[diesel(belongs_to(Package, foreign_key = requested_package_id))]
 #[diesel(belongs_to(Image, foreign_key = requested_image_id))]
@@ -31,6 +52,8 @@ pub struct Submit {
     pub requested_image_id: i32,
     pub requested_package_id: i32,
     pub repo_hash_id: i32,
+    pub phase_modifications: Vec<String>,
+    pub state: String,
 }
 
 #[derive(Insertable)]
@@ -41,6 +64,8 @@ struct NewSubmit<'a> {
     pub requested_image_id: i32,
     pub requested_package_id: i32,
     pub repo_hash_id: i32,
+    pub phase_modifications: &'a [String],
+    pub state: String,
 }
 
 impl Submit {
@@ -51,6 +76,7 @@ impl Submit {
         requested_image: &Image,
         requested_package: &Package,
         repo_hash: &GitHash,
+        phase_modifications: &[String],
     ) -> Result<Submit> {
         let new_submit = NewSubmit {
             uuid: submit_id,
@@ -58,13 +84,17 @@ impl Submit {
             requested_image_id: requested_image.id,
             requested_package_id: requested_package.id,
             repo_hash_id: repo_hash.id,
+            phase_modifications,
+            state: SubmitState::Created.to_string(),
         };
 
         database_connection.transaction::<_, Error, _>(|conn| {
             diesel::insert_into(submits::table)
                 .values(&new_submit)
                 // required because if we re-use the staging store, we do not create a new UUID but re-use the old one
-                .on_conflict_do_nothing()
+                .on_conflict(submits::uuid)
+                .do_update()
+                .set(state.eq(SubmitState::Created.to_string()))
                 .execute(conn)
                 .context("Inserting new submit into submits table")?;
 
@@ -72,6 +102,19 @@ impl Submit {
         })
     }
 
+    /// Record the state of the submit
+    pub fn set_state(
+        &self,
+        database_connection: &mut PgConnection,
+        new_state: SubmitState,
+    ) -> Result<()> {
+        diesel::update(self)
+            .set(state.eq(new_state.to_string()))
+            .execute(database_connection)
+            .map(|_| ())
+            .with_context(|| anyhow!("Setting the state of submit {} to {}", self.uuid, new_state))
+    }
+
     pub fn with_id(
         database_connection: &mut PgConnection,
         submit_id: &::uuid::Uuid,
@@ -8,7 +8,9 @@
 // SPDX-License-Identifier: EPL-2.0
 //
 
+use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
+use std::path::Path;
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -25,21 +27,37 @@ use shiplift::ExecContainerOptions;
 use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::RwLock;
 use tokio_stream::StreamExt;
-use tracing::{debug, trace};
+use tracing::{debug, trace, warn};
 use typed_builder::TypedBuilder;
 
 use crate::config::EndpointName;
+use crate::config::EnvironmentProbe;
+use crate::endpoint::upload::chunk_stream;
+use crate::endpoint::upload::directory_archive;
+use crate::endpoint::upload::file_archive_stream;
+use crate::endpoint::upload::file_sha256;
+use crate::endpoint::upload::symlink_archive;
+use crate::endpoint::DiskUsage;
+use crate::endpoint::EndpointArtifactCache;
 use crate::endpoint::EndpointConfiguration;
+use crate::endpoint::RecordedChecksums;
+use crate::endpoint::UploadProgress;
 use crate::filestore::path::ArtifactPath;
+use crate::filestore::Checkpoint;
+use crate::filestore::CheckpointStore;
 use crate::filestore::ReleaseStore;
 use crate::filestore::StagingStore;
 use crate::job::JobResource;
 use crate::job::RunnableJob;
 use crate::log::buffer_stream_to_line_stream;
 use crate::log::LogItem;
+use crate::package::PackageName;
 use crate::package::Script;
+use crate::util::archive::ArchiveEntry;
+use crate::util::archive::NormalizedArchive;
 use crate::util::docker::ContainerHash;
 use crate::util::docker::ImageName;
+use crate::util::docker::Mount;
 
 #[derive(Getters, CopyGetters, TypedBuilder)]
 pub struct Endpoint {
@@ -58,8 +76,35 @@ pub struct Endpoint {
     #[getset(get = "pub")]
     uri: String,
 
+    /// Whether `uri` is an HTTP URI or the path of a socket
+    endpoint_type: crate::config::EndpointType,
+
+    /// The timeout for connecting to the endpoint
+    #[getset(get_copy = "pub")]
+    timeout: std::time::Duration,
+
     #[builder(default)]
     running_jobs: std::sync::atomic::AtomicUsize,
+
+    /// The compiler cache that is mounted into the build containers, if any
+    #[getset(get = "pub")]
+    compiler_cache: Option<crate::config::CompilerCache>,
+
+    /// The volume in which the artifacts of the dependencies are cached, if any
+    #[getset(get = "pub")]
+    artifact_cache: Option<String>,
+
+    /// The tags of the endpoint, see `crate::config::Endpoint::tags`
+    #[getset(get = "pub")]
+    tags: Vec<String>,
+
+    /// The architecture of the endpoint, see `crate::config::Endpoint::arch`
+    #[getset(get = "pub")]
+    arch: Option<String>,
+
+    /// Whether the endpoint was reachable when it was checked the last time
+    #[builder(default = std::sync::atomic::AtomicBool::new(true))]
+    healthy: std::sync::atomic::AtomicBool,
 }
 
 impl Debug for Endpoint {
@@ -86,7 +131,7 @@ impl Endpoint {
         let imgs_avail = Endpoint::check_images_available(epc.required_images().as_ref(), &ep);
 
         let (versions_compat, api_versions_compat, imgs_avail) = {
-            let timeout = std::time::Duration::from_secs(epc.endpoint().timeout().unwrap_or(10));
+            let timeout = ep.timeout();
             let versions_compat = tokio::time::timeout(timeout, versions_compat);
             let api_versions_compat = tokio::time::timeout(timeout, api_versions_compat);
             let imgs_avail = tokio::time::timeout(timeout, imgs_avail);
@@ -119,6 +164,7 @@ impl Endpoint {
     }
 
     fn setup_endpoint(ep_name: &EndpointName, ep: &crate::config::Endpoint) -> Result<Endpoint> {
+        let timeout = std::time::Duration::from_secs(ep.timeout().unwrap_or(10));
         match ep.endpoint_type() {
             crate::config::EndpointType::Http => shiplift::Uri::from_str(ep.uri())
                 .map(shiplift::Docker::host)
@@ -127,9 +173,15 @@ impl Endpoint {
                     Endpoint::builder()
                         .name(ep_name.clone())
                         .uri(ep.uri().clone())
+                        .endpoint_type(crate::config::EndpointType::Http)
+                        .timeout(timeout)
                         .docker(docker)
                         .num_max_jobs(ep.maxjobs())
                         .network_mode(ep.network_mode().clone())
+                        .compiler_cache(ep.compiler_cache().clone())
+                        .artifact_cache(ep.artifact_cache().clone())
+                        .tags(ep.tags().clone())
+                        .arch(ep.arch().clone())
                         .build()
                 }),
 
@@ -137,11 +189,22 @@ impl Endpoint {
                 Endpoint::builder()
                     .name(ep_name.clone())
                     .uri(ep.uri().clone())
+                    .endpoint_type(crate::config::EndpointType::Socket)
+                    .timeout(timeout)
                     .num_max_jobs(ep.maxjobs())
                     .network_mode(ep.network_mode().clone())
+                    .compiler_cache(ep.compiler_cache().clone())
+                    .artifact_cache(ep.artifact_cache().clone())
+                    .tags(ep.tags().clone())
+                    .arch(ep.arch().clone())
                     .docker(shiplift::Docker::unix(ep.uri()))
                     .build()
             }),
+
+            crate::config::EndpointType::Kubernetes => Err(anyhow!(
+                "{} is a Kubernetes endpoint, not a Docker endpoint",
+                ep_name
+            )),
         }
     }
 
@@ -227,13 +290,33 @@ impl Endpoint {
             .map(|_| ())
     }
 
+    /// Create the container of the job and copy the sources, patches, artifacts and script into it
+    ///
+    /// The artifacts are streamed into the container, at most `max_parallel_uploads` at a time.
+    /// If the endpoint has an artifact cache, `artifact_cache` must be passed.
     pub async fn prepare_container(
         &self,
         job: &RunnableJob,
         staging_store: Arc<RwLock<StagingStore>>,
         release_stores: Vec<Arc<ReleaseStore>>,
+        upload_progress: Arc<UploadProgress>,
+        max_parallel_uploads: usize,
+        artifact_cache: Option<&EndpointArtifactCache>,
+        checksums: &RecordedChecksums,
+        checkpoint: Option<&Checkpoint>,
     ) -> Result<PreparedContainer<'_>> {
-        PreparedContainer::new(self, job, staging_store, release_stores).await
+        PreparedContainer::new(
+            self,
+            job,
+            staging_store,
+            release_stores,
+            upload_progress,
+            max_parallel_uploads,
+            artifact_cache,
+            checksums,
+            checkpoint,
+        )
+        .await
     }
 
     pub fn running_jobs(&self) -> usize {
@@ -253,17 +336,38 @@ impl Endpoint {
         100.0 / max_jobs * run_jobs
     }
 
+    /// Whether the endpoint was reachable when it was checked the last time
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Check whether the endpoint is reachable and remember the result
+    ///
+    /// Returns the new health state if it changed since the last check.
+    pub async fn check_health(&self) -> Option<bool> {
+        let healthy = matches!(
+            tokio::time::timeout(self.timeout, self.ping()).await,
+            Ok(Ok(_))
+        );
+        let was_healthy = self
+            .healthy
+            .swap(healthy, std::sync::atomic::Ordering::Relaxed);
+        trace!("Health of endpoint {}: {}", self.name(), healthy);
+        (healthy != was_healthy).then_some(healthy)
+    }
+
     /// Ping the endpoint (once)
     pub async fn ping(&self) -> Result<String> {
         self.docker.ping().await.map_err(Error::from)
     }
 
     pub async fn stats(&self) -> Result<EndpointStats> {
-        self.docker
-            .info()
-            .await
-            .map(EndpointStats::from)
-            .map_err(Error::from)
+        self.info().await.map(EndpointStats::from)
+    }
+
+    /// Get the system information of the docker daemon of the endpoint
+    pub async fn info(&self) -> Result<shiplift::rep::Info> {
+        self.docker.info().await.map_err(Error::from)
     }
 
     pub async fn container_stats(&self) -> Result<Vec<ContainerStat>> {
@@ -279,6 +383,163 @@ impl Endpoint {
             .map(|containers| containers.into_iter().map(ContainerStat::from).collect())
     }
 
+    /// Get the load average of the endpoint host
+    ///
+    /// The docker API does not report the load of the host, so `/proc/loadavg` (which is not
+    /// namespaced) is read in one of the running butido containers on the endpoint.
+    /// Returns `None` if there is no running butido container in `containers` (the containers on
+    /// the endpoint, see `container_stats()`).
+    pub async fn load_average(&self, containers: &[ContainerStat]) -> Result<Option<String>> {
+        let output = self
+            .exec_in_running_butido_container(containers, vec!["cat", "/proc/loadavg"])
+            .await
+            .context("Reading load average")?;
+
+        Ok(output.map(|output| {
+            output
+                .split_whitespace()
+                .take(3)
+                .collect::<Vec<_>>()
+                .join(" ")
+        }))
+    }
+
+    /// Get the free disk space (in bytes) for the containers on the endpoint
+    ///
+    /// Only some storage drivers report the free space in the docker info (e.g. "devicemapper"),
+    /// otherwise the free space of the root file system of one of the running butido containers
+    /// is used (which is the file system of the docker storage).
+    /// Returns `None` if the free space cannot be determined.
+    pub async fn free_disk_space(&self) -> Result<Option<u64>> {
+        let info = self.info().await?;
+        let containers = self.container_stats().await?;
+        self.free_disk_space_from(&info, &containers).await
+    }
+
+    /// Get the free disk space like `free_disk_space()`, from the already fetched system
+    /// information and containers of the endpoint
+    async fn free_disk_space_from(
+        &self,
+        info: &shiplift::rep::Info,
+        containers: &[ContainerStat],
+    ) -> Result<Option<u64>> {
+        let from_driver_status = info
+            .driver_status
+            .iter()
+            .find(|entry| entry.first().map(String::as_str) == Some("Data Space Available"))
+            .and_then(|entry| entry.get(1))
+            .and_then(|space| space.parse::<bytesize::ByteSize>().ok())
+            .map(|space| space.as_u64());
+        if from_driver_status.is_some() {
+            return Ok(from_driver_status);
+        }
+
+        self.exec_in_running_butido_container(containers, vec!["df", "-P", "-k", "/"])
+            .await
+            .context("Reading free disk space")?
+            .map(|output| crate::util::disk_space::parse_df_output(&output))
+            .transpose()
+    }
+
+    /// Get the disk usage of the docker data root of the endpoint
+    ///
+    /// `info` and `containers` are the already fetched system information and containers of the
+    /// endpoint (see `info()` and `container_stats()`).
+    pub async fn disk_usage(
+        &self,
+        info: &shiplift::rep::Info,
+        containers: &[ContainerStat],
+    ) -> Result<DiskUsage> {
+        let df = self
+            .request_raw("/system/df")
+            .await
+            .context("Getting the disk usage")?;
+        let available = self.free_disk_space_from(info, containers).await?;
+        DiskUsage::from_system_df(&df, info.docker_root_dir.clone(), available)
+            .with_context(|| anyhow!("Getting the disk usage of '{}'", self.name))
+    }
+
+    /// Send a GET request to the docker API of the endpoint and return the response body
+    ///
+    /// This is for the parts of the API that shiplift does not implement.
+    async fn request_raw(&self, path: &str) -> Result<hyper::body::Bytes> {
+        let response = match self.endpoint_type {
+            crate::config::EndpointType::Http => {
+                let uri = format!("{}{}", self.uri.trim_end_matches('/'), path)
+                    .parse::<hyper::Uri>()
+                    .with_context(|| anyhow!("Parsing URI {}{}", self.uri, path))?;
+                hyper::Client::new().get(uri).await
+            }
+            crate::config::EndpointType::Socket => {
+                let uri = hyperlocal::Uri::new(&self.uri, path).into();
+                hyper::Client::builder()
+                    .build::<_, hyper::Body>(hyperlocal::UnixConnector)
+                    .get(uri)
+                    .await
+            }
+            crate::config::EndpointType::Kubernetes => {
+                return Err(anyhow!("{} is not a Docker endpoint", self.name))
+            }
+        }
+        .with_context(|| anyhow!("Requesting {} from '{}'", path, self.name))?;
+
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .with_context(|| anyhow!("Reading the response of {} from '{}'", path, self.name))?;
+        if !status.is_success() {
+            return Err(anyhow!(
+                "Requesting {} from '{}' failed with {}: {}",
+                path,
+                self.name,
+                status,
+                String::from_utf8_lossy(&body)
+            ));
+        }
+        Ok(body)
+    }
+
+    /// Execute a command in one of the running butido containers and return its output
+    ///
+    /// Returns `None` if there is no running butido container in `containers`.
+    async fn exec_in_running_butido_container(
+        &self,
+        containers: &[ContainerStat],
+        cmd: Vec<&str>,
+    ) -> Result<Option<String>> {
+        let container = containers
+            .iter()
+            .find(|container| container.is_running_butido_container());
+
+        let Some(container) = container else {
+            return Ok(None);
+        };
+
+        let exec_opts = ExecContainerOptions::builder()
+            .cmd(cmd)
+            .attach_stdout(true)
+            .build();
+
+        let output = self
+            .docker
+            .containers()
+            .get(&container.id)
+            .exec(&exec_opts)
+            .map(|chunk| chunk.map_err(Error::from))
+            .collect::<Result<Vec<_>>>()
+            .await
+            .with_context(|| anyhow!("Executing command in container {}", container.id))?
+            .into_iter()
+            .filter_map(|chunk| match chunk {
+                shiplift::tty::TtyChunk::StdOut(v) => Some(v),
+                _ => None,
+            })
+            .flatten()
+            .collect::<Vec<u8>>();
+
+        Ok(Some(String::from_utf8_lossy(&output).into_owned()))
+    }
+
     pub async fn has_container_with_id(&self, id: &str) -> Result<bool> {
         self.container_stats()
             .await?
@@ -297,6 +558,161 @@ impl Endpoint {
         }
     }
 
+    /// Copy the directory at `path` out of the container into the checkpoint store
+    pub async fn save_checkpoint(
+        &self,
+        container_id: &str,
+        path: &Path,
+        package: &crate::package::Package,
+        arch: Option<&str>,
+        key: &str,
+        checkpoint_store: &CheckpointStore,
+    ) -> Result<()> {
+        let container = self.docker.containers().get(container_id);
+        let archive = container
+            .copy_from(path)
+            .map(|chunk| chunk.map_err(Error::from));
+        checkpoint_store
+            .save(package, arch, key, path, archive)
+            .await
+            .with_context(|| {
+                anyhow!(
+                    "Saving checkpoint {} of container {} on '{}'",
+                    path.display(),
+                    container_id,
+                    self.name
+                )
+            })
+    }
+
+    /// Remove a container, it is stopped first if it is still running
+    pub async fn remove_container(&self, id: &str) -> Result<()> {
+        self.docker
+            .containers()
+            .get(id)
+            .remove(shiplift::RmContainerOptions::builder().force(true).build())
+            .await
+            .with_context(|| anyhow!("Removing container {} on '{}'", id, self.name))
+    }
+
+    /// Get the ID (digest) of the image with the passed name on this endpoint
+    pub async fn image_id(&self, image: &ImageName) -> Result<String> {
+        self.docker
+            .images()
+            .get(image.as_ref())
+            .inspect()
+            .await
+            .map(|details| details.id)
+            .with_context(|| anyhow!("Inspecting image {} on '{}'", image.as_ref(), self.name))
+    }
+
+    /// Get the digest of the image (with the name `image`) a container was created from
+    ///
+    /// This is the registry digest of the image (see "RepoDigests" of the image), which is the
+    /// same on all endpoints and does not change if the image is re-tagged. Images that were
+    /// neither pulled from nor pushed to a registry have no registry digest, their ID is used
+    /// instead.
+    pub async fn container_image_digest(
+        &self,
+        container_id: &str,
+        image: &ImageName,
+    ) -> Result<String> {
+        let image_id = self
+            .docker
+            .containers()
+            .get(container_id)
+            .inspect()
+            .await
+            .map(|details| details.image)
+            .with_context(|| anyhow!("Inspecting container {} on '{}'", container_id, self.name))?;
+        let repo_digests = self
+            .docker
+            .images()
+            .get(&image_id)
+            .inspect()
+            .await
+            .map(|details| details.repo_digests.unwrap_or_default())
+            .with_context(|| anyhow!("Inspecting image {} on '{}'", image_id, self.name))?;
+
+        Ok(repo_digest(image, &repo_digests).unwrap_or(image_id))
+    }
+
+    /// Run a shell command in a helper container with the compiler cache of this endpoint
+    ///
+    /// Returns the output of the command or `None` if the endpoint has no compiler cache, and an
+    /// error if the command fails.
+    pub async fn run_in_compiler_cache(
+        &self,
+        image: &ImageName,
+        command: impl Fn(crate::config::CompilerCacheTool) -> &'static str,
+    ) -> Result<Option<String>> {
+        let Some(cache) = self.compiler_cache.as_ref() else {
+            return Ok(None);
+        };
+
+        let envs = compiler_cache_env(cache);
+        let volume = compiler_cache_volume(cache);
+        let opts = shiplift::ContainerOptions::builder(image.as_ref())
+            .env(envs.iter().map(AsRef::as_ref).collect::<Vec<&str>>())
+            .volumes(vec![volume.as_str()])
+            .cmd(vec!["/bin/bash"])
+            .attach_stdin(true) // we have to attach, otherwise bash exits
+            .build();
+
+        let create_info = self
+            .docker
+            .containers()
+            .create(&opts)
+            .await
+            .with_context(|| anyhow!("Creating helper container on '{}'", self.name))?;
+        let container = self.docker.containers().get(&create_info.id);
+
+        let output = async {
+            container.start().await?;
+            let exec_opts = ExecContainerOptions::builder()
+                .cmd(vec!["/bin/sh", "-c", command(cache.tool())])
+                .attach_stdout(true)
+                .attach_stderr(true)
+                .build();
+            let exec = shiplift::Exec::create(&self.docker, &create_info.id, &exec_opts).await?;
+            let output = exec
+                .start()
+                .map(|chunk| chunk.map_err(Error::from))
+                .collect::<Result<Vec<_>>>()
+                .await?;
+            match exec.inspect().await?.exit_code {
+                Some(0) => Ok(output),
+                exit_code => Err(anyhow!(
+                    "Command exited with {:?}: {}",
+                    exit_code,
+                    String::from_utf8_lossy(&tty_output(output))
+                )),
+            }
+        }
+        .await
+        .with_context(|| anyhow!("Running '{}' on '{}'", command(cache.tool()), self.name));
+
+        // The helper container is removed in any case
+        let _ = container.stop(None).await;
+        container
+            .delete()
+            .await
+            .with_context(|| anyhow!("Removing helper container on '{}'", self.name))?;
+
+        Ok(Some(
+            String::from_utf8_lossy(&tty_output(output?)).into_owned(),
+        ))
+    }
+
+    /// Get the Docker version of this endpoint
+    pub async fn docker_version(&self) -> Result<String> {
+        self.docker
+            .version()
+            .await
+            .map(|version| version.version)
+            .with_context(|| anyhow!("Getting version of endpoint: {}", self.name))
+    }
+
     pub async fn images(&self, name_filter: Option<&str>) -> Result<impl Iterator<Item = Image>> {
         let mut listopts = shiplift::builder::ImageListOptions::builder();
 
@@ -315,6 +731,41 @@ impl Endpoint {
     }
 }
 
+/// The stdout and stderr output of an exec in a container
+fn tty_output(chunks: Vec<shiplift::tty::TtyChunk>) -> Vec<u8> {
+    chunks
+        .into_iter()
+        .flat_map(|chunk| match chunk {
+            shiplift::tty::TtyChunk::StdOut(v) | shiplift::tty::TtyChunk::StdErr(v) => v,
+            shiplift::tty::TtyChunk::StdIn(_) => Vec::new(),
+        })
+        .collect()
+}
+
+/// The environment variables that make the compiler cache available in a container
+fn compiler_cache_env(cache: &crate::config::CompilerCache) -> Vec<String> {
+    vec![
+        format!(
+            "{}={}",
+            cache.tool().dir_env_var(),
+            crate::consts::COMPILER_CACHE_DIR_PATH
+        ),
+        format!("{}={}", crate::consts::COMPILER_CACHE_ENV_VAR, cache.tool()),
+    ]
+}
+
+/// The volume specification ("source:destination[:mode]") that mounts the compiler cache
+fn compiler_cache_volume(cache: &crate::config::CompilerCache) -> String {
+    match cache.host_mount() {
+        Some(mount) => mount.volume_spec(),
+        None => format!(
+            "{}:{}",
+            cache.volume(),
+            crate::consts::COMPILER_CACHE_DIR_PATH
+        ),
+    }
+}
+
 /// Helper type to store endpoint statistics
 ///
 /// Currently, this can only be generated from a shiplift::rep::Info, but it does not hold all
@@ -357,10 +808,25 @@ pub struct ContainerStat {
     pub id: String,
     pub image: String,
     pub image_id: String,
+    pub names: Vec<String>,
     pub state: String,
     pub status: String,
 }
 
+impl ContainerStat {
+    /// Whether this is a container that was created by butido for a job
+    pub fn is_butido_container(&self) -> bool {
+        self.names
+            .iter()
+            .any(|name| name.trim_start_matches('/').starts_with("butido-"))
+    }
+
+    /// Whether this is a running container that was created by butido for a job
+    pub fn is_running_butido_container(&self) -> bool {
+        self.state == "running" && self.is_butido_container()
+    }
+}
+
 impl From<shiplift::rep::Container> for ContainerStat {
     fn from(cont: shiplift::rep::Container) -> Self {
         ContainerStat {
@@ -368,6 +834,7 @@ impl From<shiplift::rep::Container> for ContainerStat {
             id: cont.id,
             image: cont.image,
             image_id: cont.image_id,
+            names: cont.names,
             state: cont.state,
             status: cont.status,
         }
@@ -433,6 +900,10 @@ pub struct PreparedContainer<'a> {
 
     #[getset(get = "pub")]
     create_info: shiplift::rep::ContainerCreateInfo,
+
+    /// The SHA-256 hash of the archive of the sources that were copied into the container
+    #[getset(get = "pub")]
+    sources_hash: String,
 }
 
 impl<'a> PreparedContainer<'a> {
@@ -441,19 +912,49 @@ impl<'a> PreparedContainer<'a> {
         job: &RunnableJob,
         staging_store: Arc<RwLock<StagingStore>>,
         release_stores: Vec<Arc<ReleaseStore>>,
+        upload_progress: Arc<UploadProgress>,
+        max_parallel_uploads: usize,
+        artifact_cache: Option<&EndpointArtifactCache>,
+        checksums: &RecordedChecksums,
+        checkpoint: Option<&Checkpoint>,
     ) -> Result<PreparedContainer<'a>> {
         let script = job.script().clone();
-        let create_info = Self::build_container(endpoint, job).await?;
+        let create_info = Self::build_container(endpoint, job, checkpoint).await?;
         let container = endpoint.docker.containers().get(&create_info.id);
 
-        let (cpysrc, cpypch, cpyart, cpyscr) = tokio::join!(
+        let has_artifacts = job
+            .resources()
+            .iter()
+            .any(|res| JobResource::artifact(res).is_some());
+        let cache_helper = match artifact_cache {
+            Some(cache) if has_artifacts => {
+                Some(ArtifactCacheHelper::create(endpoint, job.image(), cache).await?)
+            }
+            _ => None,
+        };
+
+        let (cpysrc, cpypch, cpyart, cpyscr, cpychk) = tokio::join!(
             Self::copy_source_to_container(&container, job),
             Self::copy_patches_to_container(&container, job),
-            Self::copy_artifacts_to_container(&container, job, staging_store, &release_stores),
-            Self::copy_script_to_container(&container, &script)
+            Self::copy_artifacts_to_container(
+                &container,
+                job,
+                staging_store,
+                &release_stores,
+                &upload_progress,
+                max_parallel_uploads,
+                cache_helper.as_ref(),
+                checksums
+            ),
+            Self::copy_script_to_container(&container, &script),
+            Self::copy_checkpoint_to_container(&container, checkpoint)
         );
+        upload_progress.finish();
+        if let Some(helper) = cache_helper {
+            helper.remove().await?;
+        }
 
-        cpysrc.with_context(|| {
+        let sources_hash = cpysrc.with_context(|| {
             anyhow!(
                 "Copying the sources to container {} on '{}'",
                 create_info.id,
@@ -485,11 +986,20 @@ impl<'a> PreparedContainer<'a> {
             )
         })?;
 
+        cpychk.with_context(|| {
+            anyhow!(
+                "Restoring the checkpoint in container {} on '{}'",
+                create_info.id,
+                endpoint.name
+            )
+        })?;
+
         Ok({
             PreparedContainer {
                 endpoint,
                 script,
                 create_info,
+                sources_hash,
             }
         })
     }
@@ -497,23 +1007,68 @@ impl<'a> PreparedContainer<'a> {
     async fn build_container(
         endpoint: &Endpoint,
         job: &RunnableJob,
+        checkpoint: Option<&Checkpoint>,
     ) -> Result<shiplift::rep::ContainerCreateInfo> {
-        let envs = job
+        let mut envs = job
             .environment()
             .map(|(k, v)| format!("{}={}", k.as_ref(), v))
             .collect::<Vec<_>>();
+        let mut volumes = job
+            .mounts()
+            .iter()
+            .map(Mount::volume_spec)
+            .collect::<Vec<_>>();
+        if let Some(cache) = endpoint.compiler_cache().as_ref() {
+            envs.extend(compiler_cache_env(cache));
+            volumes.push(compiler_cache_volume(cache));
+        }
+        if let Some(checkpoint) = checkpoint {
+            envs.push(format!(
+                "{}={}",
+                crate::consts::CHECKPOINT_ENV_VAR,
+                checkpoint.path().display()
+            ));
+        }
+        if let Some(volume) = endpoint.artifact_cache().as_ref() {
+            // Read-only, so that a build script cannot change the artifacts that are linked into
+            // the inputs of other jobs (see `ArtifactCacheHelper`)
+            volumes.push(format!(
+                "{}:{}:ro",
+                volume,
+                crate::consts::ARTIFACT_CACHE_DIR_PATH
+            ));
+        }
+        trace!("Job resources: Volumes = {:?}", volumes);
         trace!("Job resources: Environment variables = {:?}", envs);
 
         let builder_opts = {
             let mut builder_opts = shiplift::ContainerOptions::builder(job.image().as_ref());
+            // Docker does not allow the namespace separator in container names
             let container_name = format!(
                 "butido-{package}-{version}-{id}",
-                package = job.package().name().as_ref(),
+                package = job
+                    .package()
+                    .name()
+                    .replace(PackageName::NAMESPACE_SEPARATOR, "_"),
                 version = job.package().version().as_ref(),
                 id = job.uuid()
             );
             trace!("container name = {}", container_name);
             builder_opts.name(&container_name);
+
+            let job_uuid = job.uuid().to_string();
+            let labels = HashMap::from([
+                (crate::consts::CONTAINER_LABEL_JOB_UUID, job_uuid.as_str()),
+                (
+                    crate::consts::CONTAINER_LABEL_PACKAGE_NAME,
+                    job.package().name().as_ref(),
+                ),
+                (
+                    crate::consts::CONTAINER_LABEL_PACKAGE_VERSION,
+                    job.package().version().as_ref(),
+                ),
+            ]);
+            builder_opts.labels(&labels);
             builder_opts.env(envs.iter().map(AsRef::as_ref).collect::<Vec<&str>>());
             builder_opts.cmd(vec!["/bin/bash"]); // we start the container with /bin/bash, but exec() the script in it later
             builder_opts.attach_stdin(true); // we have to attach, otherwise bash exits
@@ -522,6 +1077,10 @@ impl<'a> PreparedContainer<'a> {
                 builder_opts.network_mode(network_mode);
             }
 
+            if !volumes.is_empty() {
+                builder_opts.volumes(volumes.iter().map(AsRef::as_ref).collect::<Vec<&str>>());
+            }
+
             builder_opts.build()
         };
         trace!("Builder options = {:?}", builder_opts);
@@ -542,62 +1101,34 @@ impl<'a> PreparedContainer<'a> {
         Ok(create_info)
     }
 
-    async fn copy_source_to_container(container: &Container<'_>, job: &RunnableJob) -> Result<()> {
-        use tokio::io::AsyncReadExt;
+    /// Copy the sources of the job into the container
+    ///
+    /// The sources are copied as one normalized archive (see `NormalizedArchive`), so that the
+    /// same sources always result in the same archive. Returns the hash of the archive.
+    async fn copy_source_to_container(
+        container: &Container<'_>,
+        job: &RunnableJob,
+    ) -> Result<String> {
+        let files = read_package_sources(job).await.with_context(|| {
+            anyhow!(
+                "Collecting package sources for container {}",
+                container.id()
+            )
+        })?;
 
-        job.package_sources()
-            .into_iter()
-            .map(|entry| async {
-                let source_path = entry.path();
-                let destination = PathBuf::from(crate::consts::INPUTS_DIR_PATH).join({
-                    source_path
-                        .file_name()
-                        .ok_or_else(|| anyhow!("Not a file: {}", source_path.display()))
-                        .with_context(|| {
-                            anyhow!(
-                                "Copying package source from {} to container {}",
-                                source_path.display(),
-                                container.id()
-                            )
-                        })?
-                });
-                trace!("Source path    = {:?}", source_path);
-                trace!("Source dest    = {:?}", destination);
-                let mut buf = vec![];
-                tokio::fs::OpenOptions::new()
-                    .create(false)
-                    .create_new(false)
-                    .append(false)
-                    .write(false)
-                    .read(true)
-                    .open(&source_path)
-                    .await
-                    .with_context(|| anyhow!("Getting source file: {}", source_path.display()))?
-                    .read_to_end(&mut buf)
-                    .await
-                    .with_context(|| anyhow!("Reading file {}", source_path.display()))?;
+        let archive =
+            tokio::task::spawn_blocking(move || NormalizedArchive::build_entries(files)).await??;
+        let hash = archive.sha256();
+        trace!("Sources archive for container {}: {}", container.id(), hash);
 
-                drop(entry);
-                container
-                    .copy_file_into(destination, &buf)
-                    .await
-                    .inspect(|_| {
-                        trace!(
-                            "Successfully copied source {} to container {}",
-                            source_path.display(),
-                            container.id()
-                        )
-                    })
-                    .with_context(|| {
-                        anyhow!(
-                            "Failed to copy source {} to container {}",
-                            source_path.display(),
-                            container.id()
-                        )
-                    })
-            })
-            .collect::<futures::stream::FuturesUnordered<_>>()
-            .collect::<Result<()>>()
+        let file = tokio::fs::File::open(archive.path())
+            .await
+            .with_context(|| anyhow!("Opening {}", archive.path().display()))?;
+        container
+            .copy_to(
+                std::path::Path::new("/"),
+                hyper::Body::wrap_stream(chunk_stream(file)),
+            )
             .await
             .inspect(|_| {
                 trace!(
@@ -605,7 +1136,43 @@ impl<'a> PreparedContainer<'a> {
                     container.id()
                 )
             })
-            .with_context(|| anyhow!("Copying sources to container {}", container.id()))
+            .with_context(|| anyhow!("Copying sources to container {}", container.id()))?;
+        Ok(hash)
+    }
+
+    /// Restore the checkpoint of the package (if any) at its path in the container
+    async fn copy_checkpoint_to_container(
+        container: &Container<'_>,
+        checkpoint: Option<&Checkpoint>,
+    ) -> Result<()> {
+        let Some(checkpoint) = checkpoint else {
+            return Ok(());
+        };
+        // The archive contains the checkpoint directory itself
+        let parent = checkpoint.path().parent().unwrap_or(Path::new("/"));
+        trace!(
+            "Restoring checkpoint {} in container {}",
+            checkpoint.path().display(),
+            container.id()
+        );
+
+        container
+            .copy_to(Path::new("/"), directory_archive(parent)?.into())
+            .await
+            .with_context(|| anyhow!("Creating {} in container", parent.display()))?;
+        let archive = tokio::fs::File::open(checkpoint.archive())
+            .await
+            .with_context(|| anyhow!("Opening {}", checkpoint.archive().display()))?;
+        container
+            .copy_to(parent, hyper::Body::wrap_stream(chunk_stream(archive)))
+            .await
+            .with_context(|| {
+                anyhow!(
+                    "Copying checkpoint {} to container",
+                    checkpoint.path().display()
+                )
+            })?;
+        Ok(())
     }
 
     async fn copy_patches_to_container(container: &Container<'_>, job: &RunnableJob) -> Result<()> {
@@ -660,11 +1227,25 @@ impl<'a> PreparedContainer<'a> {
             .with_context(|| anyhow!("Copying patches to container {}", container.id()))
     }
 
+    /// Stream the artifacts of the dependencies into the container
+    ///
+    /// The artifacts are read while they are uploaded, so that large artifacts are never held in
+    /// memory, and the progress is shown in the progress bar of the job.
+    ///
+    /// With an artifact cache, the artifacts are uploaded to the cache through the helper container
+    /// (unless they are cached already) and the inputs are symlinks to the cached artifacts.
+    ///
+    /// The artifacts are verified against the checksums that were recorded when they were built
+    /// before they are copied.
     async fn copy_artifacts_to_container(
         container: &Container<'_>,
         job: &RunnableJob,
         staging_store: Arc<RwLock<StagingStore>>,
         release_stores: &[Arc<ReleaseStore>],
+        upload_progress: &Arc<UploadProgress>,
+        max_parallel_uploads: usize,
+        cache_helper: Option<&ArtifactCacheHelper<'_>>,
+        checksums: &RecordedChecksums,
     ) -> Result<()> {
         let stream = job
             .resources()
@@ -689,65 +1270,50 @@ impl<'a> PreparedContainer<'a> {
                     container.id(),
                     destination.display()
                 );
-                let staging_read = staging_store.read().await;
-                let buf = match staging_read.root_path().join(&art)? {
-                    Some(fp) => fp,
+                let (path, release_store_name) =
+                    find_artifact(&art, &staging_store, release_stores).await?;
+                let sha256 = checksums
+                    .verify(&art, &path, release_store_name.as_deref())
+                    .await?;
+                let r = match cache_helper {
+                    Some(helper) => {
+                        Self::copy_cached_artifact_to_container(
+                            container,
+                            job,
+                            &path,
+                            sha256,
+                            &destination,
+                            helper,
+                            upload_progress,
+                        )
+                        .await
+                    }
                     None => {
-                        // TODO: Optimize.
-                        // I know this is not nice, but it works for now.
-                        let mut found = None;
-                        for release_store in release_stores.iter() {
-                            let p = release_store.root_path().join(&art);
-                            match p {
-                                Ok(Some(path)) => {
-                                    found = Some(path);
-                                    break;
-                                }
-                                Err(e) => {
-                                    trace!(
-                                        "Failed to join '{:?}' + '{:?}'",
-                                        release_store.root_path(),
-                                        art.display()
-                                    );
-                                    return Err(e);
-                                }
-                                Ok(None) => continue,
-                            }
-                        }
-                        found.ok_or_else(|| {
-                            anyhow!("Not found in staging or release store: {:?}", art)
-                        })?
+                        Self::stream_file_to_container(
+                            container,
+                            &path,
+                            &destination,
+                            upload_progress,
+                        )
+                        .await
                     }
                 }
-                .read()
-                .await
+                .inspect(|_| trace!("Successfully copied {} to container", art.display()))
                 .with_context(|| {
                     anyhow!(
-                        "Reading artifact {}, so it can be copied to container",
-                        art.display()
+                        "Copying artifact {} to container {} at {}",
+                        art.display(),
+                        container.id(),
+                        destination.display()
                     )
-                })?;
-                trace!("Successfully read {} into buffer", art.display());
-
-                let r = container
-                    .copy_file_into(&destination, &buf)
-                    .await
-                    .inspect(|_| trace!("Successfully copied {} to container", art.display()))
-                    .with_context(|| {
-                        anyhow!(
-                            "Copying artifact {} to container {} at {}",
-                            art.display(),
-                            container.id(),
-                            destination.display()
-                        )
-                    });
+                });
                 drop(art); // ensure `art` is moved into closure
                 r
             });
 
         let stream = {
             use futures::stream::StreamExt;
-            futures::stream::iter(stream).buffer_unordered(100)
+            futures::stream::iter(stream).buffer_unordered(max_parallel_uploads.max(1))
         };
 
         stream
@@ -763,6 +1329,86 @@ impl<'a> PreparedContainer<'a> {
             .map(|_| ())
     }
 
+    /// Stream a file into the container
+    async fn stream_file_to_container(
+        container: &Container<'_>,
+        path: &Path,
+        destination: &Path,
+        upload_progress: &Arc<UploadProgress>,
+    ) -> Result<()> {
+        let archive = file_archive_stream(path, destination, upload_progress.clone()).await?;
+        container
+            .copy_to(Path::new("/"), hyper::Body::wrap_stream(archive))
+            .await
+            .map_err(Error::from)
+    }
+
+    /// Upload an artifact to the artifact cache of the endpoint, unless it is cached already, and
+    /// link it into the container
+    ///
+    /// Each job uploads to its own file in the cache, so that a cached artifact is never
+    /// overwritten while another job reads it. `sha256` is the hash of the file, if it is known
+    /// already.
+    ///
+    /// A cached artifact is verified before it is linked. If it is missing in the volume or has a
+    /// different hash (e.g., because the volume was pruned or recreated), its entry in the database
+    /// is removed and the artifact is uploaded again.
+    async fn copy_cached_artifact_to_container(
+        container: &Container<'_>,
+        job: &RunnableJob,
+        path: &Path,
+        sha256: Option<String>,
+        destination: &Path,
+        helper: &ArtifactCacheHelper<'_>,
+        upload_progress: &Arc<UploadProgress>,
+    ) -> Result<()> {
+        let sha256 = match sha256 {
+            Some(sha256) => sha256,
+            None => file_sha256(path).await?,
+        };
+        let cached = match helper.cache.cached_file_name(&sha256)? {
+            Some(file_name) if helper.verify(&file_name, &sha256).await? => {
+                trace!("Artifact {} is cached as {}", path.display(), file_name);
+                Some(file_name)
+            }
+            Some(file_name) => {
+                warn!(
+                    "Cached artifact {} is missing or corrupted on '{}', uploading it again",
+                    file_name, helper.endpoint.name
+                );
+                helper.cache.forget(&sha256, &file_name)?;
+                None
+            }
+            None => None,
+        };
+        let file_name = match cached {
+            Some(file_name) => file_name,
+            None => {
+                let file_name = format!("{}-{}", sha256, job.uuid());
+                let cached_path =
+                    PathBuf::from(crate::consts::ARTIFACT_CACHE_DIR_PATH).join(&file_name);
+                Self::stream_file_to_container(
+                    &helper.container,
+                    path,
+                    &cached_path,
+                    upload_progress,
+                )
+                .await?;
+                helper.cache.record(&sha256, &file_name)?;
+                file_name
+            }
+        };
+
+        let target = PathBuf::from(crate::consts::ARTIFACT_CACHE_DIR_PATH).join(file_name);
+        container
+            .copy_to(
+                Path::new("/"),
+                symlink_archive(destination, &target)?.into(),
+            )
+            .await
+            .map_err(Error::from)
+    }
+
     async fn copy_script_to_container(container: &Container<'_>, script: &Script) -> Result<()> {
         let script_path = PathBuf::from(crate::consts::SCRIPT_PATH);
         container
@@ -800,6 +1446,192 @@ impl<'a> PreparedContainer<'a> {
     }
 }
 
+/// A helper container that mounts the artifact cache volume of an endpoint writable
+///
+/// The containers of the jobs mount the volume read-only, so that a build script cannot change
+/// the cached artifacts. The artifacts are verified and uploaded through this container instead,
+/// it is removed after the inputs of the job were copied.
+struct ArtifactCacheHelper<'a> {
+    endpoint: &'a Endpoint,
+    cache: &'a EndpointArtifactCache,
+    container: Container<'a>,
+}
+
+impl<'a> ArtifactCacheHelper<'a> {
+    async fn create(
+        endpoint: &'a Endpoint,
+        image: &ImageName,
+        cache: &'a EndpointArtifactCache,
+    ) -> Result<ArtifactCacheHelper<'a>> {
+        let volume = format!(
+            "{}:{}",
+            cache.volume(),
+            crate::consts::ARTIFACT_CACHE_DIR_PATH
+        );
+        let opts = shiplift::ContainerOptions::builder(image.as_ref())
+            .volumes(vec![volume.as_str()])
+            .cmd(vec!["/bin/bash"])
+            .attach_stdin(true) // we have to attach, otherwise bash exits
+            .build();
+
+        let create_info = endpoint
+            .docker
+            .containers()
+            .create(&opts)
+            .await
+            .with_context(|| {
+                anyhow!(
+                    "Creating artifact cache helper container on '{}'",
+                    endpoint.name
+                )
+            })?;
+        let helper = ArtifactCacheHelper {
+            endpoint,
+            cache,
+            container: endpoint.docker.containers().get(&create_info.id),
+        };
+
+        if let Err(e) = helper.container.start().await {
+            let _ = helper.remove().await;
+            return Err(Error::from(e)).with_context(|| {
+                anyhow!(
+                    "Starting artifact cache helper container on '{}'",
+                    endpoint.name
+                )
+            });
+        }
+        Ok(helper)
+    }
+
+    /// Check whether the file `file_name` exists in the cache and has the hash `sha256`
+    async fn verify(&self, file_name: &str, sha256: &str) -> Result<bool> {
+        let cached_path = PathBuf::from(crate::consts::ARTIFACT_CACHE_DIR_PATH).join(file_name);
+        let cached_path = cached_path.to_string_lossy();
+        let exec_opts = ExecContainerOptions::builder()
+            .cmd(vec!["sha256sum", "--", cached_path.as_ref()])
+            .attach_stdout(true)
+            .attach_stderr(true)
+            .build();
+        let output = self
+            .container
+            .exec(&exec_opts)
+            .map(|chunk| chunk.map_err(Error::from))
+            .collect::<Result<Vec<_>>>()
+            .await
+            .with_context(|| {
+                anyhow!(
+                    "Verifying cached artifact {} on '{}'",
+                    file_name,
+                    self.endpoint.name
+                )
+            })?
+            .into_iter()
+            .flat_map(|chunk| match chunk {
+                shiplift::tty::TtyChunk::StdOut(v) => v,
+                shiplift::tty::TtyChunk::StdErr(_) | shiplift::tty::TtyChunk::StdIn(_) => {
+                    Vec::new()
+                }
+            })
+            .collect::<Vec<u8>>();
+
+        // A missing file only produces an error message on stderr
+        let actual = String::from_utf8_lossy(&output)
+            .split_whitespace()
+            .next()
+            .map(str::to_owned);
+        Ok(actual.as_deref() == Some(sha256))
+    }
+
+    async fn remove(self) -> Result<()> {
+        self.container
+            .remove(shiplift::RmContainerOptions::builder().force(true).build())
+            .await
+            .with_context(|| {
+                anyhow!(
+                    "Removing artifact cache helper container on '{}'",
+                    self.endpoint.name
+                )
+            })
+    }
+}
+
+/// The entries of the sources of the package of the job, with their paths in the container
+/// (relative to `/`)
+///
+/// The archive of the entries is extracted in the root directory of the container.
+pub(super) async fn read_package_sources(job: &RunnableJob) -> Result<Vec<ArchiveEntry>> {
+    job.package_sources()
+        .into_iter()
+        .map(|entry| async move {
+            entry
+                .container_entries()
+                .await
+                .with_context(|| anyhow!("Collecting package source {}", entry.path().display()))
+        })
+        .collect::<futures::stream::FuturesUnordered<_>>()
+        .collect::<Result<Vec<_>>>()
+        .await
+        .map(|entries| entries.into_iter().flatten().collect())
+}
+
+/// Read an artifact from the staging store or, if it is not there, from one of the release stores
+pub(super) async fn read_artifact(
+    art: &ArtifactPath,
+    staging_store: &RwLock<StagingStore>,
+    release_stores: &[Arc<ReleaseStore>],
+) -> Result<Vec<u8>> {
+    let (path, _) = find_artifact(art, staging_store, release_stores).await?;
+    tokio::fs::read(&path)
+        .await
+        .with_context(|| anyhow!("Reading artifact from path {}", path.display()))
+}
+
+/// The path of an artifact in the staging store or, if it is not there, in one of the release
+/// stores, and the name of the release store it was found in (`None` for the staging store)
+async fn find_artifact(
+    art: &ArtifactPath,
+    staging_store: &RwLock<StagingStore>,
+    release_stores: &[Arc<ReleaseStore>],
+) -> Result<(PathBuf, Option<String>)> {
+    let staging_read = staging_store.read().await;
+    let (path, release_store_name) = match staging_read.root_path().join(art)? {
+        Some(fp) => (fp, None),
+        None => {
+            // TODO: Optimize.
+            // I know this is not nice, but it works for now.
+            let mut found = None;
+            for release_store in release_stores.iter() {
+                let p = release_store.root_path().join(art);
+                match p {
+                    Ok(Some(path)) => {
+                        let name = release_store
+                            .root_path()
+                            .as_ref()
+                            .file_name()
+                            .map(|name| name.to_string_lossy().to_string());
+                        found = Some((path, name));
+                        break;
+                    }
+                    Err(e) => {
+                        trace!(
+                            "Failed to join '{:?}' + '{:?}'",
+                            release_store.root_path(),
+                            art.display()
+                        );
+                        return Err(e);
+                    }
+                    Ok(None) => continue,
+                }
+            }
+            found.ok_or_else(|| anyhow!("Not found in staging or release store: {:?}", art))?
+        }
+    };
+    Ok((path.joined(), release_store_name))
+}
+
+/// The maximum number of bytes of the output of an environment probe that is recorded
+const MAX_PROBE_OUTPUT_LENGTH: usize = 64 * 1024;
+
 pub struct StartedContainer<'a> {
     endpoint: &'a Endpoint,
     script: Script,
@@ -807,9 +1639,66 @@ pub struct StartedContainer<'a> {
 }
 
 impl<'a> StartedContainer<'a> {
+    /// Run the environment probes in the container and collect their output (stdout and stderr)
+    ///
+    /// A probe that cannot be run is skipped with a warning, it never fails the job. The output
+    /// of a probe is truncated to `MAX_PROBE_OUTPUT_LENGTH` bytes.
+    pub async fn probe_environment(&self, probes: &[EnvironmentProbe]) -> Vec<(String, String)> {
+        let container = self.endpoint.docker.containers().get(&self.create_info.id);
+        let mut results = Vec::with_capacity(probes.len());
+        for probe in probes.iter() {
+            let exec_opts = ExecContainerOptions::builder()
+                .cmd(vec!["/bin/sh", "-c", probe.command()])
+                .attach_stdout(true)
+                .attach_stderr(true)
+                .build();
+            let output = container
+                .exec(&exec_opts)
+                .map(|chunk| chunk.map_err(Error::from))
+                .collect::<Result<Vec<_>>>()
+                .await;
+
+            match output {
+                Ok(chunks) => {
+                    let output = chunks
+                        .into_iter()
+                        .flat_map(|chunk| match chunk {
+                            shiplift::tty::TtyChunk::StdOut(v)
+                            | shiplift::tty::TtyChunk::StdErr(v) => v,
+                            shiplift::tty::TtyChunk::StdIn(_) => Vec::new(),
+                        })
+                        .collect::<Vec<u8>>();
+                    let output = String::from_utf8_lossy(&output).into_owned();
+                    trace!(
+                        "Probe '{}' in container {}: {:?}",
+                        probe.name(),
+                        self.create_info.id,
+                        output
+                    );
+                    results.push((
+                        probe.name().clone(),
+                        crate::log::truncate_line(output, MAX_PROBE_OUTPUT_LENGTH),
+                    ));
+                }
+                Err(e) => warn!(
+                    "Cannot run environment probe '{}' in container {} on '{}': {:?}",
+                    probe.name(),
+                    self.create_info.id,
+                    self.endpoint.name,
+                    e
+                ),
+            }
+        }
+        results
+    }
+
+    /// Execute the packaging script in the container
+    ///
+    /// Log lines that are longer than `max_line_length` bytes are truncated before they are parsed.
     pub async fn execute_script(
         self,
         logsink: UnboundedSender<LogItem>,
+        max_line_length: usize,
     ) -> Result<ExecutedContainer<'a>> {
         let exec_opts = ExecContainerOptions::builder()
             .cmd(vec!["/bin/bash", "/script"])
@@ -845,15 +1734,17 @@ impl<'a> StartedContainer<'a> {
                             self.create_info.id
                         )
                     })
+                    .map(|l| crate::log::truncate_line(l, max_line_length))
                     .and_then(|l| {
-                        crate::log::parser().parse(l.as_bytes()).with_context(|| {
-                            anyhow!(
-                                "Parsing log from {}:{}: {:?}",
-                                self.endpoint.name,
-                                self.create_info.id,
-                                l
-                            )
-                        })
+                        crate::log::parse_log_line(&crate::log::parser(), l.as_bytes())
+                            .with_context(|| {
+                                anyhow!(
+                                    "Parsing log from {}:{}: {:?}",
+                                    self.endpoint.name,
+                                    self.create_info.id,
+                                    l
+                                )
+                            })
                     })
                     .and_then(|item| {
                         let exited_successfully = match item {
@@ -925,6 +1816,7 @@ impl ExecutedContainer<'_> {
     pub async fn finalize(
         self,
         staging_store: Arc<RwLock<StagingStore>>,
+        arch: Option<&str>,
     ) -> Result<FinalizedContainer> {
         let (exit_info, artifacts) = match self.exit_info {
             Some((false, msg)) => {
@@ -958,7 +1850,7 @@ impl ExecutedContainer<'_> {
 
                 let mut writelock = staging_store.write().await;
                 let artifacts = writelock
-                    .write_files_from_tar_stream(tar_stream)
+                    .write_files_from_tar_stream(tar_stream, arch)
                     .await
                     .with_context(|| anyhow!("Copying the TAR stream to the staging store"))?;
                 container
@@ -985,7 +1877,103 @@ pub struct FinalizedContainer {
 }
 
 impl FinalizedContainer {
+    pub(super) fn new(artifacts: Vec<ArtifactPath>, exit_info: Result<()>) -> Self {
+        FinalizedContainer {
+            artifacts,
+            exit_info,
+        }
+    }
+
     pub fn unpack(self) -> (Vec<ArtifactPath>, Result<()>) {
         (self.artifacts, self.exit_info)
     }
 }
+
+/// The digest (e.g. "sha256:...") of the repository digest (e.g. "registry/image@sha256:...") of
+/// the image `image`, or of the first repository digest if none belongs to its repository
+fn repo_digest(image: &ImageName, repo_digests: &[String]) -> Option<String> {
+    let name = image.as_ref();
+    let name = name.split_once('@').map(|(name, _)| name).unwrap_or(name);
+    // A tag follows the last colon, unless the colon belongs to the port of the registry
+    let repository = match name.rsplit_once(':') {
+        Some((repository, tag)) if !tag.contains('/') => repository,
+        _ => name,
+    };
+
+    repo_digests
+        .iter()
+        .filter_map(|repo_digest| repo_digest.split_once('@'))
+        .min_by_key(|(digest_repository, _)| *digest_repository != repository)
+        .map(|(_, digest)| digest.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repo_digest() {
+        let digests = vec![
+            String::from("registry.example.com:5000/other@sha256:aaaa"),
+            String::from("registry.example.com:5000/debian@sha256:bbbb"),
+        ];
+
+        for name in [
+            "registry.example.com:5000/debian",
+            "registry.example.com:5000/debian:12",
+            "registry.example.com:5000/debian:12@sha256:bbbb",
+        ] {
+            assert_eq!(
+                repo_digest(&ImageName::from(name), &digests).as_deref(),
+                Some("sha256:bbbb"),
+                "{name}"
+            );
+        }
+        assert_eq!(
+            repo_digest(&ImageName::from("local:debian12"), &digests).as_deref(),
+            Some("sha256:aaaa")
+        );
+        assert_eq!(repo_digest(&ImageName::from("local:debian12"), &[]), None);
+    }
+
+    fn compiler_cache(tool: &str, volume: &str) -> crate::config::CompilerCache {
+        toml::from_str(&format!("tool = \"{tool}\"\nvolume = \"{volume}\"")).unwrap()
+    }
+
+    #[test]
+    fn test_compiler_cache_env() {
+        assert_eq!(
+            compiler_cache_env(&compiler_cache("ccache", "butido-ccache")),
+            [
+                "CCACHE_DIR=/var/cache/butido-compiler-cache",
+                "BUTIDO_COMPILER_CACHE=ccache"
+            ]
+        );
+        assert_eq!(
+            compiler_cache_env(&compiler_cache("sccache", "butido-sccache")),
+            [
+                "SCCACHE_DIR=/var/cache/butido-compiler-cache",
+                "BUTIDO_COMPILER_CACHE=sccache"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compiler_cache_volume() {
+        let named = compiler_cache("ccache", "butido-ccache");
+        assert!(named.host_mount().is_none());
+        assert_eq!(
+            compiler_cache_volume(&named),
+            "butido-ccache:/var/cache/butido-compiler-cache"
+        );
+
+        let host_path = compiler_cache("ccache", "/srv/ccache");
+        assert_eq!(
+            compiler_cache_volume(&host_path),
+            "/srv/ccache:/var/cache/butido-compiler-cache:rw"
+        );
+        let mount = host_path.host_mount().unwrap();
+        assert!(mount.check_allowed(&[PathBuf::from("/srv")]).is_ok());
+        assert!(mount.check_allowed(&[PathBuf::from("/opt")]).is_err());
+    }
+}
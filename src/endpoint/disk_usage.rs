@@ -0,0 +1,157 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! The disk usage of the docker data root of an endpoint
+//!
+//! shiplift does not implement the `system df` API of docker, so the response is parsed here.
+
+use anyhow::Context;
+use anyhow::Result;
+use serde::Deserialize;
+
+/// The response of the `/system/df` API, only with the fields that are relevant for us
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SystemDataUsage {
+    layers_size: i64,
+    containers: Option<Vec<ContainerUsage>>,
+    volumes: Option<Vec<VolumeUsage>>,
+    build_cache: Option<Vec<BuildCacheUsage>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ContainerUsage {
+    size_rw: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct VolumeUsage {
+    usage_data: Option<VolumeUsageData>,
+}
+
+/// The size is -1 if docker could not compute it (e.g. for volumes of other drivers)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct VolumeUsageData {
+    size: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct BuildCacheUsage {
+    size: i64,
+    #[serde(default)]
+    shared: bool,
+}
+
+/// The disk usage of the docker data root of an endpoint
+#[derive(Debug)]
+pub struct DiskUsage {
+    /// The path of the data root on the endpoint host
+    pub data_root: String,
+
+    /// The space used by images, containers, volumes and the build cache
+    pub data_root_size: u64,
+
+    /// The free space of the file system of the data root, if it can be determined
+    pub available: Option<u64>,
+
+    pub images_size: u64,
+    pub volumes_size: u64,
+}
+
+impl DiskUsage {
+    /// Build the disk usage from the JSON response of the `/system/df` API
+    pub fn from_system_df(json: &[u8], data_root: String, available: Option<u64>) -> Result<Self> {
+        let usage = serde_json::from_slice::<SystemDataUsage>(json)
+            .context("Parsing the response of the system df API")?;
+
+        let size = |size: i64| u64::try_from(size).unwrap_or(0);
+        let images_size = size(usage.layers_size);
+        let containers_size = usage
+            .containers
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|container| container.size_rw)
+            .map(size)
+            .sum::<u64>();
+        let volumes_size = usage
+            .volumes
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|volume| volume.usage_data)
+            .map(|data| size(data.size))
+            .sum::<u64>();
+        let build_cache_size = usage
+            .build_cache
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|cache| !cache.shared)
+            .map(|cache| size(cache.size))
+            .sum::<u64>();
+
+        Ok(DiskUsage {
+            data_root,
+            data_root_size: images_size + containers_size + volumes_size + build_cache_size,
+            available,
+            images_size,
+            volumes_size,
+        })
+    }
+
+    /// The share (in percent) of the space for the data root that is used
+    ///
+    /// This is the size of the data root relative to the size of the data root plus the free
+    /// space. Returns `None` if the free space is unknown.
+    pub fn used_percent(&self) -> Option<f64> {
+        let available = self.available?;
+        let total = self.data_root_size + available;
+        if total == 0 {
+            return Some(0.0);
+        }
+        Some(self.data_root_size as f64 * 100.0 / total as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_system_df() {
+        let json = br#"{
+            "LayersSize": 3000,
+            "Images": [{"Id": "sha256:1234", "Size": 3000, "SharedSize": 0, "Containers": 1}],
+            "Containers": [{"Id": "abcd", "SizeRw": 100}, {"Id": "efgh"}],
+            "Volumes": [
+                {"Name": "artifacts", "UsageData": {"Size": 800, "RefCount": 1}},
+                {"Name": "remote", "UsageData": {"Size": -1, "RefCount": 0}}
+            ],
+            "BuildCache": [
+                {"ID": "c1", "Size": 100, "Shared": false},
+                {"ID": "c2", "Size": 50, "Shared": true}
+            ]
+        }"#;
+
+        let usage =
+            DiskUsage::from_system_df(json, String::from("/var/lib/docker"), Some(6000)).unwrap();
+        assert_eq!(usage.images_size, 3000);
+        assert_eq!(usage.volumes_size, 800);
+        assert_eq!(usage.data_root_size, 4000);
+        assert_eq!(usage.used_percent(), Some(40.0));
+
+        let json = br#"{"LayersSize": 0, "Images": [], "Containers": null, "Volumes": null, "BuildCache": null}"#;
+        let usage = DiskUsage::from_system_df(json, String::from("/var/lib/docker"), None).unwrap();
+        assert_eq!(usage.data_root_size, 0);
+        assert_eq!(usage.used_percent(), None);
+    }
+}
@@ -0,0 +1,678 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Running jobs as pods in a Kubernetes cluster (endpoints with `endpoint_type = "kubernetes"`)
+//!
+//! The cluster is accessed with `kubectl`, so the context and credentials are the ones of the
+//! user that runs butido. Each job runs in a pod that idles until the inputs are copied into it,
+//! then the script is executed in the pod and the outputs are copied out of it, like for jobs in
+//! Docker containers. The pod is deleted when the job is done.
+
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use getset::{CopyGetters, Getters};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::RwLock;
+use tracing::{debug, trace, warn};
+
+use crate::config::EndpointName;
+use crate::endpoint::configured::read_artifact;
+use crate::endpoint::configured::read_package_sources;
+use crate::endpoint::util::forward_script_output;
+use crate::endpoint::FinalizedContainer;
+use crate::filestore::ReleaseStore;
+use crate::filestore::StagingStore;
+use crate::job::JobResource;
+use crate::job::RunnableJob;
+use crate::log::LogItem;
+use crate::package::ResourceLimits;
+use crate::package::Script;
+use crate::util::archive::NormalizedArchive;
+use crate::util::docker::ContainerHash;
+use crate::util::docker::ImageName;
+
+/// How long to wait for a pod to become ready (which includes pulling the image)
+const POD_READY_TIMEOUT: &str = "600s";
+
+/// A Kubernetes endpoint from the configuration
+///
+/// Unlike Docker endpoints, Kubernetes endpoints are not connected to during the setup, the
+/// cluster is only accessed when a job is prepared (see `prepare_pod()`).
+#[derive(Getters, CopyGetters)]
+pub struct KubernetesEndpoint {
+    /// The name of the endpoint in the configuration
+    #[getset(get = "pub")]
+    name: EndpointName,
+
+    /// Runs kubectl with the context (`uri`) and the namespace of the endpoint
+    kubectl: Kubectl,
+
+    /// How many jobs may run on the endpoint at the same time (`maxjobs`)
+    #[getset(get_copy = "pub")]
+    num_max_jobs: usize,
+
+    /// The tags of the endpoint, see `crate::config::Endpoint::tags`
+    #[getset(get = "pub")]
+    tags: Vec<String>,
+
+    /// The architecture of the endpoint, see `crate::config::Endpoint::arch`
+    #[getset(get = "pub")]
+    arch: Option<String>,
+
+    /// The number of jobs that currently run on the endpoint (see `KubernetesHandle`)
+    running_jobs: AtomicUsize,
+}
+
+impl KubernetesEndpoint {
+    pub fn new(name: EndpointName, config: &crate::config::Endpoint) -> Result<Self> {
+        which::which("kubectl")
+            .with_context(|| anyhow!("Finding kubectl for Kubernetes endpoint {}", name))?;
+
+        Ok(KubernetesEndpoint {
+            name,
+            kubectl: Kubectl {
+                context: Some(config.uri().clone()).filter(|uri| !uri.is_empty()),
+                namespace: config.namespace().clone(),
+            },
+            num_max_jobs: config.maxjobs(),
+            tags: config.tags().clone(),
+            arch: config.arch().clone(),
+            running_jobs: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn running_jobs(&self) -> usize {
+        self.running_jobs.load(Ordering::Relaxed)
+    }
+
+    /// The utilization of the endpoint, like `Endpoint::utilization()`
+    pub fn utilization(&self) -> f64 {
+        100.0 / (self.num_max_jobs as f64) * (self.running_jobs() as f64)
+    }
+
+    /// Create the pod of the job and copy the sources, patches, artifacts and script into it
+    ///
+    /// `attempt` is the number of the attempt to run the job (see `pod_name()`). If the pod does
+    /// not become ready (e.g., because it stays pending or fails) or the inputs cannot be copied
+    /// into it, the pod is deleted again.
+    pub async fn prepare_pod(
+        &self,
+        job: &RunnableJob,
+        attempt: usize,
+        staging_store: Arc<RwLock<StagingStore>>,
+        release_stores: &[Arc<ReleaseStore>],
+    ) -> Result<KubernetesPod> {
+        if !job.mounts().is_empty() {
+            return Err(anyhow!(
+                "Jobs with mounts (see 'containers.mounts' and 'mounts' in pkg.toml) cannot run on Kubernetes endpoints"
+            ));
+        }
+
+        let pod = pod_name(job.uuid(), attempt);
+        let env = job
+            .environment()
+            .map(|(k, v)| (k.as_ref(), v.as_str()))
+            .collect::<Vec<_>>();
+        let manifest = pod_manifest(
+            &pod,
+            job.uuid(),
+            job.image(),
+            &env,
+            job.package().resources().as_ref(),
+        );
+        trace!("Pod manifest: {}", manifest);
+
+        self.kubectl
+            .run(
+                &["create", "-f", "-"],
+                Some(manifest.to_string().into_bytes()),
+            )
+            .await
+            .with_context(|| anyhow!("Creating pod {} on '{}'", pod, self.name))?;
+        // From here on, the pod is deleted when it is dropped
+        let mut pod = KubernetesPod {
+            kubectl: self.kubectl.clone(),
+            name: pod,
+            script: job.script().clone(),
+            sources_hash: String::new(),
+        };
+
+        let timeout = format!("--timeout={POD_READY_TIMEOUT}");
+        let pod_ref = format!("pod/{}", pod.name);
+        debug!("Waiting for pod {} on '{}'", pod.name, self.name);
+        self.kubectl
+            .run(
+                &[
+                    "wait",
+                    "--for=condition=Ready",
+                    pod_ref.as_str(),
+                    timeout.as_str(),
+                ],
+                None,
+            )
+            .await
+            .with_context(|| {
+                if crate::util::offline::is_offline() {
+                    anyhow!(
+                        "Waiting for pod {} on '{}' (with --offline, the image {} is not pulled, it has to be present on the node)",
+                        pod.name,
+                        self.name,
+                        job.image()
+                    )
+                } else {
+                    anyhow!("Waiting for pod {} on '{}'", pod.name, self.name)
+                }
+            })?;
+
+        let sources = read_package_sources(job).await?;
+        let sources =
+            tokio::task::spawn_blocking(move || NormalizedArchive::build_entries(sources))
+                .await??;
+        let sources_hash = sources.sha256();
+        pod.extract(&sources)
+            .await
+            .with_context(|| anyhow!("Copying the sources to pod {}", pod.name))?;
+
+        // The archive is extracted in the root directory of the pod, so the paths are relative
+        let mut files = Vec::new();
+        for patch in job.package().patches() {
+            let buf = tokio::fs::read(patch)
+                .await
+                .with_context(|| anyhow!("Reading patch {}", patch.display()))?;
+            files.push((in_pod_root(crate::consts::PATCH_DIR_PATH).join(patch), buf));
+        }
+        for art in job.resources().iter().filter_map(JobResource::artifact) {
+            let file_name = art
+                .file_name()
+                .ok_or_else(|| anyhow!("BUG: artifact {} is not a file", art.display()))?;
+            let buf = read_artifact(art, &staging_store, release_stores).await?;
+            files.push((
+                in_pod_root(crate::consts::INPUTS_DIR_PATH).join(file_name),
+                buf,
+            ));
+        }
+        files.push((
+            in_pod_root(crate::consts::SCRIPT_PATH),
+            job.script().as_ref().as_bytes().to_vec(),
+        ));
+        pod.extract(&NormalizedArchive::build(files)?)
+            .await
+            .with_context(|| anyhow!("Copying the inputs to pod {}", pod.name))?;
+
+        pod.sources_hash = sources_hash;
+        Ok(pod)
+    }
+}
+
+/// Counts the job on the Kubernetes endpoint while it runs, like `EndpointHandle`
+pub struct KubernetesHandle(Arc<KubernetesEndpoint>);
+
+impl KubernetesHandle {
+    pub fn new(ep: Arc<KubernetesEndpoint>) -> Self {
+        let res = ep.running_jobs.fetch_add(1, Ordering::Relaxed);
+        trace!("Endpoint {} has one job more: {}", ep.name(), res + 1);
+        KubernetesHandle(ep)
+    }
+}
+
+impl Drop for KubernetesHandle {
+    fn drop(&mut self) {
+        let res = self.0.running_jobs.fetch_sub(1, Ordering::Relaxed);
+        trace!("Endpoint {} has one job less: {}", self.0.name(), res - 1);
+    }
+}
+
+impl std::ops::Deref for KubernetesHandle {
+    type Target = KubernetesEndpoint;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.deref()
+    }
+}
+
+/// The pod of a job on a Kubernetes endpoint, with the inputs copied into it
+///
+/// The pod idles (see `pod_manifest()`) and would hold its resources in the cluster forever, so
+/// it is deleted when this is dropped: after the job finished (successfully or not), but also
+/// if the job is aborted.
+#[derive(Getters)]
+pub struct KubernetesPod {
+    /// Runs kubectl for the endpoint the pod runs on
+    kubectl: Kubectl,
+
+    /// The name of the pod (see `pod_name()`)
+    name: String,
+
+    #[getset(get = "pub")]
+    script: Script,
+
+    /// The SHA-256 hash of the archive of the sources that were copied into the pod
+    #[getset(get = "pub")]
+    sources_hash: String,
+}
+
+impl KubernetesPod {
+    pub fn container_hash(&self) -> ContainerHash {
+        ContainerHash::from(self.name.clone())
+    }
+
+    /// Execute the packaging script in the pod
+    ///
+    /// Returns whether the script reported success (or failure) like
+    /// `StartedContainer::execute_script()`.
+    pub async fn execute(
+        &self,
+        logsink: UnboundedSender<LogItem>,
+        max_line_length: usize,
+    ) -> Result<Option<(bool, Option<String>)>> {
+        let mut child = self
+            .kubectl
+            .command()
+            .args([
+                "exec",
+                self.name.as_str(),
+                "--",
+                "/bin/bash",
+                crate::consts::SCRIPT_PATH,
+            ])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| anyhow!("Running the script in pod {}", self.name))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow!("BUG: No stdout of kubectl exec"))?;
+        let stderr = child
+            .stderr
+            .take()
+            .ok_or_else(|| anyhow!("BUG: No stderr of kubectl exec"))?;
+
+        let exit_info = forward_script_output(stdout, stderr, &logsink, max_line_length)
+            .await
+            .with_context(|| anyhow!("Fetching log from pod {}", self.name))?;
+        let status = child
+            .wait()
+            .await
+            .with_context(|| anyhow!("Waiting for the script in pod {}", self.name))?;
+        match exit_info {
+            Some((false, msg)) => Ok(Some((false, msg))),
+            _ if !status.success() => Ok(Some((false, Some(format!("Script failed: {status}"))))),
+            other => Ok(other),
+        }
+    }
+
+    /// Copy the outputs of a successful job to the staging store and delete the pod
+    ///
+    /// The outputs are written to a temporary file in the staging store, not kept in memory.
+    pub async fn finalize(
+        self,
+        exit_info: Option<(bool, Option<String>)>,
+        staging_store: Arc<RwLock<StagingStore>>,
+        arch: Option<&str>,
+    ) -> Result<FinalizedContainer> {
+        if let Some((false, msg)) = exit_info {
+            let err = anyhow!(
+                "Error during pod run: '{msg}' (pod {pod})",
+                msg = msg.as_deref().unwrap_or(""),
+                pod = self.name
+            );
+            return Ok(FinalizedContainer::new(vec![], Err(err)));
+        }
+
+        // The archive is written into the staging directory (so that it is on the same file
+        // system), it is removed in any case
+        let archive_path = staging_store
+            .read()
+            .await
+            .root_path()
+            .as_ref()
+            .join(format!(".{}-outputs.tar", self.name));
+        let result = async {
+            self.kubectl
+                .run_to_file(
+                    &[
+                        "exec",
+                        self.name.as_str(),
+                        "--",
+                        "tar",
+                        "-c",
+                        "-C",
+                        "/",
+                        crate::consts::OUTPUTS_DIR_NAME,
+                    ],
+                    &archive_path,
+                )
+                .await
+                .with_context(|| anyhow!("Copying the outputs from pod {}", self.name))?;
+            staging_store
+                .write()
+                .await
+                .write_files_from_tar_file(&archive_path, arch)
+                .context("Copying the outputs to the staging store")
+        }
+        .await;
+
+        if let Err(e) = tokio::fs::remove_file(&archive_path).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!("Failed to remove {}: {}", archive_path.display(), e);
+            }
+        }
+        Ok(FinalizedContainer::new(result?, Ok(())))
+    }
+
+    /// Extract a tar archive in the root directory of the pod
+    async fn extract(&self, archive: &NormalizedArchive) -> Result<()> {
+        self.kubectl
+            .run_from_file(
+                &[
+                    "exec",
+                    "-i",
+                    self.name.as_str(),
+                    "--",
+                    "tar",
+                    "-x",
+                    "-C",
+                    "/",
+                ],
+                archive.path(),
+            )
+            .await
+    }
+}
+
+impl Drop for KubernetesPod {
+    fn drop(&mut self) {
+        let name = std::mem::take(&mut self.name);
+        let kubectl = self.kubectl.clone();
+        debug!("Deleting pod {}", name);
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(async move {
+                    if let Err(e) = kubectl
+                        .run(&["delete", "pod", name.as_str(), "--wait=false"], None)
+                        .await
+                    {
+                        warn!("Failed to delete pod {}: {:?}", name, e);
+                    }
+                });
+            }
+            Err(_) => warn!(
+                "Cannot delete pod {}, delete it with `{} delete pod {}`",
+                name, kubectl, name
+            ),
+        }
+    }
+}
+
+/// Runs kubectl for a context and namespace
+#[derive(Clone, Debug)]
+struct Kubectl {
+    context: Option<String>,
+    namespace: Option<String>,
+}
+
+impl Kubectl {
+    fn command(&self) -> tokio::process::Command {
+        let mut command = tokio::process::Command::new("kubectl");
+        if let Some(context) = self.context.as_ref() {
+            command.arg("--context").arg(context);
+        }
+        if let Some(namespace) = self.namespace.as_ref() {
+            command.arg("--namespace").arg(namespace);
+        }
+        command
+    }
+
+    /// Run kubectl with the passed arguments and input and return its output
+    async fn run(&self, args: &[&str], stdin: Option<Vec<u8>>) -> Result<Vec<u8>> {
+        let mut command = self.command();
+        command
+            .args(args)
+            .stdin(if stdin.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        trace!("Running {:?}", command);
+
+        let mut child = command.spawn().context("Running kubectl")?;
+        if let (Some(input), Some(mut child_stdin)) = (stdin, child.stdin.take()) {
+            child_stdin
+                .write_all(&input)
+                .await
+                .context("Writing to kubectl")?;
+            // kubectl only finishes once its input is closed
+            drop(child_stdin);
+        }
+
+        let output = child
+            .wait_with_output()
+            .await
+            .context("Waiting for kubectl")?;
+        check_status(args, &output)?;
+        Ok(output.stdout)
+    }
+
+    /// Run kubectl with the passed arguments and the file at `path` as input
+    async fn run_from_file(&self, args: &[&str], path: &Path) -> Result<()> {
+        let file =
+            std::fs::File::open(path).with_context(|| anyhow!("Opening {}", path.display()))?;
+        let mut command = self.command();
+        command
+            .args(args)
+            .stdin(Stdio::from(file))
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        trace!("Running {:?}", command);
+
+        let output = command
+            .spawn()
+            .context("Running kubectl")?
+            .wait_with_output()
+            .await
+            .context("Waiting for kubectl")?;
+        check_status(args, &output)
+    }
+
+    /// Run kubectl with the passed arguments and write its output to the file at `path`
+    async fn run_to_file(&self, args: &[&str], path: &Path) -> Result<()> {
+        let file =
+            std::fs::File::create(path).with_context(|| anyhow!("Creating {}", path.display()))?;
+        let mut command = self.command();
+        command
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::from(file))
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        trace!("Running {:?}", command);
+
+        let output = command
+            .spawn()
+            .context("Running kubectl")?
+            .wait_with_output()
+            .await
+            .context("Waiting for kubectl")?;
+        check_status(args, &output)
+    }
+}
+
+/// Fail with the error output of kubectl if it did not succeed
+fn check_status(args: &[&str], output: &std::process::Output) -> Result<()> {
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "kubectl {} failed ({}): {}",
+            args.join(" "),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+impl std::fmt::Display for Kubectl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "kubectl")?;
+        if let Some(context) = self.context.as_ref() {
+            write!(f, " --context {context}")?;
+        }
+        if let Some(namespace) = self.namespace.as_ref() {
+            write!(f, " --namespace {namespace}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The path of a container path (e.g. `/inputs`) relative to the root directory of the pod
+fn in_pod_root(container_path: &str) -> PathBuf {
+    PathBuf::from(container_path.trim_start_matches('/'))
+}
+
+/// The name of the pod of an attempt to run a job
+///
+/// A job that is rescheduled or retried (see `EndpointScheduler::schedule_job()`) gets a new pod,
+/// while the pod of the previous attempt might still be terminating.
+fn pod_name(job_uuid: &uuid::Uuid, attempt: usize) -> String {
+    format!("butido-{job_uuid}-{attempt}")
+}
+
+/// The manifest of the pod of a job
+///
+/// The container idles until the script is executed in it (see `KubernetesPod::execute()`).
+fn pod_manifest(
+    pod: &str,
+    job_uuid: &uuid::Uuid,
+    image: &ImageName,
+    env: &[(&str, &str)],
+    resources: Option<&ResourceLimits>,
+) -> serde_json::Value {
+    let env = env
+        .iter()
+        .map(|(k, v)| serde_json::json!({ "name": k, "value": v }))
+        .collect::<Vec<_>>();
+
+    let mut limits = serde_json::Map::new();
+    if let Some(resources) = resources {
+        if let Some(cpu) = resources.cpu() {
+            limits.insert(String::from("cpu"), cpu.clone().into());
+        }
+        if let Some(memory) = resources.memory() {
+            limits.insert(String::from("memory"), memory.clone().into());
+        }
+    }
+
+    let mut container = serde_json::json!({
+        "name": "build",
+        "image": image.to_string(),
+        "command": ["sleep", "infinity"],
+        "env": env,
+        "resources": { "limits": limits },
+    });
+    if crate::util::offline::is_offline() {
+        // The image has to be present on the node already
+        container["imagePullPolicy"] = "Never".into();
+    }
+
+    serde_json::json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": {
+            "name": pod,
+            "labels": {
+                "app.kubernetes.io/managed-by": "butido",
+                (crate::consts::CONTAINER_LABEL_JOB_UUID): job_uuid.to_string(),
+            },
+        },
+        "spec": {
+            "restartPolicy": "Never",
+            "containers": [container],
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pod_name() {
+        let job_uuid = uuid::Uuid::new_v4();
+        assert_eq!(pod_name(&job_uuid, 0), format!("butido-{job_uuid}-0"));
+        assert_ne!(pod_name(&job_uuid, 0), pod_name(&job_uuid, 1));
+    }
+
+    #[test]
+    fn test_pod_manifest() {
+        let job_uuid = uuid::Uuid::new_v4();
+        let resources: ResourceLimits = toml::from_str("cpu = \"2\"\nmemory = \"8Gi\"").unwrap();
+        let manifest = pod_manifest(
+            "butido-pod",
+            &job_uuid,
+            &ImageName::from(String::from("debian:bookworm")),
+            &[("FOO", "bar")],
+            Some(&resources),
+        );
+
+        assert_eq!(manifest["kind"], "Pod");
+        assert_eq!(manifest["metadata"]["name"], "butido-pod");
+        assert_eq!(
+            manifest["metadata"]["labels"][crate::consts::CONTAINER_LABEL_JOB_UUID],
+            job_uuid.to_string()
+        );
+        assert_eq!(manifest["spec"]["restartPolicy"], "Never");
+
+        let container = &manifest["spec"]["containers"][0];
+        assert_eq!(container["image"], "debian:bookworm");
+        assert_eq!(
+            container["command"],
+            serde_json::json!(["sleep", "infinity"])
+        );
+        assert_eq!(
+            container["env"],
+            serde_json::json!([{ "name": "FOO", "value": "bar" }])
+        );
+        assert_eq!(
+            container["resources"]["limits"],
+            serde_json::json!({ "cpu": "2", "memory": "8Gi" })
+        );
+    }
+
+    #[test]
+    fn test_pod_manifest_without_resources() {
+        let manifest = pod_manifest(
+            "butido-pod",
+            &uuid::Uuid::new_v4(),
+            &ImageName::from(String::from("debian:bookworm")),
+            &[],
+            None,
+        );
+
+        let container = &manifest["spec"]["containers"][0];
+        assert_eq!(container["env"], serde_json::json!([]));
+        assert_eq!(container["resources"]["limits"], serde_json::json!({}));
+    }
+}
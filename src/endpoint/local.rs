@@ -0,0 +1,418 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Running jobs on the host instead of a Docker endpoint (`butido build --local-exec`)
+
+use std::ffi::OsString;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use getset::Getters;
+use regex::Regex;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::OwnedSemaphorePermit;
+use tokio::sync::RwLock;
+use tokio::sync::Semaphore;
+use tracing::{debug, trace};
+
+use crate::config::EndpointName;
+use crate::endpoint::configured::read_artifact;
+use crate::endpoint::configured::read_package_sources;
+use crate::endpoint::util::forward_script_output;
+use crate::endpoint::FinalizedContainer;
+use crate::filestore::ReleaseStore;
+use crate::filestore::StagingStore;
+use crate::job::JobResource;
+use crate::job::RunnableJob;
+use crate::log::LogItem;
+use crate::package::Script;
+use crate::util::archive::NormalizedArchive;
+use crate::util::docker::ContainerHash;
+
+/// How a job that runs on the host is isolated from it
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LocalIsolation {
+    /// The script runs directly on the host, the container paths in the script are replaced by
+    /// the paths in the work directory of the job (`--local-exec`)
+    None,
+
+    /// The script runs in a bubblewrap sandbox, in which the work directory of the job is mounted
+    /// at the container paths and the rest of the host file system is read-only (`--local-chroot`)
+    Bubblewrap,
+}
+
+impl LocalIsolation {
+    /// Recorded as the container hash of the jobs, to mark them as local jobs in the database
+    fn marker(&self) -> &'static str {
+        match self {
+            LocalIsolation::None => "local-exec",
+            LocalIsolation::Bubblewrap => "local-chroot",
+        }
+    }
+}
+
+/// Runs jobs on the host, one job at a time
+///
+/// Each job gets a work directory with the inputs, outputs and patches directories and the
+/// script, which is removed after the job succeeded (and kept for debugging if it failed).
+#[derive(Getters)]
+pub struct LocalExecutor {
+    /// The name under which the jobs are recorded in the database
+    #[getset(get = "pub")]
+    name: EndpointName,
+    isolation: LocalIsolation,
+    work_root: PathBuf,
+    slot: Arc<Semaphore>,
+}
+
+impl LocalExecutor {
+    pub fn new(isolation: LocalIsolation) -> Result<Self> {
+        if isolation == LocalIsolation::Bubblewrap {
+            which::which("bwrap").context("Finding bubblewrap (bwrap) for --local-chroot")?;
+        }
+
+        Ok(LocalExecutor {
+            name: EndpointName::from(String::from(crate::consts::LOCAL_ENDPOINT_NAME)),
+            isolation,
+            work_root: std::env::temp_dir().join("butido-local"),
+            slot: Arc::new(Semaphore::new(1)),
+        })
+    }
+
+    /// Wait until no other job runs on the host
+    pub async fn acquire_slot(&self) -> Result<OwnedSemaphorePermit> {
+        self.slot
+            .clone()
+            .acquire_owned()
+            .await
+            .context("Waiting for the local job slot")
+    }
+
+    /// Create the work directory of the job with its inputs, patches and script
+    pub async fn prepare(
+        &self,
+        job: &RunnableJob,
+        staging_store: Arc<RwLock<StagingStore>>,
+        release_stores: &[Arc<ReleaseStore>],
+    ) -> Result<LocalJob> {
+        if !job.mounts().is_empty() {
+            return Err(anyhow!(
+                "Jobs with mounts (see 'containers.mounts' and 'mounts' in pkg.toml) cannot run on the host"
+            ));
+        }
+
+        let work_dir = self.work_root.join(job.uuid().to_string());
+        debug!("Preparing work directory {}", work_dir.display());
+        for dir in [
+            crate::consts::INPUTS_DIR_PATH,
+            crate::consts::OUTPUTS_DIR_PATH,
+            crate::consts::PATCH_DIR_PATH,
+            crate::consts::BUILD_DIR_PATH,
+        ] {
+            let dir = in_work_dir(&work_dir, dir);
+            tokio::fs::create_dir_all(&dir)
+                .await
+                .with_context(|| anyhow!("Creating directory {}", dir.display()))?;
+        }
+
+        // The sources are hashed like the archive that is copied into a container
+        let sources = read_package_sources(job).await?;
+        let archive =
+            tokio::task::spawn_blocking(move || NormalizedArchive::build_entries(sources))
+                .await??;
+        let sources_hash = archive.sha256();
+        std::fs::File::open(archive.path())
+            .map(tar::Archive::new)
+            .and_then(|mut tar| tar.unpack(&work_dir))
+            .with_context(|| anyhow!("Unpacking the sources to {}", work_dir.display()))?;
+
+        for patch in job.package().patches() {
+            let destination = in_work_dir(&work_dir, crate::consts::PATCH_DIR_PATH).join(patch);
+            if let Some(parent) = destination.parent() {
+                tokio::fs::create_dir_all(parent)
+                    .await
+                    .with_context(|| anyhow!("Creating directory {}", parent.display()))?;
+            }
+            tokio::fs::copy(patch, &destination)
+                .await
+                .with_context(|| anyhow!("Copying patch {}", patch.display()))?;
+        }
+
+        for art in job.resources().iter().filter_map(JobResource::artifact) {
+            let file_name = art
+                .file_name()
+                .ok_or_else(|| anyhow!("BUG: artifact {} is not a file", art.display()))?;
+            let buf = read_artifact(art, &staging_store, release_stores).await?;
+            let destination =
+                in_work_dir(&work_dir, crate::consts::INPUTS_DIR_PATH).join(file_name);
+            tokio::fs::write(&destination, buf)
+                .await
+                .with_context(|| anyhow!("Writing artifact {}", destination.display()))?;
+        }
+
+        let script = job.script().clone();
+        let script_text = match self.isolation {
+            LocalIsolation::None => relocate_script(script.as_ref(), &work_dir),
+            LocalIsolation::Bubblewrap => script.as_ref().to_string(),
+        };
+        let script_path = in_work_dir(&work_dir, crate::consts::SCRIPT_PATH);
+        tokio::fs::write(&script_path, script_text)
+            .await
+            .with_context(|| anyhow!("Writing script {}", script_path.display()))?;
+
+        Ok(LocalJob {
+            work_dir,
+            isolation: self.isolation,
+            environment: job
+                .environment()
+                .map(|(k, v)| {
+                    let value = match self.isolation {
+                        LocalIsolation::None
+                            if k.as_ref().starts_with(crate::consts::SOURCE_ENV_VAR_PREFIX) =>
+                        {
+                            relocate_script(v, &work_dir)
+                        }
+                        _ => v.clone(),
+                    };
+                    (k.as_ref().to_string(), value)
+                })
+                .collect(),
+            script,
+            sources_hash,
+        })
+    }
+}
+
+/// A job whose work directory is prepared
+#[derive(Getters)]
+pub struct LocalJob {
+    #[getset(get = "pub")]
+    work_dir: PathBuf,
+    isolation: LocalIsolation,
+    environment: Vec<(String, String)>,
+
+    #[getset(get = "pub")]
+    script: Script,
+
+    /// The SHA-256 hash of the archive of the sources, like for jobs in containers
+    #[getset(get = "pub")]
+    sources_hash: String,
+}
+
+impl LocalJob {
+    pub fn container_hash(&self) -> ContainerHash {
+        ContainerHash::from(String::from(self.isolation.marker()))
+    }
+
+    /// Execute the packaging script
+    ///
+    /// Returns whether the script reported success (or failure) like
+    /// `StartedContainer::execute_script()`, a script that exits with a non-zero exit code always
+    /// failed.
+    pub async fn execute(
+        &self,
+        logsink: UnboundedSender<LogItem>,
+        max_line_length: usize,
+    ) -> Result<Option<(bool, Option<String>)>> {
+        let mut command = match self.isolation {
+            LocalIsolation::None => {
+                let mut command = tokio::process::Command::new("/bin/bash");
+                command
+                    .arg(in_work_dir(&self.work_dir, crate::consts::SCRIPT_PATH))
+                    .current_dir(&self.work_dir);
+                command
+            }
+            LocalIsolation::Bubblewrap => {
+                let mut command = tokio::process::Command::new("bwrap");
+                command
+                    .args(bubblewrap_args(&self.work_dir)?)
+                    .arg("/bin/bash")
+                    .arg(crate::consts::SCRIPT_PATH);
+                command
+            }
+        };
+        command
+            .envs(self.environment.iter().map(|(k, v)| (k, v)))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        trace!("Running local job: {:?}", command);
+
+        let mut child = command
+            .spawn()
+            .with_context(|| anyhow!("Running the script in {}", self.work_dir.display()))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow!("BUG: No stdout of the local job"))?;
+        let stderr = child
+            .stderr
+            .take()
+            .ok_or_else(|| anyhow!("BUG: No stderr of the local job"))?;
+
+        let exit_info = forward_script_output(stdout, stderr, &logsink, max_line_length)
+            .await
+            .context("Forwarding the output of the local job")?;
+
+        let status = child
+            .wait()
+            .await
+            .context("Waiting for the local job to exit")?;
+        match exit_info {
+            Some((false, msg)) => Ok(Some((false, msg))),
+            _ if !status.success() => Ok(Some((false, Some(format!("Script failed: {status}"))))),
+            other => Ok(other),
+        }
+    }
+
+    /// Move the outputs of a successful job to the staging store and remove the work directory
+    pub async fn finalize(
+        self,
+        exit_info: Option<(bool, Option<String>)>,
+        staging_store: Arc<RwLock<StagingStore>>,
+        arch: Option<&str>,
+    ) -> Result<FinalizedContainer> {
+        if let Some((false, msg)) = exit_info {
+            let err = anyhow!(
+                "Error during local run: '{msg}' (the work directory is kept at {path})",
+                msg = msg.as_deref().unwrap_or(""),
+                path = self.work_dir.display()
+            );
+            return Ok(FinalizedContainer::new(vec![], Err(err)));
+        }
+
+        let outputs = in_work_dir(&self.work_dir, crate::consts::OUTPUTS_DIR_PATH);
+        let archive = tokio::task::spawn_blocking(move || -> Result<Vec<u8>> {
+            let mut builder = tar::Builder::new(Vec::new());
+            builder
+                .append_dir_all(crate::consts::OUTPUTS_DIR_NAME, &outputs)
+                .with_context(|| anyhow!("Archiving {}", outputs.display()))?;
+            builder.into_inner().context("Finishing the archive")
+        })
+        .await??;
+
+        let artifacts = staging_store
+            .write()
+            .await
+            .write_files_from_tar_stream(futures::stream::once(async { Ok(archive) }), arch)
+            .await
+            .with_context(|| anyhow!("Copying the outputs to the staging store"))?;
+
+        tokio::fs::remove_dir_all(&self.work_dir)
+            .await
+            .with_context(|| anyhow!("Removing {}", self.work_dir.display()))?;
+        Ok(FinalizedContainer::new(artifacts, Ok(())))
+    }
+}
+
+/// The path of a container path (e.g. `/inputs`) in the work directory
+fn in_work_dir(work_dir: &Path, container_path: &str) -> PathBuf {
+    work_dir.join(container_path.trim_start_matches('/'))
+}
+
+/// Replace the container paths in the script by the paths in the work directory
+fn relocate_script(script: &str, work_dir: &Path) -> String {
+    // Only whole paths are replaced, not e.g. "/usr/share/patches"
+    let regex = Regex::new(r"(^|[^\w./-])(/inputs|/outputs|/patches|/build)\b").unwrap(); // constant
+    regex
+        .replace_all(script, |captures: &regex::Captures| {
+            format!("{}{}{}", &captures[1], work_dir.display(), &captures[2])
+        })
+        .into_owned()
+}
+
+/// The arguments of bubblewrap for a read-only view of the host with the work directory mounted
+fn bubblewrap_args(work_dir: &Path) -> Result<Vec<OsString>> {
+    const SKIPPED: [&str; 8] = [
+        "dev", "proc", "tmp", "inputs", "outputs", "patches", "build", "script",
+    ];
+
+    let mut args: Vec<OsString> = vec![
+        "--die-with-parent".into(),
+        "--unshare-all".into(),
+        "--share-net".into(),
+    ];
+    for entry in std::fs::read_dir("/").context("Reading /")? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if name.to_str().is_some_and(|name| SKIPPED.contains(&name)) {
+            continue;
+        }
+
+        let path = entry.path();
+        if entry.file_type()?.is_symlink() {
+            let target = std::fs::read_link(&path)
+                .with_context(|| anyhow!("Reading link {}", path.display()))?;
+            args.extend([
+                OsString::from("--symlink"),
+                target.into_os_string(),
+                path.into_os_string(),
+            ]);
+        } else {
+            args.extend([
+                OsString::from("--ro-bind"),
+                path.clone().into_os_string(),
+                path.into_os_string(),
+            ]);
+        }
+    }
+    args.extend(["--dev", "/dev", "--proc", "/proc", "--tmpfs", "/tmp"].map(OsString::from));
+
+    for (container_path, flag) in [
+        (crate::consts::INPUTS_DIR_PATH, "--bind"),
+        (crate::consts::OUTPUTS_DIR_PATH, "--bind"),
+        (crate::consts::PATCH_DIR_PATH, "--ro-bind"),
+        (crate::consts::BUILD_DIR_PATH, "--bind"),
+        (crate::consts::SCRIPT_PATH, "--ro-bind"),
+    ] {
+        args.extend([
+            OsString::from(flag),
+            in_work_dir(work_dir, container_path).into_os_string(),
+            OsString::from(container_path),
+        ]);
+    }
+    args.extend(["--chdir", "/"].map(OsString::from));
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relocate_script() {
+        let script = indoc::indoc!(
+            r#"
+            cd /inputs && tar xf /inputs/foo.tar.gz
+            patch -p1 < "/patches/fix.patch"
+            cp /usr/share/patches/x /outputsx
+            cd /build/src && make DESTDIR=/outputs install
+        "#
+        );
+        let relocated = relocate_script(script, Path::new("/tmp/job"));
+        assert_eq!(
+            relocated,
+            indoc::indoc!(
+                r#"
+                cd /tmp/job/inputs && tar xf /tmp/job/inputs/foo.tar.gz
+                patch -p1 < "/tmp/job/patches/fix.patch"
+                cp /usr/share/patches/x /outputsx
+                cd /tmp/job/build/src && make DESTDIR=/tmp/job/outputs install
+            "#
+            )
+        );
+    }
+}
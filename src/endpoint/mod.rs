@@ -17,4 +17,16 @@ pub use scheduler::*;
 mod configured;
 pub use configured::*;
 
+mod disk_usage;
+pub use disk_usage::*;
+
+mod local;
+pub use local::*;
+
+mod kubernetes;
+pub use kubernetes::*;
+
+mod upload;
+pub use upload::*;
+
 pub mod util;
@@ -8,6 +8,8 @@
 // SPDX-License-Identifier: EPL-2.0
 //
 
+use std::collections::HashMap;
+use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
 
@@ -18,31 +20,73 @@ use anyhow::Result;
 use colored::Colorize;
 use diesel::r2d2::ConnectionManager;
 use diesel::r2d2::Pool;
+use diesel::Connection;
 use diesel::PgConnection;
 use getset::{CopyGetters, Getters};
 use indicatif::ProgressBar;
 use itertools::Itertools;
 use tokio::io::AsyncWriteExt;
 use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::RwLock;
+use tokio_stream::StreamExt;
+use tracing::debug;
+use tracing::info;
 use tracing::trace;
+use tracing::warn;
 use uuid::Uuid;
 
+use crate::config::has_endpoint_tags;
+use crate::config::ContainerRetention;
+use crate::config::EndpointName;
+use crate::config::EndpointType;
+use crate::config::EnvironmentProbe;
+use crate::config::LogLimits;
+use crate::config::PostBuildHook;
 use crate::db::models as dbmodels;
 use crate::endpoint::Endpoint;
+use crate::endpoint::EndpointArtifactCache;
 use crate::endpoint::EndpointConfiguration;
 use crate::endpoint::EndpointHandle;
+use crate::endpoint::FinalizedContainer;
+use crate::endpoint::KubernetesEndpoint;
+use crate::endpoint::KubernetesHandle;
+use crate::endpoint::KubernetesPod;
+use crate::endpoint::LocalExecutor;
+use crate::endpoint::LocalJob;
+use crate::endpoint::RecordedChecksums;
+use crate::endpoint::UploadProgress;
+use crate::endpoint::UploadStats;
 use crate::filestore::ArtifactPath;
+use crate::filestore::CheckpointStore;
 use crate::filestore::ReleaseStore;
 use crate::filestore::StagingStore;
+use crate::job::CacheKey;
 use crate::job::JobResource;
 use crate::job::RunnableJob;
+use crate::log::FailureClassifiers;
+use crate::log::LogBuffer;
 use crate::log::LogItem;
+use crate::package::Package;
+use crate::package::Script;
+use crate::schema;
+use crate::util::docker::ContainerHash;
+use crate::util::docker::ImageName;
+use crate::util::progress::StatusLines;
+
+/// The interval in which a job that waits in the job queue checks whether it can be dispatched
+const QUEUE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
 
 #[derive(Getters, CopyGetters)]
 pub struct EndpointScheduler {
     log_dir: Option<PathBuf>,
+    log_limits: LogLimits,
+    failure_classifiers: Arc<FailureClassifiers>,
     endpoints: Vec<Arc<Endpoint>>,
+
+    /// The endpoints that run the jobs as pods in a Kubernetes cluster
+    kubernetes_endpoints: Vec<Arc<KubernetesEndpoint>>,
+
     #[getset(get = "pub")]
     max_endpoint_name_length: usize,
 
@@ -50,9 +94,53 @@ pub struct EndpointScheduler {
     release_stores: Vec<Arc<ReleaseStore>>,
     db: Pool<ConnectionManager<PgConnection>>,
     submit: crate::db::models::Submit,
+
+    /// Whether jobs that failed because their endpoint became unreachable may be rescheduled
+    reschedule_failed_jobs: bool,
+
+    /// The failure classes of jobs that are retried on another endpoint
+    retry_failure_classes: Vec<String>,
+
+    /// How often a job is retried at most because of its failure class
+    max_job_retries: usize,
+
+    /// The priority of the jobs of the submit in the job queue
+    priority: i32,
+
+    /// The task that checks the health of the endpoints periodically, if enabled
+    health_checker: Option<tokio::task::JoinHandle<()>>,
+
+    /// The task that refreshes the heartbeats of the jobs of the submit in the job queue
+    queue_heartbeat: tokio::task::JoinHandle<()>,
+
+    /// Runs the jobs on the host instead of the endpoints (`build --local-exec`), if set
+    local: Option<Arc<LocalExecutor>>,
+
+    /// How many artifacts are uploaded into the container of a job in parallel at most
+    max_parallel_uploads: usize,
+
+    /// The transfer statistics of the artifact uploads of all jobs
+    #[getset(get = "pub")]
+    upload_stats: Arc<UploadStats>,
+
+    /// The commands that check the artifacts of every successful job
+    post_build_hooks: Arc<Vec<PostBuildHook>>,
+
+    /// What happens to the containers of the jobs after the jobs finished
+    container_retention: ContainerRetention,
+
+    /// Where the checkpoints of the jobs are stored, if checkpoints are enabled
+    checkpoint_store: Option<Arc<CheckpointStore>>,
+
+    /// The commands that record the build environment in the container of every job
+    environment_probes: Arc<Vec<EnvironmentProbe>>,
+
+    /// The tags an endpoint must have to run jobs with an image (see `Endpoint::tags`)
+    image_endpoint_tags: HashMap<ImageName, Vec<String>>,
 }
 
 impl EndpointScheduler {
+    #[allow(clippy::too_many_arguments)]
     pub async fn setup(
         endpoints: Vec<EndpointConfiguration>,
         staging_store: Arc<RwLock<StagingStore>>,
@@ -60,92 +148,744 @@ impl EndpointScheduler {
         db: Pool<ConnectionManager<PgConnection>>,
         submit: crate::db::models::Submit,
         log_dir: Option<PathBuf>,
+        log_limits: LogLimits,
+        failure_classifiers: FailureClassifiers,
+        health_check_interval: Option<std::time::Duration>,
+        reschedule_failed_jobs: bool,
+        retry_failure_classes: Vec<String>,
+        max_job_retries: usize,
+        priority: i32,
+        local: Option<LocalExecutor>,
+        max_parallel_uploads: usize,
+        post_build_hooks: Vec<PostBuildHook>,
+        container_retention: ContainerRetention,
+        checkpoint_store: Option<CheckpointStore>,
+        environment_probes: Vec<EnvironmentProbe>,
+        image_endpoint_tags: HashMap<ImageName, Vec<String>>,
     ) -> Result<Self> {
+        let (kubernetes_endpoints, endpoints): (Vec<_>, Vec<_>) = endpoints
+            .into_iter()
+            .partition(|epc| *epc.endpoint().endpoint_type() == EndpointType::Kubernetes);
+        let kubernetes_endpoints = kubernetes_endpoints
+            .iter()
+            .map(|epc| {
+                KubernetesEndpoint::new(epc.endpoint_name().clone(), epc.endpoint()).map(Arc::new)
+            })
+            .collect::<Result<Vec<_>>>()?;
         let endpoints = crate::endpoint::util::setup_endpoints(endpoints).await?;
         let max_endpoint_name_length = endpoints
             .iter()
             .map(|ep| ep.name().len())
+            .chain(kubernetes_endpoints.iter().map(|ep| ep.name().len()))
+            .chain(local.iter().map(|local| local.name().len()))
             .max()
             .unwrap_or(0);
 
+        let health_checker = health_check_interval
+            .map(|interval| tokio::spawn(check_endpoint_health(endpoints.clone(), interval)));
+        let queue_heartbeat = tokio::spawn(refresh_queue_heartbeats(db.clone(), submit.clone()));
+
         Ok(EndpointScheduler {
             log_dir,
+            log_limits,
+            failure_classifiers: Arc::new(failure_classifiers),
             endpoints,
+            kubernetes_endpoints,
             max_endpoint_name_length,
             staging_store,
             release_stores,
             db,
             submit,
+            reschedule_failed_jobs,
+            retry_failure_classes,
+            max_job_retries,
+            priority,
+            health_checker,
+            queue_heartbeat,
+            local: local.map(Arc::new),
+            max_parallel_uploads,
+            upload_stats: Arc::new(UploadStats::default()),
+            post_build_hooks: Arc::new(post_build_hooks),
+            container_retention,
+            checkpoint_store: checkpoint_store.map(Arc::new),
+            environment_probes: Arc::new(environment_probes),
+            image_endpoint_tags,
         })
     }
 
+    /// Whether the jobs run on the host instead of the endpoints
+    pub fn is_local(&self) -> bool {
+        self.local.is_some()
+    }
+
     /// Schedule a Job
     ///
+    /// The job is added to the job queue (see `dbmodels::QueuedJob`) and dispatched to an endpoint
+    /// when it is the next job in the queue.
+    ///
     /// # Warning
     ///
-    /// This function blocks as long as there is no free endpoint available!
+    /// This function blocks as long as the job is not the next job in the queue or there is no
+    /// free endpoint available!
+    ///
+    /// The job is not scheduled on the excluded endpoints, unless all endpoints are excluded.
+    ///
+    /// Jobs that run on the host bypass the job queue and only wait until no other job of the
+    /// submit runs on the host.
     pub async fn schedule_job(
         &self,
         job: RunnableJob,
         bar: indicatif::ProgressBar,
+        status_lines: StatusLines,
+        excluded_endpoints: &[EndpointName],
     ) -> Result<JobHandle> {
-        let endpoint = self.select_free_endpoint().await?;
+        let target = match self.local.as_ref() {
+            Some(local) => JobTarget::Local(local.clone(), local.acquire_slot().await?),
+            None => self.enqueue_job(&job, excluded_endpoints).await?,
+        };
 
         Ok(JobHandle {
             log_dir: self.log_dir.clone(),
+            log_limits: self.log_limits,
+            failure_classifiers: self.failure_classifiers.clone(),
             bar,
-            endpoint,
+            status_lines,
+            target,
             max_endpoint_name_length: self.max_endpoint_name_length,
             job,
             staging_store: self.staging_store.clone(),
             release_stores: self.release_stores.clone(),
             db: self.db.clone(),
             submit: self.submit.clone(),
+            max_parallel_uploads: self.max_parallel_uploads,
+            upload_stats: self.upload_stats.clone(),
+            post_build_hooks: self.post_build_hooks.clone(),
+            container_retention: self.container_retention,
+            checkpoint_store: self.checkpoint_store.clone(),
+            environment_probes: self.environment_probes.clone(),
+            // Every reschedule and retry excludes the endpoint of the failed attempt
+            attempt: excluded_endpoints.len(),
         })
     }
 
-    async fn select_free_endpoint(&self) -> Result<EndpointHandle> {
-        loop {
-            let ep = self
-                .endpoints
-                .iter()
-                .filter(|ep| {
-                    // filter out all running containers where the number of max jobs is reached
-                    let r = ep.running_jobs() < ep.num_max_jobs();
-                    trace!(
-                        "Endpoint {} considered for scheduling job: {}",
-                        ep.name(),
-                        r
+    /// Add the job to the job queue and wait until it is dispatched to an endpoint
+    async fn enqueue_job(
+        &self,
+        job: &RunnableJob,
+        excluded_endpoints: &[EndpointName],
+    ) -> Result<JobTarget> {
+        // Checked before the job is queued, it would block the queue otherwise
+        let requirements = self.endpoint_requirements(job);
+        let suitable_endpoints = self
+            .endpoints
+            .iter()
+            .map(|ep| (ep.tags(), ep.arch()))
+            .chain(
+                self.kubernetes_endpoints
+                    .iter()
+                    .map(|ep| (ep.tags(), ep.arch())),
+            )
+            .filter(|(tags, arch)| requirements.is_met_by(tags, arch))
+            .count();
+        if suitable_endpoints == 0 {
+            return Err(anyhow!(
+                "No endpoint has {} that are required by {} {} with image {}",
+                requirements,
+                job.package().name(),
+                job.package().version(),
+                job.image()
+            ));
+        }
+
+        let priority = self.priority + job.package().priority().unwrap_or(0);
+        let mut queued_job =
+            dbmodels::QueuedJob::enqueue(&mut self.db.get()?, &self.submit, job.uuid(), priority)?;
+        self.select_free_endpoint(&mut queued_job, excluded_endpoints, &requirements)
+            .await
+    }
+
+    /// What an endpoint must provide to run the job: the tags required by its package and image
+    /// and the architecture of the job
+    fn endpoint_requirements(&self, job: &RunnableJob) -> EndpointRequirements {
+        let tags = job
+            .package()
+            .endpoint_tags()
+            .iter()
+            .flatten()
+            .chain(
+                self.image_endpoint_tags
+                    .get(job.image())
+                    .into_iter()
+                    .flatten(),
+            )
+            .unique()
+            .cloned()
+            .collect();
+
+        EndpointRequirements {
+            tags,
+            arch: job.arch().clone(),
+        }
+    }
+
+    /// Get the digest of the image of a job
+    ///
+    /// All Docker endpoints the job can run on (see `EndpointRequirements`, a multi-architecture
+    /// image has a different ID on each architecture) are asked for the ID of the image. If the
+    /// endpoints do not agree on the ID (e.g., because they pulled the image at different points
+    /// in time), `None` is returned, because the result of a job would then depend on the
+    /// endpoint it was scheduled on.
+    ///
+    /// Kubernetes endpoints are not asked, because the nodes of the cluster pull the images
+    /// themselves. The digest is `None` if the job can only run on Kubernetes endpoints, jobs that
+    /// are dispatched to Kubernetes endpoints are not cached (see `JobHandle::caches_artifacts()`).
+    ///
+    /// If an endpoint cannot inspect the image (e.g., because it did not pull it yet or is not
+    /// reachable), the digest is `None` as well: The job is not cached, but it does not fail.
+    pub async fn image_digest(&self, job: &RunnableJob) -> Option<String> {
+        let requirements = self.endpoint_requirements(job);
+        let results = self
+            .endpoints
+            .iter()
+            .filter(|ep| requirements.is_met_by(ep.tags(), ep.arch()))
+            .map(|ep| ep.image_id(job.image()))
+            .collect::<futures::stream::FuturesUnordered<_>>()
+            .collect::<Vec<Result<String>>>()
+            .await;
+
+        let mut ids = Vec::with_capacity(results.len());
+        for result in results {
+            match result {
+                Ok(id) => ids.push(id),
+                Err(e) => {
+                    warn!(
+                        job_uuid = %job.uuid(),
+                        "Cannot get the digest of image {}, not caching job: {:?}",
+                        job.image(),
+                        e
                     );
-                    r
-                })
-                .sorted_by(|ep1, ep2| {
-                    ep1.utilization()
-                        .partial_cmp(&ep2.utilization())
-                        .unwrap_or(std::cmp::Ordering::Equal)
-                })
-                .next();
-
-            if let Some(endpoint) = ep {
-                return Ok(EndpointHandle::new(endpoint.clone()));
+                    return None;
+                }
+            }
+        }
+
+        if ids.is_empty() {
+            trace!(
+                job_uuid = %job.uuid(),
+                "No Docker endpoint can run the job, not caching it"
+            );
+            None
+        } else if ids.iter().all_equal() {
+            ids.into_iter().next()
+        } else {
+            warn!(
+                job_uuid = %job.uuid(),
+                "Image {} differs between endpoints, not caching job: {:?}",
+                job.image(),
+                ids
+            );
+            None
+        }
+    }
+
+    /// Record a job whose artifacts were fetched from the remote build cache instead of running it
+    ///
+    /// The job is recorded with the `remote-cache` endpoint and its cache key, the fetched files
+    /// as its artifacts. Otherwise the files in the staging store would not be known to the
+    /// database (see "db artifacts --missing-on-disk") and could not be found in the cache by
+    /// later builds.
+    pub async fn record_remote_cache_hit(
+        &self,
+        job: &RunnableJob,
+        cache_key: &CacheKey,
+        paths: &[ArtifactPath],
+    ) -> Result<()> {
+        let mut new_artifacts = Vec::with_capacity(paths.len());
+        {
+            let staging_read = self.staging_store.read().await;
+            for p in paths.iter() {
+                let full_path = staging_read
+                    .root_path()
+                    .join(p)?
+                    .ok_or_else(|| anyhow!("Artifact not in staging store: {:?}", p))?;
+                let size = tokio::fs::metadata(full_path.joined())
+                    .await
+                    .with_context(|| anyhow!("Getting size of artifact {}", p.display()))?
+                    .len();
+                let sha256 = full_path.sha256().await?.to_string();
+                let kind = job.package().artifact_kind(p.as_ref());
+                new_artifacts.push((p, size, kind, sha256));
+            }
+        }
+
+        let now = chrono::offset::Local::now().naive_local();
+        let log =
+            format!("Artifacts fetched from the remote build cache (cache key {cache_key})\n");
+        let input_artifacts = job
+            .resources()
+            .iter()
+            .filter_map(JobResource::artifact)
+            .map(|path| path.display().to_string())
+            .collect();
+
+        self.db
+            .get()?
+            .transaction::<_, Error, _>(|conn| {
+                let endpoint = dbmodels::Endpoint::create_or_fetch(
+                    conn,
+                    &EndpointName::from(String::from(crate::consts::REMOTE_CACHE_ENDPOINT_NAME)),
+                )?;
+                let package = dbmodels::Package::create_or_fetch(conn, job.package())?;
+                let image = dbmodels::Image::create_or_fetch(conn, job.image())?;
+                let db_job = dbmodels::Job::create(
+                    conn,
+                    job.uuid(),
+                    &self.submit,
+                    &endpoint,
+                    &package,
+                    &image,
+                    &ContainerHash::from(cache_key.to_string()),
+                    job.script(),
+                    &log,
+                    Some(cache_key),
+                    &now,
+                    &now,
+                    None,
+                    None,
+                    "",
+                    job.arch().as_deref(),
+                    input_artifacts,
+                )?;
+
+                for (p, size, kind, sha256) in new_artifacts {
+                    trace!("DB: Creating artifact entry for path: {}", p.display());
+                    dbmodels::Artifact::create(conn, p, &db_job, Some(size), kind, Some(&sha256))?;
+                }
+                Ok(())
+            })
+            .with_context(|| {
+                anyhow!(
+                    "Recording the artifacts of job {} from the remote cache in database",
+                    job.uuid()
+                )
+            })
+    }
+
+    /// Check whether a job that failed on an endpoint may be rescheduled on another endpoint
+    ///
+    /// This is only the case if rescheduling is enabled, the job was not retried or rescheduled
+    /// `max_job_retries` times yet (`retries`), the endpoint is not reachable (anymore) and the
+    /// job was not recorded in the database yet (i.e., the container did not finish).
+    pub async fn may_reschedule(
+        &self,
+        endpoint_name: &EndpointName,
+        job_uuid: &Uuid,
+        retries: usize,
+    ) -> Result<bool> {
+        use diesel::ExpressionMethods;
+        use diesel::QueryDsl;
+        use diesel::RunQueryDsl;
+
+        if !self.reschedule_failed_jobs || retries >= self.max_job_retries {
+            return Ok(false);
+        }
+
+        let Some(endpoint) = self.endpoints.iter().find(|ep| ep.name() == endpoint_name) else {
+            return Ok(false);
+        };
+
+        // The periodic health check might not have noticed the failure yet
+        if endpoint.check_health().await == Some(false) {
+            warn!(
+                "Endpoint {} is not reachable, marking it as degraded",
+                endpoint.name()
+            );
+        }
+        if endpoint.is_healthy() {
+            return Ok(false);
+        }
+
+        let recorded = schema::jobs::table
+            .filter(schema::jobs::uuid.eq(*job_uuid))
+            .count()
+            .get_result::<i64>(&mut self.db.get()?)
+            .with_context(|| anyhow!("Checking whether job {} was recorded", job_uuid))?;
+        Ok(recorded == 0)
+    }
+
+    /// Check whether a failed job is retried on another endpoint because of its failure class
+    ///
+    /// If so, the failed attempt is marked as retried in the database (so that it does not count
+    /// as a failure of the package) and the failure class is returned.
+    pub async fn may_retry(
+        &self,
+        job_uuid: &Uuid,
+        result: &Result<Result<Vec<ArtifactPath>>>,
+        retries: usize,
+    ) -> Result<Option<String>> {
+        if retries >= self.max_job_retries || self.retry_failure_classes.is_empty() {
+            return Ok(None);
+        }
+
+        let job = match result {
+            Ok(Ok(_)) => return Ok(None),
+            _ => dbmodels::Job::with_uuid(&mut self.db.get()?, job_uuid)?,
+        };
+        let Some(class) = retry_class(
+            result,
+            job.as_ref().map(|job| job.failure_class.as_deref()),
+            retries,
+            self.max_job_retries,
+            &self.retry_failure_classes,
+        ) else {
+            return Ok(None);
+        };
+
+        if let Some(job) = job {
+            job.mark_retried(&mut self.db.get()?)?;
+        }
+        Ok(Some(class))
+    }
+
+    async fn select_free_endpoint(
+        &self,
+        queued_job: &mut dbmodels::QueuedJob,
+        excluded_endpoints: &[EndpointName],
+        requirements: &EndpointRequirements,
+    ) -> Result<JobTarget> {
+        let all_excluded = all_excluded(
+            self.endpoints
+                .iter()
+                .map(|ep| (ep.name(), ep.tags(), ep.arch()))
+                .chain(
+                    self.kubernetes_endpoints
+                        .iter()
+                        .map(|ep| (ep.name(), ep.tags(), ep.arch())),
+                )
+                .filter(|(_, tags, arch)| requirements.is_met_by(tags, arch))
+                .map(|(name, _, _)| name),
+            excluded_endpoints,
+        );
+
+        loop {
+            // A job that cannot be placed on an endpoint of this process is skipped by the queue,
+            // so it does not block the jobs of other submits
+            let can_dispatch = self
+                .free_endpoint(excluded_endpoints, all_excluded, requirements)
+                .is_some();
+            if !queued_job.is_next(&mut self.db.get()?, can_dispatch)? {
+                trace!(
+                    "Job {} is not the next job in the queue",
+                    queued_job.job_uuid
+                );
+                tokio::time::sleep(QUEUE_POLL_INTERVAL).await;
+                continue;
+            }
+
+            // The endpoint might have been taken by another job of this process in the meantime
+            if let Some(free) = self.free_endpoint(excluded_endpoints, all_excluded, requirements) {
+                queued_job.mark_dispatched(&mut self.db.get()?)?;
+                return Ok(free.into_target());
             } else {
                 trace!("No free endpoint found, retry...");
-                tokio::task::yield_now().await
+                tokio::time::sleep(QUEUE_POLL_INTERVAL).await
+            }
+        }
+    }
+
+    /// Find the least utilized endpoint that is free and can run a job with the `requirements`
+    fn free_endpoint(
+        &self,
+        excluded_endpoints: &[EndpointName],
+        all_excluded: bool,
+        requirements: &EndpointRequirements,
+    ) -> Option<FreeEndpoint<'_>> {
+        let ep = self
+            .endpoints
+            .iter()
+            .filter(|ep| ep.is_healthy())
+            .filter(|ep| requirements.is_met_by(ep.tags(), ep.arch()))
+            .filter(|ep| is_not_excluded(ep.name(), excluded_endpoints, all_excluded))
+            .filter(|ep| {
+                // filter out all running containers where the number of max jobs is reached
+                let r = ep.running_jobs() < ep.num_max_jobs();
+                trace!(
+                    "Endpoint {} considered for scheduling job: {}",
+                    ep.name(),
+                    r
+                );
+                r
+            })
+            .sorted_by(|ep1, ep2| {
+                ep1.utilization()
+                    .partial_cmp(&ep2.utilization())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .next();
+
+        let kubernetes_ep = self
+            .kubernetes_endpoints
+            .iter()
+            .filter(|ep| requirements.is_met_by(ep.tags(), ep.arch()))
+            .filter(|ep| is_not_excluded(ep.name(), excluded_endpoints, all_excluded))
+            .filter(|ep| ep.running_jobs() < ep.num_max_jobs())
+            .sorted_by(|ep1, ep2| {
+                ep1.utilization()
+                    .partial_cmp(&ep2.utilization())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .next();
+
+        match (ep, kubernetes_ep) {
+            (Some(ep), Some(kubernetes_ep)) if kubernetes_ep.utilization() < ep.utilization() => {
+                Some(FreeEndpoint::Kubernetes(kubernetes_ep))
+            }
+            (Some(ep), _) => Some(FreeEndpoint::Docker(ep)),
+            (None, Some(kubernetes_ep)) => Some(FreeEndpoint::Kubernetes(kubernetes_ep)),
+            (None, None) => None,
+        }
+    }
+}
+
+/// The failure class because of which a failed job is retried on another endpoint, if it is
+/// retried at all
+///
+/// `recorded` is the failure class of the job if it was recorded in the database (i.e., the
+/// container finished). A job is retried at most `max_job_retries` times (`retries`).
+fn retry_class(
+    result: &Result<Result<Vec<ArtifactPath>>>,
+    recorded: Option<Option<&str>>,
+    retries: usize,
+    max_job_retries: usize,
+    retry_failure_classes: &[String],
+) -> Option<String> {
+    if retries >= max_job_retries {
+        return None;
+    }
+
+    let class = match (result, recorded) {
+        (Ok(Err(_)), Some(class)) => class.map(String::from),
+        // The job was not recorded, i.e. the container did not finish
+        (Err(_), None) => Some(String::from(crate::log::DOCKER_FAILURE_CLASS)),
+        _ => None,
+    };
+    class.filter(|class| retry_failure_classes.contains(class))
+}
+
+/// Whether all endpoints that can run a job (`candidates`) are excluded for it
+fn all_excluded<'a>(
+    mut candidates: impl Iterator<Item = &'a EndpointName>,
+    excluded_endpoints: &[EndpointName],
+) -> bool {
+    candidates.all(|name| excluded_endpoints.contains(name))
+}
+
+/// Whether a job may run on the endpoint `name`
+///
+/// A job is not scheduled on the endpoints it failed on (`excluded_endpoints`), unless it failed
+/// on all endpoints that can run it (`all_excluded`).
+fn is_not_excluded(
+    name: &EndpointName,
+    excluded_endpoints: &[EndpointName],
+    all_excluded: bool,
+) -> bool {
+    all_excluded || !excluded_endpoints.contains(name)
+}
+
+impl Drop for EndpointScheduler {
+    fn drop(&mut self) {
+        if let Some(health_checker) = self.health_checker.as_ref() {
+            health_checker.abort();
+        }
+        self.queue_heartbeat.abort();
+    }
+}
+
+/// Refresh the heartbeats of the jobs of the submit in the job queue periodically
+///
+/// The jobs that were dispatched would otherwise be removed from the queue as jobs of a killed
+/// butido process while they run.
+async fn refresh_queue_heartbeats(
+    db: Pool<ConnectionManager<PgConnection>>,
+    submit: crate::db::models::Submit,
+) {
+    let mut interval = tokio::time::interval(dbmodels::QueuedJob::HEARTBEAT_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+        let refreshed = db
+            .get()
+            .map_err(Error::from)
+            .and_then(|mut conn| dbmodels::QueuedJob::refresh_heartbeats(&mut conn, &submit));
+        if let Err(e) = refreshed {
+            warn!("Cannot refresh the heartbeats in the job queue: {:?}", e);
+        }
+    }
+}
+
+/// Check the health of the endpoints periodically
+///
+/// No jobs are scheduled on endpoints that are not healthy (see
+/// `EndpointScheduler::select_free_endpoint()`), so the jobs are scheduled on the remaining
+/// endpoints until the endpoint is reachable again.
+async fn check_endpoint_health(endpoints: Vec<Arc<Endpoint>>, interval: std::time::Duration) {
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    // The first tick completes immediately, but the endpoints were just checked during the setup
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+        let changes = futures::future::join_all(
+            endpoints
+                .iter()
+                .map(|ep| async move { (ep, ep.check_health().await) }),
+        )
+        .await;
+
+        for (ep, change) in changes {
+            match change {
+                Some(false) => warn!(
+                    "Endpoint {} is not reachable, marking it as degraded",
+                    ep.name()
+                ),
+                Some(true) => info!("Endpoint {} is reachable again", ep.name()),
+                None => {}
+            }
+        }
+    }
+}
+
+/// What an endpoint must provide to run a job
+struct EndpointRequirements {
+    /// The tags required by the package and the image of the job
+    tags: Vec<String>,
+
+    /// The architecture the job builds for, if any
+    arch: Option<String>,
+}
+
+impl EndpointRequirements {
+    fn is_met_by(&self, tags: &[String], arch: &Option<String>) -> bool {
+        has_endpoint_tags(tags, &self.tags) && (self.arch.is_none() || self.arch == *arch)
+    }
+}
+
+impl std::fmt::Display for EndpointRequirements {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
+        write!(f, "the tags [{}]", self.tags.join(", "))?;
+        if let Some(arch) = self.arch.as_ref() {
+            write!(f, " and the architecture {arch}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Where a job runs
+enum JobTarget {
+    Endpoint(EndpointHandle),
+
+    /// On the host, while holding the slot for local jobs
+    Local(Arc<LocalExecutor>, tokio::sync::OwnedSemaphorePermit),
+
+    /// In a pod in a Kubernetes cluster
+    Kubernetes(KubernetesHandle),
+}
+
+/// An endpoint that has a free slot for a job
+enum FreeEndpoint<'a> {
+    Docker(&'a Arc<Endpoint>),
+    Kubernetes(&'a Arc<KubernetesEndpoint>),
+}
+
+impl FreeEndpoint<'_> {
+    /// Occupy the slot of the endpoint
+    fn into_target(self) -> JobTarget {
+        match self {
+            FreeEndpoint::Docker(ep) => JobTarget::Endpoint(EndpointHandle::new(ep.clone())),
+            FreeEndpoint::Kubernetes(ep) => {
+                JobTarget::Kubernetes(KubernetesHandle::new(ep.clone()))
             }
         }
     }
 }
 
+/// A job that is prepared to run outside of a Docker endpoint
+enum PreparedJob {
+    Local(LocalJob),
+    Kubernetes(KubernetesPod),
+}
+
+impl PreparedJob {
+    fn container_hash(&self) -> ContainerHash {
+        match self {
+            PreparedJob::Local(job) => job.container_hash(),
+            PreparedJob::Kubernetes(pod) => pod.container_hash(),
+        }
+    }
+
+    fn script(&self) -> &Script {
+        match self {
+            PreparedJob::Local(job) => job.script(),
+            PreparedJob::Kubernetes(pod) => pod.script(),
+        }
+    }
+
+    fn sources_hash(&self) -> &String {
+        match self {
+            PreparedJob::Local(job) => job.sources_hash(),
+            PreparedJob::Kubernetes(pod) => pod.sources_hash(),
+        }
+    }
+
+    async fn execute(
+        &self,
+        logsink: tokio::sync::mpsc::UnboundedSender<LogItem>,
+        max_line_length: usize,
+    ) -> Result<Option<(bool, Option<String>)>> {
+        match self {
+            PreparedJob::Local(job) => job.execute(logsink, max_line_length).await,
+            PreparedJob::Kubernetes(pod) => pod.execute(logsink, max_line_length).await,
+        }
+    }
+
+    async fn finalize(
+        self,
+        exit_info: Option<(bool, Option<String>)>,
+        staging_store: Arc<RwLock<StagingStore>>,
+        arch: Option<&str>,
+    ) -> Result<FinalizedContainer> {
+        match self {
+            PreparedJob::Local(job) => job.finalize(exit_info, staging_store, arch).await,
+            PreparedJob::Kubernetes(pod) => pod.finalize(exit_info, staging_store, arch).await,
+        }
+    }
+}
+
 pub struct JobHandle {
     log_dir: Option<PathBuf>,
-    endpoint: EndpointHandle,
+    log_limits: LogLimits,
+    failure_classifiers: Arc<FailureClassifiers>,
+    target: JobTarget,
     max_endpoint_name_length: usize,
     job: RunnableJob,
     bar: ProgressBar,
+    status_lines: StatusLines,
     db: Pool<ConnectionManager<PgConnection>>,
     staging_store: Arc<RwLock<StagingStore>>,
     release_stores: Vec<Arc<ReleaseStore>>,
     submit: crate::db::models::Submit,
+    max_parallel_uploads: usize,
+    upload_stats: Arc<UploadStats>,
+    post_build_hooks: Arc<Vec<PostBuildHook>>,
+    container_retention: ContainerRetention,
+    checkpoint_store: Option<Arc<CheckpointStore>>,
+    environment_probes: Arc<Vec<EnvironmentProbe>>,
+
+    /// The number of the attempt to run the job, 0 for the first one
+    attempt: usize,
 }
 
 impl std::fmt::Debug for JobHandle {
@@ -155,61 +895,161 @@ impl std::fmt::Debug for JobHandle {
 }
 
 impl JobHandle {
+    /// The name of the endpoint the job is scheduled on
+    pub fn endpoint_name(&self) -> &EndpointName {
+        match &self.target {
+            JobTarget::Endpoint(endpoint) => endpoint.name(),
+            JobTarget::Local(local, _) => local.name(),
+            JobTarget::Kubernetes(kubernetes) => kubernetes.name(),
+        }
+    }
+
+    /// Whether the artifacts of the job are recorded with its cache key
+    ///
+    /// The image digest that goes into the cache key is only known for Docker endpoints (see
+    /// `EndpointScheduler::image_digest()`).
+    pub fn caches_artifacts(&self) -> bool {
+        matches!(self.target, JobTarget::Endpoint(_))
+    }
+
     pub async fn run(self) -> Result<Result<Vec<ArtifactPath>>> {
+        match self.target {
+            JobTarget::Endpoint(_) => self.run_on_endpoint().await,
+            JobTarget::Local(..) | JobTarget::Kubernetes(_) => self.run_without_docker().await,
+        }
+    }
+
+    async fn run_on_endpoint(self) -> Result<Result<Vec<ArtifactPath>>> {
+        let JobTarget::Endpoint(endpoint_handle) = &self.target else {
+            return Err(anyhow!(
+                "BUG: Job {} is not scheduled on an endpoint",
+                self.job.uuid()
+            ));
+        };
         let (log_sender, log_receiver) = tokio::sync::mpsc::unbounded_channel::<LogItem>();
-        let endpoint_uri = self.endpoint.uri().clone();
-        let endpoint_name = self.endpoint.name().clone();
-        let endpoint =
-            dbmodels::Endpoint::create_or_fetch(&mut self.db.get().unwrap(), self.endpoint.name())?;
+        let endpoint_uri = endpoint_handle.uri().clone();
+        let endpoint_name = endpoint_handle.name().clone();
+        let endpoint = dbmodels::Endpoint::create_or_fetch(
+            &mut self.db.get().unwrap(),
+            endpoint_handle.name(),
+        )?;
         let package =
             dbmodels::Package::create_or_fetch(&mut self.db.get().unwrap(), self.job.package())?;
         let image =
             dbmodels::Image::create_or_fetch(&mut self.db.get().unwrap(), self.job.image())?;
         let envs = self.create_env_in_db()?;
         let job_id = *self.job.uuid();
+        let cache_key = self.job.cache_key().clone();
+        let job_arch = self.job.arch().clone();
+        // Needed to classify the artifacts after the job was moved to the log receiver
+        let job_package = self.job.package().clone();
+        let input_artifacts = self.input_artifacts();
         trace!(
             "Running on Job {} on Endpoint {}",
             job_id,
-            self.endpoint.name()
+            endpoint_handle.name()
         );
-        let prepared_container = self
-            .endpoint
+        let artifact_cache = endpoint_handle
+            .artifact_cache()
+            .as_ref()
+            .map(|volume| EndpointArtifactCache::new(self.db.clone(), &endpoint, volume.clone()));
+        let checkpoint = match self.checkpoint_store.as_ref() {
+            Some(store) => {
+                store
+                    .load(
+                        self.job.package(),
+                        job_arch.as_deref(),
+                        &checkpoint_key(&self.job),
+                    )
+                    .await?
+            }
+            None => None,
+        };
+        if let Some(checkpoint) = checkpoint.as_ref() {
+            debug!(
+                "Restoring checkpoint {} for job {}",
+                checkpoint.path().display(),
+                job_id
+            );
+        }
+        let prepared_container = endpoint_handle
             .prepare_container(
                 &self.job,
                 self.staging_store.clone(),
                 self.release_stores.clone(),
+                Arc::new(UploadProgress::new(
+                    self.bar.clone(),
+                    self.upload_stats.clone(),
+                )),
+                self.max_parallel_uploads,
+                artifact_cache.as_ref(),
+                &RecordedChecksums::new(self.db.clone(), &self.submit),
+                checkpoint.as_ref(),
             )
             .await?;
         let container_id = prepared_container.create_info().id.clone();
-        let running_container = prepared_container
-            .start()
-            .await
-            .with_context(|| {
-                Self::create_job_run_error(
-                    &job_id,
-                    &package.name,
-                    &package.version,
-                    &endpoint_uri,
-                    &container_id,
+        let sources_hash = prepared_container.sources_hash().clone();
+
+        // Recorded with the job, because image tags may be re-pushed (and endpoints upgraded)
+        let (image_digest, docker_version) = tokio::join!(
+            endpoint_handle.container_image_digest(&container_id, self.job.image()),
+            endpoint_handle.docker_version()
+        );
+        let image_digest = image_digest
+            .inspect_err(|e| warn!("Cannot record the image digest of job {}: {:?}", job_id, e))
+            .ok();
+        let docker_version = docker_version
+            .inspect_err(|e| {
+                warn!(
+                    "Cannot record the Docker version of job {}: {:?}",
+                    job_id, e
                 )
-            })?
-            .execute_script(log_sender);
+            })
+            .ok();
+
+        let started_at = chrono::offset::Local::now().naive_local();
+        let started_container = prepared_container.start().await.with_context(|| {
+            Self::create_job_run_error(
+                &job_id,
+                &package.name,
+                &package.version,
+                &endpoint_uri,
+                &container_id,
+            )
+        })?;
+        let probes = started_container
+            .probe_environment(&self.environment_probes)
+            .await;
+        let running_container =
+            started_container.execute_script(log_sender, self.log_limits.max_line_length());
+        self.status_lines.job(
+            &job_id,
+            &job_package,
+            "started",
+            Some(endpoint_name.as_ref()),
+        );
 
         let logres = LogReceiver {
+            endpoint: Some(&**endpoint_handle),
+            container_id: Some(&container_id),
+            checkpoint_store: self.checkpoint_store.as_deref(),
             endpoint_name: endpoint_name.as_ref(),
             max_endpoint_name_length: &self.max_endpoint_name_length,
             container_id_chrs: container_id.chars().take(7).collect(),
             package_name: &package.name,
             package_version: &package.version,
             log_dir: self.log_dir.as_ref(),
+            log_limits: &self.log_limits,
             job: self.job,
             log_receiver,
             bar: self.bar.clone(),
+            status_lines: &self.status_lines,
         }
         .join();
         drop(self.bar);
 
         let (run_container, logres) = tokio::join!(running_container, logres);
+        let finished_at = chrono::offset::Local::now().naive_local();
         let log =
             logres.with_context(|| anyhow!("Collecting logs for job on '{}'", endpoint_name))?;
         let run_container = run_container
@@ -224,33 +1064,49 @@ impl JobHandle {
                 )
             })?;
 
-        let job = dbmodels::Job::create(
-            &mut self.db.get().unwrap(),
-            &job_id,
-            &self.submit,
-            &endpoint,
-            &package,
-            &image,
-            &run_container.container_hash(),
-            run_container.script(),
-            &log,
-        )
-        .context("Recording job that is ready in database")?;
+        // The job and its environment are recorded together, so that a crash never leaves a job
+        // without its environment in the database
+        let job = self.db.get().unwrap().transaction::<_, Error, _>(|conn| {
+            let job = dbmodels::Job::create(
+                conn,
+                &job_id,
+                &self.submit,
+                &endpoint,
+                &package,
+                &image,
+                &run_container.container_hash(),
+                run_container.script(),
+                &log,
+                cache_key.as_ref(),
+                &started_at,
+                &finished_at,
+                image_digest.as_deref(),
+                docker_version.as_deref(),
+                &sources_hash,
+                job_arch.as_deref(),
+                input_artifacts,
+            )
+            .context("Recording job that is ready in database")?;
 
-        trace!("DB: Job entry for job {} created: {}", job.uuid, job.id);
-        for env in envs {
-            dbmodels::JobEnv::create(&mut self.db.get().unwrap(), &job, &env).with_context(
-                || {
+            trace!("DB: Job entry for job {} created: {}", job.uuid, job.id);
+            for env in envs {
+                dbmodels::JobEnv::create(conn, &job, &env).with_context(|| {
                     format!(
                         "Creating Environment Variable mapping for Job: {}",
                         job.uuid
                     )
-                },
-            )?;
-        }
+                })?;
+            }
+            for (name, output) in probes.iter() {
+                dbmodels::JobProbe::create(conn, &job, name, output).with_context(|| {
+                    anyhow!("Recording environment probe '{}' of job {}", name, job.uuid)
+                })?;
+            }
+            Ok(job)
+        })?;
 
         let res: crate::endpoint::FinalizedContainer = run_container
-            .finalize(self.staging_store.clone())
+            .finalize(self.staging_store.clone(), job_arch.as_deref())
             .await
             .context("Finalizing container")
             .with_context(|| {
@@ -277,8 +1133,226 @@ impl JobHandle {
                 )
             });
 
+        let result = Self::record_result(
+            &self.db,
+            &self.staging_store,
+            &self.failure_classifiers,
+            &self.post_build_hooks,
+            &job,
+            &job_package,
+            paths,
+            res,
+        )
+        .await;
+
+        // The checkpoint is not needed anymore once the package was built
+        if let (Ok(Ok(_)), Some(store)) = (&result, self.checkpoint_store.as_ref()) {
+            if let Err(e) = store.remove(&job_package, job_arch.as_deref()).await {
+                warn!("Cannot remove the checkpoint of job {}: {:?}", job.uuid, e);
+            }
+        }
+
+        // The containers of failed jobs are kept, so that the failure can be inspected
+        if matches!(result, Ok(Ok(_))) && self.container_retention.remove_successful() {
+            if let Err(e) = endpoint_handle.remove_container(&container_id).await {
+                warn!(
+                    "Cannot remove container {} of job {}: {:?}",
+                    container_id, job.uuid, e
+                );
+            }
+        }
+        result
+    }
+
+    /// Run the job on the host or in a Kubernetes pod
+    async fn run_without_docker(self) -> Result<Result<Vec<ArtifactPath>>> {
+        let (log_sender, log_receiver) = tokio::sync::mpsc::unbounded_channel::<LogItem>();
+        let endpoint_name = self.endpoint_name().clone();
+        let endpoint = dbmodels::Endpoint::create_or_fetch(&mut self.db.get()?, &endpoint_name)?;
+        let package = dbmodels::Package::create_or_fetch(&mut self.db.get()?, self.job.package())?;
+        let image = dbmodels::Image::create_or_fetch(&mut self.db.get()?, self.job.image())?;
+        let envs = self.create_env_in_db()?;
+        let job_id = *self.job.uuid();
+        let cache_key = self
+            .job
+            .cache_key()
+            .clone()
+            .filter(|_| self.caches_artifacts());
+        let job_arch = self.job.arch().clone();
+        let job_package = self.job.package().clone();
+        let input_artifacts = self.input_artifacts();
+        trace!("Running job {} on '{}'", job_id, endpoint_name);
+
+        let prepared_job = match &self.target {
+            JobTarget::Local(local, _) => local
+                .prepare(&self.job, self.staging_store.clone(), &self.release_stores)
+                .await
+                .map(PreparedJob::Local),
+            JobTarget::Kubernetes(kubernetes) => kubernetes
+                .prepare_pod(
+                    &self.job,
+                    self.attempt,
+                    self.staging_store.clone(),
+                    &self.release_stores,
+                )
+                .await
+                .map(PreparedJob::Kubernetes),
+            JobTarget::Endpoint(_) => Err(anyhow!("BUG: Job is scheduled on a Docker endpoint")),
+        }
+        .with_context(|| anyhow!("Preparing job {} on '{}'", job_id, endpoint_name))?;
+
+        let started_at = chrono::offset::Local::now().naive_local();
+        let execution = prepared_job.execute(log_sender, self.log_limits.max_line_length());
+        self.status_lines.job(
+            &job_id,
+            &job_package,
+            "started",
+            Some(endpoint_name.as_ref()),
+        );
+
+        let logres = LogReceiver {
+            endpoint: None,
+            container_id: None,
+            checkpoint_store: self.checkpoint_store.as_deref(),
+            endpoint_name: endpoint_name.as_ref(),
+            max_endpoint_name_length: &self.max_endpoint_name_length,
+            container_id_chrs: "-".repeat(7),
+            package_name: &package.name,
+            package_version: &package.version,
+            log_dir: self.log_dir.as_ref(),
+            log_limits: &self.log_limits,
+            job: self.job,
+            log_receiver,
+            bar: self.bar.clone(),
+            status_lines: &self.status_lines,
+        }
+        .join();
+        drop(self.bar);
+
+        let (exit_info, logres) = tokio::join!(execution, logres);
+        let finished_at = chrono::offset::Local::now().naive_local();
+        let log = logres.with_context(|| {
+            anyhow!("Collecting logs for job {} on '{}'", job_id, endpoint_name)
+        })?;
+        let exit_info =
+            exit_info.with_context(|| anyhow!("Running job {} on '{}'", job_id, endpoint_name))?;
+
+        // Local jobs are marked by the endpoint and the container hash, there is no image digest
+        // and Docker version for jobs that do not run on a Docker endpoint
+        let job = self.db.get()?.transaction::<_, Error, _>(|conn| {
+            let job = dbmodels::Job::create(
+                conn,
+                &job_id,
+                &self.submit,
+                &endpoint,
+                &package,
+                &image,
+                &prepared_job.container_hash(),
+                prepared_job.script(),
+                &log,
+                cache_key.as_ref(),
+                &started_at,
+                &finished_at,
+                None,
+                None,
+                prepared_job.sources_hash(),
+                job_arch.as_deref(),
+                input_artifacts,
+            )
+            .context("Recording job that is ready in database")?;
+
+            for env in envs {
+                dbmodels::JobEnv::create(conn, &job, &env).with_context(|| {
+                    format!(
+                        "Creating Environment Variable mapping for Job: {}",
+                        job.uuid
+                    )
+                })?;
+            }
+            Ok(job)
+        })?;
+
+        let (paths, res) = prepared_job
+            .finalize(exit_info, self.staging_store.clone(), job_arch.as_deref())
+            .await
+            .with_context(|| anyhow!("Finalizing job {} on '{}'", job_id, endpoint_name))?
+            .unpack();
+        let res = res.with_context(|| {
+            anyhow!(
+                "Error while running job {} for {} {} on '{}'",
+                job_id,
+                package.name,
+                package.version,
+                endpoint_name
+            )
+        });
+
+        Self::record_result(
+            &self.db,
+            &self.staging_store,
+            &self.failure_classifiers,
+            &self.post_build_hooks,
+            &job,
+            &job_package,
+            paths,
+            res,
+        )
+        .await
+    }
+
+    /// Classify the failure of a job or record the artifacts of a successful job
+    async fn record_result(
+        db: &Pool<ConnectionManager<PgConnection>>,
+        staging_store: &RwLock<StagingStore>,
+        failure_classifiers: &FailureClassifiers,
+        post_build_hooks: &[PostBuildHook],
+        job: &dbmodels::Job,
+        job_package: &Package,
+        paths: Vec<ArtifactPath>,
+        res: Result<()>,
+    ) -> Result<Result<Vec<ArtifactPath>>> {
+        // A job that does not produce the declared outputs fails
+        let res = res.and_then(|_| {
+            job_package
+                .check_outputs(paths.iter().map(AsRef::as_ref))
+                .with_context(|| anyhow!("Validating the artifacts of job {}", job.uuid))
+        });
+
+        // The post-build hooks can veto the artifacts of the job
+        let res = match res {
+            Ok(()) if !post_build_hooks.is_empty() => {
+                let staging_read = staging_store.read().await;
+                let artifacts = paths
+                    .iter()
+                    .map(|p| staging_read.root_path().as_ref().join(p))
+                    .collect::<Vec<_>>();
+                crate::util::hooks::run_post_build_hooks(
+                    post_build_hooks,
+                    job_package,
+                    &job.uuid,
+                    staging_read.root_path().as_ref(),
+                    &artifacts,
+                )
+                .await
+            }
+            res => res,
+        };
+
         if res.is_err() {
             trace!("Error was returned from script");
+            // Rejected artifacts must not be released or used by other jobs
+            if !paths.is_empty() {
+                if let Err(e) = staging_store.write().await.remove(&paths) {
+                    warn!(
+                        "Failed to remove the artifacts of job {} from the staging store: {:?}",
+                        job.uuid, e
+                    );
+                }
+            }
+            if let Some(class) = failure_classifiers.classify(&job.log_text) {
+                debug!("Failure of job {} classified as {}", job.uuid, class);
+                job.set_failure_class(&mut db.get()?, class)?;
+            }
             return Ok({
                 res.map(|_| vec![]) // to have the proper type, will never be executed
             });
@@ -286,10 +1360,21 @@ impl JobHandle {
 
         // Have to do it the ugly way here because of borrowing semantics
         let mut r = vec![];
-        let staging_read = self.staging_store.read().await;
+        let mut new_artifacts = vec![];
+        let staging_read = staging_store.read().await;
         for p in paths.iter() {
-            trace!("DB: Creating artifact entry for path: {}", p.display());
-            let _ = dbmodels::Artifact::create(&mut self.db.get().unwrap(), p, &job)?;
+            let (size, sha256) = match staging_read.root_path().join(p)? {
+                Some(full_path) => {
+                    let size = tokio::fs::metadata(full_path.joined())
+                        .await
+                        .with_context(|| anyhow!("Getting size of artifact {}", p.display()))?
+                        .len();
+                    (Some(size), Some(full_path.sha256().await?.to_string()))
+                }
+                None => (None, None),
+            };
+            let kind = job_package.artifact_kind(p.as_ref());
+            new_artifacts.push((p, size, kind, sha256));
             r.push({
                 staging_read
                     .get(p)
@@ -297,6 +1382,19 @@ impl JobHandle {
                     .clone()
             });
         }
+
+        // All artifacts of the job are recorded or none of them
+        db.get()
+            .unwrap()
+            .transaction::<_, Error, _>(|conn| {
+                for (p, size, kind, sha256) in new_artifacts {
+                    trace!("DB: Creating artifact entry for path: {}", p.display());
+                    let _ =
+                        dbmodels::Artifact::create(conn, p, job, size, kind, sha256.as_deref())?;
+                }
+                Ok(())
+            })
+            .with_context(|| anyhow!("Recording the artifacts of job {} in database", job.uuid))?;
         Ok(Ok(r))
     }
 
@@ -331,6 +1429,16 @@ impl JobHandle {
         ))
     }
 
+    /// The paths of the dependency artifacts of the job, recorded as the inputs of the job
+    fn input_artifacts(&self) -> Vec<String> {
+        self.job
+            .resources()
+            .iter()
+            .filter_map(JobResource::artifact)
+            .map(|path| path.display().to_string())
+            .collect()
+    }
+
     fn create_env_in_db(&self) -> Result<Vec<dbmodels::EnvVar>> {
         trace!("Creating environment in database");
         trace!("Hardcoded = {:?}", self.job.package().environment());
@@ -370,22 +1478,50 @@ impl JobHandle {
 }
 
 struct LogReceiver<'a> {
+    /// The endpoint the job runs on, `None` for jobs on the host
+    endpoint: Option<&'a Endpoint>,
+
+    /// The container the job runs in, `None` for jobs on the host
+    container_id: Option<&'a str>,
+
+    /// Where the checkpoints the job marks are stored, if checkpoints are enabled
+    checkpoint_store: Option<&'a CheckpointStore>,
     endpoint_name: &'a str,
     max_endpoint_name_length: &'a usize,
     container_id_chrs: String,
     package_name: &'a str,
     package_version: &'a str,
     log_dir: Option<&'a PathBuf>,
+    log_limits: &'a LogLimits,
     job: RunnableJob,
     log_receiver: UnboundedReceiver<LogItem>,
     bar: ProgressBar,
+    status_lines: &'a StatusLines,
 }
 
 impl LogReceiver<'_> {
     async fn join(mut self) -> Result<String> {
+        // The checkpoints are copied out of the container next to the log handling, so that
+        // copying a large checkpoint does not hold up the logs
+        let (checkpoint_sender, checkpoint_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let checkpoints = CheckpointSaver {
+            store: self.checkpoint_store,
+            endpoint: self.endpoint,
+            container_id: self.container_id,
+            job_uuid: *self.job.uuid(),
+            package: self.job.package().clone(),
+            arch: self.job.arch().clone(),
+            key: checkpoint_key(&self.job),
+        }
+        .run(checkpoint_receiver);
+
+        let (log, ()) = tokio::join!(self.receive(checkpoint_sender), checkpoints);
+        log
+    }
+
+    async fn receive(&mut self, checkpoints: UnboundedSender<String>) -> Result<String> {
         let mut success = None;
-        // Reserve a reasonable amount of elements.
-        let mut accu = Vec::with_capacity(4096);
+        let mut accu = LogBuffer::new(self.log_limits);
 
         let mut logfile = self
             .get_logfile()
@@ -402,6 +1538,9 @@ impl LogReceiver<'_> {
         // progress bar secondly.
         let timeout_duration = std::time::Duration::from_millis(250);
         let max_endpoint_name_length = self.max_endpoint_name_length;
+        let mut endpoint_degraded = false;
+        let mut current_phase = None;
+        let mut warnings = 0;
 
         loop {
             // Timeout for receiving from the log receiver channel
@@ -410,6 +1549,13 @@ impl LogReceiver<'_> {
             let logitem =
                 match tokio::time::timeout(timeout_duration, self.log_receiver.recv()).await {
                     Err(_ /* elapsed */) => {
+                        let healthy = self.endpoint.map(|ep| ep.is_healthy()).unwrap_or(true);
+                        if endpoint_degraded == healthy {
+                            endpoint_degraded = !endpoint_degraded;
+                            if endpoint_degraded {
+                                self.mark_endpoint_degraded();
+                            }
+                        }
                         self.bar.tick(); // just ping the progressbar here
                         continue;
                     }
@@ -431,19 +1577,57 @@ impl LogReceiver<'_> {
                 LogItem::Progress(u) => {
                     trace!("Setting bar to {}", u as u64);
                     self.bar.set_position(u as u64);
+                    self.status_lines.job(
+                        self.job.uuid(),
+                        self.job.package(),
+                        "progress",
+                        Some(&format!("{u}%")),
+                    );
                 }
                 LogItem::CurrentPhase(ref phasename) => {
                     trace!("Setting bar phase to {}", phasename);
-                    self.bar.set_message(format!(
-                        "{:<max_endpoint_name_length$} {} {} {} {} {} {}",
-                        self.endpoint_name,
-                        self.container_id_chrs,
+                    self.status_lines.job(
                         self.job.uuid(),
-                        "\u{2588}\u{2588}".yellow(),
-                        self.package_name,
-                        self.package_version,
-                        phasename
-                    ));
+                        self.job.package(),
+                        "phase",
+                        Some(phasename),
+                    );
+                    current_phase = Some(phasename.clone());
+                    self.bar
+                        .set_message(self.running_message(current_phase.as_deref(), warnings));
+                }
+                LogItem::Artifact(ref path) => {
+                    trace!("Job reported artifact {}", path);
+                    self.status_lines.job(
+                        self.job.uuid(),
+                        self.job.package(),
+                        "artifact",
+                        Some(path),
+                    );
+                }
+                LogItem::Checkpoint(ref path) => {
+                    trace!("Job marked checkpoint {}", path);
+                    self.status_lines.job(
+                        self.job.uuid(),
+                        self.job.package(),
+                        "checkpoint",
+                        Some(path),
+                    );
+                    checkpoints
+                        .send(path.clone())
+                        .context("Passing on the checkpoint")?;
+                }
+                LogItem::Warnings(n) => {
+                    warnings += n;
+                    trace!("Job reported {} warnings, {} in total", n, warnings);
+                    self.status_lines.job(
+                        self.job.uuid(),
+                        self.job.package(),
+                        "warnings",
+                        Some(&warnings.to_string()),
+                    );
+                    self.bar
+                        .set_message(self.running_message(current_phase.as_deref(), warnings));
                 }
                 LogItem::State(Ok(())) => {
                     trace!("Setting bar state to Ok");
@@ -498,12 +1682,41 @@ impl LogReceiver<'_> {
             lf.flush().await?;
         }
 
-        Ok({
-            accu.iter()
-                .map(crate::log::LogItem::raw)
-                .collect::<Result<Vec<String>>>()?
-                .join("\n")
-        })
+        accu.into_text()
+    }
+
+    /// The message of the progress bar while the job is running
+    fn running_message(&self, phase: Option<&str>, warnings: usize) -> String {
+        let max_endpoint_name_length = *self.max_endpoint_name_length;
+        let mut message = format!(
+            "{:<max_endpoint_name_length$} {} {} {} {} {} {}",
+            self.endpoint_name,
+            self.container_id_chrs,
+            self.job.uuid(),
+            "\u{2588}\u{2588}".yellow(),
+            self.package_name,
+            self.package_version,
+            phase.unwrap_or_default()
+        );
+        if warnings > 0 {
+            message.push_str(&format!(" {}", format!("({warnings} warnings)").yellow()));
+        }
+        message
+    }
+
+    /// Show in the progress bar (and status lines) that the endpoint of the job is not reachable
+    fn mark_endpoint_degraded(&self) {
+        self.status_lines.job(
+            self.job.uuid(),
+            self.job.package(),
+            "degraded",
+            Some(self.endpoint_name),
+        );
+        self.bar.set_message(format!(
+            "{} {}",
+            self.bar.message(),
+            format!("(endpoint {} degraded)", self.endpoint_name).red()
+        ));
     }
 
     async fn get_logfile(&self) -> Option<Result<tokio::io::BufWriter<tokio::fs::File>>> {
@@ -529,3 +1742,169 @@ impl LogReceiver<'_> {
         }
     }
 }
+
+/// Copies the checkpoints a job marks out of its container
+struct CheckpointSaver<'a> {
+    /// Where the checkpoints are stored, `None` if checkpoints are disabled
+    store: Option<&'a CheckpointStore>,
+
+    /// The endpoint the job runs on, `None` for jobs on the host
+    endpoint: Option<&'a Endpoint>,
+
+    /// The container the job runs in, `None` for jobs on the host
+    container_id: Option<&'a str>,
+    job_uuid: Uuid,
+    package: Package,
+    arch: Option<String>,
+
+    /// The inputs of the job (see `checkpoint_key()`)
+    key: String,
+}
+
+impl CheckpointSaver<'_> {
+    /// Save the checkpoints received from `paths` until the sender is dropped
+    ///
+    /// If the job marked several checkpoints while one was copied, only the last one is saved.
+    async fn run(self, mut paths: UnboundedReceiver<String>) {
+        while let Some(mut path) = paths.recv().await {
+            while let Ok(newer) = paths.try_recv() {
+                path = newer;
+            }
+            self.save(&path).await;
+        }
+    }
+
+    /// Copy the checkpoint the job marked out of its container
+    ///
+    /// A checkpoint that cannot be saved does not fail the job.
+    async fn save(&self, path: &str) {
+        let Some(store) = self.store else {
+            trace!("Checkpoints are disabled, ignoring checkpoint {}", path);
+            return;
+        };
+        let (Some(endpoint), Some(container_id)) = (self.endpoint, self.container_id) else {
+            warn!(
+                "Job {} marked checkpoint {}, but checkpoints are only supported on Docker endpoints",
+                self.job_uuid,
+                path
+            );
+            return;
+        };
+
+        if let Err(e) = endpoint
+            .save_checkpoint(
+                container_id,
+                Path::new(path),
+                &self.package,
+                self.arch.as_deref(),
+                &self.key,
+                store,
+            )
+            .await
+        {
+            warn!("Cannot save checkpoint of job {}: {:?}", self.job_uuid, e);
+        }
+    }
+}
+
+/// The key of the inputs of a job, a checkpoint is only restored for a job with the same inputs
+///
+/// This is the cache key of the job if it was computed, otherwise a hash of the image, the script
+/// and the sources of the job.
+fn checkpoint_key(job: &RunnableJob) -> String {
+    use sha2::Digest;
+
+    if let Some(cache_key) = job.cache_key() {
+        return cache_key.to_string();
+    }
+
+    let sources = job
+        .package()
+        .sources()
+        .iter()
+        .sorted_by(|a, b| a.0.cmp(b.0))
+        .map(|(name, source)| format!("{}:{}", name, source.hash().value()))
+        .collect::<Vec<_>>();
+    let mut hasher = sha2::Sha256::new();
+    for value in [job.image().as_ref(), job.script().as_ref()]
+        .into_iter()
+        .chain(sources.iter().map(String::as_str))
+    {
+        hasher.update((value.len() as u64).to_le_bytes());
+        hasher.update(value.as_bytes());
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classes() -> Vec<String> {
+        vec![
+            String::from(crate::log::DOCKER_FAILURE_CLASS),
+            String::from("network"),
+        ]
+    }
+
+    #[test]
+    fn test_retry_limit_is_enforced() {
+        let failed: Result<Result<Vec<ArtifactPath>>> = Ok(Err(anyhow!("build failed")));
+        let recorded = Some(Some("network"));
+
+        assert_eq!(
+            retry_class(&failed, recorded, 0, 2, &classes()),
+            Some(String::from("network"))
+        );
+        assert_eq!(
+            retry_class(&failed, recorded, 1, 2, &classes()),
+            Some(String::from("network"))
+        );
+        assert_eq!(retry_class(&failed, recorded, 2, 2, &classes()), None);
+        assert_eq!(retry_class(&failed, recorded, 0, 0, &classes()), None);
+    }
+
+    #[test]
+    fn test_retry_only_for_configured_failure_classes() {
+        let failed: Result<Result<Vec<ArtifactPath>>> = Ok(Err(anyhow!("build failed")));
+        assert_eq!(
+            retry_class(&failed, Some(Some("compiler")), 0, 2, &classes()),
+            None
+        );
+        assert_eq!(retry_class(&failed, Some(None), 0, 2, &classes()), None);
+        assert_eq!(retry_class(&failed, Some(Some("network")), 0, 2, &[]), None);
+
+        let succeeded: Result<Result<Vec<ArtifactPath>>> = Ok(Ok(Vec::new()));
+        assert_eq!(retry_class(&succeeded, Some(None), 0, 2, &classes()), None);
+
+        // The container did not finish, so the job was not recorded
+        let errored: Result<Result<Vec<ArtifactPath>>> = Err(anyhow!("container vanished"));
+        assert_eq!(
+            retry_class(&errored, None, 0, 2, &classes()),
+            Some(String::from(crate::log::DOCKER_FAILURE_CLASS))
+        );
+    }
+
+    #[test]
+    fn test_failed_endpoint_is_excluded() {
+        let a = EndpointName::from(String::from("a"));
+        let b = EndpointName::from(String::from("b"));
+        let endpoints = [a.clone(), b.clone()];
+
+        let excluded = vec![a.clone()];
+        let all = all_excluded(endpoints.iter(), &excluded);
+        assert!(!all);
+        assert!(!is_not_excluded(&a, &excluded, all));
+        assert!(is_not_excluded(&b, &excluded, all));
+
+        // If the job failed on all endpoints, it may run on any of them again
+        let excluded = vec![a.clone(), b.clone()];
+        let all = all_excluded(endpoints.iter(), &excluded);
+        assert!(all);
+        assert!(is_not_excluded(&a, &excluded, all));
+        assert!(is_not_excluded(&b, &excluded, all));
+
+        // Only the endpoints that can run the job count
+        assert!(all_excluded([&a].into_iter(), &[a.clone()]));
+    }
+}
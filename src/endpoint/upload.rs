@@ -0,0 +1,444 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Streaming artifacts into containers, with progress
+//!
+//! Endpoints can cache the artifacts in a volume (see `artifact_cache` in the endpoint
+//! configuration), then artifacts are only uploaded if they are not cached on the endpoint yet.
+//! The cached artifacts are recorded in the database (see `EndpointArtifact`).
+
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use diesel::r2d2::ConnectionManager;
+use diesel::r2d2::Pool;
+use diesel::PgConnection;
+use futures::Stream;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use indicatif::ProgressBar;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+use tracing::trace;
+
+use crate::db::models::Artifact;
+use crate::db::models::EndpointArtifact;
+use crate::filestore::ArtifactPath;
+use crate::package::HashType;
+
+/// The size of the chunks in which files are read while they are uploaded
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// The size of a block in a TAR archive
+const TAR_BLOCK_SIZE: u64 = 512;
+
+/// The transfer statistics of the artifact uploads of all jobs of a build
+#[derive(Debug, Default)]
+pub struct UploadStats {
+    bytes: AtomicU64,
+
+    /// The time spent uploading, summed up over all jobs
+    micros: AtomicU64,
+}
+
+impl UploadStats {
+    fn record(&self, bytes: u64, duration: Duration) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// A summary of the uploads with the average transfer rate, if anything was uploaded
+    pub fn summary(&self) -> Option<String> {
+        let bytes = self.bytes.load(Ordering::Relaxed);
+        if bytes == 0 {
+            return None;
+        }
+
+        let duration = Duration::from_micros(self.micros.load(Ordering::Relaxed));
+        Some(format!(
+            "Uploaded {} of artifacts into containers in {} ({}/s per job)",
+            bytesize::ByteSize::b(bytes),
+            humantime::format_duration(Duration::from_secs(duration.as_secs())),
+            bytesize::ByteSize::b(rate(bytes, duration))
+        ))
+    }
+}
+
+/// Shows the progress of the artifact uploads of a job in the progress bar of the job
+pub struct UploadProgress {
+    bar: ProgressBar,
+    message: String,
+    stats: Arc<UploadStats>,
+    total: AtomicU64,
+    uploaded: AtomicU64,
+    started: Instant,
+}
+
+impl UploadProgress {
+    /// The progress is appended to the current message of the bar
+    pub fn new(bar: ProgressBar, stats: Arc<UploadStats>) -> Self {
+        UploadProgress {
+            message: bar.message(),
+            bar,
+            stats,
+            total: AtomicU64::new(0),
+            uploaded: AtomicU64::new(0),
+            started: Instant::now(),
+        }
+    }
+
+    fn add_total(&self, bytes: u64) {
+        self.total.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn advance(&self, bytes: u64) {
+        let uploaded = self.uploaded.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.bar.set_message(format!(
+            "{} Uploading {}/{} ({}/s)",
+            self.message,
+            bytesize::ByteSize::b(uploaded),
+            bytesize::ByteSize::b(self.total.load(Ordering::Relaxed)),
+            bytesize::ByteSize::b(rate(uploaded, self.started.elapsed()))
+        ));
+    }
+
+    /// Record the uploads of the job in the statistics of the build and restore the message
+    pub fn finish(&self) {
+        self.stats.record(
+            self.uploaded.load(Ordering::Relaxed),
+            self.started.elapsed(),
+        );
+        self.bar.set_message(self.message.clone());
+    }
+}
+
+/// The artifact cache of an endpoint
+pub struct EndpointArtifactCache {
+    db: Pool<ConnectionManager<PgConnection>>,
+    endpoint_id: i32,
+    volume: String,
+}
+
+impl EndpointArtifactCache {
+    pub fn new(
+        db: Pool<ConnectionManager<PgConnection>>,
+        endpoint: &crate::db::models::Endpoint,
+        volume: String,
+    ) -> Self {
+        EndpointArtifactCache {
+            db,
+            endpoint_id: endpoint.id,
+            volume,
+        }
+    }
+
+    /// The file name of the artifact with the passed hash in the cache, if it is cached
+    pub(super) fn cached_file_name(&self, sha256: &str) -> Result<Option<String>> {
+        EndpointArtifact::find(&mut self.db.get()?, self.endpoint_id, &self.volume, sha256)
+            .map(|entry| entry.map(|entry| entry.file_name))
+    }
+
+    /// The volume that holds the cached artifacts
+    pub(super) fn volume(&self) -> &str {
+        &self.volume
+    }
+
+    /// Remove the entry of an artifact that is not in the cache (anymore)
+    pub(super) fn forget(&self, sha256: &str, file_name: &str) -> Result<()> {
+        EndpointArtifact::remove(
+            &mut self.db.get()?,
+            self.endpoint_id,
+            &self.volume,
+            sha256,
+            file_name,
+        )
+    }
+
+    /// Record that the artifact with the passed hash was uploaded to the cache as `file_name`
+    pub(super) fn record(&self, sha256: &str, file_name: &str) -> Result<()> {
+        EndpointArtifact::create(
+            &mut self.db.get()?,
+            self.endpoint_id,
+            &self.volume,
+            sha256,
+            file_name,
+        )
+    }
+}
+
+/// The checksums that were recorded for the artifacts when they were built
+///
+/// The artifacts of the dependencies of a job are verified against them before they are copied
+/// into the container, so that a corrupted file in a store fails the job instead of producing a
+/// broken build.
+pub struct RecordedChecksums {
+    db: Pool<ConnectionManager<PgConnection>>,
+    submit_id: i32,
+}
+
+impl RecordedChecksums {
+    pub fn new(
+        db: Pool<ConnectionManager<PgConnection>>,
+        submit: &crate::db::models::Submit,
+    ) -> Self {
+        RecordedChecksums {
+            db,
+            submit_id: submit.id,
+        }
+    }
+
+    /// Verify the artifact at `path` against the recorded checksum, if there is one
+    ///
+    /// `release_store_name` is the release store the artifact was found in (`None` for the
+    /// staging store). Returns the SHA-256 hash of the file if it was computed.
+    pub(super) async fn verify(
+        &self,
+        art: &ArtifactPath,
+        path: &Path,
+        release_store_name: Option<&str>,
+    ) -> Result<Option<String>> {
+        let art_path = art
+            .to_str()
+            .ok_or_else(|| anyhow!("Path is not valid UTF-8: {}", art.display()))?;
+        let Some(expected) = Artifact::recorded_sha256(
+            &mut self.db.get()?,
+            art_path,
+            self.submit_id,
+            release_store_name,
+        )?
+        else {
+            trace!("No checksum recorded for {}", art.display());
+            return Ok(None);
+        };
+
+        let actual = file_sha256(path).await?;
+        if actual != expected {
+            return Err(anyhow!(
+                "Store corruption: {} has the SHA-256 hash {}, but {} was recorded when it was built",
+                path.display(),
+                actual,
+                expected
+            ));
+        }
+        Ok(Some(actual))
+    }
+}
+
+/// The SHA-256 hash of a file, the key of the file in the artifact cache of an endpoint
+pub(super) async fn file_sha256(path: &Path) -> Result<String> {
+    let file = tokio::fs::File::open(path)
+        .await
+        .with_context(|| anyhow!("Opening {}", path.display()))?;
+    HashType::Sha256
+        .hash_from_reader(tokio::io::BufReader::new(file))
+        .await
+        .with_context(|| anyhow!("Hashing {}", path.display()))
+        .map(|hash| hash.to_string())
+}
+
+/// A TAR archive that contains a symlink at `destination` that points to `target`
+///
+/// The inputs of a job are symlinks to the cached artifacts if the endpoint has an artifact cache.
+pub(super) fn symlink_archive(destination: &Path, target: &Path) -> Result<Vec<u8>> {
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Symlink);
+    header.set_size(0);
+    header.set_mode(0o777);
+
+    let mut builder = tar::Builder::new(Vec::new());
+    builder
+        .append_link(
+            &mut header,
+            destination.strip_prefix("/").unwrap_or(destination),
+            target,
+        )
+        .with_context(|| anyhow!("Adding symlink {} to the archive", destination.display()))?;
+    builder.into_inner().context("Finishing the archive")
+}
+
+/// A TAR archive that contains the file at `path` as `destination`, which is read while the
+/// archive is streamed
+///
+/// The archive can be extracted in the root directory of a container, like the archive that
+/// `shiplift::Container::copy_file_into()` creates.
+pub(super) async fn file_archive_stream(
+    path: &Path,
+    destination: &Path,
+    progress: Arc<UploadProgress>,
+) -> Result<impl Stream<Item = std::io::Result<Vec<u8>>> + Send + 'static> {
+    let file = tokio::fs::File::open(path)
+        .await
+        .with_context(|| anyhow!("Opening {}", path.display()))?;
+    let size = file
+        .metadata()
+        .await
+        .with_context(|| anyhow!("Getting the size of {}", path.display()))?
+        .len();
+    progress.add_total(size);
+
+    let mut header = tar::Header::new_gnu();
+    header
+        .set_path(destination.strip_prefix("/").unwrap_or(destination))
+        .with_context(|| anyhow!("Setting the path {} in the archive", destination.display()))?;
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_cksum();
+    let header = header.as_bytes().to_vec();
+
+    let content =
+        chunk_stream(file.take(size)).inspect_ok(move |buf| progress.advance(buf.len() as u64));
+
+    // The content is padded to full blocks and the archive ends with two empty blocks
+    let padding = (TAR_BLOCK_SIZE - size % TAR_BLOCK_SIZE) % TAR_BLOCK_SIZE;
+    let trailer = vec![0; (padding + 2 * TAR_BLOCK_SIZE) as usize];
+
+    Ok(
+        futures::stream::once(futures::future::ready(std::io::Result::Ok(header)))
+            .chain(content)
+            .chain(futures::stream::once(futures::future::ready(Ok(trailer)))),
+    )
+}
+
+/// The content of `reader`, read in chunks while the stream is consumed
+pub(super) fn chunk_stream<R>(reader: R) -> impl Stream<Item = std::io::Result<Vec<u8>>> + Send
+where
+    R: AsyncRead + Unpin + Send,
+{
+    futures::stream::try_unfold(reader, |mut reader| async move {
+        let mut buf = vec![0; CHUNK_SIZE];
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            Ok::<_, std::io::Error>(None)
+        } else {
+            buf.truncate(n);
+            Ok(Some((buf, reader)))
+        }
+    })
+}
+
+/// A TAR archive that contains the directory `path` and all its parent directories
+///
+/// Docker can only extract archives into existing directories, so this archive is extracted in
+/// the root directory of a container to create a directory first.
+pub(super) fn directory_archive(path: &Path) -> Result<Vec<u8>> {
+    let mut builder = tar::Builder::new(Vec::new());
+    let mut dir = PathBuf::new();
+    for component in path.strip_prefix("/").unwrap_or(path).components() {
+        dir.push(component);
+
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_size(0);
+        header.set_mode(0o755);
+        builder
+            .append_data(&mut header, &dir, std::io::empty())
+            .with_context(|| anyhow!("Adding directory {} to the archive", dir.display()))?;
+    }
+    builder.into_inner().context("Finishing the archive")
+}
+
+/// Bytes per second
+fn rate(bytes: u64, duration: Duration) -> u64 {
+    if duration.is_zero() {
+        0
+    } else {
+        (bytes as f64 / duration.as_secs_f64()) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_file_archive_stream() {
+        let dir = std::env::temp_dir().join(format!("butido-test-upload-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("foo-1.0.tar.gz");
+        let content = vec![42u8; 1000];
+        std::fs::write(&path, &content).unwrap();
+
+        let progress = Arc::new(UploadProgress::new(
+            ProgressBar::hidden(),
+            Arc::new(UploadStats::default()),
+        ));
+        let archive =
+            file_archive_stream(&path, Path::new("/inputs/foo-1.0.tar.gz"), progress.clone())
+                .await
+                .unwrap()
+                .try_concat()
+                .await
+                .unwrap();
+        assert_eq!(archive.len() as u64 % TAR_BLOCK_SIZE, 0);
+        assert_eq!(progress.uploaded.load(Ordering::Relaxed), 1000);
+
+        let mut archive = tar::Archive::new(archive.as_slice());
+        let mut entries = archive.entries().unwrap();
+        let mut entry = entries.next().unwrap().unwrap();
+        assert_eq!(entry.path().unwrap(), Path::new("inputs/foo-1.0.tar.gz"));
+        let mut buf = Vec::new();
+        std::io::Read::read_to_end(&mut entry, &mut buf).unwrap();
+        assert_eq!(buf, content);
+        drop(entry);
+        assert!(entries.next().is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_directory_archive() {
+        let archive = directory_archive(Path::new("/build/foo")).unwrap();
+
+        let mut archive = tar::Archive::new(archive.as_slice());
+        let entries = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                assert_eq!(entry.header().entry_type(), tar::EntryType::Directory);
+                entry.path().unwrap().into_owned()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![PathBuf::from("build"), PathBuf::from("build/foo")]
+        );
+    }
+
+    #[test]
+    fn test_symlink_archive() {
+        let archive = symlink_archive(
+            Path::new("/inputs/foo-1.0.tar.gz"),
+            Path::new("/var/cache/butido-artifacts/0123abcd"),
+        )
+        .unwrap();
+
+        let mut archive = tar::Archive::new(archive.as_slice());
+        let mut entries = archive.entries().unwrap();
+        let entry = entries.next().unwrap().unwrap();
+        assert_eq!(entry.header().entry_type(), tar::EntryType::Symlink);
+        assert_eq!(entry.path().unwrap(), Path::new("inputs/foo-1.0.tar.gz"));
+        assert_eq!(
+            entry.link_name().unwrap().unwrap(),
+            Path::new("/var/cache/butido-artifacts/0123abcd")
+        );
+        drop(entry);
+        assert!(entries.next().is_none());
+    }
+}
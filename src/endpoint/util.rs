@@ -10,12 +10,19 @@
 
 use std::sync::Arc;
 
+use anyhow::anyhow;
+use anyhow::Context;
 use anyhow::Result;
 use futures::FutureExt;
+use futures::TryStreamExt;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncRead;
+use tokio::sync::mpsc::UnboundedSender;
 use tokio_stream::StreamExt;
 
 use crate::endpoint::Endpoint;
 use crate::endpoint::EndpointConfiguration;
+use crate::log::LogItem;
 
 pub async fn setup_endpoints(endpoints: Vec<EndpointConfiguration>) -> Result<Vec<Arc<Endpoint>>> {
     let unordered = futures::stream::FuturesUnordered::new();
@@ -26,3 +33,69 @@ pub async fn setup_endpoints(endpoints: Vec<EndpointConfiguration>) -> Result<Ve
 
     unordered.collect().await
 }
+
+/// Forward the output of a script that runs outside of a Docker container to the log sink
+///
+/// The lines of `stdout` and `stderr` are parsed like the output of a container. Returns whether
+/// the script reported success or failure, like `StartedContainer::execute_script()`.
+pub(super) async fn forward_script_output<O, E>(
+    stdout: O,
+    stderr: E,
+    logsink: &UnboundedSender<LogItem>,
+    max_line_length: usize,
+) -> Result<Option<(bool, Option<String>)>>
+where
+    O: AsyncRead + Unpin,
+    E: AsyncRead + Unpin,
+{
+    let exit_info = futures::stream::select(lines(stdout), lines(stderr))
+        .map(|line| {
+            line.context("Reading the output of the script")
+                .map(|l| crate::log::truncate_line(l, max_line_length))
+                .and_then(|l| {
+                    crate::log::parse_log_line(&crate::log::parser(), l.as_bytes())
+                        .with_context(|| anyhow!("Parsing log line: {:?}", l))
+                })
+                .and_then(|item| {
+                    let exited_successfully = match item {
+                        LogItem::State(Ok(_)) => Some((true, None)),
+                        LogItem::State(Err(ref msg)) => Some((false, Some(msg.clone()))),
+                        _ => None,
+                    };
+
+                    logsink
+                        .send(item)
+                        .with_context(|| anyhow!("Sending log to log sink"))
+                        .map(|_| exited_successfully)
+                })
+        })
+        .try_collect::<Vec<_>>()
+        .await?
+        .into_iter()
+        .fold(None, |accu, elem| match (accu, elem) {
+            (None, b) => b,
+            (Some((false, msg)), _) => Some((false, msg)),
+            (_, Some((false, msg))) => Some((false, msg)),
+            (a, None) => a,
+            (Some((true, _)), Some((true, _))) => Some((true, None)),
+        });
+    Ok(exit_info)
+}
+
+/// The lines of the output of a process
+fn lines<R>(reader: R) -> impl futures::Stream<Item = std::io::Result<String>>
+where
+    R: AsyncRead + Unpin,
+{
+    // Split at newlines instead of using `lines()`, which fails on invalid UTF-8
+    futures::stream::unfold(
+        tokio::io::BufReader::new(reader).split(b'\n'),
+        |mut lines| async move {
+            lines
+                .next_segment()
+                .await
+                .transpose()
+                .map(|line| (line.map(crate::log::decode_log_line), lines))
+        },
+    )
+}
@@ -0,0 +1,284 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! The checkpoints of jobs, to resume long builds
+//!
+//! A job marks a directory in its container as checkpoint with "#BUTIDO:CHECKPOINT:<path>". The
+//! directory is copied (as TAR archive) into the checkpoint store, there is one checkpoint per
+//! package, version and architecture (the last one wins). When the package is built again, the
+//! checkpoint is restored at the same path in the new container, if the job has the same inputs
+//! (see `key`) as the job that stored it. The checkpoint is removed when a job of the package
+//! succeeded.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use futures::Stream;
+use futures::StreamExt;
+use getset::Getters;
+use tokio::io::AsyncWriteExt;
+use tracing::trace;
+
+use crate::package::Package;
+
+/// The name of the archive of a checkpoint in the directory of the package
+const ARCHIVE_FILE_NAME: &str = "checkpoint.tar";
+
+/// The name of the file that holds the path of the checkpoint in the container
+const PATH_FILE_NAME: &str = "path";
+
+/// The name of the file that holds the key of the inputs of the job that stored the checkpoint
+const KEY_FILE_NAME: &str = "key";
+
+#[derive(Debug)]
+pub struct CheckpointStore {
+    root: PathBuf,
+}
+
+/// A stored checkpoint of a package
+#[derive(Debug, Getters)]
+pub struct Checkpoint {
+    /// The TAR archive of the directory, which contains the directory itself (i.e. its name)
+    #[getset(get = "pub")]
+    archive: PathBuf,
+
+    /// The path of the directory in the container
+    #[getset(get = "pub")]
+    path: PathBuf,
+}
+
+impl CheckpointStore {
+    pub fn new(root: PathBuf) -> Result<Self> {
+        std::fs::create_dir_all(&root)
+            .with_context(|| anyhow!("Creating checkpoint directory {}", root.display()))?;
+        Ok(CheckpointStore { root })
+    }
+
+    fn package_dir(&self, package: &Package, arch: Option<&str>) -> PathBuf {
+        match arch {
+            Some(arch) => {
+                self.root
+                    .join(format!("{}-{}-{}", package.name(), package.version(), arch))
+            }
+            None => self
+                .root
+                .join(format!("{}-{}", package.name(), package.version())),
+        }
+    }
+
+    /// Store the archive of the directory at `path` in the container as checkpoint of the package
+    /// (built for the architecture `arch`) by a job with the inputs `key`
+    ///
+    /// The path must be absolute. The archive is written to a temporary file first, so that an
+    /// existing checkpoint is only replaced by a complete one.
+    pub async fn save<S>(
+        &self,
+        package: &Package,
+        arch: Option<&str>,
+        key: &str,
+        path: &Path,
+        archive: S,
+    ) -> Result<()>
+    where
+        S: Stream<Item = Result<Vec<u8>>>,
+    {
+        let is_plain_absolute = path.is_absolute()
+            && !path
+                .components()
+                .any(|c| matches!(c, std::path::Component::ParentDir));
+        if !is_plain_absolute {
+            return Err(anyhow!(
+                "Checkpoint path {} is not an absolute path",
+                path.display()
+            ));
+        }
+
+        let dir = self.package_dir(package, arch);
+        tokio::fs::create_dir_all(&dir)
+            .await
+            .with_context(|| anyhow!("Creating {}", dir.display()))?;
+
+        let tmp_archive = dir.join(format!("{ARCHIVE_FILE_NAME}.tmp"));
+        let written = async {
+            let mut file = tokio::fs::File::create(&tmp_archive).await?;
+            let mut archive = Box::pin(archive);
+            while let Some(chunk) = archive.next().await {
+                file.write_all(&chunk?).await?;
+            }
+            file.flush().await?;
+            Ok::<_, anyhow::Error>(())
+        }
+        .await;
+        if let Err(e) = written {
+            let _ = tokio::fs::remove_file(&tmp_archive).await;
+            return Err(e).with_context(|| anyhow!("Writing {}", tmp_archive.display()));
+        }
+
+        tokio::fs::write(dir.join(PATH_FILE_NAME), path.to_string_lossy().as_bytes())
+            .await
+            .with_context(|| anyhow!("Writing the checkpoint path to {}", dir.display()))?;
+        tokio::fs::write(dir.join(KEY_FILE_NAME), key.as_bytes())
+            .await
+            .with_context(|| anyhow!("Writing the checkpoint key to {}", dir.display()))?;
+        tokio::fs::rename(&tmp_archive, dir.join(ARCHIVE_FILE_NAME))
+            .await
+            .with_context(|| anyhow!("Moving {} into place", tmp_archive.display()))?;
+        trace!(
+            "Stored checkpoint {} of {} {}",
+            path.display(),
+            package.name(),
+            package.version()
+        );
+        Ok(())
+    }
+
+    /// The checkpoint of the package, if there is one that was stored by a job with the inputs
+    /// `key`
+    ///
+    /// A checkpoint of a job with other inputs (e.g. an older script or another image) is removed.
+    pub async fn load(
+        &self,
+        package: &Package,
+        arch: Option<&str>,
+        key: &str,
+    ) -> Result<Option<Checkpoint>> {
+        let dir = self.package_dir(package, arch);
+        let archive = dir.join(ARCHIVE_FILE_NAME);
+        if !tokio::fs::try_exists(&archive).await? {
+            return Ok(None);
+        }
+
+        // Checkpoints without a key file were stored before the key was recorded
+        let stored_key = tokio::fs::read_to_string(dir.join(KEY_FILE_NAME))
+            .await
+            .ok();
+        if stored_key.as_deref() != Some(key) {
+            trace!(
+                "Discarding checkpoint of {} {} stored by a job with other inputs",
+                package.name(),
+                package.version()
+            );
+            self.remove(package, arch).await?;
+            return Ok(None);
+        }
+
+        let path = tokio::fs::read_to_string(dir.join(PATH_FILE_NAME))
+            .await
+            .with_context(|| anyhow!("Reading the checkpoint path from {}", dir.display()))?;
+        Ok(Some(Checkpoint {
+            archive,
+            path: PathBuf::from(path),
+        }))
+    }
+
+    /// Remove the checkpoint of the package, if there is one
+    pub async fn remove(&self, package: &Package, arch: Option<&str>) -> Result<()> {
+        let dir = self.package_dir(package, arch);
+        if tokio::fs::try_exists(&dir).await? {
+            tokio::fs::remove_dir_all(&dir)
+                .await
+                .with_context(|| anyhow!("Removing checkpoint {}", dir.display()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::package::tests::package;
+
+    #[tokio::test]
+    async fn test_save_load_remove() {
+        let dir =
+            std::env::temp_dir().join(format!("butido-test-checkpoint-{}", uuid::Uuid::new_v4()));
+        let store = CheckpointStore::new(dir.clone()).unwrap();
+        let pkg = package("foo", "1.0", "https://rust-lang.org", "123");
+        let other = package("bar", "1.0", "https://rust-lang.org", "123");
+
+        assert!(store.load(&pkg, None, "key").await.unwrap().is_none());
+
+        let chunks = vec![Ok(b"first ".to_vec()), Ok(b"checkpoint".to_vec())];
+        store
+            .save(
+                &pkg,
+                None,
+                "key",
+                Path::new("/build/foo"),
+                futures::stream::iter(chunks),
+            )
+            .await
+            .unwrap();
+        let checkpoint = store.load(&pkg, None, "key").await.unwrap().unwrap();
+        assert_eq!(checkpoint.path(), Path::new("/build/foo"));
+        assert_eq!(
+            std::fs::read(checkpoint.archive()).unwrap(),
+            b"first checkpoint"
+        );
+        assert!(store.load(&other, None, "key").await.unwrap().is_none());
+        assert!(store
+            .load(&pkg, Some("aarch64"), "key")
+            .await
+            .unwrap()
+            .is_none());
+
+        // A failing stream keeps the previous checkpoint
+        let chunks = vec![Ok(b"second".to_vec()), Err(anyhow!("connection lost"))];
+        assert!(store
+            .save(
+                &pkg,
+                None,
+                "key",
+                Path::new("/build/foo"),
+                futures::stream::iter(chunks)
+            )
+            .await
+            .is_err());
+        let checkpoint = store.load(&pkg, None, "key").await.unwrap().unwrap();
+        assert_eq!(
+            std::fs::read(checkpoint.archive()).unwrap(),
+            b"first checkpoint"
+        );
+
+        store.remove(&pkg, None).await.unwrap();
+        assert!(store.load(&pkg, None, "key").await.unwrap().is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_of_other_inputs_is_discarded() {
+        let dir =
+            std::env::temp_dir().join(format!("butido-test-checkpoint-{}", uuid::Uuid::new_v4()));
+        let store = CheckpointStore::new(dir.clone()).unwrap();
+        let pkg = package("foo", "1.0", "https://rust-lang.org", "123");
+        let archive = || futures::stream::iter(vec![Ok(b"checkpoint".to_vec())]);
+
+        store
+            .save(&pkg, None, "old", Path::new("/build/foo"), archive())
+            .await
+            .unwrap();
+        assert!(store.load(&pkg, None, "new").await.unwrap().is_none());
+        // The stale checkpoint is gone, not only ignored
+        assert!(store.load(&pkg, None, "old").await.unwrap().is_none());
+
+        for path in ["build/foo", "/build/../etc"] {
+            assert!(store
+                .save(&pkg, None, "new", Path::new(path), archive())
+                .await
+                .is_err());
+        }
+        assert!(store.load(&pkg, None, "new").await.unwrap().is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,126 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Copying artifacts between stores
+//!
+//! Hard links and reflinks are only possible if the source and the destination are on the same
+//! device. If that is not the case, or if creating the link fails, the file is copied.
+
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Error;
+use anyhow::Result;
+use tracing::{debug, trace};
+
+use crate::config::ArtifactCopyMode;
+
+/// Copy `source` to `dest` using the passed mode, falling back to a plain copy
+pub async fn copy_artifact(source: &Path, dest: &Path, mode: &ArtifactCopyMode) -> Result<()> {
+    if *mode != ArtifactCopyMode::Copy {
+        if same_device(source, dest).await? {
+            let res = match mode {
+                ArtifactCopyMode::Hardlink => tokio::fs::hard_link(source, dest)
+                    .await
+                    .map_err(Error::from),
+                ArtifactCopyMode::Reflink => reflink(source, dest).await,
+                ArtifactCopyMode::Copy => unreachable!(),
+            };
+
+            match res {
+                Ok(()) => {
+                    trace!(
+                        "Linked {} to {} ({:?})",
+                        source.display(),
+                        dest.display(),
+                        mode
+                    );
+                    return Ok(());
+                }
+                Err(e) => debug!(
+                    "Linking {} to {} ({:?}) failed, falling back to copying: {:?}",
+                    source.display(),
+                    dest.display(),
+                    mode,
+                    e
+                ),
+            }
+        } else {
+            debug!(
+                "{} and {} are on different devices, copying",
+                source.display(),
+                dest.display()
+            );
+        }
+    }
+
+    tokio::fs::copy(source, dest)
+        .await
+        .map(|_| ())
+        .with_context(|| anyhow!("Copying {} to {}", source.display(), dest.display()))
+}
+
+async fn same_device(source: &Path, dest: &Path) -> Result<bool> {
+    let dest_dir = dest
+        .parent()
+        .ok_or_else(|| anyhow!("No parent directory: {}", dest.display()))?;
+    let source_meta = tokio::fs::metadata(source)
+        .await
+        .with_context(|| anyhow!("Getting metadata of {}", source.display()))?;
+    let dest_meta = tokio::fs::metadata(dest_dir)
+        .await
+        .with_context(|| anyhow!("Getting metadata of {}", dest_dir.display()))?;
+    Ok(source_meta.dev() == dest_meta.dev())
+}
+
+/// Create a reflink with `cp`, as there is no portable API for it
+async fn reflink(source: &Path, dest: &Path) -> Result<()> {
+    let status = tokio::process::Command::new("cp")
+        .arg("--reflink=always")
+        .arg(source)
+        .arg(dest)
+        .status()
+        .await
+        .context("Running cp --reflink=always")?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!("cp --reflink=always failed: {}", status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_copy_artifact_hardlink() {
+        let dir = std::env::temp_dir().join(format!("butido-test-copy-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("source");
+        let dest = dir.join("dest");
+        std::fs::write(&source, b"content").unwrap();
+
+        copy_artifact(&source, &dest, &ArtifactCopyMode::Hardlink)
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read(&dest).unwrap(), b"content");
+        assert_eq!(
+            std::fs::metadata(&source).unwrap().ino(),
+            std::fs::metadata(&dest).unwrap().ino()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,261 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! The staging store and the release stores, merged into one view
+//!
+//! This is the lookup that decides which file is used for an artifact when a job reuses
+//! artifacts: The staging store is searched first, then the release stores in the configured
+//! order.
+
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::filestore::path::ArtifactPath;
+use crate::filestore::path::FullArtifactPath;
+use crate::filestore::path::StoreRoot;
+use crate::filestore::ReleaseStore;
+use crate::filestore::StagingStore;
+
+/// The name that selects the staging store in a `StoreSelection`
+pub const STAGING_STORE_NAME: &str = "staging";
+
+/// The stores that are searched for artifacts to reuse, and their order
+///
+/// The staging store is always searched first if it is selected, the release stores are
+/// searched in the order in which they are selected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoreSelection {
+    staging: bool,
+    release_stores: Vec<String>,
+}
+
+impl StoreSelection {
+    /// The staging store and all release stores (in the passed order)
+    pub fn all(release_stores: &[String]) -> Self {
+        StoreSelection {
+            staging: true,
+            release_stores: release_stores.to_vec(),
+        }
+    }
+
+    /// Select the stores by name, `STAGING_STORE_NAME` selects the staging store
+    pub fn new(names: &[String], release_stores: &[String]) -> Result<Self> {
+        if names.is_empty() {
+            return Err(anyhow!("At least one store must be selected"));
+        }
+
+        let mut selection = StoreSelection {
+            staging: false,
+            release_stores: Vec::new(),
+        };
+        for (i, name) in names.iter().enumerate() {
+            if names[..i].contains(name) {
+                return Err(anyhow!("Store selected multiple times: {}", name));
+            }
+
+            if name == STAGING_STORE_NAME {
+                if i != 0 {
+                    return Err(anyhow!(
+                        "The {} store is always searched first and must be selected first",
+                        STAGING_STORE_NAME
+                    ));
+                }
+                selection.staging = true;
+            } else if release_stores.contains(name) {
+                selection.release_stores.push(name.clone());
+            } else {
+                return Err(anyhow!("Unknown store: {}", name));
+            }
+        }
+        Ok(selection)
+    }
+
+    pub fn staging(&self) -> bool {
+        self.staging
+    }
+
+    /// The names of the selected release stores, in the order in which they are searched
+    pub fn release_stores(&self) -> &[String] {
+        &self.release_stores
+    }
+}
+
+/// The store an artifact was found in
+#[derive(Clone, Copy, Debug)]
+pub enum StoreOrigin<'a> {
+    Staging(&'a StagingStore),
+    Release(&'a ReleaseStore),
+}
+
+impl<'a> StoreOrigin<'a> {
+    pub fn root_path(&self) -> &'a StoreRoot {
+        match self {
+            StoreOrigin::Staging(store) => store.root_path(),
+            StoreOrigin::Release(store) => store.root_path(),
+        }
+    }
+}
+
+impl std::fmt::Display for StoreOrigin<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StoreOrigin::Staging(_) => write!(f, "staging"),
+            StoreOrigin::Release(store) => match store.root_path().file_name() {
+                Some(name) => write!(f, "{}", name.to_string_lossy()),
+                None => write!(f, "{}", store.root_path().display()),
+            },
+        }
+    }
+}
+
+pub struct MergedStores<'a> {
+    staging: Option<&'a StagingStore>,
+    release_stores: &'a [Arc<ReleaseStore>],
+}
+
+impl<'a> MergedStores<'a> {
+    pub fn new(staging: Option<&'a StagingStore>, release_stores: &'a [Arc<ReleaseStore>]) -> Self {
+        MergedStores {
+            staging,
+            release_stores,
+        }
+    }
+
+    /// The stores in the order in which they are searched
+    fn stores(&self) -> impl Iterator<Item = StoreOrigin<'a>> + 'a {
+        self.staging.map(StoreOrigin::Staging).into_iter().chain(
+            self.release_stores
+                .iter()
+                .map(|store| StoreOrigin::Release(store.as_ref())),
+        )
+    }
+
+    /// Get the artifact from the first store that contains it
+    pub fn get(&self, p: &ArtifactPath) -> Option<&'a ArtifactPath> {
+        self.stores().find_map(|origin| match origin {
+            StoreOrigin::Staging(store) => store.get(p),
+            StoreOrigin::Release(store) => store.get(p),
+        })
+    }
+
+    /// All stores that contain the artifact, in the order in which they are searched
+    ///
+    /// The first entry is the one that is used (see `MergedStores::get()`), the artifact is
+    /// shadowed in all other stores.
+    pub fn provenance(&self, p: &ArtifactPath) -> Vec<(StoreOrigin<'a>, FullArtifactPath<'a>)> {
+        self.stores()
+            .filter_map(|origin| {
+                let ap = match origin {
+                    StoreOrigin::Staging(store) => store.get(p),
+                    StoreOrigin::Release(store) => store.get(p),
+                }?;
+                Some((origin, FullArtifactPath(origin.root_path(), ap)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use indicatif::ProgressBar;
+
+    fn store_root(dir: &std::path::Path, name: &str, files: &[&str]) -> StoreRoot {
+        let root = dir.join(name);
+        std::fs::create_dir_all(&root).unwrap();
+        for file in files {
+            std::fs::write(root.join(file), file).unwrap();
+        }
+        StoreRoot::new(root).unwrap()
+    }
+
+    #[test]
+    fn test_store_selection() {
+        let release_stores = vec![String::from("prod-2023"), String::from("prod-2024")];
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        let selection = StoreSelection::new(
+            &names(&["staging", "prod-2024", "prod-2023"]),
+            &release_stores,
+        )
+        .unwrap();
+        assert!(selection.staging());
+        assert_eq!(
+            selection.release_stores(),
+            &names(&["prod-2024", "prod-2023"])[..]
+        );
+
+        let selection = StoreSelection::new(&names(&["prod-2023"]), &release_stores).unwrap();
+        assert!(!selection.staging());
+        assert_eq!(selection.release_stores(), &names(&["prod-2023"])[..]);
+
+        assert_eq!(
+            StoreSelection::new(
+                &names(&["staging", "prod-2023", "prod-2024"]),
+                &release_stores
+            )
+            .unwrap(),
+            StoreSelection::all(&release_stores)
+        );
+
+        assert!(StoreSelection::new(&[], &release_stores).is_err());
+        assert!(StoreSelection::new(&names(&["prod-2025"]), &release_stores).is_err());
+        assert!(StoreSelection::new(&names(&["prod-2023", "prod-2023"]), &release_stores).is_err());
+        assert!(StoreSelection::new(&names(&["prod-2023", "staging"]), &release_stores).is_err());
+    }
+
+    #[test]
+    fn test_lookup_order() {
+        let dir = std::env::temp_dir().join(format!("butido-test-merged-{}", uuid::Uuid::new_v4()));
+        let bar = ProgressBar::hidden();
+        let staging =
+            StagingStore::load(store_root(&dir, "staging", &["a-1.0.tar.gz"]), &bar).unwrap();
+        let release_stores = vec![
+            Arc::new(
+                ReleaseStore::load(
+                    store_root(&dir, "stable", &["a-1.0.tar.gz", "b-1.0.tar.gz"]),
+                    &bar,
+                )
+                .unwrap(),
+            ),
+            Arc::new(
+                ReleaseStore::load(store_root(&dir, "testing", &["b-1.0.tar.gz"]), &bar).unwrap(),
+            ),
+        ];
+        let merged = MergedStores::new(Some(&staging), &release_stores);
+
+        let a = ArtifactPath::new_unchecked("a-1.0.tar.gz".into());
+        let origins = merged
+            .provenance(&a)
+            .into_iter()
+            .map(|(origin, _)| origin.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(origins, vec!["staging", "stable"]);
+
+        let b = ArtifactPath::new_unchecked("b-1.0.tar.gz".into());
+        let provenance = merged.provenance(&b);
+        assert_eq!(provenance.len(), 2);
+        assert_eq!(provenance[0].0.to_string(), "stable");
+        assert_eq!(
+            provenance[0].1.joined(),
+            dir.join("stable").join("b-1.0.tar.gz")
+        );
+        assert_eq!(merged.get(&b), Some(&b));
+
+        let c = ArtifactPath::new_unchecked("c-1.0.tar.gz".into());
+        assert!(merged.get(&c).is_none());
+        assert!(merged.provenance(&c).is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
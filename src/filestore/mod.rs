@@ -8,6 +8,15 @@
 // SPDX-License-Identifier: EPL-2.0
 //
 
+mod checkpoint;
+pub use checkpoint::*;
+
+mod copy;
+pub use copy::*;
+
+mod merged;
+pub use merged::*;
+
 mod release;
 pub use release::*;
 
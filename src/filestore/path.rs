@@ -22,6 +22,8 @@ use resiter::Map;
 use tracing::trace;
 
 use crate::filestore::staging::StagingStore;
+use crate::package::HashType;
+use crate::package::HashValue;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct StoreRoot(PathBuf);
@@ -77,6 +79,10 @@ impl StoreRoot {
         self.0.display()
     }
 
+    pub fn file_name(&self) -> Option<&OsStr> {
+        self.0.file_name()
+    }
+
     pub(in crate::filestore) fn find_artifacts_recursive(
         &self,
     ) -> impl Iterator<Item = Result<ArtifactPath>> {
@@ -85,6 +91,15 @@ impl StoreRoot {
         walkdir::WalkDir::new(&self.0)
             .follow_links(false)
             .into_iter()
+            .filter_entry(|e| {
+                // Skip the temporary directories of releases that are in progress (or failed)
+                let is_release_tmp_dir = e.depth() == 1
+                    && e.file_type().is_dir()
+                    && e.file_name()
+                        .to_str()
+                        .is_some_and(|n| n.starts_with(crate::consts::RELEASE_TMP_DIR_PREFIX));
+                !is_release_tmp_dir
+            })
             .filter_ok(|e| {
                 let is_file = e.file_type().is_file();
                 trace!("{:?} is file = {}", e, is_file);
@@ -107,9 +122,11 @@ impl StoreRoot {
     /// `self` and returns the written paths.
     ///
     /// The function filters out the "/output" directory (that's what is meant by "butido-style").
+    /// If `prefix` is passed, the files are unpacked into this subdirectory.
     pub(in crate::filestore) fn unpack_archive_here<R>(
         &self,
         mut ar: tar::Archive<R>,
+        prefix: Option<&Path>,
     ) -> Result<Vec<PathBuf>>
     where
         R: std::io::Read,
@@ -131,11 +148,19 @@ impl StoreRoot {
                         }
                     })
                     .collect::<PathBuf>();
+                let path = match prefix {
+                    Some(prefix) => prefix.join(path),
+                    None => path,
+                };
 
                 trace!("Path = '{:?}'", path);
                 let unpack_dest = self.0.join(&path);
                 trace!("Unpack to = '{:?}'", unpack_dest);
 
+                if let Some(parent) = unpack_dest.parent() {
+                    std::fs::create_dir_all(parent)
+                        .with_context(|| anyhow!("Creating {}", parent.display()))?;
+                }
                 entry.unpack(unpack_dest).map(|_| path).map_err(Error::from)
             })
             .collect::<Result<Vec<_>>>()
@@ -177,6 +202,12 @@ impl ArtifactPath {
     }
 }
 
+impl AsRef<Path> for StoreRoot {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
 impl AsRef<Path> for ArtifactPath {
     fn as_ref(&self) -> &Path {
         &self.0
@@ -184,7 +215,10 @@ impl AsRef<Path> for ArtifactPath {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct FullArtifactPath<'a>(&'a StoreRoot, &'a ArtifactPath);
+pub struct FullArtifactPath<'a>(
+    pub(in crate::filestore) &'a StoreRoot,
+    pub(in crate::filestore) &'a ArtifactPath,
+);
 
 impl<'a> FullArtifactPath<'a> {
     pub fn is_in_staging_store(&self, store: &StagingStore) -> bool {
@@ -203,10 +237,16 @@ impl<'a> FullArtifactPath<'a> {
         FullArtifactPathDisplay(self.0, self.1)
     }
 
-    pub async fn read(self) -> Result<Vec<u8>> {
-        tokio::fs::read(self.joined())
+    /// The SHA-256 hash of the artifact file
+    pub async fn sha256(&self) -> Result<HashValue> {
+        let path = self.joined();
+        let file = tokio::fs::File::open(&path)
+            .await
+            .with_context(|| anyhow!("Opening {}", path.display()))?;
+        HashType::Sha256
+            .hash_from_reader(tokio::io::BufReader::new(file))
             .await
-            .with_context(|| anyhow!("Reading artifact from path {}", self.0.display()))
+            .with_context(|| anyhow!("Hashing {}", path.display()))
     }
 }
 
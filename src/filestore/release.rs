@@ -38,4 +38,17 @@ impl ReleaseStore {
     pub fn get(&self, p: &ArtifactPath) -> Option<&ArtifactPath> {
         self.0.get(p)
     }
+
+    /// All artifacts in the store
+    pub fn artifacts(&self) -> impl Iterator<Item = &ArtifactPath> {
+        self.0.iter()
+    }
+
+    /// Only keep the artifacts for which `f` returns true, e.g., the artifacts of a snapshot
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: FnMut(&ArtifactPath) -> bool,
+    {
+        self.0.retain(f)
+    }
 }
@@ -9,6 +9,7 @@
 //
 
 use std::fmt::Debug;
+use std::path::Path;
 
 use anyhow::anyhow;
 use anyhow::Context;
@@ -36,25 +37,66 @@ impl StagingStore {
 
     /// Write the passed tar stream to the file store
     ///
+    /// The files of a job that builds for an architecture are written to the directory `arch` in
+    /// the store, so that the jobs of a package for several architectures do not overwrite each
+    /// other's artifacts.
+    ///
     /// # Returns
     ///
     /// Returns a list of Artifacts that were written from the stream
-    pub async fn write_files_from_tar_stream<S>(&mut self, stream: S) -> Result<Vec<ArtifactPath>>
+    pub async fn write_files_from_tar_stream<S>(
+        &mut self,
+        stream: S,
+        arch: Option<&str>,
+    ) -> Result<Vec<ArtifactPath>>
     where
         S: Stream<Item = Result<Vec<u8>>>,
     {
         use futures::stream::TryStreamExt;
 
         let dest = self.0.root_path();
-        stream
+        let paths = stream
             .try_concat()
             .await
             .and_then(|bytes| {
                 trace!("Unpacking archive to {}", dest.display());
-                dest.unpack_archive_here(tar::Archive::new(&bytes[..]))
+                dest.unpack_archive_here(tar::Archive::new(&bytes[..]), arch.map(Path::new))
                     .context("Unpacking TAR")
             })
-            .context("Concatenating the output bytestream")?
+            .context("Concatenating the output bytestream")?;
+        self.load_unpacked(paths)
+    }
+
+    /// Write the files of the TAR archive at `archive` to the file store
+    ///
+    /// Like `write_files_from_tar_stream()`, but the archive is read from a file instead of being
+    /// kept in memory.
+    pub fn write_files_from_tar_file(
+        &mut self,
+        archive: &Path,
+        arch: Option<&str>,
+    ) -> Result<Vec<ArtifactPath>> {
+        let file = std::fs::File::open(archive)
+            .with_context(|| anyhow!("Opening {}", archive.display()))?;
+        trace!(
+            "Unpacking {} to {}",
+            archive.display(),
+            self.0.root_path().display()
+        );
+        let paths = self
+            .0
+            .root_path()
+            .unpack_archive_here(
+                tar::Archive::new(std::io::BufReader::new(file)),
+                arch.map(Path::new),
+            )
+            .context("Unpacking TAR")?;
+        self.load_unpacked(paths)
+    }
+
+    /// Load the files that were unpacked to the passed paths into the store
+    fn load_unpacked(&mut self, paths: Vec<std::path::PathBuf>) -> Result<Vec<ArtifactPath>> {
+        paths
             .into_iter()
             .inspect(|p| trace!("Trying to load into staging store: {}", p.display()))
             .filter_map(|path| {
@@ -79,4 +121,131 @@ impl StagingStore {
     pub fn get(&self, p: &ArtifactPath) -> Option<&ArtifactPath> {
         self.0.get(p)
     }
+
+    /// Remove the passed artifacts from the store and delete their files, e.g., the artifacts of
+    /// a job whose artifacts were rejected
+    pub fn remove(&mut self, artifacts: &[ArtifactPath]) -> Result<()> {
+        for artifact in artifacts {
+            if let Some(full_path) = self.0.root_path().join(artifact)? {
+                let path = full_path.joined();
+                trace!("Removing from staging store: {}", path.display());
+                std::fs::remove_file(&path)
+                    .with_context(|| anyhow!("Removing {}", path.display()))?;
+            }
+        }
+        self.0.retain(|p| !artifacts.contains(p));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    fn archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, *content).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_archs_do_not_overwrite_each_other() {
+        let dir =
+            std::env::temp_dir().join(format!("butido-test-staging-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        let mut store =
+            StagingStore::load(StoreRoot::new(dir.clone()).unwrap(), &ProgressBar::hidden())
+                .unwrap();
+
+        for arch in ["x86_64", "aarch64"] {
+            let content = archive(&[("outputs/foo-1.0.tar.gz", arch.as_bytes())]);
+            let artifacts = store
+                .write_files_from_tar_stream(
+                    futures::stream::once(async { Ok(content) }),
+                    Some(arch),
+                )
+                .await
+                .unwrap();
+            assert_eq!(
+                artifacts,
+                vec![ArtifactPath::new_unchecked(
+                    Path::new(arch).join("foo-1.0.tar.gz")
+                )]
+            );
+        }
+
+        assert_eq!(
+            std::fs::read(dir.join("x86_64/foo-1.0.tar.gz")).unwrap(),
+            b"x86_64"
+        );
+        assert_eq!(
+            std::fs::read(dir.join("aarch64/foo-1.0.tar.gz")).unwrap(),
+            b"aarch64"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_files_from_tar_file() {
+        let dir =
+            std::env::temp_dir().join(format!("butido-test-staging-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        let mut store =
+            StagingStore::load(StoreRoot::new(dir.clone()).unwrap(), &ProgressBar::hidden())
+                .unwrap();
+        let archive_path = dir.join(".archive.tar");
+        std::fs::write(&archive_path, archive(&[("foo-1.0.tar.gz", b"foo")])).unwrap();
+
+        let artifacts = store
+            .write_files_from_tar_file(&archive_path, Some("x86_64"))
+            .unwrap();
+        assert_eq!(
+            artifacts,
+            vec![ArtifactPath::new_unchecked(PathBuf::from(
+                "x86_64/foo-1.0.tar.gz"
+            ))]
+        );
+        assert_eq!(
+            std::fs::read(dir.join("x86_64/foo-1.0.tar.gz")).unwrap(),
+            b"foo"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_remove_deletes_artifacts() {
+        let dir =
+            std::env::temp_dir().join(format!("butido-test-staging-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        let mut store =
+            StagingStore::load(StoreRoot::new(dir.clone()).unwrap(), &ProgressBar::hidden())
+                .unwrap();
+        let archive_path = dir.join(".archive.tar");
+        std::fs::write(
+            &archive_path,
+            archive(&[("foo-1.0.tar.gz", b"foo"), ("bar-1.0.tar.gz", b"bar")]),
+        )
+        .unwrap();
+        store
+            .write_files_from_tar_file(&archive_path, None)
+            .unwrap();
+
+        let foo = ArtifactPath::new_unchecked(PathBuf::from("foo-1.0.tar.gz"));
+        let bar = ArtifactPath::new_unchecked(PathBuf::from("bar-1.0.tar.gz"));
+        store.remove(std::slice::from_ref(&foo)).unwrap();
+
+        assert!(store.get(&foo).is_none());
+        assert!(!dir.join("foo-1.0.tar.gz").exists());
+        assert!(store.get(&bar).is_some());
+        assert!(dir.join("bar-1.0.tar.gz").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
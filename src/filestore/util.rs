@@ -51,6 +51,17 @@ impl FileStoreImpl {
         self.store.get(artifact_path)
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = &ArtifactPath> {
+        self.store.iter()
+    }
+
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: FnMut(&ArtifactPath) -> bool,
+    {
+        self.store.retain(f)
+    }
+
     pub(in crate::filestore) fn load_from_path<'a>(
         &mut self,
         artifact_path: &'a ArtifactPath,
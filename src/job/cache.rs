@@ -0,0 +1,257 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Build cache keys for jobs
+//!
+//! A cache key identifies the inputs of a job: The package definition (including the generated
+//! script, the sources, the patches and the environment), the host paths that are mounted into the
+//! container, the digest of the image the job runs on and the contents of all artifacts the job
+//! gets as input.
+//! If two jobs have the same cache key, the artifacts of one job can be used for the other one.
+
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use itertools::Itertools;
+use sha2::Digest;
+use tokio::sync::RwLock;
+use tracing::trace;
+
+use crate::filestore::ReleaseStore;
+use crate::filestore::StagingStore;
+use crate::job::JobResource;
+use crate::job::RunnableJob;
+use crate::package::HashType;
+use crate::package::Package;
+use crate::util::docker::Mount;
+use crate::util::EnvironmentVariableName;
+
+#[derive(parse_display::Display, Clone, Debug, Eq, PartialEq, Hash)]
+#[display("{0}")]
+pub struct CacheKey(String);
+
+impl AsRef<str> for CacheKey {
+    fn as_ref(&self) -> &str {
+        self.0.as_ref()
+    }
+}
+
+impl CacheKey {
+    /// Compute the cache key for a job that runs on an image with the digest `image_digest`
+    ///
+    /// The input artifacts of the job are searched in the staging store first and in the release
+    /// stores afterwards.
+    pub async fn compute(
+        job: &RunnableJob,
+        image_digest: &str,
+        staging_store: Arc<RwLock<StagingStore>>,
+        release_stores: &[Arc<ReleaseStore>],
+    ) -> Result<CacheKey> {
+        let mut patches = Vec::with_capacity(job.package().patches().len());
+        for patch in job.package().patches().iter() {
+            let file = tokio::fs::File::open(patch)
+                .await
+                .with_context(|| anyhow!("Opening patch {}", patch.display()))?;
+            let hash = HashType::Sha256
+                .hash_from_reader(tokio::io::BufReader::new(file))
+                .await
+                .with_context(|| anyhow!("Hashing patch {}", patch.display()))?;
+            patches.push((patch.to_string_lossy().to_string(), hash.to_string()));
+        }
+
+        let staging_read = staging_store.read().await;
+        let mut artifacts = Vec::new();
+        for artifact in job.resources().iter().filter_map(JobResource::artifact) {
+            let full_path = match staging_read.root_path().join(artifact)? {
+                Some(path) => path,
+                None => release_stores
+                    .iter()
+                    .map(|store| store.root_path().join(artifact))
+                    .find_map(|res| res.transpose())
+                    .transpose()?
+                    .ok_or_else(|| {
+                        anyhow!("Not found in staging or release store: {:?}", artifact)
+                    })?,
+            };
+
+            let file_name = artifact
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            artifacts.push((file_name, full_path.sha256().await?.to_string()));
+        }
+        drop(staging_read);
+
+        let key = Inputs {
+            package: job.package(),
+            script: job.script().as_ref(),
+            patches,
+            environment: job.environment().collect(),
+            mounts: job.mounts(),
+            image_digest,
+            arch: job.arch().as_deref(),
+            artifacts,
+        }
+        .key();
+        trace!(job_uuid = %job.uuid(), %key, "Computed cache key");
+        Ok(key)
+    }
+}
+
+/// Everything a cache key is computed from, with the patches and input artifacts already hashed
+struct Inputs<'a> {
+    package: &'a Package,
+    script: &'a str,
+    /// The patches, with the SHA-256 hashes of their contents
+    patches: Vec<(String, String)>,
+    environment: Vec<(&'a EnvironmentVariableName, &'a String)>,
+    mounts: &'a [Mount],
+    image_digest: &'a str,
+    arch: Option<&'a str>,
+    /// The file names of the input artifacts, with the SHA-256 hashes of their contents
+    artifacts: Vec<(String, String)>,
+}
+
+impl Inputs<'_> {
+    fn key(&self) -> CacheKey {
+        let mut hasher = sha2::Sha256::new();
+
+        let mut update = |name: &str, value: &[u8]| {
+            hasher.update(name.as_bytes());
+            hasher.update((value.len() as u64).to_le_bytes());
+            hasher.update(value);
+        };
+
+        update("package-name", self.package.name().as_bytes());
+        update("package-version", self.package.version().as_bytes());
+        update("script", self.script.as_bytes());
+
+        for (name, source) in self.package.sources().iter().sorted_by(|a, b| a.0.cmp(b.0)) {
+            update("source-name", name.as_bytes());
+            update("source-url", source.url().as_str().as_bytes());
+            update(
+                "source-hash-type",
+                source.hash().hashtype().to_string().as_bytes(),
+            );
+            update("source-hash", source.hash().value().to_string().as_bytes());
+        }
+
+        for (name, hash) in self.patches.iter() {
+            update("patch-name", name.as_bytes());
+            update("patch", hash.as_bytes());
+        }
+
+        for (name, value) in self.environment.iter().sorted() {
+            update("env-name", name.as_ref().as_bytes());
+            update("env-value", value.as_bytes());
+        }
+
+        // Only the mount specifications are hashed, not the contents (they might be huge
+        // toolchains), so a changed toolchain has to be mounted from a new path
+        for mount in self
+            .mounts
+            .iter()
+            .sorted_by(|a, b| (&a.source, &a.target).cmp(&(&b.source, &b.target)))
+        {
+            update("mount-source", mount.source.to_string_lossy().as_bytes());
+            update("mount-target", mount.target.to_string_lossy().as_bytes());
+            update("mount-writable", &[u8::from(mount.writable)]);
+        }
+
+        update("image-digest", self.image_digest.as_bytes());
+        if let Some(arch) = self.arch {
+            update("arch", arch.as_bytes());
+        }
+
+        for (file_name, hash) in self.artifacts.iter().sorted() {
+            update("artifact-name", file_name.as_bytes());
+            update("artifact", hash.as_bytes());
+        }
+
+        CacheKey(format!("{:x}", hasher.finalize()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::package::tests::package;
+
+    fn inputs<'a>(
+        package: &'a Package,
+        environment: Vec<(&'a EnvironmentVariableName, &'a String)>,
+    ) -> Inputs<'a> {
+        Inputs {
+            package,
+            script: "#!/bin/bash\nmake\n",
+            patches: vec![(String::from("fix.patch"), String::from("aa"))],
+            environment,
+            mounts: &[],
+            image_digest: "sha256:1234",
+            arch: None,
+            artifacts: vec![
+                (String::from("a-1.0.tar.gz"), String::from("bb")),
+                (String::from("b-2.0.tar.gz"), String::from("cc")),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_key_is_stable() {
+        let pkg = package("a", "1", "https://rust-lang.org", "123");
+        let name = EnvironmentVariableName::from("FOO");
+        let other_name = EnvironmentVariableName::from("BAR");
+        let (value, other_value) = (String::from("foo"), String::from("bar"));
+
+        let key = inputs(&pkg, vec![(&name, &value), (&other_name, &other_value)]).key();
+        let mut reordered = inputs(&pkg, vec![(&other_name, &other_value), (&name, &value)]);
+        reordered.artifacts.reverse();
+
+        assert_eq!(key, reordered.key());
+        assert_eq!(
+            key,
+            inputs(&pkg, vec![(&name, &value), (&other_name, &other_value)]).key()
+        );
+    }
+
+    #[test]
+    fn test_key_changes_with_inputs() {
+        let pkg = package("a", "1", "https://rust-lang.org", "123");
+        let other_source = package("a", "1", "https://rust-lang.org", "456");
+        let name = EnvironmentVariableName::from("FOO");
+        let (value, other_value) = (String::from("foo"), String::from("bar"));
+        let key = inputs(&pkg, vec![(&name, &value)]).key();
+
+        let mut script = inputs(&pkg, vec![(&name, &value)]);
+        script.script = "#!/bin/bash\nmake install\n";
+        let mut patch = inputs(&pkg, vec![(&name, &value)]);
+        patch.patches[0].1 = String::from("ab");
+        let mut artifact = inputs(&pkg, vec![(&name, &value)]);
+        artifact.artifacts[1].1 = String::from("cd");
+        let mut image = inputs(&pkg, vec![(&name, &value)]);
+        image.image_digest = "sha256:5678";
+
+        let changed = [
+            script.key(),
+            inputs(&other_source, vec![(&name, &value)]).key(),
+            patch.key(),
+            inputs(&pkg, vec![(&name, &other_value)]).key(),
+            inputs(&pkg, vec![]).key(),
+            artifact.key(),
+            image.key(),
+        ];
+        for (idx, changed) in changed.iter().enumerate() {
+            assert_ne!(&key, changed, "Key did not change for input {idx}");
+        }
+    }
+}
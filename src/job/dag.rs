@@ -11,12 +11,12 @@
 use getset::Getters;
 use petgraph::acyclic::Acyclic;
 use petgraph::graph::DiGraph;
+use petgraph::graph::NodeIndex;
 use uuid::Uuid;
 
 use crate::job::Job;
 use crate::job::JobResource;
 use crate::package::DependencyType;
-use crate::package::Package;
 use crate::package::PhaseName;
 use crate::package::Shebang;
 use crate::util::docker::ImageName;
@@ -28,26 +28,44 @@ pub struct Dag {
 }
 
 impl Dag {
+    /// Build the jobs for the packages in `dag`
+    ///
+    /// The jobs are built once per architecture in `archs`, i.e., the graph contains one
+    /// independent copy of the package DAG per architecture. `None` builds jobs that are not
+    /// bound to an architecture.
     pub fn from_package_dag(
         dag: crate::package::Dag,
         script_shebang: Shebang,
         image: ImageName,
         phases: Vec<PhaseName>,
         resources: Vec<JobResource>,
+        archs: &[Option<String>],
     ) -> Self {
-        let build_job = |_, p: &Package| {
-            Job::new(
-                p.clone(),
-                script_shebang.clone(),
-                image.clone(),
-                phases.clone(),
-                resources.clone(),
-            )
-        };
+        let mut graph = DiGraph::new();
+        for arch in archs {
+            let offset = graph.node_count();
+            for idx in dag.dag().node_indices() {
+                graph.add_node(Job::new(
+                    dag.dag()[idx].clone(),
+                    script_shebang.clone(),
+                    image.clone(),
+                    phases.clone(),
+                    resources.clone(),
+                    arch.clone(),
+                ));
+            }
+            for edge in dag.dag().raw_edges() {
+                graph.add_edge(
+                    NodeIndex::new(offset + edge.source().index()),
+                    NodeIndex::new(offset + edge.target().index()),
+                    edge.weight.clone(),
+                );
+            }
+        }
 
         Dag {
-            dag: Acyclic::<_>::try_from_graph(dag.dag().map(build_job, |_, e| (*e).clone()))
-                .unwrap(), // The dag.dag() is already acyclic so this cannot fail
+            // The copies of dag.dag() are acyclic and not connected, so this cannot fail
+            dag: Acyclic::<_>::try_from_graph(graph).unwrap(),
         }
     }
 
@@ -0,0 +1,230 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Predict how long a submit takes, based on the durations of previous builds of its packages
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::time::Duration;
+
+use anyhow::Result;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::job::Dag;
+use crate::package::Package;
+
+/// The number of previous builds of a package whose durations are considered
+const DURATION_HISTORY: i64 = 5;
+
+/// The estimated duration of a submit
+///
+/// The durations of the jobs are simulated on the available slots of the endpoints (a job starts
+/// as soon as its dependencies are finished and a slot is free). Jobs of packages that were not
+/// built before are assumed to take the average time of the other jobs.
+#[derive(Debug)]
+pub struct SubmitEstimate {
+    durations: HashMap<Uuid, Duration>,
+    dependencies: HashMap<Uuid, Vec<Uuid>>,
+    parallelism: usize,
+    finished: HashSet<Uuid>,
+}
+
+impl SubmitEstimate {
+    /// Estimate the duration of the jobs of a submit with the durations of previous builds
+    ///
+    /// Returns `None` if none of the packages was built before.
+    pub fn load(
+        database_connection: &mut PgConnection,
+        jobdag: &Dag,
+        parallelism: usize,
+    ) -> Result<Option<Self>> {
+        let mut known = HashMap::new();
+        for jobdef in jobdag.iter() {
+            if let Some(duration) = previous_duration(database_connection, jobdef.job.package())? {
+                known.insert(*jobdef.job.uuid(), duration);
+            }
+        }
+
+        let dependencies = jobdag
+            .iter()
+            .map(|jobdef| (*jobdef.job.uuid(), jobdef.dependencies))
+            .collect();
+        Ok(Self::new(known, dependencies, parallelism))
+    }
+
+    fn new(
+        known: HashMap<Uuid, Duration>,
+        dependencies: HashMap<Uuid, Vec<Uuid>>,
+        parallelism: usize,
+    ) -> Option<Self> {
+        if known.is_empty() {
+            return None;
+        }
+
+        let average = known.values().sum::<Duration>() / known.len() as u32;
+        let durations = dependencies
+            .keys()
+            .map(|uuid| (*uuid, known.get(uuid).copied().unwrap_or(average)))
+            .collect();
+
+        Some(SubmitEstimate {
+            durations,
+            dependencies,
+            parallelism: parallelism.max(1),
+            finished: HashSet::new(),
+        })
+    }
+
+    /// Record that a job finished (or will not run at all)
+    pub fn job_finished(&mut self, job_uuid: &Uuid) {
+        self.finished.insert(*job_uuid);
+    }
+
+    /// The number of jobs that did not finish yet
+    pub fn remaining_jobs(&self) -> usize {
+        self.dependencies.len() - self.finished.len()
+    }
+
+    /// The estimated time until all jobs that did not finish yet are finished
+    ///
+    /// The jobs that are currently running are assumed to take their full duration, so the
+    /// estimate is rather pessimistic.
+    pub fn remaining(&self) -> Duration {
+        let mut finish_times = HashMap::<Uuid, Duration>::new();
+        let mut slots = (0..self.parallelism)
+            .map(|_| Reverse(Duration::ZERO))
+            .collect::<BinaryHeap<_>>();
+
+        for uuid in self.unfinished_in_dependency_order() {
+            let ready = self.dependencies[&uuid]
+                .iter()
+                .filter_map(|dependency| finish_times.get(dependency))
+                .max()
+                .copied()
+                .unwrap_or_default();
+            let Reverse(free) = slots.pop().unwrap_or(Reverse(Duration::ZERO));
+            let finish = ready.max(free) + self.durations[&uuid];
+            slots.push(Reverse(finish));
+            finish_times.insert(uuid, finish);
+        }
+
+        finish_times.into_values().max().unwrap_or_default()
+    }
+
+    /// The jobs that did not finish yet, every job after its dependencies
+    fn unfinished_in_dependency_order(&self) -> Vec<Uuid> {
+        fn visit(
+            uuid: &Uuid,
+            estimate: &SubmitEstimate,
+            visited: &mut HashSet<Uuid>,
+            order: &mut Vec<Uuid>,
+        ) {
+            if estimate.finished.contains(uuid) || !visited.insert(*uuid) {
+                return;
+            }
+            for dependency in estimate.dependencies.get(uuid).into_iter().flatten() {
+                visit(dependency, estimate, visited, order);
+            }
+            order.push(*uuid);
+        }
+
+        // Sorted, so that the estimate does not depend on the order of the HashMap
+        let mut uuids = self.dependencies.keys().collect::<Vec<_>>();
+        uuids.sort();
+
+        let mut visited = HashSet::new();
+        let mut order = Vec::new();
+        for uuid in uuids {
+            visit(uuid, self, &mut visited, &mut order);
+        }
+        order
+    }
+}
+
+/// Format an estimated duration, rounded up to full minutes (the estimate is not more precise)
+pub fn format_estimate(duration: Duration) -> String {
+    let minutes = duration.as_secs().div_ceil(60).max(1);
+    humantime::format_duration(Duration::from_secs(minutes * 60)).to_string()
+}
+
+/// The median duration of the last successful builds of a package
+fn previous_duration(
+    database_connection: &mut PgConnection,
+    package: &Package,
+) -> Result<Option<Duration>> {
+    use crate::schema;
+
+    // Only jobs that produced artifacts were successful
+    let mut durations = schema::jobs::table
+        .inner_join(schema::packages::table)
+        .filter(schema::packages::name.eq(package.name().as_ref() as &str))
+        .filter(schema::packages::version.eq(package.version().as_ref() as &str))
+        .filter(schema::jobs::retried.eq(false))
+        .filter(diesel::dsl::exists(
+            schema::artifacts::table.filter(schema::artifacts::job_id.eq(schema::jobs::id)),
+        ))
+        .select((schema::jobs::started_at, schema::jobs::finished_at))
+        .order_by(schema::jobs::id.desc())
+        .limit(DURATION_HISTORY)
+        .load::<(Option<chrono::NaiveDateTime>, Option<chrono::NaiveDateTime>)>(
+            database_connection,
+        )?
+        .into_iter()
+        .filter_map(|(started, finished)| (finished? - started?).to_std().ok())
+        .collect::<Vec<_>>();
+
+    durations.sort();
+    Ok(durations.get(durations.len() / 2).copied())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remaining_with_parallelism() {
+        // c depends on a and b
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let minutes = |m| Duration::from_secs(m * 60);
+        let known = HashMap::from([(a, minutes(10)), (b, minutes(20)), (c, minutes(5))]);
+        let dependencies = HashMap::from([(a, vec![]), (b, vec![]), (c, vec![a, b])]);
+
+        let mut estimate = SubmitEstimate::new(known.clone(), dependencies.clone(), 2).unwrap();
+        assert_eq!(estimate.remaining(), minutes(25));
+
+        let sequential = SubmitEstimate::new(known, dependencies, 1).unwrap();
+        assert_eq!(sequential.remaining(), minutes(35));
+
+        estimate.job_finished(&b);
+        assert_eq!(estimate.remaining_jobs(), 2);
+        assert_eq!(estimate.remaining(), minutes(15));
+    }
+
+    #[test]
+    fn test_unknown_jobs_take_the_average() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let known = HashMap::from([(a, Duration::from_secs(60))]);
+        let dependencies = HashMap::from([(a, vec![]), (b, vec![a])]);
+
+        let estimate = SubmitEstimate::new(known, dependencies, 4).unwrap();
+        assert_eq!(estimate.remaining(), Duration::from_secs(120));
+        assert!(SubmitEstimate::new(HashMap::new(), HashMap::new(), 4).is_none());
+    }
+
+    #[test]
+    fn test_format_estimate() {
+        assert_eq!(format_estimate(Duration::ZERO), "1m");
+        assert_eq!(format_estimate(Duration::from_secs(61)), "2m");
+        assert_eq!(format_estimate(Duration::from_secs(3600)), "1h");
+    }
+}
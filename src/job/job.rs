@@ -38,6 +38,10 @@ pub struct Job {
 
     #[getset(get = "pub")]
     resources: Vec<JobResource>,
+
+    /// The architecture the job builds for, if the submit builds for specific architectures
+    #[getset(get = "pub")]
+    arch: Option<String>,
 }
 
 impl Job {
@@ -47,6 +51,7 @@ impl Job {
         image: ImageName,
         phases: Vec<PhaseName>,
         resources: Vec<JobResource>,
+        arch: Option<String>,
     ) -> Self {
         let uuid = Uuid::new_v4();
 
@@ -57,6 +62,7 @@ impl Job {
             script_shebang,
             script_phases: phases,
             resources,
+            arch,
         }
     }
 }
@@ -12,9 +12,18 @@
 mod job;
 pub use job::*;
 
+mod cache;
+pub use cache::*;
+
 mod dag;
 pub use dag::*;
 
+mod eta;
+pub use eta::*;
+
+mod remote_cache;
+pub use remote_cache::*;
+
 mod resource;
 pub use resource::*;
 
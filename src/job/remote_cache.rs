@@ -0,0 +1,386 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Client for a remote (HTTP) build cache
+//!
+//! The artifacts of a job are stored as TAR archive at `<url>/<cache key>.tar`, the SHA-256 hash
+//! of the archive at `<url>/<cache key>.tar.sha256` (in the format of `sha256sum`).
+//! They are fetched with GET requests (404 means that there is no cached result) and uploaded
+//! with PUT requests. The hash is uploaded after the archive, an archive without hash is not used.
+
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use futures::StreamExt;
+use indicatif::ProgressBar;
+use sha2::Digest;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::RwLock;
+use tracing::trace;
+use tracing::warn;
+
+use crate::config::RemoteCacheConfig;
+use crate::filestore::ArtifactPath;
+use crate::filestore::StagingStore;
+use crate::job::CacheKey;
+
+const APP_USER_AGENT: &str = concat! {env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")};
+
+/// The size of the chunks in which the archive is uploaded
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+#[derive(Clone, Debug)]
+pub struct RemoteCache {
+    client: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+    upload: bool,
+}
+
+impl RemoteCache {
+    pub fn new(config: &RemoteCacheConfig) -> Result<Self> {
+        let client_builder = reqwest::Client::builder().user_agent(APP_USER_AGENT);
+
+        let client_builder = if let Some(to) = config.timeout() {
+            client_builder.timeout(std::time::Duration::from_secs(*to))
+        } else {
+            client_builder
+        };
+
+        let client = client_builder
+            .build()
+            .context("Building HTTP client for the remote cache failed")?;
+
+        Ok(RemoteCache {
+            client,
+            base_url: config.url().as_str().trim_end_matches('/').to_string(),
+            token: config.token().clone(),
+            username: config.username().clone(),
+            password: config.password().clone(),
+            upload: *config.upload(),
+        })
+    }
+
+    fn archive_url(&self, key: &CacheKey) -> String {
+        format!("{}/{}.tar", self.base_url, key)
+    }
+
+    fn digest_url(&self, key: &CacheKey) -> String {
+        format!("{}/{}.tar.sha256", self.base_url, key)
+    }
+
+    fn request(&self, method: reqwest::Method, url: &str) -> reqwest::RequestBuilder {
+        let builder = self.client.request(method, url);
+        if let Some(token) = self.token.as_ref() {
+            builder.bearer_auth(token)
+        } else if let Some(username) = self.username.as_ref() {
+            builder.basic_auth(username, self.password.as_ref())
+        } else {
+            builder
+        }
+    }
+
+    /// Send a GET request, `None` means that the remote cache does not have the requested file
+    async fn get(&self, url: &str) -> Result<Option<reqwest::Response>> {
+        let response = self
+            .request(reqwest::Method::GET, url)
+            .send()
+            .await
+            .with_context(|| anyhow!("Fetching '{}' from remote cache", url))?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        response
+            .error_for_status()
+            .map(Some)
+            .with_context(|| anyhow!("Fetching '{}' from remote cache", url))
+    }
+
+    /// Fetch the artifacts for the passed cache key into the staging store
+    ///
+    /// The archive is hashed while it is downloaded to a temporary file and only unpacked if its
+    /// SHA-256 hash matches the hash that was uploaded with it. The artifacts are written to the
+    /// directory of the architecture `arch` in the staging store (see
+    /// `StagingStore::write_files_from_tar_file()`).
+    /// The number of received bytes is reported to `bar`.
+    /// Returns `None` if the remote cache has no artifacts (or no hash) for the key.
+    pub async fn fetch(
+        &self,
+        key: &CacheKey,
+        staging_store: Arc<RwLock<StagingStore>>,
+        arch: Option<&str>,
+        bar: &ProgressBar,
+    ) -> Result<Option<Vec<ArtifactPath>>> {
+        let digest_url = self.digest_url(key);
+        trace!("Fetching {} from remote cache", digest_url);
+        let Some(response) = self.get(&digest_url).await? else {
+            trace!("Remote cache has no entry for {}", key);
+            return Ok(None);
+        };
+        let expected_digest = response
+            .text()
+            .await
+            .with_context(|| anyhow!("Fetching '{}' from remote cache", digest_url))?
+            .split_whitespace()
+            .next()
+            .map(str::to_lowercase)
+            .ok_or_else(|| anyhow!("No hash in '{}'", digest_url))?;
+
+        let url = self.archive_url(key);
+        trace!("Fetching {} from remote cache", url);
+        let Some(response) = self.get(&url).await? else {
+            trace!("Remote cache has no entry for {}", key);
+            return Ok(None);
+        };
+        if let Some(len) = response.content_length() {
+            bar.set_length(len);
+        }
+
+        // The archive is downloaded into the staging directory (so that it is on the same file
+        // system) and only unpacked if its hash matches, it is removed in any case
+        let archive_path = staging_store
+            .read()
+            .await
+            .root_path()
+            .as_ref()
+            .join(format!(".remote-cache-{}.tar", uuid::Uuid::new_v4()));
+        let result = async {
+            let digest = download_to_file(response, &archive_path, bar)
+                .await
+                .with_context(|| anyhow!("Fetching '{}' from remote cache", url))?;
+            if digest != expected_digest {
+                return Err(anyhow!(
+                    "Hash of '{}' does not match: expected {}, got {}",
+                    url,
+                    expected_digest,
+                    digest
+                ));
+            }
+
+            staging_store
+                .write()
+                .await
+                .write_files_from_tar_file(&archive_path, arch)
+                .with_context(|| anyhow!("Writing artifacts from '{}' to staging store", url))
+        }
+        .await;
+
+        if let Err(e) = tokio::fs::remove_file(&archive_path).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!("Failed to remove {}: {}", archive_path.display(), e);
+            }
+        }
+        let artifacts = result?;
+
+        if artifacts.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(artifacts))
+        }
+    }
+
+    /// Upload the passed artifacts from the staging store for the passed cache key
+    ///
+    /// The archive is streamed while it is built, so the artifacts are not read into memory.
+    /// Its hash is uploaded afterwards.
+    /// Does nothing if uploading is disabled in the configuration.
+    pub async fn store(
+        &self,
+        key: &CacheKey,
+        artifacts: &[ArtifactPath],
+        staging_store: Arc<RwLock<StagingStore>>,
+        arch: Option<&str>,
+    ) -> Result<()> {
+        if !self.upload || artifacts.is_empty() {
+            return Ok(());
+        }
+
+        // (path in the staging store, name in the archive)
+        let files = {
+            let staging_read = staging_store.read().await;
+            artifacts
+                .iter()
+                .map(|artifact| {
+                    let path = staging_read
+                        .root_path()
+                        .join(artifact)?
+                        .ok_or_else(|| anyhow!("Artifact not in staging store: {:?}", artifact))?
+                        .joined();
+
+                    // The entries are relative to the directory of the architecture, `fetch()`
+                    // puts them there again
+                    let name = artifact
+                        .as_ref()
+                        .strip_prefix(arch.unwrap_or_default())
+                        .unwrap_or(artifact.as_ref())
+                        .to_path_buf();
+                    Ok((path, name))
+                })
+                .collect::<Result<Vec<(PathBuf, PathBuf)>>>()?
+        };
+
+        // The archive is built in a blocking task and sent to the request body in chunks
+        let (sender, receiver) = tokio::sync::mpsc::channel::<std::io::Result<Vec<u8>>>(4);
+        let archiver = tokio::task::spawn_blocking(move || {
+            let result = write_archive(&files, &sender);
+            if let Err(e) = result.as_ref() {
+                // Abort the upload, the archive is incomplete
+                let _ = sender.blocking_send(Err(std::io::Error::other(format!("{e:?}"))));
+            }
+            result
+        });
+        let body =
+            reqwest::Body::wrap_stream(tokio_stream::wrappers::ReceiverStream::new(receiver));
+
+        let url = self.archive_url(key);
+        trace!("Uploading archive to {}", url);
+        let upload = self
+            .request(reqwest::Method::PUT, &url)
+            .body(body)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .with_context(|| anyhow!("Uploading artifacts to '{}'", url));
+        // A failed upload makes building the archive fail as well (the channel is closed), so the
+        // error of the upload is returned first
+        let digest = archiver.await.context("Building the archive panicked")?;
+        upload?;
+        let digest = digest.context("Building the archive")?;
+
+        let digest_url = self.digest_url(key);
+        trace!("Uploading hash {} to {}", digest, digest_url);
+        self.request(reqwest::Method::PUT, &digest_url)
+            .body(format!("{digest}  {key}.tar\n"))
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map(|_| ())
+            .with_context(|| anyhow!("Uploading hash to '{}'", digest_url))
+    }
+}
+
+/// Write the body of `response` to a new file at `path`, reporting the received bytes to `bar`
+///
+/// Returns the SHA-256 hash of the body.
+async fn download_to_file(
+    response: reqwest::Response,
+    path: &Path,
+    bar: &ProgressBar,
+) -> Result<String> {
+    let mut file = tokio::fs::File::create(path)
+        .await
+        .with_context(|| anyhow!("Creating {}", path.display()))?;
+    let mut hasher = sha2::Sha256::new();
+    let mut body = response.bytes_stream();
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.context("Reading response from remote cache")?;
+        hasher.update(&chunk);
+        file.write_all(&chunk)
+            .await
+            .with_context(|| anyhow!("Writing {}", path.display()))?;
+        bar.inc(chunk.len() as u64);
+    }
+    file.flush()
+        .await
+        .with_context(|| anyhow!("Writing {}", path.display()))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Write a TAR archive of the passed files (path, name in the archive) to `sender` in chunks
+///
+/// Returns the SHA-256 hash of the archive.
+fn write_archive(
+    files: &[(PathBuf, PathBuf)],
+    sender: &tokio::sync::mpsc::Sender<std::io::Result<Vec<u8>>>,
+) -> Result<String> {
+    let writer = std::io::BufWriter::with_capacity(
+        CHUNK_SIZE,
+        ChannelWriter {
+            sender: sender.clone(),
+            hasher: sha2::Sha256::new(),
+        },
+    );
+    let mut builder = tar::Builder::new(writer);
+    for (path, name) in files {
+        builder
+            .append_path_with_name(path, name)
+            .with_context(|| anyhow!("Adding {} to archive", path.display()))?;
+    }
+
+    let writer = builder
+        .into_inner()
+        .context("Finishing archive")?
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context("Finishing archive")?;
+    Ok(format!("{:x}", writer.hasher.finalize()))
+}
+
+/// A writer that sends the written data to a channel and hashes it
+struct ChannelWriter {
+    sender: tokio::sync::mpsc::Sender<std::io::Result<Vec<u8>>>,
+    hasher: sha2::Sha256,
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.sender
+            .blocking_send(Ok(buf.to_vec()))
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "Upload aborted"))?;
+        self.hasher.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_digest_matches_sent_bytes() -> Result<()> {
+        let dir =
+            std::env::temp_dir().join(format!("butido-test-remote-cache-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        let file = dir.join("foo-1.0.tar.gz");
+        std::fs::write(&file, b"foo")?;
+
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(4);
+        let files = vec![(file, PathBuf::from("foo-1.0.tar.gz"))];
+        let writer = std::thread::spawn(move || write_archive(&files, &sender));
+        let mut sent = Vec::new();
+        while let Some(chunk) = receiver.blocking_recv() {
+            sent.extend(chunk?);
+        }
+        let digest = writer.join().unwrap();
+        std::fs::remove_dir_all(&dir)?;
+
+        assert_eq!(digest?, format!("{:x}", sha2::Sha256::digest(&sent)));
+        let mut archive = tar::Archive::new(&sent[..]);
+        let names = archive
+            .entries()?
+            .map(|entry| Ok(entry?.path()?.to_path_buf()))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(names, [PathBuf::from("foo-1.0.tar.gz")]);
+        Ok(())
+    }
+}
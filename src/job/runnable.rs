@@ -12,11 +12,14 @@ use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Result;
 use getset::Getters;
+use getset::Setters;
+use itertools::Itertools;
 use tracing::{debug, trace};
 use uuid::Uuid;
 
 use crate::config::Configuration;
 use crate::filestore::ArtifactPath;
+use crate::job::CacheKey;
 use crate::job::Job;
 use crate::job::JobResource;
 use crate::package::Package;
@@ -25,10 +28,11 @@ use crate::package::ScriptBuilder;
 use crate::source::SourceCache;
 use crate::source::SourceEntry;
 use crate::util::docker::ImageName;
+use crate::util::docker::Mount;
 use crate::util::EnvironmentVariableName;
 
 /// A job configuration that can be run. All inputs are clear here.
-#[derive(Debug, Getters)]
+#[derive(Clone, Debug, Getters, Setters)]
 pub struct RunnableJob {
     #[getset(get = "pub")]
     uuid: Uuid,
@@ -42,11 +46,27 @@ pub struct RunnableJob {
     #[getset(get = "pub")]
     source_cache: SourceCache,
 
+    /// The environment variables with the paths of the sources in the container (see
+    /// `SourceEntry::environment_variable()`), they are not recorded in the database
+    source_env: Vec<(EnvironmentVariableName, String)>,
+
     #[getset(get = "pub")]
     script: Script,
 
     #[getset(get = "pub")]
     resources: Vec<JobResource>,
+
+    /// The architecture the job builds for (see `Job::arch`)
+    #[getset(get = "pub")]
+    arch: Option<String>,
+
+    /// The host paths that are mounted into the container (from the configuration and package)
+    #[getset(get = "pub")]
+    mounts: Vec<Mount>,
+
+    /// The build cache key of the job, if it was computed
+    #[getset(get = "pub", set = "pub")]
+    cache_key: Option<CacheKey>,
 }
 
 impl RunnableJob {
@@ -98,6 +118,42 @@ impl RunnableJob {
             debug!("Environment checking disabled");
         }
 
+        let mounts = config
+            .containers()
+            .mounts()
+            .iter()
+            .chain(job.package().mounts().iter().flatten())
+            .cloned()
+            .collect::<Vec<_>>();
+        for mount in mounts.iter() {
+            mount
+                .check_allowed(config.containers().allowed_mounts())
+                .with_context(|| {
+                    anyhow!(
+                        "Checking mounts of package {} {}",
+                        job.package().name(),
+                        job.package().version()
+                    )
+                })?;
+        }
+
+        // The paths of the sources in the container are passed to the script, these variables
+        // are set by butido and therefore not checked against the allowed variables
+        let mut source_env = source_cache
+            .sources_for(job.package())
+            .iter()
+            .map(SourceEntry::environment_variable)
+            .collect::<Result<Vec<_>>>()?;
+        source_env.sort();
+        if let Some(name) = source_env.iter().map(|(name, _)| name).duplicates().next() {
+            return Err(anyhow!(
+                "Multiple sources of package {} {} have the environment variable {}, rename one of them",
+                job.package().name(),
+                job.package().version(),
+                name
+            ));
+        }
+
         let resources = dependencies
             .into_iter()
             .map(JobResource::from)
@@ -123,9 +179,13 @@ impl RunnableJob {
             package: job.package().clone(),
             image: job.image().clone(),
             resources,
+            arch: job.arch().clone(),
+            mounts,
             source_cache: source_cache.clone(),
+            source_env,
 
             script,
+            cache_key: None,
         })
     }
 
@@ -134,13 +194,17 @@ impl RunnableJob {
     }
 
     pub fn environment(&self) -> impl Iterator<Item = (&EnvironmentVariableName, &String)> {
-        self.resources.iter().filter_map(|r| r.env()).chain({
-            self.package()
-                .environment()
-                .as_ref()
-                .map(|hm| hm.iter())
-                .into_iter()
-                .flatten()
-        })
+        self.resources
+            .iter()
+            .filter_map(|r| r.env())
+            .chain({
+                self.package()
+                    .environment()
+                    .as_ref()
+                    .map(|hm| hm.iter())
+                    .into_iter()
+                    .flatten()
+            })
+            .chain(self.source_env.iter().map(|(k, v)| (k, v)))
     }
 }
@@ -0,0 +1,286 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+use anyhow::Result;
+
+use crate::config::LogLimits;
+use crate::config::LogTruncation;
+use crate::log::LogItem;
+
+/// Decode a line of the output of a job, without the line terminator
+///
+/// Builds may print anything, so invalid UTF-8 is replaced with U+FFFD instead of failing the
+/// job. NUL bytes are replaced as well, because PostgreSQL cannot store them in a text column.
+pub fn decode_log_line(line: Vec<u8>) -> String {
+    let line = line.strip_suffix(b"\n").unwrap_or(&line);
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    String::from_utf8_lossy(line).replace('\0', "\u{FFFD}")
+}
+
+/// Remove ANSI escape sequences (e.g. the colors of compiler output) from a log line
+///
+/// Logs are stored with the escape sequences, they are only removed where they get in the way,
+/// e.g. when looking for markers or matching patterns.
+pub fn strip_ansi_escapes(line: &str) -> Cow<'_, str> {
+    if !line.contains('\x1b') {
+        return Cow::Borrowed(line);
+    }
+
+    let mut stripped = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            stripped.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI sequences (e.g. "\x1b[1;31m") end with a byte in the range '@'..='~'
+            Some('[') => {
+                chars.by_ref().find(|c| ('@'..='~').contains(c));
+            }
+            // OSC sequences (e.g. hyperlinks) end with BEL or ST ("\x1b\\")
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            // Other sequences (e.g. "\x1b(B") may have intermediate characters before the final
+            // character
+            Some(c) if (' '..='/').contains(&c) => {
+                chars.by_ref().find(|c| ('0'..='~').contains(c));
+            }
+            _ => {}
+        }
+    }
+    Cow::Owned(stripped)
+}
+
+/// Truncate a log line that is longer than `max_length` bytes
+///
+/// A truncated line ends with a note about the number of bytes that were removed.
+pub fn truncate_line(mut line: String, max_length: usize) -> String {
+    if line.len() <= max_length {
+        return line;
+    }
+
+    let mut end = max_length;
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    let truncated = line.len() - end;
+    line.truncate(end);
+    line.push_str(&format!(" [... {truncated} bytes truncated by butido]"));
+    line
+}
+
+/// Collects the log items of a job while limiting the size of the log
+///
+/// If the size of the log lines exceeds the limit, the lines in the middle, at the beginning or
+/// at the end of the log are dropped, depending on the truncation policy, and a note about the
+/// dropped lines is inserted instead. Items that are not log lines (progress, phase, state, ...)
+/// are never dropped.
+pub struct LogBuffer {
+    head: Vec<LogItem>,
+    head_size: usize,
+    head_budget: usize,
+
+    tail: VecDeque<LogItem>,
+    tail_size: usize,
+    tail_budget: usize,
+
+    /// Items that were not dropped from the omitted part of the log
+    omitted_items: Vec<LogItem>,
+    omitted_lines: usize,
+    omitted_bytes: usize,
+}
+
+impl LogBuffer {
+    pub fn new(limits: &LogLimits) -> Self {
+        let max_size = limits.max_size();
+        let (head_budget, tail_budget) = match limits.truncation() {
+            LogTruncation::Head => (max_size, 0),
+            LogTruncation::Tail => (0, max_size),
+            LogTruncation::HeadAndTail => (max_size / 2, max_size - max_size / 2),
+        };
+
+        LogBuffer {
+            head: Vec::new(),
+            head_size: 0,
+            head_budget,
+            tail: VecDeque::new(),
+            tail_size: 0,
+            tail_budget,
+            omitted_items: Vec::new(),
+            omitted_lines: 0,
+            omitted_bytes: 0,
+        }
+    }
+
+    pub fn push(&mut self, item: LogItem) {
+        let size = Self::size_of(&item);
+        let head_open = self.tail.is_empty() && self.omitted_lines == 0;
+        if head_open && self.head_size + size <= self.head_budget {
+            self.head_size += size;
+            self.head.push(item);
+            return;
+        }
+
+        self.tail_size += size;
+        self.tail.push_back(item);
+        while self.tail_size > self.tail_budget {
+            match self.tail.pop_front() {
+                Some(LogItem::Line(line)) => {
+                    self.tail_size -= line.len() + 1;
+                    self.omitted_lines += 1;
+                    self.omitted_bytes += line.len() + 1;
+                }
+                Some(other) => self.omitted_items.push(other),
+                None => break,
+            }
+        }
+    }
+
+    /// The raw log text
+    pub fn into_text(self) -> Result<String> {
+        let note = (self.omitted_lines > 0).then(|| {
+            Ok(format!(
+                "[... {} lines ({} bytes) of the log truncated by butido]",
+                self.omitted_lines, self.omitted_bytes
+            ))
+        });
+
+        self.head
+            .iter()
+            .map(LogItem::raw)
+            .chain(note)
+            .chain(self.omitted_items.iter().map(LogItem::raw))
+            .chain(self.tail.iter().map(LogItem::raw))
+            .collect::<Result<Vec<String>>>()
+            .map(|lines| lines.join("\n"))
+    }
+
+    /// Only log lines count towards the size limit
+    fn size_of(item: &LogItem) -> usize {
+        match item {
+            LogItem::Line(line) => line.len() + 1,
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(max_size: usize, truncation: &str) -> LogLimits {
+        toml::from_str(&format!(
+            "max_size = {max_size}\ntruncation = \"{truncation}\""
+        ))
+        .unwrap()
+    }
+
+    fn line(s: &str) -> LogItem {
+        LogItem::Line(s.as_bytes().to_vec())
+    }
+
+    fn buffer_with(limits: &LogLimits) -> LogBuffer {
+        let mut buffer = LogBuffer::new(limits);
+        buffer.push(LogItem::CurrentPhase(String::from("build")));
+        for i in 0..6 {
+            buffer.push(line(&format!("line {i}")));
+        }
+        buffer.push(LogItem::State(Ok(())));
+        buffer
+    }
+
+    #[test]
+    fn test_decode_log_line() {
+        assert_eq!(decode_log_line(b"plain line\n".to_vec()), "plain line");
+        assert_eq!(
+            decode_log_line(b"windows line\r\n".to_vec()),
+            "windows line"
+        );
+        assert_eq!(
+            decode_log_line(b"garbage: \xff\xfe\x00 \xe2\x82 end".to_vec()),
+            "garbage: \u{FFFD}\u{FFFD}\u{FFFD} \u{FFFD} end"
+        );
+        assert_eq!(decode_log_line("aä€".as_bytes().to_vec()), "aä€");
+    }
+
+    #[test]
+    fn test_strip_ansi_escapes() {
+        assert!(matches!(
+            strip_ansi_escapes("no colors"),
+            Cow::Borrowed("no colors")
+        ));
+        assert_eq!(
+            strip_ansi_escapes("\x1b[1mmain.c:1:\x1b[0m \x1b[01;31merror:\x1b[m expected ';'"),
+            "main.c:1: error: expected ';'"
+        );
+        assert_eq!(
+            strip_ansi_escapes("\x1b]8;;https://gcc.gnu.org\x07link\x1b]8;;\x1b\\ \x1b(Bdone"),
+            "link done"
+        );
+    }
+
+    #[test]
+    fn test_truncate_line() {
+        assert_eq!(truncate_line(String::from("short"), 10), "short");
+        assert_eq!(
+            truncate_line(String::from("0123456789abc"), 10),
+            "0123456789 [... 3 bytes truncated by butido]"
+        );
+        // Never split a character
+        assert_eq!(
+            truncate_line(String::from("aä"), 2),
+            "a [... 2 bytes truncated by butido]"
+        );
+    }
+
+    #[test]
+    fn test_log_within_limits_is_not_truncated() {
+        let text = buffer_with(&limits(1024, "head-and-tail"))
+            .into_text()
+            .unwrap();
+        assert_eq!(
+            text,
+            "#BUTIDO:PHASE:build\nline 0\nline 1\nline 2\nline 3\nline 4\nline 5\n#BUTIDO:STATE:OK"
+        );
+    }
+
+    #[test]
+    fn test_truncation_policies() {
+        // Every line is 7 bytes (including the newline)
+        let text = buffer_with(&limits(14, "head")).into_text().unwrap();
+        assert_eq!(
+            text,
+            "#BUTIDO:PHASE:build\nline 0\nline 1\n[... 4 lines (28 bytes) of the log truncated by butido]\n#BUTIDO:STATE:OK"
+        );
+
+        let text = buffer_with(&limits(14, "tail")).into_text().unwrap();
+        assert_eq!(
+            text,
+            "#BUTIDO:PHASE:build\n[... 4 lines (28 bytes) of the log truncated by butido]\nline 4\nline 5\n#BUTIDO:STATE:OK"
+        );
+
+        let text = buffer_with(&limits(14, "head-and-tail"))
+            .into_text()
+            .unwrap();
+        assert_eq!(
+            text,
+            "#BUTIDO:PHASE:build\nline 0\n[... 4 lines (28 bytes) of the log truncated by butido]\nline 5\n#BUTIDO:STATE:OK"
+        );
+    }
+}
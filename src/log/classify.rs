@@ -0,0 +1,95 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use regex::RegexSet;
+
+use crate::config::FailureClassifier;
+use crate::log::strip_ansi_escapes;
+
+/// The failure class of jobs that failed because of an error of the Docker daemon (e.g. the
+/// container could not be created or the connection to the endpoint broke)
+pub const DOCKER_FAILURE_CLASS: &str = "docker";
+
+/// Classifies the failures of jobs based on their logs (e.g. "oom" or "network")
+///
+/// This makes it possible to distinguish infrastructure failures from failures of the build
+/// itself.
+#[derive(Debug)]
+pub struct FailureClassifiers(Vec<(String, RegexSet)>);
+
+impl FailureClassifiers {
+    pub fn new(classifiers: &[FailureClassifier]) -> Result<Self> {
+        classifiers
+            .iter()
+            .map(|classifier| {
+                RegexSet::new(classifier.patterns())
+                    .with_context(|| {
+                        anyhow!(
+                            "Compiling the patterns of failure class {}",
+                            classifier.name()
+                        )
+                    })
+                    .map(|set| (classifier.name().clone(), set))
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(FailureClassifiers)
+    }
+
+    /// The name of the first class with a pattern that matches a line of the log, if any
+    pub fn classify(&self, log: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(_, patterns)| {
+                log.lines()
+                    .any(|line| patterns.is_match(&strip_ansi_escapes(line)))
+            })
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::default_failure_classifiers;
+
+    #[test]
+    fn test_default_classifiers() {
+        let classifiers = FailureClassifiers::new(&default_failure_classifiers()).unwrap();
+
+        let log = indoc::indoc! {"
+            #BUTIDO:PHASE:build
+            cc1: out of memory allocating 65536 bytes
+            make: *** [Makefile:12: all] Error 1
+            #BUTIDO:STATE:ERR:make failed
+        "};
+        assert_eq!(classifiers.classify(log), Some("oom"));
+
+        let log = indoc::indoc! {"
+            curl: (6) Could not resolve host: example.com
+            #BUTIDO:STATE:ERR:download failed
+        "};
+        assert_eq!(classifiers.classify(log), Some("network"));
+
+        let log = indoc::indoc! {"
+            FAIL: test_parse
+            make: *** [Makefile:30: check] Error 1
+        "};
+        assert_eq!(classifiers.classify(log), Some("test-failure"));
+
+        let log = indoc::indoc! {"
+            main.c:2: error: expected ';'
+            #BUTIDO:STATE:ERR:make failed
+        "};
+        assert_eq!(classifiers.classify(log), None);
+    }
+}
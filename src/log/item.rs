@@ -8,7 +8,6 @@
 // SPDX-License-Identifier: EPL-2.0
 //
 
-use anyhow::Error;
 use anyhow::Result;
 use colored::Colorize;
 
@@ -23,6 +22,15 @@ pub enum LogItem {
     /// The name of the current phase the process is in
     CurrentPhase(String),
 
+    /// The path of an artifact the process produced
+    Artifact(String),
+
+    /// The number of warnings the process encountered (since the last report)
+    Warnings(usize),
+
+    /// The path of a directory in the container that should be kept as checkpoint
+    Checkpoint(String),
+
     /// The end-state of the process
     /// Either Ok or Error
     State(Result<(), String>),
@@ -31,9 +39,12 @@ pub enum LogItem {
 impl LogItem {
     pub fn display(&self) -> Result<Display> {
         match self {
-            LogItem::Line(s) => Ok(Display(String::from_utf8(s.to_vec())?.normal())),
+            LogItem::Line(s) => Ok(Display(String::from_utf8_lossy(s).normal())),
             LogItem::Progress(u) => Ok(Display(format!("#BUTIDO:PROGRESS:{u}").cyan())),
             LogItem::CurrentPhase(p) => Ok(Display(format!("#BUTIDO:PHASE:{p}").cyan())),
+            LogItem::Artifact(p) => Ok(Display(format!("#BUTIDO:ARTIFACT:{p}").cyan())),
+            LogItem::Warnings(u) => Ok(Display(format!("#BUTIDO:WARNINGS:{u}").yellow())),
+            LogItem::Checkpoint(p) => Ok(Display(format!("#BUTIDO:CHECKPOINT:{p}").cyan())),
             LogItem::State(Ok(())) => Ok(Display("#BUTIDO:STATE:OK".to_string().green())),
             LogItem::State(Err(s)) => Ok(Display(format!("#BUTIDO:STATE:ERR:{s}").red())),
         }
@@ -41,9 +52,12 @@ impl LogItem {
 
     pub fn raw(&self) -> Result<String> {
         match self {
-            LogItem::Line(s) => String::from_utf8(s.to_vec()).map_err(Error::from),
+            LogItem::Line(s) => Ok(String::from_utf8_lossy(s).into_owned()),
             LogItem::Progress(u) => Ok(format!("#BUTIDO:PROGRESS:{u}")),
             LogItem::CurrentPhase(p) => Ok(format!("#BUTIDO:PHASE:{p}")),
+            LogItem::Artifact(p) => Ok(format!("#BUTIDO:ARTIFACT:{p}")),
+            LogItem::Warnings(u) => Ok(format!("#BUTIDO:WARNINGS:{u}")),
+            LogItem::Checkpoint(p) => Ok(format!("#BUTIDO:CHECKPOINT:{p}")),
             LogItem::State(Ok(())) => Ok("#BUTIDO:STATE:OK".to_string()),
             LogItem::State(Err(s)) => Ok(format!("#BUTIDO:STATE:ERR:{s}")),
         }
@@ -14,4 +14,10 @@ pub use parser::*;
 mod item;
 pub use item::*;
 
+mod buffer;
+pub use buffer::*;
+
+mod classify;
+pub use classify::*;
+
 mod util;
@@ -8,6 +8,7 @@
 // SPDX-License-Identifier: EPL-2.0
 //
 
+use std::borrow::Cow;
 use std::result::Result as RResult;
 use std::str::FromStr;
 
@@ -18,8 +19,11 @@ use futures::Stream;
 use futures::StreamExt;
 use futures::TryStreamExt;
 use pom::parser::Parser as PomParser;
+use regex::RegexSet;
 use shiplift::tty::TtyChunk;
 
+use crate::log::decode_log_line;
+use crate::log::strip_ansi_escapes;
 use crate::log::util::*;
 use crate::log::LogItem;
 
@@ -29,11 +33,22 @@ pub fn buffer_stream_to_line_stream<S>(stream: S) -> impl Stream<Item = IoResult
 where
     S: Stream<Item = shiplift::Result<TtyChunk>> + std::marker::Unpin,
 {
-    stream
+    let reader = stream
         .map(|r| r.map(TtyChunkBuf::from))
         .map_err(|e| futures::io::Error::new(futures::io::ErrorKind::Other, e))
-        .into_async_read()
-        .lines()
+        .into_async_read();
+
+    // Not `lines()`, which fails on the first line that is not valid UTF-8
+    futures::stream::unfold(Some(reader), |reader| async move {
+        let mut reader = reader?;
+        let mut line = Vec::new();
+        match reader.read_until(b'\n', &mut line).await {
+            Ok(0) => None,
+            Ok(_) => Some((Ok(decode_log_line(line)), Some(reader))),
+            // The stream ends after an error
+            Err(e) => Some((Err(e), None)),
+        }
+    })
 }
 
 pub struct ParsedLog(Vec<LogItem>);
@@ -49,6 +64,9 @@ impl std::fmt::Debug for ParsedLog {
                 }
                 LogItem::Progress(u) => writeln!(f, "[{i}] Progress({u})")?,
                 LogItem::CurrentPhase(s) => writeln!(f, "[{i}] Phase({s})")?,
+                LogItem::Artifact(s) => writeln!(f, "[{i}] Artifact({s})")?,
+                LogItem::Warnings(u) => writeln!(f, "[{i}] Warnings({u})")?,
+                LogItem::Checkpoint(s) => writeln!(f, "[{i}] Checkpoint({s})")?,
                 LogItem::State(Ok(_)) => writeln!(f, "[{i}] State::OK")?,
                 LogItem::State(Err(_)) => writeln!(f, "[{i}] State::Err")?,
             }
@@ -62,14 +80,96 @@ impl FromStr for ParsedLog {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self> {
-        let p = parser();
-        s.lines()
-            .map(|line| p.parse(line.as_bytes()).map_err(Error::from))
-            .collect::<Result<Vec<_>>>()
-            .map(ParsedLog)
+        let mut parser = LogParser::default();
+        let mut items = parser.feed(s.as_bytes())?;
+        items.extend(parser.finish()?);
+        Ok(ParsedLog(items))
     }
 }
 
+/// A parser for logs that arrive in chunks (e.g. while a job runs)
+///
+/// The bytes of the log are fed in arbitrary chunks, the items of all lines that are complete
+/// are returned right away. Lines are split like `str::lines()` does it, so the items are the
+/// same as the ones of `ParsedLog::from_str()` for the whole log.
+pub struct LogParser {
+    parser: PomParser<'static, u8, LogItem>,
+
+    /// The beginning of a line whose end was not fed yet
+    partial_line: Vec<u8>,
+
+    /// The result of the job according to the last state marker that was parsed so far
+    result: JobResult,
+}
+
+impl Default for LogParser {
+    fn default() -> Self {
+        LogParser {
+            parser: parser(),
+            partial_line: Vec::new(),
+            result: JobResult::Unknown,
+        }
+    }
+}
+
+impl LogParser {
+    /// Feed the next chunk of the log and get the items of the lines that were completed by it
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<Vec<LogItem>> {
+        let mut items = Vec::new();
+        let mut rest = chunk;
+        while let Some(pos) = rest.iter().position(|b| *b == b'\n') {
+            let item = if self.partial_line.is_empty() {
+                self.parse_line(&rest[..pos])?
+            } else {
+                let mut line = std::mem::take(&mut self.partial_line);
+                line.extend_from_slice(&rest[..pos]);
+                self.parse_line(&line)?
+            };
+            items.push(item);
+            rest = &rest[pos + 1..];
+        }
+        self.partial_line.extend_from_slice(rest);
+        Ok(items)
+    }
+
+    /// Parse the last line of the log if it is not terminated by a newline
+    ///
+    /// Must be called after the last chunk was fed.
+    pub fn finish(&mut self) -> Result<Option<LogItem>> {
+        if self.partial_line.is_empty() {
+            return Ok(None);
+        }
+        let line = std::mem::take(&mut self.partial_line);
+        self.parse_line(&line).map(Some)
+    }
+
+    /// The result of the job, as far as it is known from the log that was fed so far
+    pub fn result(&self) -> &JobResult {
+        &self.result
+    }
+
+    fn parse_line(&mut self, line: &[u8]) -> Result<LogItem> {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let item = parse_log_line(&self.parser, line)?;
+        match item {
+            LogItem::State(Ok(_)) => self.result = JobResult::Success,
+            LogItem::State(Err(_)) => self.result = JobResult::Errored,
+            _ => {}
+        }
+        Ok(item)
+    }
+}
+
+/// The structured results that a job reported via markers in its log
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct LogMarkers {
+    /// The paths of the artifacts that were reported via "#BUTIDO:ARTIFACT:<path>"
+    pub artifacts: Vec<String>,
+
+    /// The sum of the warnings that were reported via "#BUTIDO:WARNINGS:<n>", if any were reported
+    pub warnings: Option<usize>,
+}
+
 #[derive(Debug, Eq, PartialEq)]
 pub enum JobResult {
     Success,
@@ -101,19 +201,98 @@ impl ParsedLog {
             .unwrap_or(JobResult::Unknown)
     }
 
+    pub fn markers(&self) -> LogMarkers {
+        self.0
+            .iter()
+            .fold(LogMarkers::default(), |mut markers, item| {
+                match item {
+                    LogItem::Artifact(path) => markers.artifacts.push(path.clone()),
+                    LogItem::Warnings(n) => {
+                        markers.warnings = Some(markers.warnings.unwrap_or(0) + n)
+                    }
+                    _ => {}
+                }
+                markers
+            })
+    }
+
+    /// Select items of the log
+    ///
+    /// If a phase is passed, only the items that were logged in this phase (as announced with
+    /// "#BUTIDO:PHASE") are kept. If patterns are passed, only the log lines that match one of
+    /// them are kept (the other items are kept regardless of the patterns).
+    pub fn select(self, phase: Option<&str>, patterns: Option<&RegexSet>) -> Vec<LogItem> {
+        let mut current_phase = None;
+        self.0
+            .into_iter()
+            .filter(|item| {
+                if let LogItem::CurrentPhase(p) = item {
+                    current_phase = Some(p.clone());
+                }
+
+                let in_phase = phase
+                    .map(|phase| current_phase.as_deref() == Some(phase))
+                    .unwrap_or(true);
+                let matches = match (item, patterns) {
+                    (LogItem::Line(line), Some(patterns)) => {
+                        patterns.is_match(&strip_ansi_escapes(&String::from_utf8_lossy(line)))
+                    }
+                    _ => true,
+                };
+                in_phase && matches
+            })
+            .collect()
+    }
+
     pub fn into_iter(self) -> impl Iterator<Item = LogItem> {
         self.0.into_iter()
     }
 }
 
+/// The last `n` log lines of the items, with the markers between and after them
+///
+/// Only log lines count towards `n`, the markers directly before the first of the lines (e.g.
+/// the phase it was logged in) are kept as well.
+pub fn tail(mut items: Vec<LogItem>, n: usize) -> Vec<LogItem> {
+    let mut lines = 0;
+    let start = items
+        .iter()
+        .rposition(|item| {
+            if matches!(item, LogItem::Line(_)) {
+                lines += 1;
+            }
+            lines > n
+        })
+        .map(|position| position + 1)
+        .unwrap_or(0);
+    items.split_off(start)
+}
+
+/// Parse a line of a log with `parser`
+///
+/// ANSI escape sequences are ignored when looking for markers (the script may print them in
+/// color), lines that are not markers are kept as they are, including the escape sequences.
+pub fn parse_log_line(parser: &PomParser<'_, u8, LogItem>, line: &[u8]) -> Result<LogItem> {
+    let text = String::from_utf8_lossy(line);
+    match strip_ansi_escapes(&text) {
+        Cow::Borrowed(_) => parser.parse(line).map_err(Error::from),
+        Cow::Owned(stripped) => match parser.parse(stripped.as_bytes())? {
+            LogItem::Line(_) => Ok(LogItem::Line(line.to_vec())),
+            marker => Ok(marker),
+        },
+    }
+}
+
 pub fn parser<'a>() -> PomParser<'a, u8, LogItem> {
     use pom::parser::*;
 
-    let number = one_of(b"0123456789")
-        .repeat(1..)
-        .collect()
-        .convert(|b| String::from_utf8(b.to_vec()))
-        .convert(|s| usize::from_str(&s));
+    fn number<'a>() -> PomParser<'a, u8, usize> {
+        one_of(b"0123456789")
+            .repeat(1..)
+            .collect()
+            .convert(|b| String::from_utf8(b.to_vec()))
+            .convert(|s| usize::from_str(&s))
+    }
 
     fn ignored<'a>() -> PomParser<'a, u8, Vec<u8>> {
         none_of(b"\n").repeat(0..)
@@ -135,8 +314,11 @@ pub fn parser<'a>() -> PomParser<'a, u8, LogItem> {
     }
 
     (seq(b"#BUTIDO:")
-        * ((seq(b"PROGRESS:") * number.map(LogItem::Progress))
+        * ((seq(b"PROGRESS:") * number().map(LogItem::Progress))
             | (seq(b"PHASE:") * string().map(LogItem::CurrentPhase))
+            | (seq(b"ARTIFACT:") * string().map(LogItem::Artifact))
+            | (seq(b"WARNINGS:") * number().map(LogItem::Warnings))
+            | (seq(b"CHECKPOINT:") * string().map(LogItem::Checkpoint))
             | ((seq(b"STATE:ERR:") * string().map(|s| LogItem::State(Err(s))))
                 | seq(b"STATE:OK").map(|_| LogItem::State(Ok(()))))))
         | ignored().map(LogItem::Line)
@@ -462,4 +644,212 @@ mod tests {
         let log = ParsedLog::from_str(buffer).unwrap();
         assert_eq!(log.is_successfull(), JobResult::Errored);
     }
+
+    #[test]
+    fn test_log_parser_chunks() {
+        let buffer: &'static str = indoc::indoc! {"
+            #BUTIDO:PHASE:build
+            Some log line\r
+            #BUTIDO:STATE:ERR:make failed
+            #BUTIDO:STATE:OK
+            no newline at the end"};
+
+        // Any split of the log yields the same items as parsing it at once
+        let expected = ParsedLog::from_str(buffer)
+            .unwrap()
+            .into_iter()
+            .collect::<Vec<_>>();
+        assert_eq!(expected.len(), 5);
+        for chunk_size in [1, 3, 7, buffer.len()] {
+            let mut parser = LogParser::default();
+            let mut items = Vec::new();
+            for chunk in buffer.as_bytes().chunks(chunk_size) {
+                items.extend(parser.feed(chunk).unwrap());
+            }
+            items.extend(parser.finish().unwrap());
+            assert_eq!(items, expected, "chunk size {chunk_size}");
+            assert_eq!(*parser.result(), JobResult::Success);
+        }
+    }
+
+    #[test]
+    fn test_log_parser_result() {
+        let mut parser = LogParser::default();
+        assert_eq!(
+            parser
+                .feed(b"#BUTIDO:PHASE:build\n#BUTIDO:STA")
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(*parser.result(), JobResult::Unknown);
+
+        let items = parser.feed(b"TE:ERR:make failed\n").unwrap();
+        assert_eq!(
+            items,
+            vec![LogItem::State(Err(String::from("make failed")))]
+        );
+        assert_eq!(*parser.result(), JobResult::Errored);
+        assert!(parser.finish().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_line_stream_with_invalid_utf8() {
+        let chunks = vec![
+            Ok(TtyChunk::StdOut(
+                b"#BUTIDO:PHASE:build\nbinary: \xff".to_vec(),
+            )),
+            Ok(TtyChunk::StdErr(b"\x00\xc3 garbage\r\n".to_vec())),
+            Ok(TtyChunk::StdOut(b"#BUTIDO:STATE:OK".to_vec())),
+        ];
+        let lines = buffer_stream_to_line_stream(futures::stream::iter(chunks))
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(
+            lines,
+            [
+                "#BUTIDO:PHASE:build",
+                "binary: \u{FFFD}\u{FFFD}\u{FFFD} garbage",
+                "#BUTIDO:STATE:OK",
+            ]
+        );
+
+        let log = ParsedLog::from_str(&lines.join("\n")).unwrap();
+        assert_eq!(log.is_successfull(), JobResult::Success);
+    }
+
+    #[test]
+    fn test_colored_markers() {
+        let p = parser();
+        assert_eq!(
+            parse_log_line(&p, b"\x1b[32m#BUTIDO:STATE:OK\x1b[0m").unwrap(),
+            LogItem::State(Ok(()))
+        );
+        assert_eq!(
+            parse_log_line(&p, b"\x1b[1m#BUTIDO:PHASE:build\x1b[0m").unwrap(),
+            LogItem::CurrentPhase(String::from("build"))
+        );
+        // Other lines keep their colors
+        assert_eq!(
+            parse_log_line(&p, b"\x1b[31merror:\x1b[0m failed").unwrap(),
+            LogItem::Line(b"\x1b[31merror:\x1b[0m failed".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_artifact() {
+        let s = "#BUTIDO:ARTIFACT:/outputs/foo-1.0.tar.gz";
+        let p = parser();
+        let r = p.parse(s.as_bytes());
+
+        assert!(r.is_ok(), "Not ok: {r:?}");
+        let r = r.unwrap();
+        assert_eq!(
+            r,
+            LogItem::Artifact(String::from("/outputs/foo-1.0.tar.gz"))
+        );
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let s = "#BUTIDO:CHECKPOINT:/build/foo-1.0";
+        let p = parser();
+        let r = p.parse(s.as_bytes());
+
+        assert!(r.is_ok(), "Not ok: {r:?}");
+        let r = r.unwrap();
+        assert_eq!(r, LogItem::Checkpoint(String::from("/build/foo-1.0")));
+    }
+
+    #[test]
+    fn test_markers() {
+        let buffer: &'static str = indoc::indoc! {"
+            #BUTIDO:PHASE:build
+            warning: unused variable
+            #BUTIDO:WARNINGS:1
+            #BUTIDO:PHASE:pack
+            #BUTIDO:ARTIFACT:/outputs/foo-1.0.tar.gz
+            #BUTIDO:WARNINGS:2
+            #BUTIDO:ARTIFACT:/outputs/foo-doc-1.0.tar.gz
+            #BUTIDO:STATE:OK
+        "};
+
+        let markers = ParsedLog::from_str(buffer).unwrap().markers();
+        assert_eq!(
+            markers,
+            LogMarkers {
+                artifacts: vec![
+                    String::from("/outputs/foo-1.0.tar.gz"),
+                    String::from("/outputs/foo-doc-1.0.tar.gz"),
+                ],
+                warnings: Some(3),
+            }
+        );
+
+        let markers = ParsedLog::from_str("foo bar").unwrap().markers();
+        assert_eq!(markers, LogMarkers::default());
+    }
+
+    #[test]
+    fn test_select() {
+        let buffer: &'static str = indoc::indoc! {"
+            #BUTIDO:PHASE:configure
+            checking for gcc... yes
+            configure: WARNING: unrecognized options
+            #BUTIDO:PHASE:build
+            main.c:1: warning: unused variable
+            main.c:2: error: expected ';'
+            #BUTIDO:STATE:ERR:make failed
+        "};
+
+        let patterns = RegexSet::new([r"(?i)\bwarning\b"]).unwrap();
+        let selected = ParsedLog::from_str(buffer)
+            .unwrap()
+            .select(Some("build"), Some(&patterns));
+        assert_eq!(
+            selected,
+            vec![
+                LogItem::CurrentPhase(String::from("build")),
+                LogItem::Line("main.c:1: warning: unused variable".bytes().collect()),
+                LogItem::State(Err(String::from("make failed"))),
+            ]
+        );
+
+        let selected = ParsedLog::from_str(buffer)
+            .unwrap()
+            .select(None, Some(&patterns));
+        assert_eq!(selected.len(), 5);
+    }
+
+    #[test]
+    fn test_tail_counts_only_lines() {
+        let buffer: &'static str = indoc::indoc! {"
+            #BUTIDO:PHASE:configure
+            checking for gcc... yes
+            #BUTIDO:PHASE:build
+            #BUTIDO:PROGRESS:50
+            main.c:1: warning: unused variable
+            main.c:2: error: expected ';'
+            #BUTIDO:STATE:ERR:make failed
+        "};
+        let items = || ParsedLog::from_str(buffer).unwrap().select(None, None);
+
+        assert_eq!(
+            tail(items(), 2),
+            vec![
+                LogItem::CurrentPhase(String::from("build")),
+                LogItem::Progress(50),
+                LogItem::Line("main.c:1: warning: unused variable".bytes().collect()),
+                LogItem::Line("main.c:2: error: expected ';'".bytes().collect()),
+                LogItem::State(Err(String::from("make failed"))),
+            ]
+        );
+        assert_eq!(
+            tail(items(), 0),
+            vec![LogItem::State(Err(String::from("make failed")))]
+        );
+        assert_eq!(tail(items(), 3), items());
+        assert_eq!(tail(items(), 10), items());
+    }
 }
@@ -47,6 +47,7 @@
 extern crate diesel;
 
 use std::path::PathBuf;
+use std::process::ExitCode;
 
 use anyhow::anyhow;
 use anyhow::Context;
@@ -77,6 +78,8 @@ mod util;
 
 use crate::config::*;
 use crate::repository::Repository;
+use crate::util::error::ErrorCategory;
+use crate::util::notify::LongOperation;
 use crate::util::progress::ProgressBars;
 use indoc::concatdoc;
 
@@ -89,7 +92,7 @@ pub const VERSION_LONG: &str = concatdoc! {"
 };
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     human_panic::setup_panic!(human_panic::Metadata::new(
         env!("CARGO_PKG_NAME"),
         env!("CARGO_PKG_VERSION")
@@ -101,6 +104,18 @@ async fn main() -> Result<()> {
     let app = cli::cli();
     let cli = app.get_matches();
 
+    match run(&cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            let json = cli
+                .get_one::<String>("error-format")
+                .is_some_and(|format| format == "json");
+            crate::util::error::report(&e, json)
+        }
+    }
+}
+
+async fn run(cli: &ArgMatches) -> Result<()> {
     let (chrome_layer, _guard) = match cli
         .get_flag("tracing-chrome")
         .then(|| tracing_chrome::ChromeLayerBuilder::new().build())
@@ -109,18 +124,40 @@ async fn main() -> Result<()> {
         _ => (None, None),
     };
 
-    let subscriber = tracing_subscriber::fmt::fmt()
-        .with_env_filter(
+    let (plain_layer, json_layer) = if cli.get_flag("log-json") {
+        let json_layer = tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .with_writer(std::io::stderr);
+        (None, Some(json_layer))
+    } else {
+        (Some(tracing_subscriber::fmt::layer()), None)
+    };
+
+    let subscriber = tracing_subscriber::registry()
+        .with(
             tracing_subscriber::filter::EnvFilter::builder()
                 .with_default_directive(tracing_subscriber::filter::LevelFilter::WARN.into())
                 .from_env_lossy(),
         )
-        .finish()
+        .with(plain_layer)
+        .with(json_layer)
         .with(chrome_layer);
 
     tracing::subscriber::set_global_default(subscriber)?;
     debug!("Debugging enabled");
 
+    crate::commands::set_table_options(crate::commands::TableOptions {
+        columns: cli
+            .get_many::<String>("columns")
+            .map(|columns| columns.cloned().collect())
+            .unwrap_or_default(),
+        full_ids: cli.get_flag("full-ids"),
+    });
+
+    crate::util::offline::set_offline(cli.get_flag("offline"));
+
     // check if the version flag is set
     if cli.get_flag("version") {
         println!("{VERSION_LONG}");
@@ -139,74 +176,109 @@ async fn main() -> Result<()> {
         .workdir()
         .ok_or_else(|| anyhow!("Not a repository with working directory. Cannot do my job!"))?;
 
-    let mut config_builder =
-        ::config::Config::builder().add_source(::config::File::from(repo_path.join("config.toml")));
+    let config_files = crate::config::config_files(repo_path)?;
 
-    {
-        let xdg = xdg::BaseDirectories::with_prefix("butido")?;
-        let xdg_config_file = xdg.find_config_file("config.toml");
-        if let Some(xdg_config) = xdg_config_file {
-            debug!(
-                "Configuration file found with XDG: {}",
-                xdg_config.display()
-            );
-            config_builder = config_builder.add_source(::config::File::from(xdg_config));
-        } else {
-            debug!(
-                "No configuration file found with XDG: {}",
-                xdg.get_config_home().display()
-            );
+    // These subcommands work on the configuration itself (or check it), so they must work with an
+    // invalid configuration as well (loading it below fails on the first error)
+    let profile = cli.get_one::<String>("profile").map(String::as_str);
+    match cli.subcommand() {
+        Some(("validate-config", matches)) => {
+            return crate::commands::validate_config(matches, &config_files, profile).await
+        }
+        Some(("config", matches)) => {
+            return crate::commands::config(matches, repo_path, &config_files, profile)
         }
+        Some(("doctor", _)) => {
+            return crate::commands::doctor(cli, repo_path, &config_files, profile).await
+        }
+        _ => {}
     }
 
-    config_builder = config_builder.add_source(::config::Environment::with_prefix("BUTIDO"));
-
-    let config = config_builder
-        .build()
-        .context("Failed to load and build the butido configuration")?;
+    let config =
+        crate::config::load_config(&config_files, profile).context(ErrorCategory::Config)?;
 
     // Check the "compatibility" setting before loading (type checking) the configuration so that
     // we can better inform the users about required changes:
     check_compatibility(&config)
-        .context("The butido configuration failed the compatibility check")?;
+        .context("The butido configuration failed the compatibility check")
+        .context(ErrorCategory::Config)?;
 
     let config = config
         .try_deserialize::<NotValidatedConfiguration>()
-        .context("Failed to load (type check) the butido configuration")?
-        .validate()
-        .context("Failed to validate the butido configuration")?;
+        .context("Failed to load (type check) the butido configuration")
+        .and_then(|config| {
+            config
+                .validate()
+                .context("Failed to validate the butido configuration")
+        })
+        .context(ErrorCategory::Config)?;
 
     let hide_bars = cli.get_flag("hide_bars") || crate::util::stdout_is_pipe();
-    let progressbars = ProgressBars::setup(config.progress_format().clone(), hide_bars);
+    let progressbars = ProgressBars::setup(
+        config.progress_format().clone(),
+        config.progress_format_bytes().clone(),
+        hide_bars,
+    );
 
     let load_repo = || -> Result<Repository> {
         let bar = progressbars.bar()?;
         bar.set_message("Loading repository...");
-        let repo = Repository::load(repo_path, &bar).context("Loading the repository")?;
+        let repo = Repository::load(
+            repo_path,
+            config.repository_overlays(),
+            config.package_namespaces(),
+            &bar,
+        )
+        .context("Loading the repository")?;
         bar.finish_with_message("Repository loading finished");
         Ok(repo)
     };
 
-    let db_connection_config = crate::db::DbConnectionConfig::parse(&config, &cli)?;
+    let db_connection_config = crate::db::DbConnectionConfig::parse(&config, cli)?;
     match cli.subcommand() {
         Some(("generate-completions", matches)) => generate_completions(matches),
-        Some(("db", matches)) => crate::commands::db(db_connection_config, &config, matches)?,
-        Some(("build", matches)) => {
-            let pool = db_connection_config.establish_pool()?;
-
-            let repo = load_repo()?;
-
-            crate::commands::build(
-                repo_path,
-                matches,
-                progressbars,
-                pool,
+        Some(("db", matches)) => {
+            crate::commands::db(
+                db_connection_config,
                 &config,
-                repo,
+                matches,
                 repo_path,
+                load_repo,
+                &progressbars,
             )
+            .await?
+        }
+        Some(("build", matches)) => {
+            let notification = LongOperation::start(
+                config.notifications().as_ref(),
+                operation_description("build", matches),
+            );
+            // The repository is loaded outside of the async block, which takes the progress bars
+            let repo = load_repo();
+            let result = async {
+                let repo = repo?;
+                let pool = db_connection_config.establish_pool()?;
+
+                crate::commands::build(
+                    repo_path,
+                    matches,
+                    progressbars,
+                    pool,
+                    &config,
+                    repo,
+                    repo_path,
+                )
+                .await
+            }
             .await
-            .context("build command failed")?
+            .context("build command failed");
+            notification.finish(&result).await;
+            result?
+        }
+        Some(("daemon", matches)) => {
+            crate::commands::daemon(db_connection_config, cli, matches, repo_path)
+                .await
+                .context("daemon command failed")?
         }
         Some(("what-depends", matches)) => {
             let repo = load_repo()?;
@@ -222,6 +294,31 @@ async fn main() -> Result<()> {
                 .context("dependencies-of command failed")?
         }
 
+        Some(("bump-version", matches)) => {
+            let repo = load_repo()?;
+            crate::commands::bump_version(matches, repo)
+                .await
+                .context("bump-version command failed")?
+        }
+
+        Some(("watch", matches)) => crate::commands::watch(matches, repo_path)
+            .await
+            .context("watch command failed")?,
+
+        Some(("outdated", matches)) => {
+            let repo = load_repo()?;
+            crate::commands::outdated(matches, repo)
+                .await
+                .context("outdated command failed")?
+        }
+
+        Some(("new-package", matches)) => {
+            let repo = load_repo()?;
+            crate::commands::new_package(matches, &config, repo_path, repo)
+                .await
+                .context("new-package command failed")?
+        }
+
         Some(("versions-of", matches)) => {
             let repo = load_repo()?;
             crate::commands::versions_of(matches, repo)
@@ -252,18 +349,38 @@ async fn main() -> Result<()> {
         }
 
         Some(("source", matches)) => {
-            let repo = load_repo()?;
-            crate::commands::source(matches, &config, repo, progressbars)
+            // Only downloads may take long enough to be worth a notification
+            let notification = matches.subcommand_matches("download").map(|matches| {
+                LongOperation::start(
+                    config.notifications().as_ref(),
+                    operation_description("source download", matches),
+                )
+            });
+            let result = crate::commands::source(matches, &config, load_repo, progressbars)
                 .await
-                .context("source command failed")?
+                .context("source command failed");
+            if let Some(notification) = notification {
+                notification.finish(&result).await;
+            }
+            result?
         }
 
         Some(("release", matches)) => {
-            crate::commands::release(db_connection_config, &config, matches)
+            crate::commands::release(db_connection_config, &config, matches, progressbars)
                 .await
                 .context("release command failed")?
         }
 
+        Some(("store", matches)) => crate::commands::store(
+            db_connection_config,
+            &config,
+            matches,
+            progressbars,
+            load_repo,
+        )
+        .await
+        .context("store command failed")?,
+
         Some(("lint", matches)) => {
             let repo = load_repo()?;
             crate::commands::lint(repo_path, matches, progressbars, &config, repo)
@@ -278,6 +395,27 @@ async fn main() -> Result<()> {
                 .context("tree-of command failed")?
         }
 
+        Some(("runtime-closure", matches)) => crate::commands::runtime_closure(
+            db_connection_config,
+            &config,
+            matches,
+            repo_path,
+            load_repo,
+        )
+        .await
+        .context("runtime-closure command failed")?,
+
+        Some(("lock", matches)) => crate::commands::lock(matches, &config, load_repo)
+            .await
+            .context("lock command failed")?,
+
+        Some(("why", matches)) => {
+            let repo = load_repo()?;
+            crate::commands::why(matches, repo, &config)
+                .await
+                .context("why command failed")?
+        }
+
         Some(("metrics", _)) => {
             let repo = load_repo()?;
             let pool = db_connection_config.establish_pool()?;
@@ -286,13 +424,30 @@ async fn main() -> Result<()> {
                 .context("metrics command failed")?
         }
 
-        Some(("endpoint", matches)) => crate::commands::endpoint(matches, &config, progressbars)
+        Some(("endpoint", matches)) => {
+            crate::commands::endpoint(db_connection_config, matches, &config, progressbars)
+                .await
+                .context("endpoint command failed")?
+        }
+        Some(("cache", matches)) => crate::commands::cache(matches, &config)
             .await
-            .context("endpoint command failed")?,
-        Some((other, _)) => {
-            error!("Unknown subcommand: {}", other);
-            error!("Use --help to find available subcommands");
-            return Err(anyhow!("Unknown subcommand: {}", other));
+            .context("cache command failed")?,
+        Some((other, matches)) => {
+            let Some(plugin) = crate::commands::find_plugin(other) else {
+                error!("Unknown subcommand: {}", other);
+                error!("Use --help to find available subcommands");
+                return Err(anyhow!("Unknown subcommand: {}", other));
+            };
+            crate::commands::plugin(
+                &plugin,
+                matches,
+                repo_path,
+                &config_files,
+                profile,
+                db_connection_config,
+            )
+            .await
+            .with_context(|| anyhow!("{} plugin failed", other))?
         }
         None => {
             error!("No subcommand.");
@@ -304,6 +459,28 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// A description of a command for notifications, e.g. "build of openssl 3.0"
+fn operation_description(command: &str, matches: &ArgMatches) -> String {
+    let package = [
+        matches.get_one::<String>("package_name"),
+        matches
+            .try_get_one::<String>("package_version")
+            .ok()
+            .flatten(),
+    ]
+    .into_iter()
+    .flatten()
+    .map(String::as_str)
+    .collect::<Vec<_>>()
+    .join(" ");
+
+    if package.is_empty() {
+        command.to_string()
+    } else {
+        format!("{command} of {package}")
+    }
+}
+
 fn generate_completions(matches: &ArgMatches) {
     use clap_complete::generate;
     use clap_complete::Shell;
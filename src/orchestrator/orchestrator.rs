@@ -33,20 +33,30 @@ use tokio::sync::mpsc::Sender;
 use tokio::sync::RwLock;
 use tokio_stream::StreamExt;
 use tracing::Instrument;
-use tracing::{debug, error, trace};
+use tracing::{debug, error, info, trace, warn};
 use typed_builder::TypedBuilder;
 use uuid::Uuid;
 
 use crate::config::Configuration;
 use crate::db::models as dbmodels;
+use crate::db::ResourceLock;
+use crate::db::ReuseArtifacts;
+use crate::db::SubmitLocks;
 use crate::endpoint::EndpointConfiguration;
 use crate::endpoint::EndpointScheduler;
+use crate::endpoint::LocalExecutor;
 use crate::filestore::ArtifactPath;
+use crate::filestore::CheckpointStore;
+use crate::filestore::MergedStores;
 use crate::filestore::ReleaseStore;
 use crate::filestore::StagingStore;
+use crate::job::CacheKey;
 use crate::job::Dag;
 use crate::job::JobDefinition;
+use crate::job::RemoteCache;
 use crate::job::RunnableJob;
+use crate::job::SubmitEstimate;
+use crate::log::FailureClassifiers;
 use crate::orchestrator::util::*;
 use crate::source::SourceCache;
 use crate::util::progress::ProgressBars;
@@ -171,6 +181,13 @@ pub struct Orchestrator<'a> {
     config: &'a Configuration,
     repository: Repository,
     database: Pool<ConnectionManager<PgConnection>>,
+    no_cache: bool,
+    reuse_staging_artifacts: bool,
+    reuse_artifacts: ReuseArtifacts,
+    remote_cache: Option<RemoteCache>,
+    estimate: Option<SubmitEstimate>,
+    submit: dbmodels::Submit,
+    submit_locks: Arc<SubmitLocks>,
 }
 
 #[derive(TypedBuilder)]
@@ -183,13 +200,47 @@ pub struct OrchestratorSetup<'a> {
     jobdag: Dag,
     database: Pool<ConnectionManager<PgConnection>>,
     submit: dbmodels::Submit,
+    /// The locks of the submit, the jobs lock the packages they build with them
+    submit_locks: Arc<SubmitLocks>,
     log_dir: Option<PathBuf>,
     config: &'a Configuration,
     repository: Repository,
+
+    /// Do not reuse artifacts from the build cache
+    #[builder(default)]
+    no_cache: bool,
+
+    /// Whether artifacts from the staging store are reused (see `--use-store`)
+    #[builder(default = true)]
+    reuse_staging_artifacts: bool,
+
+    /// Which artifacts of previous submits are reused (see `--reuse-artifacts`)
+    #[builder(default = ReuseArtifacts::Any)]
+    reuse_artifacts: ReuseArtifacts,
+
+    /// The priority of the jobs in the job queue
+    #[builder(default)]
+    priority: i32,
+
+    /// The estimated duration of the submit, to show an ETA while the jobs run
+    #[builder(default)]
+    estimate: Option<SubmitEstimate>,
+
+    /// Run the jobs on the host instead of the endpoints
+    #[builder(default)]
+    local: Option<LocalExecutor>,
 }
 
 impl<'a> OrchestratorSetup<'a> {
     pub async fn setup(self) -> Result<Orchestrator<'a>> {
+        let remote_cache = match self.config.remote_cache().as_ref() {
+            Some(_) if crate::util::offline::is_offline() => {
+                warn!("The remote cache is not used with --offline");
+                None
+            }
+            remote_cache => remote_cache.map(RemoteCache::new).transpose()?,
+        };
+
         let scheduler = EndpointScheduler::setup(
             self.endpoint_config,
             self.staging_store.clone(),
@@ -197,6 +248,31 @@ impl<'a> OrchestratorSetup<'a> {
             self.database.clone(),
             self.submit.clone(),
             self.log_dir,
+            *self.config.log_limits(),
+            FailureClassifiers::new(self.config.failure_classifiers())?,
+            Some(self.config.docker().endpoint_health_check_interval())
+                .filter(|secs| *secs > 0)
+                .map(std::time::Duration::from_secs),
+            self.config.docker().reschedule_failed_jobs(),
+            self.config.docker().retry_failure_classes().clone(),
+            self.config.docker().max_job_retries(),
+            self.priority,
+            self.local,
+            self.config.docker().max_parallel_uploads(),
+            self.config.post_build_hooks().clone(),
+            self.config.containers().retention(),
+            self.config
+                .checkpoint_dir()
+                .clone()
+                .map(CheckpointStore::new)
+                .transpose()?,
+            self.config.environment_probes().clone(),
+            self.config
+                .docker()
+                .images()
+                .iter()
+                .map(|image| (image.name.clone(), image.endpoint_tags.clone()))
+                .collect::<HashMap<_, _>>(),
         )
         .await?;
 
@@ -210,6 +286,13 @@ impl<'a> OrchestratorSetup<'a> {
             config: self.config,
             database: self.database,
             repository: self.repository,
+            no_cache: self.no_cache,
+            reuse_staging_artifacts: self.reuse_staging_artifacts,
+            reuse_artifacts: self.reuse_artifacts,
+            remote_cache,
+            estimate: self.estimate,
+            submit: self.submit,
+            submit_locks: self.submit_locks,
         })
     }
 }
@@ -226,6 +309,33 @@ impl<'a> OrchestratorSetup<'a> {
 /// why.
 type JobResult = std::result::Result<HashMap<Uuid, Vec<ProducedArtifact>>, HashMap<Uuid, Error>>;
 
+/// The ETA of the submit, updated whenever a job finishes
+struct EtaProgress {
+    estimate: Mutex<SubmitEstimate>,
+    bar: ProgressBar,
+}
+
+impl EtaProgress {
+    fn job_finished(&self, job_uuid: &Uuid) {
+        let mut estimate = self.estimate.lock().unwrap();
+        estimate.job_finished(job_uuid);
+        self.bar.inc(1);
+        if estimate.remaining_jobs() == 0 {
+            self.bar.finish_and_clear();
+        } else {
+            self.bar.set_message(Self::message(&estimate));
+        }
+    }
+
+    fn message(estimate: &SubmitEstimate) -> String {
+        format!(
+            "{} jobs remaining, ETA ~{}",
+            estimate.remaining_jobs(),
+            crate::job::format_estimate(estimate.remaining())
+        )
+    }
+}
+
 /// A type that represents whether an artifact was built or reused from an old job
 ///
 /// This is necessary to decide in dependent jobs whether a package needs to be rebuild even though
@@ -287,6 +397,19 @@ impl Orchestrator<'_> {
             mp
         });
 
+        let eta = self
+            .estimate
+            .map(|estimate| -> Result<_> {
+                let bar = self.progress_generator.bar()?;
+                bar.set_length(estimate.remaining_jobs() as u64);
+                bar.set_message(EtaProgress::message(&estimate));
+                Ok(EtaProgress {
+                    estimate: Mutex::new(estimate),
+                    bar,
+                })
+            })
+            .transpose()?;
+
         let git_author_env = {
             self.config
                 .containers()
@@ -341,6 +464,8 @@ impl Orchestrator<'_> {
                     jobdef,
 
                     bar,
+                    multibar: multibar.clone(),
+                    progress_generator: &self.progress_generator,
                     config: self.config,
                     git_author_env: git_author_env.as_ref(),
                     git_commit_env: git_commit_env.as_ref(),
@@ -349,6 +474,13 @@ impl Orchestrator<'_> {
                     staging_store: self.staging_store.clone(),
                     release_stores: self.release_stores.clone(),
                     database: self.database.clone(),
+                    no_cache: self.no_cache,
+                    reuse_staging_artifacts: self.reuse_staging_artifacts,
+                    reuse_artifacts: self.reuse_artifacts,
+                    submit: &self.submit,
+                    submit_locks: &self.submit_locks,
+                    remote_cache: self.remote_cache.as_ref(),
+                    eta: eta.as_ref(),
                 };
 
                 Ok((
@@ -414,30 +546,36 @@ impl Orchestrator<'_> {
             };
         }
 
-        // Find the id of the root task
+        // Find the root tasks
         //
         // By now, all tasks should be associated with their respective sender.
-        // Only one has None sender: The task that is the "root" of the tree.
-        // By that property, we can find the root task.
-        // (Note: It should normally be the first jobs item as we start building the DAG from the
-        // root task (-> first node in the DAG) but we don't explicitly guarantee this.)
-        //
-        // Here, we copy its uuid, because we need it later.
-        let root_job = jobs
+        // Only the tasks that are the "root" of a tree have a None sender. There is one tree per
+        // architecture the submit builds for (see `Dag::from_package_dag()`), usually only one.
+        // By that property, we can find the root tasks.
+        let root_jobs = jobs
             .iter()
-            .find(|j| j.3.borrow().is_none())
-            .ok_or_else(|| anyhow!("Failed to find root task"))?;
-        let root_job_id = root_job.1.jobdef.job.uuid();
-        trace!(%root_job_id, "Root job id found");
-        // Move the progress bar for the root task to the bottom to ensure that it will be visible
-        // without having to scroll up (the MultiProgress implementation doesn't let us modify the
-        // order so we have to remove and re-add it - it works despite the clone because
-        // ProgressBar is an Arc around its internal state and is documented that way):
-        let root_job_bar = &root_job.1.bar;
-        multibar.remove(root_job_bar);
-        multibar.add(root_job_bar.clone());
-
-        // Create a sender and a receiver for the root of the tree
+            .filter(|j| j.3.borrow().is_none())
+            .collect::<Vec<_>>();
+        if root_jobs.is_empty() {
+            return Err(anyhow!("Failed to find root task"));
+        }
+        for root_job in root_jobs.iter() {
+            let root_job_id = root_job.1.jobdef.job.uuid();
+            trace!(%root_job_id, "Root job id found");
+            // Move the progress bar for the root task to the bottom to ensure that it will be
+            // visible without having to scroll up (the MultiProgress implementation doesn't let
+            // us modify the order so we have to remove and re-add it - it works despite the clone
+            // because ProgressBar is an Arc around its internal state and is documented that
+            // way):
+            let root_job_bar = &root_job.1.bar;
+            multibar.remove(root_job_bar);
+            multibar.add(root_job_bar.clone());
+        }
+        if let Some(eta) = eta.as_ref() {
+            multibar.add(eta.bar.clone());
+        }
+
+        // Create a sender and a receiver for the roots of the trees
         let (root_sender, mut root_receiver) = tokio::sync::mpsc::channel(100);
 
         // preparation ended
@@ -467,8 +605,19 @@ impl Orchestrator<'_> {
                 |task| trace!(parent: &run_span, job_uuid = %task.jobdef.job.uuid(), "Running job"),
             )
             .map(|task| {
+                // The fields of this span are attached to all diagnostics of the job, hence it is
+                // enabled on every level
+                let job_span = tracing::error_span!(
+                    parent: &run_span,
+                    "job",
+                    submit_uuid = %self.submit.uuid,
+                    job_uuid = %task.jobdef.job.uuid(),
+                    package = %task.jobdef.job.package().name(),
+                    version = %task.jobdef.job.package().version(),
+                );
                 task.run()
-                    .instrument(tracing::debug_span!(parent: &run_span, "JobTask::run"))
+                    .instrument(tracing::debug_span!(parent: &job_span, "JobTask::run"))
+                    .instrument(job_span)
             })
             .collect::<futures::stream::FuturesUnordered<_>>();
         debug!("Built {} jobs", running_jobs.len());
@@ -480,17 +629,32 @@ impl Orchestrator<'_> {
         trace!(parent: &run_span, "All jobs finished");
         drop(run_span);
 
-        match root_receiver.recv().await {
-            None => Err(anyhow!("No result received...")),
-            Some(Ok(results)) => {
-                let results = results
-                    .into_iter()
-                    .flat_map(|tpl| tpl.1.into_iter())
-                    .map(ProducedArtifact::unpack)
-                    .collect();
-                Ok((results, HashMap::with_capacity(0)))
+        if let Some(summary) = self.scheduler.upload_stats().summary() {
+            info!("{}", summary);
+        }
+
+        // Every root task sends exactly one result
+        drop(root_sender);
+        let mut results = Vec::new();
+        let mut errors = HashMap::new();
+        let mut received = false;
+        while let Some(result) = root_receiver.recv().await {
+            received = true;
+            match result {
+                Ok(artifacts) => results.extend(
+                    artifacts
+                        .into_iter()
+                        .flat_map(|tpl| tpl.1.into_iter())
+                        .map(ProducedArtifact::unpack),
+                ),
+                Err(root_errors) => errors.extend(root_errors),
             }
-            Some(Err(errors)) => Ok((vec![], errors)),
+        }
+
+        if received {
+            Ok((results, errors))
+        } else {
+            Err(anyhow!("No result received..."))
         }
     }
 }
@@ -505,6 +669,8 @@ struct TaskPreparation<'a> {
     jobdef: JobDefinition<'a>,
 
     bar: ProgressBar,
+    multibar: Arc<indicatif::MultiProgress>,
+    progress_generator: &'a ProgressBars,
 
     config: &'a Configuration,
     git_author_env: Option<&'a (EnvironmentVariableName, String)>,
@@ -514,6 +680,13 @@ struct TaskPreparation<'a> {
     staging_store: Arc<RwLock<StagingStore>>,
     release_stores: Vec<Arc<ReleaseStore>>,
     database: Pool<ConnectionManager<PgConnection>>,
+    no_cache: bool,
+    reuse_staging_artifacts: bool,
+    reuse_artifacts: ReuseArtifacts,
+    submit: &'a dbmodels::Submit,
+    submit_locks: &'a SubmitLocks,
+    remote_cache: Option<&'a RemoteCache>,
+    eta: Option<&'a EtaProgress>,
 }
 
 /// Helper type for executing one job task
@@ -523,6 +696,8 @@ struct JobTask<'a> {
     jobdef: JobDefinition<'a>,
 
     bar: ProgressBar,
+    multibar: Arc<indicatif::MultiProgress>,
+    progress_generator: &'a ProgressBars,
 
     config: &'a Configuration,
     git_author_env: Option<&'a (EnvironmentVariableName, String)>,
@@ -532,6 +707,13 @@ struct JobTask<'a> {
     staging_store: Arc<RwLock<StagingStore>>,
     release_stores: Vec<Arc<ReleaseStore>>,
     database: Pool<ConnectionManager<PgConnection>>,
+    no_cache: bool,
+    reuse_staging_artifacts: bool,
+    reuse_artifacts: ReuseArtifacts,
+    submit: &'a dbmodels::Submit,
+    submit_locks: &'a SubmitLocks,
+    remote_cache: Option<&'a RemoteCache>,
+    eta: Option<&'a EtaProgress>,
 
     /// Channel where the dependencies arrive
     receiver: Receiver<JobResult>,
@@ -559,6 +741,10 @@ struct JobTask<'a> {
 /// In the latter case, we cleanup by telling the progressbar to finish.
 impl Drop for JobTask<'_> {
     fn drop(&mut self) {
+        if let Some(eta) = self.eta {
+            eta.job_finished(self.jobdef.job.uuid());
+        }
+
         if !self.bar.is_finished() {
             // If there are dependencies, the error is probably from another task
             // If there are no dependencies, the error was caused by something else
@@ -604,6 +790,8 @@ impl<'a> JobTask<'a> {
             jobdef: prep.jobdef,
 
             bar,
+            multibar: prep.multibar,
+            progress_generator: prep.progress_generator,
 
             config: prep.config,
             git_author_env: prep.git_author_env,
@@ -613,6 +801,13 @@ impl<'a> JobTask<'a> {
             staging_store: prep.staging_store,
             release_stores: prep.release_stores,
             database: prep.database.clone(),
+            no_cache: prep.no_cache,
+            reuse_staging_artifacts: prep.reuse_staging_artifacts,
+            reuse_artifacts: prep.reuse_artifacts,
+            submit: prep.submit,
+            submit_locks: prep.submit_locks,
+            remote_cache: prep.remote_cache,
+            eta: prep.eta,
 
             receiver,
             sender,
@@ -699,6 +894,12 @@ impl<'a> JobTask<'a> {
                        errors = tracing::field::display(&received_errors.display_error_map()),
                        "Received errors");
                 self.sender[0].send(Err(received_errors)).await;
+                self.progress_generator.status_lines().job(
+                    self.jobdef.job.uuid(),
+                    self.jobdef.job.package(),
+                    "stopped",
+                    Some("errors from dependencies received"),
+                );
 
                 // ... and stop operation, because the whole tree will fail anyways.
                 self.bar.finish_with_message(format!(
@@ -729,9 +930,13 @@ impl<'a> JobTask<'a> {
         // If no dependency was built, we can check for replacements for this job as well, so
         // check if a job that looks very similar to this job has already produced artifacts.
         // If it has, simply return those (plus the received ones)
-        if !any_dependency_was_built {
+        if !any_dependency_was_built && self.reuse_artifacts != ReuseArtifacts::Never {
             let staging_store = self.staging_store.read().await;
 
+            // The staging store is not searched if it is not selected (see `--use-store`)
+            let reusable_staging_store =
+                Some(&*staging_store).filter(|_| self.reuse_staging_artifacts);
+
             // Use the environment of the job definition, as it appears in the job DAG.
             //
             // This is because we do not have access to the commandline-passed (additional)
@@ -754,6 +959,11 @@ impl<'a> JobTask<'a> {
                 .package(self.jobdef.job.package())
                 .release_stores(&self.release_stores)
                 .image_name(Some(self.jobdef.job.image()))
+                .arch(self.jobdef.job.arch().as_ref())
+                .repo_hash_id(
+                    Some(self.submit.repo_hash_id)
+                        .filter(|_| self.reuse_artifacts == ReuseArtifacts::SameGithash),
+                )
                 // We can simply pass the staging store here, because it doesn't hurt. There are
                 // two scenarios:
                 //
@@ -767,11 +977,11 @@ impl<'a> JobTask<'a> {
                 // The fact that released artifacts are returned preferably from this function
                 // call does not change anything, because if there is an artifact that's a released
                 // one that matches this job, we should use it anyways.
-                .staging_store(Some(&staging_store))
+                .staging_store(reusable_staging_store)
                 .env_filter(&additional_env)
                 .script_filter(true)
                 .build()
-                .run()?;
+                .find()?;
 
             debug!(
                 job_uuid = %self.jobdef.job.uuid(),
@@ -783,37 +993,51 @@ impl<'a> JobTask<'a> {
                 ?replacement_artifacts,
                 "Found replacement artifacts",
             );
-            let mut artifacts = replacement_artifacts
+            let merged_stores = MergedStores::new(reusable_staging_store, &self.release_stores);
+            let artifacts = replacement_artifacts
                 .into_iter()
                 // First of all, we sort by whether the artifact path is in the staging store,
                 // because we prefer staging store artifacts at this point.
-                .sorted_by(|(p1, _), (p2, _)| {
-                    let r1 = p1.is_in_staging_store(&staging_store);
-                    let r2 = p2.is_in_staging_store(&staging_store);
+                .sorted_by(|a1, a2| {
+                    let r1 = a1.path.is_in_staging_store(&staging_store);
+                    let r2 = a2.path.is_in_staging_store(&staging_store);
                     r1.cmp(&r2)
                 })
                 // We don't need duplicates here, so remove them by making the iterator unique
                 // If we have two artifacts that are the same, the one in the staging store will be
                 // preferred in the next step
-                .unique_by(|tpl| tpl.0.artifact_path().clone())
+                .unique_by(|found| found.path.artifact_path().clone())
                 // Fetch the artifact from the staging store, if there is one.
-                // If there is none, try the release store.
+                // If there is none, try the release stores.
                 // If there is none, there won't be a replacement artifact
-                .filter_map(|(full_artifact_path, _)| {
-                    trace!("Searching for {:?} in stores", full_artifact_path.display());
-                    if let Some(ap) = staging_store.get(full_artifact_path.artifact_path()) {
-                        Some(ap.clone())
-                    } else {
-                        self.release_stores
-                            .iter()
-                            .find_map(|rs| rs.get(full_artifact_path.artifact_path()))
-                            .cloned()
-                    }
+                .filter_map(|found| {
+                    trace!("Searching for {:?} in stores", found.path.display());
+                    merged_stores
+                        .get(found.path.artifact_path())
+                        .cloned()
+                        .map(|path| (found, path))
                 })
-                .map(ProducedArtifact::Reused)
-                .collect::<Vec<ProducedArtifact>>();
+                .collect::<Vec<_>>();
 
             if !artifacts.is_empty() {
+                // Record where the artifacts came from, the job itself is not recorded
+                let mut conn = self.database.get()?;
+                for (found, _) in artifacts.iter() {
+                    dbmodels::ReusedArtifact::create(
+                        &mut conn,
+                        self.submit,
+                        self.jobdef.job.uuid(),
+                        found.artifact_id,
+                        found.submit_id,
+                        self.reuse_artifacts,
+                    )?;
+                }
+                drop(conn);
+
+                let artifacts = artifacts
+                    .into_iter()
+                    .map(|(_, path)| ProducedArtifact::Reused(path))
+                    .collect::<Vec<ProducedArtifact>>();
                 received_dependencies.insert(*self.jobdef.job.uuid(), artifacts);
                 trace!(job_uuid = %self.jobdef.job.uuid(), "Sending to parent: {:?}", received_dependencies);
                 for s in self.sender.iter() {
@@ -829,6 +1053,12 @@ impl<'a> JobTask<'a> {
                             )
                         })?;
                 }
+                self.progress_generator.status_lines().job(
+                    self.jobdef.job.uuid(),
+                    self.jobdef.job.package(),
+                    "reused",
+                    None,
+                );
                 self.bar.finish_with_message(format!(
                     "{:-<max_endpoint_name_length$} {:-<CONTAINER_ID_LENGTH$} {} {} {} {} Reusing artifact",
                     "",
@@ -868,7 +1098,7 @@ impl<'a> JobTask<'a> {
         ));
 
         // Create a RunnableJob object
-        let runnable = RunnableJob::build_from_job(
+        let mut runnable = RunnableJob::build_from_job(
             self.jobdef.job,
             self.source_cache,
             self.config,
@@ -877,6 +1107,77 @@ impl<'a> JobTask<'a> {
             dependency_artifacts,
         )?;
 
+        // Compute the cache key for the job, so that it is recorded in the database, and check
+        // whether a job with the very same inputs already produced artifacts that we can use.
+        // With `--no-cache`, the key is neither computed nor recorded.
+        let cache_key = if self.no_cache {
+            None
+        } else {
+            self.compute_cache_key(&runnable).await?
+        };
+
+        // Only the packages that are built are locked, so that submits that merely share
+        // dependencies do not conflict. If another submit held the lock, it probably built the
+        // package in the meantime, so the build cache is checked again after waiting.
+        let mut package_lock = None;
+        loop {
+            let Some(cache_key) = cache_key.as_ref() else {
+                break;
+            };
+            let mut artifacts = self.find_cached_artifacts(cache_key).await?;
+            if artifacts.is_empty() {
+                artifacts = self.fetch_from_remote_cache(&runnable, cache_key).await?;
+            }
+
+            if !artifacts.is_empty() {
+                debug!(
+                    job_uuid = %self.jobdef.job.uuid(),
+                    %cache_key,
+                    "Found cached artifacts",
+                );
+                let artifacts = artifacts
+                    .into_iter()
+                    .map(ProducedArtifact::Reused)
+                    .collect();
+                received_dependencies.insert(*self.jobdef.job.uuid(), artifacts);
+                for s in self.sender.iter() {
+                    s.send(Ok(received_dependencies.clone()))
+                        .await
+                        .context("Cannot send received dependencies to parent")?;
+                }
+                self.progress_generator.status_lines().job(
+                    self.jobdef.job.uuid(),
+                    self.jobdef.job.package(),
+                    "cached",
+                    None,
+                );
+                self.bar.finish_with_message(format!(
+                    "{:-<max_endpoint_name_length$} {:-<CONTAINER_ID_LENGTH$} {} {} {} {} Using cached artifact",
+                    "",
+                    "",
+                    self.jobdef.job.uuid(),
+                    "\u{2588}\u{2588}".white(),
+                    self.jobdef.job.package().name(),
+                    self.jobdef.job.package().version()
+                ));
+                return Ok(());
+            }
+
+            if package_lock.is_some() {
+                break;
+            }
+            let (lock, waited) = self.lock_package().await?;
+            package_lock = Some(lock);
+            if !waited {
+                break;
+            }
+        }
+        let _package_lock = match package_lock {
+            Some(lock) => lock,
+            None => self.lock_package().await?.0,
+        };
+        runnable.set_cache_key(cache_key.clone());
+
         self.bar.set_message(format!(
             "{:-<max_endpoint_name_length$} {:-<CONTAINER_ID_LENGTH$} {} {} {} {}",
             "",
@@ -889,15 +1190,85 @@ impl<'a> JobTask<'a> {
         let job_uuid = *self.jobdef.job.uuid();
 
         // Schedule the job on the scheduler
-        match self
-            .scheduler
-            .schedule_job(runnable, self.bar.clone())
-            .await?
-            .run()
-            .await?
-        {
+        //
+        // If the endpoint becomes unreachable while the job is running, the job might be
+        // rescheduled on another (healthy) endpoint. Jobs that fail because of the infrastructure
+        // might be retried on another endpoint. Both count against the retry limit and exclude
+        // the failed endpoint.
+        let mut excluded_endpoints = Vec::new();
+        let mut retries = 0;
+        let mut caches_artifacts;
+        let job_result = loop {
+            let job_handle = self
+                .scheduler
+                .schedule_job(
+                    runnable.clone(),
+                    self.bar.clone(),
+                    self.progress_generator.status_lines().clone(),
+                    &excluded_endpoints,
+                )
+                .await?;
+            let endpoint_name = job_handle.endpoint_name().clone();
+            caches_artifacts = job_handle.caches_artifacts();
+
+            match job_handle.run().await {
+                Err(e)
+                    if self
+                        .scheduler
+                        .may_reschedule(&endpoint_name, &job_uuid, retries)
+                        .await? =>
+                {
+                    warn!(
+                        job_uuid = %job_uuid,
+                        "Rescheduling job, endpoint {} is not reachable: {:?}",
+                        endpoint_name,
+                        e
+                    );
+                    self.progress_generator.status_lines().job(
+                        self.jobdef.job.uuid(),
+                        self.jobdef.job.package(),
+                        "rescheduled",
+                        Some(endpoint_name.as_ref()),
+                    );
+                    excluded_endpoints.push(endpoint_name);
+                    retries += 1;
+                }
+                job_result => {
+                    let Some(class) = self
+                        .scheduler
+                        .may_retry(&job_uuid, &job_result, retries)
+                        .await?
+                    else {
+                        break job_result?;
+                    };
+
+                    warn!(
+                        job_uuid = %job_uuid,
+                        "Retrying job on another endpoint, it failed on {} because of: {}",
+                        endpoint_name,
+                        class
+                    );
+                    self.progress_generator.status_lines().job(
+                        self.jobdef.job.uuid(),
+                        self.jobdef.job.package(),
+                        "retried",
+                        Some(&class),
+                    );
+                    excluded_endpoints.push(endpoint_name);
+                    retries += 1;
+                }
+            }
+        };
+
+        match job_result {
             Err(e) => {
                 trace!(job_uuid = %self.jobdef.job.uuid(), "Scheduler returned error = {:?}", e);
+                self.progress_generator.status_lines().job(
+                    self.jobdef.job.uuid(),
+                    self.jobdef.job.package(),
+                    "failed",
+                    Some(&e.root_cause().to_string()),
+                );
                 // ... and we send that to our parent
                 //
                 // We only send to one parent, because it doesn't matter anymore
@@ -924,6 +1295,16 @@ impl<'a> JobTask<'a> {
                     "Scheduler returned artifacts = {:?}",
                     artifacts
                 );
+                self.progress_generator.status_lines().job(
+                    self.jobdef.job.uuid(),
+                    self.jobdef.job.package(),
+                    "finished",
+                    None,
+                );
+
+                if let Some(cache_key) = cache_key.as_ref().filter(|_| caches_artifacts) {
+                    self.upload_to_remote_cache(cache_key, &artifacts).await;
+                }
 
                 // mark the produced artifacts as "built" (rather than reused)
                 let artifacts = artifacts.into_iter().map(ProducedArtifact::Built).collect();
@@ -939,6 +1320,202 @@ impl<'a> JobTask<'a> {
         Ok(())
     }
 
+    /// Lock the package of the job for the submit (see `SubmitLocks::lock()`)
+    ///
+    /// Returns whether another submit held the lock.
+    async fn lock_package(&self) -> Result<(ResourceLock<'a>, bool)> {
+        let package = self.jobdef.job.package();
+        self.submit_locks
+            .lock(format!("package {} {}", package.name(), package.version()))
+            .await
+    }
+
+    /// Compute the build cache key for the job
+    ///
+    /// Returns `None` if the digest of the image cannot be determined unambiguously (see
+    /// `EndpointScheduler::image_digest()`), because the artifacts of the job cannot be cached in
+    /// this case. The same holds for jobs on the host, which do not run in the image at all.
+    async fn compute_cache_key(&self, runnable: &RunnableJob) -> Result<Option<CacheKey>> {
+        if self.scheduler.is_local() {
+            trace!("Job {} runs on the host, not caching job", runnable.uuid());
+            return Ok(None);
+        }
+
+        match self.scheduler.image_digest(runnable).await {
+            Some(digest) => CacheKey::compute(
+                runnable,
+                &digest,
+                self.staging_store.clone(),
+                &self.release_stores,
+            )
+            .await
+            .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Find the artifacts of a previous job with the passed cache key
+    ///
+    /// The artifacts of the most recent job are returned for which all artifacts are still
+    /// available in the staging store or one of the release stores.
+    async fn find_cached_artifacts(&self, cache_key: &CacheKey) -> Result<Vec<ArtifactPath>> {
+        use diesel::ExpressionMethods;
+        use diesel::NullableExpressionMethods;
+        use diesel::QueryDsl;
+        use diesel::RunQueryDsl;
+
+        let job_artifacts = crate::schema::artifacts::table
+            .inner_join(crate::schema::jobs::table)
+            .left_join(crate::schema::releases::table)
+            .filter(crate::schema::jobs::cache_key.eq(cache_key.as_ref()))
+            .select((
+                crate::schema::jobs::id,
+                crate::schema::artifacts::path,
+                crate::schema::releases::path.nullable(),
+            ))
+            .load::<(i32, String, Option<String>)>(&mut *self.database.get()?)
+            .with_context(|| anyhow!("Loading artifacts for cache key {}", cache_key))?
+            .into_iter()
+            .map(|(job_id, path, release_path)| (job_id, (path, release_path)))
+            .into_group_map();
+
+        let staging_store = self.staging_store.read().await;
+        let reusable_staging_store = Some(&*staging_store).filter(|_| self.reuse_staging_artifacts);
+        for (_, rows) in job_artifacts.into_iter().sorted_by(|a, b| b.0.cmp(&a.0)) {
+            // artifact path -> paths of the releases of the artifact (if they differ)
+            let artifacts = rows
+                .into_iter()
+                .map(|(path, release_path)| {
+                    let path = ArtifactPath::new(PathBuf::from(path))?;
+                    let release_path = release_path
+                        .map(PathBuf::from)
+                        .map(ArtifactPath::new)
+                        .transpose()?;
+                    Ok((path, release_path))
+                })
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
+                .into_group_map();
+
+            let found = artifacts
+                .iter()
+                .map(|(path, release_paths)| {
+                    reusable_staging_store
+                        .and_then(|store| store.get(path))
+                        .cloned()
+                        .or_else(|| {
+                            release_paths.iter().find_map(|release_path| {
+                                let release_path = release_path.as_ref().unwrap_or(path);
+                                self.release_stores
+                                    .iter()
+                                    .find_map(|rs| rs.get(release_path))
+                                    .cloned()
+                            })
+                        })
+                })
+                .collect::<Option<Vec<ArtifactPath>>>();
+
+            if let Some(found) = found {
+                return Ok(found);
+            }
+        }
+
+        Ok(Vec::new())
+    }
+
+    /// Fetch the artifacts for the passed cache key from the remote cache, if one is configured,
+    /// and record them in the database
+    ///
+    /// Failures to fetch are not fatal (the job is simply run in this case), so they are only
+    /// logged.
+    async fn fetch_from_remote_cache(
+        &self,
+        runnable: &RunnableJob,
+        cache_key: &CacheKey,
+    ) -> Result<Vec<ArtifactPath>> {
+        let Some(remote_cache) = self.remote_cache else {
+            return Ok(Vec::new());
+        };
+
+        // The artifacts are fetched with their own bar (below the bar of the job), because the
+        // bar of the job does not track bytes
+        let bar = match self.progress_generator.bytes_bar() {
+            Ok(bar) => self.multibar.insert_after(&self.bar, bar),
+            Err(e) => {
+                warn!(
+                    job_uuid = %self.jobdef.job.uuid(),
+                    "Failed to create progress bar for remote cache: {:?}",
+                    e
+                );
+                ProgressBar::hidden()
+            }
+        };
+        bar.set_message(format!(
+            "{} {} {} Fetching from remote cache",
+            self.jobdef.job.uuid(),
+            self.jobdef.job.package().name(),
+            self.jobdef.job.package().version()
+        ));
+
+        let result = remote_cache
+            .fetch(
+                cache_key,
+                self.staging_store.clone(),
+                self.jobdef.job.arch().as_deref(),
+                &bar,
+            )
+            .await;
+        bar.finish_and_clear();
+        self.multibar.remove(&bar);
+
+        match result {
+            Ok(Some(artifacts)) => {
+                debug!(
+                    job_uuid = %self.jobdef.job.uuid(),
+                    %cache_key,
+                    "Fetched artifacts from remote cache",
+                );
+                self.scheduler
+                    .record_remote_cache_hit(runnable, cache_key, &artifacts)
+                    .await?;
+                Ok(artifacts)
+            }
+            Ok(None) => Ok(Vec::new()),
+            Err(e) => {
+                warn!(
+                    job_uuid = %self.jobdef.job.uuid(),
+                    "Failed to fetch artifacts from remote cache: {:?}",
+                    e
+                );
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    /// Upload the artifacts of the job to the remote cache, if one is configured
+    ///
+    /// Failures are not fatal (the artifacts were built successfully after all), so they are only
+    /// logged.
+    async fn upload_to_remote_cache(&self, cache_key: &CacheKey, artifacts: &[ArtifactPath]) {
+        if let Some(remote_cache) = self.remote_cache {
+            if let Err(e) = remote_cache
+                .store(
+                    cache_key,
+                    artifacts,
+                    self.staging_store.clone(),
+                    self.jobdef.job.arch().as_deref(),
+                )
+                .await
+            {
+                warn!(
+                    job_uuid = %self.jobdef.job.uuid(),
+                    "Failed to upload artifacts to remote cache: {:?}",
+                    e
+                );
+            }
+        }
+    }
+
     /// Perform a recv() call on the receiving side of the channel
     ///
     /// Put the dependencies you received into the `received_dependencies`, the errors in the
@@ -24,9 +24,13 @@ use petgraph::data::Build;
 use petgraph::graph::DiGraph;
 use petgraph::graph::EdgeIndex;
 use petgraph::graph::NodeIndex;
+use petgraph::visit::Dfs;
+use petgraph::visit::EdgeFiltered;
 use ptree::Style;
 use ptree::TreeItem;
 use resiter::AndThen;
+use serde::Deserialize;
+use serde::Serialize;
 use tracing::trace;
 
 use crate::package::condition::ConditionCheckable;
@@ -46,7 +50,8 @@ pub struct Dag {
     root_idx: NodeIndex,
 }
 
-#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DependencyType {
     Build,
     Runtime,
@@ -264,6 +269,96 @@ impl Dag {
             .collect()
     }
 
+    /// Get the packages of the tree grouped into "waves" of packages that can be built in parallel
+    ///
+    /// The first wave contains all packages without dependencies, every following wave only
+    /// contains packages whose dependencies are all part of one of the previous waves. The last
+    /// wave therefore always contains (only) the root package.
+    ///
+    /// The packages within a wave are sorted by name and version.
+    pub fn waves(&self) -> Vec<Vec<&Package>> {
+        fn wave_of(
+            dag: &Acyclic<DiGraph<Package, DependencyType>>,
+            idx: NodeIndex,
+            cache: &mut HashMap<NodeIndex, usize>,
+        ) -> usize {
+            if let Some(wave) = cache.get(&idx) {
+                return *wave;
+            }
+
+            let wave = dag
+                .neighbors_directed(idx, petgraph::Outgoing)
+                .map(|dep_idx| wave_of(dag, dep_idx, cache) + 1)
+                .max()
+                .unwrap_or(0);
+            cache.insert(idx, wave);
+            wave
+        }
+
+        let mut cache = HashMap::new();
+        let mut waves: Vec<Vec<&Package>> = Vec::new();
+        for idx in self.dag.node_indices() {
+            let wave = wave_of(&self.dag, idx, &mut cache);
+            if waves.len() <= wave {
+                waves.resize_with(wave + 1, Vec::new);
+            }
+            if let Some(p) = self.dag.node_weight(idx) {
+                waves[wave].push(p);
+            }
+        }
+
+        for wave in waves.iter_mut() {
+            wave.sort_by(|a, b| (a.name(), a.version()).cmp(&(b.name(), b.version())));
+        }
+        waves
+    }
+
+    /// Get the packages that are required at runtime of the root package
+    ///
+    /// These are the packages that can be reached from the root package via runtime dependencies
+    /// only, i.e., the build dependencies and everything that is only required by them are
+    /// pruned. The root package itself is not included.
+    ///
+    /// The packages are sorted by name and version.
+    pub fn runtime_closure(&self) -> Vec<&Package> {
+        let runtime_edges = EdgeFiltered::from_fn(self.dag.inner(), |edge| {
+            *edge.weight() == DependencyType::Runtime
+        });
+
+        let mut dfs = Dfs::new(&runtime_edges, self.root_idx);
+        let mut closure = Vec::new();
+        while let Some(idx) = dfs.next(&runtime_edges) {
+            if idx != self.root_idx {
+                closure.push(&self.dag[idx]);
+            }
+        }
+        closure.sort_by(|a, b| (a.name(), a.version()).cmp(&(b.name(), b.version())));
+        closure
+    }
+
+    /// Get the chains of dependencies from the root package to the packages with the name `name`
+    ///
+    /// Every chain is a list of the indices of the packages, starting with the root package and
+    /// ending with a package with the name `name`.
+    pub fn chains_to<'a>(
+        &'a self,
+        name: &'a PackageName,
+    ) -> impl Iterator<Item = Vec<NodeIndex>> + 'a {
+        let graph = self.dag.inner();
+        let root_idx = self.root_idx;
+        graph
+            .node_indices()
+            .filter(move |idx| graph[*idx].name() == name)
+            .flat_map(move |target| {
+                // The graph is acyclic, so the only chain to the root package is the root package
+                std::iter::once(vec![root_idx])
+                    .filter(move |_| target == root_idx)
+                    .chain(petgraph::algo::all_simple_paths::<Vec<_>, _>(
+                        graph, root_idx, target, 0, None,
+                    ))
+            })
+    }
+
     pub fn display(&self) -> DagDisplay {
         DagDisplay(self, self.root_idx, None)
     }
@@ -496,6 +591,147 @@ mod tests {
         assert!(ps.iter().any(|p| *p.name() == pname("p6")));
     }
 
+    /// The DAG of the following (made up) tree:
+    ///
+    ///  a
+    ///   - b
+    ///     - c
+    ///   - c
+    ///   - d
+    fn dag_with_shared_dependency() -> Dag {
+        let mut btree = BTreeMap::new();
+
+        let a = {
+            let name = "a";
+            let vers = "1";
+            let mut pack = package(name, vers, "https://rust-lang.org", "123");
+            {
+                let d1 = Dependency::from(String::from("b =2"));
+                let d2 = Dependency::from(String::from("c =3"));
+                let d3 = Dependency::from(String::from("d =4"));
+                let ds = Dependencies::with_runtime_dependencies(vec![d1, d2, d3]);
+                pack.set_dependencies(ds);
+            }
+            btree.insert((pname(name), pversion(vers)), pack.clone());
+            pack
+        };
+
+        {
+            let name = "b";
+            let vers = "2";
+            let mut pack = package(name, vers, "https://rust-lang.org", "124");
+            {
+                let d1 = Dependency::from(String::from("c =3"));
+                let ds = Dependencies::with_runtime_dependencies(vec![d1]);
+                pack.set_dependencies(ds);
+            }
+            btree.insert((pname(name), pversion(vers)), pack);
+        }
+
+        for (name, vers) in [("c", "3"), ("d", "4")] {
+            let pack = package(name, vers, "https://rust-lang.org", "125");
+            btree.insert((pname(name), pversion(vers)), pack);
+        }
+
+        let repo = Repository::from(btree);
+        let progress = ProgressBar::hidden();
+
+        let condition_data = ConditionData {
+            image_name: None,
+            env: &[],
+        };
+
+        Dag::for_root_package(a, &repo, Some(&progress), &condition_data).unwrap()
+    }
+
+    #[test]
+    fn test_waves() {
+        let dag = dag_with_shared_dependency();
+        let waves = dag
+            .waves()
+            .into_iter()
+            .map(|wave| {
+                wave.into_iter()
+                    .map(|p| p.name().to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            waves,
+            vec![
+                vec![String::from("c"), String::from("d")],
+                vec![String::from("b")],
+                vec![String::from("a")],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chains_to() {
+        let dag = dag_with_shared_dependency();
+        let chains_to = |name: &str| {
+            dag.chains_to(&pname(name))
+                .map(|chain| {
+                    chain
+                        .into_iter()
+                        .map(|idx| dag.dag()[idx].name().to_string())
+                        .join(" -> ")
+                })
+                .sorted()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(chains_to("c"), vec!["a -> b -> c", "a -> c"]);
+        assert_eq!(chains_to("d"), vec!["a -> d"]);
+        assert_eq!(chains_to("a"), vec!["a"]);
+        assert!(chains_to("e").is_empty());
+    }
+
+    #[test]
+    fn test_runtime_closure() {
+        let mut btree = BTreeMap::new();
+
+        //
+        // Test the following (made up) tree, where only b and d are required at runtime of a:
+        //
+        //  a
+        //   - b (runtime)
+        //     - d (runtime)
+        //     - e (build)
+        //   - c (build)
+        //     - f (runtime)
+        //
+        let dependencies = [
+            ("a", "build = [\"c =1\"]\nruntime = [\"b =1\"]"),
+            ("b", "build = [\"e =1\"]\nruntime = [\"d =1\"]"),
+            ("c", "build = []\nruntime = [\"f =1\"]"),
+            ("d", "build = []\nruntime = []"),
+            ("e", "build = []\nruntime = []"),
+            ("f", "build = []\nruntime = []"),
+        ];
+        for (name, deps) in dependencies {
+            let mut pack = package(name, "1", "https://rust-lang.org", "123");
+            pack.set_dependencies(toml::from_str(deps).unwrap());
+            btree.insert((pname(name), pversion("1")), pack);
+        }
+
+        let repo = Repository::from(btree);
+        let a = repo.find(&pname("a"), &pversion("1"))[0].clone();
+        let condition_data = ConditionData {
+            image_name: None,
+            env: &[],
+        };
+        let dag = Dag::for_root_package(a, &repo, None, &condition_data).unwrap();
+
+        let closure = dag
+            .runtime_closure()
+            .into_iter()
+            .map(|p| p.name().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(closure, vec!["b", "d"]);
+    }
+
     #[test]
     fn test_add_deep_package_tree_with_irrelevant_packages() {
         // this is the same test as test_add_deep_package_tree(), but with a bunch of irrelevant
@@ -34,8 +34,9 @@ lazy_static! {
     // validation could and probably should be done when parsing `name` and `version` (can make the
     // errors more precise and we avoid that the regex diverges from the rest of the validation as
     // it's already the case):
+    // The name may be prefixed with a namespace ("team/name").
     pub(in crate::package::dependency)  static ref DEPENDENCY_PARSING_RE: Regex =
-        Regex::new("^(?P<name>[[:alnum:]][[:alnum:]._-]*) (?P<version>[*=><]?[[:alnum:]][[:alnum:][:punct:]]*)$").unwrap();
+        Regex::new("^(?P<name>(?:[[:alnum:]][[:alnum:]._-]*/)?[[:alnum:]][[:alnum:]._-]*) (?P<version>[*=><]?[[:alnum:]][[:alnum:][:punct:]]*)$").unwrap();
 }
 
 /// Helper function for the actual implementation of the ParseDependency trait.
@@ -151,6 +152,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_dependency_with_namespace() {
+        dep_parse_test("team/foo", "1.0");
+        dep_parse_test("team-a.b/foo_bar", "2");
+
+        dep_parse_expect_err("team/ =1");
+        dep_parse_expect_err("/foo =1");
+        dep_parse_expect_err("a/b/c =1");
+    }
+
     #[test]
     fn test_complex_dependency_parsing() {
         dep_parse_test("0ad_", "42");
@@ -0,0 +1,51 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Helpers for the license metadata of packages
+
+use crate::package::glob_matches;
+
+/// The license identifiers in a (SPDX) license expression
+///
+/// The operators ("AND", "OR" and "WITH") and the parentheses are dropped.
+pub fn license_identifiers(expression: &str) -> impl Iterator<Item = &str> {
+    expression
+        .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+        .filter(|token| !token.is_empty())
+        .filter(|token| !matches!(*token, "AND" | "OR" | "WITH"))
+}
+
+/// The license identifiers of `expression` that match one of the (glob) patterns of `deny_list`
+pub fn denied_licenses<'a>(expression: &'a str, deny_list: &[String]) -> Vec<&'a str> {
+    license_identifiers(expression)
+        .filter(|id| deny_list.iter().any(|pattern| glob_matches(pattern, id)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_denied_licenses() {
+        let deny_list = vec![String::from("GPL-3.0*"), String::from("SSPL-1.0")];
+
+        assert!(denied_licenses("MIT", &deny_list).is_empty());
+        assert!(denied_licenses("GPL-2.0-only", &deny_list).is_empty());
+        assert_eq!(
+            denied_licenses("(MIT OR GPL-3.0-or-later) AND SSPL-1.0", &deny_list),
+            vec!["GPL-3.0-or-later", "SSPL-1.0"]
+        );
+        assert_eq!(
+            denied_licenses("Apache-2.0 WITH LLVM-exception", &deny_list),
+            Vec::<&str>::new()
+        );
+    }
+}
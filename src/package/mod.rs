@@ -13,6 +13,9 @@
 mod dependency;
 pub use dependency::*;
 
+mod license;
+pub use license::*;
+
 mod name;
 pub use name::*;
 
@@ -32,5 +35,8 @@ pub use source::*;
 mod dag;
 pub use dag::*;
 
+mod upstream;
+pub use upstream::*;
+
 mod version;
 pub use version::*;
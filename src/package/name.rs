@@ -50,6 +50,36 @@ impl From<String> for PackageName {
 }
 
 impl PackageName {
+    /// The separator between the namespace and the name of a package, e.g. "team/name"
+    pub const NAMESPACE_SEPARATOR: char = '/';
+
+    /// The namespace of the package, e.g. "team" for "team/name"
+    pub fn namespace(&self) -> Option<&str> {
+        self.0
+            .split_once(Self::NAMESPACE_SEPARATOR)
+            .map(|(namespace, _)| namespace)
+    }
+
+    /// The name of the package in the namespace `namespace`
+    pub fn in_namespace(&self, namespace: &str) -> Self {
+        PackageName(format!(
+            "{namespace}{}{}",
+            Self::NAMESPACE_SEPARATOR,
+            self.0
+        ))
+    }
+
+    /// Whether `namespace` can be used as namespace of package names
+    pub fn is_valid_namespace(namespace: &str) -> bool {
+        namespace
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphanumeric())
+            && namespace
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+    }
+
     pub fn parser<'a>() -> PomParser<'a, u8, Self> {
         use crate::util::parser::*;
         (letters() + ((letters() | numbers()).repeat(0..)))
@@ -57,3 +87,26 @@ impl PackageName {
             .convert(|b| String::from_utf8(b.to_vec()).map(Self::from))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_namespace() {
+        let name = PackageName::from(String::from("team/foo"));
+        assert_eq!(name.namespace(), Some("team"));
+
+        let name = PackageName::from(String::from("foo"));
+        assert_eq!(name.namespace(), None);
+        assert_eq!(
+            name.in_namespace("team"),
+            PackageName::from(String::from("team/foo"))
+        );
+
+        assert!(PackageName::is_valid_namespace("team-a.b_c"));
+        assert!(!PackageName::is_valid_namespace(""));
+        assert!(!PackageName::is_valid_namespace("-team"));
+        assert!(!PackageName::is_valid_namespace("team/a"));
+    }
+}
@@ -23,9 +23,11 @@ use crate::package::dependency::*;
 use crate::package::name::*;
 use crate::package::source::*;
 use crate::package::version::*;
-use crate::package::{Phase, PhaseName};
+use crate::package::Upstream;
+use crate::package::{Phase, PhaseModifications, PhaseName};
 use crate::repository::normalize_relative_path;
 use crate::util::docker::ImageName;
+use crate::util::docker::Mount;
 use crate::util::EnvironmentVariableName;
 
 #[derive(Clone, Serialize, Deserialize, Getters)]
@@ -53,6 +55,14 @@ pub struct Package {
     #[serde(skip_serializing_if = "Option::is_none")]
     environment: Option<HashMap<EnvironmentVariableName, String>>,
 
+    /// Dotenv files (relative to the pkg.toml that declares them) with additional environment
+    ///
+    /// The variables are merged into `environment` when the package is loaded. Variables that are
+    /// set in `environment` take precedence, as do variables from later files.
+    #[getset(get = "pub")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    env_files: Option<Vec<PathBuf>>,
+
     #[getset(get = "pub")]
     #[serde(skip_serializing_if = "Option::is_none")]
     allowed_images: Option<Vec<ImageName>>,
@@ -64,6 +74,53 @@ pub struct Package {
     #[getset(get = "pub")]
     phases: HashMap<PhaseName, Phase>,
 
+    /// The pkg.toml files that define the phases (set when the package is loaded)
+    #[getset(get = "pub")]
+    #[serde(skip)]
+    phase_origins: HashMap<PhaseName, PathBuf>,
+
+    /// The pkg.toml file that defines the package (set when the package is loaded)
+    #[getset(get = "pub")]
+    #[serde(skip)]
+    origin: PathBuf,
+
+    /// The pkg.toml files that are merged into the package, from the root of the repository down
+    /// to `origin` (set when the package is loaded)
+    #[getset(get = "pub")]
+    #[serde(skip)]
+    layers: Vec<PathBuf>,
+
+    /// The repository overlay the package is loaded from, if it does not come from the repository
+    /// itself
+    #[getset(get = "pub")]
+    #[serde(skip)]
+    overlay: Option<PathBuf>,
+
+    /// The maintainer of the package definition, e.g. "Jane Doe <jane@example.com>"
+    #[getset(get = "pub")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    maintainer: Option<String>,
+
+    /// The license of the package, preferably as SPDX license expression
+    #[getset(get = "pub")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    license: Option<String>,
+
+    /// A short description of the package
+    #[getset(get = "pub")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+
+    /// The homepage of the project the package is built from
+    #[getset(get = "pub")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    homepage: Option<String>,
+
+    /// Where to look for new versions of the package (`butido outdated`)
+    #[getset(get = "pub")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    upstream: Option<Upstream>,
+
     /// Meta field
     ///
     /// Contains only key-value string-string data, that the packager can set for a package and
@@ -72,8 +129,73 @@ pub struct Package {
     #[getset(get = "pub")]
     #[serde(skip_serializing_if = "Option::is_none")]
     meta: Option<HashMap<String, String>>,
+
+    /// Classification of the artifacts of the package
+    ///
+    /// Maps glob patterns (matched against the file name of an artifact) to the kind of the
+    /// artifact (e.g. "debug" or "docs"). Artifacts that match no pattern are of the kind
+    /// "default".
+    #[getset(get = "pub")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    artifact_kinds: Option<HashMap<String, String>>,
+
+    /// The artifacts that the package is expected to produce
+    ///
+    /// Glob patterns (matched against the file name of an artifact). Every pattern must match at
+    /// least one artifact of a job and every artifact must match one of the patterns, otherwise
+    /// the job fails.
+    #[getset(get = "pub")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    outputs: Option<Vec<String>>,
+
+    /// Priority of the jobs of the package in the job queue
+    ///
+    /// This is added to the priority of the submit, jobs with a higher priority are dispatched
+    /// first.
+    #[getset(get = "pub")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<i32>,
+
+    /// Host paths that are mounted into the build containers of the package
+    ///
+    /// The paths must be allowed in the configuration (`containers.allowed_mounts`).
+    #[getset(get = "pub")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mounts: Option<Vec<Mount>>,
+
+    /// Resource limits of the build containers of the package (only applied on Kubernetes
+    /// endpoints)
+    #[getset(get = "pub")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resources: Option<ResourceLimits>,
+
+    /// The tags an endpoint must have to build the package (e.g. "fast-disk")
+    #[getset(get = "pub")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    endpoint_tags: Option<Vec<String>>,
+
+    /// The architectures the package can be built for (all architectures if not set)
+    #[getset(get = "pub")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    archs: Option<Vec<String>>,
+}
+
+/// Resource limits of a build container, in the notation of Kubernetes (e.g. "500m" or "8Gi")
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Getters)]
+#[serde(deny_unknown_fields)]
+pub struct ResourceLimits {
+    #[getset(get = "pub")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cpu: Option<String>,
+
+    #[getset(get = "pub")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memory: Option<String>,
 }
 
+/// The kind of artifacts that do not match any of the `artifact_kinds` patterns of a package
+pub const DEFAULT_ARTIFACT_KIND: &str = "default";
+
 impl std::hash::Hash for Package {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.name.hash(state);
@@ -98,10 +220,27 @@ impl Package {
             dependencies,
             patches: vec![],
             environment: None,
+            env_files: None,
             allowed_images: None,
             denied_images: None,
             phases: HashMap::new(),
+            phase_origins: HashMap::new(),
+            origin: PathBuf::new(),
+            layers: vec![],
+            overlay: None,
+            maintainer: None,
+            license: None,
+            description: None,
+            homepage: None,
+            upstream: None,
             meta: None,
+            artifact_kinds: None,
+            outputs: None,
+            priority: None,
+            mounts: None,
+            resources: None,
+            endpoint_tags: None,
+            archs: None,
         }
     }
 
@@ -146,6 +285,136 @@ impl Package {
         Ok(())
     }
 
+    pub fn set_phase_origins(&mut self, phase_origins: HashMap<PhaseName, PathBuf>) {
+        self.phase_origins = phase_origins;
+    }
+
+    pub fn set_name(&mut self, name: PackageName) {
+        self.name = name;
+    }
+
+    pub fn set_origin(&mut self, layers: Vec<PathBuf>, overlay: Option<PathBuf>) {
+        self.origin = layers.last().cloned().unwrap_or_default();
+        self.layers = layers;
+        self.overlay = overlay;
+    }
+
+    /// Skip and override phases as requested when submitting a build
+    ///
+    /// The modified phases are replaced by scripts that start with a comment that describes the
+    /// modification, so that it is visible in the script of the jobs.
+    pub fn modify_phases(&mut self, modifications: &PhaseModifications) {
+        for name in modifications.skipped() {
+            let script = format!("# Phase skipped at submit time: {}", name.as_str());
+            self.phases.insert(name.clone(), Phase::Text(script));
+        }
+
+        for (name, path, content) in modifications.overridden() {
+            let script = format!(
+                "# Phase overridden at submit time with {}: {}\n{}",
+                path.display(),
+                name.as_str(),
+                content
+            );
+            self.phases.insert(name.clone(), Phase::Text(script));
+        }
+    }
+
+    /// Merge the variables from the `env_files` into the environment of the package
+    pub fn load_env_files(&mut self, base_dir: &Path) -> Result<()> {
+        let mut environment = HashMap::new();
+        for file in self.env_files.iter().flatten() {
+            environment.extend(crate::util::env::read_env_file(&base_dir.join(file))?);
+        }
+        environment.extend(self.environment.take().unwrap_or_default());
+        self.environment = Some(environment);
+        Ok(())
+    }
+
+    /// Get the kind of an artifact of this package
+    ///
+    /// If multiple patterns match the file name of the artifact, the longest (most specific)
+    /// pattern wins.
+    pub fn artifact_kind(&self, artifact: &Path) -> &str {
+        let Some(file_name) = artifact.file_name().and_then(|f| f.to_str()) else {
+            return DEFAULT_ARTIFACT_KIND;
+        };
+
+        self.artifact_kinds
+            .iter()
+            .flatten()
+            .filter(|(pattern, _)| glob_matches(pattern, file_name))
+            .max_by(|a, b| a.0.len().cmp(&b.0.len()).then_with(|| b.0.cmp(a.0)))
+            .map(|(_, kind)| kind.as_str())
+            .unwrap_or(DEFAULT_ARTIFACT_KIND)
+    }
+
+    /// Check the artifacts of a job of this package against the declared `outputs`
+    ///
+    /// Fails with a list of the patterns that match no artifact and of the artifacts that match
+    /// no pattern.
+    pub fn check_outputs<'a, I>(&self, artifacts: I) -> Result<()>
+    where
+        I: IntoIterator<Item = &'a Path>,
+    {
+        let Some(patterns) = self.outputs.as_ref() else {
+            return Ok(());
+        };
+
+        let file_names = artifacts
+            .into_iter()
+            .map(|artifact| {
+                (
+                    artifact,
+                    artifact
+                        .file_name()
+                        .and_then(|f| f.to_str())
+                        .unwrap_or_default(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let missing = patterns
+            .iter()
+            .filter(|pattern| {
+                !file_names
+                    .iter()
+                    .any(|(_, file_name)| glob_matches(pattern, file_name))
+            })
+            .map(|pattern| format!("\n  missing: {pattern}"));
+        let unexpected = file_names
+            .iter()
+            .filter(|(_, file_name)| {
+                !patterns
+                    .iter()
+                    .any(|pattern| glob_matches(pattern, file_name))
+            })
+            .map(|(artifact, _)| format!("\n  unexpected: {}", artifact.display()));
+        let problems = missing.chain(unexpected).collect::<String>();
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "The artifacts of {} {} do not match the declared outputs ({}):{}",
+                self.name,
+                self.version,
+                patterns.join(", "),
+                problems
+            ))
+        }
+    }
+
+    #[cfg(test)]
+    pub fn set_outputs(&mut self, outputs: Vec<String>) {
+        self.outputs = Some(outputs);
+    }
+
+    #[cfg(test)]
+    pub fn set_artifact_kinds(&mut self, artifact_kinds: HashMap<String, String>) {
+        self.artifact_kinds = Some(artifact_kinds);
+    }
+
     #[cfg(test)]
     pub fn set_dependencies(&mut self, dependencies: Dependencies) {
         self.dependencies = dependencies;
@@ -159,6 +428,40 @@ impl Package {
     }
 }
 
+/// Check whether `name` matches the glob `pattern`
+///
+/// Supports `*` (any number of characters) and `?` (exactly one character).
+pub fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+
+    // Iterative matching with backtracking to the last `*`
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
 impl std::fmt::Debug for Package {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
         if self.patches().is_empty() {
@@ -350,4 +653,49 @@ pub mod tests {
         let dependencies = Dependencies::empty();
         Package::new(name, version, version_is_semver, sources, dependencies)
     }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("*", "foo.tar.gz"));
+        assert!(glob_matches("*.tar.gz", "foo.tar.gz"));
+        assert!(glob_matches("foo-?.?.tar.gz", "foo-1.0.tar.gz"));
+        assert!(glob_matches("*-doc*", "foo-doc-1.0.tar.gz"));
+        assert!(!glob_matches("*.debug", "foo.tar.gz"));
+        assert!(!glob_matches("foo-?.tar.gz", "foo-10.tar.gz"));
+    }
+
+    #[test]
+    fn test_artifact_kind() {
+        let mut p = package("foo", "1.0", "https://rust-lang.org", "123");
+        assert_eq!(p.artifact_kind(Path::new("foo-1.0.tar.gz")), "default");
+
+        let mut kinds = HashMap::new();
+        kinds.insert(String::from("*.debug.tar.gz"), String::from("debug"));
+        kinds.insert(String::from("*-doc-*"), String::from("docs"));
+        kinds.insert(String::from("*"), String::from("binary"));
+        p.set_artifact_kinds(kinds);
+
+        assert_eq!(p.artifact_kind(Path::new("dir/foo-1.0.tar.gz")), "binary");
+        assert_eq!(p.artifact_kind(Path::new("foo-1.0.debug.tar.gz")), "debug");
+        assert_eq!(p.artifact_kind(Path::new("foo-doc-1.0.tar.gz")), "docs");
+    }
+
+    #[test]
+    fn test_check_outputs() {
+        let mut p = package("foo", "1.0", "https://rust-lang.org", "123");
+        assert!(p.check_outputs([Path::new("whatever.txt")]).is_ok());
+
+        p.set_outputs(vec![String::from("*.pkg.tar.xz"), String::from("*.sig")]);
+        assert!(p
+            .check_outputs([Path::new("foo-1.0.pkg.tar.xz"), Path::new("foo-1.0.sig")])
+            .is_ok());
+
+        let err = p
+            .check_outputs([Path::new("foo-1.0.pkg.tar.xz"), Path::new("dir/build.log")])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("missing: *.sig"), "{err}");
+        assert!(err.contains("unexpected: dir/build.log"), "{err}");
+        assert!(!err.contains("unexpected: foo-1.0.pkg.tar.xz"), "{err}");
+    }
 }
@@ -10,6 +10,9 @@
 
 use std::path::PathBuf;
 
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
 use serde::Deserialize;
 use serde::Serialize;
 
@@ -23,7 +26,6 @@ impl PhaseName {
     }
 }
 
-#[cfg(test)]
 impl From<String> for PhaseName {
     fn from(s: String) -> Self {
         PhaseName(s)
@@ -38,3 +40,135 @@ pub enum Phase {
     #[serde(rename = "script")]
     Text(String),
 }
+
+/// Modifications of the phases of the requested package, passed when submitting a build
+///
+/// Skipped phases are not executed, overridden phases execute the content of a local script
+/// instead of the script from the pkg.toml.
+#[derive(Clone, Debug, Default)]
+pub struct PhaseModifications {
+    skipped: Vec<PhaseName>,
+    overridden: Vec<(PhaseName, PathBuf, String)>,
+}
+
+impl PhaseModifications {
+    /// Create the modifications from the "--skip-phase" ("NAME") and "--override-phase"
+    /// ("NAME=FILE") arguments, reading the override scripts
+    pub fn new<'a>(
+        skip: impl Iterator<Item = &'a String>,
+        overrides: impl Iterator<Item = &'a String>,
+        available_phases: &[PhaseName],
+    ) -> Result<Self> {
+        let check_available = |name: PhaseName| -> Result<PhaseName> {
+            if available_phases.contains(&name) {
+                Ok(name)
+            } else {
+                Err(anyhow!(
+                    "Phase '{}' is not one of the available phases: {}",
+                    name.as_str(),
+                    available_phases
+                        .iter()
+                        .map(PhaseName::as_str)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+            }
+        };
+
+        let skipped = skip
+            .map(|name| check_available(PhaseName::from(name.clone())))
+            .collect::<Result<Vec<_>>>()?;
+
+        let overridden = overrides
+            .map(|s| {
+                let (name, path) = parse_override(s)?;
+                let name = check_available(name)?;
+                if skipped.contains(&name) {
+                    return Err(anyhow!(
+                        "Phase '{}' cannot be skipped and overridden at the same time",
+                        name.as_str()
+                    ));
+                }
+                let script = std::fs::read_to_string(&path).with_context(|| {
+                    anyhow!(
+                        "Reading script {} for phase '{}'",
+                        path.display(),
+                        name.as_str()
+                    )
+                })?;
+                Ok((name, path, script))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(PhaseModifications {
+            skipped,
+            overridden,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.skipped.is_empty() && self.overridden.is_empty()
+    }
+
+    pub fn skipped(&self) -> &[PhaseName] {
+        &self.skipped
+    }
+
+    /// The overridden phases with the path and content of the script that replaces them
+    pub fn overridden(&self) -> &[(PhaseName, PathBuf, String)] {
+        &self.overridden
+    }
+
+    /// Human readable description of the modifications (one entry per modified phase)
+    pub fn describe(&self) -> Vec<String> {
+        self.skipped
+            .iter()
+            .map(|name| format!("skip {}", name.as_str()))
+            .chain(
+                self.overridden.iter().map(|(name, path, _)| {
+                    format!("override {}={}", name.as_str(), path.display())
+                }),
+            )
+            .collect()
+    }
+}
+
+/// Parse an override of a phase in the form "NAME=FILE"
+fn parse_override(s: &str) -> Result<(PhaseName, PathBuf)> {
+    match s.split_once('=') {
+        Some((name, path)) if !name.is_empty() && !path.is_empty() => {
+            Ok((PhaseName::from(name.to_string()), PathBuf::from(path)))
+        }
+        _ => Err(anyhow!(
+            "Invalid phase override '{}', expected the form NAME=FILE",
+            s
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_override() {
+        let (name, path) = parse_override("install=./local-install.sh").unwrap();
+        assert_eq!(name.as_str(), "install");
+        assert_eq!(path, PathBuf::from("./local-install.sh"));
+
+        assert!(parse_override("install").is_err());
+        assert!(parse_override("=./local-install.sh").is_err());
+        assert!(parse_override("install=").is_err());
+    }
+
+    #[test]
+    fn test_skipped_phase_must_be_available() {
+        let available = vec![PhaseName::from(String::from("build"))];
+        let skip = [String::from("build")];
+        let mods = PhaseModifications::new(skip.iter(), std::iter::empty(), &available).unwrap();
+        assert_eq!(mods.describe(), vec![String::from("skip build")]);
+
+        let skip = [String::from("test")];
+        assert!(PhaseModifications::new(skip.iter(), std::iter::empty(), &available).is_err());
+    }
+}
@@ -33,6 +33,12 @@ pub struct Source {
     #[serde(default = "default_download_manually")]
     #[getset(get = "pub")]
     download_manually: bool,
+
+    /// Whether the source is an archive that is extracted into the build directory of the
+    /// container, instead of being copied to the inputs directory as-is
+    #[serde(default)]
+    #[getset(get = "pub")]
+    extract: bool,
 }
 
 impl Source {
@@ -42,6 +48,7 @@ impl Source {
             url,
             hash,
             download_manually: false,
+            extract: false,
         }
     }
 }
@@ -102,7 +109,7 @@ pub enum HashType {
 }
 
 impl HashType {
-    async fn hash_from_reader<R: tokio::io::AsyncRead + Unpin>(
+    pub async fn hash_from_reader<R: tokio::io::AsyncRead + Unpin>(
         &self,
         mut reader: R,
     ) -> Result<HashValue> {
@@ -0,0 +1,132 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+use std::cmp::Ordering;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use getset::Getters;
+use regex::Regex;
+use serde::Deserialize;
+use serde::Serialize;
+use url::Url;
+
+/// Where to look for new versions of a package (`butido outdated`)
+///
+/// Either the releases of a GitHub repository (`github = "owner/repository"`) or the versions that
+/// `regex` finds on a web page (`url`, e.g. a directory listing). The first capture group of the
+/// regex is the version.
+#[derive(Clone, Debug, Serialize, Deserialize, Getters)]
+#[serde(deny_unknown_fields)]
+pub struct Upstream {
+    #[getset(get = "pub")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    github: Option<String>,
+
+    #[getset(get = "pub")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<Url>,
+
+    #[getset(get = "pub")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    regex: Option<String>,
+}
+
+/// The default regex for the tags of GitHub releases
+const GITHUB_TAG_REGEX: &str = r"^v?(\d[\w.\-]*)$";
+
+impl Upstream {
+    /// The regex that extracts the version from the tags or the web page
+    pub fn version_regex(&self) -> Result<Regex> {
+        let regex = match (self.github.as_ref(), self.regex.as_ref()) {
+            (_, Some(regex)) => regex.as_str(),
+            (Some(_), None) => GITHUB_TAG_REGEX,
+            (None, None) => return Err(anyhow!("Upstream with a URL but without a regex")),
+        };
+
+        let regex = Regex::new(regex)?;
+        if regex.captures_len() < 2 {
+            return Err(anyhow!(
+                "The upstream regex \"{}\" has no capture group for the version",
+                regex
+            ));
+        }
+        Ok(regex)
+    }
+
+    /// All versions that `regex` finds in `text`
+    pub fn find_versions<'t>(regex: &Regex, text: &'t str) -> impl Iterator<Item = &'t str> {
+        regex
+            .captures_iter(text)
+            .filter_map(|captures| captures.get(1))
+            .map(|m| m.as_str())
+    }
+}
+
+/// Compare two versions, numerical parts are compared as numbers ("1.10" > "1.9")
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    fn parts(v: &str) -> Vec<&str> {
+        let mut parts = Vec::new();
+        let mut start = 0;
+        let mut chars = v.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            let next_is_digit = chars.peek().map(|(_, n)| n.is_ascii_digit());
+            if next_is_digit.is_some_and(|d| d != c.is_ascii_digit()) {
+                parts.push(&v[start..=i]);
+                start = i + c.len_utf8();
+            }
+        }
+        parts.push(&v[start..]);
+        parts
+            .into_iter()
+            .filter(|p| !p.is_empty() && !matches!(*p, "." | "-" | "_"))
+            .collect()
+    }
+
+    let (a, b) = (parts(a), parts(b));
+    for (x, y) in a.iter().zip(b.iter()) {
+        let ord = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            _ => x.cmp(y),
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("1.10", "1.9"), Ordering::Greater);
+        assert_eq!(compare_versions("1.2.3", "1.2.3"), Ordering::Equal);
+        assert_eq!(compare_versions("1.2", "1.2.1"), Ordering::Less);
+        assert_eq!(compare_versions("2.0.0.1", "2.0.0"), Ordering::Greater);
+        assert_eq!(compare_versions("19.0", "19.1"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_find_versions_in_directory_listing() {
+        let upstream = Upstream {
+            github: None,
+            url: Some(Url::parse("https://example.com/foo/").unwrap()),
+            regex: Some(String::from(r"foo-(\d[\d.]*)\.tar\.gz")),
+        };
+        let listing = r#"<a href="foo-1.9.tar.gz">foo-1.9.tar.gz</a> <a href="foo-1.10.tar.gz">"#;
+
+        let regex = upstream.version_regex().unwrap();
+        let newest = Upstream::find_versions(&regex, listing).max_by(|a, b| compare_versions(a, b));
+        assert_eq!(newest, Some("1.10"));
+    }
+}
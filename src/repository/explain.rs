@@ -0,0 +1,198 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Explain where the (merged) values of a package definition come from
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use config::Config;
+use config::Map;
+use config::Source;
+use config::Value;
+use config::ValueKind;
+use getset::Getters;
+
+use crate::package::Package;
+use crate::repository::pkg_toml_source::PkgTomlSource;
+
+/// An effective field of a package and the pkg.toml file (layer) it comes from
+#[derive(Debug, Getters)]
+pub struct ExplainedField {
+    /// The path of the field, e.g. "sources.src.url"
+    #[getset(get = "pub")]
+    key: String,
+
+    #[getset(get = "pub")]
+    value: String,
+
+    /// The level of the layer the value comes from (0 is the first layer, usually the pkg.toml
+    /// file at the root of the repository)
+    #[getset(get = "pub")]
+    level: usize,
+
+    /// The pkg.toml file the value comes from
+    #[getset(get = "pub")]
+    origin: PathBuf,
+
+    /// The levels of the layers that define the field as well, but are overridden
+    #[getset(get = "pub")]
+    overrides: Vec<usize>,
+}
+
+impl ExplainedField {
+    /// Whether the value is inherited from a layer above the pkg.toml file of the package
+    pub fn is_inherited(&self, package: &Package) -> bool {
+        self.origin != *package.origin()
+    }
+}
+
+/// Explain all effective fields of a package, sorted by their path
+pub fn explain(package: &Package) -> Result<Vec<ExplainedField>> {
+    let layers = package
+        .layers()
+        .iter()
+        .map(|path| {
+            std::fs::read_to_string(path)
+                .with_context(|| anyhow!("Reading {}", path.display()))
+                .map(|content| PkgTomlSource::new(path, content))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // Remember all layers that define a field to find the overridden values:
+    let mut defined_in: HashMap<String, Vec<usize>> = HashMap::new();
+    for (level, layer) in layers.iter().enumerate() {
+        let mut fields = BTreeMap::new();
+        flatten("", &layer.collect()?, &mut fields);
+        for key in fields.into_keys() {
+            defined_in.entry(key).or_default().push(level);
+        }
+    }
+
+    let merged = layers
+        .into_iter()
+        .fold(Config::builder(), |builder, layer| {
+            builder.add_source(layer)
+        })
+        .build()
+        .with_context(|| anyhow!("Merging the layers of {}", package.origin().display()))?;
+    let mut fields = BTreeMap::new();
+    flatten("", &merged.collect()?, &mut fields);
+
+    fields
+        .into_iter()
+        .map(|(key, value)| {
+            let origin = value
+                .origin()
+                .map(PathBuf::from)
+                .ok_or_else(|| anyhow!("Bug: The value of {} has no origin", key))?;
+            let level = package
+                .layers()
+                .iter()
+                .position(|layer| *layer == origin)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Bug: The origin of {} is not a layer: {}",
+                        key,
+                        origin.display()
+                    )
+                })?;
+            let overrides = defined_in
+                .get(&key)
+                .map(|levels| levels.iter().copied().filter(|l| *l < level).collect())
+                .unwrap_or_default();
+
+            Ok(ExplainedField {
+                value: display_value(&value),
+                key,
+                level,
+                origin,
+                overrides,
+            })
+        })
+        .collect()
+}
+
+/// Flatten the (nested) tables into fields with dotted paths
+fn flatten(prefix: &str, table: &Map<String, Value>, out: &mut BTreeMap<String, Value>) {
+    for (key, value) in table.iter() {
+        let key = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+
+        match value.kind {
+            ValueKind::Table(ref table) => flatten(&key, table, out),
+            _ => {
+                out.insert(key, value.clone());
+            }
+        }
+    }
+}
+
+fn display_value(value: &Value) -> String {
+    match value.kind {
+        ValueKind::Array(ref values) => {
+            let values = values.iter().map(display_value).collect::<Vec<_>>();
+            format!("[{}]", values.join(", "))
+        }
+        ValueKind::Table(ref table) => {
+            let mut fields = table
+                .iter()
+                .map(|(k, v)| format!("{k} = {}", display_value(v)))
+                .collect::<Vec<_>>();
+            fields.sort();
+            format!("{{ {} }}", fields.join(", "))
+        }
+        _ => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::package::tests::pname;
+    use crate::package::tests::pversion;
+    use crate::repository::Repository;
+
+    #[test]
+    fn test_explain_example_package() -> Result<()> {
+        let repo = Repository::load(
+            &PathBuf::from("examples/packages/repo/"),
+            &[],
+            &std::collections::HashMap::new(),
+            &indicatif::ProgressBar::hidden(),
+        )?;
+        let pkgs = repo.find(&pname("s"), &pversion("19.0"));
+        let s = pkgs.first().unwrap();
+
+        let fields = explain(s)?;
+        let field = |key: &str| fields.iter().find(|f| f.key() == key).unwrap();
+
+        // The patches are declared in s/pkg.toml and overridden in s/19.0/pkg.toml:
+        let patches = field("patches");
+        assert_eq!(*patches.level(), 2);
+        assert_eq!(patches.overrides(), &vec![0, 1]);
+        assert!(!patches.is_inherited(s));
+
+        // The build phase is inherited from the root of the repository:
+        let build = field("phases.build.script");
+        assert_eq!(*build.level(), 0);
+        assert!(build.overrides().is_empty());
+        assert!(build.is_inherited(s));
+
+        Ok(())
+    }
+}
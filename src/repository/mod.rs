@@ -12,5 +12,8 @@
 mod repository;
 pub use repository::*;
 
+mod explain;
+pub use explain::*;
+
 mod fs;
 mod pkg_toml_source;
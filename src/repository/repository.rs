@@ -9,6 +9,7 @@
 //
 
 use std::collections::BTreeMap;
+use std::collections::HashMap;
 use std::path::Component;
 use std::path::Path;
 use std::path::PathBuf;
@@ -18,6 +19,7 @@ use anyhow::Context;
 use anyhow::Error;
 use anyhow::Result;
 use regex::Regex;
+use tracing::info;
 use tracing::trace;
 
 use crate::package::Package;
@@ -93,12 +95,111 @@ pub fn normalize_relative_path(path: PathBuf) -> Result<PathBuf> {
     Ok(normalized_path)
 }
 
+/// Put a package into the namespace of the subtree of the repository it is defined in
+///
+/// `relative_path` is the path of the pkg.toml file of the package, relative to the root of the
+/// repository (or overlay). A package without namespace in its name gets the namespace of the
+/// subtree, a package with another namespace in its name is an error. The namespaces of subtrees
+/// must not be used by packages outside of the subtree.
+fn apply_namespace(
+    pkg: &mut Package,
+    relative_path: &Path,
+    namespaces: &HashMap<String, PathBuf>,
+) -> Result<()> {
+    // The innermost subtree wins if the subtrees are nested
+    let subtree_namespace = namespaces
+        .iter()
+        .filter(|(_, subtree)| relative_path.starts_with(subtree))
+        .max_by_key(|(_, subtree)| subtree.components().count())
+        .map(|(namespace, _)| namespace.as_str());
+
+    match (pkg.name().namespace(), subtree_namespace) {
+        (None, Some(namespace)) => {
+            let name = pkg.name().in_namespace(namespace);
+            pkg.set_name(name);
+            Ok(())
+        }
+        (Some(declared), Some(namespace)) if declared != namespace => Err(anyhow!(
+            "Package {} is defined below the subtree of namespace {}",
+            pkg.name(),
+            namespace
+        )),
+        (Some(declared), None) if namespaces.contains_key(declared) => Err(anyhow!(
+            "Package {} is not defined below the subtree of its namespace ({})",
+            pkg.name(),
+            namespaces[declared].display()
+        )),
+        _ => Ok(()),
+    }
+}
+
 impl Repository {
     fn new(inner: BTreeMap<(PackageName, PackageVersion), Package>) -> Self {
         Repository { inner }
     }
 
-    pub fn load(path: &Path, progress: &indicatif::ProgressBar) -> Result<Self> {
+    /// Load the repository at `path` and layer the `overlays` over it
+    ///
+    /// The overlays are package-definition roots with the same layout as the repository. A package
+    /// that is defined in an overlay replaces the package with the same name and version from the
+    /// repository and from all earlier overlays. The same package must not be defined in more than
+    /// one overlay, though, as that is most likely a mistake.
+    ///
+    /// The packages below the subtrees in `namespaces` (relative to the repository and to each
+    /// overlay) are put into the namespace of the subtree (see `apply_namespace()`).
+    pub fn load(
+        path: &Path,
+        overlays: &[PathBuf],
+        namespaces: &HashMap<String, PathBuf>,
+        progress: &indicatif::ProgressBar,
+    ) -> Result<Self> {
+        let mut inner = Self::load_root(path, None, namespaces, progress)?;
+
+        let mut overlay_packages: BTreeMap<(PackageName, PackageVersion), PathBuf> =
+            BTreeMap::new();
+        for overlay in overlays {
+            let overlay_path = path.join(overlay);
+            if !overlay_path.is_dir() {
+                return Err(anyhow!(
+                    "Repository overlay is not a directory: {}",
+                    overlay_path.display()
+                ));
+            }
+
+            for (key, pkg) in Self::load_root(&overlay_path, Some(overlay), namespaces, progress)? {
+                if let Some(other) = overlay_packages.get(&key) {
+                    return Err(anyhow!(
+                        "Package {} {} is defined in the repository overlays {} and {}",
+                        key.0,
+                        key.1,
+                        other.display(),
+                        overlay.display()
+                    ));
+                }
+
+                if inner.contains_key(&key) {
+                    info!(
+                        "Package {} {} is overridden by the repository overlay {}",
+                        key.0,
+                        key.1,
+                        overlay.display()
+                    );
+                }
+                overlay_packages.insert(key.clone(), overlay.clone());
+                inner.insert(key, pkg);
+            }
+        }
+
+        Ok(Repository::new(inner))
+    }
+
+    /// Load the packages from a single package-definition root
+    fn load_root(
+        path: &Path,
+        overlay: Option<&PathBuf>,
+        namespaces: &HashMap<String, PathBuf>,
+        progress: &indicatif::ProgressBar,
+    ) -> Result<BTreeMap<(PackageName, PackageVersion), Package>> {
         use crate::repository::fs::FileSystemRepresentation;
         use config::Config;
         use rayon::iter::IntoParallelRefIterator;
@@ -108,6 +209,7 @@ impl Repository {
         let fsr = FileSystemRepresentation::load(path.to_path_buf())?;
 
         let cwd = std::env::current_dir()?;
+        let root = path;
         let leaf_files = fsr
             .files()
             .par_iter()
@@ -117,13 +219,14 @@ impl Repository {
                 Ok(false) => None,
                 Err(e) => Some(Err(e)),
             });
-        progress.set_length(leaf_files.clone().count().try_into()?);
+        progress.inc_length(leaf_files.clone().count().try_into()?);
         leaf_files
             .inspect(|r| trace!("Loading files for {:?}", r))
             .map(|path| {
                 progress.inc(1);
                 let path = path?;
-                let config = fsr.get_files_for(path)?
+                let layers = fsr.get_files_for(path)?;
+                let config = layers
                     .iter()
                     // Load all "layers":
                     .inspect(|(path, _)| trace!("Loading layer at {}", path.display()))
@@ -134,6 +237,7 @@ impl Repository {
                     .build()?;
 
                 let patches_value = config.get_array("patches");
+                let env_files_value = config.get_array("env_files");
                 let mut pkg = config
                     .try_deserialize::<Package>()
                     .map_err(Error::from)
@@ -141,6 +245,11 @@ impl Repository {
                         anyhow!("Could not load package configuration: {}", path.display())
                     })?;
 
+                apply_namespace(&mut pkg, path.strip_prefix(root).unwrap_or(path), namespaces)
+                    .with_context(|| {
+                        anyhow!("Could not load package configuration: {}", path.display())
+                    })?;
+
                 if !pkg.patches().is_empty() {
                     // We have to build the full relative paths to the patch files by
                     // prepending the path to the directory of the `pkg.toml` file they've
@@ -183,10 +292,38 @@ impl Repository {
                     }
                 }
 
+                // Remember where the phases are defined (e.g., to map linter findings back)
+                let phase_origins = pkg
+                    .phases()
+                    .keys()
+                    .filter_map(|name| {
+                        let value = config.get::<config::Value>(&format!("phases.{}", name.as_str())).ok()?;
+                        value.origin().map(|origin| (name.clone(), PathBuf::from(origin)))
+                    })
+                    .collect();
+                pkg.set_phase_origins(phase_origins);
+
+                if pkg.env_files().is_some() {
+                    // The env files are relative to the `pkg.toml` file they've been defined in
+                    // (like the patches above)
+                    let origin_dir_path = env_files_value
+                        .ok()
+                        .and_then(|files| files.first().and_then(|f| f.origin().map(PathBuf::from)))
+                        .and_then(|origin| origin.parent().map(Path::to_path_buf))
+                        .unwrap_or_default();
+                    pkg.load_env_files(&origin_dir_path).with_context(|| {
+                        anyhow!("Could not load the env files declared here: {}", path.display())
+                    })?;
+                }
+
+                pkg.set_origin(
+                    layers.into_iter().map(|(path, _)| path).collect(),
+                    overlay.cloned(),
+                );
+
                 Ok(((pkg.name().clone(), pkg.version().clone()), pkg))
             })
             .collect::<Result<BTreeMap<_, _>>>()
-            .map(Repository::new)
     }
 
     pub fn find_by_name<'a>(&'a self, name: &PackageName) -> Vec<&'a Package> {
@@ -376,6 +513,8 @@ pub mod tests {
 
         let repo = Repository::load(
             &PathBuf::from("examples/packages/repo/"),
+            &[],
+            &HashMap::new(),
             &indicatif::ProgressBar::hidden(),
         )?;
 
@@ -421,6 +560,75 @@ pub mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_load_example_pkg_repo_with_overlay() -> Result<()> {
+        let repo = Repository::load(
+            &PathBuf::from("examples/packages/repo/"),
+            &[PathBuf::from("../overlay")],
+            &HashMap::new(),
+            &indicatif::ProgressBar::hidden(),
+        )?;
+
+        let pkgs = repo.find(&pname("a"), &pversion("1"));
+        assert_eq!(pkgs.len(), 1);
+        let a = pkgs.first().unwrap();
+        assert_eq!(a.overlay().as_ref(), Some(&PathBuf::from("../overlay")));
+        assert_eq!(
+            a.sources().get("src").unwrap().url().as_str(),
+            "https://example.com/overlay"
+        );
+
+        let pkgs = repo.find(&pname("b"), &pversion("2"));
+        assert_eq!(pkgs.len(), 1);
+        assert!(pkgs.first().unwrap().overlay().is_none());
+
+        // The same package must not be defined in two overlays:
+        let conflict = Repository::load(
+            &PathBuf::from("examples/packages/repo/"),
+            &[PathBuf::from("../overlay"), PathBuf::from("../overlay")],
+            &HashMap::new(),
+            &indicatif::ProgressBar::hidden(),
+        );
+        assert!(conflict.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_namespace() {
+        let namespaces = HashMap::from([
+            (String::from("team"), PathBuf::from("teams/a")),
+            (String::from("sub"), PathBuf::from("teams/a/sub")),
+        ]);
+        let apply = |name: &str, path: &str| {
+            let mut pkg = package(name, "1", "https://rust-lang.org", "123");
+            apply_namespace(&mut pkg, Path::new(path), &namespaces).map(|_| pkg.name().clone())
+        };
+
+        assert_eq!(apply("foo", "foo/pkg.toml").unwrap(), pname("foo"));
+        assert_eq!(
+            apply("foo", "teams/a/foo/pkg.toml").unwrap(),
+            pname("team/foo")
+        );
+        assert_eq!(
+            apply("team/foo", "teams/a/foo/pkg.toml").unwrap(),
+            pname("team/foo")
+        );
+        assert_eq!(
+            apply("foo", "teams/a/sub/foo/pkg.toml").unwrap(),
+            pname("sub/foo")
+        );
+        assert_eq!(
+            apply("other/foo", "foo/pkg.toml").unwrap(),
+            pname("other/foo")
+        );
+
+        // The namespace of a subtree is reserved for the packages below it
+        assert!(apply("other/foo", "teams/a/foo/pkg.toml").is_err());
+        assert!(apply("team/foo", "foo/pkg.toml").is_err());
+        assert!(apply("foo", "teams/ab/foo/pkg.toml").is_ok());
+    }
+
     #[test]
     fn test_relative_path_normalization() -> Result<()> {
         assert!(normalize_relative_path(PathBuf::from("/root")).is_err());
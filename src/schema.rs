@@ -3,6 +3,20 @@ table! {
         id -> Int4,
         path -> Varchar,
         job_id -> Int4,
+        size -> Nullable<Int8>,
+        kind -> Varchar,
+        sha256 -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    endpoint_artifacts (id) {
+        id -> Int4,
+        endpoint_id -> Int4,
+        volume -> Varchar,
+        sha256 -> Varchar,
+        file_name -> Varchar,
+        cached_at -> Timestamptz,
     }
 }
 
@@ -43,6 +57,28 @@ table! {
     }
 }
 
+table! {
+    job_probes (id) {
+        id -> Int4,
+        job_id -> Int4,
+        name -> Varchar,
+        output -> Text,
+    }
+}
+
+table! {
+    job_queue (id) {
+        id -> Int4,
+        submit_id -> Int4,
+        job_uuid -> Uuid,
+        priority -> Int4,
+        enqueued_at -> Timestamptz,
+        heartbeat -> Timestamptz,
+        dispatched_at -> Nullable<Timestamptz>,
+        dispatchable -> Bool,
+    }
+}
+
 table! {
     jobs (id) {
         id -> Int4,
@@ -54,6 +90,19 @@ table! {
         script_text -> Text,
         log_text -> Text,
         uuid -> Uuid,
+        cache_key -> Nullable<Varchar>,
+        started_at -> Nullable<Timestamptz>,
+        finished_at -> Nullable<Timestamptz>,
+        butido_version -> Nullable<Varchar>,
+        image_digest -> Nullable<Varchar>,
+        endpoint_docker_version -> Nullable<Varchar>,
+        sources_hash -> Nullable<Varchar>,
+        reported_artifacts -> Array<Text>,
+        warnings -> Nullable<Int4>,
+        failure_class -> Nullable<Varchar>,
+        retried -> Bool,
+        arch -> Nullable<Varchar>,
+        input_artifacts -> Array<Text>,
     }
 }
 
@@ -62,6 +111,56 @@ table! {
         id -> Int4,
         name -> Varchar,
         version -> Varchar,
+        maintainer -> Nullable<Text>,
+        license -> Nullable<Text>,
+        description -> Nullable<Text>,
+        homepage -> Nullable<Text>,
+    }
+}
+
+table! {
+    queued_submits (id) {
+        id -> Int4,
+        uuid -> Uuid,
+        repo_hash_id -> Int4,
+        arguments -> Array<Text>,
+        state -> Varchar,
+        enqueued_at -> Timestamptz,
+        started_at -> Nullable<Timestamptz>,
+        finished_at -> Nullable<Timestamptz>,
+        heartbeat -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    release_attestations (id) {
+        id -> Int4,
+        release_id -> Int4,
+        envelope -> Text,
+        signed -> Bool,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    release_groups (id) {
+        id -> Int4,
+        uuid -> Uuid,
+        release_date -> Timestamptz,
+        release_store_id -> Int4,
+        name -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    release_mirror_files (id) {
+        id -> Int4,
+        release_store_id -> Int4,
+        target -> Varchar,
+        path -> Varchar,
+        size -> Int8,
+        modified_at -> Timestamptz,
+        synced_at -> Timestamptz,
     }
 }
 
@@ -78,6 +177,37 @@ table! {
         artifact_id -> Int4,
         release_date -> Timestamptz,
         release_store_id -> Int4,
+        path -> Nullable<Varchar>,
+        release_group_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    reused_artifacts (id) {
+        id -> Int4,
+        submit_id -> Int4,
+        job_uuid -> Uuid,
+        artifact_id -> Int4,
+        source_submit_id -> Int4,
+        policy -> Varchar,
+    }
+}
+
+table! {
+    store_snapshot_artifacts (id) {
+        id -> Int4,
+        store_snapshot_id -> Int4,
+        path -> Varchar,
+        sha256 -> Varchar,
+    }
+}
+
+table! {
+    store_snapshots (id) {
+        id -> Int4,
+        name -> Varchar,
+        release_store_id -> Int4,
+        created_at -> Timestamptz,
     }
 }
 
@@ -97,18 +227,32 @@ table! {
         requested_image_id -> Int4,
         requested_package_id -> Int4,
         repo_hash_id -> Int4,
+        phase_modifications -> Array<Text>,
+        state -> Varchar,
     }
 }
 
 joinable!(artifacts -> jobs (job_id));
+joinable!(endpoint_artifacts -> endpoints (endpoint_id));
 joinable!(job_envs -> envvars (env_id));
+joinable!(job_queue -> submits (submit_id));
 joinable!(job_envs -> jobs (job_id));
+joinable!(job_probes -> jobs (job_id));
 joinable!(jobs -> endpoints (endpoint_id));
 joinable!(jobs -> images (image_id));
 joinable!(jobs -> packages (package_id));
 joinable!(jobs -> submits (submit_id));
+joinable!(queued_submits -> githashes (repo_hash_id));
+joinable!(release_attestations -> releases (release_id));
+joinable!(release_groups -> release_stores (release_store_id));
+joinable!(release_mirror_files -> release_stores (release_store_id));
 joinable!(releases -> artifacts (artifact_id));
+joinable!(releases -> release_groups (release_group_id));
 joinable!(releases -> release_stores (release_store_id));
+joinable!(reused_artifacts -> artifacts (artifact_id));
+joinable!(reused_artifacts -> submits (submit_id));
+joinable!(store_snapshot_artifacts -> store_snapshots (store_snapshot_id));
+joinable!(store_snapshots -> release_stores (release_store_id));
 joinable!(submit_envs -> envvars (env_id));
 joinable!(submit_envs -> submits (submit_id));
 joinable!(submits -> githashes (repo_hash_id));
@@ -117,15 +261,25 @@ joinable!(submits -> packages (requested_package_id));
 
 allow_tables_to_appear_in_same_query!(
     artifacts,
+    endpoint_artifacts,
     endpoints,
     envvars,
     githashes,
     images,
     job_envs,
+    job_probes,
+    job_queue,
     jobs,
     packages,
+    queued_submits,
+    release_attestations,
+    release_groups,
+    release_mirror_files,
     release_stores,
     releases,
+    reused_artifacts,
+    store_snapshot_artifacts,
+    store_snapshots,
     submit_envs,
     submits,
 );
@@ -0,0 +1,152 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! The integrity journal of the source cache
+//!
+//! Every source that is put into the source cache (and whose hash was verified) is recorded in a
+//! JSON lines file in the root of the source cache. Records are only ever appended, so that
+//! concurrent downloads (also of multiple butido processes) do not need to coordinate. The last
+//! record of a file is the one that counts.
+//!
+//! The journal is self-contained, so the files in the source cache can be audited without the
+//! package repository.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+use tracing::trace;
+use url::Url;
+
+use crate::package::SourceHash;
+
+/// The file name of the journal in the root of the source cache
+pub const JOURNAL_FILE_NAME: &str = ".butido-journal.jsonl";
+
+/// A file that was put into the source cache
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JournalRecord {
+    /// The path of the file, relative to the root of the source cache
+    pub path: PathBuf,
+    pub url: Url,
+
+    /// When the file was put into the source cache (RFC 3339)
+    pub downloaded: String,
+    pub size: u64,
+
+    /// The hash that was verified when the file was put into the source cache
+    pub hash: SourceHash,
+}
+
+/// The journal of a source cache
+#[derive(Debug)]
+pub struct SourceJournal {
+    path: PathBuf,
+}
+
+impl SourceJournal {
+    pub fn new(cache_root: &Path) -> Self {
+        SourceJournal {
+            path: cache_root.join(JOURNAL_FILE_NAME),
+        }
+    }
+
+    /// Append a record to the journal
+    pub async fn record(&self, record: &JournalRecord) -> Result<()> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        trace!("Recording in {}: {}", self.path.display(), line.trim_end());
+
+        // The line is written at once, so that concurrent appends do not interleave
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .with_context(|| anyhow!("Opening source journal {}", self.path.display()))?;
+        file.write_all(line.as_bytes())
+            .await
+            .with_context(|| anyhow!("Writing source journal {}", self.path.display()))
+    }
+
+    /// The last record of every file in the journal, by path
+    ///
+    /// A journal that does not exist yet is empty.
+    pub async fn records(&self) -> Result<BTreeMap<PathBuf, JournalRecord>> {
+        let content = match tokio::fs::read_to_string(&self.path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| anyhow!("Reading source journal {}", self.path.display()))
+            }
+        };
+
+        content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(n, line)| {
+                serde_json::from_str::<JournalRecord>(line)
+                    .map(|record| (record.path.clone(), record))
+                    .with_context(|| {
+                        anyhow!(
+                            "Parsing line {} of source journal {}",
+                            n + 1,
+                            self.path.display()
+                        )
+                    })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::package::HashType;
+    use crate::package::HashValue;
+
+    fn record(path: &str, size: u64) -> JournalRecord {
+        JournalRecord {
+            path: PathBuf::from(path),
+            url: Url::parse("https://example.com/a-1.tar.gz").unwrap(),
+            downloaded: String::from("2024-11-14T10:00:00+00:00"),
+            size,
+            hash: SourceHash::new(HashType::Sha256, HashValue::from(String::from("abc"))),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_last_record_counts() {
+        let dir =
+            std::env::temp_dir().join(format!("butido-test-journal-{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let journal = SourceJournal::new(&dir);
+        assert!(journal.records().await.unwrap().is_empty());
+
+        journal.record(&record("a-1/src.source", 1)).await.unwrap();
+        journal.record(&record("b-1/src.source", 2)).await.unwrap();
+        journal.record(&record("a-1/src.source", 3)).await.unwrap();
+
+        let records = journal.records().await.unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[Path::new("a-1/src.source")].size, 3);
+        assert_eq!(records[Path::new("b-1/src.source")].size, 2);
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
@@ -8,18 +8,28 @@
 // SPDX-License-Identifier: EPL-2.0
 //
 
+use std::path::Path;
 use std::path::PathBuf;
 
 use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Result;
+use serde::Deserialize;
+use serde::Serialize;
 use tracing::trace;
 use url::Url;
 
+use crate::config::LocalSourceMode;
 use crate::package::Package;
 use crate::package::PackageName;
 use crate::package::PackageVersion;
 use crate::package::Source;
+use crate::util::archive;
+use crate::util::archive::ArchiveEntry;
+use crate::util::EnvironmentVariableName;
+
+mod journal;
+pub use journal::*;
 
 #[derive(Clone, Debug)]
 pub struct SourceCache {
@@ -80,6 +90,75 @@ impl SourceEntry {
         *self.package_source.download_manually()
     }
 
+    pub fn extract(&self) -> bool {
+        *self.package_source.extract()
+    }
+
+    /// The path of the source in the container
+    ///
+    /// Sources that are extracted are extracted into a directory with the name of the source in
+    /// the build directory, all other sources are copied to the inputs directory.
+    pub fn container_path(&self) -> Result<PathBuf> {
+        if self.extract() {
+            return Ok(Path::new(crate::consts::BUILD_DIR_PATH).join(&self.package_source_name));
+        }
+
+        let source_path = self.path();
+        source_path
+            .file_name()
+            .map(|file_name| Path::new(crate::consts::INPUTS_DIR_PATH).join(file_name))
+            .ok_or_else(|| anyhow!("Not a file: {}", source_path.display()))
+    }
+
+    /// The environment variable that tells the script the path of the source in the container
+    ///
+    /// The name is the name of the source in upper case, with all characters that are not
+    /// allowed in names of environment variables replaced by "_".
+    pub fn environment_variable(&self) -> Result<(EnvironmentVariableName, String)> {
+        let name = self
+            .package_source_name
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' => c.to_ascii_uppercase(),
+                _ => '_',
+            })
+            .collect::<String>();
+        let name = format!("{}{}", crate::consts::SOURCE_ENV_VAR_PREFIX, name);
+        let path = self.container_path()?;
+        Ok((
+            EnvironmentVariableName::from(name.as_str()),
+            path.display().to_string(),
+        ))
+    }
+
+    /// The entries of the source in the archive that is copied into the container (with paths
+    /// relative to the root directory)
+    pub async fn container_entries(&self) -> Result<Vec<ArchiveEntry>> {
+        let source_path = self.path();
+        let destination = self.container_path()?;
+        let destination = destination
+            .strip_prefix("/")
+            .map(PathBuf::from)
+            .unwrap_or(destination);
+        trace!("Source path    = {:?}", source_path);
+        trace!("Source dest    = {:?}", destination);
+
+        if self.extract() {
+            let buf = tokio::fs::read(&source_path)
+                .await
+                .with_context(|| anyhow!("Reading file {}", source_path.display()))?;
+            tokio::task::spawn_blocking(move || archive::unpack(&buf, &destination))
+                .await?
+                .with_context(|| anyhow!("Extracting source {}", source_path.display()))
+        } else {
+            // The file is only read when the archive is built
+            Ok(vec![ArchiveEntry::HostFile {
+                path: destination,
+                source: source_path,
+            }])
+        }
+    }
+
     pub async fn remove_file(&self) -> Result<()> {
         let p = self.path();
         tokio::fs::remove_file(&p).await?;
@@ -87,14 +166,17 @@ impl SourceEntry {
     }
 
     pub async fn verify_hash(&self) -> Result<()> {
-        let p = self.path();
+        self.verify_hash_of(&self.path()).await
+    }
+
+    async fn verify_hash_of(&self, p: &Path) -> Result<()> {
         trace!("Verifying : {}", p.display());
 
         let reader = tokio::fs::OpenOptions::new()
             .create(false)
             .create_new(false)
             .read(true)
-            .open(&p)
+            .open(p)
             .await
             .map(tokio::io::BufReader::new)
             .context("Opening file failed")?;
@@ -103,10 +185,165 @@ impl SourceEntry {
         self.package_source.hash().matches_hash_of(reader).await
     }
 
-    pub async fn create(&self) -> Result<tokio::fs::File> {
-        let p = self.path();
-        trace!("Creating source file: {}", p.display());
+    /// The file a source is downloaded to, it is moved to `path()` once its hash is verified
+    pub fn partial_path(&self) -> PathBuf {
+        let mut path = self.path().into_os_string();
+        path.push(".part");
+        PathBuf::from(path)
+    }
+
+    /// The bookkeeping of the partial download, next to the partial file
+    fn partial_meta_path(&self) -> PathBuf {
+        let mut path = self.partial_path().into_os_string();
+        path.push(".json");
+        PathBuf::from(path)
+    }
+
+    /// The size and the bookkeeping of the partial download of the source, if it can be resumed
+    ///
+    /// A partial file that was downloaded from a different URL (or without bookkeeping) cannot be
+    /// resumed and is removed.
+    pub async fn partial_download(&self) -> Result<Option<(u64, PartialDownload)>> {
+        let part = self.partial_path();
+        let meta = match tokio::fs::read(self.partial_meta_path()).await {
+            Ok(buf) => serde_json::from_slice::<PartialDownload>(&buf).ok(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e).context("Reading the bookkeeping of the partial download"),
+        };
+
+        match (tokio::fs::metadata(&part).await, meta) {
+            (Ok(metadata), Some(meta)) if meta.url == *self.url() => {
+                Ok(Some((metadata.len(), meta)))
+            }
+            (Err(e), _) if e.kind() == std::io::ErrorKind::NotFound => {
+                self.remove_partial().await?;
+                Ok(None)
+            }
+            (Err(e), _) => Err(e).with_context(|| anyhow!("Reading {}", part.display())),
+            (Ok(_), _) => {
+                trace!("Partial download cannot be resumed: {}", part.display());
+                self.remove_partial().await?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Open the partial file of the source to write the download into
+    ///
+    /// The download is appended to the partial file if `resume` is set, otherwise the file is
+    /// truncated. The bookkeeping is written first, so that the download can be resumed if it is
+    /// interrupted.
+    pub async fn open_partial(
+        &self,
+        meta: &PartialDownload,
+        resume: bool,
+    ) -> Result<tokio::fs::File> {
+        self.create_directory().await?;
+
+        let buf = serde_json::to_vec(meta)?;
+        tokio::fs::write(self.partial_meta_path(), buf)
+            .await
+            .context("Writing the bookkeeping of the partial download")?;
+
+        let p = self.partial_path();
+        trace!(
+            "Opening partial source file (resume = {}): {}",
+            resume,
+            p.display()
+        );
+        tokio::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(resume)
+            .truncate(!resume)
+            .open(&p)
+            .await
+            .with_context(|| anyhow!("Opening file: {}", p.display()))
+    }
+
+    /// Copy (or symlink) the file of a source with a file:// URL to the partial file
+    ///
+    /// Returns the size of the file.
+    pub async fn fetch_local(&self, mode: &LocalSourceMode) -> Result<u64> {
+        let source = self
+            .url()
+            .to_file_path()
+            .map_err(|_| anyhow!("Not a local file: {}", self.url()))?;
+        let size = tokio::fs::metadata(&source)
+            .await
+            .with_context(|| anyhow!("Reading {}", source.display()))?
+            .len();
+
+        self.remove_partial().await?;
+        self.create_directory().await?;
+        let part = self.partial_path();
+        match mode {
+            LocalSourceMode::Copy => {
+                trace!("Copying {} to {}", source.display(), part.display());
+                tokio::fs::copy(&source, &part).await.with_context(|| {
+                    anyhow!("Copying {} to {}", source.display(), part.display())
+                })?;
+            }
+            LocalSourceMode::Symlink => {
+                trace!("Linking {} to {}", part.display(), source.display());
+                tokio::fs::symlink(&source, &part).await.with_context(|| {
+                    anyhow!("Linking {} to {}", part.display(), source.display())
+                })?;
+            }
+        }
+        Ok(size)
+    }
+
+    /// Verify the hash of the completely downloaded partial file and move it into place
+    ///
+    /// The partial file is removed if the hash does not match, as it cannot be resumed either.
+    pub async fn finish_partial(&self) -> Result<()> {
+        let part = self.partial_path();
+        if let Err(e) = self.verify_hash_of(&part).await {
+            self.remove_partial().await?;
+            return Err(e).with_context(|| anyhow!("Verifying download of {}", self.url()));
+        }
+
+        tokio::fs::rename(&part, self.path())
+            .await
+            .with_context(|| anyhow!("Moving {} into place", part.display()))?;
+        self.remove_partial().await?;
+        self.record_in_journal().await
+    }
+
+    /// Record the (verified) file of the source in the journal of the source cache
+    async fn record_in_journal(&self) -> Result<()> {
+        let path = self.path();
+        let size = tokio::fs::metadata(&path)
+            .await
+            .with_context(|| anyhow!("Reading {}", path.display()))?
+            .len();
+        let record = JournalRecord {
+            path: path
+                .strip_prefix(&self.cache_root)
+                .map(Path::to_path_buf)
+                .with_context(|| anyhow!("BUG: {} is not in the source cache", path.display()))?,
+            url: self.url().clone(),
+            downloaded: chrono::Utc::now().to_rfc3339(),
+            size,
+            hash: self.package_source.hash().clone(),
+        };
+        SourceJournal::new(&self.cache_root).record(&record).await
+    }
 
+    /// Remove the partial file and its bookkeeping, if they exist
+    pub async fn remove_partial(&self) -> Result<()> {
+        for p in [self.partial_path(), self.partial_meta_path()] {
+            match tokio::fs::remove_file(&p).await {
+                Ok(()) => trace!("Removed {}", p.display()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e).with_context(|| anyhow!("Removing {}", p.display())),
+            }
+        }
+        Ok(())
+    }
+
+    async fn create_directory(&self) -> Result<()> {
         if !self.cache_root.is_dir() {
             trace!("Cache root does not exist: {}", self.cache_root.display());
             return Err(anyhow!(
@@ -115,29 +352,141 @@ impl SourceEntry {
             ));
         }
 
-        {
-            let dir = self.source_file_directory();
-            if !dir.is_dir() {
-                trace!("Creating directory: {}", dir.display());
-                tokio::fs::create_dir_all(&dir).await.with_context(|| {
-                    anyhow!(
-                        "Creating source cache directory for package {} {}: {}",
-                        self.package_source_name,
-                        self.package_source.hash().value(),
-                        dir.display()
-                    )
-                })?;
-            } else {
-                trace!("Directory exists: {}", dir.display());
-            }
+        let dir = self.source_file_directory();
+        if !dir.is_dir() {
+            trace!("Creating directory: {}", dir.display());
+            tokio::fs::create_dir_all(&dir).await.with_context(|| {
+                anyhow!(
+                    "Creating source cache directory for package {} {}: {}",
+                    self.package_source_name,
+                    self.package_source.hash().value(),
+                    dir.display()
+                )
+            })?;
+        } else {
+            trace!("Directory exists: {}", dir.display());
         }
+        Ok(())
+    }
+}
 
-        trace!("Creating file now: {}", p.display());
-        tokio::fs::OpenOptions::new()
-            .create_new(true)
-            .write(true)
-            .open(&p)
+/// The bookkeeping of a partially downloaded source
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PartialDownload {
+    /// The URL the partial file is downloaded from
+    pub url: Url,
+
+    /// The validator of the downloaded resource (its ETag or modification date), if the server
+    /// sent one
+    ///
+    /// It is sent as "If-Range" when the download is resumed, so that the server sends the whole
+    /// resource if it changed in the meantime.
+    pub validator: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::package::HashType;
+    use crate::package::HashValue;
+    use crate::package::SourceHash;
+
+    fn entry(cache_root: PathBuf) -> SourceEntry {
+        let hash = SourceHash::new(
+            HashType::Sha256,
+            HashValue::from(String::from(
+                "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9",
+            )),
+        );
+        SourceEntry {
+            cache_root,
+            package_name: PackageName::from(String::from("a")),
+            package_version: PackageVersion::from(String::from("1")),
+            package_source_name: String::from("src"),
+            package_source: Source::new(
+                Url::parse("https://example.com/a-1.tar.gz").unwrap(),
+                hash,
+            ),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resume_partial_download() {
+        let dir = std::env::temp_dir().join(format!("butido-test-source-{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let entry = entry(dir.clone());
+        assert!(entry.partial_download().await.unwrap().is_none());
+
+        let meta = PartialDownload {
+            url: entry.url().clone(),
+            validator: Some(String::from("\"etag\"")),
+        };
+        let mut file = entry.open_partial(&meta, false).await.unwrap();
+        tokio::io::AsyncWriteExt::write_all(&mut file, b"hello ")
+            .await
+            .unwrap();
+        drop(file);
+
+        let (len, resumed) = entry.partial_download().await.unwrap().unwrap();
+        assert_eq!(len, 6);
+        assert_eq!(resumed.validator.as_deref(), Some("\"etag\""));
+
+        let mut file = entry.open_partial(&resumed, true).await.unwrap();
+        tokio::io::AsyncWriteExt::write_all(&mut file, b"world")
             .await
-            .with_context(|| anyhow!("Creating file: {}", p.display()))
+            .unwrap();
+        drop(file);
+
+        entry.finish_partial().await.unwrap();
+        assert!(entry.path().is_file());
+        assert!(!entry.partial_path().exists());
+        assert!(entry.partial_download().await.unwrap().is_none());
+
+        let records = SourceJournal::new(&dir).records().await.unwrap();
+        let record = &records[Path::new("a-1/src.source")];
+        assert_eq!(record.url, *entry.url());
+        assert_eq!(record.size, 11);
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_partial_download_of_other_url_is_discarded() {
+        let dir = std::env::temp_dir().join(format!("butido-test-source-{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let entry = entry(dir.clone());
+
+        let meta = PartialDownload {
+            url: Url::parse("https://example.com/other.tar.gz").unwrap(),
+            validator: None,
+        };
+        drop(entry.open_partial(&meta, false).await.unwrap());
+        assert!(entry.partial_download().await.unwrap().is_none());
+        assert!(!entry.partial_path().exists());
+
+        // A complete download with the wrong hash cannot be resumed either
+        let meta = PartialDownload {
+            url: entry.url().clone(),
+            validator: None,
+        };
+        let mut file = entry.open_partial(&meta, false).await.unwrap();
+        tokio::io::AsyncWriteExt::write_all(&mut file, b"hello")
+            .await
+            .unwrap();
+        drop(file);
+        assert!(entry.finish_partial().await.is_err());
+        assert!(!entry.partial_path().exists());
+        assert!(!entry.path().exists());
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[test]
+    fn test_environment_variable() {
+        let mut entry = entry(PathBuf::from("/cache"));
+        entry.package_source_name = String::from("foo-docs");
+        let (name, value) = entry.environment_variable().unwrap();
+        assert_eq!(name.as_ref(), "BUTIDO_SOURCE_FOO_DOCS");
+        assert_eq!(value, "/inputs/foo-docs.source");
     }
 }
@@ -137,6 +137,19 @@ impl<P: Borrow<Package>> PreparePrintPackage<'_, P> {
         );
         data.insert("p", serde_json::to_value(self.package.borrow())?);
         data.insert("script", serde_json::Value::String(script));
+        data.insert(
+            "origin",
+            serde_json::Value::String(self.package.borrow().origin().display().to_string()),
+        );
+        data.insert(
+            "overlay",
+            self.package
+                .borrow()
+                .overlay()
+                .as_ref()
+                .map(|o| serde_json::Value::String(o.display().to_string()))
+                .unwrap_or(serde_json::Value::Null),
+        );
         data.insert("print_any", serde_json::Value::Bool(self.flags.print_any()));
         data.insert(
             "print_runtime_deps",
@@ -0,0 +1,402 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Reproducible tar archives
+
+use std::io::Read;
+use std::io::Write;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use sha2::Digest;
+use tracing::{trace, warn};
+
+/// An entry of a `NormalizedArchive`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ArchiveEntry {
+    File {
+        path: PathBuf,
+        content: Vec<u8>,
+        executable: bool,
+    },
+    Directory {
+        path: PathBuf,
+    },
+    Symlink {
+        path: PathBuf,
+        target: PathBuf,
+    },
+    /// A (not executable) file whose content is read from `source` when the archive is built
+    HostFile {
+        path: PathBuf,
+        source: PathBuf,
+    },
+}
+
+impl ArchiveEntry {
+    /// A (not executable) file
+    pub fn file(path: PathBuf, content: Vec<u8>) -> Self {
+        ArchiveEntry::File {
+            path,
+            content,
+            executable: false,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        match self {
+            ArchiveEntry::File { path, .. } => path,
+            ArchiveEntry::Directory { path } => path,
+            ArchiveEntry::Symlink { path, .. } => path,
+            ArchiveEntry::HostFile { path, .. } => path,
+        }
+    }
+}
+
+/// A tar archive that is bit-identical for the same files
+///
+/// The entries are sorted by path and all metadata that depends on the host (modification time,
+/// owner and permissions) is replaced by fixed values.
+///
+/// The archive is written to a temporary file (which is removed when the archive is dropped) and
+/// hashed while it is written, so that it is never kept in memory.
+pub struct NormalizedArchive {
+    path: PathBuf,
+    sha256: String,
+}
+
+/// A writer that hashes everything that is written to the inner writer
+struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: sha2::Sha256,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl NormalizedArchive {
+    /// Build an archive from the passed files (path in the archive and content)
+    pub fn build(files: Vec<(PathBuf, Vec<u8>)>) -> Result<Self> {
+        Self::build_entries(
+            files
+                .into_iter()
+                .map(|(path, content)| ArchiveEntry::file(path, content))
+                .collect(),
+        )
+    }
+
+    /// Build an archive from the passed entries
+    ///
+    /// Files are either executable (mode 755) or not (mode 644), directories have mode 755.
+    pub fn build_entries(mut entries: Vec<ArchiveEntry>) -> Result<Self> {
+        entries.sort_by(|a, b| a.path().cmp(b.path()));
+
+        let path =
+            std::env::temp_dir().join(format!("butido-archive-{}.tar", uuid::Uuid::new_v4()));
+        let file = std::fs::File::create(&path)
+            .with_context(|| anyhow!("Creating temporary archive {}", path.display()))?;
+        // Created first, so that the file is removed if building the archive fails
+        let mut archive = NormalizedArchive {
+            path,
+            sha256: String::new(),
+        };
+
+        let mut builder = tar::Builder::new(HashingWriter {
+            inner: std::io::BufWriter::new(file),
+            hasher: sha2::Sha256::new(),
+        });
+        for entry in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_mtime(0);
+            header.set_uid(0);
+            header.set_gid(0);
+
+            match entry {
+                ArchiveEntry::File {
+                    path,
+                    content,
+                    executable,
+                } => {
+                    header.set_size(content.len() as u64);
+                    header.set_mode(if executable { 0o755 } else { 0o644 });
+                    header.set_entry_type(tar::EntryType::Regular);
+                    builder
+                        .append_data(&mut header, &path, content.as_slice())
+                        .with_context(|| anyhow!("Adding {} to archive", path.display()))?;
+                }
+                ArchiveEntry::Directory { path } => {
+                    header.set_size(0);
+                    header.set_mode(0o755);
+                    header.set_entry_type(tar::EntryType::Directory);
+                    builder
+                        .append_data(&mut header, &path, std::io::empty())
+                        .with_context(|| anyhow!("Adding {} to archive", path.display()))?;
+                }
+                ArchiveEntry::Symlink { path, target } => {
+                    header.set_size(0);
+                    header.set_mode(0o777);
+                    header.set_entry_type(tar::EntryType::Symlink);
+                    builder
+                        .append_link(&mut header, &path, &target)
+                        .with_context(|| anyhow!("Adding {} to archive", path.display()))?;
+                }
+                ArchiveEntry::HostFile { path, source } => {
+                    let file = std::fs::File::open(&source)
+                        .with_context(|| anyhow!("Opening {}", source.display()))?;
+                    header.set_size(file.metadata()?.len());
+                    header.set_mode(0o644);
+                    header.set_entry_type(tar::EntryType::Regular);
+                    builder
+                        .append_data(&mut header, &path, file)
+                        .with_context(|| anyhow!("Adding {} to archive", path.display()))?;
+                }
+            }
+        }
+
+        let mut writer = builder.into_inner().context("Finishing archive")?;
+        writer.flush().context("Writing archive")?;
+        archive.sha256 = format!("{:x}", writer.hasher.finalize());
+        Ok(archive)
+    }
+
+    /// The (hex encoded) SHA-256 hash of the archive
+    pub fn sha256(&self) -> String {
+        self.sha256.clone()
+    }
+
+    /// The path of the (temporary) archive file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for NormalizedArchive {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            warn!("Failed to remove {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// The entries of a (possibly compressed) tar archive, with their paths below `destination`
+///
+/// Uncompressed archives and archives that are compressed with gzip or zstd are supported, the
+/// compression is detected from the content. Hard links are resolved to copies of the files they
+/// link to, other special files (e.g. devices) are skipped.
+pub fn unpack(bytes: &[u8], destination: &Path) -> Result<Vec<ArchiveEntry>> {
+    let reader: Box<dyn Read + '_> = match bytes {
+        [0x1f, 0x8b, ..] => Box::new(flate2::read::MultiGzDecoder::new(bytes)),
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => Box::new(zstd::stream::read::Decoder::new(bytes)?),
+        _ if bytes.get(257..262) == Some(&b"ustar"[..]) => Box::new(bytes),
+        _ => {
+            return Err(anyhow!(
+                "Unsupported archive format, only tar archives (uncompressed or compressed with gzip or zstd) can be extracted"
+            ))
+        }
+    };
+
+    let mut archive = tar::Archive::new(reader);
+    let mut entries = Vec::new();
+    for entry in archive.entries().context("Reading archive")? {
+        let mut entry = entry.context("Reading archive entry")?;
+        let entry_path = entry.path()?.into_owned();
+        let relative = relative_path(&entry_path)?;
+        if relative.as_os_str().is_empty() {
+            continue;
+        }
+        let path = destination.join(relative);
+
+        match entry.header().entry_type() {
+            tar::EntryType::Regular | tar::EntryType::Continuous | tar::EntryType::GNUSparse => {
+                let executable = entry.header().mode()? & 0o111 != 0;
+                let mut content = Vec::new();
+                entry
+                    .read_to_end(&mut content)
+                    .with_context(|| anyhow!("Reading {}", entry_path.display()))?;
+                entries.push(ArchiveEntry::File {
+                    path,
+                    content,
+                    executable,
+                });
+            }
+            tar::EntryType::Directory => entries.push(ArchiveEntry::Directory { path }),
+            tar::EntryType::Symlink => {
+                let target = entry
+                    .link_name()?
+                    .ok_or_else(|| anyhow!("Symlink without target: {}", entry_path.display()))?
+                    .into_owned();
+                entries.push(ArchiveEntry::Symlink { path, target });
+            }
+            tar::EntryType::Link => {
+                let target = entry
+                    .link_name()?
+                    .ok_or_else(|| anyhow!("Hard link without target: {}", entry_path.display()))
+                    .and_then(|target| relative_path(&target))
+                    .map(|target| destination.join(target))?;
+                let linked = entries
+                    .iter()
+                    .rev()
+                    .find_map(|e| match e {
+                        ArchiveEntry::File {
+                            path,
+                            content,
+                            executable,
+                        } if *path == target => Some((content.clone(), *executable)),
+                        _ => None,
+                    })
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Hard link {} to unknown file {}",
+                            entry_path.display(),
+                            target.display()
+                        )
+                    })?;
+                entries.push(ArchiveEntry::File {
+                    path,
+                    content: linked.0,
+                    executable: linked.1,
+                });
+            }
+            other => trace!("Skipping {} ({:?})", entry_path.display(), other),
+        }
+    }
+    Ok(entries)
+}
+
+/// The path of an archive entry without "." components, paths that leave the directory the
+/// archive is extracted in are rejected
+fn relative_path(path: &Path) -> Result<PathBuf> {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .map(|component| match component {
+            Component::Normal(name) => Ok(name),
+            _ => Err(anyhow!("Invalid path in archive: {}", path.display())),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_does_not_depend_on_file_order() {
+        let a = (PathBuf::from("inputs/a.tar.gz"), b"a".to_vec());
+        let b = (PathBuf::from("inputs/b.tar.gz"), b"bb".to_vec());
+
+        let first = NormalizedArchive::build(vec![a.clone(), b.clone()]).unwrap();
+        let second = NormalizedArchive::build(vec![b, a]).unwrap();
+        assert_eq!(first.sha256(), second.sha256());
+        assert_eq!(
+            std::fs::read(first.path()).unwrap(),
+            std::fs::read(second.path()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_archive_is_hashed_and_removed() {
+        let dir =
+            std::env::temp_dir().join(format!("butido-test-archive-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("a.tar.gz");
+        std::fs::write(&source, b"a").unwrap();
+
+        let archive = NormalizedArchive::build_entries(vec![ArchiveEntry::HostFile {
+            path: PathBuf::from("inputs/a.tar.gz"),
+            source,
+        }])
+        .unwrap();
+        let in_memory =
+            NormalizedArchive::build(vec![(PathBuf::from("inputs/a.tar.gz"), b"a".to_vec())])
+                .unwrap();
+
+        let bytes = std::fs::read(archive.path()).unwrap();
+        assert_eq!(
+            archive.sha256(),
+            format!("{:x}", sha2::Sha256::digest(&bytes))
+        );
+        assert_eq!(archive.sha256(), in_memory.sha256());
+
+        let path = archive.path().to_path_buf();
+        drop(archive);
+        assert!(!path.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn tar_archive(entries: &[(&str, &[u8], u32)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, content, mode) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(*mode);
+            builder.append_data(&mut header, path, *content).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_unpack() {
+        let tar = tar_archive(&[
+            ("./foo-1.0/configure", b"#!/bin/sh", 0o755),
+            ("./foo-1.0/README", b"foo", 0o644),
+        ]);
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut gz, &tar).unwrap();
+        let gz = gz.finish().unwrap();
+
+        for bytes in [tar, gz] {
+            let entries = unpack(&bytes, Path::new("build/src")).unwrap();
+            assert_eq!(
+                entries,
+                vec![
+                    ArchiveEntry::File {
+                        path: PathBuf::from("build/src/foo-1.0/configure"),
+                        content: b"#!/bin/sh".to_vec(),
+                        executable: true,
+                    },
+                    ArchiveEntry::File {
+                        path: PathBuf::from("build/src/foo-1.0/README"),
+                        content: b"foo".to_vec(),
+                        executable: false,
+                    },
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_unpack_rejects_paths_outside_of_destination() {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(1);
+        header.set_mode(0o644);
+        // `set_path()` rejects "..", so the name is written directly
+        header.as_gnu_mut().unwrap().name[..8].copy_from_slice(b"../evil\0");
+        header.set_cksum();
+        let mut builder = tar::Builder::new(Vec::new());
+        builder.append(&header, &b"x"[..]).unwrap();
+        let tar = builder.into_inner().unwrap();
+
+        assert!(unpack(&tar, Path::new("build/src")).is_err());
+        assert!(unpack(b"not an archive", Path::new("build/src")).is_err());
+    }
+}
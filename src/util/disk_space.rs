@@ -0,0 +1,114 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Helpers to check the free disk space before a submit
+
+use std::path::Path;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+
+use crate::config::DiskSpaceLimits;
+
+/// The free disk space (in bytes) of the file system that contains `path`
+pub async fn free_space(path: &Path) -> Result<u64> {
+    let output = tokio::process::Command::new("df")
+        .arg("-P")
+        .arg("-k")
+        .arg(path)
+        .output()
+        .await
+        .with_context(|| anyhow!("Running df for {}", path.display()))?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "df for {} failed: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    parse_df_output(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parse the available space (in bytes) from the output of `df -P -k`
+pub fn parse_df_output(output: &str) -> Result<u64> {
+    output
+        .lines()
+        .nth(1)
+        .and_then(|line| line.split_whitespace().nth(3))
+        .ok_or_else(|| anyhow!("Unexpected output of df: {}", output))?
+        .parse::<u64>()
+        .map(|kib| kib * 1024)
+        .with_context(|| {
+            anyhow!(
+                "Parsing the available space from the output of df: {}",
+                output
+            )
+        })
+}
+
+/// The result of comparing the free disk space with the limits
+#[derive(Debug, Eq, PartialEq)]
+pub enum DiskSpaceVerdict {
+    Ok,
+    Warn,
+    Refuse,
+}
+
+impl DiskSpaceVerdict {
+    /// Check whether enough space is left after `required` bytes were written
+    pub fn check(free: u64, required: u64, limits: &DiskSpaceLimits) -> Self {
+        let left = free.saturating_sub(required);
+        if left < limits.min_free() {
+            DiskSpaceVerdict::Refuse
+        } else if left < limits.warn_free() {
+            DiskSpaceVerdict::Warn
+        } else {
+            DiskSpaceVerdict::Ok
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_df_output() {
+        let output = indoc::indoc!(
+            "
+            Filesystem     1024-blocks      Used Available Capacity Mounted on
+            /dev/sda1        102687672  52428800  45000000      54% /var/lib/butido
+            "
+        );
+        assert_eq!(parse_df_output(output).unwrap(), 45000000 * 1024);
+        assert!(parse_df_output("").is_err());
+    }
+
+    #[test]
+    fn test_check_disk_space() {
+        let limits = DiskSpaceLimits::default();
+        let gib = 1024 * 1024 * 1024;
+
+        assert_eq!(
+            DiskSpaceVerdict::check(100 * gib, 5 * gib, &limits),
+            DiskSpaceVerdict::Ok
+        );
+        assert_eq!(
+            DiskSpaceVerdict::check(12 * gib, 5 * gib, &limits),
+            DiskSpaceVerdict::Warn
+        );
+        assert_eq!(
+            DiskSpaceVerdict::check(5 * gib, 5 * gib, &limits),
+            DiskSpaceVerdict::Refuse
+        );
+    }
+}
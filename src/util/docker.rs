@@ -9,6 +9,8 @@
 //
 
 use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
 
 use anyhow::anyhow;
 use anyhow::Context;
@@ -50,11 +52,41 @@ impl AsRef<str> for ImageName {
     }
 }
 
+lazy_static::lazy_static! {
+    // A (simplified) version of the grammar for image references from the Docker distribution
+    // project: [domain[:port]/]path[:tag][@digest]
+    static ref IMAGE_REFERENCE_RE: regex::Regex = regex::Regex::new(concat!(
+        r"^(?:[a-zA-Z0-9](?:[a-zA-Z0-9-]*[a-zA-Z0-9])?(?:\.[a-zA-Z0-9](?:[a-zA-Z0-9-]*[a-zA-Z0-9])?)*(?::[0-9]+)?/)?",
+        r"[a-z0-9]+(?:(?:[._]|__|-+)[a-z0-9]+)*(?:/[a-z0-9]+(?:(?:[._]|__|-+)[a-z0-9]+)*)*",
+        r"(?::[a-zA-Z0-9_][a-zA-Z0-9_.-]{0,127})?",
+        r"(?:@[A-Za-z][A-Za-z0-9]*(?:[-_+.][A-Za-z][A-Za-z0-9]*)*:[0-9a-fA-F]{32,})?$",
+    ))
+    .unwrap();
+}
+
+impl ImageName {
+    /// Check whether the name is a syntactically valid image reference
+    pub fn is_valid_reference(&self) -> bool {
+        IMAGE_REFERENCE_RE.is_match(&self.0)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct ContainerImage {
     pub name: ImageName,
     pub short_name: ImageName,
+
+    /// The tags an endpoint must have to run jobs with this image (see `Endpoint::tags`)
+    #[serde(default)]
+    pub endpoint_tags: Vec<String>,
+
+    /// The architectures the image is available for (see `Endpoint::arch`)
+    ///
+    /// A submit with the image builds the packages for each of these architectures, unless the
+    /// architectures are passed explicitly.
+    #[serde(default)]
+    pub archs: Vec<String>,
 }
 
 pub struct ImageNameLookup {
@@ -163,3 +195,118 @@ impl AsRef<str> for ContainerHash {
         self.0.as_ref()
     }
 }
+
+/// A host path that is bind-mounted into the build containers
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Mount {
+    /// The (absolute) path on the endpoint host
+    pub source: PathBuf,
+
+    /// The (absolute) path inside the container
+    pub target: PathBuf,
+
+    /// Whether the container may write to the mount (mounts are read-only by default)
+    #[serde(default)]
+    pub writable: bool,
+}
+
+impl Mount {
+    /// Check whether the source of the mount is below one of the allowed paths
+    pub fn check_allowed(&self, allowed: &[PathBuf]) -> Result<()> {
+        let is_plain_absolute = |path: &Path| {
+            path.is_absolute()
+                && !path
+                    .components()
+                    .any(|c| matches!(c, std::path::Component::ParentDir))
+        };
+
+        if !is_plain_absolute(&self.source) || !is_plain_absolute(&self.target) {
+            return Err(anyhow!(
+                "Mount paths must be absolute and must not contain '..': {} -> {}",
+                self.source.display(),
+                self.target.display()
+            ));
+        }
+
+        if !allowed.iter().any(|path| self.source.starts_with(path)) {
+            return Err(anyhow!(
+                "Mounting {} is not allowed (see 'containers.allowed_mounts')",
+                self.source.display()
+            ));
+        }
+        Ok(())
+    }
+
+    /// The volume specification ("source:target:mode") for the Docker API
+    pub fn volume_spec(&self) -> String {
+        format!(
+            "{}:{}:{}",
+            self.source.display(),
+            self.target.display(),
+            if self.writable { "rw" } else { "ro" }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::ImageName;
+    use super::Mount;
+
+    #[test]
+    fn test_valid_image_references() {
+        for name in [
+            "debian",
+            "local:debian12-default",
+            "library/debian:bookworm",
+            "registry.example.com:5000/team/builder:1.2.3",
+            "debian@sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef",
+        ] {
+            assert!(ImageName::from(name).is_valid_reference(), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_invalid_image_references() {
+        for name in [
+            "",
+            "Debian",
+            "debian:",
+            "debian::12",
+            "deb ian",
+            "/debian",
+            "debian/",
+        ] {
+            assert!(!ImageName::from(name).is_valid_reference(), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_mount_allow_list() {
+        let allowed = [PathBuf::from("/opt/toolchains")];
+        let mount = |source: &str| Mount {
+            source: PathBuf::from(source),
+            target: PathBuf::from("/toolchain"),
+            writable: false,
+        };
+
+        assert!(mount("/opt/toolchains/gcc-13")
+            .check_allowed(&allowed)
+            .is_ok());
+        assert!(mount("/opt/toolchains").check_allowed(&allowed).is_ok());
+        assert!(mount("/opt/toolchains-old")
+            .check_allowed(&allowed)
+            .is_err());
+        assert!(mount("/opt/toolchains/../../etc")
+            .check_allowed(&allowed)
+            .is_err());
+        assert!(mount("opt/toolchains").check_allowed(&allowed).is_err());
+        assert_eq!(
+            mount("/opt/toolchains/gcc-13").volume_spec(),
+            "/opt/toolchains/gcc-13:/toolchain:ro"
+        );
+    }
+}
@@ -8,7 +8,10 @@
 // SPDX-License-Identifier: EPL-2.0
 //
 
+use std::path::Path;
+
 use anyhow::anyhow;
+use anyhow::Context;
 use anyhow::Result;
 
 use crate::util::EnvironmentVariableName;
@@ -26,3 +29,88 @@ pub fn parse_to_env(s: &str) -> Result<(EnvironmentVariableName, String)> {
         ),
     ))
 }
+
+/// Parse the content of a dotenv file
+///
+/// Every non-empty line that is not a comment (`#`) must have the form `KEY=VALUE` and may be
+/// prefixed with `export `. Values may be enclosed in single or double quotes.
+pub fn parse_env_file(content: &str) -> Result<Vec<(EnvironmentVariableName, String)>> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(lineno, line)| {
+            let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Line {}: Expected KEY=VALUE: {}", lineno, line))?;
+
+            let key = key.trim();
+            if key.is_empty() || key.contains(char::is_whitespace) {
+                return Err(anyhow!("Line {}: Invalid variable name: '{}'", lineno, key));
+            }
+
+            let value = value.trim();
+            let value = ['"', '\'']
+                .iter()
+                .find_map(|quote| {
+                    value
+                        .strip_prefix(*quote)
+                        .and_then(|v| v.strip_suffix(*quote))
+                })
+                .unwrap_or(value);
+
+            Ok((EnvironmentVariableName::from(key), value.to_string()))
+        })
+        .collect()
+}
+
+/// Read and parse a dotenv file (see `parse_env_file()`)
+pub fn read_env_file(path: &Path) -> Result<Vec<(EnvironmentVariableName, String)>> {
+    std::fs::read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|content| parse_env_file(&content))
+        .with_context(|| anyhow!("Reading environment file {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_env_file() {
+        let content = indoc::indoc!(
+            r#"
+            # A comment
+            FOO=bar
+            export BAZ = "quoted value"
+
+            EMPTY=
+            SINGLE='a=b'
+            "#
+        );
+
+        let env = parse_env_file(content).unwrap();
+        let env = env
+            .iter()
+            .map(|(k, v)| (k.as_ref(), v.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            env,
+            [
+                ("FOO", "bar"),
+                ("BAZ", "quoted value"),
+                ("EMPTY", ""),
+                ("SINGLE", "a=b")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_env_file_errors() {
+        assert!(parse_env_file("FOO").is_err());
+        assert!(parse_env_file("=bar").is_err());
+        assert!(parse_env_file("FO O=bar").is_err());
+    }
+}
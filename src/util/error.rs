@@ -0,0 +1,182 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! The categories of errors, the exit codes of butido and the machine-readable error output
+//! (`--error-format json`)
+
+use std::process::ExitCode;
+
+use serde::Serialize;
+
+/// The category of an error, which determines the exit code of butido
+///
+/// The exit codes are stable, so that calling systems (e.g. CI pipelines) can branch on them.
+/// A category is attached to an error as context (`.context(ErrorCategory::Resolution)`), errors
+/// of the database and the Docker API are categorized by their type.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorCategory {
+    /// Any error that does not belong to another category
+    Other,
+
+    /// The configuration cannot be loaded or is invalid
+    Config,
+
+    /// The database cannot be reached or a query failed
+    Database,
+
+    /// The package or its dependencies cannot be found
+    Resolution,
+
+    /// Jobs of the build failed and no job succeeded
+    BuildFailure,
+
+    /// Jobs of the build failed, but other jobs succeeded
+    PartialFailure,
+
+    /// Only jobs that failed because of the infrastructure (e.g. the Docker daemon) failed, or the
+    /// endpoints cannot be used
+    Infrastructure,
+}
+
+impl ErrorCategory {
+    /// The exit code of butido for errors of this category
+    ///
+    /// 2 is not used, clap exits with 2 if the command line arguments are invalid.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ErrorCategory::Other => 1,
+            ErrorCategory::Config => 3,
+            ErrorCategory::Database => 4,
+            ErrorCategory::Resolution => 5,
+            ErrorCategory::BuildFailure => 6,
+            ErrorCategory::PartialFailure => 7,
+            ErrorCategory::Infrastructure => 8,
+        }
+    }
+
+    /// The category of an error: the outermost category that was attached to it, or the category
+    /// of the first error in its chain with a known type
+    pub fn of(error: &anyhow::Error) -> ErrorCategory {
+        if let Some(category) = error.downcast_ref::<ErrorCategory>() {
+            return *category;
+        }
+
+        error
+            .chain()
+            .find_map(|cause| {
+                if cause.is::<diesel::result::Error>()
+                    || cause.is::<diesel::ConnectionError>()
+                    || cause.is::<diesel::r2d2::PoolError>()
+                {
+                    Some(ErrorCategory::Database)
+                } else if cause.is::<shiplift::Error>() {
+                    Some(ErrorCategory::Infrastructure)
+                } else if cause.is::<::config::ConfigError>() {
+                    Some(ErrorCategory::Config)
+                } else {
+                    None
+                }
+            })
+            .unwrap_or(ErrorCategory::Other)
+    }
+}
+
+impl std::fmt::Display for ErrorCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorCategory::Other => write!(f, "Error"),
+            ErrorCategory::Config => write!(f, "Invalid configuration"),
+            ErrorCategory::Database => write!(f, "Database error"),
+            ErrorCategory::Resolution => write!(f, "Cannot resolve the packages"),
+            ErrorCategory::BuildFailure => write!(f, "Build failed"),
+            ErrorCategory::PartialFailure => write!(f, "Build partially failed"),
+            ErrorCategory::Infrastructure => write!(f, "Infrastructure failure"),
+        }
+    }
+}
+
+/// An error as it is printed with `--error-format json`
+#[derive(Debug, Serialize)]
+struct JsonError {
+    category: ErrorCategory,
+    exit_code: u8,
+    /// The messages of the error and its causes, outermost first
+    messages: Vec<String>,
+}
+
+impl JsonError {
+    fn new(error: &anyhow::Error) -> Self {
+        let category = ErrorCategory::of(error);
+        JsonError {
+            category,
+            exit_code: category.exit_code(),
+            messages: error.chain().map(|cause| cause.to_string()).collect(),
+        }
+    }
+}
+
+/// Print the error that butido failed with to stderr and return the exit code for it
+pub fn report(error: &anyhow::Error, json: bool) -> ExitCode {
+    if json {
+        match serde_json::to_string(&JsonError::new(error)) {
+            Ok(json) => eprintln!("{json}"),
+            Err(e) => eprintln!("Error: {error:?}\n\nSerializing the error failed: {e}"),
+        }
+    } else {
+        eprintln!("Error: {error:?}");
+    }
+    ExitCode::from(ErrorCategory::of(error).exit_code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anyhow::anyhow;
+    use anyhow::Context;
+
+    #[test]
+    fn test_category_of() {
+        let error = anyhow!("Found no package.")
+            .context(ErrorCategory::Resolution)
+            .context("build command failed");
+        assert_eq!(ErrorCategory::of(&error), ErrorCategory::Resolution);
+
+        let error = Err::<(), _>(diesel::result::Error::NotFound)
+            .context("Loading the submit")
+            .unwrap_err();
+        assert_eq!(ErrorCategory::of(&error), ErrorCategory::Database);
+
+        assert_eq!(
+            ErrorCategory::of(&anyhow!("Something else")),
+            ErrorCategory::Other
+        );
+    }
+
+    #[test]
+    fn test_json_error() {
+        let error = anyhow!("One or multiple errors during build")
+            .context(ErrorCategory::PartialFailure)
+            .context("build command failed");
+        assert_eq!(
+            serde_json::to_value(JsonError::new(&error)).unwrap(),
+            serde_json::json!({
+                "category": "partial-failure",
+                "exit_code": 7,
+                "messages": [
+                    "build command failed",
+                    "Build partially failed",
+                    "One or multiple errors during build",
+                ],
+            })
+        );
+    }
+}
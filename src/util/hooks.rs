@@ -0,0 +1,252 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Configured commands that run at certain points of a build
+
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Stdio;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, trace};
+use uuid::Uuid;
+
+use crate::config::PostBuildHook;
+use crate::config::PreSubmitHook;
+use crate::package::glob_matches;
+use crate::package::Package;
+
+/// The metadata fields of packages that pre-submit hooks can require
+pub const METADATA_FIELDS: &[&str] = &[
+    "maintainer",
+    "license",
+    "description",
+    "homepage",
+    "upstream",
+];
+
+/// Run the pre-submit hooks for the packages of a submit, in the configured order
+///
+/// Fails with the violations or the output of the first hook that rejects the submit.
+pub async fn run_pre_submit_hooks(hooks: &[PreSubmitHook], packages: &[&Package]) -> Result<()> {
+    if hooks.is_empty() {
+        return Ok(());
+    }
+
+    let json = serde_json::to_vec(packages).context("Serializing the packages of the submit")?;
+    for hook in hooks {
+        debug!("Running pre-submit hook '{}'", hook.name());
+        let violations = builtin_violations(hook, packages);
+        if !violations.is_empty() {
+            return Err(anyhow!(
+                "Pre-submit hook '{}' rejected the submit:\n  {}",
+                hook.name(),
+                violations.join("\n  ")
+            ));
+        }
+
+        let Some(command) = hook.command() else {
+            continue;
+        };
+
+        // safe because the configuration validates that the command is not empty
+        let mut child = tokio::process::Command::new(&command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| anyhow!("Running pre-submit hook '{}'", hook.name()))?;
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("No stdin for pre-submit hook '{}'", hook.name()))?;
+
+        // The packages are written while the output is read, a hook does not have to read them
+        let write = async move {
+            if let Err(e) = stdin.write_all(&json).await {
+                debug!("Writing the packages to pre-submit hook failed: {}", e);
+            }
+        };
+        let ((), output) = tokio::join!(write, child.wait_with_output());
+        let output =
+            output.with_context(|| anyhow!("Running pre-submit hook '{}'", hook.name()))?;
+        trace!(
+            "Pre-submit hook '{}' exited with {}",
+            hook.name(),
+            output.status
+        );
+
+        if !output.status.success() {
+            return Err(anyhow!(
+                "Pre-submit hook '{}' rejected the submit ({}):\n{}{}",
+                hook.name(),
+                output.status,
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// The violations of the built-in checks of a pre-submit hook
+fn builtin_violations(hook: &PreSubmitHook, packages: &[&Package]) -> Vec<String> {
+    packages
+        .iter()
+        .flat_map(|package| {
+            let denied = hook
+                .denied_packages()
+                .iter()
+                .filter(|pattern| glob_matches(pattern, package.name()))
+                .map(move |pattern| {
+                    format!(
+                        "{} {} is denied ({})",
+                        package.name(),
+                        package.version(),
+                        pattern
+                    )
+                });
+            let missing = hook
+                .required_metadata()
+                .iter()
+                .filter(|field| !has_metadata(package, field))
+                .map(move |field| {
+                    format!(
+                        "{} {} does not set '{}'",
+                        package.name(),
+                        package.version(),
+                        field
+                    )
+                });
+            denied.chain(missing)
+        })
+        .collect()
+}
+
+fn has_metadata(package: &Package, field: &str) -> bool {
+    match field {
+        "maintainer" => package.maintainer().is_some(),
+        "license" => package.license().is_some(),
+        "description" => package.description().is_some(),
+        "homepage" => package.homepage().is_some(),
+        "upstream" => package.upstream().is_some(),
+        _ => false,
+    }
+}
+
+/// Run the post-build hooks for the artifacts of a job, in the configured order
+///
+/// Fails with the output of the first hook that exits with a non-zero exit code.
+pub async fn run_post_build_hooks(
+    hooks: &[PostBuildHook],
+    package: &Package,
+    job_uuid: &Uuid,
+    staging_dir: &Path,
+    artifacts: &[PathBuf],
+) -> Result<()> {
+    let env = [
+        ("BUTIDO_PACKAGE_NAME", package.name().to_string()),
+        ("BUTIDO_PACKAGE_VERSION", package.version().to_string()),
+        ("BUTIDO_JOB_UUID", job_uuid.to_string()),
+    ];
+
+    for hook in hooks {
+        debug!(
+            "Running post-build hook '{}' for job {}",
+            hook.name(),
+            job_uuid
+        );
+        let mut command = match hook.image() {
+            Some(image) => {
+                let mut command = tokio::process::Command::new("docker");
+                command
+                    .arg("run")
+                    .arg("--rm")
+                    .arg("--volume")
+                    .arg(format!("{0}:{0}:ro", staging_dir.display()));
+                // Without a value, docker passes the variables from its own environment
+                for (key, _) in env.iter() {
+                    command.arg("--env").arg(key);
+                }
+                command.arg(image).args(hook.command());
+                command
+            }
+            None => {
+                // safe because the configuration validates that the command is not empty
+                let mut command = tokio::process::Command::new(&hook.command()[0]);
+                command.args(&hook.command()[1..]);
+                command
+            }
+        };
+
+        let output = command
+            .args(artifacts)
+            .envs(env.iter().cloned())
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output()
+            .await
+            .with_context(|| anyhow!("Running post-build hook '{}'", hook.name()))?;
+        trace!(
+            "Post-build hook '{}' exited with {}",
+            hook.name(),
+            output.status
+        );
+
+        if !output.status.success() {
+            return Err(anyhow!(
+                "Post-build hook '{}' rejected the artifacts of {} {} ({}):\n{}{}",
+                hook.name(),
+                package.name(),
+                package.version(),
+                output.status,
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::package::tests::package;
+
+    #[test]
+    fn test_builtin_violations() {
+        let hook = serde_json::from_value::<PreSubmitHook>(serde_json::json!({
+            "name": "policy",
+            "denied_packages": ["openssl*"],
+            "required_metadata": ["license"],
+        }))
+        .unwrap();
+        let a = package("a", "1.0", "https://rust-lang.org", "123");
+        let openssl = package("openssl-legacy", "1.1", "https://rust-lang.org", "123");
+
+        let violations = builtin_violations(&hook, &[&a, &openssl]);
+        assert_eq!(
+            violations,
+            vec![
+                "a 1.0 does not set 'license'",
+                "openssl-legacy 1.1 is denied (openssl*)",
+                "openssl-legacy 1.1 does not set 'license'",
+            ]
+        );
+    }
+}
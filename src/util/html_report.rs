@@ -0,0 +1,323 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Standalone HTML reports for submits
+//!
+//! The report is a single HTML file without external resources, so that it can be attached to
+//! release tickets or sent by mail.
+
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Error;
+use anyhow::Result;
+use diesel::ExpressionMethods;
+use diesel::PgConnection;
+use diesel::QueryDsl;
+use diesel::RunQueryDsl;
+use itertools::Itertools;
+
+use crate::config::Configuration;
+use crate::db::models as dbmodels;
+use crate::log::JobResult;
+use crate::log::ParsedLog;
+use crate::schema;
+use crate::util::junit::escape;
+
+const STYLE: &str = r#"
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; vertical-align: top; }
+th { background: #eee; }
+tr.success td.status { background: #c8e6c9; }
+tr.errored td.status { background: #ffcdd2; }
+tr.unknown td.status { background: #fff3c4; }
+pre { background: #f6f6f6; padding: 0.5em; overflow-x: auto; max-height: 40em; }
+summary { cursor: pointer; }
+"#;
+
+/// An artifact of a job with the location it can be found at
+#[derive(Debug)]
+struct ArtifactLink {
+    path: String,
+
+    /// A file URL, if the location could be determined
+    href: Option<String>,
+}
+
+#[derive(Debug)]
+struct JobRow {
+    uuid: uuid::Uuid,
+    package_name: String,
+    package_version: String,
+    endpoint: String,
+    duration: Option<chrono::Duration>,
+    result: JobResult,
+    failure_class: Option<String>,
+
+    /// The last lines of the log of a job that did not succeed
+    log_tail: Option<String>,
+    artifacts: Vec<ArtifactLink>,
+}
+
+/// An HTML report for one submit
+#[derive(Debug)]
+pub struct HtmlReport {
+    submit: uuid::Uuid,
+    submit_time: chrono::NaiveDateTime,
+    commit: String,
+    state: String,
+    requested_package: String,
+    image: String,
+    jobs: Vec<JobRow>,
+}
+
+impl HtmlReport {
+    /// Create a report with a summary of the submit and a row for each of its jobs (retried jobs
+    /// are left out)
+    ///
+    /// The log tails of the jobs that did not succeed contain the last `log_tail_lines` lines.
+    pub fn for_submit(
+        conn: &mut PgConnection,
+        config: &Configuration,
+        submit: &dbmodels::Submit,
+        log_tail_lines: usize,
+    ) -> Result<Self> {
+        let githash = dbmodels::GitHash::with_id(conn, submit.repo_hash_id)?;
+        let requested_package = dbmodels::Package::fetch_by_id(conn, submit.requested_package_id)?
+            .ok_or_else(|| anyhow!("Package for submit {} not found", submit.uuid))?;
+        let image = dbmodels::Image::fetch_by_id(conn, submit.requested_image_id)?
+            .ok_or_else(|| anyhow!("Image for submit {} not found", submit.uuid))?;
+
+        let staging_dir = config.staging_directory().join(submit.uuid.to_string());
+        let jobs = schema::jobs::table
+            .inner_join(schema::packages::table)
+            .inner_join(schema::endpoints::table)
+            .filter(schema::jobs::submit_id.eq(submit.id))
+            .filter(schema::jobs::retried.eq(false))
+            .order_by(schema::jobs::id)
+            .load::<(dbmodels::Job, dbmodels::Package, dbmodels::Endpoint)>(conn)
+            .with_context(|| anyhow!("Loading jobs for submit = {}", submit.uuid))?
+            .into_iter()
+            .map(|(job, package, endpoint)| {
+                let result = ParsedLog::from_str(&job.log_text)
+                    .with_context(|| anyhow!("Parsing log of job {}", job.uuid))?
+                    .is_successfull();
+                let log_tail = (result != JobResult::Success).then(|| {
+                    let lines = job.log_text.lines().collect::<Vec<_>>();
+                    lines[lines.len().saturating_sub(log_tail_lines)..]
+                        .iter()
+                        .map(|line| crate::log::strip_ansi_escapes(line))
+                        .join("\n")
+                });
+
+                // Link to the latest release of an artifact, the staging store of the submit
+                // might have been cleaned up already
+                let artifacts = dbmodels::Artifact::of_job_with_releases(conn, &job)?
+                    .into_iter()
+                    .map(|(artifact, releases)| {
+                        let location = match releases.last() {
+                            Some((release, store)) => config
+                                .releases_directory()
+                                .join(&store.store_name)
+                                .join(release.path_in_store(&artifact)),
+                            None => staging_dir.join(&artifact.path),
+                        };
+                        ArtifactLink {
+                            href: file_url(&location),
+                            path: artifact.path,
+                        }
+                    })
+                    .collect();
+
+                Ok(JobRow {
+                    uuid: job.uuid,
+                    package_name: package.name,
+                    package_version: package.version,
+                    endpoint: endpoint.name,
+                    duration: job.duration(),
+                    result,
+                    failure_class: job.failure_class,
+                    log_tail,
+                    artifacts,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(HtmlReport {
+            submit: submit.uuid,
+            submit_time: submit.submit_time,
+            commit: githash.hash,
+            state: submit.state.clone(),
+            requested_package: format!("{} {}", requested_package.name, requested_package.version),
+            image: image.name,
+            jobs,
+        })
+    }
+
+    pub fn write_to_file(&self, path: &Path) -> Result<()> {
+        let file = std::fs::File::create(path)
+            .with_context(|| anyhow!("Creating HTML report {}", path.display()))?;
+        let mut file = std::io::BufWriter::new(file);
+        self.write(&mut file)
+            .and_then(|_| file.flush().map_err(Error::from))
+            .with_context(|| anyhow!("Writing HTML report {}", path.display()))
+    }
+
+    fn write<W: Write>(&self, out: &mut W) -> Result<()> {
+        let count = |result: JobResult| self.jobs.iter().filter(|j| j.result == result).count();
+        let title = escape(&format!("butido submit {}", self.submit));
+
+        writeln!(out, "<!DOCTYPE html>")?;
+        writeln!(out, r#"<html lang="en">"#)?;
+        writeln!(out, r#"<head><meta charset="utf-8"><title>{title}</title>"#)?;
+        writeln!(out, "<style>{STYLE}</style></head>")?;
+        writeln!(out, "<body>")?;
+        writeln!(out, "<h1>{title}</h1>")?;
+
+        writeln!(out, "<table>")?;
+        for (key, value) in [
+            ("Date", self.submit_time.to_string()),
+            ("Commit", self.commit.clone()),
+            ("State", self.state.clone()),
+            ("Package", self.requested_package.clone()),
+            ("Image", self.image.clone()),
+            ("Jobs", self.jobs.len().to_string()),
+            ("Success", count(JobResult::Success).to_string()),
+            ("Errored", count(JobResult::Errored).to_string()),
+            ("Unknown", count(JobResult::Unknown).to_string()),
+        ] {
+            writeln!(out, "<tr><th>{key}</th><td>{}</td></tr>", escape(&value))?;
+        }
+        writeln!(out, "</table>")?;
+
+        writeln!(out, "<h2>Jobs</h2>")?;
+        writeln!(out, "<table>")?;
+        writeln!(
+            out,
+            "<tr><th>Job</th><th>Package</th><th>Version</th><th>Endpoint</th><th>Duration</th><th>Status</th><th>Failure</th><th>Artifacts</th></tr>"
+        )?;
+        for job in self.jobs.iter() {
+            let (class, status) = match job.result {
+                JobResult::Success => ("success", "Success"),
+                JobResult::Errored => ("errored", "Error"),
+                JobResult::Unknown => ("unknown", "Unknown"),
+            };
+            let duration = job
+                .duration
+                .and_then(|d| d.to_std().ok())
+                .map(|d| humantime::format_duration(std::time::Duration::from_secs(d.as_secs())))
+                .map(|d| d.to_string())
+                .unwrap_or_else(|| String::from("-"));
+            let artifacts = job
+                .artifacts
+                .iter()
+                .map(|artifact| match artifact.href.as_ref() {
+                    Some(href) => format!(
+                        r#"<a href="{}">{}</a>"#,
+                        escape(href),
+                        escape(&artifact.path)
+                    ),
+                    None => escape(&artifact.path),
+                })
+                .join("<br>");
+
+            writeln!(
+                out,
+                r#"<tr class="{class}"><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class="status">{status}</td><td>{}</td><td>{artifacts}</td></tr>"#,
+                job.uuid,
+                escape(&job.package_name),
+                escape(&job.package_version),
+                escape(&job.endpoint),
+                duration,
+                escape(job.failure_class.as_deref().unwrap_or("-")),
+            )?;
+        }
+        writeln!(out, "</table>")?;
+
+        let failed = self
+            .jobs
+            .iter()
+            .filter_map(|job| job.log_tail.as_ref().map(|tail| (job, tail)))
+            .collect::<Vec<_>>();
+        if !failed.is_empty() {
+            writeln!(out, "<h2>Logs of failed jobs</h2>")?;
+            for (job, tail) in failed {
+                writeln!(
+                    out,
+                    "<details><summary>{} {} (<code>{}</code>)</summary><pre>{}</pre></details>",
+                    escape(&job.package_name),
+                    escape(&job.package_version),
+                    job.uuid,
+                    escape(tail)
+                )?;
+            }
+        }
+
+        writeln!(out, "</body>")?;
+        writeln!(out, "</html>")?;
+        Ok(())
+    }
+}
+
+/// The file URL of a (possibly relative) path
+fn file_url(path: &Path) -> Option<String> {
+    let path = std::path::absolute(path).ok()?;
+    url::Url::from_file_path(path).ok().map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_report() {
+        let job = |name: &str, result: JobResult, log_tail: Option<&str>| JobRow {
+            uuid: uuid::Uuid::nil(),
+            package_name: String::from(name),
+            package_version: String::from("1"),
+            endpoint: String::from("ep"),
+            duration: Some(chrono::Duration::seconds(90)),
+            result,
+            failure_class: None,
+            log_tail: log_tail.map(String::from),
+            artifacts: vec![ArtifactLink {
+                path: format!("{name}-1.tar.gz"),
+                href: Some(format!("file:///staging/{name}-1.tar.gz")),
+            }],
+        };
+        let report = HtmlReport {
+            submit: uuid::Uuid::nil(),
+            submit_time: chrono::NaiveDateTime::default(),
+            commit: String::from("abc"),
+            state: String::from("finished"),
+            requested_package: String::from("a 1"),
+            image: String::from("debian:bookworm"),
+            jobs: vec![
+                job("a", JobResult::Success, None),
+                job("b", JobResult::Errored, Some("make: *** <all> Error 1")),
+            ],
+        };
+
+        let mut out = Vec::new();
+        report.write(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("<tr><th>Errored</th><td>1</td></tr>"));
+        assert!(out.contains(r#"<tr class="errored">"#));
+        assert!(out.contains(r#"<td class="status">Error</td>"#));
+        assert!(out.contains("<td>1m 30s</td>"));
+        assert!(out.contains(r#"<a href="file:///staging/a-1.tar.gz">a-1.tar.gz</a>"#));
+        assert!(out.contains("<pre>make: *** &lt;all&gt; Error 1</pre>"));
+    }
+}
@@ -0,0 +1,333 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! JUnit XML reports for submits
+//!
+//! Each job of a submit is rendered as one test case, so that CI systems (e.g., Jenkins or GitLab)
+//! can display the results of a submit natively.
+
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Error;
+use anyhow::Result;
+use diesel::ExpressionMethods;
+use diesel::PgConnection;
+use diesel::QueryDsl;
+use diesel::RunQueryDsl;
+use itertools::Itertools;
+
+use crate::db::models as dbmodels;
+use crate::log::JobResult;
+use crate::log::LogItem;
+use crate::log::ParsedLog;
+use crate::schema;
+
+#[derive(Debug)]
+enum Outcome {
+    Passed,
+
+    /// The packaging script failed
+    Failed {
+        message: String,
+        excerpt: String,
+    },
+
+    /// The job did not report a result or could not be run at all
+    Error {
+        message: String,
+        excerpt: String,
+    },
+}
+
+#[derive(Debug)]
+struct TestCase {
+    job_uuid: uuid::Uuid,
+    classname: String,
+    name: String,
+    time: f64,
+    outcome: Outcome,
+}
+
+/// A JUnit report for one submit
+#[derive(Debug)]
+pub struct JunitReport {
+    submit: uuid::Uuid,
+    timestamp: chrono::NaiveDateTime,
+    testcases: Vec<TestCase>,
+}
+
+impl JunitReport {
+    /// Create a report with one test case for each job of the submit that is recorded in the
+    /// database
+    ///
+    /// The failure messages contain the last `excerpt_lines` lines of the job logs.
+    pub fn for_submit(
+        conn: &mut PgConnection,
+        submit: &dbmodels::Submit,
+        excerpt_lines: usize,
+    ) -> Result<Self> {
+        let testcases = schema::jobs::table
+            .inner_join(schema::packages::table)
+            .filter(schema::jobs::submit_id.eq(submit.id))
+            .order_by(schema::jobs::id)
+            .load::<(dbmodels::Job, dbmodels::Package)>(conn)
+            .with_context(|| anyhow!("Loading jobs for submit = {}", submit.uuid))?
+            .into_iter()
+            .map(|(job, package)| TestCase::from_job(&job, &package, excerpt_lines))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(JunitReport {
+            submit: submit.uuid,
+            timestamp: submit.submit_time,
+            testcases,
+        })
+    }
+
+    /// Add a test case for a job that failed without being recorded in the database (e.g.,
+    /// because its container could not be started)
+    pub fn add_unrecorded_failure(
+        &mut self,
+        job_uuid: &uuid::Uuid,
+        package_name: &str,
+        package_version: &str,
+        error: &Error,
+    ) {
+        self.testcases.push(TestCase {
+            job_uuid: *job_uuid,
+            classname: package_name.to_string(),
+            name: format!("{package_name} {package_version} ({job_uuid})"),
+            time: 0.0,
+            outcome: Outcome::Error {
+                message: error.to_string(),
+                excerpt: error
+                    .chain()
+                    .map(|cause| cause.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            },
+        });
+    }
+
+    /// Whether the report contains a test case for the passed job
+    pub fn contains_job(&self, job_uuid: &uuid::Uuid) -> bool {
+        self.testcases.iter().any(|tc| tc.job_uuid == *job_uuid)
+    }
+
+    pub fn write_to_file(&self, path: &Path) -> Result<()> {
+        let file = std::fs::File::create(path)
+            .with_context(|| anyhow!("Creating JUnit report {}", path.display()))?;
+        let mut file = std::io::BufWriter::new(file);
+        self.write(&mut file)
+            .and_then(|_| file.flush().map_err(Error::from))
+            .with_context(|| anyhow!("Writing JUnit report {}", path.display()))
+    }
+
+    fn write<W: Write>(&self, out: &mut W) -> Result<()> {
+        let tests = self.testcases.len();
+        let failures = self
+            .testcases
+            .iter()
+            .filter(|tc| matches!(tc.outcome, Outcome::Failed { .. }))
+            .count();
+        let errors = self
+            .testcases
+            .iter()
+            .filter(|tc| matches!(tc.outcome, Outcome::Error { .. }))
+            .count();
+        let time = self.testcases.iter().map(|tc| tc.time).sum::<f64>();
+        let name = escape(&format!("butido submit {}", self.submit));
+
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            out,
+            r#"<testsuites name="{name}" tests="{tests}" failures="{failures}" errors="{errors}" time="{time:.3}">"#
+        )?;
+        writeln!(
+            out,
+            r#"  <testsuite name="{name}" tests="{tests}" failures="{failures}" errors="{errors}" time="{time:.3}" timestamp="{}">"#,
+            self.timestamp.format("%Y-%m-%dT%H:%M:%S")
+        )?;
+
+        for tc in self.testcases.iter() {
+            write!(
+                out,
+                r#"    <testcase classname="{}" name="{}" time="{:.3}""#,
+                escape(&tc.classname),
+                escape(&tc.name),
+                tc.time
+            )?;
+
+            match &tc.outcome {
+                Outcome::Passed => writeln!(out, "/>")?,
+                Outcome::Failed { message, excerpt } => {
+                    writeln!(out, ">")?;
+                    writeln!(
+                        out,
+                        r#"      <failure message="{}" type="failure">{}</failure>"#,
+                        escape(message),
+                        escape(excerpt)
+                    )?;
+                    writeln!(out, "    </testcase>")?;
+                }
+                Outcome::Error { message, excerpt } => {
+                    writeln!(out, ">")?;
+                    writeln!(
+                        out,
+                        r#"      <error message="{}" type="error">{}</error>"#,
+                        escape(message),
+                        escape(excerpt)
+                    )?;
+                    writeln!(out, "    </testcase>")?;
+                }
+            }
+        }
+
+        writeln!(out, "  </testsuite>")?;
+        writeln!(out, "</testsuites>")?;
+        Ok(())
+    }
+}
+
+impl TestCase {
+    fn from_job(
+        job: &dbmodels::Job,
+        package: &dbmodels::Package,
+        excerpt_lines: usize,
+    ) -> Result<Self> {
+        let parsed = ParsedLog::from_str(&job.log_text)
+            .with_context(|| anyhow!("Parsing log of job {}", job.uuid))?;
+        let result = parsed.is_successfull();
+
+        let (last_phase, error_message) = parsed.into_iter().fold(
+            (None, None),
+            |(last_phase, error_message), item| match item {
+                LogItem::CurrentPhase(phase) if error_message.is_none() => {
+                    (Some(phase), error_message)
+                }
+                LogItem::State(Err(msg)) => (last_phase, Some(msg)),
+                _ => (last_phase, error_message),
+            },
+        );
+
+        let excerpt = || {
+            let lines = job.log_text.lines().collect::<Vec<_>>();
+            lines[lines.len().saturating_sub(excerpt_lines)..]
+                .iter()
+                .map(|line| crate::log::strip_ansi_escapes(line))
+                .join("\n")
+        };
+
+        let outcome = match result {
+            JobResult::Success => Outcome::Passed,
+            JobResult::Errored => Outcome::Failed {
+                message: match (last_phase, error_message) {
+                    (Some(phase), Some(msg)) => format!("Job errored in phase '{phase}': {msg}"),
+                    (Some(phase), None) => format!("Job errored in phase '{phase}'"),
+                    (None, Some(msg)) => format!("Job errored: {msg}"),
+                    (None, None) => String::from("Job errored"),
+                },
+                excerpt: excerpt(),
+            },
+            JobResult::Unknown => Outcome::Error {
+                message: String::from("Job did not report a result"),
+                excerpt: excerpt(),
+            },
+        };
+
+        let time = job
+            .duration()
+            .and_then(|d| d.to_std().ok())
+            .map(|d| d.as_secs_f64())
+            .unwrap_or(0.0);
+
+        Ok(TestCase {
+            job_uuid: job.uuid,
+            classname: package.name.clone(),
+            name: format!("{} {} ({})", package.name, package.version, job.uuid),
+            time,
+            outcome,
+        })
+    }
+}
+
+/// Escape a string for use in XML (and HTML) attributes and text
+///
+/// Control characters (except for whitespace) are not allowed in XML 1.0, so they are dropped.
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' | '\r' | '\t' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("a < b && c > \"d\""),
+            "a &lt; b &amp;&amp; c &gt; &quot;d&quot;"
+        );
+        assert_eq!(escape("line\nline\x1b[31m"), "line\nline[31m");
+    }
+
+    #[test]
+    fn test_write_report() {
+        let report = JunitReport {
+            submit: uuid::Uuid::nil(),
+            timestamp: chrono::NaiveDateTime::default(),
+            testcases: vec![
+                TestCase {
+                    job_uuid: uuid::Uuid::nil(),
+                    classname: String::from("a"),
+                    name: String::from("a 1"),
+                    time: 1.5,
+                    outcome: Outcome::Passed,
+                },
+                TestCase {
+                    job_uuid: uuid::Uuid::nil(),
+                    classname: String::from("b"),
+                    name: String::from("b 2"),
+                    time: 2.0,
+                    outcome: Outcome::Failed {
+                        message: String::from("Job errored in phase 'build'"),
+                        excerpt: String::from("make: *** [all] Error 1"),
+                    },
+                },
+            ],
+        };
+
+        let mut out = Vec::new();
+        report.write(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains(r#"tests="2" failures="1" errors="0" time="3.500""#));
+        assert!(out.contains(r#"<testcase classname="a" name="a 1" time="1.500"/>"#));
+        assert!(out.contains(
+            r#"<failure message="Job errored in phase &apos;build&apos;" type="failure">make: *** [all] Error 1</failure>"#
+        ));
+    }
+}
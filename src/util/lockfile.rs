@@ -0,0 +1,419 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Lockfiles that pin the dependency tree of a package
+//!
+//! A lockfile records everything that the dependency tree of a root package was resolved to: the
+//! exact version of every package, the sources (URL and hash) of every package, the dependency
+//! edges between the packages and the digest of the image. Comparing a lockfile with a freshly
+//! generated one reveals every change of the repository (or the image) that affects the build.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use itertools::Itertools;
+use petgraph::visit::EdgeRef;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::package::Dag;
+use crate::package::DependencyType;
+use crate::util::docker::ImageName;
+
+/// The version of the lockfile format
+const LOCKFILE_VERSION: u32 = 1;
+
+/// The default path of a lockfile, relative to the current directory
+pub const DEFAULT_LOCKFILE_PATH: &str = "butido.lock";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lockfile {
+    version: u32,
+    root: LockedPackageId,
+    image: LockedImage,
+
+    /// All packages of the dependency tree (including the root package), sorted by name and
+    /// version
+    packages: Vec<LockedPackage>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct LockedPackageId {
+    name: String,
+    version: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct LockedImage {
+    name: String,
+
+    /// The image ID on the endpoints, if it could be determined
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    digest: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct LockedPackage {
+    name: String,
+    version: String,
+    sources: BTreeMap<String, LockedSource>,
+
+    /// The direct dependencies of the package, sorted
+    dependencies: Vec<LockedDependency>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct LockedSource {
+    url: String,
+    #[serde(rename = "type")]
+    hash_type: String,
+    hash: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+struct LockedDependency {
+    name: String,
+    version: String,
+    #[serde(rename = "type")]
+    dependency_type: DependencyType,
+}
+
+impl std::fmt::Display for LockedDependency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let dependency_type = match self.dependency_type {
+            DependencyType::Build => "build",
+            DependencyType::Runtime => "runtime",
+        };
+        write!(f, "{} {} ({})", self.name, self.version, dependency_type)
+    }
+}
+
+impl Lockfile {
+    /// Create the lockfile of a dependency tree that was resolved for the image `image`
+    pub fn from_dag(dag: &Dag, image: &ImageName, digest: Option<String>) -> Self {
+        let graph = dag.dag().inner();
+        let root = &graph[*dag.root_idx()];
+
+        let packages = graph
+            .node_indices()
+            .map(|idx| {
+                let package = &graph[idx];
+                let sources = package
+                    .sources()
+                    .iter()
+                    .map(|(name, source)| {
+                        let locked = LockedSource {
+                            url: source.url().to_string(),
+                            hash_type: source.hash().hashtype().to_string(),
+                            hash: source.hash().value().to_string(),
+                        };
+                        (name.clone(), locked)
+                    })
+                    .collect();
+                let dependencies = graph
+                    .edges_directed(idx, petgraph::Outgoing)
+                    .map(|edge| {
+                        let dependency = &graph[edge.target()];
+                        LockedDependency {
+                            name: dependency.name().to_string(),
+                            version: dependency.version().to_string(),
+                            dependency_type: edge.weight().clone(),
+                        }
+                    })
+                    .sorted()
+                    .collect();
+
+                LockedPackage {
+                    name: package.name().to_string(),
+                    version: package.version().to_string(),
+                    sources,
+                    dependencies,
+                }
+            })
+            .sorted_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)))
+            .collect();
+
+        Lockfile {
+            version: LOCKFILE_VERSION,
+            root: LockedPackageId {
+                name: root.name().to_string(),
+                version: root.version().to_string(),
+            },
+            image: LockedImage {
+                name: image.to_string(),
+                digest,
+            },
+            packages,
+        }
+    }
+
+    pub fn image_digest(&self) -> Option<&String> {
+        self.image.digest.as_ref()
+    }
+
+    /// The ways in which `current` deviates from this (locked) lockfile, as messages for the user
+    ///
+    /// The image digests are only compared if both are known.
+    pub fn differences(&self, current: &Lockfile) -> Vec<String> {
+        let mut differences = Vec::new();
+
+        if self.root != current.root {
+            differences.push(format!(
+                "Root package: locked {} {}, is {} {}",
+                self.root.name, self.root.version, current.root.name, current.root.version
+            ));
+        }
+        if self.image.name != current.image.name {
+            differences.push(format!(
+                "Image: locked {}, is {}",
+                self.image.name, current.image.name
+            ));
+        }
+        if let (Some(locked), Some(digest)) = (&self.image.digest, &current.image.digest) {
+            if locked != digest {
+                differences.push(format!(
+                    "Digest of image {}: locked {}, is {}",
+                    current.image.name, locked, digest
+                ));
+            }
+        }
+
+        let versions = |lockfile: &Lockfile| -> BTreeMap<String, BTreeSet<String>> {
+            lockfile
+                .packages
+                .iter()
+                .fold(BTreeMap::new(), |mut map, p| {
+                    map.entry(p.name.clone())
+                        .or_insert_with(BTreeSet::new)
+                        .insert(p.version.clone());
+                    map
+                })
+        };
+        let locked_versions = versions(self);
+        let current_versions = versions(current);
+        for name in locked_versions
+            .keys()
+            .chain(current_versions.keys())
+            .unique()
+        {
+            match (locked_versions.get(name), current_versions.get(name)) {
+                (Some(locked), Some(current)) if locked != current => differences.push(format!(
+                    "Version of {}: locked {}, is {}",
+                    name,
+                    locked.iter().join(", "),
+                    current.iter().join(", ")
+                )),
+                (Some(locked), None) => differences.push(format!(
+                    "Package {} {} is not in the dependency tree anymore",
+                    name,
+                    locked.iter().join(", ")
+                )),
+                (None, Some(current)) => differences.push(format!(
+                    "Package {} {} is not in the lockfile",
+                    name,
+                    current.iter().join(", ")
+                )),
+                _ => {}
+            }
+        }
+
+        // Packages whose version changed are reported above already, only the packages that
+        // are in both lockfiles are compared in detail
+        for locked in self.packages.iter() {
+            let Some(current) = current
+                .packages
+                .iter()
+                .find(|p| p.name == locked.name && p.version == locked.version)
+            else {
+                continue;
+            };
+            differences.extend(locked.differences(current));
+        }
+
+        differences
+    }
+
+    pub fn write_to_file(&self, path: &Path) -> Result<()> {
+        let mut content = serde_json::to_string_pretty(self)?;
+        content.push('\n');
+        std::fs::write(path, content)
+            .with_context(|| anyhow!("Writing lockfile {}", path.display()))
+    }
+
+    pub fn read_from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| anyhow!("Reading lockfile {}", path.display()))?;
+        let lockfile = serde_json::from_str::<Lockfile>(&content)
+            .with_context(|| anyhow!("Parsing lockfile {}", path.display()))?;
+        if lockfile.version != LOCKFILE_VERSION {
+            return Err(anyhow!(
+                "Lockfile {} has version {}, only version {} is supported",
+                path.display(),
+                lockfile.version,
+                LOCKFILE_VERSION
+            ));
+        }
+        Ok(lockfile)
+    }
+}
+
+impl LockedPackage {
+    fn differences(&self, current: &LockedPackage) -> Vec<String> {
+        let mut differences = Vec::new();
+        let package = format!("{} {}", self.name, self.version);
+
+        for name in self.sources.keys().chain(current.sources.keys()).unique() {
+            match (self.sources.get(name), current.sources.get(name)) {
+                (Some(locked), Some(current)) => {
+                    if locked.url != current.url {
+                        differences.push(format!(
+                            "URL of source '{}' of {}: locked {}, is {}",
+                            name, package, locked.url, current.url
+                        ));
+                    }
+                    if (&locked.hash_type, &locked.hash) != (&current.hash_type, &current.hash) {
+                        differences.push(format!(
+                            "Hash of source '{}' of {}: locked {}:{}, is {}:{}",
+                            name,
+                            package,
+                            locked.hash_type,
+                            locked.hash,
+                            current.hash_type,
+                            current.hash
+                        ));
+                    }
+                }
+                (Some(_), None) => {
+                    differences.push(format!("Source '{name}' of {package} was removed"))
+                }
+                (None, Some(_)) => {
+                    differences.push(format!("Source '{name}' of {package} was added"))
+                }
+                (None, None) => {}
+            }
+        }
+
+        for dependency in self.dependencies.iter() {
+            if !current.dependencies.contains(dependency) {
+                differences.push(format!(
+                    "Dependency of {package} on {dependency} was removed"
+                ));
+            }
+        }
+        for dependency in current.dependencies.iter() {
+            if !self.dependencies.contains(dependency) {
+                differences.push(format!("Dependency of {package} on {dependency} was added"));
+            }
+        }
+
+        differences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, version: &str, hash: &str, deps: &[(&str, &str)]) -> LockedPackage {
+        LockedPackage {
+            name: String::from(name),
+            version: String::from(version),
+            sources: BTreeMap::from([(
+                String::from("src"),
+                LockedSource {
+                    url: format!("https://example.com/{name}-{version}.tar.gz"),
+                    hash_type: String::from("sha256"),
+                    hash: String::from(hash),
+                },
+            )]),
+            dependencies: deps
+                .iter()
+                .map(|(name, version)| LockedDependency {
+                    name: String::from(*name),
+                    version: String::from(*version),
+                    dependency_type: DependencyType::Runtime,
+                })
+                .collect(),
+        }
+    }
+
+    fn lockfile(digest: Option<&str>, packages: Vec<LockedPackage>) -> Lockfile {
+        Lockfile {
+            version: LOCKFILE_VERSION,
+            root: LockedPackageId {
+                name: String::from("a"),
+                version: String::from("1"),
+            },
+            image: LockedImage {
+                name: String::from("debian:bookworm"),
+                digest: digest.map(String::from),
+            },
+            packages,
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let locked = lockfile(
+            Some("sha256:aaaa"),
+            vec![
+                package("a", "1", "11", &[("b", "1")]),
+                package("b", "1", "22", &[]),
+            ],
+        );
+        let json = serde_json::to_string_pretty(&locked).unwrap();
+        assert!(json.contains(r#""type": "runtime""#));
+        assert_eq!(serde_json::from_str::<Lockfile>(&json).unwrap(), locked);
+        assert!(locked.differences(&locked).is_empty());
+    }
+
+    #[test]
+    fn test_differences() {
+        let locked = lockfile(
+            Some("sha256:aaaa"),
+            vec![
+                package("a", "1", "11", &[("b", "1"), ("c", "1")]),
+                package("b", "1", "22", &[]),
+                package("c", "1", "33", &[]),
+            ],
+        );
+        let current = lockfile(
+            Some("sha256:bbbb"),
+            vec![
+                package("a", "1", "11", &[("b", "2"), ("d", "1")]),
+                package("b", "2", "22", &[]),
+                package("c", "1", "34", &[]),
+                package("d", "1", "44", &[]),
+            ],
+        );
+
+        assert_eq!(
+            locked.differences(&current),
+            vec![
+                "Digest of image debian:bookworm: locked sha256:aaaa, is sha256:bbbb",
+                "Version of b: locked 1, is 2",
+                "Package d 1 is not in the lockfile",
+                "Dependency of a 1 on b 1 (runtime) was removed",
+                "Dependency of a 1 on c 1 (runtime) was removed",
+                "Dependency of a 1 on b 2 (runtime) was added",
+                "Dependency of a 1 on d 1 (runtime) was added",
+                "Hash of source 'src' of c 1: locked sha256:33, is sha256:34",
+            ]
+        );
+
+        // An unknown digest is not a difference
+        let current = lockfile(None, locked.packages.clone());
+        assert!(locked.differences(&current).is_empty());
+    }
+}
@@ -41,12 +41,22 @@ impl AsRef<str> for EnvironmentVariableName {
     }
 }
 
+pub mod archive;
+pub mod disk_space;
 pub mod docker;
 pub mod env;
+pub mod error;
 pub mod filters;
 pub mod git;
+pub mod hooks;
+pub mod html_report;
+pub mod junit;
+pub mod lockfile;
+pub mod notify;
+pub mod offline;
 pub mod parser;
 pub mod progress;
+pub mod provenance;
 
 pub fn stdout_is_pipe() -> bool {
     !std::io::stdout().is_terminal()
@@ -0,0 +1,129 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Desktop notifications when long local operations finish
+
+use std::process::Stdio;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use tracing::{debug, warn};
+
+use crate::config::NotificationConfig;
+
+/// An operation (e.g. a build) that sends a notification when it finishes, if it took long enough
+///
+/// Without a notification configuration, nothing is sent.
+pub struct LongOperation<'a> {
+    config: Option<&'a NotificationConfig>,
+    description: String,
+    started: Instant,
+}
+
+impl<'a> LongOperation<'a> {
+    pub fn start(config: Option<&'a NotificationConfig>, description: String) -> Self {
+        LongOperation {
+            config,
+            description,
+            started: Instant::now(),
+        }
+    }
+
+    /// Send a notification about the result of the operation, if it took at least the configured
+    /// minimum duration
+    ///
+    /// A failure to send the notification is only logged, it does not change the result.
+    pub async fn finish<T>(self, result: &Result<T>) {
+        let Some(config) = self.config else {
+            return;
+        };
+        let elapsed = self.started.elapsed();
+        if elapsed < Duration::from_secs(config.min_duration()) {
+            return;
+        }
+
+        let (summary, body) = notification_text(&self.description, elapsed, result);
+        if let Err(e) = send(config.command(), &summary, &body).await {
+            warn!("Sending the notification failed: {:?}", e);
+        }
+    }
+}
+
+/// The summary and the body of the notification about an operation
+fn notification_text<T>(
+    description: &str,
+    elapsed: Duration,
+    result: &Result<T>,
+) -> (String, String) {
+    let elapsed = humantime::format_duration(Duration::from_secs(elapsed.as_secs()));
+    match result {
+        Ok(_) => (
+            format!("butido: {description} finished"),
+            format!("Finished after {elapsed}"),
+        ),
+        Err(e) => (
+            format!("butido: {description} failed"),
+            format!("Failed after {elapsed}: {e:#}"),
+        ),
+    }
+}
+
+async fn send(command: &[String], summary: &str, body: &str) -> Result<()> {
+    debug!("Sending notification '{}' with {:?}", summary, command);
+    // safe because the configuration validates that the command is not empty
+    let output = tokio::process::Command::new(&command[0])
+        .args(&command[1..])
+        .arg(summary)
+        .arg(body)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .with_context(|| anyhow!("Running {}", command[0]))?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{} failed ({}): {}",
+            command[0],
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notification_text() {
+        let elapsed = Duration::from_millis(125_500);
+        assert_eq!(
+            notification_text("build of a 1.0", elapsed, &Ok(())),
+            (
+                String::from("butido: build of a 1.0 finished"),
+                String::from("Finished after 2m 5s")
+            )
+        );
+
+        let result = Err::<(), _>(anyhow!("Job failed").context("build command failed"));
+        assert_eq!(
+            notification_text("build of a 1.0", elapsed, &result),
+            (
+                String::from("butido: build of a 1.0 failed"),
+                String::from("Failed after 2m 5s: build command failed: Job failed")
+            )
+        );
+    }
+}
@@ -0,0 +1,45 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! The offline mode (`--offline`), in which butido does not access the network itself
+//!
+//! Everything that would be fetched from the network has to be available locally already (sources
+//! in the source cache, images on the endpoints or nodes). The endpoints and the database are
+//! still used, they are part of the build environment.
+
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+static OFFLINE: OnceLock<bool> = OnceLock::new();
+
+/// Set the offline mode for the whole process
+pub fn set_offline(offline: bool) {
+    // Only set once, from the command line arguments
+    let _ = OFFLINE.set(offline);
+}
+
+pub fn is_offline() -> bool {
+    OFFLINE.get().copied().unwrap_or(false)
+}
+
+/// Fail if butido runs in offline mode, `what` describes what requires the network access
+pub fn ensure_online<D: Display>(what: D) -> Result<()> {
+    if is_offline() {
+        Err(anyhow!(
+            "{} requires network access, which is forbidden with --offline",
+            what
+        ))
+    } else {
+        Ok(())
+    }
+}
@@ -8,29 +8,200 @@
 // SPDX-License-Identifier: EPL-2.0
 //
 
+use std::io::Write;
+use std::time::Duration;
+use std::time::Instant;
+
 use getset::CopyGetters;
+use getset::Getters;
 use indicatif::*;
 
-#[derive(Clone, Debug, CopyGetters)]
+#[derive(Clone, Debug, CopyGetters, Getters)]
 pub struct ProgressBars {
     bar_template: String,
+    bytes_bar_template: String,
 
     #[getset(get_copy = "pub")]
     hide: bool,
+
+    #[getset(get = "pub")]
+    status_lines: StatusLines,
 }
 
 impl ProgressBars {
-    pub fn setup(bar_template: String, hide: bool) -> Self {
-        ProgressBars { bar_template, hide }
+    pub fn setup(bar_template: String, bytes_bar_template: String, hide: bool) -> Self {
+        ProgressBars {
+            bar_template,
+            bytes_bar_template,
+            hide,
+            status_lines: StatusLines::default(),
+        }
+    }
+
+    /// Replace the progress bars with status lines in the passed format
+    pub fn with_status_lines(self, format: StatusLineFormat) -> Self {
+        ProgressBars {
+            hide: true,
+            status_lines: StatusLines(Some(format)),
+            ..self
+        }
     }
 
     pub fn bar(&self) -> anyhow::Result<ProgressBar> {
+        self.styled_bar(&self.bar_template)
+    }
+
+    /// Get a progress bar for operations that process bytes (downloads, unpacking, hashing)
+    ///
+    /// The length and position of this bar are expected to be set in bytes, so that the template
+    /// can show the throughput and the ETA.
+    pub fn bytes_bar(&self) -> anyhow::Result<ProgressBar> {
+        self.styled_bar(&self.bytes_bar_template)
+    }
+
+    fn styled_bar(&self, template: &str) -> anyhow::Result<ProgressBar> {
         if self.hide {
             Ok(ProgressBar::hidden())
         } else {
             let b = ProgressBar::new(1);
-            b.set_style(ProgressStyle::default_bar().template(&self.bar_template)?);
+            b.set_style(ProgressStyle::default_bar().template(template)?);
             Ok(b)
         }
     }
+
+    /// Print a summary line for a finished phase of an operation to stderr
+    ///
+    /// The time that passed since `started` is appended to the message.
+    /// Nothing is printed if progress bars are hidden (and not replaced by status lines).
+    pub fn summary<D: std::fmt::Display>(&self, started: Instant, msg: D) -> anyhow::Result<()> {
+        if self.status_lines.is_enabled() {
+            return self.status_lines.summary(started, &msg.to_string());
+        }
+
+        if self.hide {
+            return Ok(());
+        }
+
+        writeln!(
+            std::io::stderr(),
+            "{msg} (took {})",
+            humantime::format_duration(rounded_elapsed(started))
+        )
+        .map_err(anyhow::Error::from)
+    }
+}
+
+/// Only show full seconds (or milliseconds for short phases) to keep the output readable
+fn rounded_elapsed(started: Instant) -> Duration {
+    let elapsed = started.elapsed();
+    if elapsed.as_secs() > 0 {
+        Duration::from_secs(elapsed.as_secs())
+    } else {
+        Duration::from_millis(elapsed.as_millis() as u64)
+    }
+}
+
+/// The format of status lines
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StatusLineFormat {
+    /// Human readable lines
+    Plain,
+
+    /// One JSON object per line
+    Json,
+}
+
+/// Status lines that replace the progress bars in non-interactive environments (e.g., CI logs)
+///
+/// Instead of updating a bar, one line is printed to stderr for each event (job started, phase
+/// transitions, job finished, ...). If no format is set, nothing is printed.
+#[derive(Clone, Debug, Default)]
+pub struct StatusLines(Option<StatusLineFormat>);
+
+#[derive(serde::Serialize)]
+struct StatusLine<'a> {
+    time: String,
+    event: &'a str,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    job: Option<&'a uuid::Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    package: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    elapsed_secs: Option<u64>,
+}
+
+impl StatusLines {
+    pub fn is_enabled(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// Print a status line for an event of a job
+    ///
+    /// Failing to write to stderr is not fatal for a build, so errors are only logged.
+    pub fn job(
+        &self,
+        job_uuid: &uuid::Uuid,
+        package: &crate::package::Package,
+        event: &str,
+        detail: Option<&str>,
+    ) {
+        let line = StatusLine {
+            time: chrono::Local::now().to_rfc3339(),
+            event,
+            job: Some(job_uuid),
+            package: Some(package.name().as_ref()),
+            version: Some(package.version().as_ref()),
+            detail,
+            elapsed_secs: None,
+        };
+
+        if let Err(e) = self.print(&line) {
+            tracing::warn!("Failed to print status line: {:?}", e);
+        }
+    }
+
+    fn summary(&self, started: Instant, msg: &str) -> anyhow::Result<()> {
+        self.print(&StatusLine {
+            time: chrono::Local::now().to_rfc3339(),
+            event: "summary",
+            job: None,
+            package: None,
+            version: None,
+            detail: Some(msg),
+            elapsed_secs: Some(started.elapsed().as_secs()),
+        })
+    }
+
+    fn print(&self, line: &StatusLine) -> anyhow::Result<()> {
+        let text = match self.0 {
+            None => return Ok(()),
+            Some(StatusLineFormat::Json) => serde_json::to_string(line)?,
+            Some(StatusLineFormat::Plain) => {
+                let mut text = format!("[{}] {}", line.time, line.event);
+                if let Some(job) = line.job {
+                    text.push_str(&format!(" {job}"));
+                }
+                if let (Some(package), Some(version)) = (line.package, line.version) {
+                    text.push_str(&format!(" {package} {version}"));
+                }
+                if let Some(detail) = line.detail {
+                    text.push_str(&format!(": {detail}"));
+                }
+                if let Some(elapsed) = line.elapsed_secs {
+                    text.push_str(&format!(
+                        " (took {})",
+                        humantime::format_duration(Duration::from_secs(elapsed))
+                    ));
+                }
+                text
+            }
+        };
+
+        writeln!(std::io::stderr(), "{text}").map_err(anyhow::Error::from)
+    }
 }
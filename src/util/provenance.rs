@@ -0,0 +1,411 @@
+//
+// Copyright (c) 2020-2022 science+computing ag and other contributors
+//
+// This program and the accompanying materials are made
+// available under the terms of the Eclipse Public License 2.0
+// which is available at https://www.eclipse.org/legal/epl-2.0/
+//
+// SPDX-License-Identifier: EPL-2.0
+//
+
+//! Provenance attestations for released artifacts
+//!
+//! The provenance of an artifact is an in-toto statement (https://in-toto.io/Statement/v1) with
+//! a SLSA provenance predicate (https://slsa.dev/provenance/v1), wrapped in a DSSE envelope
+//! (https://github.com/secure-systems-lab/dsse) that is optionally signed.
+
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Stdio;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use base64::Engine;
+use chrono::NaiveDateTime;
+use diesel::PgConnection;
+use serde_json::json;
+use serde_json::Value;
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+
+use crate::config::Configuration;
+use crate::config::ProvenanceConfig;
+use crate::db::models as dbmodels;
+
+const STATEMENT_TYPE: &str = "https://in-toto.io/Statement/v1";
+const PREDICATE_TYPE: &str = "https://slsa.dev/provenance/v1";
+const BUILD_TYPE: &str = "https://github.com/science-computing/butido/build/v1";
+const PAYLOAD_TYPE: &str = "application/vnd.in-toto+json";
+
+/// The file name extension of the attestations next to the released artifacts
+const ATTESTATION_EXTENSION: &str = "intoto.json";
+
+/// The path of the attestation of an artifact, next to the artifact
+pub fn attestation_path(artifact_path: &Path) -> PathBuf {
+    let mut path = artifact_path.as_os_str().to_owned();
+    path.push(".");
+    path.push(ATTESTATION_EXTENSION);
+    PathBuf::from(path)
+}
+
+/// An input artifact of a job
+#[derive(Debug)]
+pub struct InputArtifact {
+    pub path: String,
+
+    /// The recorded SHA-256 hash, unknown if the artifact was built by an older version of butido
+    pub sha256: Option<String>,
+}
+
+/// The input artifacts of a job of the submit with the ID `submit_id`, with their recorded hashes
+///
+/// The inputs are either artifacts of the same submit (from the staging store) or released
+/// artifacts.
+pub fn input_artifacts(
+    conn: &mut PgConnection,
+    config: &Configuration,
+    job: &dbmodels::Job,
+    submit_id: i32,
+) -> Result<Vec<InputArtifact>> {
+    job.input_artifacts
+        .iter()
+        .map(|path| {
+            let mut sha256 = dbmodels::Artifact::recorded_sha256(conn, path, submit_id, None)?;
+            for store in config.release_stores() {
+                if sha256.is_some() {
+                    break;
+                }
+                sha256 = dbmodels::Artifact::recorded_sha256(conn, path, submit_id, Some(store))?;
+            }
+            if sha256.is_none() {
+                warn!(
+                    "No hash recorded for input artifact {} of job {}",
+                    path, job.uuid
+                );
+            }
+            Ok(InputArtifact {
+                path: path.clone(),
+                sha256,
+            })
+        })
+        .collect()
+}
+
+/// Everything that is recorded in the provenance of a released artifact
+#[derive(Debug)]
+pub struct Provenance {
+    builder_id: String,
+    release_path: String,
+    sha256: String,
+    package_name: String,
+    package_version: String,
+    submit: uuid::Uuid,
+    commit: String,
+    image: String,
+    image_digest: Option<String>,
+    endpoint: String,
+    job: uuid::Uuid,
+    butido_version: Option<String>,
+    arch: Option<String>,
+    started_at: Option<NaiveDateTime>,
+    finished_at: Option<NaiveDateTime>,
+    inputs: Vec<InputArtifact>,
+}
+
+impl Provenance {
+    /// Collect the provenance of an artifact that is released at `release_path` (relative to the
+    /// release store) and has the SHA-256 hash `sha256`
+    pub fn for_artifact(
+        conn: &mut PgConnection,
+        config: &Configuration,
+        provenance_config: &ProvenanceConfig,
+        artifact: &dbmodels::Artifact,
+        release_path: &str,
+        sha256: String,
+    ) -> Result<Self> {
+        let (job, submit, package, image, endpoint, githash) = {
+            use crate::schema;
+            use diesel::ExpressionMethods;
+            use diesel::QueryDsl;
+            use diesel::RunQueryDsl;
+
+            schema::jobs::table
+                .inner_join(schema::submits::table.inner_join(schema::githashes::table))
+                .inner_join(schema::packages::table)
+                .inner_join(schema::images::table)
+                .inner_join(schema::endpoints::table)
+                .filter(schema::jobs::id.eq(artifact.job_id))
+                .select((
+                    schema::jobs::all_columns,
+                    schema::submits::all_columns,
+                    schema::packages::all_columns,
+                    schema::images::all_columns,
+                    schema::endpoints::all_columns,
+                    schema::githashes::all_columns,
+                ))
+                .first::<(
+                    dbmodels::Job,
+                    dbmodels::Submit,
+                    dbmodels::Package,
+                    dbmodels::Image,
+                    dbmodels::Endpoint,
+                    dbmodels::GitHash,
+                )>(conn)
+                .with_context(|| anyhow!("Loading the job of artifact {}", artifact.path))?
+        };
+
+        let inputs = input_artifacts(conn, config, &job, submit.id)?;
+
+        Ok(Provenance {
+            builder_id: provenance_config.builder_id().clone(),
+            release_path: release_path.to_owned(),
+            sha256,
+            package_name: package.name,
+            package_version: package.version,
+            submit: submit.uuid,
+            commit: githash.hash,
+            image: image.name,
+            image_digest: job.image_digest,
+            endpoint: endpoint.name,
+            job: job.uuid,
+            butido_version: job.butido_version,
+            arch: job.arch,
+            started_at: job.started_at,
+            finished_at: job.finished_at,
+            inputs,
+        })
+    }
+
+    /// The in-toto statement with the SLSA provenance predicate
+    pub fn statement(&self) -> Value {
+        let mut resolved_dependencies = vec![json!({
+            "name": "repository",
+            "digest": { "gitCommit": self.commit },
+        })];
+
+        // Image digests are recorded as "<algorithm>:<hex>"
+        let image_digest = self
+            .image_digest
+            .as_deref()
+            .and_then(|digest| digest.split_once(':'))
+            .map(|(algorithm, hex)| json!({ algorithm: hex }));
+        resolved_dependencies.push(match image_digest {
+            Some(digest) => json!({ "name": self.image, "digest": digest }),
+            None => json!({ "name": self.image }),
+        });
+
+        resolved_dependencies.extend(self.inputs.iter().map(|input| match &input.sha256 {
+            Some(sha256) => json!({ "name": input.path, "digest": { "sha256": sha256 } }),
+            None => json!({ "name": input.path }),
+        }));
+
+        let mut metadata = json!({ "invocationId": self.job.to_string() });
+        if let Some(started_at) = self.started_at.and_then(rfc3339) {
+            metadata["startedOn"] = json!(started_at);
+        }
+        if let Some(finished_at) = self.finished_at.and_then(rfc3339) {
+            metadata["finishedOn"] = json!(finished_at);
+        }
+
+        let mut builder = json!({ "id": self.builder_id });
+        if let Some(version) = self.butido_version.as_ref() {
+            builder["version"] = json!({ "butido": version });
+        }
+
+        json!({
+            "_type": STATEMENT_TYPE,
+            "subject": [{
+                "name": self.release_path,
+                "digest": { "sha256": self.sha256 },
+            }],
+            "predicateType": PREDICATE_TYPE,
+            "predicate": {
+                "buildDefinition": {
+                    "buildType": BUILD_TYPE,
+                    "externalParameters": {
+                        "package": {
+                            "name": self.package_name,
+                            "version": self.package_version,
+                        },
+                        "image": self.image,
+                        "submit": self.submit.to_string(),
+                    },
+                    "internalParameters": {
+                        "endpoint": self.endpoint,
+                        "arch": self.arch,
+                    },
+                    "resolvedDependencies": resolved_dependencies,
+                },
+                "runDetails": {
+                    "builder": builder,
+                    "metadata": metadata,
+                },
+            },
+        })
+    }
+
+    /// The DSSE envelope with the statement, signed with the configured command if there is one
+    ///
+    /// Returns the envelope as JSON and whether it is signed.
+    pub async fn envelope(&self, provenance_config: &ProvenanceConfig) -> Result<(String, bool)> {
+        let payload = serde_json::to_vec(&self.statement())
+            .context("Serializing the provenance statement")?;
+
+        let signatures = match provenance_config.sign_command() {
+            Some(command) => {
+                let signature = sign(command, &pae(PAYLOAD_TYPE, &payload))
+                    .await
+                    .with_context(|| anyhow!("Signing the provenance of {}", self.release_path))?;
+                let mut signature = json!({
+                    "sig": base64::engine::general_purpose::STANDARD.encode(signature),
+                });
+                if let Some(key_id) = provenance_config.key_id() {
+                    signature["keyid"] = json!(key_id);
+                }
+                vec![signature]
+            }
+            None => Vec::new(),
+        };
+
+        let signed = !signatures.is_empty();
+        let envelope = json!({
+            "payloadType": PAYLOAD_TYPE,
+            "payload": base64::engine::general_purpose::STANDARD.encode(payload),
+            "signatures": signatures,
+        });
+        serde_json::to_string_pretty(&envelope)
+            .context("Serializing the provenance envelope")
+            .map(|envelope| (envelope, signed))
+    }
+}
+
+/// The DSSE pre-authentication encoding, the message that is signed
+fn pae(payload_type: &str, payload: &[u8]) -> Vec<u8> {
+    let mut message = format!(
+        "DSSEv1 {} {} {} ",
+        payload_type.len(),
+        payload_type,
+        payload.len()
+    )
+    .into_bytes();
+    message.extend_from_slice(payload);
+    message
+}
+
+/// Sign a message with the configured command, which gets the message on stdin
+async fn sign(command: &[String], message: &[u8]) -> Result<Vec<u8>> {
+    // safe because the configuration validates that the command is not empty
+    let mut child = tokio::process::Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| anyhow!("Running sign command '{}'", command[0]))?;
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow!("No stdin for sign command '{}'", command[0]))?;
+    stdin.write_all(message).await?;
+    drop(stdin);
+
+    let output = child
+        .wait_with_output()
+        .await
+        .with_context(|| anyhow!("Running sign command '{}'", command[0]))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Sign command '{}' failed ({}):\n{}",
+            command[0],
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    if output.stdout.is_empty() {
+        return Err(anyhow!(
+            "Sign command '{}' printed no signature",
+            command[0]
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// A time that was recorded in local time as RFC 3339 timestamp
+fn rfc3339(time: NaiveDateTime) -> Option<String> {
+    time.and_local_timezone(chrono::Local)
+        .earliest()
+        .map(|time| time.to_rfc3339())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provenance() -> Provenance {
+        Provenance {
+            builder_id: String::from("https://build.example.com/butido"),
+            release_path: String::from("a-1.tar.gz"),
+            sha256: String::from("aaaa"),
+            package_name: String::from("a"),
+            package_version: String::from("1"),
+            submit: uuid::Uuid::nil(),
+            commit: String::from("0123abc"),
+            image: String::from("debian:bookworm"),
+            image_digest: Some(String::from("sha256:bbbb")),
+            endpoint: String::from("ep"),
+            job: uuid::Uuid::nil(),
+            butido_version: Some(String::from("0.5.0")),
+            arch: None,
+            started_at: None,
+            finished_at: None,
+            inputs: vec![
+                InputArtifact {
+                    path: String::from("b-2.tar.gz"),
+                    sha256: Some(String::from("cccc")),
+                },
+                InputArtifact {
+                    path: String::from("c-3.tar.gz"),
+                    sha256: None,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_statement() {
+        let statement = provenance().statement();
+        assert_eq!(statement["_type"], STATEMENT_TYPE);
+        assert_eq!(statement["predicateType"], PREDICATE_TYPE);
+        assert_eq!(statement["subject"][0]["name"], "a-1.tar.gz");
+        assert_eq!(statement["subject"][0]["digest"]["sha256"], "aaaa");
+
+        let dependencies = &statement["predicate"]["buildDefinition"]["resolvedDependencies"];
+        assert_eq!(dependencies[0]["digest"]["gitCommit"], "0123abc");
+        assert_eq!(dependencies[1]["name"], "debian:bookworm");
+        assert_eq!(dependencies[1]["digest"]["sha256"], "bbbb");
+        assert_eq!(dependencies[2]["digest"]["sha256"], "cccc");
+        assert!(dependencies[3].get("digest").is_none());
+
+        let run_details = &statement["predicate"]["runDetails"];
+        assert_eq!(
+            run_details["builder"]["id"],
+            "https://build.example.com/butido"
+        );
+        assert_eq!(
+            run_details["metadata"]["invocationId"],
+            uuid::Uuid::nil().to_string()
+        );
+        assert!(run_details["metadata"].get("startedOn").is_none());
+    }
+
+    #[test]
+    fn test_pae() {
+        // The example from the DSSE specification
+        assert_eq!(
+            pae("http://example.com/HelloWorld", b"hello world"),
+            b"DSSEv1 29 http://example.com/HelloWorld 11 hello world"
+        );
+    }
+}